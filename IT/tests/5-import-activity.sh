@@ -0,0 +1,24 @@
+#!/bin/bash
+
+echo "5-import-activity.sh"
+
+url='http://localhost:8000/api/vehicle/d13fe953-297a-4781-807a-f9becc1b71f6/60e18f00-34b8-4a52-916c-adbb0204618e/activity'
+
+response=$( curl -s -X POST "${url}" --data-binary @ride.gpx )
+file_format=$( jq -r  '.file_format' <<< "${response}" )
+
+if [ "$file_format" != "gpx" ]
+then
+    echo "Test failed! file_format does not contain the expected value"
+    exit 1
+fi
+
+status=$( curl -s -o /dev/null -w '%{http_code}' -X POST "${url}" --data-binary @ride.gpx )
+
+if [ "$status" != "409" ]
+then
+    echo "Test failed! duplicated file was not rejected"
+    exit 1
+fi
+
+exit 0
@@ -0,0 +1,154 @@
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+
+use rocket::serde::uuid::Uuid;
+use rocket::tokio::fs::File;
+use rocket::tokio::io::BufReader;
+
+use crate::controller::bulk_controllers::{EXPORT_PAGE_SIZE, IMPORT_CHUNK_SIZE};
+use crate::dto::vehicle_dto::ImportReportDTO;
+use crate::parser::vehicle_records::{self, RecordFormat, RecordLines};
+use crate::service::vehicle_service::{self, VehicleService};
+
+/// Actor of the versions written by the imports run from the command line.
+pub const CLI_ACTOR: &str = "cli";
+pub const USAGE: &str = "Usage: rust_rocket_micro_service [import <csv|ndjson> <file> | export <csv|ndjson> [<user_id>] | reindex]";
+
+/// Subcommands run instead of the server.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Import { format: RecordFormat, path: String },
+    Export { format: RecordFormat, user_id: Option<Uuid> },
+    /// Rebuilds the full-text vehicle index from Cassandra.
+    Reindex
+}
+
+/// The subcommand named by the arguments, `None` to start the server.
+pub fn parse(args: &[String]) -> Result<Option<Command>, String> {
+    let format = |name: Option<&String>| name
+        .and_then(|name| RecordFormat::parse(name))
+        .ok_or_else(|| USAGE.to_string());
+
+    match args.first().map(|command| command.as_str()) {
+        None => Ok(None),
+        Some("import") if args.len() == 3 => Ok(Some(Command::Import { format: format(args.get(1))?, path: args[2].to_owned() })),
+        Some("export") if args.len() <= 3 => {
+            let user_id = match args.get(2) {
+                Some(user_id) => Some(Uuid::parse_str(user_id).map_err(|_| format!("Invalid user_id {}", user_id))?),
+                None => None
+            };
+            Ok(Some(Command::Export { format: format(args.get(1))?, user_id }))
+        },
+        Some("reindex") if args.len() == 1 => Ok(Some(Command::Reindex)),
+        _ => Err(USAGE.to_string())
+    }
+}
+
+pub async fn run(command: Command, vehicle_service: Arc<VehicleService>) -> Result<(), String> {
+    match command {
+        Command::Import { format, path } => import(&vehicle_service, format, &path).await,
+        Command::Export { format, user_id } => export(&vehicle_service, format, user_id).await,
+        Command::Reindex => reindex(&vehicle_service).await
+    }
+}
+
+/// Imports a file chunk by chunk, printing the report of the lines that could not be imported.
+async fn import(vehicle_service: &VehicleService, format: RecordFormat, path: &str) -> Result<(), String> {
+    let file = File::open(path).await.map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let mut lines = RecordLines::new(BufReader::new(file));
+
+    let header = lines.read_header(format).await?;
+
+    let mut report = ImportReportDTO::default();
+    loop {
+        let chunk = lines.next_chunk(IMPORT_CHUNK_SIZE).await.map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        if chunk.is_empty() {
+            break;
+        }
+
+        vehicle_service::merge_reports(&mut report, vehicle_service.import_vehicles(format, header.clone(), chunk, CLI_ACTOR).await);
+    }
+
+    println!("{}", serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?);
+
+    Ok(())
+}
+
+/// Writes the vehicles of a user, or of every user, to the standard output one page at a time.
+async fn export(vehicle_service: &VehicleService, format: RecordFormat, user_id: Option<Uuid>) -> Result<(), String> {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    if format == RecordFormat::Csv {
+        writeln!(out, "{}", vehicle_records::csv_header()).map_err(|e| e.to_string())?;
+    }
+
+    let mut after = None;
+    loop {
+        let page = vehicle_service.export_vehicles(user_id, after, EXPORT_PAGE_SIZE).await
+            .ok_or_else(|| "Failed to read vehicles".to_string())?;
+
+        for vehicle_dto in &page {
+            writeln!(out, "{}", vehicle_records::write(format, vehicle_dto)).map_err(|e| e.to_string())?;
+        }
+
+        after = match page.last() {
+            Some(last) if page.len() == EXPORT_PAGE_SIZE => last.vehicle_id.map(|vehicle_id| (last.user_id, vehicle_id)),
+            _ => None
+        };
+        if after.is_none() {
+            break;
+        }
+    }
+
+    out.flush().map_err(|e| e.to_string())
+}
+
+async fn reindex(vehicle_service: &VehicleService) -> Result<(), String> {
+    let indexed = vehicle_service.reindex_vehicles().await
+        .ok_or_else(|| "Failed to rebuild the vehicle index".to_string())?;
+
+    println!("Indexed {} vehicles", indexed);
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn given_no_argument_when_parse_then_starts_server() {
+        assert_eq!(Ok(None), parse(&[]));
+    }
+
+    #[test]
+    fn when_parse_import_then_returns_import_command() {
+        assert_eq!(Ok(Some(Command::Import { format: RecordFormat::Csv, path: "garage.csv".to_string() })),
+                   parse(&args(&["import", "csv", "garage.csv"])));
+    }
+
+    #[test]
+    fn when_parse_export_then_returns_export_command() {
+        assert_eq!(Ok(Some(Command::Export { format: RecordFormat::Ndjson, user_id: None })), parse(&args(&["export", "ndjson"])));
+        assert_eq!(Ok(Some(Command::Export { format: RecordFormat::Csv, user_id: Some(Uuid::parse_str("a906615e-2e6a-4edb-9377-5a6b8544791b").unwrap()) })),
+                   parse(&args(&["export", "csv", "a906615e-2e6a-4edb-9377-5a6b8544791b"])));
+    }
+
+    #[test]
+    fn when_parse_reindex_then_returns_reindex_command() {
+        assert_eq!(Ok(Some(Command::Reindex)), parse(&args(&["reindex"])));
+        assert_eq!(Err(USAGE.to_string()), parse(&args(&["reindex", "now"])));
+    }
+
+    #[test]
+    fn given_unknown_format_or_command_when_parse_then_returns_usage() {
+        assert_eq!(Err(USAGE.to_string()), parse(&args(&["import", "xml", "garage.xml"])));
+        assert_eq!(Err(USAGE.to_string()), parse(&args(&["serve"])));
+        assert!(parse(&args(&["export", "csv", "not-a-uuid"])).is_err());
+    }
+}
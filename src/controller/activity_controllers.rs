@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket::serde::uuid::Uuid;
+use mockall_double::double;
+
+use crate::dto::activity_dto::ActivityDTO;
+use crate::service::activity_service::ImportError;
+
+#[double]
+use crate::service::activity_service::ActivityService;
+
+const ACTIVITY_FILE_LIMIT_MIB: u64 = 20;
+
+#[post("/vehicle/<user_id>/<vehicle_id>/activity", data = "<file>")]
+pub async fn import_activity(activity_service: &State<Arc<ActivityService>>, user_id: Uuid, vehicle_id: Uuid, file: Data<'_>) -> Result<Json<ActivityDTO>, Status> {
+    let data = file.open(ACTIVITY_FILE_LIMIT_MIB.mebibytes())
+        .into_bytes()
+        .await
+        .map_err(|_| Status::BadRequest)?;
+
+    if !data.is_complete() {
+        return Err(Status::PayloadTooLarge);
+    }
+
+    match activity_service.import_activity(user_id, vehicle_id, data.into_inner()).await {
+        Ok(activity_dto) => Ok(Json(activity_dto)),
+        Err(ImportError::VehicleNotFound) => Err(Status::NotFound),
+        Err(ImportError::UnsupportedFormat) => Err(Status::UnsupportedMediaType),
+        Err(ImportError::InvalidFile(reason)) => {
+            println!("Rejected activity file for vehicle {}: {}", vehicle_id, reason);
+            Err(Status::UnprocessableEntity)
+        },
+        Err(ImportError::Duplicate) => Err(Status::Conflict),
+        Err(ImportError::StorageFailure) => Err(Status::InternalServerError)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use chrono::{Utc, TimeZone};
+
+    #[test]
+    fn when_posts_activity_file_then_responds_with_json_activity() {
+        let mut activity_service = ActivityService::default();
+        activity_service.expect_import_activity()
+            .withf(|user_id: &Uuid, _, data: &Vec<u8>| user_id == &Uuid::parse_str(fixture::USER_ID_STR).unwrap() && data == fixture::FILE.as_bytes())
+            .times(1)
+            .returning(move |user_id, vehicle_id, _| Ok(fixture::activity_dto(user_id, vehicle_id)));
+
+        let rocket_build = rocket::build().manage(Arc::new(activity_service)).mount("/", routes![import_activity]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post(format!("/vehicle/{}/{}/activity", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR))
+            .body(fixture::FILE)
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json_response = response.into_json::<ActivityDTO>().unwrap();
+        assert_eq!(fixture::VEHICLE_ID_STR.to_string(), json_response.vehicle_id.to_string());
+        assert_eq!(fixture::DISTANCE, json_response.distance);
+    }
+
+    #[test]
+    fn given_duplicate_file_when_posts_activity_file_then_responds_with_409() {
+        assert_eq!(Status::Conflict, fixture::dispatch_with_error(ImportError::Duplicate));
+    }
+
+    #[test]
+    fn given_unknown_vehicle_when_posts_activity_file_then_responds_with_404() {
+        assert_eq!(Status::NotFound, fixture::dispatch_with_error(ImportError::VehicleNotFound));
+    }
+
+    #[test]
+    fn given_unsupported_file_when_posts_activity_file_then_responds_with_415() {
+        assert_eq!(Status::UnsupportedMediaType, fixture::dispatch_with_error(ImportError::UnsupportedFormat));
+    }
+
+    #[test]
+    fn given_invalid_file_when_posts_activity_file_then_responds_with_422() {
+        assert_eq!(Status::UnprocessableEntity, fixture::dispatch_with_error(ImportError::InvalidFile("broken".to_string())));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "6176bc4b-33b6-4c9c-a4ad-c65da1322a80";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const FILE: &str = "<gpx></gpx>";
+        pub const DISTANCE: f64 = 1234.5;
+
+        pub fn activity_dto(user_id: Uuid, vehicle_id: Uuid) -> ActivityDTO {
+            ActivityDTO {
+                activity_id: Uuid::new_v4(),
+                user_id,
+                vehicle_id,
+                file_format: "gpx".to_string(),
+                started_at: None,
+                duration: 0,
+                distance: DISTANCE,
+                elevation_gain: 0.0,
+                created_at: Utc.timestamp(5, 0)
+            }
+        }
+
+        pub fn dispatch_with_error(error: ImportError) -> Status {
+            let mut activity_service = ActivityService::default();
+            activity_service.expect_import_activity()
+                .times(1)
+                .return_once(move |_, _, _| Err(error));
+
+            let rocket_build = rocket::build().manage(Arc::new(activity_service)).mount("/", routes![import_activity]);
+            let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+            client.post(format!("/vehicle/{}/{}/activity", USER_ID_STR, VEHICLE_ID_STR))
+                .body(FILE)
+                .dispatch()
+                .status()
+        }
+    }
+}
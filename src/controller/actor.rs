@@ -0,0 +1,55 @@
+use rocket::request::{self, FromRequest, Request};
+
+/// Header naming who a request acts on behalf of, recorded with the changes it makes.
+pub const ACTOR_HEADER: &str = "X-Actor";
+/// Actor of the requests without an `X-Actor` header.
+pub const ANONYMOUS_ACTOR: &str = "anonymous";
+/// Longest actor kept, longer ones being truncated.
+const MAX_ACTOR_LENGTH: usize = 128;
+
+/// Who a request acts on behalf of, as named by its `X-Actor` header. The API does not authenticate it,
+/// leaving that to the gateway in front of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Actor(pub String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Actor {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(actor(request))
+    }
+}
+
+/// Actor of a request, for the code reading it outside of a route.
+pub fn actor(request: &Request<'_>) -> Actor {
+    let actor = request.headers().get_one(ACTOR_HEADER)
+        .map(str::trim)
+        .filter(|actor| !actor.is_empty())
+        .unwrap_or(ANONYMOUS_ACTOR);
+
+    Actor(actor.chars().take(MAX_ACTOR_LENGTH).collect())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::http::Header;
+    use rocket::local::blocking::Client;
+
+    #[get("/actor")]
+    fn echo_actor(actor: Actor) -> String {
+        actor.0
+    }
+
+    #[test]
+    fn given_actor_header_or_none_when_request_then_reads_actor_or_anonymous() {
+        let client = Client::untracked(rocket::build().mount("/", routes![echo_actor])).expect("valid rocket instance");
+
+        let named = client.get("/actor").header(Header::new(ACTOR_HEADER, " jane ")).dispatch();
+        assert_eq!("jane", named.into_string().unwrap());
+
+        let anonymous = client.get("/actor").header(Header::new(ACTOR_HEADER, "")).dispatch();
+        assert_eq!(ANONYMOUS_ACTOR, anonymous.into_string().unwrap());
+    }
+}
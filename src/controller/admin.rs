@@ -0,0 +1,52 @@
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::serde::Deserialize;
+
+pub const ADMIN_TOKEN_HEADER: &str = "X-Admin-Token";
+
+/// Admin settings read from the `admin` section of `Rocket.toml`, admin features are disabled without a token.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct AdminSettings {
+    pub token               : Option<String>
+}
+
+/// Whether the request carries the configured admin token, compared in constant time.
+pub fn is_admin(request: &Request<'_>, settings: &AdminSettings) -> bool {
+    match (settings.token.as_deref(), request.headers().get_one(ADMIN_TOKEN_HEADER)) {
+        (Some(token), Some(candidate)) if !token.is_empty() => constant_time_eq(token.as_bytes(), candidate.as_bytes()),
+        _ => false
+    }
+}
+
+/// Guards the routes only admins may call, answering `403 Forbidden` to the others. The admin settings are
+/// read from the managed state, so admin routes are forbidden to everyone when they are not managed.
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match request.rocket().state::<AdminSettings>() {
+            Some(settings) if is_admin(request, settings) => request::Outcome::Success(Admin),
+            _ => request::Outcome::Failure((Status::Forbidden, ()))
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn when_constant_time_eq_then_compares_content_and_length() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+}
@@ -0,0 +1,234 @@
+use chrono::{TimeZone, Utc};
+use rocket::data::Data;
+use rocket::http::Header;
+use rocket::request::Request;
+use rocket::route::{Handler, Outcome, Route};
+use rocket::serde::Deserialize;
+
+use crate::controller::controllers;
+use crate::controller::v2::vehicle_controllers;
+
+/// Base the API was served under before it was versioned, still serving v1.
+pub const UNVERSIONED_BASE: &str = "/api";
+pub const V1_BASE: &str = "/api/v1";
+pub const V2_BASE: &str = "/api/v2";
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// When v1 was deprecated and when it will be removed, in seconds since the epoch, read from the
+/// `api.v1` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct DeprecationSettings {
+    pub deprecated_at       : i64,
+    pub sunset_at           : i64
+}
+
+impl Default for DeprecationSettings {
+    fn default() -> Self {
+        DeprecationSettings {
+            // 2026-10-19 and 2027-10-19.
+            deprecated_at: 1_792_368_000,
+            sunset_at: 1_823_904_000
+        }
+    }
+}
+
+/// Routes whose contract changed in v2, in their v1 form.
+pub fn v1_routes() -> Vec<Route> {
+    routes![controllers::get_vehicle, controllers::new_vehicle, controllers::new_vehicles]
+}
+
+/// Routes whose contract changed in v2: whole vehicles by default on `GET` and creation times set by the server.
+pub fn v2_routes() -> Vec<Route> {
+    routes![vehicle_controllers::get_vehicle, vehicle_controllers::new_vehicle, vehicle_controllers::new_vehicles]
+}
+
+/// Wraps a route handler so its responses announce that v1 is deprecated, when it will be removed and
+/// where the same resource is served by v2.
+#[derive(Clone)]
+struct DeprecatedHandler {
+    handler: Box<dyn Handler>,
+    settings: DeprecationSettings
+}
+
+#[rocket::async_trait]
+impl Handler for DeprecatedHandler {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        match self.handler.handle(request, data).await {
+            Outcome::Success(mut response) => {
+                for header in deprecation_headers(request.uri().path().as_str(), &self.settings) {
+                    response.set_header(header);
+                }
+                Outcome::Success(response)
+            },
+            outcome => outcome
+        }
+    }
+}
+
+pub fn deprecated(routes: Vec<Route>, settings: &DeprecationSettings) -> Vec<Route> {
+    routes.into_iter()
+        .map(|mut route| {
+            let handler = route.handler;
+            route.handler = Box::new(DeprecatedHandler {
+                handler,
+                settings: settings.clone()
+            });
+            route
+        })
+        .collect()
+}
+
+/// `Deprecation` as a structured date, `Sunset` as an HTTP date and a `Link` to the v2 successor of `path`.
+fn deprecation_headers(path: &str, settings: &DeprecationSettings) -> Vec<Header<'static>> {
+    let resource = path.strip_prefix(V1_BASE)
+        .or_else(|| path.strip_prefix(UNVERSIONED_BASE))
+        .unwrap_or(path);
+
+    vec!(
+        Header::new("Deprecation", format!("@{}", settings.deprecated_at)),
+        Header::new("Sunset", Utc.timestamp(settings.sunset_at, 0).format(HTTP_DATE_FORMAT).to_string()),
+        Header::new("Link", format!("<{}{}>; rel=\"successor-version\"", V2_BASE, resource))
+    )
+}
+
+/// The vehicle routes of both versions, each test running against `/api/v1` and `/api/v2`.
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use rocket::http::{ContentType, Status};
+    use rocket::local::blocking::Client;
+    use rocket::serde::json::{json, Value};
+    use rocket::serde::uuid::Uuid;
+
+    use crate::controller::search_controllers;
+    use crate::dto::vehicle_dto::{VehicleDTO, VehicleProjectionDTO, VehicleSearchDTO};
+    use crate::service::vehicle_service::MockVehicleService;
+
+    const VERSIONS: [&str; 2] = [V1_BASE, V2_BASE];
+
+    #[test]
+    fn when_deprecation_headers_then_point_to_v2_successor() {
+        let headers = deprecation_headers("/api/v1/vehicle/search", &DeprecationSettings::default());
+
+        assert_eq!("@1792368000", headers[0].value());
+        assert_eq!("Tue, 19 Oct 2027 00:00:00 GMT", headers[1].value());
+        assert_eq!("</api/v2/vehicle/search>; rel=\"successor-version\"", headers[2].value());
+        assert_eq!("</api/v2/hello>; rel=\"successor-version\"", deprecation_headers("/api/hello", &DeprecationSettings::default())[2].value());
+    }
+
+    #[test]
+    fn when_gets_vehicle_then_both_versions_respond_with_vehicle_and_only_v1_is_deprecated() {
+        for version in VERSIONS {
+            let mut vehicle_service = MockVehicleService::default();
+            vehicle_service.expect_get_vehicle()
+                .times(1)
+                .returning(move |_, vehicle_id, fields| Some(VehicleProjectionDTO {
+                    vehicle_id: Some(vehicle_id),
+                    name: Some(fixture::EXPECTED_VEHICLE_NAME.to_string()),
+                    brand: Some(fixture::EXPECTED_BRAND.to_string()).filter(|_| fields.contains(&"brand")),
+                    ..VehicleProjectionDTO::default()
+                }));
+
+            let response = fixture::client(vehicle_service).get(format!("{}/vehicle/{}/{}", version, fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+            assert_eq!(Status::Ok, response.status(), "{}", version);
+            assert_eq!(version == V1_BASE, response.headers().get_one("Deprecation").is_some(), "{}", version);
+            assert_eq!(version == V1_BASE, response.headers().get_one("Sunset").is_some(), "{}", version);
+            let vehicle = response.into_json::<Value>().unwrap();
+            assert_eq!(json!(fixture::EXPECTED_VEHICLE_NAME), vehicle["name"], "{}", version);
+            // v1 keeps answering the id and name only, v2 answers whole vehicles.
+            assert_eq!(version == V2_BASE, vehicle.get("brand").is_some(), "{}", version);
+        }
+    }
+
+    #[test]
+    fn given_unknown_field_when_gets_vehicle_then_both_versions_respond_bad_request() {
+        for version in VERSIONS {
+            let mut vehicle_service = MockVehicleService::default();
+            vehicle_service.expect_get_vehicle()
+                .times(0);
+
+            let response = fixture::client(vehicle_service).get(format!("{}/vehicle/{}/{}?fields=colour", version, fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+            assert_eq!(Status::BadRequest, response.status(), "{}", version);
+        }
+    }
+
+    #[test]
+    fn when_posts_vehicle_then_both_versions_save_it_and_only_v2_sets_creation_time() {
+        for version in VERSIONS {
+            let mut vehicle_service = MockVehicleService::default();
+            let expected_version = version;
+            vehicle_service.expect_save_vehicle()
+                .withf(move |vehicle_dto: &VehicleDTO, _: &str| vehicle_dto.name == fixture::EXPECTED_VEHICLE_NAME
+                    && (expected_version == V2_BASE) == (vehicle_dto.created_at.timestamp() != fixture::EXPECTED_CREATED_AT))
+                .times(1)
+                .returning(move |vehicle_dto, _| Some(VehicleDTO { vehicle_id: Some(Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap()), ..vehicle_dto }));
+
+            let response = fixture::client(vehicle_service).post(format!("{}/vehicle", version))
+                .header(ContentType::JSON)
+                .body(fixture::vehicle_json().to_string())
+                .dispatch();
+
+            assert_eq!(Status::Ok, response.status(), "{}", version);
+            let vehicle = response.into_json::<Value>().unwrap();
+            assert_eq!(json!(fixture::VEHICLE_ID_STR), vehicle["vehicle_id"], "{}", version);
+        }
+    }
+
+    #[test]
+    fn when_gets_user_vehicles_then_both_versions_respond_with_page() {
+        for version in VERSIONS {
+            let mut vehicle_service = MockVehicleService::default();
+            vehicle_service.expect_list_vehicles()
+                .times(1)
+                .returning(|_, _, _, _, _| Some(VehicleSearchDTO { vehicles: vec!(), next: None }));
+
+            let response = fixture::client(vehicle_service).get(format!("{}/vehicle/{}", version, fixture::USER_ID_STR)).dispatch();
+
+            assert_eq!(Status::Ok, response.status(), "{}", version);
+            assert!(response.into_json::<VehicleSearchDTO>().unwrap().vehicles.is_empty(), "{}", version);
+        }
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const EXPECTED_VEHICLE_NAME: &str = "the vehicle name";
+        pub const EXPECTED_BRAND: &str = "the brand";
+        pub const EXPECTED_CREATED_AT: i64 = 5;
+
+        /// A vehicle both versions accept, v2 ignoring `created_at`.
+        pub fn vehicle_json() -> Value {
+            json!({
+                "name": EXPECTED_VEHICLE_NAME,
+                "user_id": USER_ID_STR,
+                "created_at": "1970-01-01T00:00:05Z",
+                "vehicle_type": "bike",
+                "retired_at": null,
+                "brand": EXPECTED_BRAND,
+                "model": "the model",
+                "distance": 15,
+                "owner_since": "2015-12-02",
+                "manufacturing_date": "2015-11-20",
+                "picture": null
+            })
+        }
+
+        /// Both versions mounted as in `main`, sharing the same service.
+        pub fn client(vehicle_service: MockVehicleService) -> Client {
+            let shared_routes = || routes![search_controllers::list_vehicles];
+            let rocket_build = rocket::build()
+                .manage(Arc::new(vehicle_service))
+                .mount(V1_BASE, deprecated([v1_routes(), shared_routes()].concat(), &DeprecationSettings::default()))
+                .mount(V2_BASE, [v2_routes(), shared_routes()].concat());
+
+            Client::untracked(rocket_build).expect("valid rocket instance")
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use mockall_double::double;
+
+use crate::controller::admin::Admin;
+use crate::controller::history_controllers::parse_time;
+use crate::domain::audit::AuditFilter;
+use crate::dto::audit_dto::AuditEntryDTO;
+use crate::service::audit_service::AuditError;
+
+#[double]
+use crate::service::audit_service::AuditService;
+
+const DEFAULT_ENTRIES: usize = 100;
+const MAX_ENTRIES: usize = 1000;
+
+/// Latest entries of the audit log between the RFC 3339 times `from` and `to`, the last day by default,
+/// sent by `actor` and writing `target` when given. Admins only.
+#[get("/admin/audit?<actor>&<target>&<from>&<to>&<limit>")]
+pub async fn get_audit_log(audit_service: &State<Arc<AuditService>>, _admin: Admin, actor: Option<String>, target: Option<String>,
+                           from: Option<&str>, to: Option<&str>, limit: Option<usize>) -> Result<Json<Vec<AuditEntryDTO>>, Status> {
+    let to = match to {
+        Some(to) => parse_time(to)?,
+        None => Utc::now()
+    };
+    let from = match from {
+        Some(from) => parse_time(from)?,
+        None => to - Duration::days(1)
+    };
+
+    audit_service.search(AuditFilter { actor, target }, from, to, limit.unwrap_or(DEFAULT_ENTRIES).clamp(1, MAX_ENTRIES)).await
+        .map(Json)
+        .map_err(to_status)
+}
+
+fn to_status(error: AuditError) -> Status {
+    match error {
+        AuditError::InvalidRange => Status::BadRequest,
+        AuditError::StorageFailure => Status::ServiceUnavailable
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::{DateTime, TimeZone};
+    use rocket::http::Header;
+    use rocket::local::blocking::Client;
+
+    use crate::controller::admin::{AdminSettings, ADMIN_TOKEN_HEADER};
+
+    #[test]
+    fn given_admin_token_when_gets_audit_log_then_searches_range_with_filter() {
+        let mut audit_service = AuditService::default();
+        audit_service.expect_search()
+            .withf(|filter: &AuditFilter, from: &DateTime<Utc>, to: &DateTime<Utc>, limit: &usize| filter.actor == Some("jane".to_string())
+                && filter.target.is_none()
+                && *from == Utc.ymd(2021, 5, 1).and_hms(0, 0, 0)
+                && *to == Utc.ymd(2021, 5, 2).and_hms(0, 0, 0)
+                && *limit == DEFAULT_ENTRIES)
+            .times(1)
+            .returning(|_, _, _, _| Ok(vec!()));
+
+        let client = fixture::client(audit_service);
+
+        let response = client.get("/admin/audit?actor=jane&from=2021-05-01T00:00:00Z&to=2021-05-02T00:00:00Z")
+            .header(Header::new(ADMIN_TOKEN_HEADER, fixture::ADMIN_TOKEN))
+            .dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+    }
+
+    #[test]
+    fn given_no_admin_token_when_gets_audit_log_then_responds_forbidden() {
+        let mut audit_service = AuditService::default();
+        audit_service.expect_search()
+            .times(0);
+
+        let response = fixture::client(audit_service).get("/admin/audit").dispatch();
+
+        assert_eq!(Status::Forbidden, response.status());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const ADMIN_TOKEN: &str = "the admin token";
+
+        pub fn client(audit_service: AuditService) -> Client {
+            let rocket_build = rocket::build()
+                .manage(Arc::new(audit_service))
+                .manage(AdminSettings { token: Some(ADMIN_TOKEN.to_string()) })
+                .mount("/", routes![get_audit_log]);
+
+            Client::untracked(rocket_build).expect("valid rocket instance")
+        }
+    }
+}
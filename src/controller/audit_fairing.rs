@@ -0,0 +1,174 @@
+use std::sync::{Arc, Mutex};
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Method};
+use rocket::request::{self, FromRequest};
+use rocket::serde::uuid::Uuid;
+use rocket::{Data, Request, Response};
+use mockall_double::double;
+
+use crate::controller::actor;
+use crate::domain::audit::{AuditTargets, AuditedRequest};
+
+#[double]
+use crate::service::audit_service::AuditService;
+
+/// Header carrying the id of a request, taken from the caller when it sends one and answered on every response.
+pub const REQUEST_ID_HEADER: &str = "X-Request-ID";
+/// Longest request id kept from a caller, longer ones being replaced.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// What the audit log learns of a request while it is handled, kept in its local cache.
+struct AuditContext {
+    request_id: String,
+    targets: Mutex<AuditTargets>
+}
+
+impl AuditContext {
+    fn of(request: &Request<'_>) -> &AuditContext {
+        request.local_cache(|| AuditContext {
+            request_id: request.headers().get_one(REQUEST_ID_HEADER)
+                .map(str::trim)
+                .filter(|request_id| !request_id.is_empty() && request_id.len() <= MAX_REQUEST_ID_LENGTH)
+                .map(str::to_string)
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            targets: Mutex::new(AuditTargets::new())
+        })
+    }
+}
+
+/// Lets a handler add to the audit log the keys of what it wrote that are not in its path, such as the id of a
+/// created vehicle, once the service answered them.
+pub struct AuditTrail<'r>(&'r AuditContext);
+
+impl AuditTrail<'_> {
+    pub fn target(&self, name: &str, value: impl ToString) {
+        self.0.targets.lock().unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .push(value.to_string());
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuditTrail<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(AuditTrail(AuditContext::of(request)))
+    }
+}
+
+/// Records every routed write request in the audit log once answered: who sent it from which IP, the route
+/// and the keys in its path along with those its handler added, its status and its request id. Entries are only
+/// queued here, the response never waiting on them being written.
+pub struct Audit {
+    audit_service: Arc<AuditService>
+}
+
+impl Audit {
+    pub fn new(audit_service: Arc<AuditService>) -> Audit {
+        Audit {
+            audit_service
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Audit {
+    fn info(&self) -> Info {
+        Info {
+            name: "Audit log of the write requests",
+            kind: Kind::Request | Kind::Response
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        AuditContext::of(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let context = AuditContext::of(request);
+        response.set_header(Header::new(REQUEST_ID_HEADER, context.request_id.clone()));
+
+        let route = match request.route() {
+            Some(route) if matches!(request.method(), Method::Post | Method::Put | Method::Patch | Method::Delete) => route,
+            _ => return
+        };
+
+        let mut targets = context.targets.lock().unwrap().clone();
+        let route_segments = route.uri.path().split('/').filter(|segment| !segment.is_empty());
+        for (route_segment, segment) in route_segments.zip(request.uri().path().segments()) {
+            if let Some(name) = route_segment.strip_prefix('<').and_then(|name| name.strip_suffix('>')) {
+                targets.entry(name.trim_end_matches("..").to_string()).or_default().push(segment.to_string());
+            }
+        }
+
+        let audited_request = AuditedRequest {
+            request_id: context.request_id.clone(),
+            actor: actor::actor(request).0,
+            ip: request.client_ip().map(|ip| ip.to_string()),
+            method: request.method().as_str().to_string(),
+            route: route.uri.path().to_string()
+        };
+
+        self.audit_service.record(audited_request, targets, response.status().code);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::http::Status;
+    use rocket::local::blocking::Client;
+
+    #[post("/vehicle/<user_id>/book")]
+    fn new_book(user_id: &str, audit_trail: AuditTrail<'_>) -> Status {
+        audit_trail.target("isbn", "978-0");
+        if user_id == "unknown" { Status::NotFound } else { Status::Created }
+    }
+
+    #[get("/vehicle/<_user_id>")]
+    fn get_vehicle(_user_id: &str) -> Status {
+        Status::Ok
+    }
+
+    #[test]
+    fn when_posts_then_records_actor_route_targets_and_status_under_request_id() {
+        let mut audit_service = AuditService::default();
+        audit_service.expect_record()
+            .withf(|request: &AuditedRequest, targets: &AuditTargets, status: &u16| request.request_id == "the request"
+                && request.actor == "jane"
+                && request.route == "/vehicle/<user_id>/book"
+                && targets.get("user_id") == Some(&vec!("the user".to_string()))
+                && targets.get("isbn") == Some(&vec!("978-0".to_string()))
+                && *status == 201)
+            .times(1)
+            .return_const(());
+
+        let rocket_build = rocket::build().attach(Audit::new(Arc::new(audit_service))).mount("/", routes![new_book]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post("/vehicle/the%20user/book")
+            .header(Header::new(REQUEST_ID_HEADER, "the request"))
+            .header(Header::new(actor::ACTOR_HEADER, "jane"))
+            .dispatch();
+
+        assert_eq!(Some("the request"), response.headers().get_one(REQUEST_ID_HEADER));
+    }
+
+    #[test]
+    fn given_read_when_gets_then_answers_request_id_without_recording() {
+        let mut audit_service = AuditService::default();
+        audit_service.expect_record()
+            .times(0);
+
+        let rocket_build = rocket::build().attach(Audit::new(Arc::new(audit_service))).mount("/", routes![get_vehicle]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/vehicle/the%20user").dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+        assert!(Uuid::parse_str(response.headers().get_one(REQUEST_ID_HEADER).unwrap()).is_ok());
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::Cursor;
+
+use rocket::http::{Header, Status};
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+
+use crate::storage::blob_store::Blob;
+
+/// Pictures keep their URL when replaced, so caches must check the `ETag` again before reusing a copy.
+const CACHE_CONTROL: &str = "no-cache";
+
+/// Serves a blob honouring `If-None-Match` (304) and single `Range: bytes=` requests (206/416).
+pub struct BlobResponse(pub Blob);
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Full,
+    Partial(usize, usize),
+    Unsatisfiable
+}
+
+/// Parses a `Range` header against a body of `length` bytes. Multiple ranges and units other than
+/// bytes are ignored and the full body is served, as the RFC allows.
+fn parse_range(header: Option<&str>, length: usize) -> ByteRange {
+    let spec = match header.and_then(|h| h.trim().strip_prefix("bytes=")) {
+        Some(spec) if !spec.contains(',') => spec.trim(),
+        _ => return ByteRange::Full
+    };
+
+    let (start, end) = match spec.split_once('-') {
+        Some(bounds) => bounds,
+        None => return ByteRange::Full
+    };
+
+    let range = match (start.parse::<usize>().ok(), end.parse::<usize>().ok()) {
+        (Some(start), Some(end)) if start <= end => Some((start, end.min(length.saturating_sub(1)))),
+        (Some(start), None) if end.is_empty() => Some((start, length.saturating_sub(1))),
+        (None, Some(suffix)) if start.is_empty() && suffix > 0 => Some((length.saturating_sub(suffix), length.saturating_sub(1))),
+        _ => return ByteRange::Full
+    };
+
+    match range {
+        Some((start, end)) if start < length => ByteRange::Partial(start, end),
+        _ => ByteRange::Unsatisfiable
+    }
+}
+
+impl<'r> Responder<'r, 'static> for BlobResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let BlobResponse(blob) = self;
+        let length = blob.data.len();
+
+        let mut response = Response::build();
+        response
+            .header(Header::new("ETag", blob.etag.clone()))
+            .header(Header::new("Cache-Control", CACHE_CONTROL))
+            .header(Header::new("Accept-Ranges", "bytes"));
+
+        let not_modified = request.headers().get_one("If-None-Match")
+            .map_or(false, |tags| tags.split(',').any(|tag| tag.trim() == blob.etag || tag.trim() == "*"));
+
+        if not_modified {
+            return response.status(Status::NotModified).ok();
+        }
+
+        match parse_range(request.headers().get_one("Range"), length) {
+            ByteRange::Full => response
+                .header(blob.content_type)
+                .sized_body(length, Cursor::new(blob.data))
+                .ok(),
+            ByteRange::Partial(start, end) => {
+                let body = blob.data[start..=end].to_vec();
+
+                response
+                    .status(Status::PartialContent)
+                    .header(blob.content_type)
+                    .header(Header::new("Content-Range", format!("bytes {}-{}/{}", start, end, length)))
+                    .sized_body(body.len(), Cursor::new(body))
+                    .ok()
+            },
+            ByteRange::Unsatisfiable => response
+                .status(Status::RangeNotSatisfiable)
+                .header(Header::new("Content-Range", format!("bytes */{}", length)))
+                .ok()
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::http::ContentType;
+
+    #[test]
+    fn when_parse_range_then_handles_bounded_open_and_suffix_ranges() {
+        assert_eq!(ByteRange::Partial(0, 4), parse_range(Some("bytes=0-4"), 10));
+        assert_eq!(ByteRange::Partial(5, 9), parse_range(Some("bytes=5-"), 10));
+        assert_eq!(ByteRange::Partial(7, 9), parse_range(Some("bytes=-3"), 10));
+        assert_eq!(ByteRange::Partial(8, 9), parse_range(Some("bytes=8-100"), 10));
+        assert_eq!(ByteRange::Unsatisfiable, parse_range(Some("bytes=10-12"), 10));
+        assert_eq!(ByteRange::Full, parse_range(Some("bytes=0-1,4-5"), 10));
+        assert_eq!(ByteRange::Full, parse_range(Some("items=0-1"), 10));
+        assert_eq!(ByteRange::Full, parse_range(None, 10));
+    }
+
+    #[test]
+    fn when_gets_blob_then_responds_with_content_type_and_cache_headers() {
+        let client = fixture::client();
+
+        let response = client.get("/blob").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JPEG));
+        assert_eq!(response.headers().get_one("ETag"), Some(fixture::ETAG));
+        assert_eq!(response.headers().get_one("Cache-Control"), Some(CACHE_CONTROL));
+        assert_eq!(response.into_bytes().unwrap(), fixture::DATA.to_vec());
+    }
+
+    #[test]
+    fn given_range_when_gets_blob_then_responds_with_partial_content() {
+        let client = fixture::client();
+
+        let response = client.get("/blob").header(Header::new("Range", "bytes=2-5")).dispatch();
+
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(response.headers().get_one("Content-Range"), Some("bytes 2-5/10"));
+        assert_eq!(response.into_bytes().unwrap(), fixture::DATA[2..=5].to_vec());
+    }
+
+    #[test]
+    fn given_unsatisfiable_range_when_gets_blob_then_responds_with_416() {
+        let client = fixture::client();
+
+        let response = client.get("/blob").header(Header::new("Range", "bytes=20-")).dispatch();
+
+        assert_eq!(response.status(), Status::RangeNotSatisfiable);
+        assert_eq!(response.headers().get_one("Content-Range"), Some("bytes */10"));
+    }
+
+    #[test]
+    fn given_matching_etag_when_gets_blob_then_responds_with_304() {
+        let client = fixture::client();
+
+        let response = client.get("/blob").header(Header::new("If-None-Match", fixture::ETAG)).dispatch();
+
+        assert_eq!(response.status(), Status::NotModified);
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const DATA: &[u8] = b"0123456789";
+        pub const ETAG: &str = "\"a-1\"";
+
+        #[get("/blob")]
+        pub fn blob() -> BlobResponse {
+            BlobResponse(Blob { data: DATA.to_vec(), content_type: ContentType::JPEG, etag: ETAG.to_string() })
+        }
+
+        pub fn client() -> Client {
+            Client::tracked(rocket::build().mount("/", routes![blob])).expect("valid rocket instance")
+        }
+    }
+}
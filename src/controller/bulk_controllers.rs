@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::{ContentType, Status};
+use rocket::response::stream::TextStream;
+use rocket::serde::json::Json;
+use rocket::serde::uuid::Uuid;
+use rocket::tokio::io::BufReader;
+use rocket::State;
+use mockall_double::double;
+
+use crate::controller::actor::Actor;
+use crate::controller::audit_fairing::AuditTrail;
+use crate::dto::vehicle_dto::ImportReportDTO;
+use crate::parser::vehicle_records::{self, RecordFormat, RecordLines};
+use crate::service::vehicle_service::merge_reports;
+
+#[double]
+use crate::service::vehicle_service::VehicleService;
+
+const IMPORT_FILE_LIMIT_MIB: u64 = 512;
+pub const IMPORT_CHUNK_SIZE: usize = 200;
+pub const EXPORT_PAGE_SIZE: usize = 500;
+
+/// Imports a CSV (`text/csv`) or NDJSON (`application/x-ndjson`) file of vehicles, read and saved
+/// chunk by chunk as it is uploaded, answering which lines could not be imported.
+#[post("/vehicle/import", data = "<file>")]
+pub async fn import_vehicles(vehicle_service: &State<Arc<VehicleService>>, content_type: Option<&ContentType>, file: Data<'_>, actor: Actor, audit_trail: AuditTrail<'_>) -> Result<Json<ImportReportDTO>, Status> {
+    let format = content_type
+        .and_then(|content_type| RecordFormat::from_media_type(&format!("{}/{}", content_type.top(), content_type.sub()).to_lowercase()))
+        .ok_or(Status::UnsupportedMediaType)?;
+
+    let mut lines = RecordLines::new(BufReader::new(file.open(IMPORT_FILE_LIMIT_MIB.mebibytes())));
+
+    let header = lines.read_header(format).await.map_err(|reason| {
+        println!("Rejected vehicle import: {}", reason);
+        Status::UnprocessableEntity
+    })?;
+
+    let mut report = ImportReportDTO::default();
+    loop {
+        let chunk = lines.next_chunk(IMPORT_CHUNK_SIZE).await.map_err(|_| Status::BadRequest)?;
+        if chunk.is_empty() {
+            break;
+        }
+
+        let chunk_report = vehicle_service.import_vehicles(format, header.clone(), chunk, &actor.0).await;
+        for (user_id, vehicle_id) in &chunk_report.imported_keys {
+            audit_trail.target("user_id", user_id);
+            audit_trail.target("vehicle_id", vehicle_id);
+        }
+        merge_reports(&mut report, chunk_report);
+    }
+
+    Ok(Json(report))
+}
+
+/// Streams the vehicles of a user, or of every user, as CSV or NDJSON, one page at a time.
+#[get("/vehicle/export?<user_id>&<format>")]
+pub async fn export_vehicles(vehicle_service: &State<Arc<VehicleService>>, user_id: Option<Uuid>, format: Option<&str>) -> Result<(ContentType, TextStream![String]), Status> {
+    let format = RecordFormat::parse(format.unwrap_or("ndjson")).ok_or(Status::BadRequest)?;
+    let content_type = ContentType::parse_flexible(format.media_type()).unwrap_or(ContentType::Plain);
+    let vehicle_service = vehicle_service.inner().clone();
+
+    Ok((content_type, TextStream! {
+        if format == RecordFormat::Csv {
+            yield format!("{}\n", vehicle_records::csv_header());
+        }
+
+        let mut after = None;
+        loop {
+            let page = match vehicle_service.export_vehicles(user_id, after, EXPORT_PAGE_SIZE).await {
+                Some(page) => page,
+                None => break
+            };
+
+            for vehicle_dto in &page {
+                yield format!("{}\n", vehicle_records::write(format, vehicle_dto));
+            }
+
+            after = match page.last() {
+                Some(last) if page.len() == EXPORT_PAGE_SIZE => last.vehicle_id.map(|vehicle_id| (last.user_id, vehicle_id)),
+                _ => None
+            };
+            if after.is_none() {
+                break;
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use chrono::{NaiveDate, Utc, TimeZone};
+
+    use crate::dto::vehicle_dto::{LineErrorDTO, VehicleDTO};
+
+    #[test]
+    fn when_posts_csv_file_then_imports_lines_after_header_and_responds_with_report() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_import_vehicles()
+            .withf(|format: &RecordFormat, header: &Vec<String>, lines: &Vec<(usize, String)>, _: &str| *format == RecordFormat::Csv
+                && header.len() == vehicle_records::CSV_COLUMNS.len()
+                && lines.len() == 2 && lines[0].0 == 2 && lines[1].0 == 4)
+            .times(1)
+            .returning(move |_, _, _, _| ImportReportDTO {
+                imported: 1,
+                failed: 1,
+                errors: vec!(LineErrorDTO { line: 4, error: "distance is negative".to_string() }),
+                imported_keys: vec!((Uuid::new_v4(), Uuid::new_v4()))
+            });
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![import_vehicles]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post("/vehicle/import")
+            .header(ContentType::new("text", "csv"))
+            .body(format!("{}\n{}\n\n{}\n", vehicle_records::csv_header(), fixture::LINE, fixture::LINE))
+            .dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+        let report = response.into_json::<ImportReportDTO>().unwrap();
+        assert_eq!(1, report.imported);
+        assert_eq!(1, report.failed);
+        assert_eq!(4, report.errors[0].line);
+    }
+
+    #[test]
+    fn given_missing_csv_columns_when_posts_csv_file_then_responds_unprocessable_entity() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_import_vehicles()
+            .times(0);
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![import_vehicles]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post("/vehicle/import")
+            .header(ContentType::new("text", "csv"))
+            .body("name,user_id\n")
+            .dispatch();
+
+        assert_eq!(Status::UnprocessableEntity, response.status());
+    }
+
+    #[test]
+    fn given_unsupported_content_type_when_posts_file_then_responds_unsupported_media_type() {
+        let rocket_build = rocket::build().manage(Arc::new(VehicleService::default())).mount("/", routes![import_vehicles]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post("/vehicle/import")
+            .header(ContentType::XML)
+            .body("<vehicles/>")
+            .dispatch();
+
+        assert_eq!(Status::UnsupportedMediaType, response.status());
+    }
+
+    #[test]
+    fn when_gets_user_export_then_streams_ndjson_until_last_page() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_export_vehicles()
+            .withf(|user_id: &Option<Uuid>, after: &Option<(Uuid, Uuid)>, limit: &usize| *user_id == Some(Uuid::parse_str(fixture::USER_ID_STR).unwrap())
+                && after.is_none() && *limit == EXPORT_PAGE_SIZE)
+            .times(1)
+            .returning(move |_, _, _| Some(vec!(fixture::vehicle_dto("first"), fixture::vehicle_dto("second"))));
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![export_vehicles]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/export?user_id={}&format=ndjson", fixture::USER_ID_STR)).dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+        assert_eq!(Some(ContentType::new("application", "x-ndjson")), response.content_type());
+        let body = response.into_string().unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(2, lines.len());
+        assert!(lines[1].contains("\"second\""));
+    }
+
+    #[test]
+    fn given_unknown_format_when_gets_export_then_responds_bad_request() {
+        let rocket_build = rocket::build().manage(Arc::new(VehicleService::default())).mount("/", routes![export_vehicles]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/vehicle/export?format=xml").dispatch();
+
+        assert_eq!(Status::BadRequest, response.status());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const LINE: &str = "bike,a906615e-2e6a-4edb-9377-5a6b8544791b,,1970-01-01T00:00:05Z,bike,,brand,model,10,2015-12-02,2015-12-01";
+
+        pub fn vehicle_dto(name: &str) -> VehicleDTO {
+            VehicleDTO {
+                name: name.to_string(),
+                user_id: Uuid::parse_str(USER_ID_STR).unwrap(),
+                vehicle_id: Some(Uuid::new_v4()),
+                created_at: Utc.timestamp(5, 0),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: 10,
+                owner_since: NaiveDate::from_ymd(2015, 12, 2),
+                manufacturing_date: NaiveDate::from_ymd(2015, 12, 1),
+                picture: None
+            }
+        }
+    }
+}
@@ -10,6 +10,11 @@ pub fn internal_error() -> &'static str {
     "Whoops! Looks like we messed up."
 }
 
+#[catch(504)]
+pub fn gateway_timeout() -> &'static str {
+    "The request did not complete within its time budget."
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -42,13 +47,33 @@ pub mod tests {
         assert_eq!(fixture::EXPECTED_INTERNAL_SERVER_ERROR_RESPONSE.to_string(), str_response);
     }
 
+    #[test]
+    fn when_gateway_timeout_then_responds_with_504() {
+        let rocket_build = rocket::build()
+            .register("/", catchers![gateway_timeout])
+            .mount("/", routes![fixture::timeout]);
+        let client = Client::tracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/timeout").dispatch();
+
+        assert_eq!(response.status(), Status::GatewayTimeout);
+        let str_response = response.into_string().unwrap();
+        assert_eq!(fixture::EXPECTED_GATEWAY_TIMEOUT_RESPONSE.to_string(), str_response);
+    }
+
     mod fixture {
         pub const EXPECTED_NOT_FOUND_RESPONSE: &str = "Oh no! We couldn't find the requested path '/unexisting_path'";
         pub const EXPECTED_INTERNAL_SERVER_ERROR_RESPONSE: &str = "Whoops! Looks like we messed up.";
+        pub const EXPECTED_GATEWAY_TIMEOUT_RESPONSE: &str = "The request did not complete within its time budget.";
 
         #[get("/hello")]
         pub async fn hello() {
             panic!("internal server error");
         }
+
+        #[get("/timeout")]
+        pub async fn timeout() -> rocket::http::Status {
+            rocket::http::Status::GatewayTimeout
+        }
     }
 }
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket::serde::uuid::Uuid;
+use mockall_double::double;
+
+use crate::controller::audit_fairing::AuditTrail;
+use crate::dto::component_dto::ComponentDTO;
+use crate::service::component_service::ComponentError;
+
+#[double]
+use crate::service::component_service::ComponentService;
+
+#[get("/component/<user_id>")]
+pub async fn get_components(component_service: &State<Arc<ComponentService>>, user_id: Uuid) -> Json<Vec<ComponentDTO>> {
+    Json(component_service.get_components(user_id).await)
+}
+
+#[post("/component/<user_id>", format = "application/json", data = "<component_json>")]
+pub async fn new_component(component_service: &State<Arc<ComponentService>>, user_id: Uuid, component_json: Json<ComponentDTO>, audit_trail: AuditTrail<'_>) -> Result<Json<ComponentDTO>, Status> {
+    component_service.save_component(user_id, component_json.into_inner()).await
+        .map(|component| {
+            if let Some(component_id) = component.component_id {
+                audit_trail.target("component_id", component_id);
+            }
+            Json(component)
+        })
+        .map_err(to_status)
+}
+
+#[put("/component/<user_id>/<component_id>/install/<vehicle_id>")]
+pub async fn install_component(component_service: &State<Arc<ComponentService>>, user_id: Uuid, component_id: Uuid, vehicle_id: Uuid) -> Result<Json<ComponentDTO>, Status> {
+    component_service.install_component(user_id, component_id, vehicle_id).await
+        .map(Json)
+        .map_err(to_status)
+}
+
+#[put("/component/<user_id>/<component_id>/remove")]
+pub async fn remove_component(component_service: &State<Arc<ComponentService>>, user_id: Uuid, component_id: Uuid) -> Result<Json<ComponentDTO>, Status> {
+    component_service.remove_component(user_id, component_id).await
+        .map(Json)
+        .map_err(to_status)
+}
+
+#[get("/vehicle/<user_id>/<vehicle_id>/components")]
+pub async fn get_vehicle_components(component_service: &State<Arc<ComponentService>>, user_id: Uuid, vehicle_id: Uuid) -> Json<Vec<ComponentDTO>> {
+    Json(component_service.get_vehicle_components(user_id, vehicle_id).await)
+}
+
+#[get("/component-alerts/<user_id>")]
+pub async fn get_alerts(component_service: &State<Arc<ComponentService>>, user_id: Uuid) -> Json<Vec<ComponentDTO>> {
+    Json(component_service.get_alerts(user_id).await)
+}
+
+fn to_status(error: ComponentError) -> Status {
+    match error {
+        ComponentError::ComponentNotFound | ComponentError::VehicleNotFound => Status::NotFound,
+        ComponentError::StorageFailure => Status::InternalServerError
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::http::ContentType;
+
+    use crate::dto::component_dto::WearStatus;
+
+    #[test]
+    fn when_puts_install_component_then_responds_with_json_component() {
+        let mut component_service = ComponentService::default();
+        component_service.expect_install_component()
+            .withf(|_, _, vehicle_id: &Uuid| vehicle_id == &Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap())
+            .times(1)
+            .returning(move |_, component_id, vehicle_id| Ok(fixture::component_dto(component_id, Some(vehicle_id))));
+
+        let rocket_build = rocket::build().manage(Arc::new(component_service)).mount("/", routes![install_component]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.put(format!("/component/{}/{}/install/{}", fixture::USER_ID_STR, fixture::COMPONENT_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json_response = response.into_json::<ComponentDTO>().unwrap();
+        assert_eq!(fixture::VEHICLE_ID_STR.to_string(), json_response.vehicle_id.unwrap().to_string());
+        assert_eq!(WearStatus::Warning, json_response.wear_status);
+    }
+
+    #[test]
+    fn given_unknown_component_when_puts_remove_component_then_responds_with_404() {
+        let mut component_service = ComponentService::default();
+        component_service.expect_remove_component()
+            .times(1)
+            .returning(move |_, _| Err(ComponentError::ComponentNotFound));
+
+        let rocket_build = rocket::build().manage(Arc::new(component_service)).mount("/", routes![remove_component]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.put(format!("/component/{}/{}/remove", fixture::USER_ID_STR, fixture::COMPONENT_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn when_posts_component_then_responds_with_json_component() {
+        let mut component_service = ComponentService::default();
+        component_service.expect_save_component()
+            .withf(|_, component_dto: &ComponentDTO| component_dto.component_type == "chain")
+            .times(1)
+            .returning(move |_, _| Ok(fixture::component_dto(Uuid::parse_str(fixture::COMPONENT_ID_STR).unwrap(), None)));
+
+        let rocket_build = rocket::build().manage(Arc::new(component_service)).mount("/", routes![new_component]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post(format!("/component/{}", fixture::USER_ID_STR))
+            .header(ContentType::JSON)
+            .body(r#"{ "component_type": "chain", "brand": "KMC", "model": "X11" }"#)
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json_response = response.into_json::<ComponentDTO>().unwrap();
+        assert_eq!(fixture::COMPONENT_ID_STR.to_string(), json_response.component_id.unwrap().to_string());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "6176bc4b-33b6-4c9c-a4ad-c65da1322a80";
+        pub const COMPONENT_ID_STR: &str = "2c5e8f1a-9b3d-4e6f-8a1b-3c5d7e9f1a2b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+
+        pub fn component_dto(component_id: Uuid, vehicle_id: Option<Uuid>) -> ComponentDTO {
+            ComponentDTO {
+                component_id: Some(component_id),
+                vehicle_id,
+                component_type: "chain".to_string(),
+                brand: "KMC".to_string(),
+                model: "X11".to_string(),
+                installed_at: None,
+                removed_at: None,
+                installed_distance: Some(100),
+                wear_limit: Some(3000),
+                distance: 2800,
+                wear_status: WearStatus::Warning
+            }
+        }
+    }
+}
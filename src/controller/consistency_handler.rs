@@ -0,0 +1,116 @@
+use rocket::data::Data;
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::route::{Handler, Outcome, Route};
+
+use crate::controller::admin::{self, AdminSettings};
+use crate::dao::consistency;
+
+pub const CONSISTENCY_HEADER: &str = "X-Consistency-Level";
+
+/// Wraps a route handler so admins can force the consistency level of every statement of a request
+/// with the `X-Consistency-Level` header. Other callers sending it get `403 Forbidden`.
+#[derive(Clone)]
+struct ConsistencyOverrideHandler {
+    handler: Box<dyn Handler>,
+    admin_settings: AdminSettings
+}
+
+#[rocket::async_trait]
+impl Handler for ConsistencyOverrideHandler {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        let level = match request.headers().get_one(CONSISTENCY_HEADER) {
+            Some(level) => level,
+            None => return self.handler.handle(request, data).await
+        };
+
+        if !admin::is_admin(request, &self.admin_settings) {
+            return Outcome::Failure(Status::Forbidden);
+        }
+
+        match consistency::parse_consistency(level) {
+            Some(level) => consistency::scope(level, self.handler.handle(request, data)).await,
+            None => Outcome::Failure(Status::BadRequest)
+        }
+    }
+}
+
+pub fn with_consistency_override(routes: Vec<Route>, admin_settings: &AdminSettings) -> Vec<Route> {
+    routes.into_iter()
+        .map(|mut route| {
+            let handler = route.handler;
+            route.handler = Box::new(ConsistencyOverrideHandler {
+                handler,
+                admin_settings: admin_settings.clone()
+            });
+            route
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::http::Header;
+
+    use crate::controller::admin::ADMIN_TOKEN_HEADER;
+
+    fn client() -> Client {
+        let admin_settings = AdminSettings { token: Some(fixture::ADMIN_TOKEN.to_string()) };
+        let rocket_build = rocket::build().mount("/", with_consistency_override(routes![fixture::forced_consistency], &admin_settings));
+
+        Client::untracked(rocket_build).expect("valid rocket instance")
+    }
+
+    #[test]
+    fn given_no_header_when_gets_then_runs_without_override() {
+        let response = client().get("/consistency").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(fixture::NONE, response.into_string().unwrap());
+    }
+
+    #[test]
+    fn given_admin_header_when_gets_then_runs_with_override() {
+        let response = client().get("/consistency")
+            .header(Header::new(CONSISTENCY_HEADER, "all"))
+            .header(Header::new(ADMIN_TOKEN_HEADER, fixture::ADMIN_TOKEN))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!("All", response.into_string().unwrap());
+    }
+
+    #[test]
+    fn given_header_without_admin_token_when_gets_then_responds_with_403() {
+        let response = client().get("/consistency")
+            .header(Header::new(CONSISTENCY_HEADER, "all"))
+            .header(Header::new(ADMIN_TOKEN_HEADER, "guess"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Forbidden);
+    }
+
+    #[test]
+    fn given_unknown_level_when_gets_then_responds_with_400() {
+        let response = client().get("/consistency")
+            .header(Header::new(CONSISTENCY_HEADER, "most"))
+            .header(Header::new(ADMIN_TOKEN_HEADER, fixture::ADMIN_TOKEN))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    mod fixture {
+        use crate::dao::consistency;
+
+        pub const ADMIN_TOKEN: &str = "s3cr3t";
+        pub const NONE: &str = "none";
+
+        #[get("/consistency")]
+        pub async fn forced_consistency() -> String {
+            consistency::overridden().map(|level| format!("{:?}", level)).unwrap_or_else(|| NONE.to_string())
+        }
+    }
+}
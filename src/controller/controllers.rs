@@ -1,12 +1,19 @@
 use std::sync::Arc;
 
+use rocket::http::Status;
 use rocket::serde::json::{Json, Value, json};
+use rocket::serde::Deserialize;
 use rocket::State;
 use rocket::serde::uuid::Uuid;
 use mockall_double::double;
 
+use crate::controller::actor::Actor;
+use crate::controller::audit_fairing::AuditTrail;
+use crate::controller::history_controllers::parse_time;
+use crate::controller::negotiation::Negotiated;
 use crate::dto::book::Book;
-use crate::dto::vehicle_dto::VehicleDTO;
+use crate::dto::vehicle_dto::{VehicleBatchItemDTO, VehicleDTO, VehicleProjectionDTO};
+use crate::mapper::vehicle_mapper;
 
 #[double]
 use crate::service::vehicle_service::VehicleService;
@@ -20,46 +27,107 @@ pub async fn hello() -> Value {
     })
 }
 
-#[post("/book", format = "application/json", data = "<book>")]
-pub async fn new_book(book: Json<Book>) -> Value {
+#[post("/book", data = "<book>")]
+pub async fn new_book(book: Negotiated<Book>, audit_trail: AuditTrail<'_>) -> Negotiated<Value> {
     let mut dummy_db: Vec<&Book> = Vec::new();
     let new_book = book.into_inner();
     dummy_db.push(&new_book);
+    audit_trail.target("isbn", &new_book.isbn);
 
     println!("dummy_db = {:?}", dummy_db);
-    json!({
+    Negotiated(json!({
         "status": "success",
         "message": new_book.isbn
-    })
+    }))
 }
 
-#[get("/vehicle/<user_id>/<vehicle_id>")]
-pub async fn get_vehicle(vehicle_service: &State<Arc<VehicleService>>, user_id: Uuid, vehicle_id: Uuid) -> Value {
+/// Fields of a vehicle answered when `fields` is missing, those it was first served with.
+const DEFAULT_VEHICLE_FIELDS: &str = "vehicle_id,name";
+
+/// A vehicle holding only the comma separated `fields`, the others being left out of the JSON, as it was
+/// saved at `as_of` when given.
+#[get("/vehicle/<user_id>/<vehicle_id>?<fields>&<as_of>")]
+pub async fn get_vehicle(vehicle_service: &State<Arc<VehicleService>>, user_id: Uuid, vehicle_id: Uuid, fields: Option<&str>, as_of: Option<&str>) -> Result<Negotiated<VehicleProjectionDTO>, Status> {
+    let fields = vehicle_mapper::parse_fields(fields.unwrap_or(DEFAULT_VEHICLE_FIELDS)).map_err(|reason| {
+        println!("Rejected vehicle fields: {}", reason);
+        Status::BadRequest
+    })?;
+
+    let vehicle = match as_of {
+        Some(as_of) => vehicle_service.get_vehicle_as_of(user_id, vehicle_id, fields, parse_time(as_of)?).await,
+        None => vehicle_service.get_vehicle(user_id, vehicle_id, fields).await
+    };
+
+    vehicle
+        .map(Negotiated)
+        .ok_or(Status::NotFound)
+}
 
-    let name = vehicle_service.get_vehicle_name(user_id, vehicle_id).await;
+/// Creates a vehicle sent as JSON, MessagePack or CBOR, answering it in the format the client accepts.
+#[post("/vehicle", data = "<vehicle_body>")]
+pub async fn new_vehicle(vehicle_service: &State<Arc<VehicleService>>, vehicle_body: Negotiated<VehicleDTO>, actor: Actor, audit_trail: AuditTrail<'_>) -> Negotiated<VehicleDTO> {
+    let vehicle_dto = vehicle_body.into_inner();
 
-    json!({
-        "vehicle_id": vehicle_id,
-        "name": name
-    })
+    let result = vehicle_service.save_vehicle(vehicle_dto, &actor.0).await.expect("Failed save Vehicle");
+    audit_vehicle(&audit_trail, &result);
+
+    Negotiated(result)
 }
 
-#[post("/vehicle", format = "application/json", data = "<vehicle_json>")]
-pub async fn new_vehicle(vehicle_service: &State<Arc<VehicleService>>, vehicle_json: Json<VehicleDTO>) -> Json<VehicleDTO> {
-    let vehicle_dto = vehicle_json.into_inner();
+pub const BATCH_ITEM_CREATED: &str = "created";
+pub const BATCH_ITEM_INVALID: &str = "invalid";
+pub const BATCH_ITEM_FAILED: &str = "failed";
+
+/// Creates many vehicles at once, answering the outcome of each of them: a malformed vehicle or a failed
+/// write only fails its own item instead of the whole request.
+#[post("/vehicle/batch", format = "application/json", data = "<vehicles_json>")]
+pub async fn new_vehicles(vehicle_service: &State<Arc<VehicleService>>, vehicles_json: Json<Vec<Value>>, actor: Actor, audit_trail: AuditTrail<'_>) -> Json<Vec<VehicleBatchItemDTO>> {
+    let mut items: Vec<VehicleBatchItemDTO> = Vec::new();
+    let mut valid: Vec<(usize, VehicleDTO)> = Vec::new();
+
+    for (index, value) in vehicles_json.into_inner().into_iter().enumerate() {
+        match VehicleDTO::deserialize(value) {
+            Ok(vehicle_dto) => valid.push((index, vehicle_dto)),
+            Err(e) => items.push(VehicleBatchItemDTO {
+                index,
+                status: BATCH_ITEM_INVALID.to_string(),
+                vehicle: None,
+                error: Some(e.to_string())
+            })
+        }
+    }
+
+    let (indexes, vehicle_dtos): (Vec<usize>, Vec<VehicleDTO>) = valid.into_iter().unzip();
+    let saved = vehicle_service.save_vehicles(vehicle_dtos, &actor.0).await;
+
+    for (index, vehicle) in indexes.into_iter().zip(saved) {
+        items.push(match vehicle {
+            Some(vehicle) => {
+                audit_vehicle(&audit_trail, &vehicle);
+                VehicleBatchItemDTO { index, status: BATCH_ITEM_CREATED.to_string(), vehicle: Some(vehicle), error: None }
+            },
+            None => VehicleBatchItemDTO { index, status: BATCH_ITEM_FAILED.to_string(), vehicle: None, error: Some("Failed to save vehicle".to_string()) }
+        });
+    }
+    items.sort_by_key(|item| item.index);
 
-    let result = vehicle_service.save_vehicle(vehicle_dto).await;
+    Json(items)
+}
 
-    Json(result.expect("Failed save Vehicle"))
+/// Adds the keys of a saved vehicle to the audit log, its id being assigned by the service for a new one.
+pub fn audit_vehicle(audit_trail: &AuditTrail<'_>, vehicle: &VehicleDTO) {
+    audit_trail.target("user_id", vehicle.user_id);
+    if let Some(vehicle_id) = vehicle.vehicle_id {
+        audit_trail.target("vehicle_id", vehicle_id);
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
     use rocket::local::blocking::Client;
-    use rocket::http::Status;
-    use rocket::http::ContentType;
-    use chrono::{NaiveDate, Utc, TimeZone};
+    use rocket::http::{ContentType, Header};
+    use chrono::{DateTime, NaiveDate, Utc, TimeZone};
 
     #[test]
     fn when_gets_hello_then_responds_with_json_greetings() {
@@ -93,11 +161,16 @@ pub mod tests {
     #[test]
     fn when_gets_vehicle_then_responds_with_json_vehicle_data() {
         let mut vehicle_service = VehicleService::default();
-        vehicle_service.expect_get_vehicle_name()
-            .withf(|user_id: &Uuid, _| user_id == &Uuid::parse_str(fixture::USER_ID_STR).unwrap())
-            .withf(|_, vehicle_id: &Uuid| vehicle_id == &Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap())
+        vehicle_service.expect_get_vehicle()
+            .withf(|user_id: &Uuid, vehicle_id: &Uuid, fields: &Vec<&'static str>| user_id == &Uuid::parse_str(fixture::USER_ID_STR).unwrap()
+                && vehicle_id == &Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap()
+                && *fields == vec!("vehicle_id", "name"))
             .times(1)
-            .returning(move |_, _| fixture::EXPECTED_VEHICLE_NAME.to_string())
+            .returning(move |_, vehicle_id, _| Some(VehicleProjectionDTO {
+                vehicle_id: Some(vehicle_id),
+                name: Some(fixture::EXPECTED_VEHICLE_NAME.to_string()),
+                ..VehicleProjectionDTO::default()
+            }))
         ;
         let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![get_vehicle]);
         let client = Client::untracked(rocket_build).expect("valid rocket instance");
@@ -110,13 +183,68 @@ pub mod tests {
         assert_eq!(fixture::EXPECTED_VEHICLE_NAME.to_string(), json_response.name);
     }
 
+    #[test]
+    fn given_unknown_field_when_gets_vehicle_then_responds_bad_request() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_get_vehicle()
+            .times(0);
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![get_vehicle]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/{}/{}?fields=name,colour", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn given_missing_vehicle_when_gets_vehicle_then_responds_not_found() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_get_vehicle()
+            .withf(|_, _, fields: &Vec<&'static str>| *fields == vec!("brand", "model"))
+            .times(1)
+            .returning(move |_, _, _| None);
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![get_vehicle]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/{}/{}?fields=brand,model", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn given_as_of_when_gets_vehicle_then_responds_with_vehicle_as_saved_then() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_get_vehicle()
+            .times(0);
+        vehicle_service.expect_get_vehicle_as_of()
+            .withf(|_, _, _, as_of: &DateTime<Utc>| *as_of == Utc.ymd(2021, 5, 1).and_hms(12, 0, 0))
+            .times(1)
+            .returning(move |_, vehicle_id, _, _| Some(VehicleProjectionDTO {
+                vehicle_id: Some(vehicle_id),
+                name: Some("the former name".to_string()),
+                ..VehicleProjectionDTO::default()
+            }));
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![get_vehicle]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/{}/{}?as_of=2021-05-01T12:00:00Z", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+        let invalid = client.get(format!("/vehicle/{}/{}?as_of=last-month", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!("the former name", response.into_json::<fixture::JSONVehicleResponse>().unwrap().name);
+        assert_eq!(invalid.status(), Status::BadRequest);
+    }
+
     #[test]
     fn when_posts_vehicle_dto_then_responds_with_json_vehicle_data() {
         let mut vehicle_service = VehicleService::default();
         vehicle_service.expect_save_vehicle()
-            .withf(|vehicle_dto: &VehicleDTO| vehicle_dto.name == fixture::EXPECTED_VEHICLE_NAME.to_string())
+            .withf(|vehicle_dto: &VehicleDTO, actor: &str| vehicle_dto.name == fixture::EXPECTED_VEHICLE_NAME.to_string() && actor == "jane")
             .times(1)
-            .returning(move |vehicle_dto| Some(vehicle_dto))
+            .returning(move |vehicle_dto, _| Some(vehicle_dto))
         ;
 
         let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![new_vehicle]);
@@ -139,6 +267,7 @@ pub mod tests {
 
         let response = client.post("/vehicle")
             .header(ContentType::JSON)
+            .header(Header::new("X-Actor", "jane"))
             .json(&vehicle_dto)
             .dispatch();
 
@@ -158,7 +287,101 @@ pub mod tests {
         assert_eq!(fixture::EXPECTED_PICTURE.to_string(), json_response.picture.unwrap());
     }
 
+    #[test]
+    fn given_message_pack_body_and_cbor_accepted_when_posts_vehicle_then_responds_with_cbor_vehicle() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_save_vehicle()
+            .withf(|vehicle_dto: &VehicleDTO, _: &str| vehicle_dto.name == fixture::EXPECTED_VEHICLE_NAME.to_string())
+            .times(1)
+            .returning(move |vehicle_dto, _| Some(vehicle_dto))
+        ;
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![new_vehicle]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post("/vehicle")
+            .header(ContentType::new("application", "msgpack"))
+            .header(Header::new("Accept", "application/cbor"))
+            .body(rmp_serde::to_vec_named(&fixture::vehicle_dto()).unwrap())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Content-Type"), Some("application/cbor"));
+        let vehicle_dto = serde_cbor::from_slice::<VehicleDTO>(&response.into_bytes().unwrap()).unwrap();
+        assert_eq!(fixture::EXPECTED_VEHICLE_NAME.to_string(), vehicle_dto.name);
+        assert_eq!(fixture::USER_ID_STR.to_string(), vehicle_dto.user_id.to_string());
+    }
+
+    #[test]
+    fn given_unsupported_body_or_accept_when_posts_vehicle_then_responds_with_415_or_406() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_save_vehicle()
+            .times(1)
+            .returning(move |vehicle_dto, _| Some(vehicle_dto))
+        ;
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![new_vehicle]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let unsupported_body = client.post("/vehicle")
+            .header(ContentType::XML)
+            .body("<vehicle/>")
+            .dispatch();
+        let unsupported_accept = client.post("/vehicle")
+            .header(ContentType::JSON)
+            .header(Header::new("Accept", "text/csv"))
+            .json(&fixture::vehicle_dto())
+            .dispatch();
+
+        assert_eq!(unsupported_body.status(), Status::UnsupportedMediaType);
+        assert_eq!(unsupported_accept.status(), Status::NotAcceptable);
+    }
+
+    #[test]
+    fn given_invalid_and_failed_vehicles_when_posts_vehicle_batch_then_responds_with_outcome_of_each_vehicle() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_save_vehicles()
+            .withf(|vehicle_dtos: &Vec<VehicleDTO>, actor: &str| vehicle_dtos.len() == 2 && actor == "anonymous")
+            .times(1)
+            .returning(move |mut vehicle_dtos, _| vec!(Some(vehicle_dtos.remove(0)), None))
+        ;
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![new_vehicles]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let vehicle_dto = VehicleDTO {
+            name: fixture::EXPECTED_VEHICLE_NAME.to_string(),
+            user_id: Uuid::parse_str(fixture::USER_ID_STR).unwrap(),
+            vehicle_id: Some(Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap()),
+            created_at: Utc.timestamp(fixture::EXPECTED_CREATED_AT, 0),
+            vehicle_type: fixture::EXPECTED_VEHICLE_TYPE.to_string(),
+            retired_at: None,
+            brand: fixture::EXPECTED_BRAND.to_string(),
+            model: fixture::EXPECTED_MODEL.to_string(),
+            distance: fixture::EXPECTED_DISTANCE,
+            owner_since: NaiveDate::from_num_days_from_ce(fixture::EXPECTED_OWNER_SINCE),
+            manufacturing_date: NaiveDate::from_num_days_from_ce(fixture::EXPECTED_MANUFACTURING_DATE),
+            picture: None
+        };
+
+        let response = client.post("/vehicle/batch")
+            .header(ContentType::JSON)
+            .json(&vec!(json!(vehicle_dto), json!({ "name": "no user" }), json!(vehicle_dto)))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let items = response.into_json::<Vec<VehicleBatchItemDTO>>().unwrap();
+        assert_eq!(3, items.len());
+        assert_eq!("created", items[0].status);
+        assert_eq!(fixture::EXPECTED_VEHICLE_NAME.to_string(), items[0].vehicle.as_ref().unwrap().name);
+        assert_eq!("invalid", items[1].status);
+        assert!(items[1].error.is_some());
+        assert_eq!("failed", items[2].status);
+        assert_eq!(2, items[2].index);
+    }
+
     mod fixture {
+        use super::*;
         use rocket::serde::Deserialize;
 
         #[derive(Deserialize)]
@@ -188,5 +411,22 @@ pub mod tests {
         pub const EXPECTED_CREATED_AT: i64 = 5;
         pub const EXPECTED_OWNER_SINCE: i32 = 15;
         pub const EXPECTED_MANUFACTURING_DATE: i32 = 15;
+
+        pub fn vehicle_dto() -> VehicleDTO {
+            VehicleDTO {
+                name: EXPECTED_VEHICLE_NAME.to_string(),
+                user_id: Uuid::parse_str(USER_ID_STR).unwrap(),
+                vehicle_id: Some(Uuid::parse_str(VEHICLE_ID_STR).unwrap()),
+                created_at: Utc.timestamp(EXPECTED_CREATED_AT, 0),
+                vehicle_type: EXPECTED_VEHICLE_TYPE.to_string(),
+                retired_at: None,
+                brand: EXPECTED_BRAND.to_string(),
+                model: EXPECTED_MODEL.to_string(),
+                distance: EXPECTED_DISTANCE,
+                owner_since: NaiveDate::from_num_days_from_ce(EXPECTED_OWNER_SINCE),
+                manufacturing_date: NaiveDate::from_num_days_from_ce(EXPECTED_MANUFACTURING_DATE),
+                picture: None
+            }
+        }
     }
 }
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rocket::data::Data;
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::route::{Handler, Outcome, Route};
+use rocket::serde::Deserialize;
+use rocket::tokio::time::timeout_at;
+
+use crate::dao::deadline;
+
+pub const REQUEST_TIMEOUT_HEADER: &str = "X-Request-Timeout";
+
+/// Request budgets read from the `deadline` section of `Rocket.toml`, `routes` overriding
+/// `default_budget_ms` by handler name.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct DeadlineSettings {
+    pub default_budget_ms   : u64,
+    pub max_budget_ms       : u64,
+    pub routes              : HashMap<String, u64>
+}
+
+impl Default for DeadlineSettings {
+    fn default() -> Self {
+        DeadlineSettings {
+            default_budget_ms: 10_000,
+            max_budget_ms: 60_000,
+            routes: HashMap::new()
+        }
+    }
+}
+
+/// Wraps a route handler so it runs under a deadline, answering `504 Gateway Timeout` once it is exceeded.
+#[derive(Clone)]
+struct DeadlineHandler {
+    handler: Box<dyn Handler>,
+    budget: Duration,
+    max_budget: Duration
+}
+
+impl DeadlineHandler {
+    /// The route budget, or the `X-Request-Timeout` header in milliseconds capped to the max budget.
+    fn budget(&self, request: &Request<'_>) -> Duration {
+        request.headers().get_one(REQUEST_TIMEOUT_HEADER)
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(|millis| Duration::from_millis(millis).min(self.max_budget))
+            .unwrap_or(self.budget)
+    }
+}
+
+#[rocket::async_trait]
+impl Handler for DeadlineHandler {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        let deadline = Instant::now() + self.budget(request);
+
+        match timeout_at(deadline.into(), deadline::scope(deadline, self.handler.handle(request, data))).await {
+            Ok(outcome) => outcome,
+            Err(_) => Outcome::Failure(Status::GatewayTimeout)
+        }
+    }
+}
+
+/// Puts every route under the budget configured for it.
+pub fn with_deadline(routes: Vec<Route>, settings: &DeadlineSettings) -> Vec<Route> {
+    routes.into_iter()
+        .map(|mut route| {
+            let budget_ms = route.name.as_ref()
+                .and_then(|name| settings.routes.get(&**name))
+                .copied()
+                .unwrap_or(settings.default_budget_ms);
+
+            let handler = route.handler;
+            route.handler = Box::new(DeadlineHandler {
+                handler,
+                budget: Duration::from_millis(budget_ms),
+                max_budget: Duration::from_millis(settings.max_budget_ms)
+            });
+            route
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::http::Header;
+
+    #[test]
+    fn given_handler_within_budget_when_gets_then_responds_with_handler_response() {
+        let rocket_build = rocket::build().mount("/", with_deadline(routes![fixture::slow], &DeadlineSettings::default()));
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/slow/10").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(fixture::DONE, response.into_string().unwrap());
+    }
+
+    #[test]
+    fn given_route_budget_exceeded_when_gets_then_responds_with_504() {
+        let settings = DeadlineSettings {
+            routes: vec!(("slow".to_string(), 20)).into_iter().collect(),
+            ..DeadlineSettings::default()
+        };
+        let rocket_build = rocket::build().mount("/", with_deadline(routes![fixture::slow], &settings));
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/slow/1000").dispatch();
+
+        assert_eq!(response.status(), Status::GatewayTimeout);
+    }
+
+    #[test]
+    fn given_request_timeout_header_when_gets_then_uses_client_budget() {
+        let rocket_build = rocket::build().mount("/", with_deadline(routes![fixture::slow], &DeadlineSettings::default()));
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/slow/1000")
+            .header(Header::new(REQUEST_TIMEOUT_HEADER, "20"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::GatewayTimeout);
+    }
+
+    #[test]
+    fn when_handler_runs_then_sees_request_deadline() {
+        let rocket_build = rocket::build().mount("/", with_deadline(routes![fixture::remaining], &DeadlineSettings::default()));
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/remaining")
+            .header(Header::new(REQUEST_TIMEOUT_HEADER, "5000"))
+            .dispatch();
+
+        let remaining_ms: u64 = response.into_string().unwrap().parse().unwrap();
+        assert!(remaining_ms > 0 && remaining_ms <= 5000);
+    }
+
+    mod fixture {
+        use std::time::Duration;
+        use crate::dao::deadline;
+
+        pub const DONE: &str = "done";
+
+        #[get("/slow/<millis>")]
+        pub async fn slow(millis: u64) -> &'static str {
+            rocket::tokio::time::sleep(Duration::from_millis(millis)).await;
+            DONE
+        }
+
+        #[get("/remaining")]
+        pub async fn remaining() -> String {
+            deadline::remaining().map(|remaining| remaining.as_millis().to_string()).unwrap_or_default()
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::Arc;
+
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::uuid::Uuid;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{Shutdown, State};
+
+use crate::domain::vehicle_event::VehicleEvent;
+use crate::event::vehicle_event_log::{Replay, VehicleEventLog};
+use crate::mapper::vehicle_mapper;
+
+/// Event telling a resuming client that some of the events it missed are gone, so that it reads its
+/// vehicles again.
+pub const RESET_EVENT: &str = "reset";
+
+/// Id of the last event an `EventSource` received, sent in the `Last-Event-ID` header when it reconnects.
+/// An id that is not a number cannot be resumed from and reads as older than any event kept.
+pub struct LastEventId(Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let last_event_id = request.headers().get_one("Last-Event-ID")
+            .map(|id| id.trim().parse::<u64>().unwrap_or(0));
+
+        request::Outcome::Success(LastEventId(last_event_id))
+    }
+}
+
+/// Server-sent events of the vehicles of a user being created, updated, retired, transferred or deleted, replaying
+/// those following `Last-Event-ID` first. Idle streams are kept open with heartbeat comments.
+#[get("/vehicle/<user_id>/events")]
+pub fn get_vehicle_events(vehicle_events: &State<Arc<VehicleEventLog>>, user_id: Uuid, last_event_id: LastEventId, mut shutdown: Shutdown) -> EventStream![] {
+    let vehicle_events = vehicle_events.inner().clone();
+    let heartbeat = vehicle_events.heartbeat();
+    let (subscribed_at, mut receiver) = vehicle_events.subscribe();
+    let mut last_id = last_event_id.0.unwrap_or(subscribed_at);
+
+    EventStream! {
+        if last_event_id.0.is_some() {
+            for event in catch_up(&vehicle_events, user_id, &mut last_id) {
+                yield event;
+            }
+        }
+
+        loop {
+            let received = select! {
+                received = receiver.recv() => received,
+                _ = &mut shutdown => break
+            };
+
+            match received {
+                // Events up to `last_id` were already replayed.
+                Ok(event) if event.user_id == user_id && event.id > last_id => {
+                    last_id = event.id;
+                    yield to_event(event);
+                },
+                Ok(_) => {},
+                Err(RecvError::Lagged(_)) => {
+                    for event in catch_up(&vehicle_events, user_id, &mut last_id) {
+                        yield event;
+                    }
+                },
+                Err(RecvError::Closed) => break
+            }
+        }
+    }.heartbeat(heartbeat)
+}
+
+/// Events of the log following `last_id`, which is moved to the last of them.
+fn catch_up(vehicle_events: &VehicleEventLog, user_id: Uuid, last_id: &mut u64) -> Vec<Event> {
+    match vehicle_events.replay(user_id, *last_id) {
+        Replay::Events(events) => {
+            *last_id = events.last().map_or(*last_id, |event| event.id);
+            events.into_iter().map(to_event).collect()
+        },
+        Replay::Reset(latest_id) => {
+            println!("Events of user {} following {} are gone, resetting to {}", user_id, last_id, latest_id);
+            *last_id = latest_id;
+            // An event without data is not dispatched by an `EventSource`.
+            vec!(Event::data("{}").event(RESET_EVENT).id(latest_id.to_string()))
+        }
+    }
+}
+
+fn to_event(event: VehicleEvent) -> Event {
+    let (id, kind) = (event.id, event.kind);
+
+    Event::json(&vehicle_mapper::get_vehicle_event_dto(event))
+        .event(kind.name())
+        .id(id.to_string())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+    use chrono::Utc;
+
+    use crate::domain::vehicle_event::VehicleEventKind;
+    use crate::event::vehicle_event_log::VehicleEventSettings;
+
+    #[test]
+    fn given_last_event_id_when_gets_events_then_replays_following_events_of_user() {
+        let (subscribed_at, vehicle_events) = fixture::vehicle_events();
+        vehicle_events.publish(fixture::event(fixture::user_id(), VehicleEventKind::Created));
+        vehicle_events.publish(fixture::event(fixture::other_user_id(), VehicleEventKind::Created));
+        vehicle_events.publish(fixture::event(fixture::user_id(), VehicleEventKind::Retired));
+        let client = fixture::client(vehicle_events);
+
+        let response = client.get(format!("/vehicle/{}/events", fixture::USER_ID_STR))
+            .header(Header::new("Last-Event-ID", (subscribed_at + 1).to_string()))
+            .dispatch();
+        client.rocket().shutdown().notify();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::EventStream));
+        let body = response.into_string().unwrap();
+        assert!(!body.contains("event:created"));
+        assert!(body.contains(&format!("id:{}\nevent:retired\ndata:{{\"vehicle_id\":\"{}\"", subscribed_at + 3, fixture::VEHICLE_ID_STR)));
+    }
+
+    #[test]
+    fn given_last_event_id_no_longer_kept_when_gets_events_then_sends_reset() {
+        let (subscribed_at, vehicle_events) = fixture::vehicle_events();
+        vehicle_events.publish(fixture::event(fixture::user_id(), VehicleEventKind::Updated));
+        let client = fixture::client(vehicle_events);
+
+        let response = client.get(format!("/vehicle/{}/events", fixture::USER_ID_STR))
+            .header(Header::new("Last-Event-ID", "5"))
+            .dispatch();
+        client.rocket().shutdown().notify();
+
+        let body = response.into_string().unwrap();
+        assert!(body.contains(&format!("id:{}\nevent:reset\ndata:{{}}", subscribed_at + 1)));
+        assert!(!body.contains("event:updated"));
+    }
+
+    #[test]
+    fn given_no_last_event_id_when_gets_events_then_replays_nothing() {
+        let (_, vehicle_events) = fixture::vehicle_events();
+        vehicle_events.publish(fixture::event(fixture::user_id(), VehicleEventKind::Created));
+        let client = fixture::client(vehicle_events);
+
+        let response = client.get(format!("/vehicle/{}/events", fixture::USER_ID_STR)).dispatch();
+        client.rocket().shutdown().notify();
+
+        assert!(!response.into_string().unwrap().contains("event:"));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const OTHER_USER_ID_STR: &str = "6176bc4b-33b6-4c9c-a4ad-c65da1322a80";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn other_user_id() -> Uuid {
+            Uuid::parse_str(OTHER_USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn event(user_id: Uuid, kind: VehicleEventKind) -> VehicleEvent {
+            VehicleEvent {
+                id: 0,
+                event_id: Uuid::new_v4(),
+                user_id,
+                vehicle_id: vehicle_id(),
+                kind,
+                occurred_at: Utc::now(),
+                vehicle: None
+            }
+        }
+
+        /// A log without events, with the id the events published to it follow.
+        pub fn vehicle_events() -> (u64, Arc<VehicleEventLog>) {
+            let vehicle_events = VehicleEventLog::new(&VehicleEventSettings::default());
+
+            (vehicle_events.subscribe().0, Arc::new(vehicle_events))
+        }
+
+        pub fn client(vehicle_events: Arc<VehicleEventLog>) -> Client {
+            let rocket_build = rocket::build().manage(vehicle_events).mount("/", routes![get_vehicle_events]);
+
+            Client::untracked(rocket_build).expect("valid rocket instance")
+        }
+    }
+}
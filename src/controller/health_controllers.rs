@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+
+use crate::dao::circuit_breaker::CircuitBreaker;
+use crate::repository::cached_vehicle_repository::VehicleCache;
+use crate::dto::health_dto::{MetricsDTO, ReadinessDTO};
+use crate::mapper::health_mapper;
+
+#[get("/ready")]
+pub async fn ready(circuit_breaker: &State<Arc<CircuitBreaker>>) -> (Status, Json<ReadinessDTO>) {
+    let state = circuit_breaker.state();
+    let status = if health_mapper::is_ready(state) { Status::Ok } else { Status::ServiceUnavailable };
+
+    (status, Json(health_mapper::get_readiness_dto(state)))
+}
+
+#[get("/metrics")]
+pub async fn metrics(circuit_breaker: &State<Arc<CircuitBreaker>>, vehicle_cache: &State<Arc<VehicleCache>>) -> Json<MetricsDTO> {
+    Json(MetricsDTO {
+        circuit_breaker: health_mapper::get_circuit_breaker_metrics_dto(circuit_breaker.state(), circuit_breaker.metrics()),
+        vehicle_cache: health_mapper::get_cache_metrics_dto(vehicle_cache.metrics())
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::time::Duration;
+    use rocket::local::blocking::Client;
+
+    use crate::dao::circuit_breaker::CircuitBreakerSettings;
+    use crate::repository::cached_vehicle_repository::VehicleCacheSettings;
+
+    #[test]
+    fn given_closed_circuit_when_gets_ready_then_responds_with_200() {
+        let circuit_breaker = Arc::new(CircuitBreaker::new(CircuitBreakerSettings::default()));
+
+        let rocket_build = rocket::build().manage(circuit_breaker).mount("/", routes![ready]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/ready").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json_response = response.into_json::<ReadinessDTO>().unwrap();
+        assert_eq!("closed", json_response.circuit_breaker);
+    }
+
+    #[test]
+    fn given_open_circuit_when_gets_ready_then_responds_with_503() {
+        let circuit_breaker = Arc::new(fixture::open_circuit_breaker());
+
+        let rocket_build = rocket::build().manage(circuit_breaker).mount("/", routes![ready]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/ready").dispatch();
+
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        let json_response = response.into_json::<ReadinessDTO>().unwrap();
+        assert_eq!("open", json_response.circuit_breaker);
+    }
+
+    #[test]
+    fn when_gets_metrics_then_responds_with_circuit_breaker_and_cache_counters() {
+        let circuit_breaker = Arc::new(fixture::open_circuit_breaker());
+        let vehicle_cache = Arc::new(VehicleCache::new(&VehicleCacheSettings::default()));
+
+        let rocket_build = rocket::build().manage(circuit_breaker).manage(vehicle_cache).mount("/", routes![metrics]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/metrics").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json_response = response.into_json::<MetricsDTO>().unwrap();
+        assert_eq!("open", json_response.circuit_breaker.state);
+        assert_eq!(1, json_response.circuit_breaker.failures);
+        assert_eq!(1, json_response.circuit_breaker.times_opened);
+        assert_eq!(0, json_response.vehicle_cache.hits);
+        assert_eq!(0, json_response.vehicle_cache.size);
+    }
+
+    pub mod fixture {
+        use super::*;
+
+        pub fn open_circuit_breaker() -> CircuitBreaker {
+            let circuit_breaker = CircuitBreaker::new(CircuitBreakerSettings {
+                minimum_calls: 1,
+                open_duration_ms: 60_000,
+                ..CircuitBreakerSettings::default()
+            });
+            circuit_breaker.try_acquire().unwrap().record(true, Duration::from_millis(1));
+
+            circuit_breaker
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::serde::uuid::Uuid;
+use rocket::State;
+use mockall_double::double;
+
+use crate::controller::actor::Actor;
+use crate::dto::vehicle_dto::VehicleDTO;
+use crate::dto::vehicle_history_dto::VehicleVersionDTO;
+use crate::service::vehicle_service::HistoryError;
+
+#[double]
+use crate::service::vehicle_service::VehicleService;
+
+const DEFAULT_VERSIONS: usize = 20;
+const MAX_VERSIONS: usize = 100;
+
+/// Latest versions of a vehicle, newest first, with who saved them and what they changed.
+#[get("/vehicle/<user_id>/<vehicle_id>/history?<limit>")]
+pub async fn get_history(vehicle_service: &State<Arc<VehicleService>>, user_id: Uuid, vehicle_id: Uuid, limit: Option<usize>) -> Result<Json<Vec<VehicleVersionDTO>>, Status> {
+    vehicle_service.get_history(user_id, vehicle_id, limit.unwrap_or(DEFAULT_VERSIONS).clamp(1, MAX_VERSIONS)).await
+        .map(Json)
+        .ok_or(Status::ServiceUnavailable)
+}
+
+/// Saves a vehicle back as it was in one of its versions, itself recorded as a new version.
+#[post("/vehicle/<user_id>/<vehicle_id>/history/<version>/restore")]
+pub async fn restore_version(vehicle_service: &State<Arc<VehicleService>>, user_id: Uuid, vehicle_id: Uuid, version: Uuid, actor: Actor) -> Result<Json<VehicleDTO>, Status> {
+    vehicle_service.restore_version(user_id, vehicle_id, version, &actor.0).await
+        .map(Json)
+        .map_err(to_status)
+}
+
+/// The RFC 3339 time of a query parameter such as `as_of`, `400 Bad Request` when it is not one.
+pub fn parse_time(time: &str) -> Result<DateTime<Utc>, Status> {
+    DateTime::parse_from_rfc3339(time)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| {
+            println!("Rejected time {}: {}", time, e);
+            Status::BadRequest
+        })
+}
+
+fn to_status(error: HistoryError) -> Status {
+    match error {
+        HistoryError::VehicleNotFound | HistoryError::VersionNotFound => Status::NotFound,
+        HistoryError::StorageFailure => Status::ServiceUnavailable
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use chrono::{NaiveDate, TimeZone};
+    use rocket::http::Header;
+    use rocket::local::blocking::Client;
+
+    #[test]
+    fn when_gets_history_then_responds_with_versions_limited() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_get_history()
+            .withf(|_, _, limit: &usize| *limit == MAX_VERSIONS)
+            .times(1)
+            .returning(|_, _, _| Some(vec!(fixture::version_dto(5000))));
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![get_history]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/{}/{}/history?limit=1000", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+        let versions = response.into_json::<Vec<VehicleVersionDTO>>().unwrap();
+        assert_eq!((fixture::VERSION_STR.to_string(), "the actor"), (versions[0].version.to_string(), versions[0].actor.as_str()));
+        assert_eq!(5000, versions[0].changed_at.timestamp_millis());
+    }
+
+    #[test]
+    fn given_unknown_version_when_restores_version_then_responds_not_found() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_restore_version()
+            .withf(|_, _, version: &Uuid, actor: &str| version.to_string() == fixture::VERSION_STR && actor == "jane")
+            .times(1)
+            .returning(|_, _, _, _| Err(HistoryError::VersionNotFound));
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![restore_version]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post(format!("/vehicle/{}/{}/history/{}/restore", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR, fixture::VERSION_STR))
+            .header(Header::new("X-Actor", "jane"))
+            .dispatch();
+
+        assert_eq!(Status::NotFound, response.status());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const VERSION_STR: &str = "5d3c4e80-4f9a-11ec-8c4f-2f6b1a7d9e01";
+
+        pub fn version_dto(saved_at_ms: i64) -> VehicleVersionDTO {
+            VehicleVersionDTO {
+                version: Uuid::parse_str(VERSION_STR).unwrap(),
+                changed_at: Utc.timestamp_millis(saved_at_ms),
+                actor: "the actor".to_string(),
+                event: "updated".to_string(),
+                changes: BTreeMap::new(),
+                vehicle: VehicleDTO {
+                    name: "the vehicle name".to_string(),
+                    user_id: Uuid::parse_str(USER_ID_STR).unwrap(),
+                    vehicle_id: Some(Uuid::parse_str(VEHICLE_ID_STR).unwrap()),
+                    created_at: Utc.timestamp(5, 0),
+                    vehicle_type: "bike".to_string(),
+                    retired_at: None,
+                    brand: "the brand".to_string(),
+                    model: "the model".to_string(),
+                    distance: 100,
+                    owner_since: NaiveDate::from_ymd(2015, 12, 2),
+                    manufacturing_date: NaiveDate::from_ymd(2015, 12, 2),
+                    picture: None
+                },
+                restored_from: None
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket::serde::uuid::Uuid;
+use mockall_double::double;
+
+use crate::controller::audit_fairing::AuditTrail;
+use crate::dto::maintenance_dto::{MaintenanceRecordDTO, ReminderDTO, ReminderRuleDTO};
+use crate::service::maintenance_service::MaintenanceError;
+
+#[double]
+use crate::service::maintenance_service::MaintenanceService;
+
+#[get("/vehicle/<user_id>/<vehicle_id>/maintenance")]
+pub async fn get_records(maintenance_service: &State<Arc<MaintenanceService>>, user_id: Uuid, vehicle_id: Uuid) -> Json<Vec<MaintenanceRecordDTO>> {
+    Json(maintenance_service.get_records(user_id, vehicle_id).await)
+}
+
+#[post("/vehicle/<user_id>/<vehicle_id>/maintenance", format = "application/json", data = "<record_json>")]
+pub async fn new_record(maintenance_service: &State<Arc<MaintenanceService>>, user_id: Uuid, vehicle_id: Uuid, record_json: Json<MaintenanceRecordDTO>, audit_trail: AuditTrail<'_>) -> Result<Json<MaintenanceRecordDTO>, Status> {
+    maintenance_service.save_record(user_id, vehicle_id, record_json.into_inner()).await
+        .map(|record| {
+            if let Some(record_id) = record.record_id {
+                audit_trail.target("record_id", record_id);
+            }
+            Json(record)
+        })
+        .map_err(to_status)
+}
+
+#[get("/vehicle/<user_id>/<vehicle_id>/reminder-rules")]
+pub async fn get_rules(maintenance_service: &State<Arc<MaintenanceService>>, user_id: Uuid, vehicle_id: Uuid) -> Json<Vec<ReminderRuleDTO>> {
+    Json(maintenance_service.get_rules(user_id, vehicle_id).await)
+}
+
+#[post("/vehicle/<user_id>/<vehicle_id>/reminder-rules", format = "application/json", data = "<rule_json>")]
+pub async fn new_rule(maintenance_service: &State<Arc<MaintenanceService>>, user_id: Uuid, vehicle_id: Uuid, rule_json: Json<ReminderRuleDTO>, audit_trail: AuditTrail<'_>) -> Result<Json<ReminderRuleDTO>, Status> {
+    maintenance_service.save_rule(user_id, vehicle_id, rule_json.into_inner()).await
+        .map(|rule| {
+            if let Some(rule_id) = rule.rule_id {
+                audit_trail.target("rule_id", rule_id);
+            }
+            Json(rule)
+        })
+        .map_err(to_status)
+}
+
+#[get("/vehicle/<user_id>/<vehicle_id>/reminders")]
+pub async fn get_reminders(maintenance_service: &State<Arc<MaintenanceService>>, user_id: Uuid, vehicle_id: Uuid) -> Result<Json<Vec<ReminderDTO>>, Status> {
+    maintenance_service.get_reminders(user_id, vehicle_id, Utc::today().naive_utc()).await
+        .map(Json)
+        .map_err(to_status)
+}
+
+fn to_status(error: MaintenanceError) -> Status {
+    match error {
+        MaintenanceError::VehicleNotFound => Status::NotFound,
+        MaintenanceError::InvalidRule => Status::UnprocessableEntity,
+        MaintenanceError::StorageFailure => Status::InternalServerError
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::http::ContentType;
+    use chrono::NaiveDate;
+
+    use crate::dto::maintenance_dto::ReminderStatus;
+
+    #[test]
+    fn when_gets_reminders_then_responds_with_json_reminders() {
+        let mut maintenance_service = MaintenanceService::default();
+        maintenance_service.expect_get_reminders()
+            .withf(|user_id: &Uuid, _, _| user_id == &Uuid::parse_str(fixture::USER_ID_STR).unwrap())
+            .times(1)
+            .returning(move |_, _, _| Ok(vec!(fixture::reminder())));
+
+        let rocket_build = rocket::build().manage(Arc::new(maintenance_service)).mount("/", routes![get_reminders]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/{}/{}/reminders", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json_response = response.into_json::<Vec<ReminderDTO>>().unwrap();
+        assert_eq!(1, json_response.len());
+        assert_eq!(ReminderStatus::Overdue, json_response[0].status);
+    }
+
+    #[test]
+    fn given_unknown_vehicle_when_gets_reminders_then_responds_with_404() {
+        let mut maintenance_service = MaintenanceService::default();
+        maintenance_service.expect_get_reminders()
+            .times(1)
+            .returning(move |_, _, _| Err(MaintenanceError::VehicleNotFound));
+
+        let rocket_build = rocket::build().manage(Arc::new(maintenance_service)).mount("/", routes![get_reminders]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/{}/{}/reminders", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn when_posts_record_then_responds_with_json_record() {
+        let mut maintenance_service = MaintenanceService::default();
+        maintenance_service.expect_save_record()
+            .withf(|_, _, record_dto: &MaintenanceRecordDTO| record_dto.description == "chain")
+            .times(1)
+            .returning(move |_, _, record_dto| Ok(record_dto));
+
+        let rocket_build = rocket::build().manage(Arc::new(maintenance_service)).mount("/", routes![new_record]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post(format!("/vehicle/{}/{}/maintenance", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR))
+            .header(ContentType::JSON)
+            .body(r#"{ "performed_on": "2021-06-01", "odometer": 3400, "description": "chain", "cost": 30.0, "parts": ["chain"] }"#)
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json_response = response.into_json::<MaintenanceRecordDTO>().unwrap();
+        assert_eq!(3400, json_response.odometer);
+        assert_eq!(vec!("chain".to_string()), json_response.parts);
+    }
+
+    #[test]
+    fn given_invalid_rule_when_posts_rule_then_responds_with_422() {
+        let mut maintenance_service = MaintenanceService::default();
+        maintenance_service.expect_save_rule()
+            .times(1)
+            .returning(move |_, _, _| Err(MaintenanceError::InvalidRule));
+
+        let rocket_build = rocket::build().manage(Arc::new(maintenance_service)).mount("/", routes![new_rule]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post(format!("/vehicle/{}/{}/reminder-rules", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR))
+            .header(ContentType::JSON)
+            .body(r#"{ "description": "service" }"#)
+            .dispatch();
+
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "6176bc4b-33b6-4c9c-a4ad-c65da1322a80";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+
+        pub fn reminder() -> ReminderDTO {
+            ReminderDTO {
+                rule_id: Uuid::new_v4(),
+                description: "service".to_string(),
+                status: ReminderStatus::Overdue,
+                due_distance: Some(3000),
+                due_date: Some(NaiveDate::from_ymd(2021, 1, 1)),
+                remaining_distance: Some(-500)
+            }
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::cmp::Ordering;
+use std::io::Cursor;
+
+use rocket::data::{self, Data, FromData, Limits};
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::Serialize;
+use rocket::serde::de::DeserializeOwned;
+
+/// Header carrying the `next` token of a list written as CSV, which has nowhere else to hold it.
+pub const NEXT_PAGE_HEADER: &str = "Next-Page";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+    Csv
+}
+
+/// Formats of single values, JSON first as the default.
+pub const VALUE_FORMATS: [Format; 3] = [Format::Json, Format::MessagePack, Format::Cbor];
+/// Formats of lists, which can also be written as CSV.
+pub const LIST_FORMATS: [Format; 4] = [Format::Json, Format::MessagePack, Format::Cbor, Format::Csv];
+
+impl Format {
+    pub fn from_media_type(media_type: &str) -> Option<Format> {
+        match media_type {
+            "application/json" => Some(Format::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Format::MessagePack),
+            "application/cbor" => Some(Format::Cbor),
+            "text/csv" => Some(Format::Csv),
+            _ => None
+        }
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+            Format::Csv => "text/csv"
+        }
+    }
+}
+
+/// The format an `Accept` header prefers among `formats`, the first of them when there is no header or it
+/// accepts anything, `None` when it accepts none of them.
+pub fn negotiate(accept: Option<&str>, formats: &[Format]) -> Option<Format> {
+    let accept = match accept.map(str::trim).filter(|accept| !accept.is_empty()) {
+        Some(accept) => accept,
+        None => return formats.first().copied()
+    };
+
+    let mut ranges: Vec<(String, f32)> = accept.split(',')
+        .filter_map(|range| {
+            let mut parameters = range.split(';');
+            let media_range = parameters.next()?.trim().to_lowercase();
+            let quality = parameters
+                .find_map(|parameter| parameter.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |quality| quality.trim().parse::<f32>().ok())?;
+
+            Some((media_range, quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+    // Stable, so that ranges of the same quality keep the order the client listed them in.
+    ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+
+    ranges.iter().find_map(|(media_range, _)| formats.iter().copied().find(|format| matches(media_range, format.media_type())))
+}
+
+fn matches(media_range: &str, media_type: &str) -> bool {
+    match media_range.split_once('/') {
+        Some(("*", "*")) => true,
+        Some((top, "*")) => media_type.split('/').next() == Some(top),
+        _ => media_range == media_type
+    }
+}
+
+/// Writes a value in any format but CSV, which only lists implementing `CsvRecords` can be written as.
+pub fn encode<T: Serialize>(format: Format, value: &T) -> Result<Vec<u8>, String> {
+    match format {
+        Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+        Format::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+        Format::Cbor => serde_cbor::to_vec(value).map_err(|e| e.to_string()),
+        Format::Csv => Err("Only lists can be written as CSV".to_string())
+    }
+}
+
+pub fn decode<T: DeserializeOwned>(format: Format, bytes: &[u8]) -> Result<T, String> {
+    match format {
+        Format::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+        Format::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+        Format::Cbor => serde_cbor::from_slice(bytes).map_err(|e| e.to_string()),
+        Format::Csv => Err("CSV request bodies are read by the import endpoint".to_string())
+    }
+}
+
+/// A list response that can also be written as CSV.
+pub trait CsvRecords {
+    /// Header line followed by one line per record, without line terminators.
+    fn csv_lines(&self) -> Vec<String>;
+
+    /// Token of the following page, if the list is a page of a longer one.
+    fn next_page(&self) -> Option<String> {
+        None
+    }
+}
+
+/// A value written as JSON, MessagePack or CBOR, whichever the `Accept` header prefers, `406 Not Acceptable`
+/// answering any other. As a request body, read in the format of its `Content-Type`, `415 Unsupported Media
+/// Type` answering any other.
+#[derive(Debug)]
+pub struct Negotiated<T>(pub T);
+
+impl<T> Negotiated<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Negotiated<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let format = negotiate(request.headers().get_one("Accept"), &VALUE_FORMATS).ok_or(Status::NotAcceptable)?;
+
+        respond(format, encode(format, &self.0))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for Negotiated<T> {
+    type Error = String;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let format = request.content_type()
+            .and_then(|content_type| Format::from_media_type(&format!("{}/{}", content_type.top(), content_type.sub()).to_lowercase()))
+            .filter(|format| VALUE_FORMATS.contains(format));
+        let format = match format {
+            Some(format) => format,
+            None => return data::Outcome::Failure((Status::UnsupportedMediaType, "Unsupported request body format".to_string()))
+        };
+
+        let limit = request.limits().get("json").unwrap_or(Limits::JSON);
+        let bytes = match data.open(limit).into_bytes().await {
+            Ok(bytes) if bytes.is_complete() => bytes.into_inner(),
+            Ok(_) => return data::Outcome::Failure((Status::PayloadTooLarge, "Request body limit exceeded".to_string())),
+            Err(e) => return data::Outcome::Failure((Status::BadRequest, e.to_string()))
+        };
+
+        match decode(format, &bytes) {
+            Ok(value) => data::Outcome::Success(Negotiated(value)),
+            Err(reason) => {
+                println!("Rejected {} request body: {}", format.media_type(), reason);
+                data::Outcome::Failure((Status::UnprocessableEntity, reason))
+            }
+        }
+    }
+}
+
+/// Like `Negotiated`, also writing the list as CSV when preferred, the `next` token of a page then being
+/// sent in the `Next-Page` header.
+#[derive(Debug)]
+pub struct NegotiatedList<T>(pub T);
+
+impl<'r, T: Serialize + CsvRecords> Responder<'r, 'static> for NegotiatedList<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let format = negotiate(request.headers().get_one("Accept"), &LIST_FORMATS).ok_or(Status::NotAcceptable)?;
+
+        if format != Format::Csv {
+            return respond(format, encode(format, &self.0));
+        }
+
+        let body = self.0.csv_lines().iter().map(|line| format!("{}\n", line)).collect::<String>();
+        let mut response = respond(format, Ok(body.into_bytes()))?;
+        if let Some(next) = self.0.next_page() {
+            response.set_header(Header::new(NEXT_PAGE_HEADER, next));
+        }
+
+        Ok(response)
+    }
+}
+
+fn respond(format: Format, body: Result<Vec<u8>, String>) -> response::Result<'static> {
+    let body = body.map_err(|e| {
+        println!("Failed to write {} response: {}", format.media_type(), e);
+        Status::InternalServerError
+    })?;
+
+    Response::build()
+        .header(ContentType::parse_flexible(format.media_type()).unwrap_or(ContentType::Binary))
+        .header(Header::new("Vary", "Accept"))
+        .sized_body(body.len(), Cursor::new(body))
+        .ok()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+
+    #[test]
+    fn when_negotiate_then_picks_preferred_supported_format() {
+        assert_eq!(Some(Format::Json), negotiate(None, &VALUE_FORMATS));
+        assert_eq!(Some(Format::Json), negotiate(Some("*/*"), &VALUE_FORMATS));
+        assert_eq!(Some(Format::Cbor), negotiate(Some("text/html, application/cbor"), &VALUE_FORMATS));
+        assert_eq!(Some(Format::MessagePack), negotiate(Some("application/json;q=0.5, application/x-msgpack"), &VALUE_FORMATS));
+        assert_eq!(Some(Format::Csv), negotiate(Some("text/*"), &LIST_FORMATS));
+        assert_eq!(None, negotiate(Some("text/csv"), &VALUE_FORMATS));
+        assert_eq!(None, negotiate(Some("application/json;q=0"), &VALUE_FORMATS));
+    }
+
+    #[test]
+    fn given_message_pack_accepted_when_gets_value_then_responds_with_message_pack() {
+        let client = fixture::client();
+
+        let response = client.get("/value").header(Header::new("Accept", "application/msgpack")).dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Content-Type"), Some("application/msgpack"));
+        assert_eq!(fixture::value(), rmp_serde::from_slice::<fixture::Value>(&response.into_bytes().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn given_unsupported_accept_when_gets_value_then_responds_with_406() {
+        let client = fixture::client();
+
+        let response = client.get("/value").header(Header::new("Accept", "text/csv")).dispatch();
+
+        assert_eq!(response.status(), Status::NotAcceptable);
+    }
+
+    #[test]
+    fn given_csv_accepted_when_gets_list_then_responds_with_csv_and_next_page() {
+        let client = fixture::client();
+
+        let response = client.get("/list").header(Header::new("Accept", "text/csv")).dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one(NEXT_PAGE_HEADER), Some(fixture::NEXT));
+        assert_eq!(response.into_string().unwrap(), "name,count\nthe name,3\n");
+    }
+
+    #[test]
+    fn given_cbor_body_when_posts_value_then_reads_it() {
+        let client = fixture::client();
+
+        let response = client.post("/value")
+            .header(ContentType::new("application", "cbor"))
+            .body(serde_cbor::to_vec(&fixture::value()).unwrap())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(fixture::value(), response.into_json::<fixture::Value>().unwrap());
+    }
+
+    #[test]
+    fn given_unsupported_content_type_when_posts_value_then_responds_with_415() {
+        let client = fixture::client();
+
+        let response = client.post("/value")
+            .header(ContentType::new("text", "csv"))
+            .body("name,count\nthe name,3\n")
+            .dispatch();
+
+        assert_eq!(response.status(), Status::UnsupportedMediaType);
+    }
+
+    mod fixture {
+        use super::*;
+        use rocket::serde::Deserialize;
+
+        pub const NEXT: &str = "the next token";
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        pub struct Value {
+            pub name: String,
+            pub count: u32
+        }
+
+        impl CsvRecords for Value {
+            fn csv_lines(&self) -> Vec<String> {
+                vec!("name,count".to_string(), format!("{},{}", self.name, self.count))
+            }
+
+            fn next_page(&self) -> Option<String> {
+                Some(NEXT.to_string())
+            }
+        }
+
+        pub fn value() -> Value {
+            Value { name: "the name".to_string(), count: 3 }
+        }
+
+        #[get("/value")]
+        pub fn get_value() -> Negotiated<Value> {
+            Negotiated(value())
+        }
+
+        #[get("/list")]
+        pub fn get_list() -> NegotiatedList<Value> {
+            NegotiatedList(value())
+        }
+
+        #[post("/value", data = "<value>")]
+        pub fn post_value(value: Negotiated<Value>) -> Negotiated<Value> {
+            value
+        }
+
+        pub fn client() -> Client {
+            Client::tracked(rocket::build().mount("/", routes![get_value, get_list, post_value])).expect("valid rocket instance")
+        }
+    }
+}
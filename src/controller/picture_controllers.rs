@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use rocket::form::Form;
+use rocket::fs::TempFile;
+use rocket::http::Status;
+use rocket::serde::json::{Value, json};
+use rocket::State;
+use rocket::serde::uuid::Uuid;
+use rocket::tokio::io::AsyncReadExt;
+use mockall_double::double;
+
+use crate::controller::actor::Actor;
+use crate::controller::blob_response::BlobResponse;
+use crate::service::picture_service::{PictureError, MAX_PICTURE_BYTES};
+
+#[double]
+use crate::service::picture_service::PictureService;
+
+#[derive(FromForm)]
+pub struct PictureUpload<'r> {
+    picture: TempFile<'r>
+}
+
+#[post("/vehicle/<user_id>/<vehicle_id>/picture", data = "<upload>")]
+pub async fn upload_picture(picture_service: &State<Arc<PictureService>>, user_id: Uuid, vehicle_id: Uuid, upload: Form<PictureUpload<'_>>, actor: Actor) -> Result<Value, Status> {
+    if upload.picture.len() as usize > MAX_PICTURE_BYTES {
+        return Err(Status::PayloadTooLarge);
+    }
+
+    let mut data = Vec::new();
+    upload.picture.open().await
+        .map_err(|_| Status::BadRequest)?
+        .read_to_end(&mut data)
+        .await
+        .map_err(|_| Status::BadRequest)?;
+
+    match picture_service.upload_picture(user_id, vehicle_id, data, &actor.0).await {
+        Ok(url) => Ok(json!({
+            "vehicle_id": vehicle_id,
+            "picture": url
+        })),
+        Err(PictureError::VehicleNotFound) => Err(Status::NotFound),
+        Err(PictureError::TooLarge) => Err(Status::PayloadTooLarge),
+        Err(PictureError::UnsupportedFormat) => Err(Status::UnsupportedMediaType),
+        Err(PictureError::InvalidImage) => Err(Status::UnprocessableEntity),
+        Err(PictureError::StorageFailure) => Err(Status::InternalServerError)
+    }
+}
+
+#[get("/vehicle/<user_id>/<vehicle_id>/picture?<thumbnail>")]
+pub async fn get_picture(picture_service: &State<Arc<PictureService>>, user_id: Uuid, vehicle_id: Uuid, thumbnail: Option<bool>) -> Option<BlobResponse> {
+    picture_service.get_picture(user_id, vehicle_id, thumbnail.unwrap_or(false)).await
+        .map(BlobResponse)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::http::{ContentType, Header};
+
+    use crate::storage::blob_store::Blob;
+
+    #[test]
+    fn when_posts_multipart_picture_then_responds_with_picture_url() {
+        let mut picture_service = PictureService::default();
+        picture_service.expect_upload_picture()
+            .withf(|_, _, data: &Vec<u8>, _: &str| data == fixture::PICTURE.as_bytes())
+            .times(1)
+            .returning(move |_, _, _, _| Ok(fixture::URL.to_string()));
+
+        let rocket_build = rocket::build().manage(Arc::new(picture_service)).mount("/", routes![upload_picture]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post(format!("/vehicle/{}/{}/picture", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR))
+            .header(ContentType::new("multipart", "form-data").with_params(("boundary", fixture::BOUNDARY)))
+            .body(fixture::multipart_body())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json_response = response.into_json::<Value>().unwrap();
+        assert_eq!(fixture::URL, json_response["picture"]);
+    }
+
+    #[test]
+    fn given_unsupported_picture_when_posts_multipart_picture_then_responds_with_415() {
+        let mut picture_service = PictureService::default();
+        picture_service.expect_upload_picture()
+            .times(1)
+            .returning(move |_, _, _, _| Err(PictureError::UnsupportedFormat));
+
+        let rocket_build = rocket::build().manage(Arc::new(picture_service)).mount("/", routes![upload_picture]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post(format!("/vehicle/{}/{}/picture", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR))
+            .header(ContentType::new("multipart", "form-data").with_params(("boundary", fixture::BOUNDARY)))
+            .body(fixture::multipart_body())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::UnsupportedMediaType);
+    }
+
+    #[test]
+    fn when_gets_thumbnail_then_responds_with_blob() {
+        let mut picture_service = PictureService::default();
+        picture_service.expect_get_picture()
+            .withf(|_, _, thumbnail: &bool| *thumbnail)
+            .times(1)
+            .returning(move |_, _, _| Some(Blob { data: fixture::PICTURE.as_bytes().to_vec(), content_type: ContentType::JPEG, etag: "\"1\"".to_string() }));
+
+        let rocket_build = rocket::build().manage(Arc::new(picture_service)).mount("/", routes![get_picture]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/{}/{}/picture?thumbnail=true", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR))
+            .header(Header::new("Range", "bytes=0-2"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(response.content_type(), Some(ContentType::JPEG));
+        assert_eq!(response.into_bytes().unwrap(), fixture::PICTURE.as_bytes()[0..=2].to_vec());
+    }
+
+    #[test]
+    fn given_no_picture_when_gets_picture_then_responds_with_404() {
+        let mut picture_service = PictureService::default();
+        picture_service.expect_get_picture()
+            .times(1)
+            .returning(move |_, _, _| None);
+
+        let rocket_build = rocket::build().manage(Arc::new(picture_service)).mount("/", routes![get_picture]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/{}/{}/picture", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    mod fixture {
+        pub const USER_ID_STR: &str = "6176bc4b-33b6-4c9c-a4ad-c65da1322a80";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const URL: &str = "/api/vehicle/6176bc4b-33b6-4c9c-a4ad-c65da1322a80/88573010-cf4c-490e-9d29-f8517dc60b90/picture";
+        pub const BOUNDARY: &str = "X-PICTURE-BOUNDARY";
+        pub const PICTURE: &str = "not really a jpeg";
+
+        pub fn multipart_body() -> String {
+            format!("--{boundary}\r\n\
+                Content-Disposition: form-data; name=\"picture\"; filename=\"bike.jpg\"\r\n\
+                Content-Type: image/jpeg\r\n\r\n\
+                {picture}\r\n\
+                --{boundary}--\r\n", boundary = BOUNDARY, picture = PICTURE)
+        }
+    }
+}
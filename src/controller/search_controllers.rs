@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use rocket::http::Status;
+use rocket::serde::uuid::Uuid;
+use rocket::State;
+use mockall_double::double;
+
+use crate::controller::negotiation::{CsvRecords, NegotiatedList};
+use crate::domain::vehicle_lookup::{self, VehicleFilter};
+use crate::dto::vehicle_dto::VehicleSearchDTO;
+use crate::mapper::vehicle_mapper;
+use crate::parser::vehicle_records;
+
+#[double]
+use crate::service::vehicle_service::VehicleService;
+
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(FromForm, Debug)]
+pub struct SearchQuery {
+    pub brand               : Option<String>,
+    pub model               : Option<String>,
+    pub vehicle_type        : Option<String>,
+    /// `active` or `retired`.
+    pub status              : Option<String>,
+    pub min_distance        : Option<i32>,
+    pub max_distance        : Option<i32>,
+    /// `next` token of the previous page.
+    pub after               : Option<String>,
+    pub limit               : Option<usize>,
+    /// Comma separated fields of the vehicles to answer, all of them by default.
+    pub fields              : Option<String>
+}
+
+/// Searches the vehicles of every user. At least one of `brand` or `model` is required, the type, status and
+/// distances only narrowing down the vehicles found through them.
+#[get("/vehicle/search?<query..>")]
+pub async fn search_vehicles(vehicle_service: &State<Arc<VehicleService>>, query: SearchQuery) -> Result<NegotiatedList<VehicleSearchDTO>, Status> {
+    let retired = match query.status.as_deref().map(vehicle_lookup::normalize).as_deref() {
+        None => None,
+        Some(vehicle_lookup::ACTIVE) => Some(false),
+        Some(vehicle_lookup::RETIRED) => Some(true),
+        Some(_) => return Err(Status::BadRequest)
+    };
+
+    let after = page_after(query.after.as_deref())?;
+    let fields = vehicle_fields(query.fields.as_deref())?;
+
+    let filter = VehicleFilter {
+        brand: query.brand,
+        model: query.model,
+        vehicle_type: query.vehicle_type,
+        retired,
+        min_distance: query.min_distance,
+        max_distance: query.max_distance
+    };
+    if filter.lookup_key().is_none() {
+        return Err(Status::BadRequest);
+    }
+
+    vehicle_service.search_vehicles(filter, after, page_size(query.limit), fields).await
+        .map(NegotiatedList)
+        .ok_or(Status::ServiceUnavailable)
+}
+
+/// Lists the vehicles of a user page by page or, with `q`, those whose name, brand or model match its
+/// words, allowing prefixes and typos. `fields` limits the fields of the vehicles answered.
+#[get("/vehicle/<user_id>?<q>&<after>&<limit>&<fields>")]
+pub async fn list_vehicles(vehicle_service: &State<Arc<VehicleService>>, user_id: Uuid, q: Option<String>, after: Option<&str>, limit: Option<usize>, fields: Option<&str>) -> Result<NegotiatedList<VehicleSearchDTO>, Status> {
+    let after = page_after(after)?;
+    let fields = vehicle_fields(fields)?;
+
+    vehicle_service.list_vehicles(user_id, q, after, page_size(limit), fields).await
+        .map(NegotiatedList)
+        .ok_or(Status::ServiceUnavailable)
+}
+
+/// One CSV line per vehicle, all of them holding the same fields.
+impl CsvRecords for VehicleSearchDTO {
+    fn csv_lines(&self) -> Vec<String> {
+        let header = self.vehicles.first().map(vehicle_records::projection_csv_header);
+
+        header.into_iter()
+            .chain(self.vehicles.iter().map(vehicle_records::write_projection_csv))
+            .collect()
+    }
+
+    fn next_page(&self) -> Option<String> {
+        self.next.clone()
+    }
+}
+
+fn page_after(token: Option<&str>) -> Result<Option<(Uuid, Uuid)>, Status> {
+    match token {
+        Some(token) => vehicle_mapper::parse_page_token(token).map(Some).ok_or(Status::BadRequest),
+        None => Ok(None)
+    }
+}
+
+/// Fields of a `fields` parameter, every field of a vehicle when missing.
+pub fn vehicle_fields(fields: Option<&str>) -> Result<Vec<&'static str>, Status> {
+    match fields {
+        Some(fields) => vehicle_mapper::parse_fields(fields).map_err(|reason| {
+            println!("Rejected vehicle fields: {}", reason);
+            Status::BadRequest
+        }),
+        None => Ok(vehicle_mapper::all_fields())
+    }
+}
+
+fn page_size(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::http::Header;
+
+    use crate::controller::negotiation::NEXT_PAGE_HEADER;
+    use crate::dto::vehicle_dto::VehicleProjectionDTO;
+
+    #[test]
+    fn when_gets_search_then_responds_with_page_of_matching_vehicles() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_search_vehicles()
+            .withf(|filter: &VehicleFilter, after: &Option<(Uuid, Uuid)>, limit: &usize, fields: &Vec<&'static str>| *filter == VehicleFilter {
+                    brand: Some("trek".to_string()),
+                    retired: Some(false),
+                    min_distance: Some(100),
+                    ..VehicleFilter::default()
+                }
+                && *after == Some((Uuid::parse_str(fixture::USER_ID_STR).unwrap(), Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap()))
+                && *limit == MAX_PAGE_SIZE
+                && *fields == vehicle_mapper::all_fields())
+            .times(1)
+            .returning(|_, _, _, _| Some(VehicleSearchDTO { vehicles: vec!(), next: None }));
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![search_vehicles]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/search?brand=trek&status=Active&min_distance=100&after={}.{}&limit=500",
+                                          fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+        let page = response.into_json::<VehicleSearchDTO>().unwrap();
+        assert!(page.vehicles.is_empty());
+        assert!(page.next.is_none());
+    }
+
+    #[test]
+    fn given_distance_filter_only_when_gets_search_then_responds_bad_request() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_search_vehicles()
+            .times(0);
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![search_vehicles]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        assert_eq!(Status::BadRequest, client.get("/vehicle/search?max_distance=100").dispatch().status());
+        assert_eq!(Status::BadRequest, client.get("/vehicle/search?status=sold").dispatch().status());
+        assert_eq!(Status::BadRequest, client.get("/vehicle/search?vehicle_type=bike&status=active").dispatch().status());
+        assert_eq!(Status::BadRequest, client.get("/vehicle/search?brand=trek&after=garbage").dispatch().status());
+        assert_eq!(Status::BadRequest, client.get("/vehicle/search?brand=trek&fields=name,colour").dispatch().status());
+    }
+
+    #[test]
+    fn given_query_when_gets_user_vehicles_then_responds_with_matches() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_list_vehicles()
+            .withf(|user_id: &Uuid, q: &Option<String>, after: &Option<(Uuid, Uuid)>, limit: &usize, fields: &Vec<&'static str>| *user_id == Uuid::parse_str(fixture::USER_ID_STR).unwrap()
+                && *q == Some("time rtm".to_string()) && after.is_none() && *limit == DEFAULT_PAGE_SIZE && *fields == vec!("name", "model"))
+            .times(1)
+            .returning(|_, _, _, _, _| Some(VehicleSearchDTO { vehicles: vec!(), next: None }));
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![list_vehicles, search_vehicles]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/{}?q=time%20rtm&fields=name,model", fixture::USER_ID_STR)).dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+        assert!(response.into_json::<VehicleSearchDTO>().unwrap().vehicles.is_empty());
+    }
+
+    #[test]
+    fn given_csv_accepted_when_gets_user_vehicles_then_responds_with_requested_fields_as_csv() {
+        let mut vehicle_service = VehicleService::default();
+        vehicle_service.expect_list_vehicles()
+            .times(1)
+            .returning(|_, _, _, _, _| Some(VehicleSearchDTO {
+                vehicles: vec!(VehicleProjectionDTO { name: Some("the name".to_string()), distance: Some(15), ..VehicleProjectionDTO::default() }),
+                next: Some("the next token".to_string())
+            }));
+
+        let rocket_build = rocket::build().manage(Arc::new(vehicle_service)).mount("/", routes![list_vehicles]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/{}?fields=name,distance", fixture::USER_ID_STR))
+            .header(Header::new("Accept", "text/csv"))
+            .dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+        assert_eq!(Some("the next token"), response.headers().get_one(NEXT_PAGE_HEADER));
+        assert_eq!("name,distance\nthe name,15\n", response.into_string().unwrap());
+    }
+
+    mod fixture {
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+    }
+}
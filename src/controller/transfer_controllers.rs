@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket::serde::uuid::Uuid;
+use mockall_double::double;
+
+use crate::controller::actor::Actor;
+use crate::controller::audit_fairing::AuditTrail;
+use crate::dto::transfer_dto::{OwnershipRecordDTO, TransferOfferDTO, TransferRequestDTO};
+use crate::dto::vehicle_dto::VehicleDTO;
+use crate::service::transfer_service::TransferError;
+
+#[double]
+use crate::service::transfer_service::TransferService;
+
+#[post("/vehicle/<user_id>/<vehicle_id>/transfer", format = "application/json", data = "<transfer_json>")]
+pub async fn new_offer(transfer_service: &State<Arc<TransferService>>, user_id: Uuid, vehicle_id: Uuid, transfer_json: Json<TransferRequestDTO>, audit_trail: AuditTrail<'_>) -> Result<Json<TransferOfferDTO>, Status> {
+    transfer_service.create_offer(user_id, vehicle_id, transfer_json.to_user_id).await
+        .map(|offer| {
+            audit_trail.target("offer_id", offer.offer_id);
+            audit_trail.target("to_user_id", offer.to_user_id);
+            Json(offer)
+        })
+        .map_err(to_status)
+}
+
+#[get("/transfer/<user_id>")]
+pub async fn get_offers(transfer_service: &State<Arc<TransferService>>, user_id: Uuid) -> Json<Vec<TransferOfferDTO>> {
+    Json(transfer_service.get_offers(user_id).await)
+}
+
+#[put("/transfer/<user_id>/<offer_id>/accept")]
+pub async fn accept_offer(transfer_service: &State<Arc<TransferService>>, user_id: Uuid, offer_id: Uuid, actor: Actor) -> Result<Json<VehicleDTO>, Status> {
+    transfer_service.accept_offer(user_id, offer_id, Utc::today().naive_utc(), &actor.0).await
+        .map(Json)
+        .map_err(to_status)
+}
+
+#[put("/transfer/<user_id>/<offer_id>/decline")]
+pub async fn decline_offer(transfer_service: &State<Arc<TransferService>>, user_id: Uuid, offer_id: Uuid) -> Result<Json<TransferOfferDTO>, Status> {
+    transfer_service.decline_offer(user_id, offer_id).await
+        .map(Json)
+        .map_err(to_status)
+}
+
+#[get("/vehicle/<user_id>/<vehicle_id>/owners")]
+pub async fn get_owners(transfer_service: &State<Arc<TransferService>>, user_id: Uuid, vehicle_id: Uuid) -> Result<Json<Vec<OwnershipRecordDTO>>, Status> {
+    transfer_service.get_owners(user_id, vehicle_id).await
+        .map(Json)
+        .map_err(to_status)
+}
+
+fn to_status(error: TransferError) -> Status {
+    match error {
+        TransferError::VehicleNotFound | TransferError::OfferNotFound => Status::NotFound,
+        TransferError::InvalidRecipient => Status::UnprocessableEntity,
+        TransferError::OfferNotPending => Status::Conflict,
+        TransferError::StorageFailure => Status::InternalServerError
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::http::ContentType;
+    use chrono::{NaiveDate, TimeZone};
+
+    #[test]
+    fn when_posts_transfer_then_responds_with_json_offer() {
+        let mut transfer_service = TransferService::default();
+        transfer_service.expect_create_offer()
+            .withf(|_, _, to_user_id: &Uuid| to_user_id == &Uuid::parse_str(fixture::TO_USER_ID_STR).unwrap())
+            .times(1)
+            .returning(move |user_id, vehicle_id, to_user_id| Ok(TransferOfferDTO {
+                offer_id: Uuid::new_v4(),
+                from_user_id: user_id,
+                to_user_id,
+                vehicle_id,
+                status: "pending".to_string(),
+                created_at: Utc.timestamp(5, 0)
+            }));
+
+        let rocket_build = rocket::build().manage(Arc::new(transfer_service)).mount("/", routes![new_offer]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post(format!("/vehicle/{}/{}/transfer", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR))
+            .header(ContentType::JSON)
+            .body(format!(r#"{{ "to_user_id": "{}" }}"#, fixture::TO_USER_ID_STR))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json_response = response.into_json::<TransferOfferDTO>().unwrap();
+        assert_eq!(fixture::TO_USER_ID_STR.to_string(), json_response.to_user_id.to_string());
+        assert_eq!("pending", json_response.status);
+    }
+
+    #[test]
+    fn given_offer_already_handled_when_puts_accept_then_responds_with_409() {
+        let mut transfer_service = TransferService::default();
+        transfer_service.expect_accept_offer()
+            .times(1)
+            .returning(move |_, _, _, _| Err(TransferError::OfferNotPending));
+
+        let rocket_build = rocket::build().manage(Arc::new(transfer_service)).mount("/", routes![accept_offer]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.put(format!("/transfer/{}/{}/accept", fixture::TO_USER_ID_STR, fixture::OFFER_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::Conflict);
+    }
+
+    #[test]
+    fn when_gets_owners_then_responds_with_json_history() {
+        let mut transfer_service = TransferService::default();
+        transfer_service.expect_get_owners()
+            .times(1)
+            .returning(move |user_id, _| Ok(vec!(OwnershipRecordDTO {
+                user_id,
+                owner_since: NaiveDate::from_ymd(2015, 12, 2),
+                owner_until: Utc.timestamp(5, 0)
+            })));
+
+        let rocket_build = rocket::build().manage(Arc::new(transfer_service)).mount("/", routes![get_owners]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/vehicle/{}/{}/owners", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json_response = response.into_json::<Vec<OwnershipRecordDTO>>().unwrap();
+        assert_eq!(1, json_response.len());
+        assert_eq!(NaiveDate::from_ymd(2015, 12, 2), json_response[0].owner_since);
+    }
+
+    mod fixture {
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const TO_USER_ID_STR: &str = "6176bc4b-33b6-4c9c-a4ad-c65da1322a80";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const OFFER_ID_STR: &str = "9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d";
+    }
+}
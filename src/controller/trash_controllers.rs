@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::serde::uuid::Uuid;
+use rocket::State;
+use mockall_double::double;
+
+use crate::controller::actor::Actor;
+use crate::dto::trash_dto::TrashedVehicleDTO;
+use crate::dto::vehicle_dto::VehicleDTO;
+use crate::service::trash_service::TrashError;
+
+#[double]
+use crate::service::trash_service::TrashService;
+
+/// Moves a vehicle to the trash of its user, from which it can be restored until it is purged.
+#[delete("/vehicle/<user_id>/<vehicle_id>")]
+pub async fn delete_vehicle(trash_service: &State<Arc<TrashService>>, user_id: Uuid, vehicle_id: Uuid, actor: Actor) -> Result<Status, Status> {
+    trash_service.delete_vehicle(user_id, vehicle_id, &actor.0).await
+        .map(|_| Status::NoContent)
+        .map_err(to_status)
+}
+
+/// Deleted vehicles of a user, with who deleted them and when they are purged.
+#[get("/vehicle/<user_id>/trash")]
+pub async fn get_trash(trash_service: &State<Arc<TrashService>>, user_id: Uuid) -> Result<Json<Vec<TrashedVehicleDTO>>, Status> {
+    trash_service.get_trash(user_id).await
+        .map(Json)
+        .ok_or(Status::ServiceUnavailable)
+}
+
+#[post("/vehicle/<user_id>/trash/<vehicle_id>/restore")]
+pub async fn restore_vehicle(trash_service: &State<Arc<TrashService>>, user_id: Uuid, vehicle_id: Uuid, actor: Actor) -> Result<Json<VehicleDTO>, Status> {
+    trash_service.restore_vehicle(user_id, vehicle_id, &actor.0).await
+        .map(Json)
+        .map_err(to_status)
+}
+
+fn to_status(error: TrashError) -> Status {
+    match error {
+        TrashError::VehicleNotFound => Status::NotFound,
+        TrashError::VehicleExists => Status::Conflict,
+        TrashError::StorageFailure => Status::ServiceUnavailable
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use rocket::http::Header;
+    use rocket::local::blocking::Client;
+
+    #[test]
+    fn when_deletes_vehicle_then_responds_no_content() {
+        let mut trash_service = TrashService::default();
+        trash_service.expect_delete_vehicle()
+            .withf(|_, vehicle_id: &Uuid, actor: &str| vehicle_id.to_string() == fixture::VEHICLE_ID_STR && actor == "jane")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let client = fixture::client(trash_service);
+
+        let response = client.delete(format!("/vehicle/{}/{}", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR))
+            .header(Header::new("X-Actor", "jane"))
+            .dispatch();
+
+        assert_eq!(Status::NoContent, response.status());
+    }
+
+    #[test]
+    fn when_gets_trash_then_responds_with_trashed_vehicles() {
+        let mut trash_service = TrashService::default();
+        trash_service.expect_get_trash()
+            .times(1)
+            .returning(|_| Some(vec!(fixture::trashed_vehicle_dto())));
+
+        let client = fixture::client(trash_service);
+
+        let response = client.get(format!("/vehicle/{}/trash", fixture::USER_ID_STR)).dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+        let trash = response.into_json::<Vec<TrashedVehicleDTO>>().unwrap();
+        assert_eq!(("jane", Utc.timestamp(3600, 0)), (trash[0].deleted_by.as_str(), trash[0].purge_at));
+    }
+
+    #[test]
+    fn given_vehicle_saved_again_when_restores_vehicle_then_responds_conflict() {
+        let mut trash_service = TrashService::default();
+        trash_service.expect_restore_vehicle()
+            .times(1)
+            .returning(|_, _, _| Err(TrashError::VehicleExists));
+
+        let client = fixture::client(trash_service);
+
+        let response = client.post(format!("/vehicle/{}/trash/{}/restore", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR)).dispatch();
+
+        assert_eq!(Status::Conflict, response.status());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+
+        pub fn trashed_vehicle_dto() -> TrashedVehicleDTO {
+            TrashedVehicleDTO {
+                vehicle: VehicleDTO {
+                    name: "the vehicle name".to_string(),
+                    user_id: Uuid::parse_str(USER_ID_STR).unwrap(),
+                    vehicle_id: Some(Uuid::parse_str(VEHICLE_ID_STR).unwrap()),
+                    created_at: Utc.timestamp(5, 0),
+                    vehicle_type: "bike".to_string(),
+                    retired_at: None,
+                    brand: "the brand".to_string(),
+                    model: "the model".to_string(),
+                    distance: 100,
+                    owner_since: NaiveDate::from_ymd(2015, 12, 2),
+                    manufacturing_date: NaiveDate::from_ymd(2015, 12, 2),
+                    picture: None
+                },
+                deleted_at: Utc.timestamp(5, 0),
+                deleted_by: "jane".to_string(),
+                purge_at: Utc.timestamp(3600, 0)
+            }
+        }
+
+        pub fn client(trash_service: TrashService) -> Client {
+            let rocket_build = rocket::build()
+                .manage(Arc::new(trash_service))
+                .mount("/", routes![delete_vehicle, get_trash, restore_vehicle]);
+
+            Client::untracked(rocket_build).expect("valid rocket instance")
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::io::Cursor;
+use std::sync::Arc;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Status};
+use rocket::{Request, Response};
+
+use crate::dao::circuit_breaker::{CircuitBreaker, CircuitState};
+
+const UNAVAILABLE_MESSAGE: &str = "Service temporarily unavailable, please retry later.";
+
+/// Turns internal errors into `503 Service Unavailable` with a `Retry-After` header while the Cassandra
+/// circuit breaker is not closed, queries being rejected before reaching the database.
+pub struct ServiceUnavailable {
+    circuit_breaker: Arc<CircuitBreaker>
+}
+
+impl ServiceUnavailable {
+    pub fn new(circuit_breaker: Arc<CircuitBreaker>) -> ServiceUnavailable {
+        ServiceUnavailable {
+            circuit_breaker
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for ServiceUnavailable {
+    fn info(&self) -> Info {
+        Info {
+            name: "Service unavailable while the circuit breaker is open",
+            kind: Kind::Response
+        }
+    }
+
+    async fn on_response<'r>(&self, _request: &'r Request<'_>, response: &mut Response<'r>) {
+        if response.status() != Status::InternalServerError || self.circuit_breaker.state() == CircuitState::Closed {
+            return;
+        }
+
+        response.set_status(Status::ServiceUnavailable);
+        response.set_header(Header::new("Retry-After", self.circuit_breaker.retry_after().to_string()));
+        response.set_sized_body(UNAVAILABLE_MESSAGE.len(), Cursor::new(UNAVAILABLE_MESSAGE));
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+
+    use crate::controller::health_controllers::tests::fixture::open_circuit_breaker;
+    use crate::dao::circuit_breaker::CircuitBreakerSettings;
+
+    #[test]
+    fn given_open_circuit_when_internal_error_then_responds_with_503() {
+        let rocket_build = rocket::build()
+            .attach(ServiceUnavailable::new(Arc::new(open_circuit_breaker())))
+            .mount("/", routes![fixture::failing]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/failing").dispatch();
+
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        assert_eq!(Some("60"), response.headers().get_one("Retry-After"));
+        assert_eq!(UNAVAILABLE_MESSAGE, response.into_string().unwrap());
+    }
+
+    #[test]
+    fn given_closed_circuit_when_internal_error_then_responds_with_500() {
+        let rocket_build = rocket::build()
+            .attach(ServiceUnavailable::new(Arc::new(CircuitBreaker::new(CircuitBreakerSettings::default()))))
+            .mount("/", routes![fixture::failing]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get("/failing").dispatch();
+
+        assert_eq!(response.status(), Status::InternalServerError);
+    }
+
+    mod fixture {
+        use rocket::http::Status;
+
+        #[get("/failing")]
+        pub async fn failing() -> Status {
+            Status::InternalServerError
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::fs::File;
+use std::sync::Arc;
+
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::Request;
+use rocket::response::{self, status, Responder, Response};
+use rocket::serde::json::Json;
+use rocket::serde::uuid::Uuid;
+use rocket::tokio::fs::File as AsyncFile;
+use rocket::State;
+use mockall_double::double;
+
+use crate::dto::user_data_dto::ErasureJobDTO;
+
+#[double]
+use crate::service::user_data_service::UserDataService;
+
+/// The data of a user as a ZIP archive, downloaded as `<user_id>.zip` and streamed from the file it was written to.
+pub struct UserArchive {
+    user_id: Uuid,
+    file: File
+}
+
+impl<'r> Responder<'r, 'static> for UserArchive {
+    fn respond_to(self, _request: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(ContentType::ZIP)
+            .header(Header::new("Content-Disposition", format!("attachment; filename=\"{}.zip\"", self.user_id)))
+            .sized_body(None, AsyncFile::from_std(self.file))
+            .ok()
+    }
+}
+
+/// Every vehicle, log, history and picture of the user, one JSON file per table.
+#[get("/user/<user_id>/export")]
+pub async fn export_user(user_data_service: &State<Arc<UserDataService>>, user_id: Uuid) -> Result<UserArchive, Status> {
+    user_data_service.export(user_id).await
+        .map(|file| UserArchive { user_id, file })
+        .ok_or(Status::ServiceUnavailable)
+}
+
+/// Starts erasing every partition and blob of the user, answering the job to poll until it is `completed`.
+#[delete("/user/<user_id>")]
+pub async fn erase_user(user_data_service: &State<Arc<UserDataService>>, user_id: Uuid) -> Result<status::Accepted<Json<ErasureJobDTO>>, Status> {
+    user_data_service.request_erasure(user_id).await
+        .map(|job| status::Accepted(Some(Json(job))))
+        .ok_or(Status::ServiceUnavailable)
+}
+
+#[get("/user/<user_id>/erasure/<job_id>")]
+pub async fn get_erasure(user_data_service: &State<Arc<UserDataService>>, user_id: Uuid, job_id: Uuid) -> Result<Json<ErasureJobDTO>, Status> {
+    user_data_service.get_erasure(user_id, job_id).await
+        .map(Json)
+        .ok_or(Status::NotFound)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use rocket::local::blocking::Client;
+    use std::fs::OpenOptions;
+    use std::io::{Seek, SeekFrom, Write};
+
+    use crate::domain::user_data::ERASURE_PENDING;
+
+    #[test]
+    fn when_gets_export_then_responds_with_zip_attachment() {
+        let mut user_data_service = UserDataService::default();
+        user_data_service.expect_export()
+            .withf(|user_id: &Uuid| user_id.to_string() == fixture::USER_ID_STR)
+            .times(1)
+            .returning(|_| Some(fixture::archive(b"the archive")));
+
+        let client = fixture::client(user_data_service);
+
+        let response = client.get(format!("/user/{}/export", fixture::USER_ID_STR)).dispatch();
+
+        assert_eq!(Status::Ok, response.status());
+        assert_eq!(Some(ContentType::ZIP), response.content_type());
+        assert_eq!(Some(format!("attachment; filename=\"{}.zip\"", fixture::USER_ID_STR).as_str()), response.headers().get_one("Content-Disposition"));
+        assert_eq!(Some(b"the archive".to_vec()), response.into_bytes());
+    }
+
+    #[test]
+    fn when_deletes_user_then_responds_accepted_with_pending_job() {
+        let mut user_data_service = UserDataService::default();
+        user_data_service.expect_request_erasure()
+            .times(1)
+            .returning(|user_id| Some(fixture::job(user_id)));
+
+        let client = fixture::client(user_data_service);
+
+        let response = client.delete(format!("/user/{}", fixture::USER_ID_STR)).dispatch();
+
+        assert_eq!(Status::Accepted, response.status());
+        assert_eq!(ERASURE_PENDING, response.into_json::<ErasureJobDTO>().unwrap().status);
+    }
+
+    #[test]
+    fn given_unknown_job_when_gets_erasure_then_responds_not_found() {
+        let mut user_data_service = UserDataService::default();
+        user_data_service.expect_get_erasure()
+            .times(1)
+            .returning(|_, _| None);
+
+        let client = fixture::client(user_data_service);
+
+        let response = client.get(format!("/user/{}/erasure/{}", fixture::USER_ID_STR, Uuid::new_v4())).dispatch();
+
+        assert_eq!(Status::NotFound, response.status());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "d13fe953-297a-4781-807a-f9becc1b71f6";
+
+        /// Unlinked file holding `data`, rewound to its start like the archives of the service.
+        pub fn archive(data: &[u8]) -> File {
+            let path = std::env::temp_dir().join(format!("archive-{}.zip", Uuid::new_v4()));
+            let mut file = OpenOptions::new().read(true).write(true).create_new(true).open(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            file.write_all(data).unwrap();
+            file.seek(SeekFrom::Start(0)).unwrap();
+            file
+        }
+
+        pub fn job(user_id: Uuid) -> ErasureJobDTO {
+            ErasureJobDTO {
+                job_id: Uuid::new_v4(),
+                user_id,
+                status: ERASURE_PENDING.to_string(),
+                erased: vec!(),
+                error: None,
+                requested_at: Utc.timestamp(5, 0),
+                updated_at: Utc.timestamp(5, 0)
+            }
+        }
+
+        pub fn client(user_data_service: UserDataService) -> Client {
+            let rocket_build = rocket::build()
+                .manage(Arc::new(user_data_service))
+                .mount("/", routes![export_user, erase_user, get_erasure]);
+
+            Client::untracked(rocket_build).expect("valid rocket instance")
+        }
+    }
+}
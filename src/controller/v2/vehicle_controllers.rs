@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use rocket::http::Status;
+use rocket::serde::json::{Json, Value};
+use rocket::serde::Deserialize;
+use rocket::serde::uuid::Uuid;
+use rocket::State;
+use mockall_double::double;
+
+use crate::controller::actor::Actor;
+use crate::controller::audit_fairing::AuditTrail;
+use crate::controller::controllers::{audit_vehicle, BATCH_ITEM_CREATED, BATCH_ITEM_FAILED, BATCH_ITEM_INVALID};
+use crate::controller::history_controllers::parse_time;
+use crate::controller::negotiation::Negotiated;
+use crate::controller::search_controllers::vehicle_fields;
+use crate::dto::v2::vehicle_dto::{NewVehicleDTO, VehicleBatchItemDTO, VehicleDTO};
+use crate::dto::vehicle_dto::VehicleProjectionDTO;
+use crate::mapper::v2::vehicle_mapper;
+
+#[double]
+use crate::service::vehicle_service::VehicleService;
+
+/// A vehicle holding only the comma separated `fields`, every field by default, as it was saved at `as_of`
+/// when given.
+#[get("/vehicle/<user_id>/<vehicle_id>?<fields>&<as_of>")]
+pub async fn get_vehicle(vehicle_service: &State<Arc<VehicleService>>, user_id: Uuid, vehicle_id: Uuid, fields: Option<&str>, as_of: Option<&str>) -> Result<Negotiated<VehicleProjectionDTO>, Status> {
+    let fields = vehicle_fields(fields)?;
+
+    let vehicle = match as_of {
+        Some(as_of) => vehicle_service.get_vehicle_as_of(user_id, vehicle_id, fields, parse_time(as_of)?).await,
+        None => vehicle_service.get_vehicle(user_id, vehicle_id, fields).await
+    };
+
+    vehicle
+        .map(Negotiated)
+        .ok_or(Status::NotFound)
+}
+
+/// Creates a vehicle, created now under an id assigned by the server.
+#[post("/vehicle", data = "<vehicle_body>")]
+pub async fn new_vehicle(vehicle_service: &State<Arc<VehicleService>>, vehicle_body: Negotiated<NewVehicleDTO>, actor: Actor, audit_trail: AuditTrail<'_>) -> Result<Negotiated<VehicleDTO>, Status> {
+    let vehicle_dto = vehicle_mapper::get_vehicle_to_save(vehicle_body.into_inner(), Utc::now());
+
+    vehicle_service.save_vehicle(vehicle_dto, &actor.0).await
+        .map(|vehicle_dto| {
+            audit_vehicle(&audit_trail, &vehicle_dto);
+            Negotiated(vehicle_mapper::get_vehicle_dto(vehicle_dto))
+        })
+        .ok_or(Status::ServiceUnavailable)
+}
+
+/// Creates many vehicles at once like `new_vehicle`, answering the outcome of each of them.
+#[post("/vehicle/batch", format = "application/json", data = "<vehicles_json>")]
+pub async fn new_vehicles(vehicle_service: &State<Arc<VehicleService>>, vehicles_json: Json<Vec<Value>>, actor: Actor, audit_trail: AuditTrail<'_>) -> Json<Vec<VehicleBatchItemDTO>> {
+    let created_at = Utc::now();
+    let mut items: Vec<VehicleBatchItemDTO> = Vec::new();
+    let mut valid = Vec::new();
+
+    for (index, value) in vehicles_json.into_inner().into_iter().enumerate() {
+        match NewVehicleDTO::deserialize(value) {
+            Ok(new_vehicle_dto) => valid.push((index, vehicle_mapper::get_vehicle_to_save(new_vehicle_dto, created_at))),
+            Err(e) => items.push(VehicleBatchItemDTO {
+                index,
+                status: BATCH_ITEM_INVALID.to_string(),
+                vehicle: None,
+                error: Some(e.to_string())
+            })
+        }
+    }
+
+    let (indexes, vehicle_dtos): (Vec<usize>, Vec<_>) = valid.into_iter().unzip();
+    let saved = vehicle_service.save_vehicles(vehicle_dtos, &actor.0).await;
+
+    for (index, vehicle) in indexes.into_iter().zip(saved) {
+        items.push(match vehicle {
+            Some(vehicle) => {
+                audit_vehicle(&audit_trail, &vehicle);
+                VehicleBatchItemDTO { index, status: BATCH_ITEM_CREATED.to_string(), vehicle: Some(vehicle_mapper::get_vehicle_dto(vehicle)), error: None }
+            },
+            None => VehicleBatchItemDTO { index, status: BATCH_ITEM_FAILED.to_string(), vehicle: None, error: Some("Failed to save vehicle".to_string()) }
+        });
+    }
+    items.sort_by_key(|item| item.index);
+
+    Json(items)
+}
@@ -0,0 +1,152 @@
+use std::sync::Arc;
+
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket::serde::uuid::Uuid;
+use mockall_double::double;
+
+use crate::controller::audit_fairing::AuditTrail;
+use crate::dto::webhook_dto::{WebhookDTO, WebhookDeliveryDTO, WebhookRequestDTO};
+use crate::service::webhook_service::WebhookError;
+
+#[double]
+use crate::service::webhook_service::WebhookService;
+
+const DEFAULT_DELIVERIES: usize = 20;
+
+/// Subscribes a partner URL to vehicle events of the user, answering the secret its deliveries are signed with.
+#[post("/webhook/<user_id>", format = "application/json", data = "<webhook_json>")]
+pub async fn new_webhook(webhook_service: &State<Arc<WebhookService>>, user_id: Uuid, webhook_json: Json<WebhookRequestDTO>, audit_trail: AuditTrail<'_>) -> Result<Json<WebhookDTO>, Status> {
+    webhook_service.create_webhook(user_id, webhook_json.into_inner()).await
+        .map(|webhook| {
+            audit_trail.target("webhook_id", webhook.webhook_id);
+            Json(webhook)
+        })
+        .map_err(to_status)
+}
+
+#[get("/webhook/<user_id>")]
+pub async fn get_webhooks(webhook_service: &State<Arc<WebhookService>>, user_id: Uuid) -> Result<Json<Vec<WebhookDTO>>, Status> {
+    webhook_service.get_webhooks(user_id).await
+        .map(Json)
+        .map_err(to_status)
+}
+
+#[delete("/webhook/<user_id>/<webhook_id>")]
+pub async fn delete_webhook(webhook_service: &State<Arc<WebhookService>>, user_id: Uuid, webhook_id: Uuid) -> Status {
+    match webhook_service.delete_webhook(user_id, webhook_id).await {
+        Ok(_) => Status::NoContent,
+        Err(e) => to_status(e)
+    }
+}
+
+/// Delivery log of a webhook, newest first, optionally only the deliveries with `status`
+/// (`pending`, `delivered` or `dead_lettered`).
+#[get("/webhook/<user_id>/<webhook_id>/deliveries?<status>&<limit>")]
+pub async fn get_deliveries(webhook_service: &State<Arc<WebhookService>>, user_id: Uuid, webhook_id: Uuid, status: Option<String>, limit: Option<usize>) -> Result<Json<Vec<WebhookDeliveryDTO>>, Status> {
+    webhook_service.get_deliveries(user_id, webhook_id, status, limit.unwrap_or(DEFAULT_DELIVERIES)).await
+        .map(Json)
+        .map_err(to_status)
+}
+
+fn to_status(error: WebhookError) -> Status {
+    match error {
+        WebhookError::WebhookNotFound => Status::NotFound,
+        WebhookError::InvalidWebhook => Status::UnprocessableEntity,
+        WebhookError::DeliveryDisabled => Status::ServiceUnavailable,
+        WebhookError::StorageFailure => Status::InternalServerError
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::http::ContentType;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn when_posts_webhook_then_responds_with_json_webhook_and_secret() {
+        let mut webhook_service = WebhookService::default();
+        webhook_service.expect_create_webhook()
+            .withf(|_, request: &WebhookRequestDTO| request.url == fixture::URL && request.events == Some(vec!("retired".to_string())))
+            .times(1)
+            .returning(move |_, request| Ok(WebhookDTO {
+                webhook_id: Uuid::new_v4(),
+                url: request.url,
+                events: request.events.unwrap(),
+                created_at: Utc.timestamp(5, 0),
+                secret: Some("the secret".to_string())
+            }));
+
+        let rocket_build = rocket::build().manage(Arc::new(webhook_service)).mount("/", routes![new_webhook]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post(format!("/webhook/{}", fixture::USER_ID_STR))
+            .header(ContentType::JSON)
+            .body(format!(r#"{{ "url": "{}", "events": ["retired"] }}"#, fixture::URL))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let json_response = response.into_json::<WebhookDTO>().unwrap();
+        assert_eq!(Some("the secret".to_string()), json_response.secret);
+    }
+
+    #[test]
+    fn given_unknown_event_when_posts_webhook_then_responds_with_422() {
+        let mut webhook_service = WebhookService::default();
+        webhook_service.expect_create_webhook()
+            .times(1)
+            .returning(move |_, _| Err(WebhookError::InvalidWebhook));
+
+        let rocket_build = rocket::build().manage(Arc::new(webhook_service)).mount("/", routes![new_webhook]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.post(format!("/webhook/{}", fixture::USER_ID_STR))
+            .header(ContentType::JSON)
+            .body(format!(r#"{{ "url": "{}", "events": ["repainted"] }}"#, fixture::URL))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+
+    #[test]
+    fn when_deletes_webhook_then_responds_with_204() {
+        let mut webhook_service = WebhookService::default();
+        webhook_service.expect_delete_webhook()
+            .withf(|_, webhook_id: &Uuid| webhook_id.to_string() == fixture::WEBHOOK_ID_STR)
+            .times(1)
+            .returning(move |_, _| Ok(()));
+
+        let rocket_build = rocket::build().manage(Arc::new(webhook_service)).mount("/", routes![delete_webhook]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.delete(format!("/webhook/{}/{}", fixture::USER_ID_STR, fixture::WEBHOOK_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::NoContent);
+    }
+
+    #[test]
+    fn given_status_when_gets_deliveries_then_passes_status_and_default_limit() {
+        let mut webhook_service = WebhookService::default();
+        webhook_service.expect_get_deliveries()
+            .withf(|_, _, status: &Option<String>, limit: &usize| status.as_deref() == Some("dead_lettered") && *limit == DEFAULT_DELIVERIES)
+            .times(1)
+            .returning(move |_, _, _, _| Ok(vec!()));
+
+        let rocket_build = rocket::build().manage(Arc::new(webhook_service)).mount("/", routes![get_deliveries]);
+        let client = Client::untracked(rocket_build).expect("valid rocket instance");
+
+        let response = client.get(format!("/webhook/{}/{}/deliveries?status=dead_lettered", fixture::USER_ID_STR, fixture::WEBHOOK_ID_STR)).dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(0, response.into_json::<Vec<WebhookDeliveryDTO>>().unwrap().len());
+    }
+
+    mod fixture {
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const WEBHOOK_ID_STR: &str = "5f0e3c2a-8d1b-4c6e-9a7f-2b3c4d5e6f70";
+        pub const URL: &str = "https://partner.example/hooks";
+    }
+}
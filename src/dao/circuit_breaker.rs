@@ -0,0 +1,386 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rocket::serde::Deserialize;
+
+use crate::dao::session_manager::SessionManager;
+
+/// Lightest query used to find out whether Cassandra answers again.
+const PROBE_QUERY: &str = "SELECT release_version FROM system.local";
+const PROBE_OPERATION: &str = "probe";
+
+/// Circuit breaker settings read from the `cassandra.circuit_breaker` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct CircuitBreakerSettings {
+    pub window_size             : usize,
+    pub minimum_calls           : usize,
+    pub failure_rate_threshold  : f64,
+    pub slow_call_ms            : u64,
+    pub slow_call_rate_threshold: f64,
+    pub open_duration_ms        : u64,
+    pub half_open_calls         : usize
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        CircuitBreakerSettings {
+            window_size: 20,
+            minimum_calls: 10,
+            failure_rate_threshold: 0.5,
+            slow_call_ms: 2000,
+            slow_call_rate_threshold: 0.8,
+            open_duration_ms: 5000,
+            half_open_calls: 3
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen
+}
+
+impl CircuitState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open"
+        }
+    }
+}
+
+/// Counters exposed on the metrics endpoint.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CircuitMetrics {
+    pub calls                   : u64,
+    pub failures                : u64,
+    pub slow_calls              : u64,
+    pub rejected_calls          : u64,
+    pub times_opened            : u64
+}
+
+struct Call {
+    failed: bool,
+    slow: bool
+}
+
+struct Inner {
+    state: CircuitState,
+    window: VecDeque<Call>,
+    opened_at: Option<Instant>,
+    half_open_permits: usize,
+    half_open_successes: usize,
+    /// Counts the half-open periods, so a trial permit outliving its period is not handed back to the next one.
+    half_open_period: u64,
+    metrics: CircuitMetrics
+}
+
+/// Leave to send a single call, to be recorded with its outcome. A permit dropped without being recorded,
+/// because its call was abandoned or cut by the request deadline, hands a half-open trial back without
+/// counting it as a success.
+pub struct CallPermit<'a> {
+    circuit_breaker: &'a CircuitBreaker,
+    /// Half-open period the permit is a trial call of.
+    trial_of: Option<u64>,
+    recorded: bool
+}
+
+impl<'a> CallPermit<'a> {
+    pub fn record(mut self, failed: bool, latency: Duration) {
+        self.recorded = true;
+        self.circuit_breaker.record(self.trial_of, failed, latency);
+    }
+}
+
+impl<'a> Drop for CallPermit<'a> {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.circuit_breaker.release(self.trial_of);
+        }
+    }
+}
+
+/// Stops sending statements to Cassandra once too many of the last calls failed or were slow,
+/// then lets a few trial calls through after `open_duration_ms` to decide whether to close again.
+pub struct CircuitBreaker {
+    settings: CircuitBreakerSettings,
+    inner: Mutex<Inner>
+}
+
+impl CircuitBreaker {
+    pub fn new(settings: CircuitBreakerSettings) -> CircuitBreaker {
+        CircuitBreaker {
+            settings,
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                window: VecDeque::new(),
+                opened_at: None,
+                half_open_permits: 0,
+                half_open_successes: 0,
+                half_open_period: 0,
+                metrics: CircuitMetrics::default()
+            })
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.inner.lock().unwrap().state
+    }
+
+    pub fn metrics(&self) -> CircuitMetrics {
+        self.inner.lock().unwrap().metrics.clone()
+    }
+
+    /// Seconds a client should wait before trying again while the circuit is open.
+    pub fn retry_after(&self) -> u64 {
+        (self.settings.open_duration_ms + 999) / 1000
+    }
+
+    /// A permit to send a call to Cassandra, `None` while the circuit rejects calls.
+    pub fn try_acquire(&self) -> Option<CallPermit<'_>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.state == CircuitState::Open {
+            let open_duration = Duration::from_millis(self.settings.open_duration_ms);
+            if inner.opened_at.map_or(true, |opened_at| opened_at.elapsed() >= open_duration) {
+                inner.state = CircuitState::HalfOpen;
+                inner.half_open_permits = self.settings.half_open_calls;
+                inner.half_open_successes = 0;
+                inner.half_open_period += 1;
+            }
+        }
+
+        let trial_of = match inner.state {
+            CircuitState::Closed => Some(None),
+            CircuitState::Open => None,
+            CircuitState::HalfOpen if inner.half_open_permits > 0 => {
+                inner.half_open_permits -= 1;
+                Some(Some(inner.half_open_period))
+            },
+            CircuitState::HalfOpen => None
+        };
+
+        if trial_of.is_none() {
+            inner.metrics.rejected_calls += 1;
+        }
+
+        trial_of.map(|trial_of| CallPermit { circuit_breaker: self, trial_of, recorded: false })
+    }
+
+    fn record(&self, trial_of: Option<u64>, failed: bool, latency: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        let slow = latency >= Duration::from_millis(self.settings.slow_call_ms);
+
+        inner.metrics.calls += 1;
+        if failed { inner.metrics.failures += 1; }
+        if slow { inner.metrics.slow_calls += 1; }
+
+        let current_trial = inner.state == CircuitState::HalfOpen && trial_of == Some(inner.half_open_period);
+
+        match inner.state {
+            CircuitState::Closed => {
+                inner.window.push_back(Call { failed, slow });
+                while inner.window.len() > self.settings.window_size {
+                    inner.window.pop_front();
+                }
+                if self.should_open(&inner.window) {
+                    self.open(&mut inner);
+                }
+            },
+            // Calls let through before the circuit opened say nothing about whether it may close or reopen.
+            CircuitState::HalfOpen if !current_trial => {},
+            CircuitState::HalfOpen if failed || slow => self.open(&mut inner),
+            CircuitState::HalfOpen => {
+                inner.half_open_successes += 1;
+                if inner.half_open_successes >= self.settings.half_open_calls {
+                    inner.state = CircuitState::Closed;
+                    inner.window.clear();
+                    inner.opened_at = None;
+                }
+            },
+            CircuitState::Open => {}
+        }
+    }
+
+    /// Hands back the trial of a permit dropped without an outcome, so the half-open circuit can still close.
+    fn release(&self, trial_of: Option<u64>) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.state == CircuitState::HalfOpen && trial_of == Some(inner.half_open_period) {
+            inner.half_open_permits += 1;
+        }
+    }
+
+    fn should_open(&self, window: &VecDeque<Call>) -> bool {
+        if window.len() < self.settings.minimum_calls.max(1) {
+            return false;
+        }
+
+        let calls = window.len() as f64;
+        let failure_rate = window.iter().filter(|call| call.failed).count() as f64 / calls;
+        let slow_call_rate = window.iter().filter(|call| call.slow).count() as f64 / calls;
+
+        failure_rate >= self.settings.failure_rate_threshold || slow_call_rate >= self.settings.slow_call_rate_threshold
+    }
+
+    fn open(&self, inner: &mut Inner) {
+        inner.state = CircuitState::Open;
+        inner.opened_at = Some(Instant::now());
+        inner.window.clear();
+        inner.metrics.times_opened += 1;
+    }
+}
+
+/// Sends a probe query every `interval` while the circuit is not closed, so it can close again without
+/// waiting for client traffic.
+pub async fn probe_periodically(session_manager: Arc<dyn SessionManager + Sync + Send>, circuit_breaker: Arc<CircuitBreaker>, interval: Duration) {
+    loop {
+        rocket::tokio::time::sleep(interval).await;
+
+        if circuit_breaker.state() != CircuitState::Closed {
+            if let Err(e) = session_manager.execute_query(PROBE_OPERATION, PROBE_QUERY).await {
+                println!("Cassandra probe failed with error {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn settings() -> CircuitBreakerSettings {
+        CircuitBreakerSettings {
+            window_size: 4,
+            minimum_calls: 4,
+            failure_rate_threshold: 0.5,
+            slow_call_ms: 100,
+            slow_call_rate_threshold: 1.0,
+            open_duration_ms: 0,
+            half_open_calls: 2
+        }
+    }
+
+    #[test]
+    fn given_failure_rate_below_threshold_when_record_then_stays_closed() {
+        let circuit_breaker = CircuitBreaker::new(settings());
+
+        circuit_breaker.try_acquire().unwrap().record(true, Duration::from_millis(1));
+        for _ in 0..3 {
+            circuit_breaker.try_acquire().unwrap().record(false, Duration::from_millis(1));
+        }
+
+        assert_eq!(CircuitState::Closed, circuit_breaker.state());
+        assert!(circuit_breaker.try_acquire().is_some());
+    }
+
+    #[test]
+    fn given_failure_rate_reaching_threshold_when_record_then_opens() {
+        let circuit_breaker = CircuitBreaker::new(CircuitBreakerSettings { open_duration_ms: 60_000, ..settings() });
+
+        for failed in &[true, false, true, false] {
+            circuit_breaker.try_acquire().unwrap().record(*failed, Duration::from_millis(1));
+        }
+
+        assert_eq!(CircuitState::Open, circuit_breaker.state());
+        assert!(circuit_breaker.try_acquire().is_none());
+        assert_eq!(1, circuit_breaker.metrics().rejected_calls);
+        assert_eq!(1, circuit_breaker.metrics().times_opened);
+    }
+
+    #[test]
+    fn given_only_slow_calls_when_record_then_opens() {
+        let circuit_breaker = CircuitBreaker::new(CircuitBreakerSettings { open_duration_ms: 60_000, ..settings() });
+
+        for _ in 0..4 {
+            circuit_breaker.try_acquire().unwrap().record(false, Duration::from_millis(150));
+        }
+
+        assert_eq!(CircuitState::Open, circuit_breaker.state());
+        assert_eq!(4, circuit_breaker.metrics().slow_calls);
+    }
+
+    #[test]
+    fn given_open_duration_elapsed_when_try_acquire_then_lets_limited_trial_calls_through() {
+        let circuit_breaker = CircuitBreaker::new(settings());
+        for _ in 0..4 {
+            circuit_breaker.try_acquire().unwrap().record(true, Duration::from_millis(1));
+        }
+
+        let first = circuit_breaker.try_acquire();
+        assert!(first.is_some());
+        assert_eq!(CircuitState::HalfOpen, circuit_breaker.state());
+        let second = circuit_breaker.try_acquire();
+        assert!(second.is_some());
+        assert!(circuit_breaker.try_acquire().is_none());
+    }
+
+    #[test]
+    fn given_trial_call_dropped_without_outcome_when_try_acquire_then_trial_is_handed_back_and_not_counted() {
+        let circuit_breaker = CircuitBreaker::new(settings());
+        for _ in 0..4 {
+            circuit_breaker.try_acquire().unwrap().record(true, Duration::from_millis(1));
+        }
+
+        let abandoned = circuit_breaker.try_acquire();
+        let recorded = circuit_breaker.try_acquire();
+        assert!(circuit_breaker.try_acquire().is_none());
+
+        drop(abandoned);
+        recorded.unwrap().record(false, Duration::from_millis(1));
+        assert_eq!(CircuitState::HalfOpen, circuit_breaker.state());
+
+        circuit_breaker.try_acquire().unwrap().record(false, Duration::from_millis(1));
+        assert_eq!(CircuitState::Closed, circuit_breaker.state());
+    }
+
+    #[test]
+    fn given_successful_trial_calls_when_record_then_closes() {
+        let circuit_breaker = CircuitBreaker::new(settings());
+        for _ in 0..4 {
+            circuit_breaker.try_acquire().unwrap().record(true, Duration::from_millis(1));
+        }
+
+        for _ in 0..2 {
+            circuit_breaker.try_acquire().unwrap().record(false, Duration::from_millis(1));
+        }
+
+        assert_eq!(CircuitState::Closed, circuit_breaker.state());
+    }
+
+    #[test]
+    fn given_failed_trial_call_when_record_then_opens_again() {
+        let circuit_breaker = CircuitBreaker::new(settings());
+        for _ in 0..4 {
+            circuit_breaker.try_acquire().unwrap().record(true, Duration::from_millis(1));
+        }
+
+        circuit_breaker.try_acquire().unwrap().record(true, Duration::from_millis(1));
+
+        assert_eq!(CircuitState::Open, circuit_breaker.state());
+        assert_eq!(2, circuit_breaker.metrics().times_opened);
+    }
+
+    #[test]
+    fn given_call_admitted_before_opening_fails_when_half_open_then_stays_half_open() {
+        let circuit_breaker = CircuitBreaker::new(settings());
+        let stale = circuit_breaker.try_acquire().unwrap();
+        for _ in 0..4 {
+            circuit_breaker.try_acquire().unwrap().record(true, Duration::from_millis(1));
+        }
+
+        let trial = circuit_breaker.try_acquire().unwrap();
+        stale.record(true, Duration::from_millis(150));
+
+        assert_eq!(CircuitState::HalfOpen, circuit_breaker.state());
+        assert_eq!(1, circuit_breaker.metrics().times_opened);
+        trial.record(false, Duration::from_millis(1));
+        circuit_breaker.try_acquire().unwrap().record(false, Duration::from_millis(1));
+        assert_eq!(CircuitState::Closed, circuit_breaker.state());
+    }
+}
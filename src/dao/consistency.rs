@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use rocket::serde::Deserialize;
+use scylla::statement::{Consistency, SerialConsistency};
+
+use crate::dao::session_manager::Statement;
+
+rocket::tokio::task_local! {
+    static OVERRIDE: Consistency;
+}
+
+/// Consistency settings read from the `cassandra.consistency` section of `Rocket.toml`, `operations` and
+/// `serial_operations` overriding the defaults by repository operation name.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ConsistencySettings {
+    pub default             : String,
+    pub serial              : String,
+    pub operations          : HashMap<String, String>,
+    pub serial_operations   : HashMap<String, String>
+}
+
+impl Default for ConsistencySettings {
+    fn default() -> Self {
+        ConsistencySettings {
+            default: "local_quorum".to_string(),
+            serial: "local_serial".to_string(),
+            operations: HashMap::new(),
+            serial_operations: HashMap::new()
+        }
+    }
+}
+
+pub fn parse_consistency(level: &str) -> Option<Consistency> {
+    match level.trim().to_lowercase().as_str() {
+        "any" => Some(Consistency::Any),
+        "one" => Some(Consistency::One),
+        "two" => Some(Consistency::Two),
+        "three" => Some(Consistency::Three),
+        "quorum" => Some(Consistency::Quorum),
+        "all" => Some(Consistency::All),
+        "local_quorum" => Some(Consistency::LocalQuorum),
+        "each_quorum" => Some(Consistency::EachQuorum),
+        "local_one" => Some(Consistency::LocalOne),
+        _ => None
+    }
+}
+
+pub fn parse_serial_consistency(level: &str) -> Option<SerialConsistency> {
+    match level.trim().to_lowercase().as_str() {
+        "serial" => Some(SerialConsistency::Serial),
+        "local_serial" => Some(SerialConsistency::LocalSerial),
+        _ => None
+    }
+}
+
+/// Runs `future` with every statement it executes forced to `consistency`.
+pub async fn scope<F: Future>(consistency: Consistency, future: F) -> F::Output {
+    OVERRIDE.scope(consistency, future).await
+}
+
+/// Consistency forced on the current request, `None` outside of an overridden request.
+pub fn overridden() -> Option<Consistency> {
+    OVERRIDE.try_with(|consistency| *consistency).ok()
+}
+
+/// Resolves the consistency of a statement: the request override first, then the level set on the
+/// statement, then the default of its operation and finally the global default.
+#[derive(Debug, Clone)]
+pub struct ConsistencyPolicy {
+    default: Consistency,
+    serial: SerialConsistency,
+    operations: HashMap<String, Consistency>,
+    serial_operations: HashMap<String, SerialConsistency>
+}
+
+impl Default for ConsistencyPolicy {
+    fn default() -> Self {
+        ConsistencyPolicy::new(ConsistencySettings::default()).unwrap()
+    }
+}
+
+impl ConsistencyPolicy {
+    pub fn new(settings: ConsistencySettings) -> Result<ConsistencyPolicy, String> {
+        let default = parse_consistency(&settings.default)
+            .ok_or_else(|| format!("Unknown consistency level {}", settings.default))?;
+        let serial = parse_serial_consistency(&settings.serial)
+            .ok_or_else(|| format!("Unknown serial consistency level {}", settings.serial))?;
+
+        let mut operations = HashMap::new();
+        for (operation, level) in settings.operations {
+            let consistency = parse_consistency(&level)
+                .ok_or_else(|| format!("Unknown consistency level {} for {}", level, operation))?;
+            operations.insert(operation, consistency);
+        }
+
+        let mut serial_operations = HashMap::new();
+        for (operation, level) in settings.serial_operations {
+            let serial_consistency = parse_serial_consistency(&level)
+                .ok_or_else(|| format!("Unknown serial consistency level {} for {}", level, operation))?;
+            serial_operations.insert(operation, serial_consistency);
+        }
+
+        Ok(ConsistencyPolicy {
+            default,
+            serial,
+            operations,
+            serial_operations
+        })
+    }
+
+    pub fn consistency(&self, statement: &Statement) -> Consistency {
+        overridden()
+            .or(statement.consistency)
+            .or_else(|| statement.operation.as_ref().and_then(|operation| self.operations.get(operation)).copied())
+            .unwrap_or(self.default)
+    }
+
+    pub fn serial_consistency(&self, statement: &Statement) -> SerialConsistency {
+        statement.serial_consistency
+            .or_else(|| statement.operation.as_ref().and_then(|operation| self.serial_operations.get(operation)).copied())
+            .unwrap_or(self.serial)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    fn policy() -> ConsistencyPolicy {
+        ConsistencyPolicy::new(ConsistencySettings {
+            operations: vec!(("get_vehicle".to_string(), "one".to_string())).into_iter().collect(),
+            serial_operations: vec!(("update_offer_status".to_string(), "serial".to_string())).into_iter().collect(),
+            ..ConsistencySettings::default()
+        }).unwrap()
+    }
+
+    #[test]
+    fn given_unknown_level_when_new_then_returns_error() {
+        let settings = ConsistencySettings { default: "most".to_string(), ..ConsistencySettings::default() };
+
+        assert!(ConsistencyPolicy::new(settings).is_err());
+    }
+
+    #[test]
+    fn given_unconfigured_operation_when_consistency_then_returns_default() {
+        let statement = Statement::idempotent("SELECT").for_operation("get_components");
+
+        assert_eq!(Consistency::LocalQuorum, policy().consistency(&statement));
+        assert_eq!(SerialConsistency::LocalSerial, policy().serial_consistency(&statement));
+    }
+
+    #[test]
+    fn given_configured_operation_when_consistency_then_returns_operation_default() {
+        assert_eq!(Consistency::One, policy().consistency(&Statement::idempotent("SELECT").for_operation("get_vehicle")));
+        assert_eq!(SerialConsistency::Serial, policy().serial_consistency(&Statement::non_idempotent("UPDATE").for_operation("update_offer_status")));
+    }
+
+    #[test]
+    fn given_statement_level_when_consistency_then_wins_over_operation_default() {
+        let statement = Statement::idempotent("SELECT").for_operation("get_vehicle").with_consistency(Consistency::All);
+
+        assert_eq!(Consistency::All, policy().consistency(&statement));
+    }
+
+    #[test]
+    fn given_request_override_when_consistency_then_wins_over_statement_level() {
+        let statement = Statement::idempotent("SELECT").for_operation("get_vehicle").with_consistency(Consistency::All);
+
+        let consistency = aw!(scope(Consistency::Quorum, async { policy().consistency(&statement) }));
+
+        assert_eq!(Consistency::Quorum, consistency);
+    }
+
+    #[test]
+    fn when_parse_consistency_then_ignores_case() {
+        assert_eq!(Some(Consistency::LocalQuorum), parse_consistency("LOCAL_QUORUM"));
+        assert_eq!(None, parse_consistency("serial"));
+        assert_eq!(Some(SerialConsistency::Serial), parse_serial_consistency("Serial"));
+    }
+}
@@ -0,0 +1,47 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+rocket::tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// Runs `future` with `deadline` visible to every statement it executes.
+pub async fn scope<F: Future>(deadline: Instant, future: F) -> F::Output {
+    DEADLINE.scope(deadline, future).await
+}
+
+/// Time left before the deadline of the current request, `None` outside of a request.
+pub fn remaining() -> Option<Duration> {
+    DEADLINE.try_with(|deadline| deadline.saturating_duration_since(Instant::now())).ok()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn given_no_deadline_when_remaining_then_returns_none() {
+        assert_eq!(None, remaining());
+    }
+
+    #[test]
+    fn given_deadline_when_remaining_then_returns_time_left() {
+        let remaining = aw!(scope(Instant::now() + Duration::from_secs(60), async { remaining() })).unwrap();
+
+        assert!(remaining > Duration::from_secs(59));
+        assert!(remaining <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn given_past_deadline_when_remaining_then_returns_zero() {
+        let remaining = aw!(scope(Instant::now() - Duration::from_millis(1), async { remaining() }));
+
+        assert_eq!(Some(Duration::from_secs(0)), remaining);
+    }
+}
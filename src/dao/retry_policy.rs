@@ -0,0 +1,130 @@
+use std::cmp::min;
+use std::time::Duration;
+
+use rocket::serde::Deserialize;
+use scylla::transport::errors::{DbError, QueryError};
+use tokio_retry::strategy::jitter;
+
+/// Retry settings read from the `cassandra.retry` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct RetrySettings {
+    pub base_delay_ms       : u64,
+    pub max_delay_ms        : u64,
+    pub max_attempts        : u32,
+    pub jitter              : bool
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        RetrySettings {
+            base_delay_ms: 10,
+            max_delay_ms: 1000,
+            max_attempts: 4,
+            jitter: true
+        }
+    }
+}
+
+/// Decides which failed statements are attempted again and how long to wait in between.
+#[derive(Debug, Clone, Default)]
+pub struct RetryPolicy {
+    settings: RetrySettings
+}
+
+impl RetryPolicy {
+    pub fn new(settings: RetrySettings) -> RetryPolicy {
+        RetryPolicy {
+            settings
+        }
+    }
+
+    /// Delays before each retry, doubling from the base delay up to the max delay.
+    pub fn delays(&self) -> impl Iterator<Item = Duration> {
+        let base_delay_ms = self.settings.base_delay_ms;
+        let max_delay_ms = self.settings.max_delay_ms;
+        let with_jitter = self.settings.jitter;
+
+        (0..self.settings.max_attempts.saturating_sub(1))
+            .map(move |retry| {
+                let delay = Duration::from_millis(min(base_delay_ms.saturating_mul(1 << min(retry, 32)), max_delay_ms));
+                if with_jitter { jitter(delay) } else { delay }
+            })
+    }
+
+    /// Errors telling Cassandra is unreachable or struggling, as opposed to errors in the statement itself.
+    pub fn is_transient(&self, error: &QueryError) -> bool {
+        self.is_retryable(error, true)
+    }
+
+    /// Errors raised before the coordinator executed anything are always retryable,
+    /// timeouts and broken connections only when replaying the statement is harmless.
+    pub fn is_retryable(&self, error: &QueryError, idempotent: bool) -> bool {
+        match error {
+            QueryError::DbError(DbError::Unavailable { .. }, _)
+            | QueryError::DbError(DbError::Overloaded, _)
+            | QueryError::DbError(DbError::IsBootstrapping, _) => true,
+            QueryError::DbError(DbError::ReadTimeout { .. }, _)
+            | QueryError::DbError(DbError::WriteTimeout { .. }, _)
+            | QueryError::TimeoutError
+            | QueryError::IoError(_) => idempotent,
+            _ => false
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn given_default_settings_when_delays_then_returns_3_delays() {
+        let retry_policy = RetryPolicy::default();
+
+        assert_eq!(3, retry_policy.delays().count());
+    }
+
+    #[test]
+    fn given_no_jitter_when_delays_then_doubles_up_to_max_delay() {
+        let retry_policy = RetryPolicy::new(RetrySettings {
+            base_delay_ms: 100,
+            max_delay_ms: 300,
+            max_attempts: 5,
+            jitter: false
+        });
+
+        let delays: Vec<Duration> = retry_policy.delays().collect();
+
+        assert_eq!(vec!(Duration::from_millis(100), Duration::from_millis(200), Duration::from_millis(300), Duration::from_millis(300)), delays);
+    }
+
+    #[test]
+    fn given_single_attempt_when_delays_then_returns_no_delay() {
+        let retry_policy = RetryPolicy::new(RetrySettings { max_attempts: 1, ..RetrySettings::default() });
+
+        assert_eq!(0, retry_policy.delays().count());
+    }
+
+    #[test]
+    fn given_overloaded_error_when_is_retryable_then_returns_true_even_if_not_idempotent() {
+        let error = QueryError::DbError(DbError::Overloaded, "overloaded".to_owned());
+
+        assert!(RetryPolicy::default().is_retryable(&error, false));
+    }
+
+    #[test]
+    fn given_timeout_error_when_is_retryable_then_depends_on_idempotency() {
+        let retry_policy = RetryPolicy::default();
+
+        assert!(retry_policy.is_retryable(&QueryError::TimeoutError, true));
+        assert!(!retry_policy.is_retryable(&QueryError::TimeoutError, false));
+    }
+
+    #[test]
+    fn given_syntax_error_when_is_retryable_then_returns_false() {
+        let error = QueryError::DbError(DbError::SyntaxError, "line 1:0 no viable alternative".to_owned());
+
+        assert!(!RetryPolicy::default().is_retryable(&error, true));
+        assert!(!RetryPolicy::default().is_retryable(&QueryError::InvalidMessage("error".to_owned()), true));
+    }
+}
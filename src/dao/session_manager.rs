@@ -1,25 +1,205 @@
 use scylla::transport::errors::QueryError;
 use scylla::QueryResult;
 use scylla::query::Query;
+use scylla::batch::{Batch, BatchType};
 
 use cfg_if::cfg_if;
 
 use async_trait::async_trait;
 
-use tokio_retry::Retry;
-use tokio_retry::strategy::{ExponentialBackoff, jitter};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rocket::serde::Deserialize;
+use rocket::tokio::time::timeout;
+use tokio_retry::RetryIf;
+
+use crate::dao::retry_policy::RetryPolicy;
+use crate::dao::circuit_breaker::CircuitBreaker;
+use crate::dao::deadline;
+use crate::dao::consistency::ConsistencyPolicy;
+use scylla::statement::{Consistency, SerialConsistency};
+
+/// Message of the error returned without reaching Cassandra while the circuit breaker is open.
+pub const CIRCUIT_OPEN: &str = "Circuit breaker is open";
+
+/// Statement settings read from the `cassandra.statement` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct StatementSettings {
+    pub timeout_ms          : u64
+}
+
+impl Default for StatementSettings {
+    fn default() -> Self {
+        StatementSettings {
+            timeout_ms: 2000
+        }
+    }
+}
+
+/// A CQL statement along with whether it can safely be executed more than once. Each attempt is bounded
+/// by `timeout`, or the configured statement timeout when unset, and by the deadline of the current request.
+/// `operation` names the repository operation whose configured consistency applies when none is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub query_statement     : String,
+    pub idempotent          : bool,
+    pub timeout             : Option<Duration>,
+    pub operation           : Option<String>,
+    pub consistency         : Option<Consistency>,
+    pub serial_consistency  : Option<SerialConsistency>
+}
+
+impl Statement {
+    pub fn idempotent(query_statement: &str) -> Statement {
+        Statement {
+            query_statement: query_statement.to_owned(),
+            idempotent: true,
+            timeout: None,
+            operation: None,
+            consistency: None,
+            serial_consistency: None
+        }
+    }
+
+    /// Lightweight transactions and counter updates must not be replayed after a timeout.
+    pub fn non_idempotent(query_statement: &str) -> Statement {
+        Statement {
+            query_statement: query_statement.to_owned(),
+            idempotent: false,
+            timeout: None,
+            operation: None,
+            consistency: None,
+            serial_consistency: None
+        }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Statement {
+        Statement {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    pub fn for_operation(self, operation: &str) -> Statement {
+        Statement {
+            operation: Some(operation.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn with_consistency(self, consistency: Consistency) -> Statement {
+        Statement {
+            consistency: Some(consistency),
+            ..self
+        }
+    }
+
+    pub fn with_serial_consistency(self, serial_consistency: SerialConsistency) -> Statement {
+        Statement {
+            serial_consistency: Some(serial_consistency),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BatchMode {
+    Logged,
+    Unlogged
+}
+
+impl BatchMode {
+    fn batch_type(&self) -> BatchType {
+        match self {
+            BatchMode::Logged => BatchType::Logged,
+            BatchMode::Unlogged => BatchType::Unlogged
+        }
+    }
+}
+
+/// Statements applied together, either as a logged batch, atomic across partitions, or as an unlogged
+/// batch, meant for several rows of the same partition sent in a single round trip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchStatement {
+    pub mode                : BatchMode,
+    pub statements          : Vec<String>,
+    pub idempotent          : bool,
+    pub operation           : Option<String>
+}
+
+impl BatchStatement {
+    pub fn logged(statements: Vec<String>) -> BatchStatement {
+        BatchStatement {
+            mode: BatchMode::Logged,
+            statements,
+            idempotent: true,
+            operation: None
+        }
+    }
+
+    pub fn unlogged(statements: Vec<String>) -> BatchStatement {
+        BatchStatement {
+            mode: BatchMode::Unlogged,
+            statements,
+            idempotent: true,
+            operation: None
+        }
+    }
+
+    /// Batches containing lightweight transactions or counter updates must not be replayed after a timeout.
+    pub fn non_idempotent(self) -> BatchStatement {
+        BatchStatement {
+            idempotent: false,
+            ..self
+        }
+    }
+
+    pub fn for_operation(self, operation: &str) -> BatchStatement {
+        BatchStatement {
+            operation: Some(operation.to_owned()),
+            ..self
+        }
+    }
+
+    /// Statement standing for the whole batch when resolving its consistency.
+    fn as_statement(&self) -> Statement {
+        Statement {
+            operation: self.operation.clone(),
+            ..Statement::idempotent("")
+        }
+    }
+}
+
+/// Result of a statement along with the number of retries it took.
+#[derive(Debug)]
+pub struct QueryOutcome {
+    pub result              : Result<QueryResult, QueryError>,
+    pub retries             : u32
+}
 
 #[async_trait]
 pub trait SessionManager {
-    async fn execute_query(&self, query_statement: &str) -> Result<QueryResult, QueryError>;
+    /// Executes an idempotent statement on behalf of the repository `operation`.
+    async fn execute_query(&self, operation: &str, query_statement: &str) -> Result<QueryResult, QueryError>;
+    async fn execute_statement(&self, statement: Statement) -> QueryOutcome;
+    /// Executes a batch under the same retry, circuit breaker and deadline rules as a single statement.
+    async fn execute_batch(&self, batch: BatchStatement) -> QueryOutcome;
 }
 
 pub struct SessionManagerImpl {
-    session: Session
+    session: Session,
+    retry_policy: RetryPolicy,
+    circuit_breaker: Arc<CircuitBreaker>,
+    statement_timeout: Duration,
+    consistency_policy: ConsistencyPolicy
 }
 
 impl SessionManagerImpl {
-    pub async fn new(_node: &str) -> SessionManagerImpl {
+    pub async fn new(_node: &str, retry_policy: RetryPolicy, circuit_breaker: Arc<CircuitBreaker>,
+                     statement_settings: StatementSettings, consistency_policy: ConsistencyPolicy) -> SessionManagerImpl {
         cfg_if! {
             if #[cfg(test)] {
                 let session = tests::MockSession::new();
@@ -33,24 +213,102 @@ impl SessionManagerImpl {
         }
 
         SessionManagerImpl {
-            session
+            session,
+            retry_policy,
+            circuit_breaker,
+            statement_timeout: Duration::from_millis(statement_settings.timeout_ms),
+            consistency_policy
         }
     }
 }
 
 #[async_trait]
 impl SessionManager for SessionManagerImpl {
-    async fn execute_query(&self, query_statement: &str) -> Result<QueryResult, QueryError> {
-        let retry_strategy = ExponentialBackoff::from_millis(10)
-            .map(jitter) // add jitter to delays
-            .take(3);    // limit to 3 retries
+    async fn execute_query(&self, operation: &str, query_statement: &str) -> Result<QueryResult, QueryError> {
+        self.execute_statement(Statement::idempotent(query_statement).for_operation(operation)).await.result
+    }
 
-        let result = Retry::spawn(retry_strategy, || {
-            let query: Query = Query::new(query_statement.to_owned());
+    async fn execute_statement(&self, statement: Statement) -> QueryOutcome {
+        let statement_timeout = statement.timeout.unwrap_or(self.statement_timeout);
+        let consistency = self.consistency_policy.consistency(&statement);
+        let serial_consistency = self.consistency_policy.serial_consistency(&statement);
+
+        self.execute_with_retries(statement.idempotent, statement_timeout, || {
+            let mut query: Query = Query::new(statement.query_statement.to_owned());
+            query.set_consistency(consistency);
+            query.set_serial_consistency(Some(serial_consistency));
             self.session.query(query, ())
-        }).await;
+        }).await
+    }
+
+    async fn execute_batch(&self, batch_statement: BatchStatement) -> QueryOutcome {
+        let consistency = self.consistency_policy.consistency(&batch_statement.as_statement());
+        let serial_consistency = self.consistency_policy.serial_consistency(&batch_statement.as_statement());
 
-        result
+        self.execute_with_retries(batch_statement.idempotent, self.statement_timeout, || {
+            let mut batch = Batch::new(batch_statement.mode.batch_type());
+            for statement in &batch_statement.statements {
+                batch.append_statement(Query::new(statement.to_owned()));
+            }
+            batch.set_consistency(consistency);
+            batch.set_serial_consistency(Some(serial_consistency));
+            let values = vec![(); batch_statement.statements.len()];
+            async move {
+                self.session.batch(&batch, values).await.map(|_| QueryResult::default())
+            }
+        }).await
+    }
+}
+
+impl SessionManagerImpl {
+    /// Sends the request built by `send` until it succeeds, fails with an error not worth retrying or runs
+    /// out of attempts, each attempt going through the circuit breaker and bounded by the request deadline.
+    async fn execute_with_retries<F, Fut>(&self, idempotent: bool, statement_timeout: Duration, send: F) -> QueryOutcome
+        where F: Fn() -> Fut,
+              Fut: Future<Output = Result<QueryResult, QueryError>> {
+        let mut attempts: u32 = 0;
+        let send = &send;
+
+        // A retry is only scheduled when its delay ends before the request deadline.
+        let delays = self.retry_policy.delays()
+            .take_while(|delay| deadline::remaining().map_or(true, |remaining| *delay < remaining));
+
+        let result = RetryIf::spawn(delays, || {
+            attempts += 1;
+            async move {
+                let remaining = deadline::remaining();
+                if remaining == Some(Duration::from_secs(0)) {
+                    return Err(QueryError::TimeoutError);
+                }
+
+                // Dropped along with this attempt when the request deadline drops the future.
+                let permit = match self.circuit_breaker.try_acquire() {
+                    Some(permit) => permit,
+                    None => return Err(QueryError::ProtocolError(CIRCUIT_OPEN))
+                };
+
+                let attempt_timeout = remaining.map_or(statement_timeout, |remaining| remaining.min(statement_timeout));
+                let started_at = Instant::now();
+                let (result, cut_by_deadline) = match timeout(attempt_timeout, send()).await {
+                    Ok(result) => (result, false),
+                    Err(_) => (Err(QueryError::TimeoutError), attempt_timeout < statement_timeout)
+                };
+
+                // Running out of request budget says nothing about the health of Cassandra, so the permit is
+                // handed back unrecorded rather than counted as a failure or a trial success.
+                if !cut_by_deadline {
+                    let failed = result.as_ref().err().map_or(false, |error| self.retry_policy.is_transient(error));
+                    permit.record(failed, started_at.elapsed());
+                }
+
+                result
+            }
+        }, |error: &QueryError| self.retry_policy.is_retryable(error, idempotent)).await;
+
+        QueryOutcome {
+            result,
+            retries: attempts.saturating_sub(1)
+        }
     }
 }
 
@@ -74,16 +332,21 @@ cfg_if! {
 pub mod tests {
     use super::*;
     use scylla::QueryResult;
-    use scylla::transport::errors::QueryError;
+    use scylla::transport::errors::{DbError, QueryError};
     use scylla::query::Query;
     use scylla::frame::response::result::CqlValue;
 
+    use crate::dao::retry_policy::RetrySettings;
+    use crate::dao::circuit_breaker::{CircuitBreakerSettings, CircuitState};
+    use crate::dao::consistency::ConsistencySettings;
+
     use mockall::{automock, mock};
 
     #[automock]
     #[async_trait]
     pub trait Queryable {
         async fn query(&self, query: Query, values: ()) -> Result<QueryResult, QueryError>;
+        async fn batch(&self, batch: &Batch, values: Vec<()>) -> Result<(), QueryError>;
     }
 
     mock! {
@@ -92,6 +355,7 @@ pub mod tests {
         #[async_trait]
         impl Queryable for Session {
             pub async fn query(&self, query: Query, values: ()) -> Result<QueryResult, QueryError>;
+            pub async fn batch(&self, batch: &Batch, values: Vec<()>) -> Result<(), QueryError>;
         }
     }
 
@@ -101,14 +365,14 @@ pub mod tests {
 
     #[test]
     fn when_new_then_returns_session_manager() {
-        let session_manager = aw!(SessionManagerImpl::new("node"));
+        let session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
 
         assert_eq!(get_type_of(&session_manager), "rust_rocket_micro_service::dao::session_manager::SessionManagerImpl");
     }
 
     #[test]
     fn when_execute_query_then_returns_query_result_ok() {
-        let mut session_manager = aw!(SessionManagerImpl::new("node"));
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
 
         session_manager.session.expect_query()
             .withf(|query: &Query, _| query.get_contents() == fixture::QUERY_STR.to_string())
@@ -116,7 +380,7 @@ pub mod tests {
             .times(1)
             .returning(move |_, _| fixture::forge_query_result());
 
-        let result = aw!(session_manager.execute_query(fixture::QUERY_STR));
+        let result = aw!(session_manager.execute_query(fixture::OPERATION, fixture::QUERY_STR));
 
         if let Some(rows) = result
             .unwrap()
@@ -131,7 +395,7 @@ pub mod tests {
 
     #[test]
     fn given_no_matching_row_when_execute_query_then_returns_query_result_ok() {
-        let mut session_manager = aw!(SessionManagerImpl::new("node"));
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
 
         session_manager.session.expect_query()
             .withf(|query: &Query, _| query.get_contents() == fixture::QUERY_STR.to_string())
@@ -139,7 +403,7 @@ pub mod tests {
             .times(1)
             .returning(move |_, _| Ok(QueryResult::default()));
 
-        let result = aw!(session_manager.execute_query(fixture::QUERY_STR));
+        let result = aw!(session_manager.execute_query(fixture::OPERATION, fixture::QUERY_STR));
 
         if let Some(_) = result
             .unwrap()
@@ -149,29 +413,75 @@ pub mod tests {
     }
 
     #[test]
-    fn given_error_when_execute_query_then_retries_up_to_4_times_then_returns_query_error() {
-        let mut session_manager = aw!(SessionManagerImpl::new("node"));
+    fn given_retryable_error_when_execute_query_then_retries_up_to_4_times_then_returns_query_error() {
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
 
         session_manager.session.expect_query()
             .withf(|query: &Query, _| query.get_contents() == fixture::QUERY_STR.to_string())
             .withf(|_, tuple: &()| tuple == &())
             .times(4)
-            .returning(move |_, _| Err(QueryError::InvalidMessage("error".to_owned())));
+            .returning(move |_, _| Err(fixture::overloaded()));
 
-        let result = aw!(session_manager.execute_query(fixture::QUERY_STR));
+        let result = aw!(session_manager.execute_query(fixture::OPERATION, fixture::QUERY_STR));
 
         assert!(result.is_err());
     }
 
+    #[test]
+    fn given_non_retryable_error_when_execute_query_then_returns_query_error_without_retrying() {
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
+
+        session_manager.session.expect_query()
+            .withf(|query: &Query, _| query.get_contents() == fixture::QUERY_STR.to_string())
+            .times(1)
+            .returning(move |_, _| Err(QueryError::DbError(DbError::SyntaxError, "error".to_owned())));
+
+        let outcome = aw!(session_manager.execute_statement(Statement::idempotent(fixture::QUERY_STR)));
+
+        assert!(outcome.result.is_err());
+        assert_eq!(0, outcome.retries);
+    }
+
+    #[test]
+    fn given_timeout_when_execute_non_idempotent_statement_then_does_not_retry() {
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
+
+        session_manager.session.expect_query()
+            .withf(|query: &Query, _| query.get_contents() == fixture::QUERY_STR.to_string())
+            .times(1)
+            .returning(move |_, _| Err(QueryError::TimeoutError));
+
+        let outcome = aw!(session_manager.execute_statement(Statement::non_idempotent(fixture::QUERY_STR)));
+
+        assert!(outcome.result.is_err());
+        assert_eq!(0, outcome.retries);
+    }
+
+    #[test]
+    fn given_configured_attempts_when_execute_statement_then_reports_retries() {
+        let retry_policy = RetryPolicy::new(RetrySettings { max_attempts: 2, jitter: false, ..RetrySettings::default() });
+        let mut session_manager = aw!(SessionManagerImpl::new("node", retry_policy, fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
+
+        session_manager.session.expect_query()
+            .withf(|query: &Query, _| query.get_contents() == fixture::QUERY_STR.to_string())
+            .times(2)
+            .returning(move |_, _| Err(fixture::overloaded()));
+
+        let outcome = aw!(session_manager.execute_statement(Statement::idempotent(fixture::QUERY_STR)));
+
+        assert!(outcome.result.is_err());
+        assert_eq!(1, outcome.retries);
+    }
+
     #[test]
     fn given_single_error_when_execute_query_then_retries_and_returns_query_result_ok() {
-        let mut session_manager = aw!(SessionManagerImpl::new("node"));
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
 
         session_manager.session.expect_query()
             .withf(|query: &Query, _| query.get_contents() == fixture::QUERY_STR.to_string())
             .withf(|_, tuple: &()| tuple == &())
             .times(1)
-            .returning(move |_, _| Err(QueryError::InvalidMessage("error".to_owned())));
+            .returning(move |_, _| Err(fixture::overloaded()));
 
         session_manager.session.expect_query()
             .withf(|query: &Query, _| query.get_contents() == fixture::QUERY_STR.to_string())
@@ -179,7 +489,7 @@ pub mod tests {
             .times(1)
             .returning(move |_, _| fixture::forge_query_result());
 
-            let result = aw!(session_manager.execute_query(fixture::QUERY_STR));
+            let result = aw!(session_manager.execute_query(fixture::OPERATION, fixture::QUERY_STR));
 
             if let Some(rows) = result
                 .unwrap()
@@ -192,11 +502,161 @@ pub mod tests {
             }
     }
 
+    #[test]
+    fn given_open_circuit_when_execute_query_then_fails_fast_without_querying() {
+        let circuit_breaker = Arc::new(CircuitBreaker::new(CircuitBreakerSettings {
+            minimum_calls: 2,
+            window_size: 2,
+            open_duration_ms: 60_000,
+            ..CircuitBreakerSettings::default()
+        }));
+        let retry_policy = RetryPolicy::new(RetrySettings { max_attempts: 1, ..RetrySettings::default() });
+        let mut session_manager = aw!(SessionManagerImpl::new("node", retry_policy, circuit_breaker.clone(), StatementSettings::default(), ConsistencyPolicy::default()));
+
+        session_manager.session.expect_query()
+            .withf(|query: &Query, _| query.get_contents() == fixture::QUERY_STR.to_string())
+            .times(2)
+            .returning(move |_, _| Err(fixture::overloaded()));
+
+        assert!(aw!(session_manager.execute_query(fixture::OPERATION, fixture::QUERY_STR)).is_err());
+        assert!(aw!(session_manager.execute_query(fixture::OPERATION, fixture::QUERY_STR)).is_err());
+        assert_eq!(CircuitState::Open, circuit_breaker.state());
+
+        let outcome = aw!(session_manager.execute_statement(Statement::idempotent(fixture::QUERY_STR)));
+
+        match outcome.result {
+            Err(QueryError::ProtocolError(message)) => assert_eq!(CIRCUIT_OPEN, message),
+            _ => panic!("Circuit breaker did not reject the query")
+        }
+    }
+
+    #[test]
+    fn given_expired_deadline_when_execute_statement_then_returns_timeout_without_querying() {
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
+
+        session_manager.session.expect_query()
+            .times(0);
+
+        let expired = Instant::now() - Duration::from_millis(1);
+        let outcome = aw!(deadline::scope(expired, session_manager.execute_statement(Statement::idempotent(fixture::QUERY_STR))));
+
+        match outcome.result {
+            Err(QueryError::TimeoutError) => (),
+            _ => panic!("Deadline was not enforced")
+        }
+    }
+
+    #[test]
+    fn given_retry_delay_beyond_deadline_when_execute_statement_then_does_not_retry() {
+        let retry_policy = RetryPolicy::new(RetrySettings { base_delay_ms: 1000, jitter: false, ..RetrySettings::default() });
+        let mut session_manager = aw!(SessionManagerImpl::new("node", retry_policy, fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
+
+        session_manager.session.expect_query()
+            .withf(|query: &Query, _| query.get_contents() == fixture::QUERY_STR.to_string())
+            .times(1)
+            .returning(move |_, _| Err(fixture::overloaded()));
+
+        let deadline = Instant::now() + Duration::from_millis(500);
+        let outcome = aw!(deadline::scope(deadline, session_manager.execute_statement(Statement::idempotent(fixture::QUERY_STR))));
+
+        assert!(outcome.result.is_err());
+        assert_eq!(0, outcome.retries);
+    }
+
+    #[test]
+    fn given_operation_consistency_when_execute_query_then_sets_consistency_on_query() {
+        let consistency_policy = ConsistencyPolicy::new(ConsistencySettings {
+            operations: vec!((fixture::OPERATION.to_string(), "one".to_string())).into_iter().collect(),
+            ..ConsistencySettings::default()
+        }).unwrap();
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), consistency_policy));
+
+        session_manager.session.expect_query()
+            .withf(|query: &Query, _| query.get_contents() == fixture::QUERY_STR.to_string() && query.get_consistency() == Consistency::One)
+            .times(1)
+            .returning(move |_, _| fixture::forge_query_result());
+
+        assert!(aw!(session_manager.execute_query(fixture::OPERATION, fixture::QUERY_STR)).is_ok());
+    }
+
+    #[test]
+    fn given_statement_serial_consistency_when_execute_statement_then_sets_serial_consistency_on_query() {
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
+
+        session_manager.session.expect_query()
+            .withf(|query: &Query, _| query.get_consistency() == Consistency::LocalQuorum
+                && query.get_serial_consistency() == Some(SerialConsistency::Serial))
+            .times(1)
+            .returning(move |_, _| fixture::forge_query_result());
+
+        let statement = Statement::non_idempotent(fixture::QUERY_STR).with_serial_consistency(SerialConsistency::Serial);
+
+        assert!(aw!(session_manager.execute_statement(statement)).result.is_ok());
+    }
+
+    #[test]
+    fn when_execute_batch_then_sends_every_statement_in_a_single_batch() {
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
+
+        session_manager.session.expect_batch()
+            .withf(|_, values: &Vec<()>| values.len() == 2)
+            .times(1)
+            .returning(move |_, _| Ok(()));
+
+        let batch = BatchStatement::unlogged(vec!(fixture::INSERT_STR.to_string(), fixture::INSERT_STR.to_string()));
+
+        let outcome = aw!(session_manager.execute_batch(batch));
+
+        assert!(outcome.result.is_ok());
+        assert_eq!(0, outcome.retries);
+    }
+
+    #[test]
+    fn given_retryable_error_when_execute_batch_then_retries() {
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
+
+        session_manager.session.expect_batch()
+            .times(1)
+            .returning(move |_, _| Err(fixture::overloaded()));
+
+        session_manager.session.expect_batch()
+            .times(1)
+            .returning(move |_, _| Ok(()));
+
+        let outcome = aw!(session_manager.execute_batch(BatchStatement::logged(vec!(fixture::INSERT_STR.to_string()))));
+
+        assert!(outcome.result.is_ok());
+        assert_eq!(1, outcome.retries);
+    }
+
+    #[test]
+    fn given_timeout_when_execute_non_idempotent_batch_then_does_not_retry() {
+        let mut session_manager = aw!(SessionManagerImpl::new("node", RetryPolicy::default(), fixture::circuit_breaker(), StatementSettings::default(), ConsistencyPolicy::default()));
+
+        session_manager.session.expect_batch()
+            .times(1)
+            .returning(move |_, _| Err(QueryError::TimeoutError));
+
+        let batch = BatchStatement::logged(vec!(fixture::INSERT_STR.to_string())).non_idempotent();
+
+        assert!(aw!(session_manager.execute_batch(batch)).result.is_err());
+    }
+
     mod fixture {
         use super::*;
 
+        pub const OPERATION: &str = "get_something";
         pub const QUERY_STR: &str = "SELECT something FROM anywhere";
         pub const SOMETHING: &str = "something";
+        pub const INSERT_STR: &str = "INSERT INTO anywhere (something) VALUES ('something')";
+
+        pub fn circuit_breaker() -> Arc<CircuitBreaker> {
+            Arc::new(CircuitBreaker::new(CircuitBreakerSettings::default()))
+        }
+
+        pub fn overloaded() -> QueryError {
+            QueryError::DbError(DbError::Overloaded, "overloaded".to_owned())
+        }
 
         pub fn forge_query_result() -> Result<QueryResult, QueryError> {
             let cql_values = vec!(Some(scylla::frame::response::result::CqlValue::Text(SOMETHING.to_owned())));
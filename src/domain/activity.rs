@@ -0,0 +1,18 @@
+use rocket::serde::uuid::Uuid;
+use scylla::macros::FromRow;
+use scylla::frame::response::cql_to_rust::FromRow;
+use chrono::Duration;
+
+#[derive(FromRow, Debug)]
+pub struct Activity {
+    pub user_id             : Uuid,
+    pub vehicle_id          : Uuid,
+    pub activity_id         : Uuid,
+    pub file_hash           : String,
+    pub file_format         : String,
+    pub started_at          : Option<Duration>,
+    pub duration            : i64,
+    pub distance            : f64,
+    pub elevation_gain      : f64,
+    pub created_at          : Duration
+}
@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use rocket::serde::uuid::Uuid;
+use scylla::macros::FromRow;
+use scylla::frame::response::cql_to_rust::FromRow;
+
+pub const OUTCOME_SUCCEEDED: &str = "succeeded";
+pub const OUTCOME_FAILED: &str = "failed";
+/// Partitions each day of the audit log is spread over, so that the writes of a day do not all land on the
+/// same replicas.
+pub const AUDIT_SHARDS: i32 = 8;
+
+crate::cql_entity! {
+    /// A write request, only ever inserted. Entries are bucketed by the UTC day they happened on and the shard
+    /// of their request, and clustered newest first; `targets` are the keys of what the request wrote, in a
+    /// JSON object.
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct AuditEntry {
+        pub day                 : NaiveDate,
+        pub shard               : i32,
+        pub occurred_at_ms      : i64,
+        pub audit_id            : Uuid,
+        pub request_id          : String,
+        pub actor               : String,
+        pub ip                  : Option<String>,
+        pub method              : String,
+        pub route               : String,
+        pub targets             : String,
+        pub status              : i32,
+        pub outcome             : String
+    }
+    table = "vehicles.audit_log";
+    partition_key = (day: NaiveDate, shard: i32);
+    clustering_key = (occurred_at_ms: i64, audit_id: Uuid);
+}
+
+/// Shard of the entry of a request, any string being accepted as a request id.
+pub fn shard(request_id: &str) -> i32 {
+    let hash = request_id.bytes().fold(0u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32));
+
+    (hash % AUDIT_SHARDS as u32) as i32
+}
+
+/// Keys of what a request wrote, by name, a batch writing several values under the same name.
+pub type AuditTargets = BTreeMap<String, Vec<String>>;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AuditFilter {
+    pub actor               : Option<String>,
+    /// Value of any of the target keys, such as a vehicle id or a book ISBN.
+    pub target              : Option<String>
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.actor.as_ref().map_or(true, |actor| actor == &entry.actor)
+            && self.target.as_ref().map_or(true, |target| serde_json::from_str::<AuditTargets>(&entry.targets)
+                .map_or(false, |targets| targets.values().flatten().any(|value| value == target)))
+    }
+}
+
+/// What the audit log records of a request besides its targets and outcome.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditedRequest {
+    pub request_id          : String,
+    pub actor               : String,
+    pub ip                  : Option<String>,
+    pub method              : String,
+    pub route               : String
+}
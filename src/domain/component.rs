@@ -0,0 +1,21 @@
+use rocket::serde::uuid::Uuid;
+use scylla::macros::FromRow;
+use scylla::frame::response::cql_to_rust::FromRow;
+use chrono::Duration;
+
+/// A part owned by a user that can be moved between their vehicles. `accumulated_distance` holds the
+/// distance ridden during past installations, `installed_distance` the vehicle odometer when it was fitted.
+#[derive(FromRow, Debug, Clone)]
+pub struct Component {
+    pub user_id                 : Uuid,
+    pub component_id            : Uuid,
+    pub vehicle_id              : Option<Uuid>,
+    pub component_type          : String,
+    pub brand                   : String,
+    pub model                   : String,
+    pub installed_at            : Option<Duration>,
+    pub removed_at              : Option<Duration>,
+    pub installed_distance      : Option<i32>,
+    pub accumulated_distance    : i32,
+    pub wear_limit              : Option<i32>
+}
@@ -0,0 +1,27 @@
+use rocket::serde::uuid::Uuid;
+use scylla::macros::FromRow;
+use scylla::frame::response::cql_to_rust::FromRow;
+use chrono::NaiveDate;
+
+#[derive(FromRow, Debug, Clone)]
+pub struct MaintenanceRecord {
+    pub user_id             : Uuid,
+    pub vehicle_id          : Uuid,
+    pub performed_on        : NaiveDate,
+    pub record_id           : Uuid,
+    pub odometer            : i32,
+    pub description         : String,
+    pub cost                : f64,
+    pub parts               : Option<Vec<String>>,
+    pub rule_id             : Option<Uuid>
+}
+
+#[derive(FromRow, Debug, Clone)]
+pub struct ReminderRule {
+    pub user_id             : Uuid,
+    pub vehicle_id          : Uuid,
+    pub rule_id             : Uuid,
+    pub description         : String,
+    pub interval_distance   : Option<i32>,
+    pub interval_months     : Option<i32>
+}
@@ -0,0 +1,59 @@
+use rocket::serde::uuid::Uuid;
+use scylla::macros::FromRow;
+use scylla::frame::response::cql_to_rust::FromRow;
+
+/// Shards the outbox is spread over, the entries of a user always going to the same one so that
+/// they are relayed in the order they were written.
+pub const OUTBOX_SHARDS: i32 = 8;
+/// Time span of the entries of a shard held in a single partition.
+pub const OUTBOX_BUCKET_MS: i64 = 3_600_000;
+/// Entries expire this long after they were written, through the default TTL of `vehicles.outbox`, so a relay
+/// stopped for longer misses events.
+pub const OUTBOX_TTL_MS: i64 = 3 * 24 * 3_600_000;
+
+crate::cql_entity! {
+    /// A vehicle event waiting to be relayed, written in the same logged batch as the change it is about
+    /// and expiring once `OUTBOX_TTL_MS` passed. `event_id` is the same on every relay of the entry, for
+    /// consumers to discard the ones they already processed; `vehicle` is the vehicle as saved, in JSON.
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct OutboxEntry {
+        pub shard               : i32,
+        pub bucket              : i64,
+        pub occurred_at_ms      : i64,
+        pub event_id            : Uuid,
+        pub user_id             : Uuid,
+        pub vehicle_id          : Uuid,
+        pub event               : String,
+        pub vehicle             : Option<String>
+    }
+    table = "vehicles.outbox";
+    partition_key = (shard: i32, bucket: i64);
+    clustering_key = (occurred_at_ms: i64, event_id: Uuid);
+}
+
+crate::cql_entity! {
+    /// How far the relay of a shard got, the entries up to `occurred_at_ms` and `event_id` having been relayed,
+    /// and the instance relaying it until `lease_until_ms`. Every column but `shard` is null until the shard is
+    /// first leased.
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct OutboxWatermark {
+        pub shard               : i32,
+        pub occurred_at_ms      : Option<i64>,
+        pub event_id            : Option<Uuid>,
+        pub owner               : Option<Uuid>,
+        pub lease_until_ms      : Option<i64>
+    }
+    table = "vehicles.outbox_watermark";
+    partition_key = (shard: i32);
+    clustering_key = ();
+}
+
+/// Outbox shard of the events of a user.
+pub fn shard(user_id: Uuid) -> i32 {
+    (user_id.as_u128() % OUTBOX_SHARDS as u128) as i32
+}
+
+/// Outbox partition of a shard holding the entries written at `occurred_at_ms`.
+pub fn bucket(occurred_at_ms: i64) -> i64 {
+    occurred_at_ms.div_euclid(OUTBOX_BUCKET_MS)
+}
@@ -0,0 +1,31 @@
+use rocket::serde::uuid::Uuid;
+use scylla::macros::FromRow;
+use scylla::frame::response::cql_to_rust::FromRow;
+use chrono::{Duration, NaiveDate};
+
+pub const OFFER_PENDING: &str = "pending";
+pub const OFFER_ACCEPTED: &str = "accepted";
+pub const OFFER_DECLINED: &str = "declined";
+/// Seconds the acceptance lock of a vehicle is held, well past the time an acceptance takes, after which a lock left
+/// by an acceptance cut short lapses and another offer of the vehicle can be accepted.
+pub const CLAIM_TTL_SECONDS: i64 = 300;
+
+/// Offer of `from_user_id` to hand `vehicle_id` over, stored in the partition of the recipient.
+#[derive(FromRow, Debug, Clone)]
+pub struct TransferOffer {
+    pub to_user_id          : Uuid,
+    pub offer_id            : Uuid,
+    pub from_user_id        : Uuid,
+    pub vehicle_id          : Uuid,
+    pub status              : String,
+    pub created_at          : Duration
+}
+
+/// A past owner of a vehicle and the period they owned it.
+#[derive(FromRow, Debug, Clone)]
+pub struct OwnershipRecord {
+    pub vehicle_id          : Uuid,
+    pub owner_until         : Duration,
+    pub user_id             : Uuid,
+    pub owner_since         : NaiveDate
+}
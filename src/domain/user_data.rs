@@ -0,0 +1,139 @@
+use rocket::serde::uuid::Uuid;
+use scylla::macros::FromRow;
+use scylla::frame::response::cql_to_rust::FromRow;
+
+pub const ERASURE_PENDING: &str = "pending";
+pub const ERASURE_RUNNING: &str = "running";
+pub const ERASURE_COMPLETED: &str = "completed";
+pub const ERASURE_FAILED: &str = "failed";
+/// Partitions the unfinished erasures are spread over.
+pub const ERASURE_SHARDS: i32 = 8;
+
+/// How the partitions of a table holding data of a user are found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UserPartition {
+    /// Partitioned by `user_id`.
+    User,
+    /// Partitioned by `to_user_id`, the recipient of a transfer offer.
+    Recipient,
+    /// Partitioned by `user_id` and the `vehicle_id` of each vehicle of the user.
+    UserVehicle,
+    /// Partitioned by the `vehicle_id` of each vehicle of the user.
+    Vehicle,
+    /// Partitioned by the `webhook_id` of each webhook of the user.
+    Webhook
+}
+
+/// A table holding data of users, exported as `<name>.json` and erased partition by partition.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UserTable {
+    pub name                : &'static str,
+    pub table               : &'static str,
+    /// Columns exported, leaving out secrets.
+    pub columns             : &'static [&'static str],
+    pub partition           : UserPartition
+}
+
+/// Every table holding data of users, `vehicles.vehicle` last, since erasing the other tables needs its vehicles
+/// to be found again when an erasure is retried. The vehicle lookups are derived from the vehicles and removed
+/// with them, and the pending purges of trashed vehicles only name what is left to purge. Other tables holding
+/// data of users are kept on purpose:
+/// - the audit log, client IPs included, is the record of who did what, which the erasure itself is part of;
+///   entries are found by day and not by user, and name users only through the keys they wrote;
+/// - the outbox only holds the events of the user until they expire, 3 days after they are written;
+/// - the pending webhook deliveries only hold keys, and are removed by the dispatcher once it finds their
+///   delivery erased;
+/// - offers the user sent that moved a vehicle stay in the partitions of their recipients, as the record of how
+///   the recipients got their vehicles. Offers of vehicles the user still owns are erased with the vehicles.
+pub const USER_TABLES: [UserTable; 14] = [
+    UserTable { name: "vehicle_history", table: "vehicles.vehicle_history", columns: &["*"], partition: UserPartition::UserVehicle },
+    UserTable { name: "activities", table: "vehicles.activity", columns: &["*"], partition: UserPartition::UserVehicle },
+    UserTable { name: "activity_distances", table: "vehicles.activity_distance", columns: &["*"], partition: UserPartition::UserVehicle },
+    UserTable { name: "maintenance_records", table: "vehicles.maintenance_record", columns: &["*"], partition: UserPartition::UserVehicle },
+    UserTable { name: "reminder_rules", table: "vehicles.reminder_rule", columns: &["*"], partition: UserPartition::UserVehicle },
+    UserTable { name: "ownership_history", table: "vehicles.vehicle_owner_history", columns: &["*"], partition: UserPartition::Vehicle },
+    UserTable { name: "vehicle_transfer_offers", table: "vehicles.transfer_offer_by_vehicle", columns: &["*"], partition: UserPartition::Vehicle },
+    UserTable { name: "activity_files", table: "vehicles.activity_by_hash", columns: &["*"], partition: UserPartition::User },
+    UserTable { name: "components", table: "vehicles.component", columns: &["*"], partition: UserPartition::User },
+    UserTable { name: "transfer_offers", table: "vehicles.transfer_offer", columns: &["*"], partition: UserPartition::Recipient },
+    UserTable { name: "webhook_deliveries", table: "vehicles.webhook_delivery", columns: &["*"], partition: UserPartition::Webhook },
+    UserTable { name: "trash", table: "vehicles.vehicle_trash", columns: &["*"], partition: UserPartition::User },
+    UserTable { name: "webhooks", table: "vehicles.webhook", columns: &["user_id", "webhook_id", "url", "events", "created_at"], partition: UserPartition::User },
+    UserTable { name: "vehicles", table: "vehicles.vehicle", columns: &["*"], partition: UserPartition::User }
+];
+
+/// Keys of the partitions of a table to read or delete, as column and value pairs.
+pub type PartitionKey = Vec<(&'static str, Uuid)>;
+
+/// What the partitions holding data of a user are found from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserKeys {
+    pub user_id             : Uuid,
+    pub vehicle_ids         : Vec<Uuid>,
+    pub webhook_ids         : Vec<Uuid>
+}
+
+impl UserTable {
+    pub fn partitions(&self, keys: &UserKeys) -> Vec<PartitionKey> {
+        match self.partition {
+            UserPartition::User => vec!(vec!(("user_id", keys.user_id))),
+            UserPartition::Recipient => vec!(vec!(("to_user_id", keys.user_id))),
+            UserPartition::UserVehicle => keys.vehicle_ids.iter()
+                .map(|vehicle_id| vec!(("user_id", keys.user_id), ("vehicle_id", *vehicle_id)))
+                .collect(),
+            UserPartition::Vehicle => keys.vehicle_ids.iter()
+                .map(|vehicle_id| vec!(("vehicle_id", *vehicle_id)))
+                .collect(),
+            UserPartition::Webhook => keys.webhook_ids.iter()
+                .map(|webhook_id| vec!(("webhook_id", *webhook_id)))
+                .collect()
+        }
+    }
+
+    /// Partitions holding data of a single vehicle of a user, none for the tables not keyed by vehicle.
+    pub fn vehicle_partitions(&self, user_id: Uuid, vehicle_id: Uuid) -> Vec<PartitionKey> {
+        match self.partition {
+            UserPartition::UserVehicle | UserPartition::Vehicle => self.partitions(&UserKeys { user_id, vehicle_ids: vec!(vehicle_id), webhook_ids: vec!() }),
+            _ => vec!()
+        }
+    }
+}
+
+crate::cql_entity! {
+    /// Erasure of the data of a user, run in the background. `erased` names the tables and stores done so far,
+    /// and `error` tells why a `failed` erasure stopped; requesting another erasure erases what is left.
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct ErasureJob {
+        pub user_id             : Uuid,
+        pub job_id              : Uuid,
+        pub status              : String,
+        pub erased              : Vec<String>,
+        pub error               : Option<String>,
+        pub requested_at_ms     : i64,
+        pub updated_at_ms       : i64
+    }
+    table = "vehicles.erasure_job";
+    partition_key = (user_id: Uuid);
+    clustering_key = (job_id: Uuid);
+}
+
+crate::cql_entity! {
+    /// An erasure not finished yet, leased until `lease_until_ms` to the instance running it. Once the lease runs
+    /// out, because that instance stopped, any instance may take the erasure over and run it again, erasing what
+    /// is left. Removed along with the final status of the erasure.
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct UnfinishedErasure {
+        pub shard               : i32,
+        pub job_id              : Uuid,
+        pub user_id             : Uuid,
+        pub lease_until_ms      : i64
+    }
+    table = "vehicles.unfinished_erasure";
+    partition_key = (shard: i32);
+    clustering_key = (job_id: Uuid);
+}
+
+/// Unfinished erasure partition of an erasure.
+pub fn shard(job_id: Uuid) -> i32 {
+    (job_id.as_u128() % ERASURE_SHARDS as u128) as i32
+}
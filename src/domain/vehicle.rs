@@ -3,18 +3,64 @@ use scylla::macros::FromRow;
 use scylla::frame::response::cql_to_rust::FromRow;
 use chrono::{Duration, NaiveDate};
 
-#[derive(FromRow, Debug)]
-pub struct Vehicle {
-    pub name                : String,
-    pub user_id             : Uuid,
-    pub vehicle_id          : Uuid,
-    pub created_at          : Duration,
-    pub vehicle_type        : String,
-    pub retired_at          : Option<Duration>,
-    pub brand               : String,
-    pub model               : String,
-    pub distance            : i32,
-    pub owner_since         : NaiveDate,
-    pub manufacturing_date  : NaiveDate,
-    pub picture             : Option<String>
+crate::cql_entity! {
+    #[derive(FromRow, Debug, Clone)]
+    pub struct Vehicle {
+        pub name                : String,
+        pub user_id             : Uuid,
+        pub vehicle_id          : Uuid,
+        pub created_at          : Duration,
+        pub vehicle_type        : String,
+        pub retired_at          : Option<Duration>,
+        pub brand               : String,
+        pub model               : String,
+        pub distance            : i32,
+        pub owner_since         : NaiveDate,
+        pub manufacturing_date  : NaiveDate,
+        pub picture             : Option<String>
+    }
+    table = "vehicles.vehicle";
+    partition_key = (user_id: Uuid);
+    clustering_key = (vehicle_id: Uuid);
+    // Saving a vehicle coming from a DTO keeps the uploaded picture.
+    unset_when_none = (picture);
+}
+
+/// A vehicle read with only some of its columns, the others being `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VehicleProjection {
+    pub name                : Option<String>,
+    pub user_id             : Option<Uuid>,
+    pub vehicle_id          : Option<Uuid>,
+    pub created_at          : Option<Duration>,
+    pub vehicle_type        : Option<String>,
+    pub retired_at          : Option<Option<Duration>>,
+    pub brand               : Option<String>,
+    pub model               : Option<String>,
+    pub distance            : Option<i32>,
+    pub owner_since         : Option<NaiveDate>,
+    pub manufacturing_date  : Option<NaiveDate>,
+    pub picture             : Option<Option<String>>
+}
+
+impl VehicleProjection {
+    /// Projection of a vehicle already read in full onto `columns`.
+    pub fn of(vehicle: Vehicle, columns: &[&str]) -> VehicleProjection {
+        let selected = |column: &str| columns.contains(&column);
+
+        VehicleProjection {
+            name: Some(vehicle.name).filter(|_| selected("name")),
+            user_id: Some(vehicle.user_id).filter(|_| selected("user_id")),
+            vehicle_id: Some(vehicle.vehicle_id).filter(|_| selected("vehicle_id")),
+            created_at: Some(vehicle.created_at).filter(|_| selected("created_at")),
+            vehicle_type: Some(vehicle.vehicle_type).filter(|_| selected("vehicle_type")),
+            retired_at: Some(vehicle.retired_at).filter(|_| selected("retired_at")),
+            brand: Some(vehicle.brand).filter(|_| selected("brand")),
+            model: Some(vehicle.model).filter(|_| selected("model")),
+            distance: Some(vehicle.distance).filter(|_| selected("distance")),
+            owner_since: Some(vehicle.owner_since).filter(|_| selected("owner_since")),
+            manufacturing_date: Some(vehicle.manufacturing_date).filter(|_| selected("manufacturing_date")),
+            picture: Some(vehicle.picture).filter(|_| selected("picture"))
+        }
+    }
 }
@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use rocket::serde::uuid::Uuid;
+
+use crate::domain::vehicle::Vehicle;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VehicleEventKind {
+    Created,
+    Updated,
+    Retired,
+    /// The vehicle now belongs to another user, published to its previous owner.
+    Transferred,
+    Deleted
+}
+
+impl VehicleEventKind {
+    pub const ALL: [VehicleEventKind; 5] = [VehicleEventKind::Created, VehicleEventKind::Updated, VehicleEventKind::Retired,
+        VehicleEventKind::Transferred, VehicleEventKind::Deleted];
+
+    /// Kind of the save turning `previous` into `saved`, `previous` being `None` for a new vehicle.
+    pub fn of_save(previous: Option<&Vehicle>, saved: &Vehicle) -> VehicleEventKind {
+        match previous {
+            None => VehicleEventKind::Created,
+            Some(previous) if previous.retired_at.is_none() && saved.retired_at.is_some() => VehicleEventKind::Retired,
+            Some(_) => VehicleEventKind::Updated
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<VehicleEventKind> {
+        VehicleEventKind::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+
+    /// Name of the event as sent on the stream.
+    pub fn name(&self) -> &'static str {
+        match self {
+            VehicleEventKind::Created => "created",
+            VehicleEventKind::Updated => "updated",
+            VehicleEventKind::Retired => "retired",
+            VehicleEventKind::Transferred => "transferred",
+            VehicleEventKind::Deleted => "deleted"
+        }
+    }
+}
+
+/// A change to one of the vehicles of a user, holding the vehicle as saved except for deletions and transfers.
+/// `id` orders the events of the stream of an instance, while `event_id` names the change itself and is the
+/// same every time it is relayed.
+#[derive(Debug, Clone)]
+pub struct VehicleEvent {
+    pub id                  : u64,
+    pub event_id            : Uuid,
+    pub user_id             : Uuid,
+    pub vehicle_id          : Uuid,
+    pub kind                : VehicleEventKind,
+    pub occurred_at         : DateTime<Utc>,
+    pub vehicle             : Option<Vehicle>
+}
@@ -0,0 +1,35 @@
+use rocket::serde::uuid::Uuid;
+use scylla::macros::FromRow;
+use scylla::frame::response::cql_to_rust::FromRow;
+
+use crate::domain::vehicle_event::VehicleEventKind;
+
+crate::cql_entity! {
+    /// Immutable snapshot of a vehicle as saved, `version` being a timeuuid of when it was saved, unique even for
+    /// saves within the same millisecond, and `saved_at_ms` that time in milliseconds since the epoch. `diff` is a JSON object of the fields the save changed, each with its value `from` before and `to`
+    /// after the save, and `vehicle` the vehicle as saved, in JSON. `restored_from` is the version a restore
+    /// brought the vehicle back to.
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct VehicleVersion {
+        pub user_id             : Uuid,
+        pub vehicle_id          : Uuid,
+        pub version             : Uuid,
+        pub saved_at_ms         : i64,
+        pub actor               : String,
+        pub event               : String,
+        pub diff                : String,
+        pub vehicle             : String,
+        pub restored_from       : Option<Uuid>
+    }
+    table = "vehicles.vehicle_history";
+    partition_key = (user_id: Uuid, vehicle_id: Uuid);
+    clustering_key = (version: Uuid);
+}
+
+/// What a save records besides the vehicle row, in the same logged batch: the event relayed from the outbox
+/// and the version appended to the history of the vehicle.
+#[derive(Debug, Clone)]
+pub struct VehicleChange {
+    pub event               : VehicleEventKind,
+    pub version             : VehicleVersion
+}
@@ -0,0 +1,91 @@
+use rocket::serde::uuid::Uuid;
+use scylla::macros::FromRow;
+use scylla::frame::response::cql_to_rust::FromRow;
+
+use crate::domain::vehicle::Vehicle;
+
+pub const BRAND: &str = "brand";
+pub const MODEL: &str = "model";
+pub const VEHICLE_TYPE: &str = "vehicle_type";
+
+/// Attributes with a lookup partition per value. The type and status of a vehicle have too few values, each of
+/// which would grow into a partition holding a large share of all vehicles, so they are only filtered on.
+pub const LOOKUP_ATTRIBUTES: [&str; 2] = [BRAND, MODEL];
+
+pub const ACTIVE: &str = "active";
+pub const RETIRED: &str = "retired";
+
+crate::cql_entity! {
+    /// Key of a vehicle stored in the partition of one value of a searchable attribute, e.g. every
+    /// vehicle of brand `trek` under (`brand`, `trek`).
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct VehicleLookup {
+        pub attribute           : String,
+        pub value               : String,
+        pub user_id             : Uuid,
+        pub vehicle_id          : Uuid
+    }
+    table = "vehicles.vehicle_lookup";
+    partition_key = (attribute: String, value: String);
+    clustering_key = (user_id: Uuid, vehicle_id: Uuid);
+}
+
+impl VehicleLookup {
+    /// Lookup rows of every attribute of a vehicle in `LOOKUP_ATTRIBUTES`.
+    pub fn of(vehicle: &Vehicle) -> Vec<VehicleLookup> {
+        LOOKUP_ATTRIBUTES.iter()
+            .map(|attribute| VehicleLookup {
+                attribute: attribute.to_string(),
+                value: attribute_value(vehicle, attribute),
+                user_id: vehicle.user_id,
+                vehicle_id: vehicle.vehicle_id
+            })
+            .collect()
+    }
+}
+
+/// Case and surrounding blanks do not matter when searching.
+pub fn normalize(value: &str) -> String {
+    value.trim().to_lowercase()
+}
+
+/// The normalized value a vehicle is indexed under for a searchable attribute.
+pub fn attribute_value(vehicle: &Vehicle, attribute: &str) -> String {
+    match attribute {
+        BRAND => normalize(&vehicle.brand),
+        MODEL => normalize(&vehicle.model),
+        VEHICLE_TYPE => normalize(&vehicle.vehicle_type),
+        _ => String::new()
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct VehicleFilter {
+    pub brand               : Option<String>,
+    pub model               : Option<String>,
+    pub vehicle_type        : Option<String>,
+    pub retired             : Option<bool>,
+    pub min_distance        : Option<i32>,
+    pub max_distance        : Option<i32>
+}
+
+impl VehicleFilter {
+    /// The lookup partition to read, that of the model when filtered on, else that of the brand, `None` when
+    /// neither is filtered on.
+    pub fn lookup_key(&self) -> Option<(&'static str, String)> {
+        [(MODEL, &self.model), (BRAND, &self.brand)].iter()
+            .find_map(|(attribute, value)| value.as_ref().map(|value| (*attribute, normalize(value))))
+    }
+
+    pub fn matches(&self, vehicle: &Vehicle) -> bool {
+        let same = |attribute: &str, value: &Option<String>| value.as_ref()
+            .map_or(true, |value| normalize(value) == attribute_value(vehicle, attribute));
+
+        same(BRAND, &self.brand)
+            && same(MODEL, &self.model)
+            && same(VEHICLE_TYPE, &self.vehicle_type)
+            && self.retired.map_or(true, |retired| retired == vehicle.retired_at.is_some())
+            && self.min_distance.map_or(true, |min_distance| vehicle.distance >= min_distance)
+            && self.max_distance.map_or(true, |max_distance| vehicle.distance <= max_distance)
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+use rocket::serde::uuid::Uuid;
+use scylla::macros::FromRow;
+use scylla::frame::response::cql_to_rust::FromRow;
+
+/// Partitions the pending purges of each day are spread over.
+pub const PURGE_SHARDS: i32 = 8;
+
+crate::cql_entity! {
+    /// A deleted vehicle, which can be restored until `purge_at_ms`. It is written with a TTL running out then,
+    /// so Cassandra drops it even when no purge runs. `vehicle` is the vehicle as deleted, in JSON, and `picture`
+    /// the key of its picture blob, which the JSON only holds the URL of.
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct TrashedVehicle {
+        pub user_id             : Uuid,
+        pub vehicle_id          : Uuid,
+        pub deleted_at_ms       : i64,
+        pub deleted_by          : String,
+        pub purge_at_ms         : i64,
+        pub vehicle             : String,
+        pub picture             : Option<String>
+    }
+    table = "vehicles.vehicle_trash";
+    partition_key = (user_id: Uuid);
+    clustering_key = (vehicle_id: Uuid);
+}
+
+crate::cql_entity! {
+    /// Purge of the data a deleted vehicle leaves outside of the trash, due at `purge_at_ms`: its history, logs
+    /// and picture. Written and removed together with the trashed vehicle, so a restored vehicle is never purged.
+    /// Purges are bucketed by the UTC day they are due on, so the tombstones of done purges stay in the partitions
+    /// of past days instead of piling up ahead of the purges still due.
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct VehiclePurge {
+        pub day                 : NaiveDate,
+        pub shard               : i32,
+        pub purge_at_ms         : i64,
+        pub user_id             : Uuid,
+        pub vehicle_id          : Uuid,
+        pub picture             : Option<String>
+    }
+    table = "vehicles.vehicle_purge";
+    partition_key = (day: NaiveDate, shard: i32);
+    clustering_key = (purge_at_ms: i64, user_id: Uuid, vehicle_id: Uuid);
+}
+
+/// Purge shard of a vehicle.
+pub fn shard(vehicle_id: Uuid) -> i32 {
+    (vehicle_id.as_u128() % PURGE_SHARDS as u128) as i32
+}
+
+/// Purge day of a purge due at `purge_at_ms`.
+pub fn day(purge_at_ms: i64) -> NaiveDate {
+    Utc.timestamp_millis(purge_at_ms).date().naive_utc()
+}
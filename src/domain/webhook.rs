@@ -0,0 +1,139 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use rocket::serde::uuid::Uuid;
+use scylla::macros::FromRow;
+use scylla::frame::response::cql_to_rust::FromRow;
+use chrono::Duration;
+
+pub const DELIVERY_PENDING: &str = "pending";
+pub const DELIVERY_DELIVERED: &str = "delivered";
+/// Given up on after `max_attempts`, kept in the delivery log for the partner to be told about.
+pub const DELIVERY_DEAD_LETTERED: &str = "dead_lettered";
+/// Partitions the deliveries waiting for an attempt are spread over.
+pub const PENDING_DELIVERY_SHARDS: i32 = 8;
+
+crate::cql_entity! {
+    /// A partner URL the vehicle `events` of a user are posted to, signed with `secret`.
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct Webhook {
+        pub user_id             : Uuid,
+        pub webhook_id          : Uuid,
+        pub url                 : String,
+        pub secret              : String,
+        pub events              : Vec<String>,
+        pub created_at          : Duration
+    }
+    table = "vehicles.webhook";
+    partition_key = (user_id: Uuid);
+    clustering_key = (webhook_id: Uuid);
+}
+
+crate::cql_entity! {
+    /// An event posted to a webhook, with the outcome of its last attempt. Deliveries of a webhook are
+    /// clustered newest first.
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct WebhookDelivery {
+        pub webhook_id          : Uuid,
+        pub created_at          : Duration,
+        pub delivery_id         : Uuid,
+        pub user_id             : Uuid,
+        pub vehicle_id          : Uuid,
+        pub event               : String,
+        pub payload             : String,
+        pub status              : String,
+        pub attempts            : i32,
+        pub response_status     : Option<i32>,
+        pub error               : Option<String>,
+        pub updated_at          : Duration
+    }
+    table = "vehicles.webhook_delivery";
+    partition_key = (webhook_id: Uuid);
+    clustering_key = (created_at: Duration, delivery_id: Uuid);
+}
+
+crate::cql_entity! {
+    /// A delivery waiting for its next attempt, leased until `lease_until_ms` to the instance attempting it. Once
+    /// the lease runs out, because that instance stopped, any instance may take the delivery over and resume it.
+    /// Removed along with the final outcome of the delivery.
+    #[derive(FromRow, Debug, Clone, PartialEq)]
+    pub struct PendingDelivery {
+        pub shard               : i32,
+        pub delivery_id         : Uuid,
+        pub webhook_id          : Uuid,
+        pub user_id             : Uuid,
+        pub created_at          : Duration,
+        pub lease_until_ms      : i64
+    }
+    table = "vehicles.webhook_pending_delivery";
+    partition_key = (shard: i32);
+    clustering_key = (delivery_id: Uuid, webhook_id: Uuid);
+}
+
+/// Pending delivery partition of a delivery.
+pub fn shard(delivery_id: Uuid) -> i32 {
+    (delivery_id.as_u128() % PENDING_DELIVERY_SHARDS as u128) as i32
+}
+
+/// Whether a webhook URL may be posted to: its host must not be `localhost` nor an address of the loopback,
+/// private, link-local or otherwise non-public ranges. Names are checked again once resolved, when delivering.
+pub fn is_public_url(url: &reqwest::Url) -> bool {
+    let host = match url.host_str() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']').to_lowercase(),
+        None => return false
+    };
+
+    match host.parse::<IpAddr>() {
+        Ok(ip) => is_public_address(ip),
+        Err(_) => host != "localhost" && !host.ends_with(".localhost")
+    }
+}
+
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip)
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let octets = ip.octets();
+    // 100.64.0.0/10 is shared by carrier-grade NATs.
+    let shared = octets[0] == 100 && (octets[1] & 0xc0) == 64;
+
+    !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()
+        || ip.is_multicast() || ip.is_documentation() || shared || octets[0] == 0)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    let unique_local = (segments[0] & 0xfe00) == 0xfc00;
+    let link_local = (segments[0] & 0xffc0) == 0xfe80;
+    let ipv4_mapped = segments[..5].iter().all(|segment| *segment == 0) && segments[5] == 0xffff;
+
+    if ipv4_mapped {
+        return is_public_ipv4(Ipv4Addr::new((segments[6] >> 8) as u8, segments[6] as u8, (segments[7] >> 8) as u8, segments[7] as u8));
+    }
+
+    !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || unique_local || link_local)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn given_private_loopback_or_link_local_host_when_is_public_url_then_returns_false() {
+        for url in &["http://127.0.0.1/hooks", "http://localhost:8000/hooks", "http://10.1.2.3/hooks", "http://192.168.0.10/hooks",
+                     "http://169.254.169.254/latest/meta-data", "http://[::1]/hooks", "http://[fd00::1]/hooks", "http://[::ffff:127.0.0.1]/hooks",
+                     "http://0.0.0.0/hooks", "http://api.localhost/hooks"] {
+            assert!(!is_public_url(&reqwest::Url::parse(url).unwrap()), "{} is not public", url);
+        }
+    }
+
+    #[test]
+    fn given_public_host_when_is_public_url_then_returns_true() {
+        for url in &["https://partner.example/hooks", "https://93.184.216.34/hooks", "https://[2606:2800:220:1::1]/hooks"] {
+            assert!(is_public_url(&reqwest::Url::parse(url).unwrap()), "{} is public", url);
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use chrono::{DateTime, Utc};
+use rocket::serde::uuid::Uuid;
+use rocket::serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActivityDTO {
+    pub activity_id         : Uuid,
+    pub user_id             : Uuid,
+    pub vehicle_id          : Uuid,
+    pub file_format         : String,
+    pub started_at          : Option<DateTime<Utc>>,
+    pub duration            : i64,
+    pub distance            : f64,
+    pub elevation_gain      : f64,
+    pub created_at          : DateTime<Utc>
+}
@@ -0,0 +1,20 @@
+use chrono::{DateTime, Utc};
+use rocket::serde::{Serialize, Deserialize};
+use rocket::serde::uuid::Uuid;
+
+use crate::domain::audit::AuditTargets;
+
+/// A write request as recorded in the audit log.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntryDTO {
+    pub audit_id            : Uuid,
+    pub occurred_at         : DateTime<Utc>,
+    pub request_id          : String,
+    pub actor               : String,
+    pub ip                  : Option<String>,
+    pub method              : String,
+    pub route               : String,
+    pub targets             : AuditTargets,
+    pub status              : u16,
+    pub outcome             : String
+}
@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+use rocket::serde::uuid::Uuid;
+use rocket::serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum WearStatus {
+    Ok,
+    Warning,
+    Worn
+}
+
+impl Default for WearStatus {
+    fn default() -> Self {
+        WearStatus::Ok
+    }
+}
+
+/// `distance` is the total distance ridden with the component; on creation it seeds the distance
+/// already done before the component was registered. `wear_status` is computed and ignored on input.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ComponentDTO {
+    pub component_id        : Option<Uuid>,
+    pub vehicle_id          : Option<Uuid>,
+    pub component_type      : String,
+    pub brand               : String,
+    pub model               : String,
+    pub installed_at        : Option<DateTime<Utc>>,
+    pub removed_at          : Option<DateTime<Utc>>,
+    pub installed_distance  : Option<i32>,
+    pub wear_limit          : Option<i32>,
+    #[serde(default)]
+    pub distance            : i32,
+    #[serde(default)]
+    pub wear_status         : WearStatus
+}
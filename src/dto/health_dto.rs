@@ -0,0 +1,32 @@
+use rocket::serde::{Serialize, Deserialize};
+
+/// `status` is `ready` unless the Cassandra circuit breaker is open.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReadinessDTO {
+    pub status              : String,
+    pub circuit_breaker     : String
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CircuitBreakerMetricsDTO {
+    pub state               : String,
+    pub calls               : u64,
+    pub failures            : u64,
+    pub slow_calls          : u64,
+    pub rejected_calls      : u64,
+    pub times_opened        : u64
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CacheMetricsDTO {
+    pub hits                : u64,
+    pub misses              : u64,
+    pub coalesced           : u64,
+    pub size                : usize
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MetricsDTO {
+    pub circuit_breaker     : CircuitBreakerMetricsDTO,
+    pub vehicle_cache       : CacheMetricsDTO
+}
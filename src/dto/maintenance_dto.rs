@@ -0,0 +1,40 @@
+use chrono::NaiveDate;
+use rocket::serde::uuid::Uuid;
+use rocket::serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MaintenanceRecordDTO {
+    pub record_id           : Option<Uuid>,
+    pub performed_on        : NaiveDate,
+    pub odometer            : i32,
+    pub description         : String,
+    pub cost                : f64,
+    #[serde(default)]
+    pub parts               : Vec<String>,
+    pub rule_id             : Option<Uuid>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReminderRuleDTO {
+    pub rule_id             : Option<Uuid>,
+    pub description         : String,
+    pub interval_distance   : Option<i32>,
+    pub interval_months     : Option<i32>
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ReminderStatus {
+    Due,
+    Overdue
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReminderDTO {
+    pub rule_id             : Uuid,
+    pub description         : String,
+    pub status              : ReminderStatus,
+    pub due_distance        : Option<i32>,
+    pub due_date            : Option<NaiveDate>,
+    pub remaining_distance  : Option<i32>
+}
@@ -0,0 +1,25 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rocket::serde::uuid::Uuid;
+use rocket::serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TransferRequestDTO {
+    pub to_user_id          : Uuid
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TransferOfferDTO {
+    pub offer_id            : Uuid,
+    pub from_user_id        : Uuid,
+    pub to_user_id          : Uuid,
+    pub vehicle_id          : Uuid,
+    pub status              : String,
+    pub created_at          : DateTime<Utc>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OwnershipRecordDTO {
+    pub user_id             : Uuid,
+    pub owner_since         : NaiveDate,
+    pub owner_until         : DateTime<Utc>
+}
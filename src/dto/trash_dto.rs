@@ -0,0 +1,13 @@
+use chrono::{DateTime, Utc};
+use rocket::serde::{Serialize, Deserialize};
+
+use crate::dto::vehicle_dto::VehicleDTO;
+
+/// A deleted vehicle, restorable until `purge_at`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TrashedVehicleDTO {
+    pub vehicle             : VehicleDTO,
+    pub deleted_at          : DateTime<Utc>,
+    pub deleted_by          : String,
+    pub purge_at            : DateTime<Utc>
+}
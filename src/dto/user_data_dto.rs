@@ -0,0 +1,16 @@
+use chrono::{DateTime, Utc};
+use rocket::serde::{Serialize, Deserialize};
+use rocket::serde::uuid::Uuid;
+
+/// Erasure of the data of a user, polled until `completed` or `failed`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErasureJobDTO {
+    pub job_id              : Uuid,
+    pub user_id             : Uuid,
+    pub status              : String,
+    /// Tables and stores erased so far.
+    pub erased              : Vec<String>,
+    pub error               : Option<String>,
+    pub requested_at        : DateTime<Utc>,
+    pub updated_at          : DateTime<Utc>
+}
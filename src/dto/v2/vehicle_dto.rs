@@ -0,0 +1,42 @@
+use chrono::{NaiveDate, DateTime, Utc};
+use rocket::serde::uuid::Uuid;
+use rocket::serde::{Serialize, Deserialize};
+
+/// A vehicle to create, its id and creation time being assigned by the server.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NewVehicleDTO {
+    pub name                : String,
+    pub user_id             : Uuid,
+    pub vehicle_type        : String,
+    pub retired_at          : Option<DateTime<Utc>>,
+    pub brand               : String,
+    pub model               : String,
+    pub distance            : i32,
+    pub owner_since         : NaiveDate,
+    pub manufacturing_date  : NaiveDate
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VehicleDTO {
+    pub name                : String,
+    pub user_id             : Uuid,
+    pub vehicle_id          : Uuid,
+    pub created_at          : DateTime<Utc>,
+    pub vehicle_type        : String,
+    pub retired_at          : Option<DateTime<Utc>>,
+    pub brand               : String,
+    pub model               : String,
+    pub distance            : i32,
+    pub owner_since         : NaiveDate,
+    pub manufacturing_date  : NaiveDate,
+    pub picture             : Option<String>
+}
+
+/// Outcome of one vehicle of a batch, `index` being its position in the request body.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VehicleBatchItemDTO {
+    pub index               : usize,
+    pub status              : String,
+    pub vehicle             : Option<VehicleDTO>,
+    pub error               : Option<String>
+}
@@ -17,3 +17,75 @@ pub struct VehicleDTO {
     pub manufacturing_date  : NaiveDate,
     pub picture             : Option<String>
 }
+
+/// A vehicle holding only the fields requested through `fields`, the others being left out of the JSON.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct VehicleProjectionDTO {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name                : Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id             : Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vehicle_id          : Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at          : Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vehicle_type        : Option<String>,
+    /// `Some(None)` when requested for a vehicle still in use, written as `null`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retired_at          : Option<Option<DateTime<Utc>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brand               : Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model               : Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance            : Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_since         : Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manufacturing_date  : Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picture             : Option<Option<String>>
+}
+
+/// Outcome of one vehicle of a batch, `index` being its position in the request body.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VehicleBatchItemDTO {
+    pub index               : usize,
+    pub status              : String,
+    pub vehicle             : Option<VehicleDTO>,
+    pub error               : Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct LineErrorDTO {
+    pub line                : usize,
+    pub error               : String
+}
+
+/// Outcome of an import, `errors` listing the first lines that could not be imported.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ImportReportDTO {
+    pub imported            : usize,
+    pub failed              : usize,
+    pub errors              : Vec<LineErrorDTO>,
+    /// User and vehicle ids of the imported vehicles, for the audit log, left out of the answer.
+    #[serde(skip)]
+    pub imported_keys       : Vec<(Uuid, Uuid)>
+}
+
+/// Page of search results, `next` being the token to pass as `after` for the following page.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VehicleSearchDTO {
+    pub vehicles            : Vec<VehicleProjectionDTO>,
+    pub next                : Option<String>
+}
+
+/// Data of an event of the vehicle event stream, `vehicle` being left out of deletions.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VehicleEventDTO {
+    pub vehicle_id          : Uuid,
+    pub occurred_at         : DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vehicle             : Option<VehicleDTO>
+}
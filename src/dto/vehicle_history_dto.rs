@@ -0,0 +1,28 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use rocket::serde::uuid::Uuid;
+use rocket::serde::json::Value;
+use rocket::serde::{Serialize, Deserialize};
+
+use crate::dto::vehicle_dto::VehicleDTO;
+
+/// Value of a field before and after a save, `from` being null for a created vehicle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FieldChangeDTO {
+    pub from                : Value,
+    pub to                  : Value
+}
+
+/// A version of a vehicle: who saved it, when, what changed and the vehicle as saved.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VehicleVersionDTO {
+    pub version             : Uuid,
+    pub changed_at          : DateTime<Utc>,
+    pub actor               : String,
+    pub event               : String,
+    pub changes             : BTreeMap<String, FieldChangeDTO>,
+    pub vehicle             : VehicleDTO,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restored_from       : Option<Uuid>
+}
@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use rocket::serde::uuid::Uuid;
+use rocket::serde::{Serialize, Deserialize};
+
+use crate::dto::vehicle_dto::VehicleDTO;
+
+/// A webhook to create, `events` defaulting to vehicles being created, retired and transferred.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WebhookRequestDTO {
+    pub url                 : String,
+    pub events              : Option<Vec<String>>
+}
+
+/// A webhook, its `secret` only being answered when it is created.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WebhookDTO {
+    pub webhook_id          : Uuid,
+    pub url                 : String,
+    pub events              : Vec<String>,
+    pub created_at          : DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret              : Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WebhookDeliveryDTO {
+    pub delivery_id         : Uuid,
+    pub event               : String,
+    pub vehicle_id          : Uuid,
+    pub status              : String,
+    pub attempts            : i32,
+    pub response_status     : Option<i32>,
+    pub error               : Option<String>,
+    pub created_at          : DateTime<Utc>,
+    pub updated_at          : DateTime<Utc>
+}
+
+/// Body posted to a webhook, `vehicle` being left out of deletions and transfers.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WebhookPayloadDTO {
+    pub delivery_id         : Uuid,
+    pub event               : String,
+    pub user_id             : Uuid,
+    pub vehicle_id          : Uuid,
+    pub occurred_at         : DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vehicle             : Option<VehicleDTO>
+}
@@ -0,0 +1,31 @@
+use crate::domain::vehicle_event::VehicleEvent;
+
+/// Names the publishers are enabled by in the `outbox` section of `Rocket.toml`.
+pub const LOG_PUBLISHER: &str = "log";
+pub const BUS_PUBLISHER: &str = "bus";
+pub const WEBHOOK_PUBLISHER: &str = "webhook";
+
+/// Destination of the vehicle events relayed or fed from the outbox. An event may be published more than once,
+/// e.g. when the relay stops before moving its watermark, so publishers and their consumers tell repeated
+/// events apart by their `event_id`.
+#[async_trait]
+pub trait EventPublisher {
+    fn name(&self) -> &'static str;
+    /// Takes the event, failing when it should be published again later.
+    async fn publish(&self, event: &VehicleEvent) -> Result<(), String>;
+}
+
+/// Prints every event, for deployments with no other consumer to keep a trace of them.
+pub struct LogPublisher;
+
+#[async_trait]
+impl EventPublisher for LogPublisher {
+    fn name(&self) -> &'static str {
+        LOG_PUBLISHER
+    }
+
+    async fn publish(&self, event: &VehicleEvent) -> Result<(), String> {
+        println!("Vehicle event {} {} of vehicle {} of user {} at {}", event.event_id, event.kind.name(), event.vehicle_id, event.user_id, event.occurred_at);
+        Ok(())
+    }
+}
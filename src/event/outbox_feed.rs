@@ -0,0 +1,129 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use rocket::serde::uuid::Uuid;
+
+use crate::domain::outbox::OUTBOX_SHARDS;
+use crate::event::event_publisher::EventPublisher;
+use crate::event::outbox_relay::{self, OutboxSettings};
+use crate::mapper::outbox_mapper;
+use crate::repository::outbox_repository::OutboxRepository;
+
+/// Feeds the settled outbox entries of every shard to a publisher of this instance, such as the bus behind the
+/// event streams. Unlike the relay, the feed takes no lease and keeps its position in memory, so that every
+/// instance hands every event to its own publisher, starting with the events written after it started.
+pub struct OutboxFeed {
+    outbox_repository: Arc<dyn OutboxRepository + Sync + Send>,
+    publisher: Arc<dyn EventPublisher + Sync + Send>,
+    // Time and id of the last entry fed from each shard.
+    positions: Mutex<Vec<(i64, Uuid)>>,
+    settings: OutboxSettings
+}
+
+impl OutboxFeed {
+    pub fn new(outbox_repository: Arc<dyn OutboxRepository + Sync + Send>,
+               publisher: Arc<dyn EventPublisher + Sync + Send>,
+               settings: OutboxSettings) -> OutboxFeed {
+        let now = Utc::now().timestamp_millis();
+
+        OutboxFeed {
+            outbox_repository,
+            publisher,
+            positions: Mutex::new(vec!((now, Uuid::nil()); OUTBOX_SHARDS as usize)),
+            settings
+        }
+    }
+
+    /// Feeds the outbox every `poll_interval_ms` until the instance stops.
+    pub async fn run(self: Arc<Self>) {
+        let interval = Duration::from_millis(self.settings.poll_interval_ms);
+
+        loop {
+            for shard in 0..OUTBOX_SHARDS {
+                self.feed_shard(shard).await;
+            }
+            rocket::tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Hands the publisher the entries of the shard written since the last feed, returning how many of them it
+    /// was handed. An entry the publisher fails to take is not fed again.
+    pub async fn feed_shard(&self, shard: i32) -> usize {
+        let after = self.positions.lock().unwrap()[shard as usize];
+        let now = Utc::now().timestamp_millis();
+        let (entries, read_to) = outbox_relay::read_settled(self.outbox_repository.as_ref(), shard, after, now, &self.settings).await;
+
+        let mut fed = 0;
+        for entry in entries {
+            let event_id = entry.event_id;
+            let event = match outbox_mapper::get_vehicle_event(entry) {
+                Some(event) => event,
+                None => continue
+            };
+
+            match self.publisher.publish(&event).await {
+                Ok(_) => fed += 1,
+                Err(e) => println!("Failed to feed event {} to {} with error {}", event_id, self.publisher.name(), e)
+            }
+        }
+
+        self.positions.lock().unwrap()[shard as usize] = read_to;
+        fed
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::domain::outbox::OutboxEntry;
+    use crate::domain::vehicle_event::VehicleEventKind;
+    use crate::event::outbox_relay::tests::fixture as relay_fixture;
+    use crate::event::vehicle_event_log::{Replay, VehicleEventLog, VehicleEventSettings};
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn given_entries_following_position_when_feed_shard_twice_then_publishes_each_once_in_order() {
+        let event_log = Arc::new(VehicleEventLog::new(&VehicleEventSettings::default()));
+        let (last_event_id, _) = event_log.subscribe();
+        let feed = OutboxFeed::new(Arc::new(relay_fixture::outbox_repository(relay_fixture::watermark(0), fixture::entries())), event_log.clone(), OutboxSettings::default());
+        fixture::rewind(&feed, relay_fixture::minutes_ago(5));
+
+        assert_eq!(2, aw!(feed.feed_shard(3)));
+        assert_eq!(0, aw!(feed.feed_shard(3)));
+
+        match event_log.replay(Uuid::parse_str(relay_fixture::USER_ID_STR).unwrap(), last_event_id) {
+            Replay::Events(events) => assert_eq!(vec!(VehicleEventKind::Created, VehicleEventKind::Updated), events.iter().map(|event| event.kind).collect::<Vec<_>>()),
+            replay => panic!("Unexpected {:?}", replay)
+        }
+    }
+
+    #[test]
+    fn given_entries_written_before_start_when_feed_shard_then_publishes_nothing() {
+        let event_log = Arc::new(VehicleEventLog::new(&VehicleEventSettings::default()));
+        let feed = OutboxFeed::new(Arc::new(relay_fixture::outbox_repository(relay_fixture::watermark(0), fixture::entries())), event_log, OutboxSettings::default());
+
+        assert_eq!(0, aw!(feed.feed_shard(3)));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub fn entries() -> Vec<OutboxEntry> {
+            vec!(relay_fixture::entry(VehicleEventKind::Created, relay_fixture::minutes_ago(3)), relay_fixture::entry(VehicleEventKind::Updated, relay_fixture::minutes_ago(2)))
+        }
+
+        /// Moves every shard of the feed back to `occurred_at_ms`, as if it had started then.
+        pub fn rewind(feed: &OutboxFeed, occurred_at_ms: i64) {
+            for position in feed.positions.lock().unwrap().iter_mut() {
+                *position = (occurred_at_ms, Uuid::nil());
+            }
+        }
+    }
+}
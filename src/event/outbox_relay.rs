@@ -0,0 +1,427 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use lru::LruCache;
+use rocket::serde::Deserialize;
+use rocket::serde::uuid::Uuid;
+
+use crate::domain::outbox::{self, OutboxEntry, OutboxWatermark, OUTBOX_BUCKET_MS, OUTBOX_SHARDS, OUTBOX_TTL_MS};
+use crate::event::event_publisher::{EventPublisher, BUS_PUBLISHER, WEBHOOK_PUBLISHER};
+use crate::mapper::outbox_mapper;
+use crate::repository::outbox_repository::OutboxRepository;
+
+/// Outbox relay settings read from the `outbox` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct OutboxSettings {
+    pub enabled             : bool,
+    pub poll_interval_ms    : u64,
+    pub batch_size          : usize,
+    /// Names of the publishers every event is relayed to: `log` and `webhook`, or fed to by every instance: `bus`.
+    pub publishers          : Vec<String>,
+    pub dedup_capacity      : usize,
+    /// How long a shard stays leased to the instance relaying it, renewed at every poll.
+    pub lease_ms            : u64,
+    /// Age an entry must reach to be relayed, for the entries written concurrently with an earlier time to land first.
+    pub settle_ms           : u64
+}
+
+impl OutboxSettings {
+    /// Whether this instance relays events to the publisher named `name`.
+    pub fn publishes(&self, name: &str) -> bool {
+        self.enabled && self.publishers.iter().any(|publisher| publisher == name)
+    }
+}
+
+impl Default for OutboxSettings {
+    fn default() -> Self {
+        OutboxSettings {
+            enabled: true,
+            poll_interval_ms: 1000,
+            batch_size: 100,
+            publishers: vec!(BUS_PUBLISHER.to_string(), WEBHOOK_PUBLISHER.to_string()),
+            dedup_capacity: 10_000,
+            lease_ms: 10_000,
+            settle_ms: 5000
+        }
+    }
+}
+
+/// Relays the outbox entries to the publishers, oldest first within each shard. Each shard is leased to a single
+/// instance at a time, which relays its entries following the watermark of the shard and moves the watermark past
+/// those every publisher took. Entries are never deleted, they expire. Delivery is at least once: entries are
+/// relayed again when a publisher fails, or when the instance stops or loses the lease before moving the watermark.
+/// The relay remembers the publishers it recently handed each event to, so a publisher that took an event is not
+/// handed it again while another one is retried.
+pub struct OutboxRelay {
+    outbox_repository: Arc<dyn OutboxRepository + Sync + Send>,
+    publishers: Vec<Arc<dyn EventPublisher + Sync + Send>>,
+    published: Mutex<LruCache<(Uuid, &'static str), ()>>,
+    /// Names this instance as the owner of the shards it leases.
+    instance_id: Uuid,
+    settings: OutboxSettings
+}
+
+impl OutboxRelay {
+    pub fn new(outbox_repository: Arc<dyn OutboxRepository + Sync + Send>,
+               publishers: Vec<Arc<dyn EventPublisher + Sync + Send>>,
+               settings: OutboxSettings) -> OutboxRelay {
+        OutboxRelay {
+            outbox_repository,
+            publishers,
+            published: Mutex::new(LruCache::new(settings.dedup_capacity.max(1))),
+            instance_id: Uuid::new_v4(),
+            settings
+        }
+    }
+
+    /// Relays the outbox every `poll_interval_ms` until the instance stops.
+    pub async fn run(self: Arc<Self>) {
+        let interval = Duration::from_millis(self.settings.poll_interval_ms);
+
+        loop {
+            for shard in 0..OUTBOX_SHARDS {
+                self.relay_shard(shard).await;
+            }
+            rocket::tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Relays the entries of the shard following its watermark, once this instance holds its lease, returning how
+    /// many of them were relayed. It stops at the first entry that could not be relayed, keeping the events of each
+    /// user in order.
+    pub async fn relay_shard(&self, shard: i32) -> usize {
+        let now = Utc::now().timestamp_millis();
+        let watermark = match self.lease(shard, now).await {
+            Some(watermark) => watermark,
+            None => return 0
+        };
+
+        // A shard never relayed starts with the entries that did not expire yet.
+        let start = match (watermark.occurred_at_ms, watermark.event_id) {
+            (Some(occurred_at_ms), Some(event_id)) => (occurred_at_ms, event_id),
+            _ => (now - OUTBOX_TTL_MS, Uuid::nil())
+        };
+        let (entries, read_to) = read_settled(self.outbox_repository.as_ref(), shard, start, now, &self.settings).await;
+
+        let mut after = start;
+        let mut relayed = 0;
+        for entry in &entries {
+            if !self.relay(entry).await {
+                break;
+            }
+            after = (entry.occurred_at_ms, entry.event_id);
+            relayed += 1;
+        }
+        if relayed == entries.len() {
+            after = read_to;
+        }
+
+        if after != start {
+            self.outbox_repository.save_watermark(shard, self.instance_id, after.0, after.1).await;
+        }
+        relayed
+    }
+
+    /// Watermark of the shard once this instance leased it for `lease_ms`, renewing its own lease, `None` while
+    /// another instance holds it.
+    async fn lease(&self, shard: i32, now: i64) -> Option<OutboxWatermark> {
+        let watermark = self.outbox_repository.get_watermark(shard).await?;
+
+        let held_by_other = watermark.owner != Some(self.instance_id) && watermark.lease_until_ms.map_or(false, |lease_until_ms| lease_until_ms > now);
+        if held_by_other {
+            return None;
+        }
+
+        match self.outbox_repository.lease_shard(&watermark, self.instance_id, now + self.settings.lease_ms as i64).await {
+            Some(true) => Some(watermark),
+            _ => None
+        }
+    }
+
+    async fn relay(&self, entry: &OutboxEntry) -> bool {
+        let event = match outbox_mapper::get_vehicle_event(entry.clone()) {
+            Some(event) => event,
+            None => {
+                println!("Skipping unreadable OutboxEntry {} of event {}", entry.event_id, entry.event);
+                return true;
+            }
+        };
+
+        let mut relayed = true;
+        for publisher in &self.publishers {
+            let key = (event.event_id, publisher.name());
+            if self.published.lock().unwrap().contains(&key) {
+                continue;
+            }
+
+            match publisher.publish(&event).await {
+                Ok(_) => { self.published.lock().unwrap().put(key, ()); },
+                Err(e) => {
+                    println!("Failed to publish event {} to {} with error {}", event.event_id, publisher.name(), e);
+                    relayed = false;
+                }
+            }
+        }
+        relayed
+    }
+}
+
+/// Entries of a shard following `after`, the time and id of an entry, oldest first, along with the position the
+/// shard is read to once they are all handled. A page of each bucket is read in turn, up to the entries written
+/// `settle_ms` before `now`.
+pub async fn read_settled(outbox_repository: &(dyn OutboxRepository + Sync + Send), shard: i32, after: (i64, Uuid), now: i64,
+                          settings: &OutboxSettings) -> (Vec<OutboxEntry>, (i64, Uuid)) {
+    let until = now - settings.settle_ms as i64;
+    let mut entries = Vec::new();
+    let mut after = after;
+
+    loop {
+        let bucket = outbox::bucket(after.0);
+        let page = match outbox_repository.get_entries(shard, bucket, Some(after), settings.batch_size).await {
+            Some(page) => page,
+            None => break
+        };
+        let read_all = page.len() < settings.batch_size;
+
+        for entry in page {
+            if entry.occurred_at_ms > until {
+                return (entries, after);
+            }
+            after = (entry.occurred_at_ms, entry.event_id);
+            entries.push(entry);
+        }
+
+        // The next bucket is read once this one was read to its end, unless it holds unsettled entries.
+        let next = (bucket + 1) * OUTBOX_BUCKET_MS;
+        if !read_all || next > until {
+            break;
+        }
+        after = (next, Uuid::nil());
+    }
+
+    (entries, after)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use mockall::mock;
+
+    use crate::domain::vehicle_event::{VehicleEvent, VehicleEventKind};
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    mock! {
+        pub OutboxRepositoryImpl {}
+
+        #[async_trait]
+        impl OutboxRepository for OutboxRepositoryImpl {
+            async fn get_entries(&self, shard: i32, bucket: i64, after: Option<(i64, Uuid)>, limit: usize) -> Option<Vec<OutboxEntry>>;
+            async fn get_watermark(&self, shard: i32) -> Option<OutboxWatermark>;
+            async fn lease_shard(&self, watermark: &OutboxWatermark, owner: Uuid, lease_until_ms: i64) -> Option<bool>;
+            async fn save_watermark(&self, shard: i32, owner: Uuid, occurred_at_ms: i64, event_id: Uuid) -> Option<bool>;
+        }
+    }
+
+    /// Records the events it is handed, failing the first `failures` of them.
+    pub struct RecordingPublisher {
+        name: &'static str,
+        failures: Mutex<usize>,
+        events: Mutex<Vec<Uuid>>
+    }
+
+    #[async_trait]
+    impl EventPublisher for RecordingPublisher {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn publish(&self, event: &VehicleEvent) -> Result<(), String> {
+            self.events.lock().unwrap().push(event.event_id);
+
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err("unavailable".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn given_entries_after_watermark_when_relay_shard_then_publishes_them_in_order_and_moves_watermark() {
+        let entries = vec!(fixture::entry(VehicleEventKind::Created, fixture::minutes_ago(3)), fixture::entry(VehicleEventKind::Updated, fixture::minutes_ago(2)));
+        let event_ids: Vec<Uuid> = entries.iter().map(|entry| entry.event_id).collect();
+        let last = entries[1].clone();
+        let mut outbox_repository = fixture::outbox_repository(fixture::watermark(fixture::minutes_ago(4)), entries);
+
+        outbox_repository.expect_save_watermark()
+            .withf(move |shard: &i32, _, occurred_at_ms: &i64, event_id: &Uuid| *shard == 3 && *occurred_at_ms == last.occurred_at_ms && *event_id == last.event_id)
+            .times(1)
+            .returning(|_, _, _, _| Some(true));
+
+        let publisher = fixture::publisher("bus", 0);
+        let relay = OutboxRelay::new(Arc::new(outbox_repository), vec!(publisher.clone()), OutboxSettings::default());
+
+        assert_eq!(2, aw!(relay.relay_shard(3)));
+        assert_eq!(event_ids, *publisher.events.lock().unwrap());
+    }
+
+    #[test]
+    fn given_failing_publisher_when_relay_shard_twice_then_keeps_watermark_and_only_retries_that_publisher() {
+        let entry = fixture::entry(VehicleEventKind::Retired, fixture::minutes_ago(2));
+        let event_id = entry.event_id;
+        let mut outbox_repository = fixture::outbox_repository(fixture::watermark(fixture::minutes_ago(4)), vec!(entry));
+
+        outbox_repository.expect_save_watermark()
+            .withf(move |_, _, _, saved_event_id: &Uuid| *saved_event_id == event_id)
+            .times(1)
+            .returning(|_, _, _, _| Some(true));
+
+        let bus = fixture::publisher("bus", 0);
+        let webhook = fixture::publisher("webhook", 1);
+        let relay = OutboxRelay::new(Arc::new(outbox_repository), vec!(bus.clone(), webhook.clone()), OutboxSettings::default());
+
+        assert_eq!(0, aw!(relay.relay_shard(3)));
+        assert_eq!(1, aw!(relay.relay_shard(3)));
+        assert_eq!(vec!(event_id), *bus.events.lock().unwrap());
+        assert_eq!(vec!(event_id, event_id), *webhook.events.lock().unwrap());
+    }
+
+    #[test]
+    fn given_unreadable_entry_when_relay_shard_then_skips_it_without_publishing() {
+        let entry = OutboxEntry { event: "repainted".to_string(), ..fixture::entry(VehicleEventKind::Created, fixture::minutes_ago(2)) };
+        let mut outbox_repository = fixture::outbox_repository(fixture::watermark(fixture::minutes_ago(4)), vec!(entry));
+
+        outbox_repository.expect_save_watermark()
+            .times(1)
+            .returning(|_, _, _, _| Some(true));
+
+        let publisher = fixture::publisher("bus", 0);
+        let relay = OutboxRelay::new(Arc::new(outbox_repository), vec!(publisher.clone()), OutboxSettings::default());
+
+        assert_eq!(1, aw!(relay.relay_shard(3)));
+        assert!(publisher.events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn given_entry_younger_than_settle_delay_when_relay_shard_then_leaves_it_for_a_later_poll() {
+        let entry = fixture::entry(VehicleEventKind::Created, Utc::now().timestamp_millis());
+        let mut outbox_repository = fixture::outbox_repository(fixture::watermark(entry.occurred_at_ms - 1), vec!(entry));
+
+        outbox_repository.expect_save_watermark().times(0);
+
+        let publisher = fixture::publisher("bus", 0);
+        let relay = OutboxRelay::new(Arc::new(outbox_repository), vec!(publisher.clone()), OutboxSettings::default());
+
+        assert_eq!(0, aw!(relay.relay_shard(3)));
+        assert!(publisher.events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn given_empty_buckets_after_watermark_when_relay_shard_then_moves_watermark_to_current_bucket() {
+        let mut outbox_repository = fixture::outbox_repository(fixture::watermark(Utc::now().timestamp_millis() - 3 * OUTBOX_BUCKET_MS), vec!());
+        let until = Utc::now().timestamp_millis() - OutboxSettings::default().settle_ms as i64;
+
+        outbox_repository.expect_save_watermark()
+            .withf(move |_, _, occurred_at_ms: &i64, event_id: &Uuid| outbox::bucket(*occurred_at_ms) == outbox::bucket(until)
+                && *occurred_at_ms % OUTBOX_BUCKET_MS == 0 && event_id.is_nil())
+            .times(1)
+            .returning(|_, _, _, _| Some(true));
+
+        let relay = OutboxRelay::new(Arc::new(outbox_repository), vec!(fixture::publisher("bus", 0)), OutboxSettings::default());
+
+        assert_eq!(0, aw!(relay.relay_shard(3)));
+    }
+
+    #[test]
+    fn given_shard_leased_by_another_instance_when_relay_shard_then_reads_nothing() {
+        let mut outbox_repository = MockOutboxRepositoryImpl::new();
+
+        outbox_repository.expect_get_watermark()
+            .times(1)
+            .returning(|_| Some(OutboxWatermark { owner: Some(Uuid::new_v4()), lease_until_ms: Some(Utc::now().timestamp_millis() + 60_000), ..fixture::watermark(0) }));
+        outbox_repository.expect_lease_shard().times(0);
+        outbox_repository.expect_get_entries().times(0);
+
+        let relay = OutboxRelay::new(Arc::new(outbox_repository), vec!(fixture::publisher("bus", 0)), OutboxSettings::default());
+
+        assert_eq!(0, aw!(relay.relay_shard(3)));
+    }
+
+    #[test]
+    fn given_lease_taken_over_when_relay_shard_then_reads_nothing() {
+        let mut outbox_repository = MockOutboxRepositoryImpl::new();
+
+        outbox_repository.expect_get_watermark()
+            .times(1)
+            .returning(|_| Some(OutboxWatermark { owner: Some(Uuid::new_v4()), lease_until_ms: Some(5000), ..fixture::watermark(0) }));
+        outbox_repository.expect_lease_shard()
+            .withf(|watermark: &OutboxWatermark, _, _| watermark.lease_until_ms == Some(5000))
+            .times(1)
+            .returning(|_, _, _| Some(false));
+        outbox_repository.expect_get_entries().times(0);
+
+        let relay = OutboxRelay::new(Arc::new(outbox_repository), vec!(fixture::publisher("bus", 0)), OutboxSettings::default());
+
+        assert_eq!(0, aw!(relay.relay_shard(3)));
+    }
+
+    pub mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+
+        pub fn minutes_ago(minutes: i64) -> i64 {
+            Utc::now().timestamp_millis() - minutes * 60_000
+        }
+
+        pub fn entry(kind: VehicleEventKind, occurred_at_ms: i64) -> OutboxEntry {
+            let user_id = Uuid::parse_str(USER_ID_STR).unwrap();
+            OutboxEntry {
+                shard: 3,
+                bucket: outbox::bucket(occurred_at_ms),
+                occurred_at_ms,
+                ..outbox_mapper::get_outbox_entry(kind, user_id, Uuid::new_v4(), None)
+            }
+        }
+
+        pub fn watermark(occurred_at_ms: i64) -> OutboxWatermark {
+            OutboxWatermark { shard: 3, occurred_at_ms: Some(occurred_at_ms), event_id: Some(Uuid::nil()), owner: None, lease_until_ms: None }
+        }
+
+        /// Outbox of shard 3 holding `entries`, read like Cassandra reads the partition of a bucket, leased to
+        /// whichever instance asks.
+        pub fn outbox_repository(watermark: OutboxWatermark, entries: Vec<OutboxEntry>) -> MockOutboxRepositoryImpl {
+            let mut outbox_repository = MockOutboxRepositoryImpl::new();
+
+            outbox_repository.expect_get_watermark()
+                .returning(move |_| Some(watermark.clone()));
+            outbox_repository.expect_lease_shard()
+                .returning(|_, _, _| Some(true));
+            outbox_repository.expect_get_entries()
+                .withf(|shard: &i32, _, _, limit: &usize| *shard == 3 && *limit == 100)
+                .returning(move |_, bucket, after, limit| Some(entries.iter()
+                    .filter(|entry| entry.bucket == bucket && after.map_or(true, |after| (entry.occurred_at_ms, entry.event_id) > after))
+                    .take(limit)
+                    .cloned()
+                    .collect()));
+
+            outbox_repository
+        }
+
+        pub fn publisher(name: &'static str, failures: usize) -> Arc<RecordingPublisher> {
+            Arc::new(RecordingPublisher {
+                name,
+                failures: Mutex::new(failures),
+                events: Mutex::new(vec!())
+            })
+        }
+    }
+}
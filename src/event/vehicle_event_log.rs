@@ -0,0 +1,292 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use lru::LruCache;
+use rocket::serde::Deserialize;
+use rocket::serde::uuid::Uuid;
+use rocket::tokio::sync::broadcast;
+
+use crate::domain::vehicle_event::VehicleEvent;
+use crate::event::event_publisher::{EventPublisher, BUS_PUBLISHER};
+
+/// Events buffered for subscribers slower than the others, which then catch up from the log.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Event log settings read from the `events.vehicle` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct VehicleEventSettings {
+    pub capacity            : usize,
+    pub users               : usize,
+    pub heartbeat_ms        : u64
+}
+
+impl Default for VehicleEventSettings {
+    fn default() -> Self {
+        VehicleEventSettings {
+            capacity: 50,
+            users: 1000,
+            heartbeat_ms: 15_000
+        }
+    }
+}
+
+/// What a subscriber resuming after an event missed since.
+#[derive(Debug)]
+pub enum Replay {
+    Events(Vec<VehicleEvent>),
+    /// Some of the missed events are no longer kept, the subscriber having to read the vehicles again,
+    /// after which it is up to date with the event of this id.
+    Reset(u64)
+}
+
+#[derive(Default)]
+struct UserLog {
+    events: VecDeque<VehicleEvent>,
+    // Id of the last event dropped to keep the log within its capacity.
+    dropped_up_to: u64
+}
+
+impl UserLog {
+    fn last_id(&self) -> u64 {
+        self.events.back().map_or(self.dropped_up_to, |event| event.id)
+    }
+}
+
+struct Log {
+    next_id: u64,
+    // Ids given out by this log all follow it.
+    base_id: u64,
+    // Id of the last event of any user whose log was dropped to keep within `users` logs.
+    forgotten_up_to: u64,
+    users: LruCache<Uuid, UserLog>
+}
+
+/// Bounded in-memory log of the last `capacity` vehicle events of the last `users` users with changes,
+/// published to every subscriber as they happen. Ids increase with every event and carry a random tag of
+/// the log in their high 32 bits, so that an id given out by a previous process or another instance resets
+/// the subscriber instead of being mistaken for one of this log. Every instance is fed all events by its outbox feed.
+pub struct VehicleEventLog {
+    capacity: usize,
+    heartbeat: Duration,
+    log: Mutex<Log>,
+    sender: broadcast::Sender<VehicleEvent>
+}
+
+impl VehicleEventLog {
+    pub fn new(settings: &VehicleEventSettings) -> VehicleEventLog {
+        let base_id = ((Uuid::new_v4().as_u128() as u32 | 0x8000_0000) as u64) << 32;
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+        VehicleEventLog {
+            capacity: settings.capacity.max(1),
+            heartbeat: Duration::from_millis(settings.heartbeat_ms),
+            log: Mutex::new(Log {
+                next_id: base_id + 1,
+                base_id,
+                forgotten_up_to: 0,
+                users: LruCache::new(settings.users.max(1))
+            }),
+            sender
+        }
+    }
+
+    /// Interval of the comments keeping idle event streams open through proxies.
+    pub fn heartbeat(&self) -> Duration {
+        self.heartbeat
+    }
+
+    /// Appends an event to the log of its user and sends it to every subscriber, numbering it with the next `id`.
+    pub fn publish(&self, event: VehicleEvent) {
+        let mut log = self.log.lock().unwrap();
+        let user_id = event.user_id;
+
+        let event = VehicleEvent { id: log.next_id, ..event };
+        log.next_id += 1;
+
+        if !log.users.contains(&user_id) && log.users.len() == log.users.cap() {
+            if let Some((_, forgotten)) = log.users.pop_lru() {
+                log.forgotten_up_to = log.forgotten_up_to.max(forgotten.last_id());
+            }
+        }
+        if !log.users.contains(&user_id) {
+            log.users.put(user_id, UserLog::default());
+        }
+        if let Some(user_log) = log.users.get_mut(&user_id) {
+            user_log.events.push_back(event.clone());
+            while user_log.events.len() > self.capacity {
+                if let Some(dropped) = user_log.events.pop_front() {
+                    user_log.dropped_up_to = dropped.id;
+                }
+            }
+        }
+
+        // Sent holding the lock, so that subscribers receive events in the order of their ids and every
+        // event following the id they subscribed at. Failing only when nobody is subscribed.
+        let _ = self.sender.send(event);
+    }
+
+    /// Events published from now on, for every user, following the event of the id returned with them.
+    pub fn subscribe(&self) -> (u64, broadcast::Receiver<VehicleEvent>) {
+        let log = self.log.lock().unwrap();
+
+        (log.next_id - 1, self.sender.subscribe())
+    }
+
+    /// Events of a user following the one with `last_event_id`, or a reset when some of them are gone.
+    pub fn replay(&self, user_id: Uuid, last_event_id: u64) -> Replay {
+        let mut log = self.log.lock().unwrap();
+        let latest_id = log.next_id - 1;
+
+        if last_event_id >> 32 != log.base_id >> 32 || last_event_id > latest_id {
+            return Replay::Reset(latest_id);
+        }
+
+        let forgotten_up_to = log.forgotten_up_to;
+        match log.users.get(&user_id) {
+            Some(user_log) if last_event_id < user_log.dropped_up_to => Replay::Reset(latest_id),
+            Some(user_log) => Replay::Events(user_log.events.iter()
+                .filter(|event| event.id > last_event_id)
+                .cloned()
+                .collect()),
+            None if last_event_id < forgotten_up_to => Replay::Reset(latest_id),
+            None => Replay::Events(vec!())
+        }
+    }
+}
+
+/// The in-memory bus feeding the event streams of this instance.
+#[async_trait]
+impl EventPublisher for VehicleEventLog {
+    fn name(&self) -> &'static str {
+        BUS_PUBLISHER
+    }
+
+    async fn publish(&self, event: &VehicleEvent) -> Result<(), String> {
+        VehicleEventLog::publish(self, event.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    use crate::domain::vehicle_event::VehicleEventKind;
+
+    #[test]
+    fn when_replay_then_returns_events_of_user_following_last_event_id() {
+        let event_log = VehicleEventLog::new(&VehicleEventSettings::default());
+        event_log.publish(fixture::event(fixture::user_id(), VehicleEventKind::Created));
+        event_log.publish(fixture::event(fixture::other_user_id(), VehicleEventKind::Created));
+        event_log.publish(fixture::event(fixture::user_id(), VehicleEventKind::Retired));
+
+        let first_id = match event_log.replay(fixture::user_id(), fixture::base_id(&event_log)) {
+            Replay::Events(events) => {
+                assert_eq!(vec!(VehicleEventKind::Created, VehicleEventKind::Retired), events.iter().map(|event| event.kind).collect::<Vec<_>>());
+                events[0].id
+            },
+            replay => panic!("Unexpected {:?}", replay)
+        };
+
+        match event_log.replay(fixture::user_id(), first_id) {
+            Replay::Events(events) => assert_eq!(vec!(first_id + 2), events.iter().map(|event| event.id).collect::<Vec<_>>()),
+            replay => panic!("Unexpected {:?}", replay)
+        }
+    }
+
+    #[test]
+    fn given_events_dropped_beyond_capacity_when_replay_then_resets_to_latest_event() {
+        let event_log = VehicleEventLog::new(&VehicleEventSettings { capacity: 2, ..VehicleEventSettings::default() });
+        let base_id = fixture::base_id(&event_log);
+        for _ in 0..4 {
+            event_log.publish(fixture::event(fixture::user_id(), VehicleEventKind::Updated));
+        }
+
+        assert!(matches!(event_log.replay(fixture::user_id(), base_id), Replay::Reset(latest_id) if latest_id == base_id + 4));
+        assert!(matches!(event_log.replay(fixture::user_id(), base_id + 2), Replay::Events(events) if events.len() == 2));
+    }
+
+    #[test]
+    fn given_user_log_dropped_beyond_users_when_replay_then_resets() {
+        let event_log = VehicleEventLog::new(&VehicleEventSettings { users: 1, ..VehicleEventSettings::default() });
+        let base_id = fixture::base_id(&event_log);
+        event_log.publish(fixture::event(fixture::user_id(), VehicleEventKind::Created));
+        event_log.publish(fixture::event(fixture::user_id(), VehicleEventKind::Updated));
+        event_log.publish(fixture::event(fixture::other_user_id(), VehicleEventKind::Created));
+
+        assert!(matches!(event_log.replay(fixture::user_id(), base_id + 1), Replay::Reset(_)));
+        assert!(matches!(event_log.replay(fixture::user_id(), base_id + 2), Replay::Events(events) if events.is_empty()));
+    }
+
+    #[test]
+    fn given_id_of_previous_process_when_replay_then_resets() {
+        let event_log = VehicleEventLog::new(&VehicleEventSettings::default());
+
+        assert!(matches!(event_log.replay(fixture::user_id(), 5), Replay::Reset(_)));
+        assert!(matches!(event_log.replay(fixture::user_id(), u64::MAX), Replay::Reset(_)));
+    }
+
+    #[test]
+    fn given_id_of_other_instance_when_replay_then_resets() {
+        let event_log = VehicleEventLog::new(&VehicleEventSettings::default());
+        let other_event_log = VehicleEventLog::new(&VehicleEventSettings::default());
+        for _ in 0..2 {
+            event_log.publish(fixture::event(fixture::user_id(), VehicleEventKind::Updated));
+        }
+        other_event_log.publish(fixture::event(fixture::user_id(), VehicleEventKind::Updated));
+
+        assert!(matches!(event_log.replay(fixture::user_id(), fixture::base_id(&other_event_log) + 1), Replay::Reset(latest_id) if latest_id == fixture::base_id(&event_log) + 2));
+    }
+
+    #[test]
+    fn given_subscriber_when_publish_then_receives_event() {
+        let event_log = VehicleEventLog::new(&VehicleEventSettings::default());
+        let (_, mut receiver) = event_log.subscribe();
+
+        event_log.publish(fixture::event(fixture::user_id(), VehicleEventKind::Deleted));
+
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(fixture::user_id(), event.user_id);
+        assert_eq!(VehicleEventKind::Deleted, event.kind);
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const OTHER_USER_ID_STR: &str = "6176bc4b-33b6-4c9c-a4ad-c65da1322a80";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn other_user_id() -> Uuid {
+            Uuid::parse_str(OTHER_USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn event(user_id: Uuid, kind: VehicleEventKind) -> VehicleEvent {
+            VehicleEvent {
+                id: 0,
+                event_id: Uuid::new_v4(),
+                user_id,
+                vehicle_id: vehicle_id(),
+                kind,
+                occurred_at: Utc::now(),
+                vehicle: None
+            }
+        }
+
+        /// Id of the event preceding the first one published, as if a subscriber had seen none of them.
+        pub fn base_id(event_log: &VehicleEventLog) -> u64 {
+            event_log.log.lock().unwrap().base_id
+        }
+    }
+}
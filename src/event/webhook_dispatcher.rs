@@ -0,0 +1,692 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use rocket::serde::Deserialize;
+use rocket::serde::uuid::Uuid;
+use rocket::tokio;
+use sha2::Sha256;
+
+use crate::dao::retry_policy::{RetryPolicy, RetrySettings};
+use crate::domain::vehicle_event::VehicleEvent;
+use crate::domain::webhook;
+use crate::domain::webhook::{PendingDelivery, Webhook, WebhookDelivery, DELIVERY_DEAD_LETTERED, DELIVERY_DELIVERED, DELIVERY_PENDING, PENDING_DELIVERY_SHARDS};
+use crate::event::event_publisher::{EventPublisher, WEBHOOK_PUBLISHER};
+use crate::mapper::webhook_mapper;
+use crate::repository::webhook_repository::WebhookRepository;
+
+/// `sha256=` followed by the hex HMAC-SHA256, keyed with the webhook secret, of `<timestamp>.<body>`.
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+/// Seconds since the epoch when the attempt was signed, letting partners reject replayed deliveries.
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+/// Id of the event, the same on every attempt and relay of a delivery, for partners to discard the ones
+/// they already processed.
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+/// Pending deliveries read at once when looking for the ones to resume.
+const RESUME_PAGE_SIZE: usize = 100;
+
+/// Webhook settings read from the `webhooks` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct WebhookSettings {
+    pub timeout_ms          : u64,
+    /// How long a pending delivery stays with the instance attempting it past its next attempt, longer than
+    /// `timeout_ms`, before another instance may resume it.
+    pub lease_ms            : u64,
+    pub resume_interval_ms  : u64,
+    /// Lets webhooks be posted to loopback, private and link-local addresses, for local partners only.
+    pub allow_private_addresses: bool,
+    pub retry               : RetrySettings
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        WebhookSettings {
+            timeout_ms: 5000,
+            lease_ms: 60_000,
+            resume_interval_ms: 60_000,
+            allow_private_addresses: false,
+            retry: RetrySettings {
+                base_delay_ms: 1000,
+                max_delay_ms: 600_000,
+                max_attempts: 8,
+                jitter: true
+            }
+        }
+    }
+}
+
+/// Posts the vehicle events of each user to the webhooks subscribed to them, retrying failed attempts with
+/// exponential backoff and dead-lettering deliveries once `max_attempts` are exhausted.
+///
+/// Events are handed over by the outbox relay once their deliveries are stored. Each pending delivery is leased
+/// to the instance attempting it, which extends the lease over every retry delay; deliveries whose lease ran
+/// out, because their instance stopped, are resumed by `resume`.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    webhook_repository: Arc<dyn WebhookRepository + Sync + Send>,
+    client: reqwest::Client,
+    retry_policy: RetryPolicy,
+    lease_ms: i64,
+    allow_private_addresses: bool
+}
+
+impl WebhookDispatcher {
+    /// Unless `allow_private_addresses`, webhook hosts are resolved to their public addresses only, so that a
+    /// name pointed at an internal address after the webhook was created is not posted to. Redirects are not
+    /// followed, as they could lead anywhere, and proxies set in the environment are not used, as they would
+    /// resolve the host themselves.
+    pub fn new(webhook_repository: Arc<dyn WebhookRepository + Sync + Send>, settings: WebhookSettings) -> WebhookDispatcher {
+        let mut builder = reqwest::Client::builder()
+            .timeout(StdDuration::from_millis(settings.timeout_ms))
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy();
+        if !settings.allow_private_addresses {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = builder.build()
+            .unwrap_or_else(|e| panic!("Invalid webhooks settings: {}", e));
+
+        WebhookDispatcher {
+            webhook_repository,
+            client,
+            retry_policy: RetryPolicy::new(settings.retry),
+            lease_ms: settings.lease_ms as i64,
+            allow_private_addresses: settings.allow_private_addresses
+        }
+    }
+
+    /// Pending deliveries of the event to the webhooks of its user subscribed to it, stored and leased to this
+    /// instance before any attempt, or `None` when they could not all be stored. Keyed by the event, a delivery
+    /// stored again when the event is relayed again overwrites the previous one.
+    pub async fn deliveries(&self, event: &VehicleEvent) -> Option<Vec<(Webhook, WebhookDelivery, PendingDelivery)>> {
+        let webhooks = self.webhook_repository.get_webhooks(event.user_id).await?;
+
+        let mut deliveries = Vec::new();
+        for webhook in webhooks.into_iter().filter(|webhook| webhook.events.iter().any(|name| name == event.kind.name())) {
+            let created_at = Duration::seconds(event.occurred_at.timestamp());
+            let payload = serde_json::to_string(&webhook_mapper::get_payload_dto(event.event_id, event.clone()))
+                .unwrap_or_else(|e| panic!("Failed to serialize webhook payload {} with error {:?}", event.event_id, e));
+
+            let delivery = WebhookDelivery {
+                webhook_id: webhook.webhook_id,
+                created_at,
+                delivery_id: event.event_id,
+                user_id: event.user_id,
+                vehicle_id: event.vehicle_id,
+                event: event.kind.name().to_string(),
+                payload,
+                status: DELIVERY_PENDING.to_string(),
+                attempts: 0,
+                response_status: None,
+                error: None,
+                updated_at: Duration::seconds(Utc::now().timestamp())
+            };
+
+            let lease_until_ms = Utc::now().timestamp_millis() + self.lease_ms;
+            let delivery = self.webhook_repository.start_delivery(delivery, lease_until_ms).await?;
+            let pending = webhook_mapper::get_pending_delivery(&delivery, lease_until_ms);
+            deliveries.push((webhook, delivery, pending));
+        }
+
+        Some(deliveries)
+    }
+
+    /// Attempts the delivery until the webhook answers with a 2xx status, recording the outcome of every attempt.
+    /// A resumed delivery carries on with the delays left after its past attempts. A webhook deleted in between
+    /// retries dead-letters its delivery, a delivery erased in between is dropped, and a delivery taken over by
+    /// another instance is left to it.
+    pub async fn deliver(&self, webhook: &Webhook, mut delivery: WebhookDelivery, mut pending: PendingDelivery) -> WebhookDelivery {
+        let mut delays = self.retry_policy.delays().skip(delivery.attempts.max(0) as usize);
+
+        loop {
+            let (response_status, error) = self.attempt(webhook, &delivery).await;
+            let delivered = response_status.map_or(false, |status| (200..300).contains(&status));
+
+            delivery.attempts += 1;
+            delivery.response_status = response_status;
+            delivery.error = error;
+            delivery.updated_at = Duration::seconds(Utc::now().timestamp());
+
+            let delay = if delivered { None } else { delays.next() };
+            delivery.status = match (delivered, delay) {
+                (true, _) => DELIVERY_DELIVERED,
+                (false, Some(_)) => DELIVERY_PENDING,
+                (false, None) => DELIVERY_DEAD_LETTERED
+            }.to_string();
+
+            self.webhook_repository.save_delivery(delivery.clone()).await;
+
+            let delay = match delay {
+                Some(delay) => delay,
+                None => return delivery
+            };
+
+            let lease_until_ms = Utc::now().timestamp_millis() + delay.as_millis() as i64 + self.lease_ms;
+            match self.webhook_repository.lease_delivery(&pending, lease_until_ms).await {
+                Some(true) => pending.lease_until_ms = lease_until_ms,
+                Some(false) => return delivery,
+                // Attempted again anyway, at worst along with the instance resuming it.
+                None => ()
+            }
+            tokio::time::sleep(delay).await;
+
+            // Erased along with the data of its user, so neither attempted nor written back.
+            if self.webhook_repository.get_delivery(delivery.webhook_id, delivery.created_at, delivery.delivery_id).await.is_none() {
+                self.webhook_repository.delete_pending_delivery(&pending).await;
+                return delivery;
+            }
+            if self.webhook_repository.get_webhook(webhook.user_id, webhook.webhook_id).await.is_none() {
+                delivery.status = DELIVERY_DEAD_LETTERED.to_string();
+                delivery.error = Some("Webhook deleted".to_string());
+                self.webhook_repository.save_delivery(delivery.clone()).await;
+                return delivery;
+            }
+        }
+    }
+
+    /// Takes over the pending deliveries of the shard whose lease ran out at `now_ms` and resumes them on their
+    /// own tasks, returning how many were resumed. Deliveries of deleted webhooks are dead-lettered.
+    pub async fn resume(&self, shard: i32, now_ms: i64) -> usize {
+        let mut resumed = 0;
+        let mut after = None;
+
+        loop {
+            let page = match self.webhook_repository.get_pending_deliveries(shard, after, RESUME_PAGE_SIZE).await {
+                Some(page) => page,
+                None => return resumed
+            };
+            after = page.last().cloned();
+
+            for pending in page.iter().filter(|pending| pending.lease_until_ms <= now_ms) {
+                let lease_until_ms = now_ms + self.lease_ms;
+                if self.webhook_repository.lease_delivery(pending, lease_until_ms).await != Some(true) {
+                    continue;
+                }
+                let pending = PendingDelivery { lease_until_ms, ..pending.clone() };
+
+                if self.resume_delivery(pending).await {
+                    resumed += 1;
+                }
+            }
+
+            if page.len() < RESUME_PAGE_SIZE {
+                return resumed;
+            }
+        }
+    }
+
+    async fn resume_delivery(&self, pending: PendingDelivery) -> bool {
+        let delivery = match self.webhook_repository.get_delivery(pending.webhook_id, pending.created_at, pending.delivery_id).await {
+            Some(delivery) if delivery.status == DELIVERY_PENDING => delivery,
+            // Ended without its lease being removed, or erased along with its webhook.
+            _ => {
+                self.webhook_repository.delete_pending_delivery(&pending).await;
+                return false;
+            }
+        };
+
+        match self.webhook_repository.get_webhook(pending.user_id, pending.webhook_id).await {
+            Some(webhook) => {
+                let dispatcher = self.clone();
+                tokio::spawn(async move { dispatcher.deliver(&webhook, delivery, pending).await });
+                true
+            },
+            None => {
+                self.webhook_repository.save_delivery(WebhookDelivery {
+                    status: DELIVERY_DEAD_LETTERED.to_string(),
+                    error: Some("Webhook deleted".to_string()),
+                    updated_at: Duration::seconds(Utc::now().timestamp()),
+                    ..delivery
+                }).await;
+                false
+            }
+        }
+    }
+
+    async fn attempt(&self, webhook: &Webhook, delivery: &WebhookDelivery) -> (Option<i32>, Option<String>) {
+        // Addresses written in the URL are not resolved, so are checked here instead.
+        let allowed = self.allow_private_addresses
+            || reqwest::Url::parse(&webhook.url).map_or(false, |url| webhook::is_public_url(&url));
+        if !allowed {
+            return (None, Some(format!("Webhook address of {} is not public", webhook.url)));
+        }
+
+        let timestamp = Utc::now().timestamp();
+
+        let response = self.client.post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, format!("sha256={}", signature(&webhook.secret, timestamp, &delivery.payload)))
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(EVENT_HEADER, &delivery.event)
+            .header(DELIVERY_HEADER, delivery.delivery_id.to_string())
+            .body(delivery.payload.clone())
+            .send()
+            .await;
+
+        match response {
+            Ok(response) if response.status().is_success() => (Some(response.status().as_u16() as i32), None),
+            Ok(response) => (Some(response.status().as_u16() as i32), Some(format!("Webhook answered {}", response.status()))),
+            Err(e) => (None, Some(e.to_string()))
+        }
+    }
+}
+
+/// Resolves webhook hosts to their public addresses, failing the attempt when they have none.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?
+                .filter(|address| webhook::is_public_address(address.ip()))
+                .collect();
+
+            if addresses.is_empty() {
+                return Err(format!("Webhook host {} has no public address", host).into());
+            }
+
+            Ok(Box::new(addresses.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Stores the deliveries of each event, then attempts them on their own tasks.
+#[async_trait]
+impl EventPublisher for WebhookDispatcher {
+    fn name(&self) -> &'static str {
+        WEBHOOK_PUBLISHER
+    }
+
+    async fn publish(&self, event: &VehicleEvent) -> Result<(), String> {
+        let deliveries = self.deliveries(event).await
+            .ok_or_else(|| format!("Failed to store the webhook deliveries of event {}", event.event_id))?;
+
+        for (webhook, delivery, pending) in deliveries {
+            let dispatcher = self.clone();
+            tokio::spawn(async move { dispatcher.deliver(&webhook, delivery, pending).await });
+        }
+
+        Ok(())
+    }
+}
+
+/// Resumes the pending deliveries left by stopped instances when the instance starts, then every `interval`.
+pub async fn resume_periodically(webhook_dispatcher: WebhookDispatcher, interval: StdDuration) {
+    loop {
+        for shard in 0..PENDING_DELIVERY_SHARDS {
+            let resumed = webhook_dispatcher.resume(shard, Utc::now().timestamp_millis()).await;
+            if resumed > 0 {
+                println!("Resumed {} webhook deliveries of shard {}", resumed, shard);
+            }
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Hex HMAC-SHA256 of `<timestamp>.<payload>` keyed with the webhook secret.
+pub fn signature(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::thread;
+
+    use chrono::TimeZone;
+
+    use crate::domain::vehicle_event::VehicleEventKind;
+    use crate::service::webhook_service::tests::MockWebhookRepositoryImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn given_webhook_answering_ok_when_deliver_then_posts_signed_payload_once() {
+        let (url, requests) = fixture::receiver(vec!(200));
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_save_delivery()
+            .withf(|delivery: &WebhookDelivery| delivery.status == DELIVERY_DELIVERED && delivery.attempts == 1 && delivery.response_status == Some(200))
+            .times(1)
+            .returning(|delivery| Some(delivery));
+
+        let dispatcher = WebhookDispatcher::new(Arc::new(webhook_repository), fixture::settings(3));
+
+        let delivery = aw!(dispatcher.deliver(&fixture::webhook(&url), fixture::delivery(), fixture::pending()));
+
+        assert_eq!(DELIVERY_DELIVERED, delivery.status);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(1, requests.len());
+        let (headers, body) = &requests[0];
+        let timestamp: i64 = fixture::header(headers, TIMESTAMP_HEADER).parse().unwrap();
+        assert_eq!(format!("sha256={}", signature(fixture::SECRET, timestamp, body)), fixture::header(headers, SIGNATURE_HEADER));
+        assert_eq!(fixture::delivery().delivery_id.to_string(), fixture::header(headers, DELIVERY_HEADER));
+        assert_eq!(fixture::PAYLOAD, body);
+    }
+
+    #[test]
+    fn given_webhook_on_loopback_address_when_deliver_then_dead_letters_without_posting() {
+        let (url, requests) = fixture::receiver(vec!(200));
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_save_delivery()
+            .withf(|delivery: &WebhookDelivery| delivery.status == DELIVERY_DEAD_LETTERED && delivery.response_status.is_none()
+                && delivery.error.as_ref().map_or(false, |error| error.contains("not public")))
+            .times(1)
+            .returning(|delivery| Some(delivery));
+
+        let settings = WebhookSettings { allow_private_addresses: false, ..fixture::settings(1) };
+        let dispatcher = WebhookDispatcher::new(Arc::new(webhook_repository), settings);
+
+        let delivery = aw!(dispatcher.deliver(&fixture::webhook(&url), fixture::delivery(), fixture::pending()));
+
+        assert_eq!(DELIVERY_DEAD_LETTERED, delivery.status);
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn given_webhook_failing_once_when_deliver_then_retries_until_delivered() {
+        let (url, requests) = fixture::receiver(vec!(500, 200));
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_save_delivery()
+            .times(2)
+            .returning(|delivery| Some(delivery));
+        webhook_repository.expect_lease_delivery()
+            .withf(|pending: &PendingDelivery, lease_until_ms: &i64| pending.lease_until_ms == 5000 && *lease_until_ms > Utc::now().timestamp_millis())
+            .times(1)
+            .returning(|_, _| Some(true));
+        webhook_repository.expect_get_delivery()
+            .times(1)
+            .returning(|_, _, _| Some(fixture::delivery()));
+        webhook_repository.expect_get_webhook()
+            .times(1)
+            .returning(move |_, _| Some(fixture::webhook("")));
+
+        let dispatcher = WebhookDispatcher::new(Arc::new(webhook_repository), fixture::settings(3));
+
+        let delivery = aw!(dispatcher.deliver(&fixture::webhook(&url), fixture::delivery(), fixture::pending()));
+
+        assert_eq!(DELIVERY_DELIVERED, delivery.status);
+        assert_eq!(2, delivery.attempts);
+        assert_eq!(2, requests.lock().unwrap().len());
+    }
+
+    #[test]
+    fn given_delivery_erased_while_waiting_when_deliver_then_drops_it_without_writing_it_back() {
+        let (url, requests) = fixture::receiver(vec!(500, 200));
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_save_delivery()
+            .times(1)
+            .returning(|delivery| Some(delivery));
+        webhook_repository.expect_lease_delivery()
+            .times(1)
+            .returning(|_, _| Some(true));
+        webhook_repository.expect_get_delivery()
+            .times(1)
+            .returning(|_, _, _| None);
+        webhook_repository.expect_delete_pending_delivery()
+            .times(1)
+            .returning(|_| Some(()));
+
+        let dispatcher = WebhookDispatcher::new(Arc::new(webhook_repository), fixture::settings(3));
+
+        let delivery = aw!(dispatcher.deliver(&fixture::webhook(&url), fixture::delivery(), fixture::pending()));
+
+        assert_eq!((DELIVERY_PENDING, 1), (delivery.status.as_str(), delivery.attempts));
+        assert_eq!(1, requests.lock().unwrap().len());
+    }
+
+    #[test]
+    fn given_webhook_always_failing_when_deliver_then_dead_letters_after_max_attempts() {
+        let (url, requests) = fixture::receiver(vec!(500, 503));
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_save_delivery()
+            .times(2)
+            .returning(|delivery| Some(delivery));
+        webhook_repository.expect_lease_delivery()
+            .times(1)
+            .returning(|_, _| Some(true));
+        webhook_repository.expect_get_delivery()
+            .times(1)
+            .returning(|_, _, _| Some(fixture::delivery()));
+        webhook_repository.expect_get_webhook()
+            .times(1)
+            .returning(move |_, _| Some(fixture::webhook("")));
+
+        let dispatcher = WebhookDispatcher::new(Arc::new(webhook_repository), fixture::settings(2));
+
+        let delivery = aw!(dispatcher.deliver(&fixture::webhook(&url), fixture::delivery(), fixture::pending()));
+
+        assert_eq!(DELIVERY_DEAD_LETTERED, delivery.status);
+        assert_eq!((2, Some(503)), (delivery.attempts, delivery.response_status));
+        assert_eq!(2, requests.lock().unwrap().len());
+    }
+
+    #[test]
+    fn given_webhooks_of_user_when_deliveries_then_stores_pending_delivery_for_subscribed_ones() {
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_get_webhooks()
+            .times(1)
+            .returning(|_| Some(vec!(fixture::webhook(fixture::URL), Webhook { events: vec!("retired".to_string()), ..fixture::webhook(fixture::URL) })));
+        webhook_repository.expect_start_delivery()
+            .withf(|delivery: &WebhookDelivery, _| delivery.status == DELIVERY_PENDING && delivery.attempts == 0 && delivery.event == "created")
+            .times(1)
+            .returning(|delivery, _| Some(delivery));
+
+        let dispatcher = WebhookDispatcher::new(Arc::new(webhook_repository), fixture::settings(3));
+
+        let deliveries = aw!(dispatcher.deliveries(&fixture::event())).unwrap();
+
+        assert_eq!(1, deliveries.len());
+        assert_eq!(fixture::event().event_id, deliveries[0].1.delivery_id);
+        assert_eq!(fixture::event().event_id, deliveries[0].2.delivery_id);
+        assert!(deliveries[0].1.payload.contains(&format!("\"delivery_id\":\"{}\"", deliveries[0].1.delivery_id)));
+    }
+
+    #[test]
+    fn given_delivery_not_stored_when_publish_then_fails_for_event_to_be_relayed_again() {
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_get_webhooks()
+            .times(1)
+            .returning(|_| Some(vec!(fixture::webhook(fixture::URL))));
+        webhook_repository.expect_start_delivery()
+            .times(1)
+            .returning(|_, _| None);
+
+        let dispatcher = WebhookDispatcher::new(Arc::new(webhook_repository), fixture::settings(3));
+
+        assert!(aw!(EventPublisher::publish(&dispatcher, &fixture::event())).is_err());
+    }
+
+    #[test]
+    fn given_delivery_taken_over_when_deliver_then_leaves_it_to_the_other_instance() {
+        let (url, requests) = fixture::receiver(vec!(500, 200));
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_save_delivery()
+            .times(1)
+            .returning(|delivery| Some(delivery));
+        webhook_repository.expect_lease_delivery()
+            .times(1)
+            .returning(|_, _| Some(false));
+        webhook_repository.expect_get_webhook()
+            .times(0);
+
+        let dispatcher = WebhookDispatcher::new(Arc::new(webhook_repository), fixture::settings(3));
+
+        let delivery = aw!(dispatcher.deliver(&fixture::webhook(&url), fixture::delivery(), fixture::pending()));
+
+        assert_eq!((DELIVERY_PENDING, 1), (delivery.status.as_str(), delivery.attempts));
+        assert_eq!(1, requests.lock().unwrap().len());
+    }
+
+    #[test]
+    fn given_expired_lease_of_deleted_webhook_when_resume_then_dead_letters_it_and_skips_leased_ones() {
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+        let leased = PendingDelivery { delivery_id: Uuid::new_v4(), lease_until_ms: 20_000, ..fixture::pending() };
+
+        webhook_repository.expect_get_pending_deliveries()
+            .withf(|shard: &i32, after: &Option<PendingDelivery>, _| *shard == 3 && after.is_none())
+            .times(1)
+            .returning(move |_, _, _| Some(vec!(fixture::pending(), leased.clone())));
+        webhook_repository.expect_lease_delivery()
+            .withf(|pending: &PendingDelivery, lease_until_ms: &i64| pending.lease_until_ms == 5000 && *lease_until_ms == 9000 + 60_000)
+            .times(1)
+            .returning(|_, _| Some(true));
+        webhook_repository.expect_get_delivery()
+            .times(1)
+            .returning(|_, _, _| Some(fixture::delivery()));
+        webhook_repository.expect_get_webhook()
+            .times(1)
+            .returning(|_, _| None);
+        webhook_repository.expect_save_delivery()
+            .withf(|delivery: &WebhookDelivery| delivery.status == DELIVERY_DEAD_LETTERED)
+            .times(1)
+            .returning(|delivery| Some(delivery));
+
+        let dispatcher = WebhookDispatcher::new(Arc::new(webhook_repository), WebhookSettings { lease_ms: 60_000, ..fixture::settings(3) });
+
+        assert_eq!(0, aw!(dispatcher.resume(3, 9000)));
+    }
+
+    pub mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const EVENT_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const URL: &str = "https://partner.example/hooks";
+        pub const SECRET: &str = "the secret";
+        pub const PAYLOAD: &str = "{\"event\":\"created\"}";
+
+        type Requests = Arc<Mutex<Vec<(Vec<String>, String)>>>;
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn settings(max_attempts: u32) -> WebhookSettings {
+            WebhookSettings {
+                timeout_ms: 2000,
+                lease_ms: 1000,
+                resume_interval_ms: 1000,
+                allow_private_addresses: true,
+                retry: RetrySettings { base_delay_ms: 1, max_delay_ms: 1, max_attempts, jitter: false }
+            }
+        }
+
+        pub fn webhook(url: &str) -> Webhook {
+            Webhook {
+                user_id: user_id(),
+                webhook_id: Uuid::new_v4(),
+                url: url.to_string(),
+                secret: SECRET.to_string(),
+                events: vec!("created".to_string()),
+                created_at: Duration::seconds(5)
+            }
+        }
+
+        pub fn delivery() -> WebhookDelivery {
+            WebhookDelivery {
+                webhook_id: Uuid::new_v4(),
+                created_at: Duration::seconds(5),
+                delivery_id: Uuid::parse_str(USER_ID_STR).unwrap(),
+                user_id: user_id(),
+                vehicle_id: user_id(),
+                event: "created".to_string(),
+                payload: PAYLOAD.to_string(),
+                status: DELIVERY_PENDING.to_string(),
+                attempts: 0,
+                response_status: None,
+                error: None,
+                updated_at: Duration::seconds(5)
+            }
+        }
+
+        pub fn pending() -> PendingDelivery {
+            webhook_mapper::get_pending_delivery(&delivery(), 5000)
+        }
+
+        pub fn event() -> VehicleEvent {
+            VehicleEvent {
+                id: 1,
+                event_id: Uuid::parse_str(EVENT_ID_STR).unwrap(),
+                user_id: user_id(),
+                vehicle_id: user_id(),
+                kind: VehicleEventKind::Created,
+                occurred_at: Utc.timestamp(5, 0),
+                vehicle: None
+            }
+        }
+
+        pub fn header<'a>(headers: &'a [String], name: &str) -> &'a str {
+            headers.iter()
+                .find_map(|line| line.split_once(':')
+                    .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.trim()))
+                .unwrap_or_else(|| panic!("Missing header {}", name))
+        }
+
+        /// Local HTTP receiver answering its requests with `statuses` in turn, recording their headers and body.
+        pub fn receiver(statuses: Vec<u16>) -> (String, Requests) {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+            let requests: Requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = requests.clone();
+
+            thread::spawn(move || {
+                for status in statuses {
+                    let (stream, _) = match listener.accept() {
+                        Ok(accepted) => accepted,
+                        Err(_) => return
+                    };
+                    let mut reader = BufReader::new(stream);
+
+                    let mut headers = Vec::new();
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                        headers.push(line.trim().to_string());
+                    }
+
+                    let length: usize = header(&headers, "Content-Length").parse().unwrap();
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    recorded.lock().unwrap().push((headers, String::from_utf8(body).unwrap()));
+
+                    let mut stream = reader.into_inner();
+                    write!(stream, "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+                }
+            });
+
+            (url, requests)
+        }
+    }
+}
@@ -1,47 +1,424 @@
 #[macro_use] extern crate rocket;
 
-mod domain { pub mod vehicle; }
+mod domain {
+    pub mod vehicle;
+    pub mod vehicle_lookup;
+    pub mod activity;
+    pub mod maintenance;
+    pub mod component;
+    pub mod transfer;
+    pub mod vehicle_event;
+    pub mod webhook;
+    pub mod outbox;
+    pub mod vehicle_history;
+    pub mod audit;
+    pub mod user_data;
+    pub mod vehicle_trash;
+}
 mod dto {
     pub mod book;
     pub mod vehicle_dto;
+    pub mod activity_dto;
+    pub mod maintenance_dto;
+    pub mod component_dto;
+    pub mod transfer_dto;
+    pub mod health_dto;
+    pub mod webhook_dto;
+    pub mod vehicle_history_dto;
+    pub mod audit_dto;
+    pub mod user_data_dto;
+    pub mod trash_dto;
+    pub mod v2 {
+        pub mod vehicle_dto;
+    }
+}
+mod dao {
+    pub mod session_manager;
+    pub mod retry_policy;
+    pub mod circuit_breaker;
+    pub mod deadline;
+    pub mod consistency;
+}
+mod service {
+    pub mod vehicle_service;
+    pub mod activity_service;
+    pub mod maintenance_service;
+    pub mod component_service;
+    pub mod picture_service;
+    pub mod transfer_service;
+    pub mod webhook_service;
+    pub mod audit_service;
+    pub mod user_data_service;
+    pub mod trash_service;
+}
+mod mapper {
+    pub mod vehicle_mapper;
+    pub mod activity_mapper;
+    pub mod maintenance_mapper;
+    pub mod component_mapper;
+    pub mod transfer_mapper;
+    pub mod health_mapper;
+    pub mod webhook_mapper;
+    pub mod outbox_mapper;
+    pub mod vehicle_history_mapper;
+    pub mod audit_mapper;
+    pub mod user_data_mapper;
+    pub mod trash_mapper;
+    pub mod v2 {
+        pub mod vehicle_mapper;
+    }
+}
+mod repository {
+    pub mod vehicle_repository;
+    pub mod cached_vehicle_repository;
+    pub mod activity_repository;
+    pub mod maintenance_repository;
+    pub mod component_repository;
+    pub mod transfer_repository;
+    pub mod webhook_repository;
+    pub mod outbox_repository;
+    pub mod vehicle_history_repository;
+    pub mod audit_repository;
+    pub mod user_data_repository;
+    pub mod vehicle_trash_repository;
+    pub mod cql;
+    pub mod entity;
+    pub mod cql_repository;
+}
+mod storage {
+    pub mod blob_store;
+    pub mod local_blob_store;
+}
+mod search {
+    pub mod vehicle_index;
+    pub mod tantivy_vehicle_index;
+}
+mod event {
+    pub mod vehicle_event_log;
+    pub mod webhook_dispatcher;
+    pub mod event_publisher;
+    pub mod outbox_relay;
+    pub mod outbox_feed;
+}
+mod parser {
+    pub mod track;
+    pub mod gpx;
+    pub mod fit;
+    pub mod activity_file;
+    pub mod vehicle_records;
 }
-mod dao { pub mod session_manager; }
-mod service { pub mod vehicle_service; }
-mod mapper { pub mod vehicle_mapper; } 
-mod repository { pub mod vehicle_repository; }
 mod controller {
     pub mod controllers;
+    pub mod activity_controllers;
+    pub mod maintenance_controllers;
+    pub mod component_controllers;
+    pub mod picture_controllers;
+    pub mod transfer_controllers;
+    pub mod bulk_controllers;
+    pub mod search_controllers;
+    pub mod event_controllers;
+    pub mod webhook_controllers;
+    pub mod history_controllers;
+    pub mod audit_controllers;
+    pub mod user_controllers;
+    pub mod trash_controllers;
+    pub mod health_controllers;
+    pub mod unavailable_fairing;
+    pub mod audit_fairing;
+    pub mod deadline_handler;
+    pub mod consistency_handler;
+    pub mod admin;
+    pub mod blob_response;
+    pub mod negotiation;
+    pub mod actor;
     pub mod catchers;
+    pub mod api_version;
+    pub mod v2 {
+        pub mod vehicle_controllers;
+    }
 }
+mod cli;
 
 use std::sync::Arc;
 use std::env;
+use std::time::Duration;
 
-use crate::dao::session_manager::SessionManagerImpl;
-use crate::repository::vehicle_repository::VehicleRepositoryImpl;
+use crate::dao::session_manager::{SessionManagerImpl, StatementSettings};
+use crate::dao::retry_policy::{RetryPolicy, RetrySettings};
+use crate::dao::circuit_breaker::{self, CircuitBreaker, CircuitBreakerSettings};
+use crate::dao::consistency::{ConsistencyPolicy, ConsistencySettings};
+use crate::repository::vehicle_repository::{VehicleRepository, VehicleRepositoryImpl};
+use crate::repository::cached_vehicle_repository::{CachedVehicleRepository, VehicleCache, VehicleCacheSettings};
+use crate::repository::activity_repository::ActivityRepositoryImpl;
+use crate::repository::maintenance_repository::MaintenanceRepositoryImpl;
+use crate::repository::component_repository::ComponentRepositoryImpl;
+use crate::repository::transfer_repository::TransferRepositoryImpl;
+use crate::repository::webhook_repository::WebhookRepositoryImpl;
+use crate::repository::outbox_repository::OutboxRepositoryImpl;
+use crate::repository::vehicle_history_repository::VehicleHistoryRepositoryImpl;
+use crate::repository::audit_repository::AuditRepositoryImpl;
+use crate::repository::user_data_repository::UserDataRepositoryImpl;
+use crate::repository::vehicle_trash_repository::VehicleTrashRepositoryImpl;
 use crate::service::vehicle_service::VehicleService;
+use crate::service::activity_service::ActivityService;
+use crate::service::maintenance_service::MaintenanceService;
+use crate::service::component_service::ComponentService;
+use crate::service::picture_service::{PictureService, PictureSettings};
+use crate::service::transfer_service::TransferService;
+use crate::service::webhook_service::WebhookService;
+use crate::service::audit_service::{self, AuditService, AuditSettings};
+use crate::service::user_data_service::{self, ErasureSettings, UserDataService};
+use crate::service::trash_service::{self, TrashService, TrashSettings};
+use crate::storage::local_blob_store::LocalBlobStore;
+use crate::search::tantivy_vehicle_index::{SearchSettings, TantivyVehicleIndex};
+use crate::event::vehicle_event_log::{VehicleEventLog, VehicleEventSettings};
+use crate::event::webhook_dispatcher::{self, WebhookDispatcher, WebhookSettings};
+use crate::event::event_publisher::{EventPublisher, LogPublisher, BUS_PUBLISHER, LOG_PUBLISHER, WEBHOOK_PUBLISHER};
+use crate::event::outbox_relay::{OutboxRelay, OutboxSettings};
+use crate::event::outbox_feed::OutboxFeed;
 use crate::controller::controllers;
+use crate::controller::activity_controllers;
+use crate::controller::maintenance_controllers;
+use crate::controller::component_controllers;
+use crate::controller::picture_controllers;
+use crate::controller::transfer_controllers;
+use crate::controller::bulk_controllers;
+use crate::controller::search_controllers;
+use crate::controller::event_controllers;
+use crate::controller::webhook_controllers;
+use crate::controller::history_controllers;
+use crate::controller::audit_controllers;
+use crate::controller::user_controllers;
+use crate::controller::trash_controllers;
+use crate::controller::health_controllers;
+use crate::controller::unavailable_fairing::ServiceUnavailable;
+use crate::controller::audit_fairing::Audit;
+use crate::controller::deadline_handler::{self, DeadlineSettings};
+use crate::controller::consistency_handler;
+use crate::controller::admin::AdminSettings;
 use crate::controller::catchers;
+use crate::controller::api_version::{self, DeprecationSettings, UNVERSIONED_BASE, V1_BASE, V2_BASE};
 
 const CASSANDRA_NODE: &str = "localhost:9042";
+const PICTURE_STORE_DIR: &str = "pictures";
+const SEARCH_INDEX_DIR: &str = "search-index";
+
+/// Services shared by the controllers through Rocket managed state.
+struct Services {
+    vehicle_service: Arc<VehicleService>,
+    activity_service: Arc<ActivityService>,
+    maintenance_service: Arc<MaintenanceService>,
+    component_service: Arc<ComponentService>,
+    picture_service: Arc<PictureService>,
+    transfer_service: Arc<TransferService>,
+    webhook_service: Arc<WebhookService>,
+    audit_service: Arc<AuditService>,
+    user_data_service: Arc<UserDataService>,
+    trash_service: Arc<TrashService>,
+    circuit_breaker: Arc<CircuitBreaker>,
+    vehicle_cache: Arc<VehicleCache>,
+    vehicle_events: Arc<VehicleEventLog>,
+    deadline_settings: DeadlineSettings,
+    admin_settings: AdminSettings,
+    deprecation_settings: DeprecationSettings,
+    audit_settings: AuditSettings,
+}
 
 #[rocket::main]
 async fn main() -> Result<(), rocket::Error> {
 
+    let command = cli::parse(&env::args().skip(1).collect::<Vec<String>>()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2)
+    });
+
     let cassandra_node = env::var("CASSANDRA_NODE").unwrap_or_else(|_| CASSANDRA_NODE.to_string());
+    let picture_store_dir = env::var("PICTURE_STORE_DIR").unwrap_or_else(|_| PICTURE_STORE_DIR.to_string());
+    let search_index_dir = env::var("SEARCH_INDEX_DIR").unwrap_or_else(|_| SEARCH_INDEX_DIR.to_string());
+
+    let retry_settings = settings::<RetrySettings>("cassandra.retry");
+    let circuit_breaker_settings = settings::<CircuitBreakerSettings>("cassandra.circuit_breaker");
+    let statement_settings = settings::<StatementSettings>("cassandra.statement");
+    let consistency_policy = ConsistencyPolicy::new(settings::<ConsistencySettings>("cassandra.consistency"))
+        .unwrap_or_else(|e| panic!("Invalid cassandra.consistency settings: {}", e));
+    let probe_interval = Duration::from_millis(circuit_breaker_settings.open_duration_ms);
+    let circuit_breaker = Arc::new(CircuitBreaker::new(circuit_breaker_settings));
+
+    let session_manager = Arc::new(SessionManagerImpl::new(&cassandra_node, RetryPolicy::new(retry_settings), circuit_breaker.clone(), statement_settings, consistency_policy).await);
+    rocket::tokio::spawn(circuit_breaker::probe_periodically(session_manager.clone(), circuit_breaker.clone(), probe_interval));
+    let vehicle_cache_settings = settings::<VehicleCacheSettings>("cache.vehicle");
+    let vehicle_cache = Arc::new(VehicleCache::new(&vehicle_cache_settings));
+    let vehicle_repository: Arc<dyn VehicleRepository + Sync + Send> = if vehicle_cache_settings.enabled {
+        Arc::new(CachedVehicleRepository::new(Arc::new(VehicleRepositoryImpl::new(session_manager.clone())), vehicle_cache.clone()))
+    } else {
+        Arc::new(VehicleRepositoryImpl::new(session_manager.clone()))
+    };
+    let activity_repository = Arc::new(ActivityRepositoryImpl::new(session_manager.clone()));
+    let maintenance_repository = Arc::new(MaintenanceRepositoryImpl::new(session_manager.clone()));
+    let component_repository = Arc::new(ComponentRepositoryImpl::new(session_manager.clone()));
+    let transfer_repository = Arc::new(TransferRepositoryImpl::new(session_manager.clone()));
+    let webhook_repository = Arc::new(WebhookRepositoryImpl::new(session_manager.clone()));
+    let vehicle_history_repository = Arc::new(VehicleHistoryRepositoryImpl::new(session_manager.clone()));
+    let audit_repository = Arc::new(AuditRepositoryImpl::new(session_manager.clone()));
+    let user_data_repository = Arc::new(UserDataRepositoryImpl::new(session_manager.clone()));
+    let vehicle_trash_repository = Arc::new(VehicleTrashRepositoryImpl::new(session_manager.clone()));
+    let audit_settings = settings::<AuditSettings>("audit");
+    let trash_settings = settings::<TrashSettings>("trash");
+    let erasure_settings = settings::<ErasureSettings>("erasure");
+    let outbox_settings = settings::<OutboxSettings>("outbox");
+    let picture_store = Arc::new(LocalBlobStore::new(picture_store_dir));
+    let vehicle_index = Arc::new(TantivyVehicleIndex::open(&search_index_dir, settings::<SearchSettings>("search"))
+        .unwrap_or_else(|e| panic!("Invalid search index: {}", e)));
+    let vehicle_events = Arc::new(VehicleEventLog::new(&settings::<VehicleEventSettings>("events.vehicle")));
+    let audit_service = Arc::new(AuditService::new(audit_repository, audit_settings.clone()));
 
-    let session_manager = SessionManagerImpl::new(&cassandra_node).await;
-    let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
-    let vehicle_service = VehicleService::new(Arc::new(vehicle_repository));
+    let services = Services {
+        vehicle_service: Arc::new(VehicleService::new(vehicle_repository.clone(), vehicle_index.clone(), vehicle_history_repository)),
+        activity_service: Arc::new(ActivityService::new(activity_repository, vehicle_repository.clone())),
+        maintenance_service: Arc::new(MaintenanceService::new(maintenance_repository, vehicle_repository.clone())),
+        component_service: Arc::new(ComponentService::new(component_repository, vehicle_repository.clone())),
+        picture_service: Arc::new(PictureService::new(picture_store.clone(), vehicle_repository.clone(), settings::<PictureSettings>("pictures"))),
+        transfer_service: Arc::new(TransferService::new(transfer_repository.clone(), vehicle_repository.clone(), vehicle_index.clone())),
+        webhook_service: Arc::new(WebhookService::new(webhook_repository.clone(), outbox_settings.publishes(WEBHOOK_PUBLISHER))),
+        audit_service: audit_service.clone(),
+        user_data_service: Arc::new(UserDataService::new(user_data_repository.clone(), vehicle_repository.clone(), vehicle_trash_repository.clone(),
+                                                         transfer_repository, webhook_repository.clone(), picture_store.clone(),
+                                                         vehicle_index.clone(), audit_service.clone(), erasure_settings.clone())),
+        trash_service: Arc::new(TrashService::new(vehicle_trash_repository, vehicle_repository, user_data_repository, picture_store,
+                                                  vehicle_index.clone(), audit_service, trash_settings.clone())),
+        circuit_breaker,
+        vehicle_cache,
+        vehicle_events: vehicle_events.clone(),
+        deadline_settings: settings::<DeadlineSettings>("deadline"),
+        admin_settings: settings::<AdminSettings>("admin"),
+        deprecation_settings: settings::<DeprecationSettings>("api.v1"),
+        audit_settings,
+    };
 
-    rocket(Arc::new(vehicle_service))
+    if let Some(command) = command {
+        let result = cli::run(command, services.vehicle_service).await;
+        // Commits what the command indexed and releases the index directory before exiting.
+        vehicle_index.shutdown().await;
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if outbox_settings.enabled {
+        let outbox_repository = Arc::new(OutboxRepositoryImpl::new(session_manager.clone()));
+
+        // The bus of every instance is fed all events, rather than only those of the shards it relays.
+        if outbox_settings.publishes(BUS_PUBLISHER) {
+            let outbox_feed = Arc::new(OutboxFeed::new(outbox_repository.clone(), vehicle_events.clone(), outbox_settings.clone()));
+            rocket::tokio::spawn(outbox_feed.run());
+        }
+
+        let publishers = outbox_settings.publishers.iter()
+            .filter(|name| name.as_str() != BUS_PUBLISHER)
+            .map(|name| -> Arc<dyn EventPublisher + Sync + Send> { match name.as_str() {
+                LOG_PUBLISHER => Arc::new(LogPublisher),
+                WEBHOOK_PUBLISHER => {
+                    let webhook_settings = settings::<WebhookSettings>("webhooks");
+                    let resume_interval = Duration::from_millis(webhook_settings.resume_interval_ms);
+                    let webhook_dispatcher = WebhookDispatcher::new(webhook_repository.clone(), webhook_settings);
+                    rocket::tokio::spawn(webhook_dispatcher::resume_periodically(webhook_dispatcher.clone(), resume_interval));
+                    Arc::new(webhook_dispatcher)
+                },
+                _ => panic!("Invalid outbox settings: unknown publisher {}", name)
+            }})
+            .collect();
+        let outbox_relay = Arc::new(OutboxRelay::new(outbox_repository, publishers, outbox_settings));
+        rocket::tokio::spawn(outbox_relay.run());
+    }
+
+    rocket::tokio::spawn(audit_service::write_periodically(services.audit_service.clone(), services.audit_settings.clone()));
+    rocket::tokio::spawn(user_data_service::resume_periodically(services.user_data_service.clone(), erasure_settings));
+
+    if trash_settings.purge_enabled {
+        rocket::tokio::spawn(trash_service::purge_periodically(services.trash_service.clone(), trash_settings));
+    }
+
+    let launched = rocket(services)
       .launch()
-      .await
+      .await;
+    vehicle_index.shutdown().await;
+    launched
+}
+
+/// Reads an optional section of the Rocket configuration, falling back to its defaults when absent.
+fn settings<T: Default + serde::de::DeserializeOwned>(section: &str) -> T {
+    rocket::Config::figment()
+        .extract_inner::<T>(section)
+        .or_else(|e| if e.missing() { Ok(T::default()) } else { Err(e) })
+        .unwrap_or_else(|e| panic!("Invalid {} settings: {}", section, e))
+}
+
+/// Runs every route under its deadline and lets admins override the consistency level.
+fn scoped(routes: Vec<rocket::Route>, deadline_settings: &DeadlineSettings, admin_settings: &AdminSettings) -> Vec<rocket::Route> {
+    consistency_handler::with_consistency_override(deadline_handler::with_deadline(routes, deadline_settings), admin_settings)
 }
 
-fn rocket(vehicle_service: Arc<VehicleService>) -> rocket::Rocket<rocket::Build> {
-    rocket::build()
-        .register("/", catchers![catchers::internal_error, catchers::not_found])
-        .mount("/api", routes![controllers::get_vehicle, controllers::hello, controllers::new_book, controllers::new_vehicle])
-        .manage(vehicle_service)
+/// Routes whose contract is the same in every API version.
+fn api_routes() -> Vec<rocket::Route> {
+    [
+        routes![controllers::hello, controllers::new_book],
+        routes![activity_controllers::import_activity],
+        routes![maintenance_controllers::get_records, maintenance_controllers::new_record,
+                maintenance_controllers::get_rules, maintenance_controllers::new_rule,
+                maintenance_controllers::get_reminders],
+        routes![component_controllers::get_components, component_controllers::new_component,
+                component_controllers::install_component, component_controllers::remove_component,
+                component_controllers::get_vehicle_components, component_controllers::get_alerts],
+        routes![picture_controllers::upload_picture, picture_controllers::get_picture],
+        routes![transfer_controllers::new_offer, transfer_controllers::get_offers,
+                transfer_controllers::accept_offer, transfer_controllers::decline_offer,
+                transfer_controllers::get_owners],
+        routes![bulk_controllers::import_vehicles, bulk_controllers::export_vehicles],
+        routes![search_controllers::search_vehicles, search_controllers::list_vehicles],
+        routes![event_controllers::get_vehicle_events],
+        routes![webhook_controllers::new_webhook, webhook_controllers::get_webhooks,
+                webhook_controllers::delete_webhook, webhook_controllers::get_deliveries],
+        routes![history_controllers::get_history, history_controllers::restore_version],
+        routes![user_controllers::export_user, user_controllers::erase_user, user_controllers::get_erasure],
+        routes![trash_controllers::delete_vehicle, trash_controllers::get_trash, trash_controllers::restore_vehicle]
+    ].concat()
+}
+
+/// Serves v1 under `/api/v1` and, for clients predating versioning, `/api`, both deprecated, and v2 under `/api/v2`.
+fn rocket(services: Services) -> rocket::Rocket<rocket::Build> {
+    let deadline_settings = &services.deadline_settings;
+    let admin_settings = &services.admin_settings;
+    let v1_routes = || api_version::deprecated([api_version::v1_routes(), api_routes()].concat(), &services.deprecation_settings);
+    // Attached after the fairings changing responses, so that the audit log records the status the client got.
+    let audit = services.audit_settings.enabled.then(|| Audit::new(services.audit_service.clone()));
+
+    let rocket = rocket::build()
+        .attach(ServiceUnavailable::new(services.circuit_breaker.clone()))
+        .register("/", catchers![catchers::internal_error, catchers::not_found, catchers::gateway_timeout])
+        .mount(UNVERSIONED_BASE, scoped(v1_routes(), deadline_settings, admin_settings))
+        .mount(V1_BASE, scoped(v1_routes(), deadline_settings, admin_settings))
+        .mount(V2_BASE, scoped([api_version::v2_routes(), api_routes()].concat(), deadline_settings, admin_settings))
+        .mount(UNVERSIONED_BASE, routes![health_controllers::ready, health_controllers::metrics])
+        .mount(UNVERSIONED_BASE, routes![audit_controllers::get_audit_log])
+        .manage(services.vehicle_service)
+        .manage(services.activity_service)
+        .manage(services.maintenance_service)
+        .manage(services.component_service)
+        .manage(services.picture_service)
+        .manage(services.transfer_service)
+        .manage(services.webhook_service)
+        .manage(services.circuit_breaker)
+        .manage(services.vehicle_cache)
+        .manage(services.vehicle_events)
+        .manage(services.audit_service)
+        .manage(services.user_data_service)
+        .manage(services.trash_service)
+        .manage(services.admin_settings.clone());
+
+    match audit {
+        Some(audit) => rocket.attach(audit),
+        None => rocket
+    }
 }
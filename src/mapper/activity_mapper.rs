@@ -0,0 +1,66 @@
+use chrono::{Utc, TimeZone};
+
+use crate::domain::activity::Activity;
+use crate::dto::activity_dto::ActivityDTO;
+
+pub fn get_activity_dto(activity: Activity) -> ActivityDTO {
+    ActivityDTO {
+        activity_id: activity.activity_id,
+        user_id: activity.user_id,
+        vehicle_id: activity.vehicle_id,
+        file_format: activity.file_format,
+        started_at: activity.started_at.map(|d| Utc.timestamp(d.num_seconds(), 0)),
+        duration: activity.duration,
+        distance: activity.distance,
+        elevation_gain: activity.elevation_gain,
+        created_at: Utc.timestamp(activity.created_at.num_seconds(), 0)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    #[test]
+    fn given_activity_when_get_activity_dto_then_returns_activity_dto() {
+        let activity = Activity {
+            user_id: Uuid::parse_str(fixture::USER_ID_STR).unwrap(),
+            vehicle_id: Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap(),
+            activity_id: Uuid::parse_str(fixture::ACTIVITY_ID_STR).unwrap(),
+            file_hash: fixture::FILE_HASH.to_string(),
+            file_format: fixture::FILE_FORMAT.to_string(),
+            started_at: Some(Duration::seconds(fixture::STARTED_AT)),
+            duration: fixture::DURATION,
+            distance: fixture::DISTANCE,
+            elevation_gain: fixture::ELEVATION_GAIN,
+            created_at: Duration::seconds(fixture::CREATED_AT)
+        };
+
+        let activity_dto = get_activity_dto(activity);
+
+        assert_eq!(activity_dto.user_id, Uuid::parse_str(fixture::USER_ID_STR).unwrap());
+        assert_eq!(activity_dto.vehicle_id, Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap());
+        assert_eq!(activity_dto.activity_id, Uuid::parse_str(fixture::ACTIVITY_ID_STR).unwrap());
+        assert_eq!(activity_dto.file_format, fixture::FILE_FORMAT.to_string());
+        assert_eq!(activity_dto.started_at.unwrap(), Utc.timestamp(fixture::STARTED_AT, 0));
+        assert_eq!(activity_dto.duration, fixture::DURATION);
+        assert_eq!(activity_dto.distance, fixture::DISTANCE);
+        assert_eq!(activity_dto.elevation_gain, fixture::ELEVATION_GAIN);
+        assert_eq!(activity_dto.created_at, Utc.timestamp(fixture::CREATED_AT, 0));
+    }
+
+    mod fixture {
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const ACTIVITY_ID_STR: &str = "3b1f7d5e-7a51-4c39-9d0b-1a4f6f3b9e21";
+        pub const FILE_HASH: &str = "the file hash";
+        pub const FILE_FORMAT: &str = "gpx";
+        pub const STARTED_AT: i64 = 1000;
+        pub const DURATION: i64 = 3600;
+        pub const DISTANCE: f64 = 25000.5;
+        pub const ELEVATION_GAIN: f64 = 320.0;
+        pub const CREATED_AT: i64 = 5000;
+    }
+}
@@ -0,0 +1,70 @@
+use chrono::{DateTime, TimeZone, Utc};
+use rocket::serde::uuid::Uuid;
+
+use crate::domain::audit::{self, AuditEntry, AuditTargets, AuditedRequest, OUTCOME_FAILED, OUTCOME_SUCCEEDED};
+use crate::dto::audit_dto::AuditEntryDTO;
+
+/// Entry of a request answered with `status` at `occurred_at`, bucketed by its UTC day and the shard of the request.
+pub fn get_audit_entry(request: AuditedRequest, targets: &AuditTargets, status: u16, occurred_at: DateTime<Utc>) -> AuditEntry {
+    AuditEntry {
+        day: occurred_at.naive_utc().date(),
+        shard: audit::shard(&request.request_id),
+        occurred_at_ms: occurred_at.timestamp_millis(),
+        audit_id: Uuid::new_v4(),
+        request_id: request.request_id,
+        actor: request.actor,
+        ip: request.ip,
+        method: request.method,
+        route: request.route,
+        targets: serde_json::to_string(targets).unwrap_or_else(|e| panic!("Failed to serialize audit targets {:?} with error {:?}", targets, e)),
+        status: status as i32,
+        outcome: if status < 400 { OUTCOME_SUCCEEDED } else { OUTCOME_FAILED }.to_string()
+    }
+}
+
+/// An entry whose targets cannot be read is answered without them.
+pub fn get_audit_entry_dto(entry: AuditEntry) -> AuditEntryDTO {
+    AuditEntryDTO {
+        audit_id: entry.audit_id,
+        occurred_at: Utc.timestamp_millis(entry.occurred_at_ms),
+        request_id: entry.request_id,
+        actor: entry.actor,
+        ip: entry.ip,
+        method: entry.method,
+        route: entry.route,
+        targets: serde_json::from_str(&entry.targets).unwrap_or_default(),
+        status: entry.status as u16,
+        outcome: entry.outcome
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn given_failed_request_when_get_audit_entry_then_buckets_by_day_and_reads_back() {
+        let targets: AuditTargets = vec!(("isbn".to_string(), vec!("978-0".to_string()))).into_iter().collect();
+        let occurred_at = Utc.ymd(2021, 5, 1).and_hms_milli(23, 59, 59, 500);
+
+        let entry = get_audit_entry(fixture::request(), &targets, 422, occurred_at);
+
+        assert_eq!((Utc.ymd(2021, 5, 1).naive_utc(), audit::shard("the request"), OUTCOME_FAILED), (entry.day, entry.shard, entry.outcome.as_str()));
+        let entry_dto = get_audit_entry_dto(entry);
+        assert_eq!((occurred_at, targets, 422), (entry_dto.occurred_at, entry_dto.targets, entry_dto.status));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub fn request() -> AuditedRequest {
+            AuditedRequest {
+                request_id: "the request".to_string(),
+                actor: "jane".to_string(),
+                ip: None,
+                method: "POST".to_string(),
+                route: "/api/v1/book".to_string()
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use uuid::Uuid;
+use chrono::{Utc, TimeZone, Duration};
+
+use crate::domain::component::Component;
+use crate::dto::component_dto::{ComponentDTO, WearStatus};
+
+/// Share of the wear limit after which a component is flagged with a warning.
+const WEAR_WARNING_RATIO: f64 = 0.9;
+
+/// Fallback wear limits in kilometres for components created without an explicit `wear_limit`.
+pub fn default_wear_limit(component_type: &str) -> Option<i32> {
+    match component_type.to_lowercase().as_str() {
+        "chain" => Some(3000),
+        "tyre" | "tire" => Some(5000),
+        "cassette" => Some(10000),
+        "brake_pads" => Some(2000),
+        _ => None
+    }
+}
+
+/// Distance ridden with the component, `vehicle_distance` being the current odometer of the vehicle it is fitted on.
+pub fn component_distance(component: &Component, vehicle_distance: Option<i32>) -> i32 {
+    let current = match (component.vehicle_id, component.installed_distance, vehicle_distance) {
+        (Some(_), Some(installed), Some(odometer)) => (odometer - installed).max(0),
+        _ => 0
+    };
+
+    component.accumulated_distance + current
+}
+
+pub fn wear_status(distance: i32, wear_limit: Option<i32>) -> WearStatus {
+    match wear_limit {
+        Some(limit) if distance >= limit => WearStatus::Worn,
+        Some(limit) if distance as f64 >= limit as f64 * WEAR_WARNING_RATIO => WearStatus::Warning,
+        _ => WearStatus::Ok
+    }
+}
+
+pub fn get_component_dto(component: Component, vehicle_distance: Option<i32>) -> ComponentDTO {
+    let distance = component_distance(&component, vehicle_distance);
+
+    ComponentDTO {
+        component_id: Some(component.component_id),
+        vehicle_id: component.vehicle_id,
+        component_type: component.component_type,
+        brand: component.brand,
+        model: component.model,
+        installed_at: component.installed_at.map(|d| Utc.timestamp(d.num_seconds(), 0)),
+        removed_at: component.removed_at.map(|d| Utc.timestamp(d.num_seconds(), 0)),
+        installed_distance: component.installed_distance,
+        wear_limit: component.wear_limit,
+        distance,
+        wear_status: wear_status(distance, component.wear_limit)
+    }
+}
+
+/// New component from a client DTO. The id is always generated here, so a POST can't overwrite an existing component.
+pub fn get_component(user_id: Uuid, component_dto: ComponentDTO) -> Component {
+    Component {
+        user_id,
+        component_id: Uuid::new_v4(),
+        vehicle_id: component_dto.vehicle_id,
+        installed_at: component_dto.installed_at.map(|d| Duration::seconds(d.timestamp())),
+        removed_at: component_dto.removed_at.map(|d| Duration::seconds(d.timestamp())),
+        installed_distance: component_dto.installed_distance,
+        accumulated_distance: component_dto.distance,
+        wear_limit: component_dto.wear_limit.or_else(|| default_wear_limit(&component_dto.component_type)),
+        component_type: component_dto.component_type,
+        brand: component_dto.brand,
+        model: component_dto.model
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn given_installed_component_when_get_component_dto_then_distance_includes_current_installation() {
+        let component = fixture::component(Some(Uuid::new_v4()), Some(1000), 500, Some(3000));
+
+        let component_dto = get_component_dto(component, Some(3200));
+
+        assert_eq!(2700, component_dto.distance);
+        assert_eq!(WearStatus::Warning, component_dto.wear_status);
+    }
+
+    #[test]
+    fn given_removed_component_when_get_component_dto_then_distance_is_accumulated_only() {
+        let component = fixture::component(None, Some(1000), 3100, Some(3000));
+
+        let component_dto = get_component_dto(component, None);
+
+        assert_eq!(3100, component_dto.distance);
+        assert_eq!(WearStatus::Worn, component_dto.wear_status);
+    }
+
+    #[test]
+    fn given_no_wear_limit_when_wear_status_then_returns_ok() {
+        assert_eq!(WearStatus::Ok, wear_status(100000, None));
+        assert_eq!(WearStatus::Ok, wear_status(100, Some(3000)));
+    }
+
+    #[test]
+    fn given_dto_without_wear_limit_when_get_component_then_uses_default_for_type() {
+        let component = get_component(Uuid::new_v4(), ComponentDTO {
+            component_id: None,
+            vehicle_id: None,
+            component_type: "Chain".to_string(),
+            brand: "the brand".to_string(),
+            model: "the model".to_string(),
+            installed_at: None,
+            removed_at: None,
+            installed_distance: None,
+            wear_limit: None,
+            distance: 150,
+            wear_status: WearStatus::Worn
+        });
+
+        assert_eq!(Some(3000), component.wear_limit);
+        assert_eq!(150, component.accumulated_distance);
+        assert_eq!("Chain", component.component_type);
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub fn component(vehicle_id: Option<Uuid>, installed_distance: Option<i32>, accumulated_distance: i32, wear_limit: Option<i32>) -> Component {
+            Component {
+                user_id: Uuid::new_v4(),
+                component_id: Uuid::new_v4(),
+                vehicle_id,
+                component_type: "chain".to_string(),
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                installed_at: Some(Duration::seconds(5)),
+                removed_at: None,
+                installed_distance,
+                accumulated_distance,
+                wear_limit
+            }
+        }
+    }
+}
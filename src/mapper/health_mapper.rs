@@ -0,0 +1,72 @@
+use crate::dao::circuit_breaker::{CircuitMetrics, CircuitState};
+use crate::dto::health_dto::{CacheMetricsDTO, CircuitBreakerMetricsDTO, ReadinessDTO};
+use crate::repository::cached_vehicle_repository::CacheMetrics;
+
+pub const READY: &str = "ready";
+pub const NOT_READY: &str = "not_ready";
+
+pub fn is_ready(state: CircuitState) -> bool {
+    state != CircuitState::Open
+}
+
+pub fn get_readiness_dto(state: CircuitState) -> ReadinessDTO {
+    ReadinessDTO {
+        status: if is_ready(state) { READY } else { NOT_READY }.to_string(),
+        circuit_breaker: state.as_str().to_string()
+    }
+}
+
+pub fn get_circuit_breaker_metrics_dto(state: CircuitState, metrics: CircuitMetrics) -> CircuitBreakerMetricsDTO {
+    CircuitBreakerMetricsDTO {
+        state: state.as_str().to_string(),
+        calls: metrics.calls,
+        failures: metrics.failures,
+        slow_calls: metrics.slow_calls,
+        rejected_calls: metrics.rejected_calls,
+        times_opened: metrics.times_opened
+    }
+}
+
+pub fn get_cache_metrics_dto(metrics: CacheMetrics) -> CacheMetricsDTO {
+    CacheMetricsDTO {
+        hits: metrics.hits,
+        misses: metrics.misses,
+        coalesced: metrics.coalesced,
+        size: metrics.size
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn given_half_open_circuit_when_get_readiness_dto_then_is_ready() {
+        let readiness_dto = get_readiness_dto(CircuitState::HalfOpen);
+
+        assert_eq!(READY, readiness_dto.status);
+        assert_eq!("half_open", readiness_dto.circuit_breaker);
+    }
+
+    #[test]
+    fn given_open_circuit_when_get_readiness_dto_then_is_not_ready() {
+        let readiness_dto = get_readiness_dto(CircuitState::Open);
+
+        assert_eq!(NOT_READY, readiness_dto.status);
+        assert_eq!("open", readiness_dto.circuit_breaker);
+    }
+
+    #[test]
+    fn when_get_circuit_breaker_metrics_dto_then_copies_counters() {
+        let metrics = CircuitMetrics { calls: 10, failures: 4, slow_calls: 1, rejected_calls: 7, times_opened: 2 };
+
+        let metrics_dto = get_circuit_breaker_metrics_dto(CircuitState::Closed, metrics);
+
+        assert_eq!("closed", metrics_dto.state);
+        assert_eq!(10, metrics_dto.calls);
+        assert_eq!(4, metrics_dto.failures);
+        assert_eq!(1, metrics_dto.slow_calls);
+        assert_eq!(7, metrics_dto.rejected_calls);
+        assert_eq!(2, metrics_dto.times_opened);
+    }
+}
@@ -0,0 +1,136 @@
+use uuid::Uuid;
+
+use crate::domain::maintenance::{MaintenanceRecord, ReminderRule};
+use crate::dto::maintenance_dto::{MaintenanceRecordDTO, ReminderRuleDTO};
+
+pub fn get_record_dto(record: MaintenanceRecord) -> MaintenanceRecordDTO {
+    MaintenanceRecordDTO {
+        record_id: Some(record.record_id),
+        performed_on: record.performed_on,
+        odometer: record.odometer,
+        description: record.description,
+        cost: record.cost,
+        parts: record.parts.unwrap_or_default(),
+        rule_id: record.rule_id
+    }
+}
+
+pub fn get_record(user_id: Uuid, vehicle_id: Uuid, record_dto: MaintenanceRecordDTO) -> MaintenanceRecord {
+    MaintenanceRecord {
+        user_id,
+        vehicle_id,
+        performed_on: record_dto.performed_on,
+        record_id: record_dto.record_id.unwrap_or_else(Uuid::new_v4),
+        odometer: record_dto.odometer,
+        description: record_dto.description,
+        cost: record_dto.cost,
+        parts: Some(record_dto.parts),
+        rule_id: record_dto.rule_id
+    }
+}
+
+pub fn get_rule_dto(rule: ReminderRule) -> ReminderRuleDTO {
+    ReminderRuleDTO {
+        rule_id: Some(rule.rule_id),
+        description: rule.description,
+        interval_distance: rule.interval_distance,
+        interval_months: rule.interval_months
+    }
+}
+
+pub fn get_rule(user_id: Uuid, vehicle_id: Uuid, rule_dto: ReminderRuleDTO) -> ReminderRule {
+    ReminderRule {
+        user_id,
+        vehicle_id,
+        rule_id: rule_dto.rule_id.unwrap_or_else(Uuid::new_v4),
+        description: rule_dto.description,
+        interval_distance: rule_dto.interval_distance,
+        interval_months: rule_dto.interval_months
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn given_record_dto_when_get_record_then_returns_record() {
+        let record_dto = MaintenanceRecordDTO {
+            record_id: None,
+            performed_on: NaiveDate::from_ymd(2021, 6, 1),
+            odometer: fixture::ODOMETER,
+            description: fixture::DESCRIPTION.to_string(),
+            cost: fixture::COST,
+            parts: vec!(fixture::PART.to_string()),
+            rule_id: None
+        };
+
+        let record = get_record(fixture::user_id(), fixture::vehicle_id(), record_dto);
+
+        assert_eq!(record.user_id, fixture::user_id());
+        assert_eq!(record.vehicle_id, fixture::vehicle_id());
+        assert_eq!(record.performed_on, NaiveDate::from_ymd(2021, 6, 1));
+        assert_eq!(record.odometer, fixture::ODOMETER);
+        assert_eq!(record.description, fixture::DESCRIPTION.to_string());
+        assert_eq!(record.cost, fixture::COST);
+        assert_eq!(record.parts, Some(vec!(fixture::PART.to_string())));
+        assert!(record.rule_id.is_none());
+    }
+
+    #[test]
+    fn given_record_without_parts_when_get_record_dto_then_returns_empty_parts() {
+        let record = MaintenanceRecord {
+            user_id: fixture::user_id(),
+            vehicle_id: fixture::vehicle_id(),
+            performed_on: NaiveDate::from_ymd(2021, 6, 1),
+            record_id: fixture::vehicle_id(),
+            odometer: fixture::ODOMETER,
+            description: fixture::DESCRIPTION.to_string(),
+            cost: fixture::COST,
+            parts: None,
+            rule_id: None
+        };
+
+        let record_dto = get_record_dto(record);
+
+        assert_eq!(record_dto.record_id, Some(fixture::vehicle_id()));
+        assert!(record_dto.parts.is_empty());
+    }
+
+    #[test]
+    fn given_rule_dto_when_get_rule_then_returns_rule() {
+        let rule = get_rule(fixture::user_id(), fixture::vehicle_id(), ReminderRuleDTO {
+            rule_id: None,
+            description: fixture::DESCRIPTION.to_string(),
+            interval_distance: Some(3000),
+            interval_months: Some(12)
+        });
+
+        let rule_dto = get_rule_dto(rule);
+
+        assert!(rule_dto.rule_id.is_some());
+        assert_eq!(rule_dto.description, fixture::DESCRIPTION.to_string());
+        assert_eq!(rule_dto.interval_distance, Some(3000));
+        assert_eq!(rule_dto.interval_months, Some(12));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const ODOMETER: i32 = 4500;
+        pub const DESCRIPTION: &str = "chain replacement";
+        pub const COST: f64 = 35.5;
+        pub const PART: &str = "chain";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+    }
+}
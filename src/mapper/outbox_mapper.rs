@@ -0,0 +1,102 @@
+use chrono::{Utc, TimeZone};
+use rocket::serde::uuid::Uuid;
+
+use crate::domain::outbox::{self, OutboxEntry};
+use crate::domain::vehicle::Vehicle;
+use crate::domain::vehicle_event::{VehicleEvent, VehicleEventKind};
+use crate::mapper::vehicle_mapper;
+
+/// Outbox entry of an event happening now, with a new `event_id`.
+pub fn get_outbox_entry(kind: VehicleEventKind, user_id: Uuid, vehicle_id: Uuid, vehicle: Option<&Vehicle>) -> OutboxEntry {
+    let occurred_at_ms = Utc::now().timestamp_millis();
+
+    OutboxEntry {
+        shard: outbox::shard(user_id),
+        bucket: outbox::bucket(occurred_at_ms),
+        occurred_at_ms,
+        event_id: Uuid::new_v4(),
+        user_id,
+        vehicle_id,
+        event: kind.name().to_string(),
+        vehicle: vehicle.map(vehicle_mapper::get_vehicle_json)
+    }
+}
+
+/// The event of an outbox entry, its stream `id` being left for the event log to assign.
+/// `None` when the entry names no known event or holds a vehicle that cannot be read.
+pub fn get_vehicle_event(entry: OutboxEntry) -> Option<VehicleEvent> {
+    let vehicle = match entry.vehicle {
+        Some(vehicle) => Some(vehicle_mapper::read_vehicle_json(&vehicle)?),
+        None => None
+    };
+
+    Some(VehicleEvent {
+        id: 0,
+        event_id: entry.event_id,
+        user_id: entry.user_id,
+        vehicle_id: entry.vehicle_id,
+        kind: VehicleEventKind::from_name(&entry.event)?,
+        occurred_at: Utc.timestamp_millis(entry.occurred_at_ms),
+        vehicle
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+
+    #[test]
+    fn given_saved_vehicle_when_get_outbox_entry_then_get_vehicle_event_reads_it_back() {
+        let vehicle = fixture::vehicle();
+
+        let entry = get_outbox_entry(VehicleEventKind::Retired, vehicle.user_id, vehicle.vehicle_id, Some(&vehicle));
+        let event_id = entry.event_id;
+
+        assert_eq!(outbox::shard(vehicle.user_id), entry.shard);
+        assert_eq!(outbox::bucket(entry.occurred_at_ms), entry.bucket);
+        assert_eq!("retired", entry.event);
+
+        let event = get_vehicle_event(entry).unwrap();
+
+        assert_eq!(event_id, event.event_id);
+        assert_eq!(VehicleEventKind::Retired, event.kind);
+        let read = event.vehicle.unwrap();
+        assert_eq!((fixture::NAME.to_string(), Some(Duration::seconds(10))), (read.name, read.retired_at));
+        assert!(read.picture.is_some());
+    }
+
+    #[test]
+    fn given_unknown_event_when_get_vehicle_event_then_returns_none() {
+        let vehicle = fixture::vehicle();
+        let entry = OutboxEntry {
+            event: "repainted".to_string(),
+            ..get_outbox_entry(VehicleEventKind::Created, vehicle.user_id, vehicle.vehicle_id, None)
+        };
+
+        assert!(get_vehicle_event(entry).is_none());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const NAME: &str = "the vehicle name";
+
+        pub fn vehicle() -> Vehicle {
+            Vehicle {
+                name: NAME.to_string(),
+                user_id: Uuid::new_v4(),
+                vehicle_id: Uuid::new_v4(),
+                created_at: Duration::seconds(5),
+                vehicle_type: "bike".to_string(),
+                retired_at: Some(Duration::seconds(10)),
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: 500,
+                owner_since: NaiveDate::from_ymd(2015, 12, 2),
+                manufacturing_date: NaiveDate::from_ymd(2015, 12, 2),
+                picture: Some("the picture".to_string())
+            }
+        }
+    }
+}
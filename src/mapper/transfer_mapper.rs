@@ -0,0 +1,73 @@
+use chrono::{Utc, TimeZone};
+
+use crate::domain::transfer::{OwnershipRecord, TransferOffer};
+use crate::dto::transfer_dto::{OwnershipRecordDTO, TransferOfferDTO};
+
+pub fn get_offer_dto(offer: TransferOffer) -> TransferOfferDTO {
+    TransferOfferDTO {
+        offer_id: offer.offer_id,
+        from_user_id: offer.from_user_id,
+        to_user_id: offer.to_user_id,
+        vehicle_id: offer.vehicle_id,
+        status: offer.status,
+        created_at: Utc.timestamp(offer.created_at.num_seconds(), 0)
+    }
+}
+
+pub fn get_ownership_record_dto(record: OwnershipRecord) -> OwnershipRecordDTO {
+    OwnershipRecordDTO {
+        user_id: record.user_id,
+        owner_since: record.owner_since,
+        owner_until: Utc.timestamp(record.owner_until.num_seconds(), 0)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+    use uuid::Uuid;
+
+    use crate::domain::transfer::OFFER_PENDING;
+
+    #[test]
+    fn given_offer_when_get_offer_dto_then_returns_offer_dto() {
+        let (from_user_id, to_user_id, vehicle_id, offer_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let offer_dto = get_offer_dto(TransferOffer {
+            to_user_id,
+            offer_id,
+            from_user_id,
+            vehicle_id,
+            status: OFFER_PENDING.to_string(),
+            created_at: Duration::seconds(fixture::CREATED_AT)
+        });
+
+        assert_eq!(offer_dto.offer_id, offer_id);
+        assert_eq!(offer_dto.from_user_id, from_user_id);
+        assert_eq!(offer_dto.to_user_id, to_user_id);
+        assert_eq!(offer_dto.vehicle_id, vehicle_id);
+        assert_eq!(offer_dto.status, OFFER_PENDING.to_string());
+        assert_eq!(offer_dto.created_at, Utc.timestamp(fixture::CREATED_AT, 0));
+    }
+
+    #[test]
+    fn given_ownership_record_when_get_ownership_record_dto_then_returns_dto() {
+        let user_id = Uuid::new_v4();
+
+        let record_dto = get_ownership_record_dto(OwnershipRecord {
+            vehicle_id: Uuid::new_v4(),
+            owner_until: Duration::seconds(fixture::CREATED_AT),
+            user_id,
+            owner_since: NaiveDate::from_ymd(2015, 12, 2)
+        });
+
+        assert_eq!(record_dto.user_id, user_id);
+        assert_eq!(record_dto.owner_since, NaiveDate::from_ymd(2015, 12, 2));
+        assert_eq!(record_dto.owner_until, Utc.timestamp(fixture::CREATED_AT, 0));
+    }
+
+    mod fixture {
+        pub const CREATED_AT: i64 = 1_600_000_000;
+    }
+}
@@ -0,0 +1,93 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use crate::domain::vehicle::Vehicle;
+use crate::domain::vehicle_trash::{self, TrashedVehicle, VehiclePurge};
+use crate::dto::trash_dto::TrashedVehicleDTO;
+use crate::mapper::vehicle_mapper;
+
+/// A vehicle deleted by `actor` at `deleted_at`, to be purged once `retention` passed.
+pub fn get_trashed_vehicle(vehicle: &Vehicle, actor: &str, deleted_at: DateTime<Utc>, retention: Duration) -> TrashedVehicle {
+    TrashedVehicle {
+        user_id: vehicle.user_id,
+        vehicle_id: vehicle.vehicle_id,
+        deleted_at_ms: deleted_at.timestamp_millis(),
+        deleted_by: actor.to_string(),
+        purge_at_ms: (deleted_at + retention).timestamp_millis(),
+        vehicle: vehicle_mapper::get_vehicle_json(vehicle),
+        picture: vehicle.picture.clone()
+    }
+}
+
+pub fn get_vehicle_purge(trashed: &TrashedVehicle) -> VehiclePurge {
+    VehiclePurge {
+        day: vehicle_trash::day(trashed.purge_at_ms),
+        shard: vehicle_trash::shard(trashed.vehicle_id),
+        purge_at_ms: trashed.purge_at_ms,
+        user_id: trashed.user_id,
+        vehicle_id: trashed.vehicle_id,
+        picture: trashed.picture.clone()
+    }
+}
+
+/// The vehicle as it was deleted, pointing at its picture blob again, `None` when it cannot be read.
+pub fn get_deleted_vehicle(trashed: &TrashedVehicle) -> Option<Vehicle> {
+    let vehicle = vehicle_mapper::read_vehicle_json(&trashed.vehicle)?;
+
+    Some(Vehicle { picture: trashed.picture.clone(), ..vehicle })
+}
+
+/// `None` when the trashed vehicle cannot be read.
+pub fn get_trashed_vehicle_dto(trashed: TrashedVehicle) -> Option<TrashedVehicleDTO> {
+    Some(TrashedVehicleDTO {
+        vehicle: vehicle_mapper::get_vehicle_dto(get_deleted_vehicle(&trashed)?),
+        deleted_at: Utc.timestamp_millis(trashed.deleted_at_ms),
+        deleted_by: trashed.deleted_by,
+        purge_at: Utc.timestamp_millis(trashed.purge_at_ms)
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rocket::serde::uuid::Uuid;
+
+    #[test]
+    fn given_vehicle_with_picture_when_trashed_then_reads_back_with_picture_key() {
+        let vehicle = fixture::vehicle();
+        let deleted_at = Utc.ymd(2021, 5, 1).and_hms(10, 0, 0);
+
+        let trashed = get_trashed_vehicle(&vehicle, "jane", deleted_at, Duration::days(30));
+
+        assert_eq!(Utc.ymd(2021, 5, 31).and_hms(10, 0, 0).timestamp_millis(), trashed.purge_at_ms);
+        assert_eq!(Some(fixture::PICTURE_KEY.to_string()), get_deleted_vehicle(&trashed).unwrap().picture);
+        let purge = get_vehicle_purge(&trashed);
+        assert_eq!((trashed.purge_at_ms, trashed.picture.clone()), (purge.purge_at_ms, purge.picture));
+        assert_eq!(NaiveDate::from_ymd(2021, 5, 31), purge.day);
+        let trashed_dto = get_trashed_vehicle_dto(trashed).unwrap();
+        assert_eq!((Some(vehicle.vehicle_id), "jane"), (trashed_dto.vehicle.vehicle_id, trashed_dto.deleted_by.as_str()));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const PICTURE_KEY: &str = "d13fe953-297a-4781-807a-f9becc1b71f6/60e18f00-34b8-4a52-916c-adbb0204618e/picture.png";
+
+        pub fn vehicle() -> Vehicle {
+            Vehicle {
+                name: "the vehicle".to_string(),
+                user_id: Uuid::parse_str("d13fe953-297a-4781-807a-f9becc1b71f6").unwrap(),
+                vehicle_id: Uuid::parse_str("60e18f00-34b8-4a52-916c-adbb0204618e").unwrap(),
+                created_at: Duration::seconds(1000),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: 100,
+                owner_since: NaiveDate::from_ymd(2021, 1, 1),
+                manufacturing_date: NaiveDate::from_ymd(2020, 1, 1),
+                picture: Some(PICTURE_KEY.to_string())
+            }
+        }
+    }
+}
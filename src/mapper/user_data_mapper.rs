@@ -0,0 +1,25 @@
+use chrono::{TimeZone, Utc};
+
+use crate::domain::user_data::{self, ErasureJob, UnfinishedErasure};
+use crate::dto::user_data_dto::ErasureJobDTO;
+
+pub fn get_unfinished_erasure(job: &ErasureJob, lease_until_ms: i64) -> UnfinishedErasure {
+    UnfinishedErasure {
+        shard: user_data::shard(job.job_id),
+        job_id: job.job_id,
+        user_id: job.user_id,
+        lease_until_ms
+    }
+}
+
+pub fn get_erasure_job_dto(job: ErasureJob) -> ErasureJobDTO {
+    ErasureJobDTO {
+        job_id: job.job_id,
+        user_id: job.user_id,
+        status: job.status,
+        erased: job.erased,
+        error: job.error,
+        requested_at: Utc.timestamp_millis(job.requested_at_ms),
+        updated_at: Utc.timestamp_millis(job.updated_at_ms)
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::dto::v2::vehicle_dto::{NewVehicleDTO, VehicleDTO};
+use crate::dto::vehicle_dto as v1;
+
+/// The vehicle the service saves for a new one, created at `created_at` under an id it assigns.
+pub fn get_vehicle_to_save(new_vehicle_dto: NewVehicleDTO, created_at: DateTime<Utc>) -> v1::VehicleDTO {
+    v1::VehicleDTO {
+        name: new_vehicle_dto.name,
+        user_id: new_vehicle_dto.user_id,
+        vehicle_id: None,
+        created_at,
+        vehicle_type: new_vehicle_dto.vehicle_type,
+        retired_at: new_vehicle_dto.retired_at,
+        brand: new_vehicle_dto.brand,
+        model: new_vehicle_dto.model,
+        distance: new_vehicle_dto.distance,
+        owner_since: new_vehicle_dto.owner_since,
+        manufacturing_date: new_vehicle_dto.manufacturing_date,
+        picture: None
+    }
+}
+
+/// A vehicle answered by the service, which always holds the id of a saved vehicle.
+pub fn get_vehicle_dto(vehicle_dto: v1::VehicleDTO) -> VehicleDTO {
+    VehicleDTO {
+        name: vehicle_dto.name,
+        user_id: vehicle_dto.user_id,
+        vehicle_id: vehicle_dto.vehicle_id.unwrap_or_else(Uuid::nil),
+        created_at: vehicle_dto.created_at,
+        vehicle_type: vehicle_dto.vehicle_type,
+        retired_at: vehicle_dto.retired_at,
+        brand: vehicle_dto.brand,
+        model: vehicle_dto.model,
+        distance: vehicle_dto.distance,
+        owner_since: vehicle_dto.owner_since,
+        manufacturing_date: vehicle_dto.manufacturing_date,
+        picture: vehicle_dto.picture
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+
+    #[test]
+    fn given_new_vehicle_dto_when_get_vehicle_to_save_then_server_assigns_id_and_creation_time() {
+        let new_vehicle_dto = NewVehicleDTO {
+            name: fixture::EXPECTED_VEHICLE_NAME.to_string(),
+            user_id: Uuid::parse_str(fixture::USER_ID_STR).unwrap(),
+            vehicle_type: "bike".to_string(),
+            retired_at: None,
+            brand: "the brand".to_string(),
+            model: "the model".to_string(),
+            distance: 15,
+            owner_since: NaiveDate::from_num_days_from_ce(15),
+            manufacturing_date: NaiveDate::from_num_days_from_ce(15)
+        };
+
+        let vehicle_dto = get_vehicle_to_save(new_vehicle_dto, Utc.timestamp(fixture::EXPECTED_CREATED_AT, 0));
+
+        assert_eq!(fixture::EXPECTED_VEHICLE_NAME, vehicle_dto.name);
+        assert!(vehicle_dto.vehicle_id.is_none());
+        assert_eq!(Utc.timestamp(fixture::EXPECTED_CREATED_AT, 0), vehicle_dto.created_at);
+        assert!(vehicle_dto.picture.is_none());
+    }
+
+    mod fixture {
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const EXPECTED_VEHICLE_NAME: &str = "the vehicle name";
+        pub const EXPECTED_CREATED_AT: i64 = 5;
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc, TimeZone};
+use rocket::serde::json::Value;
+use rocket::serde::uuid::Uuid;
+use uuid::v1::{Context, Timestamp};
+
+use crate::domain::vehicle::Vehicle;
+use crate::domain::vehicle_event::VehicleEventKind;
+use crate::domain::vehicle_history::{VehicleChange, VehicleVersion};
+use crate::dto::vehicle_history_dto::{FieldChangeDTO, VehicleVersionDTO};
+use crate::mapper::vehicle_mapper;
+
+/// Left alone by the saves without one, which do not change it, so only part of the diff of picture uploads.
+const PICTURE_FIELD: &str = "picture";
+
+/// Clock sequence of the version timeuuids, counting up so that versions made within the same tick differ.
+static VERSION_CLOCK_SEQUENCE: Context = Context::new(0);
+
+/// Change of a save by `actor` turning `previous` into `vehicle`, `previous` being `None` for a new vehicle,
+/// versioned now.
+pub fn get_vehicle_change(previous: Option<&Vehicle>, vehicle: &Vehicle, actor: &str, restored_from: Option<Uuid>) -> VehicleChange {
+    let event = VehicleEventKind::of_save(previous, vehicle);
+    let now = Utc::now();
+
+    VehicleChange {
+        event,
+        version: VehicleVersion {
+            user_id: vehicle.user_id,
+            vehicle_id: vehicle.vehicle_id,
+            version: new_version(now),
+            saved_at_ms: now.timestamp_millis(),
+            actor: actor.to_string(),
+            event: event.name().to_string(),
+            diff: serde_json::to_string(&diff(previous, vehicle))
+                .unwrap_or_else(|e| panic!("Failed to serialize diff of Vehicle {} with error {:?}", vehicle.vehicle_id, e)),
+            vehicle: vehicle_mapper::get_vehicle_json(vehicle),
+            restored_from
+        }
+    }
+}
+
+/// Change of `actor` moving a vehicle to the trash, versioned now with the vehicle as it was deleted.
+pub fn get_deletion_change(vehicle: &Vehicle, actor: &str) -> VehicleChange {
+    let change = get_vehicle_change(Some(vehicle), vehicle, actor, None);
+
+    VehicleChange {
+        event: VehicleEventKind::Deleted,
+        version: VehicleVersion { event: VehicleEventKind::Deleted.name().to_string(), ..change.version }
+    }
+}
+
+/// Change of `actor` accepting the transfer of `previous` to the new owner of `vehicle`, versioned now in the
+/// history of the new owner.
+pub fn get_transfer_change(previous: &Vehicle, vehicle: &Vehicle, actor: &str) -> VehicleChange {
+    let change = get_vehicle_change(Some(previous), vehicle, actor, None);
+
+    VehicleChange {
+        event: VehicleEventKind::Transferred,
+        version: VehicleVersion { event: VehicleEventKind::Transferred.name().to_string(), ..change.version }
+    }
+}
+
+/// The vehicle as saved in a version, `None` when it cannot be read.
+pub fn get_versioned_vehicle(version: &VehicleVersion) -> Option<Vehicle> {
+    vehicle_mapper::read_vehicle_json(&version.vehicle)
+}
+
+/// `None` when the version holds a vehicle or a diff that cannot be read.
+pub fn get_vehicle_version_dto(version: VehicleVersion) -> Option<VehicleVersionDTO> {
+    let vehicle = get_versioned_vehicle(&version)?;
+
+    Some(VehicleVersionDTO {
+        version: version.version,
+        changed_at: Utc.timestamp_millis(version.saved_at_ms),
+        actor: version.actor,
+        event: version.event,
+        changes: serde_json::from_str(&version.diff).ok()?,
+        vehicle: vehicle_mapper::get_vehicle_dto(vehicle),
+        restored_from: version.restored_from
+    })
+}
+
+/// Timeuuid of a version saved at `now`. The node id is random, so that versions made by two instances in the same
+/// tick differ as well.
+fn new_version(now: DateTime<Utc>) -> Uuid {
+    let timestamp = Timestamp::from_unix(&VERSION_CLOCK_SEQUENCE, now.timestamp() as u64, now.timestamp_subsec_nanos());
+
+    Uuid::new_v1(timestamp, &Uuid::new_v4().as_bytes()[..6])
+        .unwrap_or_else(|e| panic!("Failed to create version timeuuid with error {:?}", e))
+}
+
+/// Fields whose value differs between the JSON of both vehicles.
+fn diff(previous: Option<&Vehicle>, vehicle: &Vehicle) -> BTreeMap<String, FieldChangeDTO> {
+    let fields = |vehicle: Option<&Vehicle>| match vehicle.map(|vehicle| serde_json::to_value(vehicle_mapper::get_vehicle_dto(vehicle.clone()))) {
+        Some(Ok(Value::Object(fields))) => fields,
+        _ => Default::default()
+    };
+    let before = fields(previous);
+
+    fields(Some(vehicle)).into_iter()
+        .filter(|(field, _)| field != PICTURE_FIELD || vehicle.picture.is_some())
+        .filter_map(|(field, to)| {
+            let from = before.get(&field).cloned().unwrap_or(Value::Null);
+            if from == to { None } else { Some((field, FieldChangeDTO { from, to })) }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+    use rocket::serde::json::json;
+    use rocket::serde::uuid::Uuid;
+
+    #[test]
+    fn given_updated_vehicle_when_get_vehicle_change_then_diffs_only_changed_fields() {
+        let previous = fixture::vehicle(100);
+        let vehicle = Vehicle { picture: None, ..fixture::vehicle(250) };
+
+        let change = get_vehicle_change(Some(&previous), &vehicle, "the actor", None);
+
+        assert_eq!(VehicleEventKind::Updated, change.event);
+        assert_eq!(("the actor", "updated"), (change.version.actor.as_str(), change.version.event.as_str()));
+        let version_dto = get_vehicle_version_dto(change.version).unwrap();
+        assert_eq!(1, version_dto.changes.len());
+        assert_eq!(FieldChangeDTO { from: json!(100), to: json!(250) }, version_dto.changes["distance"]);
+        assert_eq!(250, version_dto.vehicle.distance);
+    }
+
+    #[test]
+    fn given_uploaded_picture_when_get_vehicle_change_then_diffs_picture() {
+        let previous = Vehicle { picture: None, ..fixture::vehicle(100) };
+
+        let change = get_vehicle_change(Some(&previous), &fixture::vehicle(100), "the actor", None);
+
+        let changes: BTreeMap<String, FieldChangeDTO> = serde_json::from_str(&change.version.diff).unwrap();
+        assert_eq!(vec!(PICTURE_FIELD), changes.keys().map(String::as_str).collect::<Vec<&str>>());
+        assert_eq!(Value::Null, changes[PICTURE_FIELD].from);
+    }
+
+    #[test]
+    fn given_new_vehicle_when_get_vehicle_change_then_diffs_every_field_from_null() {
+        let vehicle = Vehicle { picture: None, ..fixture::vehicle(100) };
+
+        let change = get_vehicle_change(None, &vehicle, "the actor", None);
+
+        assert_eq!(VehicleEventKind::Created, change.event);
+        let changes: BTreeMap<String, FieldChangeDTO> = serde_json::from_str(&change.version.diff).unwrap();
+        assert_eq!(Some(&FieldChangeDTO { from: Value::Null, to: json!("the vehicle name") }), changes.get("name"));
+        assert!(!changes.contains_key("picture"));
+    }
+
+    #[test]
+    fn given_same_time_when_new_version_then_versions_differ() {
+        let now = Utc.timestamp_millis(5000);
+
+        let (first, second) = (new_version(now), new_version(now));
+
+        assert_ne!(first, second);
+        assert_eq!((1, 1), (first.get_version_num(), second.get_version_num()));
+    }
+
+    #[test]
+    fn when_get_deletion_change_then_records_deleted_vehicle_without_changes() {
+        let change = get_deletion_change(&fixture::vehicle(100), "the actor");
+
+        assert_eq!((VehicleEventKind::Deleted, "deleted"), (change.event, change.version.event.as_str()));
+        let version_dto = get_vehicle_version_dto(change.version).unwrap();
+        assert!(version_dto.changes.is_empty());
+        assert_eq!(100, version_dto.vehicle.distance);
+    }
+
+    #[test]
+    fn when_get_transfer_change_then_versions_vehicle_for_new_owner_with_owner_changes() {
+        let previous = fixture::vehicle(100);
+        let vehicle = Vehicle { user_id: Uuid::parse_str("6176bc4b-33b6-4c9c-a4ad-c65da1322a80").unwrap(), owner_since: NaiveDate::from_ymd(2021, 3, 4), ..fixture::vehicle(100) };
+
+        let change = get_transfer_change(&previous, &vehicle, "the actor");
+
+        assert_eq!((VehicleEventKind::Transferred, "transferred"), (change.event, change.version.event.as_str()));
+        assert_eq!(vehicle.user_id, change.version.user_id);
+        let version_dto = get_vehicle_version_dto(change.version).unwrap();
+        assert_eq!(vec!("owner_since", "user_id"), version_dto.changes.keys().map(String::as_str).collect::<Vec<&str>>());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub fn vehicle(distance: i32) -> Vehicle {
+            Vehicle {
+                name: "the vehicle name".to_string(),
+                user_id: Uuid::parse_str("a906615e-2e6a-4edb-9377-5a6b8544791b").unwrap(),
+                vehicle_id: Uuid::parse_str("88573010-cf4c-490e-9d29-f8517dc60b90").unwrap(),
+                created_at: Duration::seconds(5),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance,
+                owner_since: NaiveDate::from_ymd(2015, 12, 2),
+                manufacturing_date: NaiveDate::from_ymd(2015, 12, 2),
+                picture: Some("the picture".to_string())
+            }
+        }
+    }
+}
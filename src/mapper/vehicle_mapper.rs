@@ -1,10 +1,19 @@
 use uuid::Uuid;
 use chrono::{Utc, TimeZone, Duration};
 
-use crate::domain::vehicle::Vehicle;
-use crate::dto::vehicle_dto::VehicleDTO;
+use crate::domain::vehicle::{Vehicle, VehicleProjection};
+use crate::domain::vehicle_event::VehicleEvent;
+use crate::dto::vehicle_dto::{VehicleDTO, VehicleEventDTO, VehicleProjectionDTO};
+use crate::repository::entity::Entity;
+
+/// Public URL under which the picture of a vehicle is served.
+pub fn picture_url(user_id: Uuid, vehicle_id: Uuid) -> String {
+    format!("/api/vehicle/{}/{}/picture", user_id, vehicle_id)
+}
 
 pub fn get_vehicle_dto(vehicle: Vehicle) -> VehicleDTO {
+    let picture = vehicle.picture.as_ref().map(|_| picture_url(vehicle.user_id, vehicle.vehicle_id));
+
     VehicleDTO {
         name: vehicle.name,
         user_id: vehicle.user_id,
@@ -17,10 +26,11 @@ pub fn get_vehicle_dto(vehicle: Vehicle) -> VehicleDTO {
         distance: vehicle.distance,
         owner_since: vehicle.owner_since,
         manufacturing_date: vehicle.manufacturing_date,
-        picture: vehicle.picture
+        picture
     }
 }
 
+/// `picture` is managed through the picture upload endpoint, so the value sent by the client is ignored.
 pub fn get_vehicle(vehicle_dto: VehicleDTO) -> Vehicle {
     Vehicle {
         name: vehicle_dto.name,
@@ -34,8 +44,104 @@ pub fn get_vehicle(vehicle_dto: VehicleDTO) -> Vehicle {
         distance: vehicle_dto.distance,
         owner_since: vehicle_dto.owner_since,
         manufacturing_date: vehicle_dto.manufacturing_date,
-        picture: vehicle_dto.picture
+        picture: None
+    }
+}
+
+/// The vehicle as a JSON `VehicleDTO`, the way it is stored with the outbox entries and versions about it.
+pub fn get_vehicle_json(vehicle: &Vehicle) -> String {
+    serde_json::to_string(&get_vehicle_dto(vehicle.clone()))
+        .unwrap_or_else(|e| panic!("Failed to serialize Vehicle {} with error {:?}", vehicle.vehicle_id, e))
+}
+
+/// The vehicle of a `get_vehicle_json` value, `None` when it cannot be read.
+pub fn read_vehicle_json(json: &str) -> Option<Vehicle> {
+    let vehicle_dto: VehicleDTO = serde_json::from_str(json).ok()?;
+    // Only whether the vehicle has a picture matters once mapped back to a DTO.
+    let picture = vehicle_dto.picture.clone();
+
+    Some(Vehicle { picture, ..get_vehicle(vehicle_dto) })
+}
+
+/// Every field of a vehicle, the fields being named after the columns they are read from.
+pub fn all_fields() -> Vec<&'static str> {
+    Vehicle::COLUMNS.to_vec()
+}
+
+/// Fields of a comma separated `fields` parameter, in the order requested and without repetitions.
+pub fn parse_fields(fields: &str) -> Result<Vec<&'static str>, String> {
+    let mut parsed: Vec<&'static str> = Vec::new();
+
+    for field in fields.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+        let column = Vehicle::COLUMNS.iter()
+            .find(|column| **column == field)
+            .ok_or_else(|| format!("Unknown vehicle field {}", field))?;
+        if !parsed.contains(column) {
+            parsed.push(*column);
+        }
+    }
+
+    if parsed.is_empty() {
+        return Err("No vehicle field requested".to_string());
+    }
+
+    Ok(parsed)
+}
+
+/// Columns to read for `fields`, always including the key of the vehicle which paging and picture URLs need.
+pub fn columns(fields: &[&'static str]) -> Vec<&'static str> {
+    let mut columns = fields.to_vec();
+    for key in Vehicle::PARTITION_KEY.iter().chain(Vehicle::CLUSTERING_KEY) {
+        if !columns.contains(key) {
+            columns.push(*key);
+        }
     }
+
+    columns
+}
+
+/// Only `fields` of a projection, which may hold more columns than requested.
+pub fn get_vehicle_projection_dto(projection: VehicleProjection, fields: &[&str]) -> VehicleProjectionDTO {
+    let requested = |field: &str| fields.contains(&field);
+    let picture = match (projection.user_id, projection.vehicle_id) {
+        (Some(user_id), Some(vehicle_id)) => projection.picture
+            .map(|picture| picture.map(|_| picture_url(user_id, vehicle_id))),
+        _ => None
+    };
+
+    VehicleProjectionDTO {
+        name: projection.name.filter(|_| requested("name")),
+        user_id: projection.user_id.filter(|_| requested("user_id")),
+        vehicle_id: projection.vehicle_id.filter(|_| requested("vehicle_id")),
+        created_at: projection.created_at.map(|d| Utc.timestamp(d.num_seconds(), 0)).filter(|_| requested("created_at")),
+        vehicle_type: projection.vehicle_type.filter(|_| requested("vehicle_type")),
+        retired_at: projection.retired_at.map(|retired_at| retired_at.map(|d| Utc.timestamp(d.num_seconds(), 0))).filter(|_| requested("retired_at")),
+        brand: projection.brand.filter(|_| requested("brand")),
+        model: projection.model.filter(|_| requested("model")),
+        distance: projection.distance.filter(|_| requested("distance")),
+        owner_since: projection.owner_since.filter(|_| requested("owner_since")),
+        manufacturing_date: projection.manufacturing_date.filter(|_| requested("manufacturing_date")),
+        picture: picture.filter(|_| requested("picture"))
+    }
+}
+
+pub fn get_vehicle_event_dto(event: VehicleEvent) -> VehicleEventDTO {
+    VehicleEventDTO {
+        vehicle_id: event.vehicle_id,
+        occurred_at: event.occurred_at,
+        vehicle: event.vehicle.map(get_vehicle_dto)
+    }
+}
+
+/// Opaque token of the key a search page ends with, `<user_id>.<vehicle_id>`.
+pub fn page_token(user_id: Uuid, vehicle_id: Uuid) -> String {
+    format!("{}.{}", user_id, vehicle_id)
+}
+
+pub fn parse_page_token(token: &str) -> Option<(Uuid, Uuid)> {
+    let (user_id, vehicle_id) = token.split_once('.')?;
+
+    Some((Uuid::parse_str(user_id).ok()?, Uuid::parse_str(vehicle_id).ok()?))
 }
 
 #[cfg(test)]
@@ -74,7 +180,7 @@ pub mod tests {
         assert_eq!(vehicle_dto.distance, fixture::EXPECTED_DISTANCE);
         assert_eq!(vehicle_dto.owner_since, NaiveDate::from_num_days_from_ce(fixture::EXPECTED_OWNER_SINCE));
         assert_eq!(vehicle_dto.manufacturing_date, NaiveDate::from_num_days_from_ce(fixture::EXPECTED_MANUFACTURING_DATE));
-        assert_eq!(vehicle_dto.picture.unwrap(), fixture::EXPECTED_PICTURE.to_string());
+        assert_eq!(vehicle_dto.picture.unwrap(), fixture::EXPECTED_PICTURE_URL.to_string());
     }
 
     #[test]
@@ -108,7 +214,42 @@ pub mod tests {
         assert_eq!(vehicle.distance, fixture::EXPECTED_DISTANCE);
         assert_eq!(vehicle.owner_since, NaiveDate::from_num_days_from_ce(fixture::EXPECTED_OWNER_SINCE));
         assert_eq!(vehicle.manufacturing_date, NaiveDate::from_num_days_from_ce(fixture::EXPECTED_MANUFACTURING_DATE));
-        assert_eq!(vehicle.picture.unwrap(), fixture::EXPECTED_PICTURE.to_string());
+        assert!(vehicle.picture.is_none());
+    }
+
+    #[test]
+    fn when_parse_page_token_then_returns_key_it_was_made_of() {
+        let key = (Uuid::parse_str(fixture::USER_ID_STR).unwrap(), Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap());
+
+        assert_eq!(Some(key), parse_page_token(&page_token(key.0, key.1)));
+        assert_eq!(None, parse_page_token("not-a-token"));
+    }
+
+    #[test]
+    fn when_parse_fields_then_returns_known_fields_once_in_order() {
+        assert_eq!(Ok(vec!("model", "name")), parse_fields(" model,name,,model"));
+        assert!(parse_fields("name,colour").is_err());
+        assert!(parse_fields(",").is_err());
+        assert_eq!(vec!("model", "user_id", "vehicle_id"), columns(&["model"]));
+    }
+
+    #[test]
+    fn given_projection_with_key_when_get_vehicle_projection_dto_then_returns_requested_fields_only() {
+        let projection = VehicleProjection {
+            user_id: Some(Uuid::parse_str(fixture::USER_ID_STR).unwrap()),
+            vehicle_id: Some(Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap()),
+            retired_at: Some(None),
+            picture: Some(Some(fixture::EXPECTED_PICTURE.to_string())),
+            ..VehicleProjection::default()
+        };
+
+        let vehicle_dto = get_vehicle_projection_dto(projection, &["retired_at", "picture"]);
+
+        assert_eq!(VehicleProjectionDTO {
+            retired_at: Some(None),
+            picture: Some(Some(fixture::EXPECTED_PICTURE_URL.to_string())),
+            ..VehicleProjectionDTO::default()
+        }, vehicle_dto);
     }
 
     mod fixture {
@@ -120,6 +261,7 @@ pub mod tests {
         pub const EXPECTED_MODEL: &str = "the model";
         pub const EXPECTED_DISTANCE: i32 = 15;
         pub const EXPECTED_PICTURE: &str = "the picture path";
+        pub const EXPECTED_PICTURE_URL: &str = "/api/vehicle/a906615e-2e6a-4edb-9377-5a6b8544791b/88573010-cf4c-490e-9d29-f8517dc60b90/picture";
         pub const EXPECTED_CREATED_AT: i64 = 5;
         pub const EXPECTED_RETIRED_AT: i64 = 5000;
         pub const EXPECTED_OWNER_SINCE: i32 = 15;
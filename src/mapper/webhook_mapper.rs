@@ -0,0 +1,104 @@
+use chrono::{Utc, TimeZone};
+use rocket::serde::uuid::Uuid;
+
+use crate::domain::vehicle_event::VehicleEvent;
+use crate::domain::webhook::{self, PendingDelivery, Webhook, WebhookDelivery};
+use crate::dto::webhook_dto::{WebhookDTO, WebhookDeliveryDTO, WebhookPayloadDTO};
+use crate::mapper::vehicle_mapper;
+
+/// The webhook without its secret, which is only answered once, when the webhook is created.
+pub fn get_webhook_dto(webhook: Webhook) -> WebhookDTO {
+    WebhookDTO {
+        webhook_id: webhook.webhook_id,
+        url: webhook.url,
+        events: webhook.events,
+        created_at: Utc.timestamp(webhook.created_at.num_seconds(), 0),
+        secret: None
+    }
+}
+
+pub fn get_pending_delivery(delivery: &WebhookDelivery, lease_until_ms: i64) -> PendingDelivery {
+    PendingDelivery {
+        shard: webhook::shard(delivery.delivery_id),
+        delivery_id: delivery.delivery_id,
+        webhook_id: delivery.webhook_id,
+        user_id: delivery.user_id,
+        created_at: delivery.created_at,
+        lease_until_ms
+    }
+}
+
+pub fn get_delivery_dto(delivery: WebhookDelivery) -> WebhookDeliveryDTO {
+    WebhookDeliveryDTO {
+        delivery_id: delivery.delivery_id,
+        event: delivery.event,
+        vehicle_id: delivery.vehicle_id,
+        status: delivery.status,
+        attempts: delivery.attempts,
+        response_status: delivery.response_status,
+        error: delivery.error,
+        created_at: Utc.timestamp(delivery.created_at.num_seconds(), 0),
+        updated_at: Utc.timestamp(delivery.updated_at.num_seconds(), 0)
+    }
+}
+
+pub fn get_payload_dto(delivery_id: Uuid, event: VehicleEvent) -> WebhookPayloadDTO {
+    WebhookPayloadDTO {
+        delivery_id,
+        event: event.kind.name().to_string(),
+        user_id: event.user_id,
+        vehicle_id: event.vehicle_id,
+        occurred_at: event.occurred_at,
+        vehicle: event.vehicle.map(vehicle_mapper::get_vehicle_dto)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    use crate::domain::vehicle_event::VehicleEventKind;
+
+    #[test]
+    fn given_webhook_when_get_webhook_dto_then_leaves_secret_out() {
+        let webhook_dto = get_webhook_dto(Webhook {
+            user_id: Uuid::new_v4(),
+            webhook_id: Uuid::new_v4(),
+            url: fixture::URL.to_string(),
+            secret: "the secret".to_string(),
+            events: vec!("created".to_string()),
+            created_at: Duration::seconds(fixture::CREATED_AT)
+        });
+
+        assert_eq!(fixture::URL, webhook_dto.url);
+        assert_eq!(vec!("created".to_string()), webhook_dto.events);
+        assert_eq!(Utc.timestamp(fixture::CREATED_AT, 0), webhook_dto.created_at);
+        assert!(webhook_dto.secret.is_none());
+    }
+
+    #[test]
+    fn given_transfer_when_get_payload_dto_then_names_event_without_vehicle() {
+        let (delivery_id, user_id, vehicle_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let payload = get_payload_dto(delivery_id, VehicleEvent {
+            id: 1,
+            event_id: delivery_id,
+            user_id,
+            vehicle_id,
+            kind: VehicleEventKind::Transferred,
+            occurred_at: Utc.timestamp(fixture::CREATED_AT, 0),
+            vehicle: None
+        });
+
+        assert_eq!(delivery_id, payload.delivery_id);
+        assert_eq!("transferred", payload.event);
+        assert_eq!((user_id, vehicle_id), (payload.user_id, payload.vehicle_id));
+        assert!(payload.vehicle.is_none());
+    }
+
+    mod fixture {
+        pub const URL: &str = "https://partner.example/hooks";
+        pub const CREATED_AT: i64 = 5;
+    }
+}
@@ -0,0 +1,65 @@
+use crate::parser::track::{ParseError, TrackPoint};
+use crate::parser::{fit, gpx};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileFormat {
+    Gpx,
+    Fit
+}
+
+impl FileFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileFormat::Gpx => "gpx",
+            FileFormat::Fit => "fit"
+        }
+    }
+}
+
+/// FIT files carry the ".FIT" signature at bytes 8..12 of their header,
+/// anything starting with an XML tag mentioning `<gpx` is taken as GPX.
+pub fn detect(data: &[u8]) -> Option<FileFormat> {
+    if data.len() >= 12 && &data[8..12] == b".FIT" {
+        return Some(FileFormat::Fit);
+    }
+
+    let head = &data[..data.len().min(1024)];
+    let head = String::from_utf8_lossy(head);
+
+    if head.trim_start_matches('\u{feff}').trim_start().starts_with('<') && head.contains("<gpx") {
+        return Some(FileFormat::Gpx);
+    }
+
+    None
+}
+
+pub fn parse(data: &[u8]) -> Result<(FileFormat, Vec<TrackPoint>), ParseError> {
+    let format = detect(data).ok_or(ParseError::UnsupportedFormat)?;
+
+    let points = match format {
+        FileFormat::Gpx => gpx::parse(data)?,
+        FileFormat::Fit => fit::parse(data)?
+    };
+
+    Ok((format, points))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn given_gpx_header_when_detect_then_returns_gpx() {
+        assert_eq!(Some(FileFormat::Gpx), detect(br#"<?xml version="1.0"?><gpx version="1.1"></gpx>"#));
+    }
+
+    #[test]
+    fn given_fit_header_when_detect_then_returns_fit() {
+        assert_eq!(Some(FileFormat::Fit), detect(&[14, 16, 0x5e, 0x08, 0, 0, 0, 0, b'.', b'F', b'I', b'T', 0, 0]));
+    }
+
+    #[test]
+    fn given_unknown_content_when_parse_then_returns_unsupported_format() {
+        assert_eq!(Err(ParseError::UnsupportedFormat), parse(b"name,distance\nride,10"));
+    }
+}
@@ -0,0 +1,49 @@
+use chrono::Utc;
+use fitparser::Value;
+use fitparser::profile::MesgNum;
+
+use crate::parser::track::{ParseError, TrackPoint};
+
+const SEMICIRCLES_TO_DEGREES: f64 = 180.0 / 2_147_483_648.0;
+
+pub fn parse(data: &[u8]) -> Result<Vec<TrackPoint>, ParseError> {
+    let records = fitparser::from_bytes(data)
+        .map_err(|e| ParseError::Malformed(e.to_string()))?;
+
+    let points = records.iter()
+        .filter(|record| record.kind() == MesgNum::Record)
+        .filter_map(|record| {
+            let mut latitude = None;
+            let mut longitude = None;
+            let mut elevation = None;
+            let mut time = None;
+
+            for field in record.fields() {
+                match (field.name(), field.value()) {
+                    ("position_lat", Value::SInt32(v)) => latitude = Some(*v as f64 * SEMICIRCLES_TO_DEGREES),
+                    ("position_long", Value::SInt32(v)) => longitude = Some(*v as f64 * SEMICIRCLES_TO_DEGREES),
+                    ("enhanced_altitude", Value::Float64(v)) => elevation = Some(*v),
+                    ("altitude", Value::Float64(v)) => elevation = elevation.or(Some(*v)),
+                    ("timestamp", Value::Timestamp(t)) => time = Some(t.with_timezone(&Utc)),
+                    _ => {}
+                }
+            }
+
+            Some(TrackPoint { latitude: latitude?, longitude: longitude?, elevation, time })
+        })
+        .collect();
+
+    Ok(points)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn given_truncated_fit_when_parse_then_returns_malformed() {
+        let result = parse(&[14, 16, 0x5e, 0x08, 0, 0, 0, 0, b'.', b'F', b'I', b'T']);
+
+        assert!(matches!(result, Err(ParseError::Malformed(_))));
+    }
+}
@@ -0,0 +1,114 @@
+use chrono::{DateTime, Utc};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+
+use crate::parser::track::{ParseError, TrackPoint};
+
+pub fn parse(data: &[u8]) -> Result<Vec<TrackPoint>, ParseError> {
+    let mut reader = Reader::from_reader(data);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut points = Vec::new();
+    let mut current: Option<TrackPoint> = None;
+    let mut element: Option<Vec<u8>> = None;
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.local_name() == b"trkpt" => current = Some(track_point(e)?),
+            Ok(Event::Start(ref e)) => element = Some(e.local_name().to_vec()),
+            Ok(Event::Empty(ref e)) if e.local_name() == b"trkpt" => points.push(track_point(e)?),
+            Ok(Event::Text(ref e)) => {
+                if let (Some(point), Some(name)) = (current.as_mut(), element.as_ref()) {
+                    let text = e.unescape_and_decode(&reader)
+                        .map_err(|e| ParseError::Malformed(e.to_string()))?;
+
+                    match name.as_slice() {
+                        b"ele" => point.elevation = text.parse().ok(),
+                        b"time" => point.time = DateTime::parse_from_rfc3339(&text).ok().map(|t| t.with_timezone(&Utc)),
+                        _ => {}
+                    }
+                }
+            },
+            Ok(Event::End(ref e)) => {
+                if e.local_name() == b"trkpt" {
+                    points.extend(current.take());
+                }
+                element = None;
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(ParseError::Malformed(e.to_string())),
+            _ => {}
+        }
+
+        buf.clear();
+    }
+
+    Ok(points)
+}
+
+fn track_point(element: &BytesStart) -> Result<TrackPoint, ParseError> {
+    let mut latitude = None;
+    let mut longitude = None;
+
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|e| ParseError::Malformed(e.to_string()))?;
+        let value = std::str::from_utf8(&attribute.value)
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok());
+
+        match attribute.key {
+            b"lat" => latitude = value,
+            b"lon" => longitude = value,
+            _ => {}
+        }
+    }
+
+    match (latitude, longitude) {
+        (Some(latitude), Some(longitude)) => Ok(TrackPoint { latitude, longitude, elevation: None, time: None }),
+        _ => Err(ParseError::Malformed("trkpt without valid lat/lon".to_string()))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn given_gpx_when_parse_then_returns_track_points() {
+        let points = parse(fixture::GPX.as_bytes()).unwrap();
+
+        assert_eq!(3, points.len());
+        assert_eq!(43.2630, points[0].latitude);
+        assert_eq!(-2.9350, points[0].longitude);
+        assert_eq!(Some(12.5), points[0].elevation);
+        assert_eq!(Some(Utc.ymd(2021, 6, 1).and_hms(8, 0, 0)), points[0].time);
+        assert_eq!(None, points[2].elevation);
+    }
+
+    #[test]
+    fn given_trkpt_without_lon_when_parse_then_returns_malformed() {
+        let result = parse(br#"<gpx><trk><trkseg><trkpt lat="1.0"/></trkseg></trk></gpx>"#);
+
+        assert!(matches!(result, Err(ParseError::Malformed(_))));
+    }
+
+    #[test]
+    fn given_broken_xml_when_parse_then_returns_malformed() {
+        let result = parse(b"<gpx><trk></gpx>");
+
+        assert!(matches!(result, Err(ParseError::Malformed(_))));
+    }
+
+    mod fixture {
+        pub const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+              <trk><name>morning ride</name><trkseg>
+                <trkpt lat="43.2630" lon="-2.9350"><ele>12.5</ele><time>2021-06-01T08:00:00Z</time></trkpt>
+                <trkpt lat="43.2640" lon="-2.9360"><ele>15.0</ele><time>2021-06-01T08:01:00Z</time></trkpt>
+                <trkpt lat="43.2650" lon="-2.9370"/>
+              </trkseg></trk>
+            </gpx>"#;
+    }
+}
@@ -0,0 +1,121 @@
+use chrono::{DateTime, Utc};
+
+const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackPoint {
+    pub latitude    : f64,
+    pub longitude   : f64,
+    pub elevation   : Option<f64>,
+    pub time        : Option<DateTime<Utc>>
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TrackSummary {
+    pub started_at      : Option<DateTime<Utc>>,
+    pub duration        : i64,
+    pub distance        : f64,
+    pub elevation_gain  : f64
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    UnsupportedFormat,
+    Malformed(String),
+    NoTrackPoints
+}
+
+pub fn summarize(points: &[TrackPoint]) -> Result<TrackSummary, ParseError> {
+    let first = points.first().ok_or(ParseError::NoTrackPoints)?;
+
+    let mut distance = 0.0;
+    let mut elevation_gain = 0.0;
+
+    for pair in points.windows(2) {
+        distance += haversine(&pair[0], &pair[1]);
+
+        if let (Some(from), Some(to)) = (pair[0].elevation, pair[1].elevation) {
+            if to > from {
+                elevation_gain += to - from;
+            }
+        }
+    }
+
+    let started_at = points.iter().find_map(|p| p.time);
+    let finished_at = points.iter().rev().find_map(|p| p.time);
+    let duration = match (started_at, finished_at) {
+        (Some(start), Some(end)) => (end - start).num_seconds().max(0),
+        _ => 0
+    };
+
+    Ok(TrackSummary {
+        started_at: started_at.or(first.time),
+        duration,
+        distance,
+        elevation_gain
+    })
+}
+
+fn haversine(from: &TrackPoint, to: &TrackPoint) -> f64 {
+    let (lat1, lat2) = (from.latitude.to_radians(), to.latitude.to_radians());
+    let delta_lat = lat2 - lat1;
+    let delta_lon = (to.longitude - from.longitude).to_radians();
+
+    let a = (delta_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (delta_lon / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_METERS * a.sqrt().asin()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn given_no_points_when_summarize_then_returns_no_track_points_error() {
+        assert_eq!(Err(ParseError::NoTrackPoints), summarize(&[]));
+    }
+
+    #[test]
+    fn given_points_when_summarize_then_returns_distance_duration_and_elevation_gain() {
+        let points = vec!(
+            fixture::point(0.0, 0.0, Some(100.0), 0),
+            fixture::point(0.0, 0.01, Some(110.0), 60),
+            fixture::point(0.0, 0.02, Some(105.0), 120),
+            fixture::point(0.0, 0.03, Some(125.0), 180));
+
+        let summary = summarize(&points).unwrap();
+
+        assert_eq!(Some(Utc.timestamp(fixture::START, 0)), summary.started_at);
+        assert_eq!(180, summary.duration);
+        assert!((summary.distance - 3335.85).abs() < 1.0);
+        assert!((summary.elevation_gain - 30.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn given_points_without_time_when_summarize_then_duration_is_zero() {
+        let mut points = vec!(fixture::point(0.0, 0.0, None, 0), fixture::point(0.01, 0.0, None, 0));
+        points.iter_mut().for_each(|p| p.time = None);
+
+        let summary = summarize(&points).unwrap();
+
+        assert_eq!(None, summary.started_at);
+        assert_eq!(0, summary.duration);
+        assert_eq!(0.0, summary.elevation_gain);
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const START: i64 = 1_600_000_000;
+
+        pub fn point(latitude: f64, longitude: f64, elevation: Option<f64>, offset: i64) -> TrackPoint {
+            TrackPoint {
+                latitude,
+                longitude,
+                elevation,
+                time: Some(Utc.timestamp(START + offset, 0))
+            }
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rocket::serde::uuid::Uuid;
+use rocket::tokio::io::{self, AsyncBufRead, AsyncBufReadExt, Lines};
+
+use crate::dto::vehicle_dto::{VehicleDTO, VehicleProjectionDTO};
+
+/// Columns of a vehicle CSV file, `picture` being managed through the picture upload endpoint.
+pub const CSV_COLUMNS: [&str; 11] = ["name", "user_id", "vehicle_id", "created_at", "vehicle_type", "retired_at",
+                                     "brand", "model", "distance", "owner_since", "manufacturing_date"];
+const OPTIONAL_CSV_COLUMNS: [&str; 2] = ["vehicle_id", "retired_at"];
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordFormat {
+    Csv,
+    Ndjson
+}
+
+impl RecordFormat {
+    pub fn parse(name: &str) -> Option<RecordFormat> {
+        match name.trim().to_lowercase().as_str() {
+            "csv" => Some(RecordFormat::Csv),
+            "ndjson" => Some(RecordFormat::Ndjson),
+            _ => None
+        }
+    }
+
+    pub fn from_media_type(media_type: &str) -> Option<RecordFormat> {
+        match media_type {
+            "text/csv" => Some(RecordFormat::Csv),
+            "application/x-ndjson" | "application/ndjson" => Some(RecordFormat::Ndjson),
+            _ => None
+        }
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            RecordFormat::Csv => "text/csv",
+            RecordFormat::Ndjson => "application/x-ndjson"
+        }
+    }
+}
+
+/// Lines of an import file read as they arrive, so a large file is never held in memory.
+pub struct RecordLines<R> {
+    lines: Lines<R>,
+    line_number: usize
+}
+
+impl<R: AsyncBufRead + Unpin> RecordLines<R> {
+    pub fn new(reader: R) -> RecordLines<R> {
+        RecordLines {
+            lines: reader.lines(),
+            line_number: 0
+        }
+    }
+
+    /// Columns named by the first line of a CSV file, nothing for NDJSON.
+    pub async fn read_header(&mut self, format: RecordFormat) -> Result<Vec<String>, String> {
+        if format == RecordFormat::Ndjson {
+            return Ok(Vec::new());
+        }
+
+        let chunk = self.next_chunk(1).await.map_err(|e| e.to_string())?;
+
+        match chunk.first() {
+            Some((_, line)) => read_csv_header(line),
+            None => Err("Missing CSV header".to_string())
+        }
+    }
+
+    /// Up to `size` non-blank lines along with their line number, empty once the input is exhausted.
+    pub async fn next_chunk(&mut self, size: usize) -> io::Result<Vec<(usize, String)>> {
+        let mut chunk = Vec::new();
+
+        while chunk.len() < size {
+            match self.lines.next_line().await? {
+                Some(line) => {
+                    self.line_number += 1;
+                    if !line.trim().is_empty() {
+                        chunk.push((self.line_number, line));
+                    }
+                },
+                None => break
+            }
+        }
+
+        Ok(chunk)
+    }
+}
+
+pub fn read_csv_header(line: &str) -> Result<Vec<String>, String> {
+    let header: Vec<String> = split_csv_line(line.trim_start_matches('\u{feff}'))?
+        .iter()
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+
+    let missing: Vec<&str> = CSV_COLUMNS.iter()
+        .copied()
+        .filter(|column| !OPTIONAL_CSV_COLUMNS.contains(column) && !header.iter().any(|h| h == column))
+        .collect();
+
+    if missing.is_empty() {
+        Ok(header)
+    } else {
+        Err(format!("Missing columns {}", missing.join(", ")))
+    }
+}
+
+/// Reads and validates one vehicle, `header` being the columns of a CSV file.
+pub fn read(format: RecordFormat, header: &[String], line: &str) -> Result<VehicleDTO, String> {
+    let vehicle_dto = match format {
+        RecordFormat::Csv => from_csv(header, line)?,
+        RecordFormat::Ndjson => serde_json::from_str::<VehicleDTO>(line).map_err(|e| e.to_string())?
+    };
+
+    validate(&vehicle_dto)?;
+
+    Ok(vehicle_dto)
+}
+
+/// Writes one vehicle, without line terminator.
+pub fn write(format: RecordFormat, vehicle_dto: &VehicleDTO) -> String {
+    match format {
+        RecordFormat::Csv => to_csv(vehicle_dto),
+        RecordFormat::Ndjson => serde_json::to_string(vehicle_dto).expect("Failed to serialize VehicleDTO")
+    }
+}
+
+pub fn csv_header() -> String {
+    CSV_COLUMNS.join(",")
+}
+
+/// Columns of the fields a projected vehicle holds, in the order of `CSV_COLUMNS` followed by `picture`.
+pub fn projection_csv_header(vehicle_dto: &VehicleProjectionDTO) -> String {
+    projection_fields(vehicle_dto).iter().map(|(column, _)| *column).collect::<Vec<&str>>().join(",")
+}
+
+/// Writes the fields a projected vehicle holds as a CSV line, in the order of `projection_csv_header`.
+pub fn write_projection_csv(vehicle_dto: &VehicleProjectionDTO) -> String {
+    let values: Vec<String> = projection_fields(vehicle_dto).into_iter().map(|(_, value)| value).collect();
+
+    join_csv_line(&values)
+}
+
+pub fn validate(vehicle_dto: &VehicleDTO) -> Result<(), String> {
+    let required = [("name", &vehicle_dto.name), ("vehicle_type", &vehicle_dto.vehicle_type),
+                    ("brand", &vehicle_dto.brand), ("model", &vehicle_dto.model)];
+
+    if let Some((column, _)) = required.iter().find(|(_, value)| value.trim().is_empty()) {
+        return Err(format!("{} is empty", column));
+    }
+
+    if vehicle_dto.distance < 0 {
+        return Err("distance is negative".to_string());
+    }
+
+    if vehicle_dto.retired_at.map_or(false, |retired_at| retired_at < vehicle_dto.created_at) {
+        return Err("retired_at is before created_at".to_string());
+    }
+
+    Ok(())
+}
+
+fn from_csv(header: &[String], line: &str) -> Result<VehicleDTO, String> {
+    let fields = split_csv_line(line)?;
+
+    if fields.len() != header.len() {
+        return Err(format!("Expected {} fields, found {}", header.len(), fields.len()));
+    }
+
+    let field = |column: &str| field_value(header, &fields, column);
+
+    Ok(VehicleDTO {
+        name: field("name").to_string(),
+        user_id: parse_uuid("user_id", field("user_id"))?,
+        vehicle_id: optional(field("vehicle_id")).map(|value| parse_uuid("vehicle_id", value)).transpose()?,
+        created_at: parse_timestamp("created_at", field("created_at"))?,
+        vehicle_type: field("vehicle_type").to_string(),
+        retired_at: optional(field("retired_at")).map(|value| parse_timestamp("retired_at", value)).transpose()?,
+        brand: field("brand").to_string(),
+        model: field("model").to_string(),
+        distance: field("distance").parse::<i32>().map_err(|_| format!("Invalid distance {}", field("distance")))?,
+        owner_since: parse_date("owner_since", field("owner_since"))?,
+        manufacturing_date: parse_date("manufacturing_date", field("manufacturing_date"))?,
+        picture: None
+    })
+}
+
+fn field_value<'a>(header: &[String], fields: &'a [String], column: &str) -> &'a str {
+    header.iter().position(|h| h == column).map_or("", |index| fields[index].trim())
+}
+
+fn to_csv(vehicle_dto: &VehicleDTO) -> String {
+    join_csv_line(&[
+        vehicle_dto.name.to_owned(),
+        vehicle_dto.user_id.to_string(),
+        vehicle_dto.vehicle_id.map(|id| id.to_string()).unwrap_or_default(),
+        vehicle_dto.created_at.to_rfc3339(),
+        vehicle_dto.vehicle_type.to_owned(),
+        vehicle_dto.retired_at.map(|d| d.to_rfc3339()).unwrap_or_default(),
+        vehicle_dto.brand.to_owned(),
+        vehicle_dto.model.to_owned(),
+        vehicle_dto.distance.to_string(),
+        vehicle_dto.owner_since.format(DATE_FORMAT).to_string(),
+        vehicle_dto.manufacturing_date.format(DATE_FORMAT).to_string()
+    ])
+}
+
+fn projection_fields(vehicle_dto: &VehicleProjectionDTO) -> Vec<(&'static str, String)> {
+    let fields: Vec<(&'static str, Option<String>)> = vec!(
+        ("name", vehicle_dto.name.clone()),
+        ("user_id", vehicle_dto.user_id.map(|id| id.to_string())),
+        ("vehicle_id", vehicle_dto.vehicle_id.map(|id| id.to_string())),
+        ("created_at", vehicle_dto.created_at.map(|d| d.to_rfc3339())),
+        ("vehicle_type", vehicle_dto.vehicle_type.clone()),
+        ("retired_at", vehicle_dto.retired_at.map(|retired_at| retired_at.map(|d| d.to_rfc3339()).unwrap_or_default())),
+        ("brand", vehicle_dto.brand.clone()),
+        ("model", vehicle_dto.model.clone()),
+        ("distance", vehicle_dto.distance.map(|distance| distance.to_string())),
+        ("owner_since", vehicle_dto.owner_since.map(|date| date.format(DATE_FORMAT).to_string())),
+        ("manufacturing_date", vehicle_dto.manufacturing_date.map(|date| date.format(DATE_FORMAT).to_string())),
+        ("picture", vehicle_dto.picture.clone().map(Option::unwrap_or_default))
+    );
+
+    fields.into_iter().filter_map(|(column, value)| value.map(|value| (column, value))).collect()
+}
+
+fn optional(value: &str) -> Option<&str> {
+    if value.is_empty() { None } else { Some(value) }
+}
+
+fn parse_uuid(column: &str, value: &str) -> Result<Uuid, String> {
+    Uuid::parse_str(value).map_err(|_| format!("Invalid {} {}", column, value))
+}
+
+fn parse_timestamp(column: &str, value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|_| format!("Invalid {} {}", column, value))
+}
+
+fn parse_date(column: &str, value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, DATE_FORMAT).map_err(|_| format!("Invalid {} {}", column, value))
+}
+
+/// Splits a CSV line on commas, double quotes enclosing fields holding commas or escaped double quotes.
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            },
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c)
+        }
+    }
+
+    if quoted {
+        return Err("Unterminated quoted field".to_string());
+    }
+    fields.push(field);
+
+    Ok(fields)
+}
+
+fn join_csv_line(fields: &[String]) -> String {
+    fields.iter()
+        .map(|field| if field.contains(&[',', '"', '\n', '\r'][..]) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_owned()
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn given_vehicle_when_write_csv_then_read_returns_same_vehicle() {
+        let header = read_csv_header(&csv_header()).unwrap();
+
+        let line = write(RecordFormat::Csv, &fixture::vehicle_dto());
+        let vehicle_dto = read(RecordFormat::Csv, &header, &line).unwrap();
+
+        assert_eq!(fixture::EXPECTED_CSV_LINE, line);
+        assert_eq!("the \"fast\" one, red", vehicle_dto.name);
+        assert_eq!(Some(Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap()), vehicle_dto.vehicle_id);
+        assert_eq!(Utc.timestamp(5, 0), vehicle_dto.created_at);
+        assert_eq!(None, vehicle_dto.retired_at);
+        assert_eq!(NaiveDate::from_ymd(2015, 12, 2), vehicle_dto.owner_since);
+    }
+
+    #[test]
+    fn given_vehicle_when_write_ndjson_then_read_returns_same_vehicle() {
+        let line = write(RecordFormat::Ndjson, &fixture::vehicle_dto());
+        let vehicle_dto = read(RecordFormat::Ndjson, &[], &line).unwrap();
+
+        assert!(!line.contains('\n'));
+        assert_eq!(fixture::vehicle_dto().name, vehicle_dto.name);
+    }
+
+    #[test]
+    fn given_reordered_columns_without_optional_ones_when_read_csv_then_reads_by_column_name() {
+        let header = read_csv_header("user_id,name,created_at,vehicle_type,brand,model,distance,owner_since,manufacturing_date").unwrap();
+
+        let vehicle_dto = read(RecordFormat::Csv, &header,
+                               "a906615e-2e6a-4edb-9377-5a6b8544791b,bike,1970-01-01T00:00:05Z,bike,brand,model,10,2015-12-02,2015-12-01").unwrap();
+
+        assert_eq!("bike", vehicle_dto.name);
+        assert_eq!(None, vehicle_dto.vehicle_id);
+    }
+
+    #[test]
+    fn given_missing_column_when_read_csv_header_then_returns_error() {
+        assert_eq!(Err("Missing columns brand, model".to_string()),
+                   read_csv_header("name,user_id,created_at,vehicle_type,distance,owner_since,manufacturing_date"));
+    }
+
+    #[test]
+    fn given_invalid_line_when_read_then_returns_reason() {
+        let header = read_csv_header(&csv_header()).unwrap();
+
+        assert_eq!(Some("Expected 11 fields, found 2".to_string()), read(RecordFormat::Csv, &header, "a,b").err());
+        assert_eq!(Some("Unterminated quoted field".to_string()), read(RecordFormat::Csv, &header, "\"a,b").err());
+        assert!(read(RecordFormat::Ndjson, &[], "{ \"name\": ").is_err());
+    }
+
+    #[test]
+    fn given_negative_distance_when_validate_then_returns_error() {
+        let vehicle_dto = VehicleDTO { distance: -1, ..fixture::vehicle_dto() };
+
+        assert_eq!(Err("distance is negative".to_string()), validate(&vehicle_dto));
+    }
+
+    #[test]
+    fn given_retired_before_created_when_validate_then_returns_error() {
+        let vehicle_dto = VehicleDTO { retired_at: Some(Utc.timestamp(1, 0)), ..fixture::vehicle_dto() };
+
+        assert_eq!(Err("retired_at is before created_at".to_string()), validate(&vehicle_dto));
+    }
+
+    #[test]
+    fn when_next_chunk_then_skips_blank_lines_and_numbers_lines() {
+        let mut lines = RecordLines::new("header\n\nfirst\r\nsecond\nthird".as_bytes());
+
+        assert_eq!(vec!((1, "header".to_string())), aw!(lines.next_chunk(1)).unwrap());
+        assert_eq!(vec!((3, "first".to_string()), (4, "second".to_string())), aw!(lines.next_chunk(2)).unwrap());
+        assert_eq!(vec!((5, "third".to_string())), aw!(lines.next_chunk(2)).unwrap());
+        assert!(aw!(lines.next_chunk(2)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn when_parse_format_then_ignores_case() {
+        assert_eq!(Some(RecordFormat::Csv), RecordFormat::parse("CSV"));
+        assert_eq!(Some(RecordFormat::Ndjson), RecordFormat::from_media_type("application/x-ndjson"));
+        assert_eq!(None, RecordFormat::parse("xml"));
+    }
+
+    #[test]
+    fn given_projected_vehicle_when_write_projection_csv_then_writes_its_fields_only() {
+        let vehicle_dto = VehicleProjectionDTO {
+            name: Some(fixture::vehicle_dto().name),
+            retired_at: Some(None),
+            distance: Some(500),
+            ..VehicleProjectionDTO::default()
+        };
+
+        assert_eq!("name,retired_at,distance", projection_csv_header(&vehicle_dto));
+        assert_eq!("\"the \"\"fast\"\" one, red\",,500", write_projection_csv(&vehicle_dto));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const EXPECTED_CSV_LINE: &str = "\"the \"\"fast\"\" one, red\",a906615e-2e6a-4edb-9377-5a6b8544791b,88573010-cf4c-490e-9d29-f8517dc60b90,\
+            1970-01-01T00:00:05+00:00,bike,,the brand,the model,500,2015-12-02,2015-11-20";
+
+        pub fn vehicle_dto() -> VehicleDTO {
+            VehicleDTO {
+                name: "the \"fast\" one, red".to_string(),
+                user_id: Uuid::parse_str(USER_ID_STR).unwrap(),
+                vehicle_id: Some(Uuid::parse_str(VEHICLE_ID_STR).unwrap()),
+                created_at: Utc.timestamp(5, 0),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: 500,
+                owner_since: NaiveDate::from_ymd(2015, 12, 2),
+                manufacturing_date: NaiveDate::from_ymd(2015, 11, 20),
+                picture: None
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use std::sync::Arc;
+use scylla::IntoTypedRows;
+
+use rocket::serde::uuid::Uuid;
+
+use crate::dao::session_manager::{SessionManager, Statement};
+use crate::domain::activity::Activity;
+use crate::repository::cql;
+
+use chrono::{Utc, TimeZone};
+
+#[async_trait]
+pub trait ActivityRepository {
+    async fn claim_file_hash(&self, user_id: Uuid, file_hash: &str, activity_id: Uuid) -> Option<bool>;
+    async fn release_file_hash(&self, user_id: Uuid, file_hash: &str);
+    async fn save_activity(&self, activity: Activity) -> Option<Activity>;
+    async fn delete_activity(&self, activity: &Activity);
+    /// Meters of the activities imported for a vehicle, `Some(None)` before the first one and `None` when they
+    /// could not be read.
+    async fn get_distance(&self, user_id: Uuid, vehicle_id: Uuid) -> Option<Option<i64>>;
+    /// Compare-and-set of the meters of a vehicle: `Some(false)` means another import changed them since
+    /// `previous` was read.
+    async fn set_distance(&self, user_id: Uuid, vehicle_id: Uuid, previous: Option<i64>, distance: i64) -> Option<bool>;
+}
+
+pub struct ActivityRepositoryImpl {
+    queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+}
+
+impl ActivityRepositoryImpl {
+    pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> ActivityRepositoryImpl {
+        ActivityRepositoryImpl {
+            queriable
+        }
+    }
+}
+
+#[async_trait]
+impl ActivityRepository for ActivityRepositoryImpl {
+    /// Lightweight transaction on the hash table: `Some(false)` means the same file was already imported by the user.
+    async fn claim_file_hash(&self, user_id: Uuid, file_hash: &str, activity_id: Uuid) -> Option<bool> {
+        let query = format!("INSERT INTO vehicles.activity_by_hash (user_id, file_hash, activity_id) \
+            VALUES ({}, '{}', {}) IF NOT EXISTS", user_id, file_hash, activity_id);
+
+        let outcome = self.queriable.execute_statement(Statement::non_idempotent(&query).for_operation("claim_file_hash")).await;
+
+        match outcome.result {
+            Ok(query_result) => Some(cql::applied(&query_result)),
+            Err(e) => {
+                println!("Failed to claim file hash {:?} after {} retries with error {:?}", query, outcome.retries, e);
+                None
+            }
+        }
+    }
+
+    async fn release_file_hash(&self, user_id: Uuid, file_hash: &str) {
+        let query = format!("DELETE FROM vehicles.activity_by_hash WHERE user_id = {} AND file_hash = '{}'", user_id, file_hash);
+
+        if let Err(e) = self.queriable.execute_query("release_file_hash", &query).await {
+            println!("Failed to release file hash {:?} with error {:?}", query, e);
+        }
+    }
+
+    async fn save_activity(&self, activity: Activity) -> Option<Activity> {
+        let query = format!("INSERT INTO vehicles.activity (user_id,     \
+                                            vehicle_id,     \
+                                            activity_id,    \
+                                            file_hash,      \
+                                            file_format,    \
+                                            started_at,     \
+                                            duration,       \
+                                            distance,       \
+                                            elevation_gain, \
+                                            created_at) VALUES ({}, {}, {}, '{}', '{}', {}, {}, {}, {}, '{}')",
+                            activity.user_id, activity.vehicle_id, activity.activity_id, activity.file_hash, activity.file_format,
+                            activity.started_at.map(|d| format!("'{}'", Utc.timestamp(d.num_seconds(), 0))).unwrap_or_else(|| "null".to_string()),
+                            activity.duration, activity.distance, activity.elevation_gain, Utc.timestamp(activity.created_at.num_seconds(), 0)
+        );
+
+        let result = self.queriable.execute_query("save_activity", &query).await;
+
+        match result {
+            Ok(_) => Some(activity),
+            Err(e) => {
+                println!("Failed to insert Activity {:?} with error {:?}", query, e);
+                None
+            }
+        }
+    }
+
+    async fn delete_activity(&self, activity: &Activity) {
+        let query = format!("DELETE FROM vehicles.activity WHERE user_id = {} AND vehicle_id = {} AND activity_id = {}",
+                            activity.user_id, activity.vehicle_id, activity.activity_id);
+
+        if let Err(e) = self.queriable.execute_query("delete_activity", &query).await {
+            println!("Failed to delete Activity {:?} with error {:?}", query, e);
+        }
+    }
+
+    async fn get_distance(&self, user_id: Uuid, vehicle_id: Uuid) -> Option<Option<i64>> {
+        let query = format!("SELECT distance_m FROM vehicles.activity_distance WHERE user_id = {} AND vehicle_id = {}", user_id, vehicle_id);
+
+        let result = match self.queriable.execute_query("get_activity_distance", &query).await {
+            Ok(result) => result,
+            Err(e) => {
+                println!("Failed to get activity distance of Vehicle {} with error {:?}", vehicle_id, e);
+                return None;
+            }
+        };
+
+        match result.rows.unwrap_or_default().into_typed::<(Option<i64>,)>().next() {
+            Some(Ok((distance,))) => Some(distance),
+            Some(Err(e)) => {
+                println!("Failed to extract activity distance of Vehicle {} from Row with error {:?}", vehicle_id, e);
+                None
+            },
+            None => Some(None)
+        }
+    }
+
+    /// Lightweight transaction on the distance row, a missing row matching a `null` distance.
+    async fn set_distance(&self, user_id: Uuid, vehicle_id: Uuid, previous: Option<i64>, distance: i64) -> Option<bool> {
+        let query = format!("UPDATE vehicles.activity_distance SET distance_m = {} WHERE user_id = {} AND vehicle_id = {} IF distance_m = {}",
+                            distance, user_id, vehicle_id, cql::optional(previous));
+
+        let outcome = self.queriable.execute_statement(Statement::non_idempotent(&query).for_operation("set_activity_distance")).await;
+
+        match outcome.result {
+            Ok(query_result) => Some(cql::applied(&query_result)),
+            Err(e) => {
+                println!("Failed to set activity distance {:?} after {} retries with error {:?}", query, outcome.retries, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use scylla::QueryResult;
+    use scylla::transport::errors::QueryError;
+    use scylla::frame::response::result::{CqlValue, Row};
+    use chrono::Duration;
+
+    use crate::dao::session_manager::QueryOutcome;
+    use crate::repository::vehicle_repository::tests::MockSessionManagerImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn given_new_hash_when_claim_file_hash_then_returns_true() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_CLAIM_QUERY && !statement.idempotent)
+            .times(1)
+            .returning(move |_| QueryOutcome { result: fixture::create_applied_result(true), retries: 0 });
+
+        let activity_repository = ActivityRepositoryImpl::new(Arc::new(session_manager));
+
+        let claimed = aw!(activity_repository.claim_file_hash(fixture::user_id(), fixture::FILE_HASH, fixture::activity_id()));
+
+        assert_eq!(Some(true), claimed);
+    }
+
+    #[test]
+    fn given_existing_hash_when_claim_file_hash_then_returns_false() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_CLAIM_QUERY && !statement.idempotent)
+            .times(1)
+            .returning(move |_| QueryOutcome { result: fixture::create_applied_result(false), retries: 0 });
+
+        let activity_repository = ActivityRepositoryImpl::new(Arc::new(session_manager));
+
+        let claimed = aw!(activity_repository.claim_file_hash(fixture::user_id(), fixture::FILE_HASH, fixture::activity_id()));
+
+        assert_eq!(Some(false), claimed);
+    }
+
+    #[test]
+    fn given_error_when_claim_file_hash_then_returns_none() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .times(1)
+            .returning(move |_| QueryOutcome { result: Err(QueryError::InvalidMessage("error".to_owned())), retries: 0 });
+
+        let activity_repository = ActivityRepositoryImpl::new(Arc::new(session_manager));
+
+        let claimed = aw!(activity_repository.claim_file_hash(fixture::user_id(), fixture::FILE_HASH, fixture::activity_id()));
+
+        assert!(claimed.is_none());
+    }
+
+    #[test]
+    fn when_release_file_hash_then_deletes_hash() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|_, query: &str| query == fixture::EXPECTED_RELEASE_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        let activity_repository = ActivityRepositoryImpl::new(Arc::new(session_manager));
+
+        aw!(activity_repository.release_file_hash(fixture::user_id(), fixture::FILE_HASH));
+    }
+
+    #[test]
+    fn when_save_activity_then_returns_activity() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|_, query: &str| query == fixture::EXPECTED_SAVE_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        let activity_repository = ActivityRepositoryImpl::new(Arc::new(session_manager));
+
+        let activity = aw!(activity_repository.save_activity(fixture::activity())).unwrap();
+
+        assert_eq!(fixture::activity_id(), activity.activity_id);
+    }
+
+    #[test]
+    fn given_error_when_save_activity_then_returns_none() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .times(1)
+            .returning(move |_, _| Err(QueryError::InvalidMessage("error".to_owned())));
+
+        let activity_repository = ActivityRepositoryImpl::new(Arc::new(session_manager));
+
+        assert!(aw!(activity_repository.save_activity(fixture::activity())).is_none());
+    }
+
+    #[test]
+    fn given_read_error_when_get_distance_then_returns_none() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, _| operation == "get_activity_distance")
+            .times(1)
+            .returning(move |_, _| Err(QueryError::TimeoutError));
+
+        let activity_repository = ActivityRepositoryImpl::new(Arc::new(session_manager));
+
+        let vehicle_id = Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap();
+        assert_eq!(None, aw!(activity_repository.get_distance(fixture::user_id(), vehicle_id)));
+    }
+
+    #[test]
+    fn given_first_activity_when_set_distance_then_compares_with_missing_distance() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_SET_DISTANCE_QUERY && !statement.idempotent)
+            .times(1)
+            .returning(move |_| QueryOutcome { result: fixture::create_applied_result(true), retries: 0 });
+
+        let activity_repository = ActivityRepositoryImpl::new(Arc::new(session_manager));
+
+        let vehicle_id = Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap();
+        assert_eq!(Some(true), aw!(activity_repository.set_distance(fixture::user_id(), vehicle_id, None, 1501)));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const ACTIVITY_ID_STR: &str = "3b1f7d5e-7a51-4c39-9d0b-1a4f6f3b9e21";
+        pub const FILE_HASH: &str = "abc123";
+        pub const EXPECTED_CLAIM_QUERY: &str = "INSERT INTO vehicles.activity_by_hash (user_id, file_hash, activity_id) \
+            VALUES (a906615e-2e6a-4edb-9377-5a6b8544791b, 'abc123', 3b1f7d5e-7a51-4c39-9d0b-1a4f6f3b9e21) IF NOT EXISTS";
+        pub const EXPECTED_RELEASE_QUERY: &str = "DELETE FROM vehicles.activity_by_hash \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b AND file_hash = 'abc123'";
+        pub const EXPECTED_SET_DISTANCE_QUERY: &str = "UPDATE vehicles.activity_distance SET distance_m = 1501 \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b AND vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90 IF distance_m = null";
+        pub const EXPECTED_SAVE_QUERY: &str = "INSERT INTO vehicles.activity (user_id,     \
+                                            vehicle_id,     \
+                                            activity_id,    \
+                                            file_hash,      \
+                                            file_format,    \
+                                            started_at,     \
+                                            duration,       \
+                                            distance,       \
+                                            elevation_gain, \
+                                            created_at) VALUES (a906615e-2e6a-4edb-9377-5a6b8544791b, 88573010-cf4c-490e-9d29-f8517dc60b90, \
+                                            3b1f7d5e-7a51-4c39-9d0b-1a4f6f3b9e21, 'abc123', 'gpx', '1970-01-01 00:00:05 UTC', 60, 1500.5, 12, '1970-01-01 00:00:10 UTC')";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn activity_id() -> Uuid {
+            Uuid::parse_str(ACTIVITY_ID_STR).unwrap()
+        }
+
+        pub fn activity() -> Activity {
+            Activity {
+                user_id: user_id(),
+                vehicle_id: Uuid::parse_str(VEHICLE_ID_STR).unwrap(),
+                activity_id: activity_id(),
+                file_hash: FILE_HASH.to_string(),
+                file_format: "gpx".to_string(),
+                started_at: Some(Duration::seconds(5)),
+                duration: 60,
+                distance: 1500.5,
+                elevation_gain: 12.0,
+                created_at: Duration::seconds(10)
+            }
+        }
+
+        pub fn create_applied_result(applied: bool) -> Result<QueryResult, QueryError> {
+            Ok(QueryResult {
+                rows: Some(vec!(Row { columns: vec!(Some(CqlValue::Boolean(applied))) })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            })
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::sync::Arc;
+use scylla::IntoTypedRows;
+
+use chrono::NaiveDate;
+use rocket::serde::uuid::Uuid;
+
+use crate::dao::session_manager::SessionManager;
+use crate::domain::audit::AuditEntry;
+use crate::repository::cql::CqlLiteral;
+use crate::repository::cql_repository::CqlRepository;
+use crate::repository::entity::Entity;
+
+/// Appends to the audit log and reads it back, entries never being updated nor deleted.
+#[async_trait]
+pub trait AuditRepository {
+    async fn insert_entry(&self, entry: &AuditEntry) -> Option<()>;
+    /// Entries of a shard of a day between `from_ms` and `to_ms`, newest first, following the entry keyed by
+    /// `before` when given, `None` when they could not be read.
+    async fn get_entries(&self, day: NaiveDate, shard: i32, from_ms: i64, to_ms: i64, before: Option<(i64, Uuid)>, limit: usize) -> Option<Vec<AuditEntry>>;
+}
+
+pub struct AuditRepositoryImpl {
+    queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+    entries: CqlRepository<AuditEntry>,
+}
+
+impl AuditRepositoryImpl {
+    pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> AuditRepositoryImpl {
+        AuditRepositoryImpl {
+            queriable: queriable.clone(),
+            entries: CqlRepository::new(queriable)
+        }
+    }
+}
+
+#[async_trait]
+impl AuditRepository for AuditRepositoryImpl {
+    async fn insert_entry(&self, entry: &AuditEntry) -> Option<()> {
+        match self.entries.insert(entry).await {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("Failed to insert AuditEntry {:?} with error {:?}", entry, e);
+                None
+            }
+        }
+    }
+
+    async fn get_entries(&self, day: NaiveDate, shard: i32, from_ms: i64, to_ms: i64, before: Option<(i64, Uuid)>, limit: usize) -> Option<Vec<AuditEntry>> {
+        // Clustering columns restricted by a tuple cannot also be restricted column by column, so both bounds are tuples.
+        let upper_bound = match before {
+            Some((occurred_at_ms, audit_id)) => format!("(occurred_at_ms, audit_id) < ({}, {})", occurred_at_ms, audit_id),
+            None => format!("(occurred_at_ms) <= ({})", to_ms)
+        };
+        let query = format!("SELECT {} FROM {} WHERE day = {} and shard = {} and (occurred_at_ms) >= ({}) and {} LIMIT {}",
+                            AuditEntry::COLUMNS.join(", "), AuditEntry::TABLE, day.literal(), shard, from_ms, upper_bound, limit);
+
+        match self.queriable.execute_query("list_audit_log", &query).await {
+            Ok(result) => Some(result.rows.unwrap_or_default()
+                .into_typed::<AuditEntry>()
+                .map(|row| row.expect("Failed to extract AuditEntry from Row"))
+                .collect()),
+            Err(e) => {
+                println!("Failed to list AuditEntries of {} shard {} with error {:?}", day, shard, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use scylla::QueryResult;
+    use scylla::frame::response::result::{CqlValue, Row};
+
+    use crate::repository::vehicle_repository::tests::MockSessionManagerImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn given_entry_before_when_get_entries_then_reads_older_entries_of_day_in_range() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_audit_log" && query == fixture::EXPECTED_BEFORE_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result());
+
+        let audit_repository = AuditRepositoryImpl::new(Arc::new(session_manager));
+
+        let entries = aw!(audit_repository.get_entries(fixture::day(), 3, 1000, 9000, Some((5000, fixture::audit_id())), 100)).unwrap();
+
+        assert_eq!(1, entries.len());
+        assert_eq!(("jane", Some("10.0.0.1".to_string()), 201), (entries[0].actor.as_str(), entries[0].ip.clone(), entries[0].status));
+    }
+
+    #[test]
+    fn when_insert_entry_then_inserts_every_column() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "insert_audit_log" && query == fixture::EXPECTED_INSERT_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result());
+
+        let audit_repository = AuditRepositoryImpl::new(Arc::new(session_manager));
+
+        assert!(aw!(audit_repository.insert_entry(&fixture::entry())).is_some());
+    }
+
+    mod fixture {
+        use super::*;
+        use scylla::transport::errors::QueryError;
+
+        /// 2021-05-01, dates being read as days since the epoch shifted by 2^31.
+        pub const DAY: u32 = (1 << 31) + 18748;
+        pub const AUDIT_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+
+        pub const EXPECTED_BEFORE_QUERY: &str = "SELECT day, shard, occurred_at_ms, audit_id, request_id, actor, ip, method, route, targets, status, outcome \
+            FROM vehicles.audit_log \
+            WHERE day = '2021-05-01' and shard = 3 and (occurred_at_ms) >= (1000) and (occurred_at_ms, audit_id) < (5000, 88573010-cf4c-490e-9d29-f8517dc60b90) LIMIT 100";
+        pub const EXPECTED_INSERT_QUERY: &str = "INSERT INTO vehicles.audit_log (day, shard, occurred_at_ms, audit_id, request_id, actor, ip, method, route, targets, status, outcome) \
+            VALUES ('2021-05-01', 3, 5000, 88573010-cf4c-490e-9d29-f8517dc60b90, 'the request', 'jane', '10.0.0.1', 'POST', '/api/v1/vehicle', \
+            '{\"vehicle_id\":[\"88573010-cf4c-490e-9d29-f8517dc60b90\"]}', 201, 'succeeded')";
+
+        pub fn day() -> NaiveDate {
+            NaiveDate::from_ymd(2021, 5, 1)
+        }
+
+        pub fn audit_id() -> Uuid {
+            Uuid::parse_str(AUDIT_ID_STR).unwrap()
+        }
+
+        pub fn entry() -> AuditEntry {
+            AuditEntry {
+                day: day(),
+                shard: 3,
+                occurred_at_ms: 5000,
+                audit_id: audit_id(),
+                request_id: "the request".to_string(),
+                actor: "jane".to_string(),
+                ip: Some("10.0.0.1".to_string()),
+                method: "POST".to_string(),
+                route: "/api/v1/vehicle".to_string(),
+                targets: format!("{{\"vehicle_id\":[\"{}\"]}}", AUDIT_ID_STR),
+                status: 201,
+                outcome: "succeeded".to_string()
+            }
+        }
+
+        pub fn create_query_result() -> Result<QueryResult, QueryError> {
+            let cql_values = vec!(
+                Some(CqlValue::Date(DAY)),
+                Some(CqlValue::Int(3)),
+                Some(CqlValue::BigInt(5000)),
+                Some(CqlValue::Uuid(audit_id())),
+                Some(CqlValue::Text("the request".to_string())),
+                Some(CqlValue::Text("jane".to_string())),
+                Some(CqlValue::Text("10.0.0.1".to_string())),
+                Some(CqlValue::Text("POST".to_string())),
+                Some(CqlValue::Text("/api/v1/vehicle".to_string())),
+                Some(CqlValue::Text("{}".to_string())),
+                Some(CqlValue::Int(201)),
+                Some(CqlValue::Text("succeeded".to_string())));
+
+            Ok(QueryResult {
+                rows: Some(vec!(Row { columns: cql_values })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            })
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use rocket::serde::Deserialize;
+use rocket::serde::uuid::Uuid;
+use rocket::tokio::sync::OnceCell;
+
+use crate::domain::vehicle::{Vehicle, VehicleProjection};
+use crate::domain::vehicle_history::VehicleChange;
+use crate::repository::vehicle_repository::VehicleRepository;
+
+type VehicleKey = (Uuid, Uuid);
+
+/// Cache settings read from the `cache.vehicle` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct VehicleCacheSettings {
+    pub enabled             : bool,
+    pub capacity            : usize,
+    pub ttl_ms              : u64
+}
+
+impl Default for VehicleCacheSettings {
+    fn default() -> Self {
+        VehicleCacheSettings {
+            enabled: true,
+            capacity: 10_000,
+            ttl_ms: 30_000
+        }
+    }
+}
+
+/// Counters exposed on the metrics endpoint. `misses` counts the queries actually sent, `coalesced`
+/// the lookups that waited for a query already in flight for the same vehicle.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheMetrics {
+    pub hits                : u64,
+    pub misses              : u64,
+    pub coalesced           : u64,
+    pub size                : usize
+}
+
+struct Entry {
+    vehicle: Vehicle,
+    expires_at: Instant
+}
+
+/// Bounded LRU cache of vehicles whose entries expire after `ttl_ms`.
+pub struct VehicleCache {
+    ttl: Duration,
+    entries: Mutex<LruCache<VehicleKey, Entry>>,
+    in_flight: Mutex<HashMap<VehicleKey, Arc<OnceCell<Option<Vehicle>>>>>,
+    // Bumped on every invalidation, so a load started before it does not cache what it read.
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    coalesced: AtomicU64
+}
+
+impl VehicleCache {
+    pub fn new(settings: &VehicleCacheSettings) -> VehicleCache {
+        VehicleCache {
+            ttl: Duration::from_millis(settings.ttl_ms),
+            entries: Mutex::new(LruCache::new(settings.capacity.max(1))),
+            in_flight: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0)
+        }
+    }
+
+    pub fn metrics(&self) -> CacheMetrics {
+        CacheMetrics {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            size: self.entries.lock().unwrap().len()
+        }
+    }
+
+    pub fn invalidate(&self, key: &VehicleKey) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.entries.lock().unwrap().pop(key);
+        self.in_flight.lock().unwrap().remove(key);
+    }
+
+    fn lookup(&self, key: &VehicleKey) -> Option<Vehicle> {
+        let mut entries = self.entries.lock().unwrap();
+
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.vehicle.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            },
+            None => None
+        }
+    }
+
+    /// The cached vehicle, if any, without loading it when missing.
+    pub fn peek(&self, key: &VehicleKey) -> Option<Vehicle> {
+        let vehicle = self.lookup(key);
+        if vehicle.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+
+        vehicle
+    }
+
+    fn store(&self, key: VehicleKey, vehicle: Vehicle, generation: u64) {
+        let mut entries = self.entries.lock().unwrap();
+
+        if self.generation.load(Ordering::SeqCst) == generation {
+            entries.put(key, Entry { vehicle, expires_at: Instant::now() + self.ttl });
+        }
+    }
+
+    /// Returns the cached vehicle or loads it, concurrent lookups of the same missing vehicle sharing one load.
+    pub async fn get_or_load<F, Fut>(&self, key: VehicleKey, load: F) -> Option<Vehicle>
+        where F: FnOnce() -> Fut, Fut: Future<Output = Option<Vehicle>> {
+        if let Some(vehicle) = self.lookup(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(vehicle);
+        }
+
+        let cell = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&key) {
+                Some(cell) => {
+                    self.coalesced.fetch_add(1, Ordering::Relaxed);
+                    cell.clone()
+                },
+                None => {
+                    let cell = Arc::new(OnceCell::new());
+                    in_flight.insert(key, cell.clone());
+                    cell
+                }
+            }
+        };
+
+        let vehicle = cell.get_or_init(|| async {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            let generation = self.generation.load(Ordering::SeqCst);
+            let vehicle = load().await;
+            if let Some(vehicle) = &vehicle {
+                self.store(key, vehicle.clone(), generation);
+            }
+            vehicle
+        }).await.clone();
+
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.get(&key).map_or(false, |current| Arc::ptr_eq(current, &cell)) {
+            in_flight.remove(&key);
+        }
+
+        vehicle
+    }
+}
+
+/// Read-through cache in front of another `VehicleRepository`, invalidated by the writes going through it.
+pub struct CachedVehicleRepository {
+    vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+    cache: Arc<VehicleCache>
+}
+
+impl CachedVehicleRepository {
+    pub fn new(vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>, cache: Arc<VehicleCache>) -> CachedVehicleRepository {
+        CachedVehicleRepository {
+            vehicle_repository,
+            cache
+        }
+    }
+}
+
+#[async_trait]
+impl VehicleRepository for CachedVehicleRepository {
+    async fn get_vehicle(&self, user_id: Uuid, vehicle_id: Uuid) -> Option<Vehicle> {
+        self.cache.get_or_load((user_id, vehicle_id), move || self.vehicle_repository.get_vehicle(user_id, vehicle_id)).await
+    }
+
+    /// Projects the cached vehicle when there is one rather than reading the columns again.
+    async fn get_vehicle_projection(&self, user_id: Uuid, vehicle_id: Uuid, columns: Vec<&'static str>) -> Option<VehicleProjection> {
+        match self.cache.peek(&(user_id, vehicle_id)) {
+            Some(vehicle) => Some(VehicleProjection::of(vehicle, &columns)),
+            None => self.vehicle_repository.get_vehicle_projection(user_id, vehicle_id, columns).await
+        }
+    }
+
+    async fn save_vehicle(&self, vehicle: Vehicle, change: VehicleChange) -> Option<Vehicle> {
+        let key = (vehicle.user_id, vehicle.vehicle_id);
+
+        let saved = self.vehicle_repository.save_vehicle(vehicle, change).await;
+        self.cache.invalidate(&key);
+
+        saved
+    }
+
+    /// Evicts the vehicle even when the compare-and-set did not apply, so that the retry reads its current distance.
+    async fn update_distance(&self, previous: &Vehicle, vehicle: Vehicle, change: VehicleChange) -> Option<bool> {
+        let key = (vehicle.user_id, vehicle.vehicle_id);
+
+        let updated = self.vehicle_repository.update_distance(previous, vehicle, change).await;
+        self.cache.invalidate(&key);
+
+        updated
+    }
+
+    async fn save_vehicles(&self, vehicles: Vec<(Vehicle, VehicleChange)>) -> Option<Vec<Vehicle>> {
+        let keys: Vec<VehicleKey> = vehicles.iter().map(|(vehicle, _)| (vehicle.user_id, vehicle.vehicle_id)).collect();
+
+        let saved = self.vehicle_repository.save_vehicles(vehicles).await;
+        for key in &keys {
+            self.cache.invalidate(key);
+        }
+
+        saved
+    }
+
+    async fn get_vehicles_page(&self, user_id: Option<Uuid>, after: Option<(Uuid, Uuid)>, limit: usize) -> Option<Vec<Vehicle>> {
+        self.vehicle_repository.get_vehicles_page(user_id, after, limit).await
+    }
+
+    async fn get_vehicles_projection_page(&self, user_id: Uuid, after: Option<(Uuid, Uuid)>, limit: usize, columns: Vec<&'static str>) -> Option<Vec<VehicleProjection>> {
+        self.vehicle_repository.get_vehicles_projection_page(user_id, after, limit, columns).await
+    }
+
+    async fn find_vehicle_keys(&self, attribute: &str, value: &str, after: Option<(Uuid, Uuid)>, limit: usize) -> Option<Vec<(Uuid, Uuid)>> {
+        self.vehicle_repository.find_vehicle_keys(attribute, value, after, limit).await
+    }
+
+    async fn remove_vehicle_key(&self, attribute: &str, value: &str, user_id: Uuid, vehicle_id: Uuid) -> Option<()> {
+        self.vehicle_repository.remove_vehicle_key(attribute, value, user_id, vehicle_id).await
+    }
+
+    async fn evict_vehicle(&self, user_id: Uuid, vehicle_id: Uuid) {
+        self.cache.invalidate(&(user_id, vehicle_id));
+        self.vehicle_repository.evict_vehicle(user_id, vehicle_id).await;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::{Duration as ChronoDuration, NaiveDate};
+
+    use crate::mapper::vehicle_history_mapper;
+
+    use crate::service::vehicle_service::tests::MockVehicleRepositoryImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn given_cached_vehicle_when_get_vehicle_then_queries_once() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle()));
+
+        let cache = Arc::new(VehicleCache::new(&VehicleCacheSettings::default()));
+        let cached_repository = CachedVehicleRepository::new(Arc::new(vehicle_repository), cache.clone());
+
+        assert!(aw!(cached_repository.get_vehicle(fixture::user_id(), fixture::vehicle_id())).is_some());
+        assert!(aw!(cached_repository.get_vehicle(fixture::user_id(), fixture::vehicle_id())).is_some());
+
+        assert_eq!(CacheMetrics { hits: 1, misses: 1, coalesced: 0, size: 1 }, cache.metrics());
+    }
+
+    #[test]
+    fn given_expired_entry_when_get_vehicle_then_queries_again() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(2)
+            .returning(move |_, _| Some(fixture::vehicle()));
+
+        let settings = VehicleCacheSettings { ttl_ms: 0, ..VehicleCacheSettings::default() };
+        let cached_repository = CachedVehicleRepository::new(Arc::new(vehicle_repository), Arc::new(VehicleCache::new(&settings)));
+
+        aw!(cached_repository.get_vehicle(fixture::user_id(), fixture::vehicle_id()));
+        aw!(cached_repository.get_vehicle(fixture::user_id(), fixture::vehicle_id()));
+    }
+
+    #[test]
+    fn given_cached_vehicle_when_get_vehicle_projection_then_projects_it_without_query() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle()));
+        vehicle_repository.expect_get_vehicle_projection()
+            .times(1)
+            .returning(move |_, _, _| Some(VehicleProjection::default()));
+
+        let cached_repository = CachedVehicleRepository::new(Arc::new(vehicle_repository), Arc::new(VehicleCache::new(&VehicleCacheSettings::default())));
+
+        aw!(cached_repository.get_vehicle_projection(fixture::user_id(), fixture::vehicle_id(), vec!("name")));
+        aw!(cached_repository.get_vehicle(fixture::user_id(), fixture::vehicle_id()));
+        let projection = aw!(cached_repository.get_vehicle_projection(fixture::user_id(), fixture::vehicle_id(), vec!("name"))).unwrap();
+
+        assert_eq!(VehicleProjection { name: Some(fixture::vehicle().name), ..VehicleProjection::default() }, projection);
+    }
+
+    #[test]
+    fn given_missing_vehicle_when_get_vehicle_then_does_not_cache_it() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(2)
+            .returning(move |_, _| None);
+
+        let cached_repository = CachedVehicleRepository::new(Arc::new(vehicle_repository), Arc::new(VehicleCache::new(&VehicleCacheSettings::default())));
+
+        assert!(aw!(cached_repository.get_vehicle(fixture::user_id(), fixture::vehicle_id())).is_none());
+        assert!(aw!(cached_repository.get_vehicle(fixture::user_id(), fixture::vehicle_id())).is_none());
+    }
+
+    #[test]
+    fn when_save_vehicle_then_invalidates_cached_vehicle() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(2)
+            .returning(move |_, _| Some(fixture::vehicle()));
+        vehicle_repository.expect_save_vehicle()
+            .times(1)
+            .returning(move |vehicle, _| Some(vehicle));
+
+        let cached_repository = CachedVehicleRepository::new(Arc::new(vehicle_repository), Arc::new(VehicleCache::new(&VehicleCacheSettings::default())));
+
+        aw!(cached_repository.get_vehicle(fixture::user_id(), fixture::vehicle_id()));
+        let change = vehicle_history_mapper::get_vehicle_change(Some(&fixture::vehicle()), &fixture::vehicle(), "the actor", None);
+        aw!(cached_repository.save_vehicle(fixture::vehicle(), change));
+        aw!(cached_repository.get_vehicle(fixture::user_id(), fixture::vehicle_id()));
+    }
+
+    #[test]
+    fn given_full_cache_when_get_vehicle_then_evicts_least_recently_used() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(3)
+            .returning(move |_, vehicle_id| Some(Vehicle { vehicle_id, ..fixture::vehicle() }));
+
+        let settings = VehicleCacheSettings { capacity: 1, ..VehicleCacheSettings::default() };
+        let cache = Arc::new(VehicleCache::new(&settings));
+        let cached_repository = CachedVehicleRepository::new(Arc::new(vehicle_repository), cache.clone());
+
+        aw!(cached_repository.get_vehicle(fixture::user_id(), fixture::vehicle_id()));
+        aw!(cached_repository.get_vehicle(fixture::user_id(), Uuid::new_v4()));
+        aw!(cached_repository.get_vehicle(fixture::user_id(), fixture::vehicle_id()));
+
+        assert_eq!(1, cache.metrics().size);
+    }
+
+    #[test]
+    fn given_concurrent_misses_when_get_or_load_then_loads_once() {
+        let cache = VehicleCache::new(&VehicleCacheSettings::default());
+        let loads = AtomicU64::new(0);
+        let loads_ref = &loads;
+
+        let load = move || async move {
+            loads_ref.fetch_add(1, Ordering::SeqCst);
+            rocket::tokio::time::sleep(Duration::from_millis(20)).await;
+            Some(fixture::vehicle())
+        };
+
+        let (first, second) = aw!(async {
+            rocket::tokio::join!(
+                cache.get_or_load((fixture::user_id(), fixture::vehicle_id()), load),
+                cache.get_or_load((fixture::user_id(), fixture::vehicle_id()), load))
+        });
+
+        assert!(first.is_some() && second.is_some());
+        assert_eq!(1, loads.load(Ordering::SeqCst));
+        assert_eq!(1, cache.metrics().coalesced);
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str("a906615e-2e6a-4edb-9377-5a6b8544791b").unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str("88573010-cf4c-490e-9d29-f8517dc60b90").unwrap()
+        }
+
+        pub fn vehicle() -> Vehicle {
+            Vehicle {
+                name: "the vehicle name".to_string(),
+                user_id: user_id(),
+                vehicle_id: vehicle_id(),
+                created_at: ChronoDuration::seconds(5),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: 15,
+                owner_since: NaiveDate::from_ymd(2015, 12, 2),
+                manufacturing_date: NaiveDate::from_ymd(2015, 12, 1),
+                picture: None
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::sync::Arc;
+use scylla::IntoTypedRows;
+
+use rocket::serde::uuid::Uuid;
+
+use crate::dao::session_manager::SessionManager;
+use crate::domain::component::Component;
+use crate::repository::cql;
+
+use chrono::{Utc, TimeZone};
+
+const COMPONENT_COLUMNS: &str = "user_id, component_id, vehicle_id, component_type, brand, model, installed_at, removed_at, \
+    installed_distance, accumulated_distance, wear_limit";
+
+#[async_trait]
+pub trait ComponentRepository {
+    async fn get_component(&self, user_id: Uuid, component_id: Uuid) -> Option<Component>;
+    async fn get_components(&self, user_id: Uuid) -> Vec<Component>;
+    async fn save_component(&self, component: Component) -> Option<Component>;
+}
+
+pub struct ComponentRepositoryImpl {
+    queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+}
+
+impl ComponentRepositoryImpl {
+    pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> ComponentRepositoryImpl {
+        ComponentRepositoryImpl {
+            queriable
+        }
+    }
+
+    async fn select(&self, operation: &str, query: String) -> Vec<Component> {
+        let result = self.queriable.execute_query(operation, &query).await;
+
+        result
+            .expect(&format!("Failed to execute query {}", query))
+            .rows
+            .unwrap_or_default()
+            .into_typed::<Component>()
+            .map(|row| row.expect("Failed to extract Component from Row"))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl ComponentRepository for ComponentRepositoryImpl {
+    async fn get_component(&self, user_id: Uuid, component_id: Uuid) -> Option<Component> {
+        let query = format!("SELECT {} FROM vehicles.component WHERE user_id = {} and component_id = {}",
+                            COMPONENT_COLUMNS, user_id, component_id);
+
+        self.select("get_component", query).await.into_iter().next()
+    }
+
+    async fn get_components(&self, user_id: Uuid) -> Vec<Component> {
+        let query = format!("SELECT {} FROM vehicles.component WHERE user_id = {}", COMPONENT_COLUMNS, user_id);
+
+        self.select("get_components", query).await
+    }
+
+    async fn save_component(&self, component: Component) -> Option<Component> {
+        let query = format!("INSERT INTO vehicles.component ({}) VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {})",
+                            COMPONENT_COLUMNS, component.user_id, component.component_id, cql::optional(component.vehicle_id),
+                            cql::text(&component.component_type), cql::text(&component.brand), cql::text(&component.model),
+                            component.installed_at.map(|d| format!("'{}'", Utc.timestamp(d.num_seconds(), 0))).unwrap_or_else(|| "null".to_string()),
+                            component.removed_at.map(|d| format!("'{}'", Utc.timestamp(d.num_seconds(), 0))).unwrap_or_else(|| "null".to_string()),
+                            cql::optional(component.installed_distance), component.accumulated_distance, cql::optional(component.wear_limit)
+        );
+
+        let result = self.queriable.execute_query("save_component", &query).await;
+
+        match result {
+            Ok(_) => Some(component),
+            Err(e) => {
+                println!("Failed to insert Component {:?} with error {:?}", query, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use scylla::QueryResult;
+    use scylla::transport::errors::QueryError;
+    use scylla::frame::response::result::{CqlValue, Row};
+    use chrono::Duration;
+
+    use crate::repository::vehicle_repository::tests::MockSessionManagerImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn when_get_component_then_returns_component() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|_, query: &str| query == fixture::EXPECTED_GET_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result());
+
+        let component_repository = ComponentRepositoryImpl::new(Arc::new(session_manager));
+
+        let component = aw!(component_repository.get_component(fixture::user_id(), fixture::component_id())).unwrap();
+
+        assert_eq!(fixture::component_id(), component.component_id);
+        assert!(component.vehicle_id.is_none());
+        assert_eq!("chain", component.component_type);
+        assert_eq!(Some(Duration::seconds(5)), component.installed_at);
+        assert_eq!(Some(1000), component.installed_distance);
+        assert_eq!(250, component.accumulated_distance);
+        assert_eq!(Some(3000), component.wear_limit);
+    }
+
+    #[test]
+    fn given_no_matching_row_when_get_component_then_returns_none() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        let component_repository = ComponentRepositoryImpl::new(Arc::new(session_manager));
+
+        assert!(aw!(component_repository.get_component(fixture::user_id(), fixture::component_id())).is_none());
+    }
+
+    #[test]
+    fn when_save_component_then_returns_component() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|_, query: &str| query == fixture::EXPECTED_SAVE_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        let component_repository = ComponentRepositoryImpl::new(Arc::new(session_manager));
+
+        let component = aw!(component_repository.save_component(fixture::component())).unwrap();
+
+        assert_eq!(fixture::component_id(), component.component_id);
+    }
+
+    #[test]
+    fn given_error_when_save_component_then_returns_none() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .times(1)
+            .returning(move |_, _| Err(QueryError::InvalidMessage("error".to_owned())));
+
+        let component_repository = ComponentRepositoryImpl::new(Arc::new(session_manager));
+
+        assert!(aw!(component_repository.save_component(fixture::component())).is_none());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const COMPONENT_ID_STR: &str = "2c5e8f1a-9b3d-4e6f-8a1b-3c5d7e9f1a2b";
+        pub const EXPECTED_GET_QUERY: &str = "SELECT user_id, component_id, vehicle_id, component_type, brand, model, installed_at, removed_at, \
+            installed_distance, accumulated_distance, wear_limit \
+            FROM vehicles.component WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and component_id = 2c5e8f1a-9b3d-4e6f-8a1b-3c5d7e9f1a2b";
+        pub const EXPECTED_SAVE_QUERY: &str = "INSERT INTO vehicles.component (user_id, component_id, vehicle_id, component_type, brand, model, installed_at, removed_at, \
+            installed_distance, accumulated_distance, wear_limit) \
+            VALUES (a906615e-2e6a-4edb-9377-5a6b8544791b, 2c5e8f1a-9b3d-4e6f-8a1b-3c5d7e9f1a2b, null, 'chain', 'the brand', 'the model', \
+            '1970-01-01 00:00:05 UTC', null, 1000, 250, 3000)";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn component_id() -> Uuid {
+            Uuid::parse_str(COMPONENT_ID_STR).unwrap()
+        }
+
+        pub fn component() -> Component {
+            Component {
+                user_id: user_id(),
+                component_id: component_id(),
+                vehicle_id: None,
+                component_type: "chain".to_string(),
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                installed_at: Some(Duration::seconds(5)),
+                removed_at: None,
+                installed_distance: Some(1000),
+                accumulated_distance: 250,
+                wear_limit: Some(3000)
+            }
+        }
+
+        pub fn create_query_result() -> Result<QueryResult, QueryError> {
+            let cql_values = vec!(
+                Some(CqlValue::Uuid(user_id())),
+                Some(CqlValue::Uuid(component_id())),
+                None,
+                Some(CqlValue::Text("chain".to_string())),
+                Some(CqlValue::Text("the brand".to_string())),
+                Some(CqlValue::Text("the model".to_string())),
+                Some(CqlValue::Timestamp(Duration::seconds(5))),
+                None,
+                Some(CqlValue::Int(1000)),
+                Some(CqlValue::Int(250)),
+                Some(CqlValue::Int(3000)));
+
+            Ok(QueryResult {
+                rows: Some(vec!(Row { columns: cql_values })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            })
+        }
+    }
+}
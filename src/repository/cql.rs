@@ -0,0 +1,132 @@
+use std::fmt::Display;
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use rocket::serde::uuid::Uuid;
+use scylla::QueryResult;
+
+/// Quotes a value as a CQL string literal, doubling any embedded single quote.
+pub fn text(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+pub fn text_list(values: &[String]) -> String {
+    format!("[{}]", values.iter().map(|v| text(v)).collect::<Vec<String>>().join(", "))
+}
+
+pub fn optional<T: Display>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_else(|| "null".to_string())
+}
+
+pub fn optional_text(value: Option<&str>) -> String {
+    value.map(text).unwrap_or_else(|| "null".to_string())
+}
+
+/// Whether a lightweight transaction applied, read from the `[applied]` column it answers first.
+pub fn applied(result: &QueryResult) -> bool {
+    result.rows
+        .as_ref()
+        .and_then(|rows| rows.first())
+        .and_then(|row| row.columns.first())
+        .and_then(|column| column.as_ref())
+        .and_then(|value| value.as_boolean())
+        .unwrap_or(false)
+}
+
+/// Renders a value as a CQL literal, so entity statements can be built column by column.
+pub trait CqlLiteral {
+    fn literal(&self) -> String;
+}
+
+impl CqlLiteral for String {
+    fn literal(&self) -> String {
+        text(self)
+    }
+}
+
+impl CqlLiteral for Uuid {
+    fn literal(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl CqlLiteral for i32 {
+    fn literal(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl CqlLiteral for i64 {
+    fn literal(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl CqlLiteral for f64 {
+    fn literal(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl CqlLiteral for bool {
+    fn literal(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Timestamps are read by the driver as a `Duration` since the epoch.
+impl CqlLiteral for Duration {
+    fn literal(&self) -> String {
+        format!("'{}'", Utc.timestamp(self.num_seconds(), 0))
+    }
+}
+
+impl CqlLiteral for NaiveDate {
+    fn literal(&self) -> String {
+        format!("'{}'", self)
+    }
+}
+
+impl CqlLiteral for Vec<String> {
+    fn literal(&self) -> String {
+        text_list(self)
+    }
+}
+
+impl<T: CqlLiteral> CqlLiteral for Option<T> {
+    fn literal(&self) -> String {
+        self.as_ref().map(|v| v.literal()).unwrap_or_else(|| "null".to_string())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn given_quote_when_text_then_quote_is_escaped() {
+        assert_eq!("'rider''s bike'", text("rider's bike"));
+    }
+
+    #[test]
+    fn when_text_list_then_returns_cql_list_literal() {
+        assert_eq!("['chain', 'o''ring']", text_list(&["chain".to_string(), "o'ring".to_string()]));
+        assert_eq!("[]", text_list(&[]));
+    }
+
+    #[test]
+    fn given_none_when_optional_then_returns_null() {
+        assert_eq!("null", optional::<i32>(None));
+        assert_eq!("3000", optional(Some(3000)));
+        assert_eq!("null", optional_text(None));
+        assert_eq!("'x'", optional_text(Some("x")));
+    }
+
+    #[test]
+    fn when_literal_then_returns_cql_literal() {
+        assert_eq!("'o''ring'", "o'ring".to_string().literal());
+        assert_eq!("'1970-01-01 00:00:05 UTC'", Duration::seconds(5).literal());
+        assert_eq!("'0001-01-15'", NaiveDate::from_num_days_from_ce(15).literal());
+        assert_eq!("null", None::<Duration>.literal());
+        assert_eq!("500", Some(500_i32).literal());
+    }
+}
@@ -0,0 +1,139 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use scylla::IntoTypedRows;
+use scylla::QueryResult;
+use scylla::frame::response::cql_to_rust::FromRow;
+use scylla::frame::response::result::Row;
+use scylla::transport::errors::QueryError;
+
+use crate::dao::session_manager::{BatchStatement, SessionManager};
+use crate::repository::entity::{self, Entity};
+
+/// Typed get/insert/update/delete/list operations of an entity, named after its table,
+/// e.g. `get_vehicle` or `insert_vehicle`, for retries and consistency settings.
+pub struct CqlRepository<E> {
+    queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+    entity: PhantomData<fn() -> E>
+}
+
+impl<E: Entity + FromRow> CqlRepository<E> {
+    pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> CqlRepository<E> {
+        CqlRepository {
+            queriable,
+            entity: PhantomData
+        }
+    }
+
+    pub async fn get(&self, key: &E::PrimaryKey) -> Result<Option<E>, QueryError> {
+        let query = entity::get_statement::<E>(key);
+
+        let result = self.queriable.execute_query(&format!("get_{}", E::name()), &query).await?;
+
+        Ok(Self::typed_rows(result).into_iter().next())
+    }
+
+    /// Raw row holding only `columns`, in that order, for reads that do not need the whole entity.
+    pub async fn get_columns(&self, key: &E::PrimaryKey, columns: &[&str]) -> Result<Option<Row>, QueryError> {
+        let query = entity::get_columns_statement::<E>(key, columns);
+
+        let result = self.queriable.execute_query(&format!("get_{}", E::name()), &query).await?;
+
+        Ok(result.rows.unwrap_or_default().into_iter().next())
+    }
+
+    pub async fn list_by_partition(&self, key: &E::PartitionKey) -> Result<Vec<E>, QueryError> {
+        let query = entity::list_by_partition_statement::<E>(key);
+
+        let result = self.queriable.execute_query(&format!("list_{}", E::name()), &query).await?;
+
+        Ok(Self::typed_rows(result))
+    }
+
+    /// Page of a partition following `after`, or its first page without `after`.
+    pub async fn list_by_partition_page(&self, key: &E::PartitionKey, after: Option<&E::PrimaryKey>, limit: usize) -> Result<Vec<E>, QueryError> {
+        let query = match after {
+            None => entity::list_by_partition_page_statement::<E>(key, limit),
+            Some(after) => match entity::partition_rest_statement::<E>(after, limit) {
+                Some(query) => query,
+                None => return Ok(Vec::new())
+            }
+        };
+
+        let result = self.queriable.execute_query(&format!("list_{}", E::name()), &query).await?;
+
+        Ok(Self::typed_rows(result))
+    }
+
+    /// Like `list_by_partition_page`, answering raw rows holding only `columns`, in that order.
+    pub async fn list_by_partition_page_columns(&self, key: &E::PartitionKey, after: Option<&E::PrimaryKey>, limit: usize, columns: &[&str]) -> Result<Vec<Row>, QueryError> {
+        let query = match after {
+            None => entity::list_by_partition_page_columns_statement::<E>(key, columns, limit),
+            Some(after) => match entity::partition_rest_columns_statement::<E>(after, columns, limit) {
+                Some(query) => query,
+                None => return Ok(Vec::new())
+            }
+        };
+
+        let result = self.queriable.execute_query(&format!("list_{}", E::name()), &query).await?;
+
+        Ok(result.rows.unwrap_or_default())
+    }
+
+    /// Page of the whole table following `after`, walking partitions in token order so that going through
+    /// a large table never holds more than one page.
+    pub async fn scan_page(&self, after: Option<&E::PrimaryKey>, limit: usize) -> Result<Vec<E>, QueryError> {
+        let operation = format!("scan_{}", E::name());
+
+        let after = match after {
+            None => return Ok(Self::typed_rows(self.queriable.execute_query(&operation, &entity::scan_statement::<E>(limit)).await?)),
+            Some(after) => after
+        };
+
+        let mut rows = match entity::partition_rest_statement::<E>(after, limit) {
+            Some(query) => Self::typed_rows(self.queriable.execute_query(&operation, &query).await?),
+            None => Vec::new()
+        };
+
+        if rows.len() < limit {
+            let query = entity::next_partitions_statement::<E>(after, limit - rows.len());
+            rows.extend(Self::typed_rows(self.queriable.execute_query(&operation, &query).await?));
+        }
+
+        Ok(rows)
+    }
+
+    pub async fn insert(&self, entity: &E) -> Result<(), QueryError> {
+        let query = entity::insert_statement(entity);
+
+        self.queriable.execute_query(&format!("insert_{}", E::name()), &query).await.map(|_| ())
+    }
+
+    /// Inserts entities in a single unlogged batch, meant for entities sharing the same partition.
+    pub async fn insert_batch(&self, entities: &[E]) -> Result<(), QueryError> {
+        let statements = entities.iter().map(entity::insert_statement).collect();
+
+        let batch = BatchStatement::unlogged(statements).for_operation(&format!("insert_{}_batch", E::name()));
+
+        self.queriable.execute_batch(batch).await.result.map(|_| ())
+    }
+
+    pub async fn update(&self, entity: &E) -> Result<(), QueryError> {
+        let query = entity::update_statement(entity);
+
+        self.queriable.execute_query(&format!("update_{}", E::name()), &query).await.map(|_| ())
+    }
+
+    pub async fn delete(&self, key: &E::PrimaryKey) -> Result<(), QueryError> {
+        let query = entity::delete_statement::<E>(key);
+
+        self.queriable.execute_query(&format!("delete_{}", E::name()), &query).await.map(|_| ())
+    }
+
+    fn typed_rows(result: QueryResult) -> Vec<E> {
+        result.rows.unwrap_or_default()
+            .into_typed::<E>()
+            .map(|row| row.unwrap_or_else(|e| panic!("Failed to extract {} from Row: {:?}", E::name(), e)))
+            .collect()
+    }
+}
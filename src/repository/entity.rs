@@ -0,0 +1,314 @@
+use crate::repository::cql::CqlLiteral;
+
+/// A domain struct mapped onto a Cassandra table, declared with `cql_entity!`.
+pub trait Entity: Send + Sync + Sized {
+    /// Tuple of the partition key columns.
+    type PartitionKey: KeyValues + Send + Sync;
+    /// Tuple of the partition key columns followed by the clustering key columns.
+    type PrimaryKey: KeyValues + Send + Sync;
+
+    const TABLE: &'static str;
+    /// Every column, in the order of the struct fields and so of the rows read by `FromRow`.
+    const COLUMNS: &'static [&'static str];
+    const PARTITION_KEY: &'static [&'static str];
+    const CLUSTERING_KEY: &'static [&'static str];
+    /// Columns left untouched instead of being written as null when `None`.
+    const UNSET_WHEN_NONE: &'static [&'static str];
+
+    /// CQL literals of every column, in the order of `COLUMNS`.
+    fn values(&self) -> Vec<String>;
+
+    fn primary_key(&self) -> Self::PrimaryKey;
+
+    /// Table name without its keyspace, used to name the repository operations.
+    fn name() -> &'static str {
+        Self::TABLE.rsplit('.').next().unwrap_or(Self::TABLE)
+    }
+}
+
+/// CQL literals of the columns of a key tuple.
+pub trait KeyValues {
+    fn values(&self) -> Vec<String>;
+}
+
+macro_rules! key_values {
+    ($($name:ident . $index:tt),+) => {
+        impl<$($name: CqlLiteral),+> KeyValues for ($($name,)+) {
+            fn values(&self) -> Vec<String> {
+                vec!($(self.$index.literal()),+)
+            }
+        }
+    };
+}
+
+key_values!(A.0);
+key_values!(A.0, B.1);
+key_values!(A.0, B.1, C.2);
+key_values!(A.0, B.1, C.2, D.3);
+
+/// Declares a struct together with the table it is stored in and its keys, implementing `Entity`.
+///
+/// ```ignore
+/// cql_entity! {
+///     #[derive(FromRow, Debug, Clone)]
+///     pub struct Part {
+///         pub user_id : Uuid,
+///         pub part_id : Uuid,
+///         pub name    : String
+///     }
+///     table = "vehicles.part";
+///     partition_key = (user_id: Uuid);
+///     clustering_key = (part_id: Uuid);
+/// }
+/// ```
+#[macro_export]
+macro_rules! cql_entity {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+        table = $table:literal;
+        partition_key = ($($partition_key:ident : $partition_ty:ty),+);
+        clustering_key = ($($clustering_key:ident : $clustering_ty:ty),*);
+        $(unset_when_none = ($($unset:ident),*);)?
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field : $ty),*
+        }
+
+        impl $crate::repository::entity::Entity for $name {
+            type PartitionKey = ($($partition_ty,)+);
+            type PrimaryKey = ($($partition_ty,)+ $($clustering_ty,)*);
+
+            const TABLE: &'static str = $table;
+            const COLUMNS: &'static [&'static str] = &[$(stringify!($field)),*];
+            const PARTITION_KEY: &'static [&'static str] = &[$(stringify!($partition_key)),+];
+            const CLUSTERING_KEY: &'static [&'static str] = &[$(stringify!($clustering_key)),*];
+            const UNSET_WHEN_NONE: &'static [&'static str] = &[$($(stringify!($unset)),*)?];
+
+            fn values(&self) -> Vec<String> {
+                vec!($($crate::repository::cql::CqlLiteral::literal(&self.$field)),*)
+            }
+
+            fn primary_key(&self) -> Self::PrimaryKey {
+                ($(self.$partition_key.clone(),)+ $(self.$clustering_key.clone(),)*)
+            }
+        }
+    };
+}
+
+pub fn get_statement<E: Entity>(key: &E::PrimaryKey) -> String {
+    get_columns_statement::<E>(key, E::COLUMNS)
+}
+
+/// Like `get_statement`, selecting only `columns`, in that order.
+pub fn get_columns_statement<E: Entity>(key: &E::PrimaryKey, columns: &[&str]) -> String {
+    format!("SELECT {} FROM {} WHERE {}", columns.join(", "), E::TABLE, where_clause(primary_key_columns::<E>(), key.values()))
+}
+
+pub fn list_by_partition_statement<E: Entity>(key: &E::PartitionKey) -> String {
+    format!("SELECT {} FROM {} WHERE {}", E::COLUMNS.join(", "), E::TABLE, where_clause(E::PARTITION_KEY.iter().copied(), key.values()))
+}
+
+pub fn insert_statement<E: Entity>(entity: &E) -> String {
+    let (columns, values): (Vec<&str>, Vec<String>) = written_columns(entity).into_iter().unzip();
+
+    format!("INSERT INTO {} ({}) VALUES ({})", E::TABLE, columns.join(", "), values.join(", "))
+}
+
+pub fn update_statement<E: Entity>(entity: &E) -> String {
+    let assignments: Vec<String> = written_columns(entity).into_iter()
+        .filter(|(column, _)| !primary_key_columns::<E>().any(|key| key == *column))
+        .map(|(column, value)| format!("{} = {}", column, value))
+        .collect();
+
+    format!("UPDATE {} SET {} WHERE {}", E::TABLE, assignments.join(", "), where_clause(primary_key_columns::<E>(), entity.primary_key().values()))
+}
+
+pub fn delete_statement<E: Entity>(key: &E::PrimaryKey) -> String {
+    format!("DELETE FROM {} WHERE {}", E::TABLE, where_clause(primary_key_columns::<E>(), key.values()))
+}
+
+/// First page of a partition, to be followed by `partition_rest_statement`.
+pub fn list_by_partition_page_statement<E: Entity>(key: &E::PartitionKey, limit: usize) -> String {
+    list_by_partition_page_columns_statement::<E>(key, E::COLUMNS, limit)
+}
+
+pub fn list_by_partition_page_columns_statement<E: Entity>(key: &E::PartitionKey, columns: &[&str], limit: usize) -> String {
+    format!("SELECT {} FROM {} WHERE {} LIMIT {}", columns.join(", "), E::TABLE, where_clause(E::PARTITION_KEY.iter().copied(), key.values()), limit)
+}
+
+/// First page of the whole table, in token order, to be followed by `partition_rest_statement` then
+/// `next_partitions_statement`.
+pub fn scan_statement<E: Entity>(limit: usize) -> String {
+    format!("SELECT {} FROM {} LIMIT {}", E::COLUMNS.join(", "), E::TABLE, limit)
+}
+
+/// Rows of the partition of `after` clustered after it, `None` for tables holding a single row per partition.
+pub fn partition_rest_statement<E: Entity>(after: &E::PrimaryKey, limit: usize) -> Option<String> {
+    partition_rest_columns_statement::<E>(after, E::COLUMNS, limit)
+}
+
+pub fn partition_rest_columns_statement<E: Entity>(after: &E::PrimaryKey, columns: &[&str], limit: usize) -> Option<String> {
+    if E::CLUSTERING_KEY.is_empty() {
+        return None;
+    }
+
+    let values = after.values();
+    let (partition_values, clustering_values) = values.split_at(E::PARTITION_KEY.len());
+
+    Some(format!("SELECT {} FROM {} WHERE {} and ({}) > ({}) LIMIT {}", columns.join(", "), E::TABLE,
+                 where_clause(E::PARTITION_KEY.iter().copied(), partition_values.to_vec()),
+                 E::CLUSTERING_KEY.join(", "), clustering_values.join(", "), limit))
+}
+
+/// Rows of the partitions following the one of `after` in token order.
+pub fn next_partitions_statement<E: Entity>(after: &E::PrimaryKey, limit: usize) -> String {
+    let values = after.values();
+
+    format!("SELECT {} FROM {} WHERE token({}) > token({}) LIMIT {}", E::COLUMNS.join(", "), E::TABLE,
+            E::PARTITION_KEY.join(", "), values[..E::PARTITION_KEY.len()].join(", "), limit)
+}
+
+fn primary_key_columns<E: Entity>() -> impl Iterator<Item = &'static str> {
+    E::PARTITION_KEY.iter().chain(E::CLUSTERING_KEY.iter()).copied()
+}
+
+fn where_clause<'a>(columns: impl Iterator<Item = &'a str>, values: Vec<String>) -> String {
+    columns.zip(values)
+        .map(|(column, value)| format!("{} = {}", column, value))
+        .collect::<Vec<String>>()
+        .join(" and ")
+}
+
+/// Columns written by an insert or update, skipping the unset columns that are `None`.
+fn written_columns<E: Entity>(entity: &E) -> Vec<(&'static str, String)> {
+    E::COLUMNS.iter().copied()
+        .zip(entity.values())
+        .filter(|(column, value)| !(value == "null" && E::UNSET_WHEN_NONE.contains(column)))
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::serde::uuid::Uuid;
+
+    crate::cql_entity! {
+        #[derive(Debug, Clone)]
+        pub struct Part {
+            pub user_id     : Uuid,
+            pub part_id     : Uuid,
+            pub position    : i32,
+            pub name        : String,
+            pub note        : Option<String>
+        }
+        table = "vehicles.part";
+        partition_key = (user_id: Uuid);
+        clustering_key = (part_id: Uuid, position: i32);
+        unset_when_none = (note);
+    }
+
+    #[test]
+    fn when_cql_entity_then_declares_table_metadata() {
+        assert_eq!("vehicles.part", Part::TABLE);
+        assert_eq!("part", Part::name());
+        assert_eq!(&["user_id", "part_id", "position", "name", "note"], Part::COLUMNS);
+        assert_eq!(&["user_id"], Part::PARTITION_KEY);
+        assert_eq!(&["part_id", "position"], Part::CLUSTERING_KEY);
+        assert_eq!(&["note"], Part::UNSET_WHEN_NONE);
+    }
+
+    #[test]
+    fn when_get_statement_then_selects_every_column_by_primary_key() {
+        let statement = get_statement::<Part>(&fixture::part(None).primary_key());
+
+        assert_eq!(fixture::EXPECTED_GET_STATEMENT, statement);
+    }
+
+    #[test]
+    fn when_list_by_partition_statement_then_selects_by_partition_key() {
+        let statement = list_by_partition_statement::<Part>(&(fixture::user_id(),));
+
+        assert_eq!(fixture::EXPECTED_LIST_STATEMENT, statement);
+    }
+
+    #[test]
+    fn given_unset_column_when_insert_statement_then_column_is_only_written_when_set() {
+        assert_eq!(fixture::EXPECTED_INSERT_STATEMENT, insert_statement(&fixture::part(Some("o'ring"))));
+        assert_eq!(fixture::EXPECTED_INSERT_STATEMENT_WITHOUT_NOTE, insert_statement(&fixture::part(None)));
+    }
+
+    #[test]
+    fn when_update_statement_then_sets_every_column_but_the_keys() {
+        assert_eq!(fixture::EXPECTED_UPDATE_STATEMENT, update_statement(&fixture::part(Some("o'ring"))));
+    }
+
+    #[test]
+    fn when_delete_statement_then_deletes_by_primary_key() {
+        let statement = delete_statement::<Part>(&fixture::part(None).primary_key());
+
+        assert_eq!(fixture::EXPECTED_DELETE_STATEMENT, statement);
+    }
+
+    #[test]
+    fn when_paging_statements_then_walk_partitions_in_token_order() {
+        let after = fixture::part(None).primary_key();
+
+        assert_eq!("SELECT user_id, part_id, position, name, note FROM vehicles.part LIMIT 10", scan_statement::<Part>(10));
+        assert_eq!(fixture::EXPECTED_PARTITION_REST_STATEMENT, partition_rest_statement::<Part>(&after, 10).unwrap());
+        assert_eq!(fixture::EXPECTED_NEXT_PARTITIONS_STATEMENT, next_partitions_statement::<Part>(&after, 10));
+        assert_eq!(format!("{} LIMIT 10", fixture::EXPECTED_LIST_STATEMENT), list_by_partition_page_statement::<Part>(&(fixture::user_id(),), 10));
+    }
+
+    #[test]
+    fn when_columns_statements_then_select_only_those_columns() {
+        let after = fixture::part(None).primary_key();
+
+        assert_eq!(fixture::EXPECTED_GET_STATEMENT.replace("user_id, part_id, position, name, note", "name, part_id"),
+                   get_columns_statement::<Part>(&after, &["name", "part_id"]));
+        assert_eq!(fixture::EXPECTED_PARTITION_REST_STATEMENT.replace("user_id, part_id, position, name, note", "note"),
+                   partition_rest_columns_statement::<Part>(&after, &["note"], 10).unwrap());
+        assert_eq!(format!("{} LIMIT 10", fixture::EXPECTED_LIST_STATEMENT.replace("user_id, part_id, position, name, note", "name")),
+                   list_by_partition_page_columns_statement::<Part>(&(fixture::user_id(),), &["name"], 10));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const PART_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const EXPECTED_GET_STATEMENT: &str = "SELECT user_id, part_id, position, name, note FROM vehicles.part \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and part_id = 88573010-cf4c-490e-9d29-f8517dc60b90 and position = 2";
+        pub const EXPECTED_LIST_STATEMENT: &str = "SELECT user_id, part_id, position, name, note FROM vehicles.part \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const EXPECTED_INSERT_STATEMENT: &str = "INSERT INTO vehicles.part (user_id, part_id, position, name, note) \
+            VALUES (a906615e-2e6a-4edb-9377-5a6b8544791b, 88573010-cf4c-490e-9d29-f8517dc60b90, 2, 'chain', 'o''ring')";
+        pub const EXPECTED_INSERT_STATEMENT_WITHOUT_NOTE: &str = "INSERT INTO vehicles.part (user_id, part_id, position, name) \
+            VALUES (a906615e-2e6a-4edb-9377-5a6b8544791b, 88573010-cf4c-490e-9d29-f8517dc60b90, 2, 'chain')";
+        pub const EXPECTED_UPDATE_STATEMENT: &str = "UPDATE vehicles.part SET name = 'chain', note = 'o''ring' \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and part_id = 88573010-cf4c-490e-9d29-f8517dc60b90 and position = 2";
+        pub const EXPECTED_PARTITION_REST_STATEMENT: &str = "SELECT user_id, part_id, position, name, note FROM vehicles.part \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and (part_id, position) > (88573010-cf4c-490e-9d29-f8517dc60b90, 2) LIMIT 10";
+        pub const EXPECTED_NEXT_PARTITIONS_STATEMENT: &str = "SELECT user_id, part_id, position, name, note FROM vehicles.part \
+            WHERE token(user_id) > token(a906615e-2e6a-4edb-9377-5a6b8544791b) LIMIT 10";
+        pub const EXPECTED_DELETE_STATEMENT: &str = "DELETE FROM vehicles.part \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and part_id = 88573010-cf4c-490e-9d29-f8517dc60b90 and position = 2";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn part(note: Option<&str>) -> Part {
+            Part {
+                user_id: user_id(),
+                part_id: Uuid::parse_str(PART_ID_STR).unwrap(),
+                position: 2,
+                name: "chain".to_string(),
+                note: note.map(|n| n.to_string())
+            }
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use std::sync::Arc;
+use scylla::IntoTypedRows;
+
+use rocket::serde::uuid::Uuid;
+
+use crate::dao::session_manager::SessionManager;
+use crate::domain::maintenance::{MaintenanceRecord, ReminderRule};
+use crate::repository::cql;
+
+#[async_trait]
+pub trait MaintenanceRepository {
+    async fn get_records(&self, user_id: Uuid, vehicle_id: Uuid) -> Vec<MaintenanceRecord>;
+    async fn save_record(&self, record: MaintenanceRecord) -> Option<MaintenanceRecord>;
+    async fn get_rules(&self, user_id: Uuid, vehicle_id: Uuid) -> Vec<ReminderRule>;
+    async fn save_rule(&self, rule: ReminderRule) -> Option<ReminderRule>;
+}
+
+pub struct MaintenanceRepositoryImpl {
+    queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+}
+
+impl MaintenanceRepositoryImpl {
+    pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> MaintenanceRepositoryImpl {
+        MaintenanceRepositoryImpl {
+            queriable
+        }
+    }
+}
+
+#[async_trait]
+impl MaintenanceRepository for MaintenanceRepositoryImpl {
+    /// Records are clustered by `performed_on` descending, so the newest one comes first.
+    async fn get_records(&self, user_id: Uuid, vehicle_id: Uuid) -> Vec<MaintenanceRecord> {
+        let query = format!("SELECT user_id, vehicle_id, performed_on, record_id, odometer, description, cost, parts, rule_id \
+            FROM vehicles.maintenance_record \
+            WHERE user_id = {} and vehicle_id = {}", user_id, vehicle_id);
+
+        let result = self.queriable.execute_query("get_records", &query).await;
+
+        result
+            .expect(&format!("Failed to execute query {}", query))
+            .rows
+            .unwrap_or_default()
+            .into_typed::<MaintenanceRecord>()
+            .map(|row| row.expect("Failed to extract MaintenanceRecord from Row"))
+            .collect()
+    }
+
+    async fn save_record(&self, record: MaintenanceRecord) -> Option<MaintenanceRecord> {
+        let query = format!("INSERT INTO vehicles.maintenance_record (user_id, \
+                                            vehicle_id,     \
+                                            performed_on,   \
+                                            record_id,      \
+                                            odometer,       \
+                                            description,    \
+                                            cost,           \
+                                            parts,          \
+                                            rule_id) VALUES ({}, {}, '{}', {}, {}, {}, {}, {}, {})",
+                            record.user_id, record.vehicle_id, record.performed_on, record.record_id, record.odometer,
+                            cql::text(&record.description), record.cost,
+                            cql::text_list(record.parts.as_deref().unwrap_or(&[])), cql::optional(record.rule_id)
+        );
+
+        let result = self.queriable.execute_query("save_record", &query).await;
+
+        match result {
+            Ok(_) => Some(record),
+            Err(e) => {
+                println!("Failed to insert MaintenanceRecord {:?} with error {:?}", query, e);
+                None
+            }
+        }
+    }
+
+    async fn get_rules(&self, user_id: Uuid, vehicle_id: Uuid) -> Vec<ReminderRule> {
+        let query = format!("SELECT user_id, vehicle_id, rule_id, description, interval_distance, interval_months \
+            FROM vehicles.reminder_rule \
+            WHERE user_id = {} and vehicle_id = {}", user_id, vehicle_id);
+
+        let result = self.queriable.execute_query("get_rules", &query).await;
+
+        result
+            .expect(&format!("Failed to execute query {}", query))
+            .rows
+            .unwrap_or_default()
+            .into_typed::<ReminderRule>()
+            .map(|row| row.expect("Failed to extract ReminderRule from Row"))
+            .collect()
+    }
+
+    async fn save_rule(&self, rule: ReminderRule) -> Option<ReminderRule> {
+        let query = format!("INSERT INTO vehicles.reminder_rule (user_id, vehicle_id, rule_id, description, interval_distance, interval_months) \
+            VALUES ({}, {}, {}, {}, {}, {})",
+                            rule.user_id, rule.vehicle_id, rule.rule_id, cql::text(&rule.description),
+                            cql::optional(rule.interval_distance), cql::optional(rule.interval_months)
+        );
+
+        let result = self.queriable.execute_query("save_rule", &query).await;
+
+        match result {
+            Ok(_) => Some(rule),
+            Err(e) => {
+                println!("Failed to insert ReminderRule {:?} with error {:?}", query, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use scylla::QueryResult;
+    use scylla::transport::errors::QueryError;
+    use scylla::frame::response::result::{CqlValue, Row};
+    use scylla::cql_to_rust::FromCqlVal;
+    use chrono::NaiveDate;
+
+    use crate::repository::vehicle_repository::tests::MockSessionManagerImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn when_get_records_then_returns_records() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|_, query: &str| query == fixture::EXPECTED_GET_RECORDS_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_record_query_result());
+
+        let maintenance_repository = MaintenanceRepositoryImpl::new(Arc::new(session_manager));
+
+        let records = aw!(maintenance_repository.get_records(fixture::user_id(), fixture::vehicle_id()));
+
+        assert_eq!(1, records.len());
+        assert_eq!(NaiveDate::from_cql(CqlValue::Date(fixture::PERFORMED_ON)).unwrap(), records[0].performed_on);
+        assert_eq!(fixture::ODOMETER, records[0].odometer);
+        assert_eq!(fixture::DESCRIPTION, records[0].description);
+        assert_eq!(Some(vec!(fixture::PART.to_string())), records[0].parts);
+        assert!(records[0].rule_id.is_none());
+    }
+
+    #[test]
+    fn given_no_rows_when_get_rules_then_returns_empty() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|_, query: &str| query == fixture::EXPECTED_GET_RULES_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        let maintenance_repository = MaintenanceRepositoryImpl::new(Arc::new(session_manager));
+
+        let rules = aw!(maintenance_repository.get_rules(fixture::user_id(), fixture::vehicle_id()));
+
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn given_error_when_get_records_then_panics() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .times(1)
+            .returning(move |_, _| Err(QueryError::InvalidMessage("error".to_owned())));
+
+        let maintenance_repository = MaintenanceRepositoryImpl::new(Arc::new(session_manager));
+
+        aw!(maintenance_repository.get_records(fixture::user_id(), fixture::vehicle_id()));
+    }
+
+    #[test]
+    fn when_save_record_then_returns_record() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|_, query: &str| query == fixture::EXPECTED_SAVE_RECORD_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        let maintenance_repository = MaintenanceRepositoryImpl::new(Arc::new(session_manager));
+
+        let record = aw!(maintenance_repository.save_record(fixture::record())).unwrap();
+
+        assert_eq!(fixture::ODOMETER, record.odometer);
+    }
+
+    #[test]
+    fn when_save_rule_then_returns_rule() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|_, query: &str| query == fixture::EXPECTED_SAVE_RULE_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        let maintenance_repository = MaintenanceRepositoryImpl::new(Arc::new(session_manager));
+
+        let rule = aw!(maintenance_repository.save_rule(fixture::rule())).unwrap();
+
+        assert_eq!(Some(3000), rule.interval_distance);
+    }
+
+    #[test]
+    fn given_error_when_save_rule_then_returns_none() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .times(1)
+            .returning(move |_, _| Err(QueryError::InvalidMessage("error".to_owned())));
+
+        let maintenance_repository = MaintenanceRepositoryImpl::new(Arc::new(session_manager));
+
+        assert!(aw!(maintenance_repository.save_rule(fixture::rule())).is_none());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const RECORD_ID_STR: &str = "5d3c1f0e-1b7a-4e44-8d8c-0f1d2e3a4b5c";
+        pub const RULE_ID_STR: &str = "7e9a2b1c-3d4e-4f50-9a6b-7c8d9e0f1a2b";
+        pub const PERFORMED_ON: u32 = 2147499963;
+        pub const ODOMETER: i32 = 4500;
+        pub const DESCRIPTION: &str = "rider's chain";
+        pub const PART: &str = "chain";
+        pub const EXPECTED_GET_RECORDS_QUERY: &str = "SELECT user_id, vehicle_id, performed_on, record_id, odometer, description, cost, parts, rule_id \
+            FROM vehicles.maintenance_record \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const EXPECTED_GET_RULES_QUERY: &str = "SELECT user_id, vehicle_id, rule_id, description, interval_distance, interval_months \
+            FROM vehicles.reminder_rule \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const EXPECTED_SAVE_RECORD_QUERY: &str = "INSERT INTO vehicles.maintenance_record (user_id, \
+                                            vehicle_id,     \
+                                            performed_on,   \
+                                            record_id,      \
+                                            odometer,       \
+                                            description,    \
+                                            cost,           \
+                                            parts,          \
+                                            rule_id) VALUES (a906615e-2e6a-4edb-9377-5a6b8544791b, 88573010-cf4c-490e-9d29-f8517dc60b90, \
+                                            '2021-06-01', 5d3c1f0e-1b7a-4e44-8d8c-0f1d2e3a4b5c, 4500, 'rider''s chain', 35.5, ['chain'], null)";
+        pub const EXPECTED_SAVE_RULE_QUERY: &str = "INSERT INTO vehicles.reminder_rule (user_id, vehicle_id, rule_id, description, interval_distance, interval_months) \
+            VALUES (a906615e-2e6a-4edb-9377-5a6b8544791b, 88573010-cf4c-490e-9d29-f8517dc60b90, 7e9a2b1c-3d4e-4f50-9a6b-7c8d9e0f1a2b, 'service', 3000, 12)";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn record() -> MaintenanceRecord {
+            MaintenanceRecord {
+                user_id: user_id(),
+                vehicle_id: vehicle_id(),
+                performed_on: NaiveDate::from_ymd(2021, 6, 1),
+                record_id: Uuid::parse_str(RECORD_ID_STR).unwrap(),
+                odometer: ODOMETER,
+                description: DESCRIPTION.to_string(),
+                cost: 35.5,
+                parts: Some(vec!(PART.to_string())),
+                rule_id: None
+            }
+        }
+
+        pub fn rule() -> ReminderRule {
+            ReminderRule {
+                user_id: user_id(),
+                vehicle_id: vehicle_id(),
+                rule_id: Uuid::parse_str(RULE_ID_STR).unwrap(),
+                description: "service".to_string(),
+                interval_distance: Some(3000),
+                interval_months: Some(12)
+            }
+        }
+
+        pub fn create_record_query_result() -> Result<QueryResult, QueryError> {
+            let cql_values = vec!(
+                Some(CqlValue::Uuid(user_id())),
+                Some(CqlValue::Uuid(vehicle_id())),
+                Some(CqlValue::Date(PERFORMED_ON)),
+                Some(CqlValue::Uuid(Uuid::parse_str(RECORD_ID_STR).unwrap())),
+                Some(CqlValue::Int(ODOMETER)),
+                Some(CqlValue::Text(DESCRIPTION.to_string())),
+                Some(CqlValue::Double(35.5)),
+                Some(CqlValue::List(vec!(CqlValue::Text(PART.to_string())))),
+                None);
+
+            Ok(QueryResult {
+                rows: Some(vec!(Row { columns: cql_values })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            })
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::Arc;
+
+use rocket::serde::uuid::Uuid;
+
+use crate::dao::session_manager::{SessionManager, Statement};
+use crate::domain::outbox::{OutboxEntry, OutboxWatermark};
+use crate::repository::cql;
+use crate::repository::cql_repository::CqlRepository;
+use crate::repository::entity::Entity;
+
+/// Reads the entries of the outbox, which are only ever written together with the change they are about, by the
+/// repository making it, and expire on their own. The relay of each shard is leased to a single instance, which
+/// records how far it got in the watermark of the shard.
+#[async_trait]
+pub trait OutboxRepository {
+    /// Oldest entries of the partition of a shard holding `bucket`, following `after`, the time and id of an entry,
+    /// `None` when they could not be read.
+    async fn get_entries(&self, shard: i32, bucket: i64, after: Option<(i64, Uuid)>, limit: usize) -> Option<Vec<OutboxEntry>>;
+    /// Watermark of a shard, with null columns when it was never leased, `None` when it could not be read.
+    async fn get_watermark(&self, shard: i32) -> Option<OutboxWatermark>;
+    /// Compare-and-set of the lease of a shard, `Some(false)` when another instance took it over since `watermark`
+    /// was read.
+    async fn lease_shard(&self, watermark: &OutboxWatermark, owner: Uuid, lease_until_ms: i64) -> Option<bool>;
+    /// Moves the watermark of a shard to an entry as long as `owner` holds its lease, `Some(false)` once it lost it.
+    async fn save_watermark(&self, shard: i32, owner: Uuid, occurred_at_ms: i64, event_id: Uuid) -> Option<bool>;
+}
+
+pub struct OutboxRepositoryImpl {
+    queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+    entries: CqlRepository<OutboxEntry>,
+    watermarks: CqlRepository<OutboxWatermark>,
+}
+
+impl OutboxRepositoryImpl {
+    pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> OutboxRepositoryImpl {
+        OutboxRepositoryImpl {
+            entries: CqlRepository::new(queriable.clone()),
+            watermarks: CqlRepository::new(queriable.clone()),
+            queriable
+        }
+    }
+
+    async fn compare_and_set(&self, operation: &str, query: String) -> Option<bool> {
+        let outcome = self.queriable.execute_statement(Statement::non_idempotent(&query).for_operation(operation)).await;
+
+        match outcome.result {
+            Ok(query_result) => Some(cql::applied(&query_result)),
+            Err(e) => {
+                println!("Failed to {} {:?} after {} retries with error {:?}", operation, query, outcome.retries, e);
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for OutboxRepositoryImpl {
+    async fn get_entries(&self, shard: i32, bucket: i64, after: Option<(i64, Uuid)>, limit: usize) -> Option<Vec<OutboxEntry>> {
+        let after = after.map(|(occurred_at_ms, event_id)| (shard, bucket, occurred_at_ms, event_id));
+
+        match self.entries.list_by_partition_page(&(shard, bucket), after.as_ref(), limit).await {
+            Ok(entries) => Some(entries),
+            Err(e) => {
+                println!("Failed to list OutboxEntries of shard {} in bucket {} with error {:?}", shard, bucket, e);
+                None
+            }
+        }
+    }
+
+    async fn get_watermark(&self, shard: i32) -> Option<OutboxWatermark> {
+        match self.watermarks.get(&(shard,)).await {
+            Ok(watermark) => Some(watermark.unwrap_or(OutboxWatermark { shard, occurred_at_ms: None, event_id: None, owner: None, lease_until_ms: None })),
+            Err(e) => {
+                println!("Failed to get OutboxWatermark of shard {} with error {:?}", shard, e);
+                None
+            }
+        }
+    }
+
+    async fn lease_shard(&self, watermark: &OutboxWatermark, owner: Uuid, lease_until_ms: i64) -> Option<bool> {
+        let query = format!("UPDATE {} SET owner = {}, lease_until_ms = {} WHERE shard = {} IF lease_until_ms = {}",
+                            OutboxWatermark::TABLE, owner, lease_until_ms, watermark.shard, cql::optional(watermark.lease_until_ms));
+
+        self.compare_and_set("lease_outbox_shard", query).await
+    }
+
+    async fn save_watermark(&self, shard: i32, owner: Uuid, occurred_at_ms: i64, event_id: Uuid) -> Option<bool> {
+        let query = format!("UPDATE {} SET occurred_at_ms = {}, event_id = {} WHERE shard = {} IF owner = {}",
+                            OutboxWatermark::TABLE, occurred_at_ms, event_id, shard, owner);
+
+        self.compare_and_set("save_outbox_watermark", query).await
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use scylla::QueryResult;
+    use scylla::frame::response::result::{CqlValue, Row};
+    use scylla::transport::errors::QueryError;
+
+    use crate::dao::session_manager::QueryOutcome;
+    use crate::repository::vehicle_repository::tests::MockSessionManagerImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn given_watermark_when_get_entries_then_reads_entries_of_bucket_following_it() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_outbox" && query == fixture::EXPECTED_LIST_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result());
+
+        let outbox_repository = OutboxRepositoryImpl::new(Arc::new(session_manager));
+
+        let entries = aw!(outbox_repository.get_entries(3, 1, Some((4000, fixture::event_id())), 100)).unwrap();
+
+        assert_eq!(vec!(fixture::entry()), entries);
+    }
+
+    #[test]
+    fn given_shard_never_leased_when_get_watermark_then_returns_empty_watermark() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|_, query: &str| query == fixture::EXPECTED_GET_WATERMARK_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        let outbox_repository = OutboxRepositoryImpl::new(Arc::new(session_manager));
+
+        assert_eq!(Some(fixture::watermark(None)), aw!(outbox_repository.get_watermark(3)));
+    }
+
+    #[test]
+    fn given_lease_taken_over_when_lease_shard_then_returns_false() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_LEASE_QUERY && !statement.idempotent)
+            .times(1)
+            .returning(move |_| fixture::applied(false));
+
+        let outbox_repository = OutboxRepositoryImpl::new(Arc::new(session_manager));
+
+        assert_eq!(Some(false), aw!(outbox_repository.lease_shard(&fixture::watermark(Some(5000)), fixture::owner(), 35000)));
+    }
+
+    #[test]
+    fn when_save_watermark_then_moves_it_if_lease_is_still_held() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_SAVE_WATERMARK_QUERY && !statement.idempotent)
+            .times(1)
+            .returning(move |_| fixture::applied(true));
+
+        let outbox_repository = OutboxRepositoryImpl::new(Arc::new(session_manager));
+
+        assert_eq!(Some(true), aw!(outbox_repository.save_watermark(3, fixture::owner(), 5000, fixture::event_id())));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const EVENT_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const OWNER_STR: &str = "6176bc4b-33b6-4c9c-a4ad-c65da1322a80";
+
+        pub const EXPECTED_LIST_QUERY: &str = "SELECT shard, bucket, occurred_at_ms, event_id, user_id, vehicle_id, event, vehicle FROM vehicles.outbox \
+            WHERE shard = 3 and bucket = 1 and (occurred_at_ms, event_id) > (4000, 88573010-cf4c-490e-9d29-f8517dc60b90) LIMIT 100";
+        pub const EXPECTED_GET_WATERMARK_QUERY: &str = "SELECT shard, occurred_at_ms, event_id, owner, lease_until_ms FROM vehicles.outbox_watermark \
+            WHERE shard = 3";
+        pub const EXPECTED_LEASE_QUERY: &str = "UPDATE vehicles.outbox_watermark SET owner = 6176bc4b-33b6-4c9c-a4ad-c65da1322a80, lease_until_ms = 35000 \
+            WHERE shard = 3 IF lease_until_ms = 5000";
+        pub const EXPECTED_SAVE_WATERMARK_QUERY: &str = "UPDATE vehicles.outbox_watermark SET occurred_at_ms = 5000, event_id = 88573010-cf4c-490e-9d29-f8517dc60b90 \
+            WHERE shard = 3 IF owner = 6176bc4b-33b6-4c9c-a4ad-c65da1322a80";
+
+        pub fn event_id() -> Uuid {
+            Uuid::parse_str(EVENT_ID_STR).unwrap()
+        }
+
+        pub fn owner() -> Uuid {
+            Uuid::parse_str(OWNER_STR).unwrap()
+        }
+
+        pub fn entry() -> OutboxEntry {
+            OutboxEntry {
+                shard: 3,
+                bucket: 1,
+                occurred_at_ms: 5000,
+                event_id: event_id(),
+                user_id: Uuid::parse_str(USER_ID_STR).unwrap(),
+                vehicle_id: event_id(),
+                event: "transferred".to_string(),
+                vehicle: None
+            }
+        }
+
+        pub fn watermark(lease_until_ms: Option<i64>) -> OutboxWatermark {
+            OutboxWatermark { shard: 3, occurred_at_ms: None, event_id: None, owner: None, lease_until_ms }
+        }
+
+        pub fn applied(applied: bool) -> QueryOutcome {
+            QueryOutcome {
+                result: Ok(QueryResult {
+                    rows: Some(vec!(Row { columns: vec!(Some(CqlValue::Boolean(applied))) })),
+                    warnings: vec!(),
+                    tracing_id: None,
+                    paging_state: None
+                }),
+                retries: 0
+            }
+        }
+
+        pub fn create_query_result() -> Result<QueryResult, QueryError> {
+            let cql_values = vec!(
+                Some(CqlValue::Int(3)),
+                Some(CqlValue::BigInt(1)),
+                Some(CqlValue::BigInt(5000)),
+                Some(CqlValue::Uuid(event_id())),
+                Some(CqlValue::Uuid(Uuid::parse_str(USER_ID_STR).unwrap())),
+                Some(CqlValue::Uuid(event_id())),
+                Some(CqlValue::Text("transferred".to_string())),
+                None);
+
+            Ok(QueryResult {
+                rows: Some(vec!(Row { columns: cql_values })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            })
+        }
+    }
+}
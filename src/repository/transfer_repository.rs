@@ -0,0 +1,487 @@
+use std::sync::Arc;
+use scylla::IntoTypedRows;
+
+use rocket::serde::uuid::Uuid;
+
+use crate::dao::session_manager::{BatchStatement, SessionManager, Statement};
+use crate::domain::transfer::{OwnershipRecord, TransferOffer, CLAIM_TTL_SECONDS, OFFER_DECLINED};
+use crate::domain::vehicle::Vehicle;
+use crate::domain::vehicle_event::VehicleEventKind;
+use crate::domain::vehicle_history::VehicleChange;
+use crate::domain::vehicle_lookup::VehicleLookup;
+use crate::repository::entity::{self, Entity};
+use crate::repository::cql;
+use crate::mapper::outbox_mapper;
+
+use chrono::{Utc, TimeZone};
+
+#[async_trait]
+pub trait TransferRepository {
+    async fn get_offer(&self, to_user_id: Uuid, offer_id: Uuid) -> Option<TransferOffer>;
+    async fn get_offers(&self, to_user_id: Uuid) -> Vec<TransferOffer>;
+    async fn save_offer(&self, offer: TransferOffer) -> Option<TransferOffer>;
+    async fn update_offer_status(&self, to_user_id: Uuid, offer_id: Uuid, expected: &str, status: &str) -> Option<bool>;
+    /// Removes an offer from the partition of its recipient, leaving its index by vehicle.
+    async fn delete_offer(&self, to_user_id: Uuid, offer_id: Uuid) -> Option<()>;
+    async fn claim_vehicle(&self, vehicle_id: Uuid, offer_id: Uuid) -> Option<bool>;
+    async fn release_vehicle(&self, vehicle_id: Uuid, offer_id: Uuid);
+    /// Recipient and id of every offer made for a vehicle since it was last transferred.
+    async fn get_vehicle_offers(&self, vehicle_id: Uuid) -> Vec<(Uuid, Uuid)>;
+    async fn transfer_vehicle(&self, previous: Vehicle, vehicle: Vehicle, record: OwnershipRecord, declined: Vec<(Uuid, Uuid)>, change: VehicleChange) -> Option<Vehicle>;
+    async fn get_ownership_records(&self, vehicle_id: Uuid) -> Vec<OwnershipRecord>;
+}
+
+pub struct TransferRepositoryImpl {
+    queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+}
+
+impl TransferRepositoryImpl {
+    pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> TransferRepositoryImpl {
+        TransferRepositoryImpl {
+            queriable
+        }
+    }
+
+    /// Sets the acceptance lock of the vehicle to `offer_id` for `CLAIM_TTL_SECONDS` if it currently is `expected`.
+    async fn lock_vehicle(&self, vehicle_id: Uuid, offer_id: Uuid, expected: String) -> Option<bool> {
+        let query = format!("UPDATE vehicles.transfer_offer_by_vehicle USING TTL {} SET accepted_offer_id = {} \
+            WHERE vehicle_id = {} IF accepted_offer_id = {}", CLAIM_TTL_SECONDS, offer_id, vehicle_id, expected);
+
+        let outcome = self.queriable.execute_statement(Statement::non_idempotent(&query).for_operation("claim_vehicle")).await;
+
+        match outcome.result {
+            Ok(query_result) => Some(cql::applied(&query_result)),
+            Err(e) => {
+                println!("Failed to claim Vehicle {:?} after {} retries with error {:?}", query, outcome.retries, e);
+                None
+            }
+        }
+    }
+
+    async fn select_offers(&self, operation: &str, query: String) -> Vec<TransferOffer> {
+        let result = self.queriable.execute_query(operation, &query).await;
+
+        result
+            .expect(&format!("Failed to execute query {}", query))
+            .rows
+            .unwrap_or_default()
+            .into_typed::<TransferOffer>()
+            .map(|row| row.expect("Failed to extract TransferOffer from Row"))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl TransferRepository for TransferRepositoryImpl {
+    async fn get_offer(&self, to_user_id: Uuid, offer_id: Uuid) -> Option<TransferOffer> {
+        let query = format!("SELECT to_user_id, offer_id, from_user_id, vehicle_id, status, created_at \
+            FROM vehicles.transfer_offer \
+            WHERE to_user_id = {} and offer_id = {}", to_user_id, offer_id);
+
+        self.select_offers("get_offer", query).await.into_iter().next()
+    }
+
+    async fn get_offers(&self, to_user_id: Uuid) -> Vec<TransferOffer> {
+        let query = format!("SELECT to_user_id, offer_id, from_user_id, vehicle_id, status, created_at \
+            FROM vehicles.transfer_offer \
+            WHERE to_user_id = {}", to_user_id);
+
+        self.select_offers("get_offers", query).await
+    }
+
+    /// Stores the offer in the partition of its recipient and indexes it by vehicle in a single logged batch,
+    /// so accepting any offer of the vehicle finds the others to decline.
+    async fn save_offer(&self, offer: TransferOffer) -> Option<TransferOffer> {
+        let statements = vec!(
+            format!("INSERT INTO vehicles.transfer_offer (to_user_id, offer_id, from_user_id, vehicle_id, status, created_at) \
+                VALUES ({}, {}, {}, {}, {}, '{}')",
+                    offer.to_user_id, offer.offer_id, offer.from_user_id, offer.vehicle_id, cql::text(&offer.status),
+                    Utc.timestamp(offer.created_at.num_seconds(), 0)),
+            format!("INSERT INTO vehicles.transfer_offer_by_vehicle (vehicle_id, offer_id, to_user_id) VALUES ({}, {}, {})",
+                    offer.vehicle_id, offer.offer_id, offer.to_user_id)
+        );
+
+        let outcome = self.queriable.execute_batch(BatchStatement::logged(statements).for_operation("save_offer")).await;
+
+        match outcome.result {
+            Ok(_) => Some(offer),
+            Err(e) => {
+                println!("Failed to insert TransferOffer {:?} with error {:?}", offer.offer_id, e);
+                None
+            }
+        }
+    }
+
+    /// Compare-and-set on the offer status, `Some(false)` when the offer was not in the `expected` status.
+    async fn update_offer_status(&self, to_user_id: Uuid, offer_id: Uuid, expected: &str, status: &str) -> Option<bool> {
+        let query = format!("UPDATE vehicles.transfer_offer SET status = {} \
+            WHERE to_user_id = {} and offer_id = {} IF status = {}",
+                            cql::text(status), to_user_id, offer_id, cql::text(expected));
+
+        let outcome = self.queriable.execute_statement(Statement::non_idempotent(&query).for_operation("update_offer_status")).await;
+
+        match outcome.result {
+            Ok(query_result) => Some(cql::applied(&query_result)),
+            Err(e) => {
+                println!("Failed to update TransferOffer {:?} after {} retries with error {:?}", query, outcome.retries, e);
+                None
+            }
+        }
+    }
+
+    async fn delete_offer(&self, to_user_id: Uuid, offer_id: Uuid) -> Option<()> {
+        let query = format!("DELETE FROM vehicles.transfer_offer WHERE to_user_id = {} and offer_id = {}", to_user_id, offer_id);
+
+        match self.queriable.execute_query("delete_offer", &query).await {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("Failed to delete TransferOffer {:?} with error {:?}", offer_id, e);
+                None
+            }
+        }
+    }
+
+    /// Locks the vehicle for the acceptance of `offer_id` with a lightweight transaction on the static
+    /// `accepted_offer_id` of its offers, `Some(false)` when another offer of the vehicle is being accepted.
+    /// The lock is written with a TTL of `CLAIM_TTL_SECONDS`, so that a lock left by an acceptance cut short lapses,
+    /// and a lock already held by `offer_id` is taken again, renewing it, so that its acceptance can be resumed.
+    async fn claim_vehicle(&self, vehicle_id: Uuid, offer_id: Uuid) -> Option<bool> {
+        match self.lock_vehicle(vehicle_id, offer_id, "null".to_string()).await? {
+            true => Some(true),
+            false => self.lock_vehicle(vehicle_id, offer_id, offer_id.to_string()).await
+        }
+    }
+
+    /// Unlocks the vehicle when the acceptance of `offer_id` failed, leaving a lock taken by another offer untouched.
+    async fn release_vehicle(&self, vehicle_id: Uuid, offer_id: Uuid) {
+        let query = format!("UPDATE vehicles.transfer_offer_by_vehicle SET accepted_offer_id = null \
+            WHERE vehicle_id = {} IF accepted_offer_id = {}", vehicle_id, offer_id);
+
+        let outcome = self.queriable.execute_statement(Statement::non_idempotent(&query).for_operation("release_vehicle")).await;
+
+        if let Err(e) = outcome.result {
+            println!("Failed to release Vehicle {:?} after {} retries with error {:?}", query, outcome.retries, e);
+        }
+    }
+
+    async fn get_vehicle_offers(&self, vehicle_id: Uuid) -> Vec<(Uuid, Uuid)> {
+        let query = format!("SELECT to_user_id, offer_id FROM vehicles.transfer_offer_by_vehicle WHERE vehicle_id = {}", vehicle_id);
+
+        let result = self.queriable.execute_query("get_vehicle_offers", &query).await;
+
+        result
+            .expect(&format!("Failed to execute query {}", query))
+            .rows
+            .unwrap_or_default()
+            .into_typed::<(Uuid, Uuid)>()
+            .map(|row| row.expect("Failed to extract vehicle offer from Row"))
+            .collect()
+    }
+
+    /// Moves the vehicle row and its lookup rows to the partition of its new owner and records the previous
+    /// owner in a single logged batch, so the row is never lost nor visible in both partitions once applied.
+    /// The batch also writes the outbox entries of the vehicle leaving the previous owner and reaching the new one,
+    /// declines the `declined` offers and drops the offers of the vehicle along with its acceptance lock.
+    /// `change` versions the vehicle in the history of its new owner. Activities, maintenance records, reminder rules,
+    /// components and earlier versions are partitioned by user and stay with the previous owner.
+    async fn transfer_vehicle(&self, previous: Vehicle, vehicle: Vehicle, record: OwnershipRecord, declined: Vec<(Uuid, Uuid)>, change: VehicleChange) -> Option<Vehicle> {
+        let mut statements = vec!(
+            entity::insert_statement(&vehicle),
+            entity::delete_statement::<Vehicle>(&(previous.user_id, previous.vehicle_id)),
+            format!("INSERT INTO vehicles.vehicle_owner_history (vehicle_id, owner_until, user_id, owner_since) VALUES ({}, '{}', {}, '{}')",
+                    record.vehicle_id, Utc.timestamp(record.owner_until.num_seconds(), 0), record.user_id, record.owner_since)
+        );
+        statements.extend(VehicleLookup::of(&previous).iter().map(|lookup| entity::delete_statement::<VehicleLookup>(&lookup.primary_key())));
+        statements.extend(VehicleLookup::of(&vehicle).iter().map(entity::insert_statement));
+        statements.push(entity::insert_statement(&outbox_mapper::get_outbox_entry(VehicleEventKind::Transferred, previous.user_id, previous.vehicle_id, None)));
+        statements.push(entity::insert_statement(&outbox_mapper::get_outbox_entry(VehicleEventKind::Created, vehicle.user_id, vehicle.vehicle_id, Some(&vehicle))));
+        statements.push(entity::insert_statement(&change.version));
+        statements.extend(declined.iter().map(|(to_user_id, offer_id)| format!("UPDATE vehicles.transfer_offer SET status = {} \
+            WHERE to_user_id = {} and offer_id = {}", cql::text(OFFER_DECLINED), to_user_id, offer_id)));
+        statements.push(format!("DELETE FROM vehicles.transfer_offer_by_vehicle WHERE vehicle_id = {}", vehicle.vehicle_id));
+
+        let outcome = self.queriable.execute_batch(BatchStatement::logged(statements).for_operation("transfer_vehicle")).await;
+
+        match outcome.result {
+            Ok(_) => Some(vehicle),
+            Err(e) => {
+                println!("Failed to transfer Vehicle {:?} with error {:?}", vehicle.vehicle_id, e);
+                None
+            }
+        }
+    }
+
+    async fn get_ownership_records(&self, vehicle_id: Uuid) -> Vec<OwnershipRecord> {
+        let query = format!("SELECT vehicle_id, owner_until, user_id, owner_since \
+            FROM vehicles.vehicle_owner_history \
+            WHERE vehicle_id = {}", vehicle_id);
+
+        let result = self.queriable.execute_query("get_ownership_records", &query).await;
+
+        result
+            .expect(&format!("Failed to execute query {}", query))
+            .rows
+            .unwrap_or_default()
+            .into_typed::<OwnershipRecord>()
+            .map(|row| row.expect("Failed to extract OwnershipRecord from Row"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use scylla::QueryResult;
+    use scylla::transport::errors::QueryError;
+    use scylla::frame::response::result::{CqlValue, Row};
+    use chrono::{Duration, NaiveDate};
+
+    use crate::domain::transfer::{OFFER_ACCEPTED, OFFER_PENDING};
+    use crate::dao::session_manager::{BatchMode, QueryOutcome};
+    use crate::repository::vehicle_repository::tests::MockSessionManagerImpl;
+    use crate::mapper::vehicle_history_mapper;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn when_get_offer_then_returns_offer() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|_, query: &str| query == fixture::EXPECTED_GET_OFFER_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_offer_query_result());
+
+        let transfer_repository = TransferRepositoryImpl::new(Arc::new(session_manager));
+
+        let offer = aw!(transfer_repository.get_offer(fixture::to_user_id(), fixture::offer_id())).unwrap();
+
+        assert_eq!(fixture::from_user_id(), offer.from_user_id);
+        assert_eq!(fixture::vehicle_id(), offer.vehicle_id);
+        assert_eq!(OFFER_PENDING, offer.status);
+    }
+
+    #[test]
+    fn given_applied_when_update_offer_status_then_returns_true() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_UPDATE_STATUS_QUERY && !statement.idempotent)
+            .times(1)
+            .returning(move |_| QueryOutcome {
+                result: Ok(QueryResult {
+                    rows: Some(vec!(Row { columns: vec!(Some(CqlValue::Boolean(true))) })),
+                    warnings: vec!(),
+                    tracing_id: None,
+                    paging_state: None
+                }),
+                retries: 0
+            });
+
+        let transfer_repository = TransferRepositoryImpl::new(Arc::new(session_manager));
+
+        let applied = aw!(transfer_repository.update_offer_status(fixture::to_user_id(), fixture::offer_id(), OFFER_PENDING, OFFER_ACCEPTED));
+
+        assert_eq!(Some(true), applied);
+    }
+
+    #[test]
+    fn given_vehicle_claimed_by_another_offer_when_claim_vehicle_then_returns_false() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_CLAIM_VEHICLE_QUERY && !statement.idempotent)
+            .times(1)
+            .returning(move |_| fixture::claim_result(false));
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_RENEW_CLAIM_QUERY && !statement.idempotent)
+            .times(1)
+            .returning(move |_| fixture::claim_result(false));
+
+        let transfer_repository = TransferRepositoryImpl::new(Arc::new(session_manager));
+
+        let applied = aw!(transfer_repository.claim_vehicle(fixture::vehicle_id(), fixture::offer_id()));
+
+        assert_eq!(Some(false), applied);
+    }
+
+    #[test]
+    fn given_vehicle_claimed_by_same_offer_when_claim_vehicle_then_renews_claim() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_CLAIM_VEHICLE_QUERY)
+            .times(1)
+            .returning(move |_| fixture::claim_result(false));
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_RENEW_CLAIM_QUERY)
+            .times(1)
+            .returning(move |_| fixture::claim_result(true));
+
+        let transfer_repository = TransferRepositoryImpl::new(Arc::new(session_manager));
+
+        let applied = aw!(transfer_repository.claim_vehicle(fixture::vehicle_id(), fixture::offer_id()));
+
+        assert_eq!(Some(true), applied);
+    }
+
+    #[test]
+    fn when_transfer_vehicle_then_executes_logged_batch() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_batch()
+            .withf(|batch: &BatchStatement| batch.mode == BatchMode::Logged
+                && batch.statements.len() == 12
+                && batch.statements[0].starts_with("INSERT INTO vehicles.vehicle ")
+                && batch.statements[1] == fixture::EXPECTED_DELETE_STATEMENT
+                && batch.statements[2] == fixture::EXPECTED_HISTORY_STATEMENT
+                && batch.statements[3..5].iter().all(|statement| statement.starts_with("DELETE FROM vehicles.vehicle_lookup ") && statement.contains(&fixture::from_user_id().to_string()))
+                && batch.statements[5..7].iter().all(|statement| statement.starts_with("INSERT INTO vehicles.vehicle_lookup ") && statement.contains(&fixture::to_user_id().to_string()))
+                && batch.statements[7].starts_with("INSERT INTO vehicles.outbox ") && batch.statements[7].contains("'transferred'")
+                && batch.statements[8].starts_with("INSERT INTO vehicles.outbox ") && batch.statements[8].contains("'created'")
+                && batch.statements[9].starts_with("INSERT INTO vehicles.vehicle_history ") && batch.statements[9].contains(&fixture::to_user_id().to_string())
+                && batch.statements[10] == fixture::EXPECTED_DECLINE_STATEMENT
+                && batch.statements[11] == fixture::EXPECTED_DELETE_OFFERS_STATEMENT)
+            .times(1)
+            .returning(move |_| QueryOutcome { result: Ok(QueryResult::default()), retries: 0 });
+
+        let transfer_repository = TransferRepositoryImpl::new(Arc::new(session_manager));
+
+        let previous = fixture::vehicle(fixture::from_user_id());
+        let vehicle = fixture::vehicle(fixture::to_user_id());
+
+        let change = vehicle_history_mapper::get_transfer_change(&previous, &vehicle, "jane");
+
+        let transferred = aw!(transfer_repository.transfer_vehicle(previous, vehicle, fixture::record(), vec!((fixture::other_user_id(), fixture::other_offer_id())), change)).unwrap();
+
+        assert_eq!(fixture::to_user_id(), transferred.user_id);
+    }
+
+    #[test]
+    fn given_error_when_transfer_vehicle_then_returns_none() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_batch()
+            .times(1)
+            .returning(move |_| QueryOutcome { result: Err(QueryError::InvalidMessage("error".to_owned())), retries: 0 });
+
+        let transfer_repository = TransferRepositoryImpl::new(Arc::new(session_manager));
+
+        let previous = fixture::vehicle(fixture::from_user_id());
+        let vehicle = fixture::vehicle(fixture::to_user_id());
+
+        let change = vehicle_history_mapper::get_transfer_change(&previous, &vehicle, "jane");
+
+        assert!(aw!(transfer_repository.transfer_vehicle(previous, vehicle, fixture::record(), vec!(), change)).is_none());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const FROM_USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const TO_USER_ID_STR: &str = "6176bc4b-33b6-4c9c-a4ad-c65da1322a80";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const OFFER_ID_STR: &str = "9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d";
+        pub const OTHER_USER_ID_STR: &str = "2c1d0e9f-8a7b-4c6d-9e5f-4a3b2c1d0e9f";
+        pub const OTHER_OFFER_ID_STR: &str = "5f4e3d2c-1b0a-4f9e-8d7c-6b5a4f3e2d1c";
+        pub const EXPECTED_GET_OFFER_QUERY: &str = "SELECT to_user_id, offer_id, from_user_id, vehicle_id, status, created_at \
+            FROM vehicles.transfer_offer \
+            WHERE to_user_id = 6176bc4b-33b6-4c9c-a4ad-c65da1322a80 and offer_id = 9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d";
+        pub const EXPECTED_UPDATE_STATUS_QUERY: &str = "UPDATE vehicles.transfer_offer SET status = 'accepted' \
+            WHERE to_user_id = 6176bc4b-33b6-4c9c-a4ad-c65da1322a80 and offer_id = 9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d IF status = 'pending'";
+        pub const EXPECTED_CLAIM_VEHICLE_QUERY: &str = "UPDATE vehicles.transfer_offer_by_vehicle USING TTL 300 SET accepted_offer_id = 9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d \
+            WHERE vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90 IF accepted_offer_id = null";
+        pub const EXPECTED_RENEW_CLAIM_QUERY: &str = "UPDATE vehicles.transfer_offer_by_vehicle USING TTL 300 SET accepted_offer_id = 9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d \
+            WHERE vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90 IF accepted_offer_id = 9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d";
+        pub const EXPECTED_DECLINE_STATEMENT: &str = "UPDATE vehicles.transfer_offer SET status = 'declined' \
+            WHERE to_user_id = 2c1d0e9f-8a7b-4c6d-9e5f-4a3b2c1d0e9f and offer_id = 5f4e3d2c-1b0a-4f9e-8d7c-6b5a4f3e2d1c";
+        pub const EXPECTED_DELETE_OFFERS_STATEMENT: &str = "DELETE FROM vehicles.transfer_offer_by_vehicle WHERE vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const EXPECTED_DELETE_STATEMENT: &str = "DELETE FROM vehicles.vehicle \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const EXPECTED_HISTORY_STATEMENT: &str = "INSERT INTO vehicles.vehicle_owner_history (vehicle_id, owner_until, user_id, owner_since) \
+            VALUES (88573010-cf4c-490e-9d29-f8517dc60b90, '1970-01-01 00:00:10 UTC', a906615e-2e6a-4edb-9377-5a6b8544791b, '2015-12-02')";
+
+        pub fn from_user_id() -> Uuid {
+            Uuid::parse_str(FROM_USER_ID_STR).unwrap()
+        }
+
+        pub fn to_user_id() -> Uuid {
+            Uuid::parse_str(TO_USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn offer_id() -> Uuid {
+            Uuid::parse_str(OFFER_ID_STR).unwrap()
+        }
+
+        pub fn other_user_id() -> Uuid {
+            Uuid::parse_str(OTHER_USER_ID_STR).unwrap()
+        }
+
+        pub fn other_offer_id() -> Uuid {
+            Uuid::parse_str(OTHER_OFFER_ID_STR).unwrap()
+        }
+
+        pub fn record() -> OwnershipRecord {
+            OwnershipRecord {
+                vehicle_id: vehicle_id(),
+                owner_until: Duration::seconds(10),
+                user_id: from_user_id(),
+                owner_since: NaiveDate::from_ymd(2015, 12, 2)
+            }
+        }
+
+        pub fn vehicle(user_id: Uuid) -> Vehicle {
+            Vehicle {
+                name: "the vehicle name".to_string(),
+                user_id,
+                vehicle_id: vehicle_id(),
+                created_at: Duration::seconds(5),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: 100,
+                owner_since: NaiveDate::from_ymd(2015, 12, 2),
+                manufacturing_date: NaiveDate::from_ymd(2015, 1, 1),
+                picture: None
+            }
+        }
+
+        pub fn claim_result(applied: bool) -> QueryOutcome {
+            QueryOutcome {
+                result: Ok(QueryResult {
+                    rows: Some(vec!(Row { columns: vec!(Some(CqlValue::Boolean(applied)), Some(CqlValue::Uuid(other_offer_id()))) })),
+                    warnings: vec!(),
+                    tracing_id: None,
+                    paging_state: None
+                }),
+                retries: 0
+            }
+        }
+
+        pub fn create_offer_query_result() -> Result<QueryResult, QueryError> {
+            let cql_values = vec!(
+                Some(CqlValue::Uuid(to_user_id())),
+                Some(CqlValue::Uuid(offer_id())),
+                Some(CqlValue::Uuid(from_user_id())),
+                Some(CqlValue::Uuid(vehicle_id())),
+                Some(CqlValue::Text(OFFER_PENDING.to_string())),
+                Some(CqlValue::Timestamp(Duration::seconds(5))));
+
+            Ok(QueryResult {
+                rows: Some(vec!(Row { columns: cql_values })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            })
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::sync::Arc;
+use scylla::IntoTypedRows;
+
+use rocket::serde::uuid::Uuid;
+
+use crate::dao::session_manager::{BatchStatement, SessionManager, Statement};
+use crate::domain::user_data::{self, ErasureJob, PartitionKey, UnfinishedErasure, UserTable, ERASURE_COMPLETED, ERASURE_FAILED};
+use crate::mapper::user_data_mapper;
+use crate::repository::cql;
+use crate::repository::cql_repository::CqlRepository;
+use crate::repository::entity::{self, Entity};
+
+/// Reads and deletes whole partitions of the tables holding data of users, and keeps track of their erasures.
+#[async_trait]
+pub trait UserDataRepository {
+    /// Rows of a partition as JSON objects, `None` when they could not be read.
+    async fn get_rows(&self, table: &UserTable, key: &PartitionKey) -> Option<Vec<String>>;
+    async fn delete_rows(&self, table: &UserTable, key: &PartitionKey) -> Option<()>;
+    /// Stores a new erasure along with its unfinished entry, leased to this instance, in a single logged batch.
+    async fn start_job(&self, job: &ErasureJob, lease_until_ms: i64) -> Option<()>;
+    /// Saves the progress of an erasure, removing its unfinished entry in the same logged batch once it is
+    /// `completed` or `failed`.
+    async fn save_job(&self, job: &ErasureJob) -> Option<()>;
+    async fn get_job(&self, user_id: Uuid, job_id: Uuid) -> Option<ErasureJob>;
+    /// Unfinished erasures of a shard following `after`, `None` when they could not be read.
+    async fn get_unfinished_jobs(&self, shard: i32, after: Option<UnfinishedErasure>, limit: usize) -> Option<Vec<UnfinishedErasure>>;
+    /// Compare-and-set of the lease of an unfinished erasure, `Some(false)` when another instance took it over.
+    async fn lease_job(&self, unfinished: &UnfinishedErasure, lease_until_ms: i64) -> Option<bool>;
+    async fn delete_unfinished_job(&self, unfinished: &UnfinishedErasure) -> Option<()>;
+}
+
+pub struct UserDataRepositoryImpl {
+    queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+    jobs: CqlRepository<ErasureJob>,
+    unfinished_jobs: CqlRepository<UnfinishedErasure>,
+}
+
+impl UserDataRepositoryImpl {
+    pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> UserDataRepositoryImpl {
+        UserDataRepositoryImpl {
+            jobs: CqlRepository::new(queriable.clone()),
+            unfinished_jobs: CqlRepository::new(queriable.clone()),
+            queriable
+        }
+    }
+
+    fn restriction(key: &PartitionKey) -> String {
+        key.iter()
+            .map(|(column, value)| format!("{} = {}", column, value))
+            .collect::<Vec<String>>()
+            .join(" and ")
+    }
+}
+
+#[async_trait]
+impl UserDataRepository for UserDataRepositoryImpl {
+    async fn get_rows(&self, table: &UserTable, key: &PartitionKey) -> Option<Vec<String>> {
+        let query = format!("SELECT JSON {} FROM {} WHERE {}", table.columns.join(", "), table.table, Self::restriction(key));
+
+        match self.queriable.execute_query(&format!("export_{}", table.name), &query).await {
+            Ok(result) => Some(result.rows.unwrap_or_default()
+                .into_typed::<(String,)>()
+                .map(|row| row.expect("Failed to extract JSON from Row").0)
+                .collect()),
+            Err(e) => {
+                println!("Failed to read {} of {:?} with error {:?}", table.table, key, e);
+                None
+            }
+        }
+    }
+
+    async fn delete_rows(&self, table: &UserTable, key: &PartitionKey) -> Option<()> {
+        let query = format!("DELETE FROM {} WHERE {}", table.table, Self::restriction(key));
+
+        match self.queriable.execute_query(&format!("erase_{}", table.name), &query).await {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("Failed to delete {} of {:?} with error {:?}", table.table, key, e);
+                None
+            }
+        }
+    }
+
+    async fn start_job(&self, job: &ErasureJob, lease_until_ms: i64) -> Option<()> {
+        let statements = vec!(
+            entity::insert_statement(job),
+            entity::insert_statement(&user_data_mapper::get_unfinished_erasure(job, lease_until_ms))
+        );
+
+        match self.queriable.execute_batch(BatchStatement::logged(statements).for_operation("start_erasure_job")).await.result {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("Failed to insert ErasureJob {:?} with error {:?}", job, e);
+                None
+            }
+        }
+    }
+
+    async fn save_job(&self, job: &ErasureJob) -> Option<()> {
+        let result = if job.status == ERASURE_COMPLETED || job.status == ERASURE_FAILED {
+            let statements = vec!(
+                entity::insert_statement(job),
+                entity::delete_statement::<UnfinishedErasure>(&(user_data::shard(job.job_id), job.job_id))
+            );
+            self.queriable.execute_batch(BatchStatement::logged(statements).for_operation("end_erasure_job")).await.result.map(|_| ())
+        } else {
+            self.jobs.insert(job).await.map(|_| ())
+        };
+
+        match result {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("Failed to save ErasureJob {:?} with error {:?}", job, e);
+                None
+            }
+        }
+    }
+
+    async fn get_job(&self, user_id: Uuid, job_id: Uuid) -> Option<ErasureJob> {
+        self.jobs.get(&(user_id, job_id)).await
+            .unwrap_or_else(|e| panic!("Failed to get ErasureJob {} of user {} with error {:?}", job_id, user_id, e))
+    }
+
+    async fn get_unfinished_jobs(&self, shard: i32, after: Option<UnfinishedErasure>, limit: usize) -> Option<Vec<UnfinishedErasure>> {
+        match self.unfinished_jobs.list_by_partition_page(&(shard,), after.map(|after| after.primary_key()).as_ref(), limit).await {
+            Ok(unfinished_jobs) => Some(unfinished_jobs),
+            Err(e) => {
+                println!("Failed to list UnfinishedErasures of shard {} with error {:?}", shard, e);
+                None
+            }
+        }
+    }
+
+    async fn lease_job(&self, unfinished: &UnfinishedErasure, lease_until_ms: i64) -> Option<bool> {
+        let query = format!("UPDATE {} SET lease_until_ms = {} WHERE shard = {} and job_id = {} IF lease_until_ms = {}",
+                            UnfinishedErasure::TABLE, lease_until_ms, unfinished.shard, unfinished.job_id, unfinished.lease_until_ms);
+
+        let outcome = self.queriable.execute_statement(Statement::non_idempotent(&query).for_operation("lease_erasure_job")).await;
+
+        match outcome.result {
+            Ok(query_result) => Some(cql::applied(&query_result)),
+            Err(e) => {
+                println!("Failed to lease UnfinishedErasure {:?} after {} retries with error {:?}", query, outcome.retries, e);
+                None
+            }
+        }
+    }
+
+    async fn delete_unfinished_job(&self, unfinished: &UnfinishedErasure) -> Option<()> {
+        match self.unfinished_jobs.delete(&unfinished.primary_key()).await {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("Failed to delete UnfinishedErasure {} with error {:?}", unfinished.job_id, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use scylla::QueryResult;
+    use scylla::frame::response::result::{CqlValue, Row};
+    use scylla::transport::errors::QueryError;
+
+    use crate::dao::session_manager::{BatchMode, QueryOutcome};
+    use crate::domain::user_data::{ERASURE_RUNNING, USER_TABLES};
+    use crate::repository::vehicle_repository::tests::MockSessionManagerImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn given_webhooks_when_get_rows_then_selects_partition_as_json_without_secret() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "export_webhooks" && query == fixture::EXPECTED_SELECT_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result());
+
+        let user_data_repository = UserDataRepositoryImpl::new(Arc::new(session_manager));
+
+        let webhooks = USER_TABLES.iter().find(|table| table.name == "webhooks").unwrap();
+        let rows = aw!(user_data_repository.get_rows(webhooks, &vec!(("user_id", fixture::user_id())))).unwrap();
+
+        assert_eq!(vec!(fixture::ROW.to_string()), rows);
+    }
+
+    #[test]
+    fn given_activities_when_delete_rows_then_deletes_partition_of_vehicle() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "erase_activities" && query == fixture::EXPECTED_DELETE_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result());
+
+        let user_data_repository = UserDataRepositoryImpl::new(Arc::new(session_manager));
+
+        let activities = USER_TABLES.iter().find(|table| table.name == "activities").unwrap();
+        let key = vec!(("user_id", fixture::user_id()), ("vehicle_id", fixture::vehicle_id()));
+
+        assert!(aw!(user_data_repository.delete_rows(activities, &key)).is_some());
+    }
+
+    #[test]
+    fn when_start_job_then_inserts_it_along_with_its_lease_in_logged_batch() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_batch()
+            .withf(|batch: &BatchStatement| batch.mode == BatchMode::Logged
+                && batch.statements.len() == 2
+                && batch.statements[0].starts_with("INSERT INTO vehicles.erasure_job ")
+                && batch.statements[1] == fixture::EXPECTED_START_UNFINISHED_STATEMENT)
+            .times(1)
+            .returning(move |_| QueryOutcome { result: Ok(QueryResult::default()), retries: 0 });
+
+        let user_data_repository = UserDataRepositoryImpl::new(Arc::new(session_manager));
+
+        assert!(aw!(user_data_repository.start_job(&fixture::job(ERASURE_RUNNING), 5000)).is_some());
+    }
+
+    #[test]
+    fn given_completed_job_when_save_job_then_removes_its_lease_in_logged_batch() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_batch()
+            .withf(|batch: &BatchStatement| batch.mode == BatchMode::Logged
+                && batch.statements.len() == 2
+                && batch.statements[0].starts_with("INSERT INTO vehicles.erasure_job ") && batch.statements[0].contains("'completed'")
+                && batch.statements[1] == fixture::EXPECTED_DELETE_UNFINISHED_STATEMENT)
+            .times(1)
+            .returning(move |_| QueryOutcome { result: Ok(QueryResult::default()), retries: 0 });
+
+        let user_data_repository = UserDataRepositoryImpl::new(Arc::new(session_manager));
+
+        assert!(aw!(user_data_repository.save_job(&fixture::job(ERASURE_COMPLETED))).is_some());
+    }
+
+    #[test]
+    fn given_lease_taken_over_when_lease_job_then_returns_false() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_LEASE_QUERY && !statement.idempotent)
+            .times(1)
+            .returning(move |_| QueryOutcome {
+                result: Ok(QueryResult {
+                    rows: Some(vec!(Row { columns: vec!(Some(CqlValue::Boolean(false)), Some(CqlValue::BigInt(9000))) })),
+                    warnings: vec!(),
+                    tracing_id: None,
+                    paging_state: None
+                }),
+                retries: 0
+            });
+
+        let user_data_repository = UserDataRepositoryImpl::new(Arc::new(session_manager));
+
+        let unfinished = user_data_mapper::get_unfinished_erasure(&fixture::job(ERASURE_RUNNING), 5000);
+
+        assert_eq!(Some(false), aw!(user_data_repository.lease_job(&unfinished, 65000)));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "d13fe953-297a-4781-807a-f9becc1b71f6";
+        pub const VEHICLE_ID_STR: &str = "60e18f00-34b8-4a52-916c-adbb0204618e";
+        pub const ROW: &str = "{\"user_id\": \"d13fe953-297a-4781-807a-f9becc1b71f6\", \"url\": \"https://example.com/hook\"}";
+
+        pub const EXPECTED_SELECT_QUERY: &str = "SELECT JSON user_id, webhook_id, url, events, created_at \
+            FROM vehicles.webhook WHERE user_id = d13fe953-297a-4781-807a-f9becc1b71f6";
+        pub const EXPECTED_DELETE_QUERY: &str = "DELETE FROM vehicles.activity \
+            WHERE user_id = d13fe953-297a-4781-807a-f9becc1b71f6 and vehicle_id = 60e18f00-34b8-4a52-916c-adbb0204618e";
+        pub const JOB_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const EXPECTED_START_UNFINISHED_STATEMENT: &str = "INSERT INTO vehicles.unfinished_erasure (shard, job_id, user_id, lease_until_ms) \
+            VALUES (0, 88573010-cf4c-490e-9d29-f8517dc60b90, d13fe953-297a-4781-807a-f9becc1b71f6, 5000)";
+        pub const EXPECTED_DELETE_UNFINISHED_STATEMENT: &str = "DELETE FROM vehicles.unfinished_erasure \
+            WHERE shard = 0 and job_id = 88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const EXPECTED_LEASE_QUERY: &str = "UPDATE vehicles.unfinished_erasure SET lease_until_ms = 65000 \
+            WHERE shard = 0 and job_id = 88573010-cf4c-490e-9d29-f8517dc60b90 IF lease_until_ms = 5000";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn job(status: &str) -> ErasureJob {
+            ErasureJob {
+                user_id: user_id(),
+                job_id: Uuid::parse_str(JOB_ID_STR).unwrap(),
+                status: status.to_string(),
+                erased: vec!(),
+                error: None,
+                requested_at_ms: 1000,
+                updated_at_ms: 2000
+            }
+        }
+
+        pub fn create_query_result() -> Result<QueryResult, QueryError> {
+            Ok(QueryResult {
+                rows: Some(vec!(Row { columns: vec!(Some(CqlValue::Text(ROW.to_string()))) })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            })
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+use scylla::IntoTypedRows;
+
+use rocket::serde::uuid::Uuid;
+
+use crate::dao::session_manager::SessionManager;
+use crate::domain::vehicle_history::VehicleVersion;
+use crate::repository::cql_repository::CqlRepository;
+use crate::repository::entity::Entity;
+
+/// Reads the history of the vehicles, whose versions are only ever written by the vehicle repository,
+/// in the same logged batch as the save they are about.
+#[async_trait]
+pub trait VehicleHistoryRepository {
+    /// Latest versions of a vehicle, newest first, `None` when they could not be read.
+    async fn get_versions(&self, user_id: Uuid, vehicle_id: Uuid, limit: usize) -> Option<Vec<VehicleVersion>>;
+    async fn get_version(&self, user_id: Uuid, vehicle_id: Uuid, version: Uuid) -> Option<VehicleVersion>;
+    /// Latest version saved at or before `as_of_ms`, in milliseconds since the epoch.
+    async fn get_version_as_of(&self, user_id: Uuid, vehicle_id: Uuid, as_of_ms: i64) -> Option<VehicleVersion>;
+}
+
+pub struct VehicleHistoryRepositoryImpl {
+    queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+    versions: CqlRepository<VehicleVersion>,
+}
+
+impl VehicleHistoryRepositoryImpl {
+    pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> VehicleHistoryRepositoryImpl {
+        VehicleHistoryRepositoryImpl {
+            queriable: queriable.clone(),
+            versions: CqlRepository::new(queriable)
+        }
+    }
+}
+
+#[async_trait]
+impl VehicleHistoryRepository for VehicleHistoryRepositoryImpl {
+    async fn get_versions(&self, user_id: Uuid, vehicle_id: Uuid, limit: usize) -> Option<Vec<VehicleVersion>> {
+        match self.versions.list_by_partition_page(&(user_id, vehicle_id), None, limit).await {
+            Ok(versions) => Some(versions),
+            Err(e) => {
+                println!("Failed to list VehicleVersions of Vehicle {} of user {} with error {:?}", vehicle_id, user_id, e);
+                None
+            }
+        }
+    }
+
+    async fn get_version(&self, user_id: Uuid, vehicle_id: Uuid, version: Uuid) -> Option<VehicleVersion> {
+        self.versions.get(&(user_id, vehicle_id, version)).await
+            .unwrap_or_else(|e| panic!("Failed to get VehicleVersion {} of Vehicle {} of user {} with error {:?}", version, vehicle_id, user_id, e))
+    }
+
+    async fn get_version_as_of(&self, user_id: Uuid, vehicle_id: Uuid, as_of_ms: i64) -> Option<VehicleVersion> {
+        // Versions are clustered newest first, so the first one not after `as_of_ms` is the one in effect then.
+        let query = format!("SELECT {} FROM {} WHERE user_id = {} and vehicle_id = {} and version <= maxTimeuuid({}) LIMIT 1",
+                            VehicleVersion::COLUMNS.join(", "), VehicleVersion::TABLE, user_id, vehicle_id, as_of_ms);
+
+        let result = self.queriable.execute_query("get_vehicle_history_as_of", &query).await;
+
+        result
+            .unwrap_or_else(|e| panic!("Failed to execute query {} with error {:?}", query, e))
+            .rows
+            .unwrap_or_default()
+            .into_typed::<VehicleVersion>()
+            .map(|row| row.expect("Failed to extract VehicleVersion from Row"))
+            .next()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use scylla::QueryResult;
+    use scylla::frame::response::result::{CqlValue, Row};
+    use scylla::transport::errors::QueryError;
+
+    use crate::repository::vehicle_repository::tests::MockSessionManagerImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn when_get_versions_then_reads_latest_versions_of_vehicle() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_vehicle_history" && query == fixture::EXPECTED_LIST_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result());
+
+        let history_repository = VehicleHistoryRepositoryImpl::new(Arc::new(session_manager));
+
+        let versions = aw!(history_repository.get_versions(fixture::user_id(), fixture::vehicle_id(), 20)).unwrap();
+
+        assert_eq!(1, versions.len());
+        assert_eq!((fixture::version(), 5000, "the actor".to_string()), (versions[0].version, versions[0].saved_at_ms, versions[0].actor.clone()));
+        assert_eq!(Some(fixture::restored_from()), versions[0].restored_from);
+    }
+
+    #[test]
+    fn when_get_version_as_of_then_reads_latest_version_not_after_it() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "get_vehicle_history_as_of" && query == fixture::EXPECTED_AS_OF_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result());
+
+        let history_repository = VehicleHistoryRepositoryImpl::new(Arc::new(session_manager));
+
+        let version = aw!(history_repository.get_version_as_of(fixture::user_id(), fixture::vehicle_id(), 7000)).unwrap();
+
+        assert_eq!(fixture::version(), version.version);
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+
+        pub const VERSION_STR: &str = "5d3c4e80-4f9a-11ec-8c4f-2f6b1a7d9e01";
+        pub const RESTORED_FROM_STR: &str = "3b1f2a40-4f9a-11ec-9d2e-7a1c5b3f8e02";
+
+        pub const EXPECTED_LIST_QUERY: &str = "SELECT user_id, vehicle_id, version, saved_at_ms, actor, event, diff, vehicle, restored_from \
+            FROM vehicles.vehicle_history \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90 LIMIT 20";
+        pub const EXPECTED_AS_OF_QUERY: &str = "SELECT user_id, vehicle_id, version, saved_at_ms, actor, event, diff, vehicle, restored_from \
+            FROM vehicles.vehicle_history \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90 and version <= maxTimeuuid(7000) LIMIT 1";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn version() -> Uuid {
+            Uuid::parse_str(VERSION_STR).unwrap()
+        }
+
+        pub fn restored_from() -> Uuid {
+            Uuid::parse_str(RESTORED_FROM_STR).unwrap()
+        }
+
+        pub fn create_query_result() -> Result<QueryResult, QueryError> {
+            let cql_values = vec!(
+                Some(CqlValue::Uuid(user_id())),
+                Some(CqlValue::Uuid(vehicle_id())),
+                Some(CqlValue::Timeuuid(version())),
+                Some(CqlValue::BigInt(5000)),
+                Some(CqlValue::Text("the actor".to_string())),
+                Some(CqlValue::Text("updated".to_string())),
+                Some(CqlValue::Text("{}".to_string())),
+                Some(CqlValue::Text("{}".to_string())),
+                Some(CqlValue::Timeuuid(restored_from())));
+
+            Ok(QueryResult {
+                rows: Some(vec!(Row { columns: cql_values })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            })
+        }
+    }
+}
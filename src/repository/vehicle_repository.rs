@@ -1,81 +1,256 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use scylla::IntoTypedRows;
 
 use rocket::serde::uuid::Uuid;
-
-use crate::dao::session_manager::SessionManager;
-use crate::domain::vehicle::Vehicle;
-
-use chrono::{Utc, TimeZone};
+use chrono::{Duration, NaiveDate};
+use scylla::cql_to_rust::{FromCqlVal, FromCqlValError};
+use scylla::frame::response::result::Row;
+use scylla::transport::errors::QueryError;
+
+use crate::dao::session_manager::{BatchStatement, SessionManager, Statement};
+use crate::domain::vehicle::{Vehicle, VehicleProjection};
+use crate::domain::vehicle_history::VehicleChange;
+use crate::domain::vehicle_lookup::VehicleLookup;
+use crate::mapper::outbox_mapper;
+use crate::repository::cql_repository::CqlRepository;
+use crate::repository::cql;
+use crate::repository::entity::{self, Entity};
 
 #[async_trait]
 pub trait VehicleRepository {
     async fn get_vehicle(&self, user_id: Uuid, vehicle_id: Uuid) -> Option<Vehicle>;
-    async fn save_vehicle(&self, vehicle: Vehicle) -> Option<Vehicle>;
+    /// The `columns` of a vehicle, reading only those.
+    async fn get_vehicle_projection(&self, user_id: Uuid, vehicle_id: Uuid, columns: Vec<&'static str>) -> Option<VehicleProjection>;
+    /// Saves a vehicle together with the outbox entry of the event the save is and the version it adds to
+    /// the history of the vehicle.
+    async fn save_vehicle(&self, vehicle: Vehicle, change: VehicleChange) -> Option<Vehicle>;
+    /// Sets the distance of a vehicle with a compare-and-set on that column alone, so that a concurrent save of its
+    /// other columns is kept, then records the change in the outbox and history. `Some(false)` means the distance
+    /// changed since `previous` was read.
+    async fn update_distance(&self, previous: &Vehicle, vehicle: Vehicle, change: VehicleChange) -> Option<bool>;
+    /// Saves vehicles of the same user, each with its change, all of them or none. Callers keep the vehicles under
+    /// the batch size limit with `batch_bytes`.
+    async fn save_vehicles(&self, vehicles: Vec<(Vehicle, VehicleChange)>) -> Option<Vec<Vehicle>>;
+    /// Page of the vehicles of a user, or of every user, following the vehicle keyed by `after`.
+    async fn get_vehicles_page(&self, user_id: Option<Uuid>, after: Option<(Uuid, Uuid)>, limit: usize) -> Option<Vec<Vehicle>>;
+    /// Page of the `columns` of the vehicles of a user, following the vehicle keyed by `after`.
+    async fn get_vehicles_projection_page(&self, user_id: Uuid, after: Option<(Uuid, Uuid)>, limit: usize, columns: Vec<&'static str>) -> Option<Vec<VehicleProjection>>;
+    /// Keys of the vehicles indexed under `value` of a searchable attribute, following the key `after`.
+    async fn find_vehicle_keys(&self, attribute: &str, value: &str, after: Option<(Uuid, Uuid)>, limit: usize) -> Option<Vec<(Uuid, Uuid)>>;
+    /// Removes a lookup row no longer matching its vehicle, `None` when it could not be removed.
+    async fn remove_vehicle_key(&self, attribute: &str, value: &str, user_id: Uuid, vehicle_id: Uuid) -> Option<()>;
+    /// Drops any cached copy of a vehicle written outside of this repository.
+    async fn evict_vehicle(&self, user_id: Uuid, vehicle_id: Uuid);
 }
 
+/// Keeps `vehicles.vehicle_lookup` in step with the vehicles: lookup rows are written before the vehicle
+/// they point to, so a saved vehicle can always be found, while rows left behind by a changed attribute,
+/// a transfer or a failed save are filtered out and removed by the search reading them.
+///
+/// Vehicles are written in a logged batch with the outbox entries of their events and their versions, so that
+/// an event is relayed and a version kept if and only if its change was saved.
 pub struct VehicleRepositoryImpl {
     queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+    vehicles: CqlRepository<Vehicle>,
+    lookups: CqlRepository<VehicleLookup>,
 }
 
 impl VehicleRepositoryImpl {
     pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> VehicleRepositoryImpl {
         VehicleRepositoryImpl {
-            queriable
+            queriable: queriable.clone(),
+            vehicles: CqlRepository::new(queriable.clone()),
+            lookups: CqlRepository::new(queriable)
+        }
+    }
+
+    /// Inserts lookup rows in one unlogged batch per lookup partition, a batch spanning partitions costing its
+    /// coordinator more than the round trips it saves.
+    async fn insert_lookups(&self, lookups: Vec<VehicleLookup>) -> Result<(), QueryError> {
+        let mut partitions: BTreeMap<(String, String), Vec<VehicleLookup>> = BTreeMap::new();
+        for lookup in lookups {
+            partitions.entry((lookup.attribute.clone(), lookup.value.clone())).or_default().push(lookup);
+        }
+
+        for partition in partitions.values() {
+            self.lookups.insert_batch(partition).await?;
         }
+        Ok(())
+    }
+
+    /// Inserts the vehicles, the outbox entries of their events and their versions in a single logged batch.
+    async fn insert_with_changes(&self, operation: &str, vehicles: &[Vehicle], changes: &[VehicleChange]) -> Result<(), QueryError> {
+        let mut statements: Vec<String> = vehicles.iter().map(entity::insert_statement).collect();
+        statements.extend(change_statements(vehicles, changes));
+
+        self.queriable.execute_batch(BatchStatement::logged(statements).for_operation(operation)).await.result.map(|_| ())
     }
 }
 
 #[async_trait]
 impl VehicleRepository for VehicleRepositoryImpl {
     async fn get_vehicle(&self, user_id: Uuid, vehicle_id: Uuid) ->  Option<Vehicle> {
-        let query = format!("SELECT name, user_id, vehicle_id, created_at, vehicle_type, retired_at, brand, model, distance, owner_since, manufacturing_date, picture \
-            FROM vehicles.vehicle \
-            WHERE user_id = {} and vehicle_id = {}", user_id, vehicle_id);
-
-        let result = self.queriable.execute_query(&query).await;
-
-        if let Some(rows) = result
-            .expect(&format!("Failed to execute query {}", query))
-            .rows {
-                for row in rows.into_typed::<Vehicle>() {
-                    let vehicle: Vehicle = row.expect("Failed to extract Vehicle from Row");
-                    return Some(vehicle);
-                }
-            };
+        self.vehicles.get(&(user_id, vehicle_id)).await
+            .unwrap_or_else(|e| panic!("Failed to get Vehicle {} of user {} with error {:?}", vehicle_id, user_id, e))
+    }
 
-        None
-    }
-
-    async fn save_vehicle(&self, vehicle: Vehicle) -> Option<Vehicle> {
-        let query = format!("INSERT INTO vehicles.vehicle   (user_id,       \
-                                            vehicle_id,    \
-                                            vehicle_type,  \
-                                            name,          \
-                                            created_at,    \
-                                            retired_at,    \
-                                            brand,         \
-                                            model,         \
-                                            distance,      \
-                                            owner_since,   \
-                                            manufacturing_date, \
-                                            picture) VALUES ({}, {}, '{}', '{}', '{}', {}, '{}', '{}', {}, '{}', '{}', {})",
-                            vehicle.user_id, vehicle.vehicle_id, vehicle.vehicle_type, vehicle.name, Utc.timestamp(vehicle.created_at.num_seconds(), 0),
-                            vehicle.retired_at.map(|d| format!("'{}'", Utc.timestamp(d.num_seconds(), 0))).unwrap_or_else(|| "null".to_string()),
-                            vehicle.brand, vehicle.model, vehicle.distance, vehicle.owner_since, vehicle.manufacturing_date,
-                            vehicle.picture.as_ref().map(|p| format!("'{}'", p)).unwrap_or_else(|| "null".to_string())
-        );
-
-        let result = self.queriable.execute_query(&query).await;
-
-        match result {
+    async fn get_vehicle_projection(&self, user_id: Uuid, vehicle_id: Uuid, columns: Vec<&'static str>) -> Option<VehicleProjection> {
+        self.vehicles.get_columns(&(user_id, vehicle_id), &columns).await
+            .unwrap_or_else(|e| panic!("Failed to get Vehicle {} of user {} with error {:?}", vehicle_id, user_id, e))
+            .map(|row| projection(&columns, row))
+    }
+
+    async fn save_vehicle(&self, vehicle: Vehicle, change: VehicleChange) -> Option<Vehicle> {
+        if let Err(e) = self.insert_lookups(VehicleLookup::of(&vehicle)).await {
+            println!("Failed to index Vehicle {:?} with error {:?}", vehicle, e);
+            return None;
+        }
+
+        match self.insert_with_changes("insert_vehicle", std::slice::from_ref(&vehicle), &[change]).await {
             Ok(_) => Some(vehicle),
             Err(e) => {
-                println!("Failed to insert Vehicle {:?} with error {:?}", query, e);
+                println!("Failed to insert Vehicle {:?} with error {:?}", vehicle, e);
+                None
+            }
+        }
+    }
+
+    /// The compare-and-set cannot share a batch with the other tables, so a failure to record the change after it
+    /// applied leaves the new distance without its event and version, which is logged.
+    async fn update_distance(&self, previous: &Vehicle, vehicle: Vehicle, change: VehicleChange) -> Option<bool> {
+        let query = format!("UPDATE {} SET distance = {} WHERE user_id = {} and vehicle_id = {} IF distance = {}",
+                            Vehicle::TABLE, vehicle.distance, vehicle.user_id, vehicle.vehicle_id, previous.distance);
+
+        let outcome = self.queriable.execute_statement(Statement::non_idempotent(&query).for_operation("update_vehicle_distance")).await;
+
+        match outcome.result {
+            Ok(query_result) if !cql::applied(&query_result) => Some(false),
+            Ok(_) => {
+                let statements = change_statements(std::slice::from_ref(&vehicle), &[change]);
+                if let Err(e) = self.queriable.execute_batch(BatchStatement::logged(statements).for_operation("record_vehicle_distance")).await.result {
+                    println!("Failed to record distance change of Vehicle {} with error {:?}", vehicle.vehicle_id, e);
+                }
+                Some(true)
+            },
+            Err(e) => {
+                println!("Failed to update distance {:?} after {} retries with error {:?}", query, outcome.retries, e);
+                None
+            }
+        }
+    }
+
+    async fn save_vehicles(&self, vehicles: Vec<(Vehicle, VehicleChange)>) -> Option<Vec<Vehicle>> {
+        let (vehicles, changes): (Vec<Vehicle>, Vec<VehicleChange>) = vehicles.into_iter().unzip();
+        let lookups: Vec<VehicleLookup> = vehicles.iter().flat_map(VehicleLookup::of).collect();
+
+        if let Err(e) = self.insert_lookups(lookups).await {
+            println!("Failed to index {} Vehicles with error {:?}", vehicles.len(), e);
+            return None;
+        }
+
+        match self.insert_with_changes("insert_vehicle_batch", &vehicles, &changes).await {
+            Ok(_) => Some(vehicles),
+            Err(e) => {
+                println!("Failed to insert {} Vehicles with error {:?}", vehicles.len(), e);
+                None
+            }
+        }
+    }
+
+    async fn get_vehicles_page(&self, user_id: Option<Uuid>, after: Option<(Uuid, Uuid)>, limit: usize) -> Option<Vec<Vehicle>> {
+        let page = match user_id {
+            Some(user_id) => self.vehicles.list_by_partition_page(&(user_id,), after.as_ref(), limit).await,
+            None => self.vehicles.scan_page(after.as_ref(), limit).await
+        };
+
+        match page {
+            Ok(vehicles) => Some(vehicles),
+            Err(e) => {
+                println!("Failed to get Vehicles after {:?} with error {:?}", after, e);
+                None
+            }
+        }
+    }
+
+    async fn get_vehicles_projection_page(&self, user_id: Uuid, after: Option<(Uuid, Uuid)>, limit: usize, columns: Vec<&'static str>) -> Option<Vec<VehicleProjection>> {
+        match self.vehicles.list_by_partition_page_columns(&(user_id,), after.as_ref(), limit, &columns).await {
+            Ok(rows) => Some(rows.into_iter().map(|row| projection(&columns, row)).collect()),
+            Err(e) => {
+                println!("Failed to get Vehicles of user {} after {:?} with error {:?}", user_id, after, e);
+                None
+            }
+        }
+    }
+
+    async fn find_vehicle_keys(&self, attribute: &str, value: &str, after: Option<(Uuid, Uuid)>, limit: usize) -> Option<Vec<(Uuid, Uuid)>> {
+        let partition = (attribute.to_string(), value.to_string());
+        let after = after.map(|(user_id, vehicle_id)| (attribute.to_string(), value.to_string(), user_id, vehicle_id));
+
+        match self.lookups.list_by_partition_page(&partition, after.as_ref(), limit).await {
+            Ok(lookups) => Some(lookups.into_iter().map(|lookup| (lookup.user_id, lookup.vehicle_id)).collect()),
+            Err(e) => {
+                println!("Failed to get Vehicles with {} {:?} with error {:?}", attribute, value, e);
+                None
+            }
+        }
+    }
+
+    async fn remove_vehicle_key(&self, attribute: &str, value: &str, user_id: Uuid, vehicle_id: Uuid) -> Option<()> {
+        match self.lookups.delete(&(attribute.to_string(), value.to_string(), user_id, vehicle_id)).await {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("Failed to remove stale {} {:?} of Vehicle {} with error {:?}", attribute, value, vehicle_id, e);
                 None
             }
         }
     }
+
+    async fn evict_vehicle(&self, _user_id: Uuid, _vehicle_id: Uuid) {}
+}
+
+/// Bytes a vehicle and its change add to the logged batch of `save_vehicles`, for callers to keep batches under
+/// the `batch_size_fail_threshold_in_kb` of Cassandra.
+pub fn batch_bytes(vehicle: &Vehicle, change: &VehicleChange) -> usize {
+    entity::insert_statement(vehicle).len() + change_statements(std::slice::from_ref(vehicle), std::slice::from_ref(change)).iter()
+        .map(String::len)
+        .sum::<usize>()
+}
+
+/// Outbox entries of the events of the changes and the versions they add.
+fn change_statements(vehicles: &[Vehicle], changes: &[VehicleChange]) -> Vec<String> {
+    let mut statements: Vec<String> = vehicles.iter().zip(changes).map(|(vehicle, change)|
+        entity::insert_statement(&outbox_mapper::get_outbox_entry(change.event, vehicle.user_id, vehicle.vehicle_id, Some(vehicle)))).collect();
+    statements.extend(changes.iter().map(|change| entity::insert_statement(&change.version)));
+    statements
+}
+
+/// Reads a row holding `columns`, in that order, panicking like `get_vehicle` on a column of an unexpected type.
+fn projection(columns: &[&str], row: Row) -> VehicleProjection {
+    read_projection(columns, row).unwrap_or_else(|e| panic!("Failed to extract Vehicle columns {:?} from Row: {:?}", columns, e))
+}
+
+fn read_projection(columns: &[&str], row: Row) -> Result<VehicleProjection, FromCqlValError> {
+    let mut projection = VehicleProjection::default();
+
+    for (column, value) in columns.iter().zip(row.columns) {
+        match *column {
+            "name" => projection.name = Some(String::from_cql(value)?),
+            "user_id" => projection.user_id = Some(Uuid::from_cql(value)?),
+            "vehicle_id" => projection.vehicle_id = Some(Uuid::from_cql(value)?),
+            "created_at" => projection.created_at = Some(Duration::from_cql(value)?),
+            "vehicle_type" => projection.vehicle_type = Some(String::from_cql(value)?),
+            "retired_at" => projection.retired_at = Some(Option::<Duration>::from_cql(value)?),
+            "brand" => projection.brand = Some(String::from_cql(value)?),
+            "model" => projection.model = Some(String::from_cql(value)?),
+            "distance" => projection.distance = Some(i32::from_cql(value)?),
+            "owner_since" => projection.owner_since = Some(NaiveDate::from_cql(value)?),
+            "manufacturing_date" => projection.manufacturing_date = Some(NaiveDate::from_cql(value)?),
+            "picture" => projection.picture = Some(Option::<String>::from_cql(value)?),
+            _ => {}
+        }
+    }
+
+    Ok(projection)
 }
 
 #[cfg(test)]
@@ -84,10 +259,11 @@ pub mod tests {
     use scylla::QueryResult;
     use scylla::transport::errors::QueryError;
     use scylla::frame::response::result::CqlValue;
-    use chrono::{Duration, NaiveDate};
 
     use mockall::mock;
-    use scylla::cql_to_rust::FromCqlVal;
+    use crate::dao::session_manager::{BatchMode, BatchStatement, QueryOutcome, Statement};
+    use crate::domain::vehicle_event::VehicleEventKind;
+    use crate::domain::vehicle_history::VehicleVersion;
 
     macro_rules! aw {
         ($e: expr) => {
@@ -96,11 +272,13 @@ pub mod tests {
     }
 
     mock! {
-        SessionManagerImpl {}
+        pub SessionManagerImpl {}
 
         #[async_trait]
         impl SessionManager for SessionManagerImpl {
-            async fn execute_query(&self, query_statement: &str) -> Result<QueryResult, QueryError>;
+            async fn execute_query(&self, operation: &str, query_statement: &str) -> Result<QueryResult, QueryError>;
+            async fn execute_statement(&self, statement: Statement) -> QueryOutcome;
+            async fn execute_batch(&self, batch: BatchStatement) -> QueryOutcome;
         }
     }
 
@@ -112,9 +290,9 @@ pub mod tests {
         let vehicle_id = Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap();
 
         session_manager.expect_execute_query()
-            .withf(|query: &str| query == fixture::EXPECTED_QUERY)
+            .withf(|operation: &str, query: &str| operation == "get_vehicle" && query == fixture::EXPECTED_QUERY)
             .times(1)
-            .returning(move |_| fixture::create_query_result(CqlValue::Text(fixture::EXPECTED_VEHICLE_NAME.to_string())));
+            .returning(move |_, _| fixture::create_query_result(CqlValue::Text(fixture::EXPECTED_VEHICLE_NAME.to_string())));
 
         let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
 
@@ -142,9 +320,9 @@ pub mod tests {
         let vehicle_id = Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap();
 
         session_manager.expect_execute_query()
-            .withf(|query: &str| query == fixture::EXPECTED_QUERY)
+            .withf(|_, query: &str| query == fixture::EXPECTED_QUERY)
             .times(1)
-            .returning(move |_| Ok(QueryResult::default()));
+            .returning(move |_, _| Ok(QueryResult::default()));
 
         let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
 
@@ -162,9 +340,9 @@ pub mod tests {
         let vehicle_id = Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap();
 
         session_manager.expect_execute_query()
-        .withf(|query: &str| query == fixture::EXPECTED_QUERY)
+        .withf(|_, query: &str| query == fixture::EXPECTED_QUERY)
             .times(1)
-            .returning(move |_| Err(QueryError::InvalidMessage("error".to_owned())));
+            .returning(move |_, _| Err(QueryError::InvalidMessage("error".to_owned())));
 
         let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
 
@@ -180,9 +358,9 @@ pub mod tests {
         let vehicle_id = Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap();
 
         session_manager.expect_execute_query()
-        .withf(|query: &str| query == fixture::EXPECTED_QUERY)
+        .withf(|_, query: &str| query == fixture::EXPECTED_QUERY)
             .times(1)
-            .returning(move |_| fixture::create_query_result(CqlValue::Int(7)));
+            .returning(move |_, _| fixture::create_query_result(CqlValue::Int(7)));
 
         let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
 
@@ -193,10 +371,12 @@ pub mod tests {
     fn when_save_vehicle_then_returns_vehicle() {
         let mut session_manager = MockSessionManagerImpl::new();
 
-        session_manager.expect_execute_query()
-            .withf(|query: &str| query == fixture::EXPECTED_SAVE_QUERY)
+        fixture::expect_lookup_batches(&mut session_manager, 2, 1);
+
+        session_manager.expect_execute_batch()
+            .withf(|batch: &BatchStatement| fixture::is_vehicle_batch(batch, "insert_vehicle", &[fixture::EXPECTED_SAVE_QUERY], "retired"))
             .times(1)
-            .returning(move |_| fixture::create_query_result(CqlValue::Text(fixture::EXPECTED_VEHICLE_NAME.to_string())));
+            .returning(move |_| QueryOutcome { result: Ok(QueryResult::default()), retries: 0 });
 
         let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
 
@@ -213,19 +393,75 @@ pub mod tests {
             owner_since         : NaiveDate::from_num_days_from_ce(15),
             manufacturing_date  : NaiveDate::from_num_days_from_ce(15),
             picture             : Some(fixture::EXPECTED_PICTURE.to_string())
-        })).unwrap();
+        }, fixture::change(VehicleEventKind::Retired))).unwrap();
 
         assert_eq!(fixture::EXPECTED_VEHICLE_NAME, vehicle.name);
     }
 
+    #[test]
+    fn given_no_picture_when_save_vehicle_then_picture_column_is_not_written() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        fixture::expect_lookup_batches(&mut session_manager, 2, 1);
+
+        session_manager.expect_execute_batch()
+            .withf(|batch: &BatchStatement| fixture::is_vehicle_batch(batch, "insert_vehicle", &[fixture::EXPECTED_SAVE_QUERY_WITHOUT_PICTURE], "created"))
+            .times(1)
+            .returning(move |_| QueryOutcome { result: Ok(QueryResult::default()), retries: 0 });
+
+        let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
+
+        let vehicle = aw!(vehicle_repository.save_vehicle(Vehicle {
+            user_id             : Uuid::parse_str(fixture::USER_ID_STR).unwrap(),
+            vehicle_id          : Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap(),
+            vehicle_type        : fixture::EXPECTED_VEHICLE_TYPE.to_string(),
+            name                : fixture::EXPECTED_VEHICLE_NAME.to_string(),
+            created_at          : Duration::seconds(fixture::EXPECTED_CREATED_AT),
+            retired_at          : None,
+            brand               : fixture::EXPECTED_BRAND.to_string(),
+            model               : fixture::EXPECTED_MODEL.to_string(),
+            distance            : fixture::EXPECTED_DISTANCE,
+            owner_since         : NaiveDate::from_num_days_from_ce(15),
+            manufacturing_date  : NaiveDate::from_num_days_from_ce(15),
+            picture             : None
+        }, fixture::change(VehicleEventKind::Created)));
+
+        assert!(vehicle.unwrap().picture.is_none());
+    }
+
+    #[test]
+    fn given_distance_changed_since_read_when_update_distance_then_returns_false_without_recording_change() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_UPDATE_DISTANCE_QUERY && !statement.idempotent)
+            .times(1)
+            .returning(move |_| QueryOutcome {
+                result: Ok(QueryResult { rows: Some(vec!(Row { columns: vec!(Some(CqlValue::Boolean(false))) })), ..QueryResult::default() }),
+                retries: 0
+            });
+        session_manager.expect_execute_batch()
+            .times(0);
+
+        let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
+
+        let previous = fixture::vehicle();
+        let vehicle = Vehicle { distance: previous.distance + 3, ..previous.clone() };
+        let updated = aw!(vehicle_repository.update_distance(&previous, vehicle, fixture::change(VehicleEventKind::Updated)));
+
+        assert_eq!(Some(false), updated);
+    }
+
     #[test]
     fn given_error_when_save_vehicle_then_returns_none() {
         let mut session_manager = MockSessionManagerImpl::new();
 
-        session_manager.expect_execute_query()
-        .withf(|query: &str| query == fixture::EXPECTED_SAVE_QUERY)
+        fixture::expect_lookup_batches(&mut session_manager, 2, 1);
+
+        session_manager.expect_execute_batch()
+            .withf(|batch: &BatchStatement| fixture::is_vehicle_batch(batch, "insert_vehicle", &[fixture::EXPECTED_SAVE_QUERY], "retired"))
             .times(1)
-            .returning(move |_| Err(QueryError::InvalidMessage("error".to_owned())));
+            .returning(move |_| QueryOutcome { result: Err(QueryError::InvalidMessage("error".to_owned())), retries: 0 });
 
         let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
 
@@ -242,11 +478,170 @@ pub mod tests {
             owner_since         : NaiveDate::from_num_days_from_ce(15),
             manufacturing_date  : NaiveDate::from_num_days_from_ce(15),
             picture             : Some(fixture::EXPECTED_PICTURE.to_string())
-        }));
+        }, fixture::change(VehicleEventKind::Retired)));
 
         assert!(vehicle.is_none());
     }
 
+    #[test]
+    fn given_error_when_indexing_vehicle_then_vehicle_is_not_saved() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_batch()
+            .times(1)
+            .returning(move |_| QueryOutcome { result: Err(QueryError::InvalidMessage("error".to_owned())), retries: 0 });
+
+        session_manager.expect_execute_query()
+            .times(0);
+
+        let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
+
+        assert!(aw!(vehicle_repository.save_vehicle(fixture::vehicle(), fixture::change(VehicleEventKind::Created))).is_none());
+    }
+
+    #[test]
+    fn when_save_vehicles_then_inserts_them_with_their_events_in_a_logged_batch() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        fixture::expect_lookup_batches(&mut session_manager, 2, 2);
+
+        session_manager.expect_execute_batch()
+            .withf(|batch: &BatchStatement| fixture::is_vehicle_batch(batch, "insert_vehicle_batch",
+                &[fixture::EXPECTED_SAVE_QUERY_WITHOUT_PICTURE, fixture::EXPECTED_SAVE_QUERY_WITHOUT_PICTURE], "updated"))
+            .times(1)
+            .returning(move |_| QueryOutcome { result: Ok(QueryResult::default()), retries: 0 });
+
+        let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
+
+        let vehicles = aw!(vehicle_repository.save_vehicles(vec!((fixture::vehicle(), fixture::change(VehicleEventKind::Updated)), (fixture::vehicle(), fixture::change(VehicleEventKind::Updated)))));
+
+        assert_eq!(2, vehicles.unwrap().len());
+    }
+
+    #[test]
+    fn given_error_when_save_vehicles_then_returns_none() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_batch()
+            .times(1)
+            .returning(move |_| QueryOutcome { result: Err(QueryError::InvalidMessage("error".to_owned())), retries: 0 });
+
+        let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
+
+        assert!(aw!(vehicle_repository.save_vehicles(vec!((fixture::vehicle(), fixture::change(VehicleEventKind::Created))))).is_none());
+    }
+
+    #[test]
+    fn given_last_vehicle_of_a_page_when_get_vehicles_page_then_continues_with_next_partitions() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "scan_vehicle" && query == fixture::EXPECTED_PARTITION_REST_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "scan_vehicle" && query == fixture::EXPECTED_NEXT_PARTITIONS_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result(CqlValue::Text(fixture::EXPECTED_VEHICLE_NAME.to_string())));
+
+        let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
+
+        let after = (Uuid::parse_str(fixture::USER_ID_STR).unwrap(), Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap());
+        let vehicles = aw!(vehicle_repository.get_vehicles_page(None, Some(after), 2)).unwrap();
+
+        assert_eq!(1, vehicles.len());
+    }
+
+    #[test]
+    fn given_user_when_get_vehicles_page_then_pages_through_its_partition() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_vehicle" && query == format!("{} LIMIT 2", fixture::EXPECTED_LIST_QUERY))
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result(CqlValue::Text(fixture::EXPECTED_VEHICLE_NAME.to_string())));
+
+        let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
+
+        let vehicles = aw!(vehicle_repository.get_vehicles_page(Some(Uuid::parse_str(fixture::USER_ID_STR).unwrap()), None, 2)).unwrap();
+
+        assert_eq!(fixture::EXPECTED_VEHICLE_NAME, vehicles[0].name);
+    }
+
+    #[test]
+    fn when_get_vehicle_projection_then_selects_only_its_columns() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "get_vehicle" && query == fixture::EXPECTED_PROJECTION_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult {
+                rows: Some(vec!(Row { columns: vec!(
+                    Some(CqlValue::Text(fixture::EXPECTED_VEHICLE_NAME.to_string())),
+                    None,
+                    Some(CqlValue::Int(fixture::EXPECTED_DISTANCE))) })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            }));
+
+        let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
+
+        let projection = aw!(vehicle_repository.get_vehicle_projection(Uuid::parse_str(fixture::USER_ID_STR).unwrap(), Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap(),
+                                                                       vec!("name", "retired_at", "distance"))).unwrap();
+
+        assert_eq!(VehicleProjection {
+            name: Some(fixture::EXPECTED_VEHICLE_NAME.to_string()),
+            retired_at: Some(None),
+            distance: Some(fixture::EXPECTED_DISTANCE),
+            ..VehicleProjection::default()
+        }, projection);
+    }
+
+    #[test]
+    fn given_after_when_get_vehicles_projection_page_then_reads_rest_of_partition() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_vehicle" && query == fixture::EXPECTED_PROJECTION_PAGE_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
+
+        let after = (Uuid::parse_str(fixture::USER_ID_STR).unwrap(), Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap());
+        let page = aw!(vehicle_repository.get_vehicles_projection_page(after.0, Some(after), 2, vec!("user_id", "vehicle_id", "model"))).unwrap();
+
+        assert!(page.is_empty());
+    }
+
+    #[test]
+    fn when_find_vehicle_keys_then_reads_lookup_partition_after_key() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_vehicle_lookup" && query == fixture::EXPECTED_LOOKUP_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult {
+                rows: Some(vec!(Row { columns: vec!(
+                    Some(CqlValue::Text("brand".to_string())),
+                    Some(CqlValue::Text("the brand".to_string())),
+                    Some(CqlValue::Uuid(Uuid::parse_str(fixture::USER_ID_STR).unwrap())),
+                    Some(CqlValue::Uuid(Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap()))) })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            }));
+
+        let vehicle_repository = VehicleRepositoryImpl::new(Arc::new(session_manager));
+
+        let after = (Uuid::parse_str(fixture::USER_ID_STR).unwrap(), Uuid::nil());
+        let keys = aw!(vehicle_repository.find_vehicle_keys("brand", "the brand", Some(after), 20)).unwrap();
+
+        assert_eq!(vec!((Uuid::parse_str(fixture::USER_ID_STR).unwrap(), Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap())), keys);
+    }
+
     mod fixture {
         use super::*;
         use scylla::frame::response::result::Row;
@@ -266,20 +661,96 @@ pub mod tests {
         pub const EXPECTED_MANUFACTURING_DATE: u32 = 2147499963;
         pub const EXPECTED_PICTURE: &str = "the picture";
         pub const EXPECTED_QUERY: &str = "SELECT name, user_id, vehicle_id, created_at, vehicle_type, retired_at, brand, model, distance, owner_since, manufacturing_date, picture \
-            FROM vehicles.vehicle \
+            FROM vehicles.vehicle WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const EXPECTED_LIST_QUERY: &str = "SELECT name, user_id, vehicle_id, created_at, vehicle_type, retired_at, brand, model, distance, owner_since, manufacturing_date, picture \
+            FROM vehicles.vehicle WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const EXPECTED_PARTITION_REST_QUERY: &str = "SELECT name, user_id, vehicle_id, created_at, vehicle_type, retired_at, brand, model, distance, owner_since, manufacturing_date, picture \
+            FROM vehicles.vehicle WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and (vehicle_id) > (88573010-cf4c-490e-9d29-f8517dc60b90) LIMIT 2";
+        pub const EXPECTED_NEXT_PARTITIONS_QUERY: &str = "SELECT name, user_id, vehicle_id, created_at, vehicle_type, retired_at, brand, model, distance, owner_since, manufacturing_date, picture \
+            FROM vehicles.vehicle WHERE token(user_id) > token(a906615e-2e6a-4edb-9377-5a6b8544791b) LIMIT 2";
+        pub const EXPECTED_SAVE_QUERY: &str = "INSERT INTO vehicles.vehicle (name, user_id, vehicle_id, created_at, vehicle_type, retired_at, brand, model, distance, owner_since, manufacturing_date, picture) \
+            VALUES ('the vehicle name', a906615e-2e6a-4edb-9377-5a6b8544791b, 88573010-cf4c-490e-9d29-f8517dc60b90, '1970-01-01 00:00:05 UTC', 'bike', '1970-01-01 00:00:10 UTC', 'the brand', 'the model', 500, '0001-01-15', '0001-01-15', 'the picture')";
+        pub const EXPECTED_SAVE_QUERY_WITHOUT_PICTURE: &str = "INSERT INTO vehicles.vehicle (name, user_id, vehicle_id, created_at, vehicle_type, retired_at, brand, model, distance, owner_since, manufacturing_date) \
+            VALUES ('the vehicle name', a906615e-2e6a-4edb-9377-5a6b8544791b, 88573010-cf4c-490e-9d29-f8517dc60b90, '1970-01-01 00:00:05 UTC', 'bike', null, 'the brand', 'the model', 500, '0001-01-15', '0001-01-15')";
+
+        pub const EXPECTED_UPDATE_DISTANCE_QUERY: &str = "UPDATE vehicles.vehicle SET distance = 503 \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90 IF distance = 500";
+        pub const EXPECTED_OUTBOX_INSERT: &str = "INSERT INTO vehicles.outbox (shard, bucket, occurred_at_ms, event_id, user_id, vehicle_id, event, vehicle) VALUES (";
+        pub const EXPECTED_HISTORY_INSERT: &str = "INSERT INTO vehicles.vehicle_history (user_id, vehicle_id, version, saved_at_ms, actor, event, diff, vehicle, restored_from) \
+            VALUES (a906615e-2e6a-4edb-9377-5a6b8544791b, 88573010-cf4c-490e-9d29-f8517dc60b90, 5d3c4e80-4f9a-11ec-8c4f-2f6b1a7d9e01, 5000, 'the actor', ";
+
+        pub const EXPECTED_PROJECTION_QUERY: &str = "SELECT name, retired_at, distance FROM vehicles.vehicle \
             WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and vehicle_id = 88573010-cf4c-490e-9d29-f8517dc60b90";
-        pub const EXPECTED_SAVE_QUERY: &str = "INSERT INTO vehicles.vehicle   (user_id,       \
-                                            vehicle_id,    \
-                                            vehicle_type,  \
-                                            name,          \
-                                            created_at,    \
-                                            retired_at,    \
-                                            brand,         \
-                                            model,         \
-                                            distance,      \
-                                            owner_since,   \
-                                            manufacturing_date, \
-                                            picture) VALUES (a906615e-2e6a-4edb-9377-5a6b8544791b, 88573010-cf4c-490e-9d29-f8517dc60b90, 'bike', 'the vehicle name', '1970-01-01 00:00:05 UTC', '1970-01-01 00:00:10 UTC', 'the brand', 'the model', 500, '0001-01-15', '0001-01-15', 'the picture')";
+        pub const EXPECTED_PROJECTION_PAGE_QUERY: &str = "SELECT user_id, vehicle_id, model FROM vehicles.vehicle \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b and (vehicle_id) > (88573010-cf4c-490e-9d29-f8517dc60b90) LIMIT 2";
+        pub const EXPECTED_LOOKUP_QUERY: &str = "SELECT attribute, value, user_id, vehicle_id FROM vehicles.vehicle_lookup \
+            WHERE attribute = 'brand' and value = 'the brand' and (user_id, vehicle_id) > (a906615e-2e6a-4edb-9377-5a6b8544791b, 00000000-0000-0000-0000-000000000000) LIMIT 20";
+
+        /// Lookup rows being written before the vehicles they point to.
+        /// `batches` lookup batches of `lookups` rows each, all of them in the same partition.
+        pub fn expect_lookup_batches(session_manager: &mut MockSessionManagerImpl, batches: usize, lookups: usize) {
+            session_manager.expect_execute_batch()
+                .withf(move |batch: &BatchStatement| batch.operation == Some("insert_vehicle_lookup_batch".to_string())
+                    && batch.statements.len() == lookups
+                    && batch.statements.iter().all(|statement| statement.starts_with("INSERT INTO vehicles.vehicle_lookup (attribute, value, user_id, vehicle_id) "))
+                    && batch.statements.iter().all(|statement| lookup_partition(statement) == lookup_partition(&batch.statements[0])))
+                .times(batches)
+                .returning(move |_| QueryOutcome { result: Ok(QueryResult::default()), retries: 0 });
+        }
+
+        /// The statement up to the attribute and value it writes, dropping the user and vehicle ids.
+        fn lookup_partition(statement: &str) -> Option<&str> {
+            statement.rsplitn(3, ", ").nth(2)
+        }
+
+        /// Vehicles being written in a logged batch, followed by the outbox entries of their `event` then their versions.
+        pub fn is_vehicle_batch(batch: &BatchStatement, operation: &str, vehicles: &[&str], event: &str) -> bool {
+            if batch.statements.len() != 3 * vehicles.len() {
+                return false;
+            }
+            let (vehicle_statements, changes) = batch.statements.split_at(vehicles.len());
+            let (outbox_statements, history_statements) = changes.split_at(vehicles.len());
+
+            batch.mode == BatchMode::Logged
+                && batch.operation == Some(operation.to_string())
+                && vehicle_statements == vehicles
+                && outbox_statements.iter().all(|statement| statement.starts_with(EXPECTED_OUTBOX_INSERT) && statement.contains(&format!(", '{}', ", event)))
+                && history_statements.iter().all(|statement| statement.starts_with(&format!("{}'{}', ", EXPECTED_HISTORY_INSERT, event)))
+        }
+
+        pub fn change(event: VehicleEventKind) -> VehicleChange {
+            VehicleChange {
+                event,
+                version: VehicleVersion {
+                    user_id: Uuid::parse_str(USER_ID_STR).unwrap(),
+                    vehicle_id: Uuid::parse_str(VEHICLE_ID_STR).unwrap(),
+                    version: Uuid::parse_str("5d3c4e80-4f9a-11ec-8c4f-2f6b1a7d9e01").unwrap(),
+                    saved_at_ms: 5000,
+                    actor: "the actor".to_string(),
+                    event: event.name().to_string(),
+                    diff: "{}".to_string(),
+                    vehicle: "{}".to_string(),
+                    restored_from: None
+                }
+            }
+        }
+
+        pub fn vehicle() -> Vehicle {
+            Vehicle {
+                user_id             : Uuid::parse_str(USER_ID_STR).unwrap(),
+                vehicle_id          : Uuid::parse_str(VEHICLE_ID_STR).unwrap(),
+                vehicle_type        : EXPECTED_VEHICLE_TYPE.to_string(),
+                name                : EXPECTED_VEHICLE_NAME.to_string(),
+                created_at          : Duration::seconds(EXPECTED_CREATED_AT),
+                retired_at          : None,
+                brand               : EXPECTED_BRAND.to_string(),
+                model               : EXPECTED_MODEL.to_string(),
+                distance            : EXPECTED_DISTANCE,
+                owner_since         : NaiveDate::from_num_days_from_ce(15),
+                manufacturing_date  : NaiveDate::from_num_days_from_ce(15),
+                picture             : None
+            }
+        }
 
         pub fn create_query_result(cql_value: CqlValue) -> Result<QueryResult, QueryError> {
             let cql_values = vec!(
@@ -0,0 +1,286 @@
+use std::sync::Arc;
+use scylla::IntoTypedRows;
+
+use chrono::NaiveDate;
+use rocket::serde::uuid::Uuid;
+
+use crate::dao::session_manager::{BatchStatement, SessionManager};
+use crate::domain::vehicle::Vehicle;
+use crate::domain::vehicle_history::VehicleChange;
+use crate::domain::vehicle_lookup::VehicleLookup;
+use crate::domain::vehicle_trash::{TrashedVehicle, VehiclePurge};
+use crate::mapper::{outbox_mapper, trash_mapper};
+use crate::repository::cql::CqlLiteral;
+use crate::repository::cql_repository::CqlRepository;
+use crate::repository::entity::{self, Entity};
+
+#[async_trait]
+pub trait VehicleTrashRepository {
+    /// Moves a vehicle to the trash along with its pending purge, the outbox entry of its deletion and the version
+    /// recording it.
+    async fn trash_vehicle(&self, vehicle: &Vehicle, trashed: &TrashedVehicle, change: VehicleChange) -> Option<()>;
+    /// Moves a trashed vehicle back, cancelling its purge, along with the outbox entry of it being created again
+    /// and the version recording it.
+    async fn restore_vehicle(&self, vehicle: Vehicle, trashed: &TrashedVehicle, change: VehicleChange) -> Option<Vehicle>;
+    async fn get_trashed_vehicle(&self, user_id: Uuid, vehicle_id: Uuid) -> Option<TrashedVehicle>;
+    /// Trashed vehicles of a user, `None` when they could not be read.
+    async fn get_trash(&self, user_id: Uuid) -> Option<Vec<TrashedVehicle>>;
+    /// Oldest purges of a day and shard due at `now_ms` following `after`, `None` when they could not be read.
+    async fn get_due_purges(&self, day: NaiveDate, shard: i32, now_ms: i64, after: Option<VehiclePurge>, limit: usize) -> Option<Vec<VehiclePurge>>;
+    /// Removes a purge once done, along with the trashed vehicle if Cassandra did not drop it yet.
+    async fn delete_purge(&self, purge: &VehiclePurge) -> Option<()>;
+}
+
+/// Keeps the trash, the pending purges and the vehicles in step: a vehicle moves to and from the trash in a
+/// single logged batch with its purge, so that a vehicle is never visible in both places nor purged once restored.
+/// Trashed vehicles are written with a TTL expiring at their purge.
+pub struct VehicleTrashRepositoryImpl {
+    queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+    trash: CqlRepository<TrashedVehicle>,
+    purges: CqlRepository<VehiclePurge>,
+}
+
+impl VehicleTrashRepositoryImpl {
+    pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> VehicleTrashRepositoryImpl {
+        VehicleTrashRepositoryImpl {
+            queriable: queriable.clone(),
+            trash: CqlRepository::new(queriable.clone()),
+            purges: CqlRepository::new(queriable)
+        }
+    }
+}
+
+#[async_trait]
+impl VehicleTrashRepository for VehicleTrashRepositoryImpl {
+    async fn trash_vehicle(&self, vehicle: &Vehicle, trashed: &TrashedVehicle, change: VehicleChange) -> Option<()> {
+        let ttl_seconds = ((trashed.purge_at_ms - trashed.deleted_at_ms) / 1000).max(1);
+
+        let mut statements = vec!(
+            entity::delete_statement::<Vehicle>(&(vehicle.user_id, vehicle.vehicle_id)),
+            format!("{} USING TTL {}", entity::insert_statement(trashed), ttl_seconds),
+            entity::insert_statement(&trash_mapper::get_vehicle_purge(trashed))
+        );
+        statements.extend(VehicleLookup::of(vehicle).iter().map(|lookup| entity::delete_statement::<VehicleLookup>(&lookup.primary_key())));
+        statements.push(entity::insert_statement(&outbox_mapper::get_outbox_entry(change.event, vehicle.user_id, vehicle.vehicle_id, None)));
+        statements.push(entity::insert_statement(&change.version));
+
+        let outcome = self.queriable.execute_batch(BatchStatement::logged(statements).for_operation("trash_vehicle")).await;
+
+        match outcome.result {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("Failed to trash Vehicle {} of user {} with error {:?}", vehicle.vehicle_id, vehicle.user_id, e);
+                None
+            }
+        }
+    }
+
+    async fn restore_vehicle(&self, vehicle: Vehicle, trashed: &TrashedVehicle, change: VehicleChange) -> Option<Vehicle> {
+        let mut statements = vec!(
+            entity::insert_statement(&vehicle),
+            entity::delete_statement::<TrashedVehicle>(&trashed.primary_key()),
+            entity::delete_statement::<VehiclePurge>(&trash_mapper::get_vehicle_purge(trashed).primary_key())
+        );
+        statements.extend(VehicleLookup::of(&vehicle).iter().map(entity::insert_statement));
+        statements.push(entity::insert_statement(&outbox_mapper::get_outbox_entry(change.event, vehicle.user_id, vehicle.vehicle_id, Some(&vehicle))));
+        statements.push(entity::insert_statement(&change.version));
+
+        let outcome = self.queriable.execute_batch(BatchStatement::logged(statements).for_operation("restore_vehicle")).await;
+
+        match outcome.result {
+            Ok(_) => Some(vehicle),
+            Err(e) => {
+                println!("Failed to restore Vehicle {} of user {} with error {:?}", vehicle.vehicle_id, vehicle.user_id, e);
+                None
+            }
+        }
+    }
+
+    async fn get_trashed_vehicle(&self, user_id: Uuid, vehicle_id: Uuid) -> Option<TrashedVehicle> {
+        self.trash.get(&(user_id, vehicle_id)).await
+            .unwrap_or_else(|e| panic!("Failed to get TrashedVehicle {} of user {} with error {:?}", vehicle_id, user_id, e))
+    }
+
+    async fn get_trash(&self, user_id: Uuid) -> Option<Vec<TrashedVehicle>> {
+        match self.trash.list_by_partition(&(user_id,)).await {
+            Ok(trash) => Some(trash),
+            Err(e) => {
+                println!("Failed to list TrashedVehicles of user {} with error {:?}", user_id, e);
+                None
+            }
+        }
+    }
+
+    async fn get_due_purges(&self, day: NaiveDate, shard: i32, now_ms: i64, after: Option<VehiclePurge>, limit: usize) -> Option<Vec<VehiclePurge>> {
+        // Clustering columns restricted by a tuple cannot also be restricted column by column, so both bounds are tuples.
+        let lower_bound = after
+            .map(|after| format!("(purge_at_ms, user_id, vehicle_id) > ({}, {}, {}) and ", after.purge_at_ms, after.user_id, after.vehicle_id))
+            .unwrap_or_default();
+        let query = format!("SELECT {} FROM {} WHERE day = {} and shard = {} and {}(purge_at_ms) <= ({}) LIMIT {}",
+                            VehiclePurge::COLUMNS.join(", "), VehiclePurge::TABLE, day.literal(), shard, lower_bound, now_ms, limit);
+
+        match self.queriable.execute_query("list_vehicle_purge", &query).await {
+            Ok(result) => Some(result.rows.unwrap_or_default()
+                .into_typed::<VehiclePurge>()
+                .map(|row| row.expect("Failed to extract VehiclePurge from Row"))
+                .collect()),
+            Err(e) => {
+                println!("Failed to list VehiclePurges of {} shard {} with error {:?}", day, shard, e);
+                None
+            }
+        }
+    }
+
+    async fn delete_purge(&self, purge: &VehiclePurge) -> Option<()> {
+        let statements = vec!(
+            entity::delete_statement::<TrashedVehicle>(&(purge.user_id, purge.vehicle_id)),
+            entity::delete_statement::<VehiclePurge>(&purge.primary_key())
+        );
+
+        match self.queriable.execute_batch(BatchStatement::logged(statements).for_operation("delete_vehicle_purge")).await.result {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("Failed to delete VehiclePurge of Vehicle {} with error {:?}", purge.vehicle_id, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+    use scylla::QueryResult;
+    use scylla::frame::response::result::{CqlValue, Row};
+    use scylla::transport::errors::QueryError;
+
+    use crate::dao::session_manager::{BatchMode, QueryOutcome};
+    use crate::mapper::vehicle_history_mapper;
+    use crate::repository::vehicle_repository::tests::MockSessionManagerImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn when_trash_vehicle_then_moves_it_to_trash_with_ttl_and_purge_in_logged_batch() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_batch()
+            .withf(|batch: &BatchStatement| batch.mode == BatchMode::Logged
+                && batch.statements.len() == 7
+                && batch.statements[0] == fixture::EXPECTED_DELETE_STATEMENT
+                && batch.statements[1].starts_with("INSERT INTO vehicles.vehicle_trash ") && batch.statements[1].ends_with(" USING TTL 2592000")
+                && batch.statements[2].starts_with("INSERT INTO vehicles.vehicle_purge ")
+                && batch.statements[3..5].iter().all(|statement| statement.starts_with("DELETE FROM vehicles.vehicle_lookup "))
+                && batch.statements[5].starts_with("INSERT INTO vehicles.outbox ") && batch.statements[5].contains("'deleted'")
+                && batch.statements[6].starts_with("INSERT INTO vehicles.vehicle_history "))
+            .times(1)
+            .returning(move |_| QueryOutcome { result: Ok(QueryResult::default()), retries: 0 });
+
+        let vehicle_trash_repository = VehicleTrashRepositoryImpl::new(Arc::new(session_manager));
+
+        let vehicle = fixture::vehicle();
+        let trashed = trash_mapper::get_trashed_vehicle(&vehicle, "jane", Utc.timestamp(5, 0), Duration::days(30));
+        let change = vehicle_history_mapper::get_deletion_change(&vehicle, "jane");
+
+        assert!(aw!(vehicle_trash_repository.trash_vehicle(&vehicle, &trashed, change)).is_some());
+    }
+
+    #[test]
+    fn when_get_due_purges_then_reads_oldest_purges_of_shard_due_now() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_vehicle_purge" && query == fixture::EXPECTED_PURGES_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result());
+
+        let vehicle_trash_repository = VehicleTrashRepositoryImpl::new(Arc::new(session_manager));
+
+        let purges = aw!(vehicle_trash_repository.get_due_purges(fixture::day(), 3, 9000, None, 100)).unwrap();
+
+        assert_eq!(vec!(fixture::vehicle().vehicle_id), purges.iter().map(|purge| purge.vehicle_id).collect::<Vec<Uuid>>());
+        assert_eq!(fixture::day(), purges[0].day);
+    }
+
+    #[test]
+    fn given_previous_purge_when_get_due_purges_then_reads_purges_after_it() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_vehicle_purge" && query == fixture::EXPECTED_PURGES_AFTER_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result());
+
+        let vehicle_trash_repository = VehicleTrashRepositoryImpl::new(Arc::new(session_manager));
+        let after = VehiclePurge {
+            day: fixture::day(),
+            shard: 3,
+            purge_at_ms: 5000,
+            user_id: fixture::vehicle().user_id,
+            vehicle_id: fixture::vehicle().vehicle_id,
+            picture: None
+        };
+
+        let purges = aw!(vehicle_trash_repository.get_due_purges(fixture::day(), 3, 9000, Some(after), 100)).unwrap();
+
+        assert_eq!(1, purges.len());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "d13fe953-297a-4781-807a-f9becc1b71f6";
+        pub const VEHICLE_ID_STR: &str = "60e18f00-34b8-4a52-916c-adbb0204618e";
+
+        pub const EXPECTED_DELETE_STATEMENT: &str = "DELETE FROM vehicles.vehicle \
+            WHERE user_id = d13fe953-297a-4781-807a-f9becc1b71f6 and vehicle_id = 60e18f00-34b8-4a52-916c-adbb0204618e";
+        pub const EXPECTED_PURGES_QUERY: &str = "SELECT day, shard, purge_at_ms, user_id, vehicle_id, picture FROM vehicles.vehicle_purge \
+            WHERE day = '1970-01-01' and shard = 3 and (purge_at_ms) <= (9000) LIMIT 100";
+        pub const EXPECTED_PURGES_AFTER_QUERY: &str = "SELECT day, shard, purge_at_ms, user_id, vehicle_id, picture FROM vehicles.vehicle_purge \
+            WHERE day = '1970-01-01' and shard = 3 and (purge_at_ms, user_id, vehicle_id) > (5000, d13fe953-297a-4781-807a-f9becc1b71f6, 60e18f00-34b8-4a52-916c-adbb0204618e) \
+            and (purge_at_ms) <= (9000) LIMIT 100";
+
+        pub fn day() -> NaiveDate {
+            NaiveDate::from_ymd(1970, 1, 1)
+        }
+
+        pub fn vehicle() -> Vehicle {
+            Vehicle {
+                name: "the vehicle".to_string(),
+                user_id: Uuid::parse_str(USER_ID_STR).unwrap(),
+                vehicle_id: Uuid::parse_str(VEHICLE_ID_STR).unwrap(),
+                created_at: Duration::seconds(1000),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: 100,
+                owner_since: NaiveDate::from_ymd(2021, 1, 1),
+                manufacturing_date: NaiveDate::from_ymd(2020, 1, 1),
+                picture: None
+            }
+        }
+
+        pub fn create_query_result() -> Result<QueryResult, QueryError> {
+            let cql_values = vec!(
+                Some(CqlValue::Date(1 << 31)),
+                Some(CqlValue::Int(3)),
+                Some(CqlValue::BigInt(5000)),
+                Some(CqlValue::Uuid(Uuid::parse_str(USER_ID_STR).unwrap())),
+                Some(CqlValue::Uuid(Uuid::parse_str(VEHICLE_ID_STR).unwrap())),
+                None);
+
+            Ok(QueryResult {
+                rows: Some(vec!(Row { columns: cql_values })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            })
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use std::sync::Arc;
+
+use rocket::serde::uuid::Uuid;
+
+use chrono::Duration;
+use scylla::transport::errors::QueryError;
+
+use crate::dao::session_manager::{BatchStatement, SessionManager, Statement};
+use crate::domain::webhook::{self, PendingDelivery, Webhook, WebhookDelivery, DELIVERY_PENDING};
+use crate::mapper::webhook_mapper;
+use crate::repository::cql;
+use crate::repository::cql_repository::CqlRepository;
+use crate::repository::entity::{self, Entity, KeyValues};
+
+#[async_trait]
+pub trait WebhookRepository {
+    async fn get_webhook(&self, user_id: Uuid, webhook_id: Uuid) -> Option<Webhook>;
+    /// Webhooks of a user, `None` when they could not be read.
+    async fn get_webhooks(&self, user_id: Uuid) -> Option<Vec<Webhook>>;
+    async fn save_webhook(&self, webhook: Webhook) -> Option<Webhook>;
+    async fn delete_webhook(&self, user_id: Uuid, webhook_id: Uuid) -> Option<()>;
+    /// Inserts a pending delivery along with its lease, held by the instance about to attempt it.
+    async fn start_delivery(&self, delivery: WebhookDelivery, lease_until_ms: i64) -> Option<WebhookDelivery>;
+    /// Overwrites a delivery with the outcome of its latest attempt, ending its lease once it is no longer pending.
+    async fn save_delivery(&self, delivery: WebhookDelivery) -> Option<WebhookDelivery>;
+    async fn get_delivery(&self, webhook_id: Uuid, created_at: Duration, delivery_id: Uuid) -> Option<WebhookDelivery>;
+    /// Page of the pending deliveries of a shard following `after`, `None` when they could not be read.
+    async fn get_pending_deliveries(&self, shard: i32, after: Option<PendingDelivery>, limit: usize) -> Option<Vec<PendingDelivery>>;
+    /// Compare-and-set of the lease of a pending delivery, `Some(false)` when another instance took it over.
+    async fn lease_delivery(&self, pending: &PendingDelivery, lease_until_ms: i64) -> Option<bool>;
+    async fn delete_pending_delivery(&self, pending: &PendingDelivery) -> Option<()>;
+    /// Deliveries of a webhook following `after`, or its latest ones without `after`, newest first.
+    async fn get_deliveries(&self, webhook_id: Uuid, after: Option<WebhookDelivery>, limit: usize) -> Option<Vec<WebhookDelivery>>;
+}
+
+pub struct WebhookRepositoryImpl {
+    queriable: Arc<dyn SessionManager + Sync + Send + 'static>,
+    webhooks: CqlRepository<Webhook>,
+    deliveries: CqlRepository<WebhookDelivery>,
+    pending_deliveries: CqlRepository<PendingDelivery>,
+}
+
+impl WebhookRepositoryImpl {
+    pub fn new(queriable: Arc<dyn SessionManager + Sync + Send + 'static>) -> WebhookRepositoryImpl {
+        WebhookRepositoryImpl {
+            webhooks: CqlRepository::new(queriable.clone()),
+            deliveries: CqlRepository::new(queriable.clone()),
+            pending_deliveries: CqlRepository::new(queriable.clone()),
+            queriable
+        }
+    }
+
+    /// Deliveries clustered after `after`. Newest first then by id, they cannot be sliced with a single clustering
+    /// relation: the rest of the second of `after` is read first, then the older seconds.
+    async fn deliveries_after(&self, after: &WebhookDelivery, limit: usize) -> Result<Vec<WebhookDelivery>, QueryError> {
+        let key = after.primary_key().values();
+        let columns = WebhookDelivery::COLUMNS.join(", ");
+
+        let mut deliveries = self.select_deliveries(format!("SELECT {} FROM {} WHERE webhook_id = {} and created_at = {} and delivery_id > {} LIMIT {}",
+                                                            columns, WebhookDelivery::TABLE, key[0], key[1], key[2], limit)).await?;
+        if deliveries.len() < limit {
+            let older = self.select_deliveries(format!("SELECT {} FROM {} WHERE webhook_id = {} and created_at < {} LIMIT {}",
+                                                       columns, WebhookDelivery::TABLE, key[0], key[1], limit - deliveries.len())).await?;
+            deliveries.extend(older);
+        }
+
+        Ok(deliveries)
+    }
+
+    async fn select_deliveries(&self, query: String) -> Result<Vec<WebhookDelivery>, QueryError> {
+        let result = self.queriable.execute_query("list_webhook_delivery", &query).await?;
+
+        Ok(result.rows.unwrap_or_default()
+            .into_typed::<WebhookDelivery>()
+            .map(|row| row.expect("Failed to extract WebhookDelivery from Row"))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for WebhookRepositoryImpl {
+    async fn get_webhook(&self, user_id: Uuid, webhook_id: Uuid) -> Option<Webhook> {
+        self.webhooks.get(&(user_id, webhook_id)).await
+            .unwrap_or_else(|e| panic!("Failed to get Webhook {} of user {} with error {:?}", webhook_id, user_id, e))
+    }
+
+    async fn get_webhooks(&self, user_id: Uuid) -> Option<Vec<Webhook>> {
+        match self.webhooks.list_by_partition(&(user_id,)).await {
+            Ok(webhooks) => Some(webhooks),
+            Err(e) => {
+                println!("Failed to list Webhooks of user {} with error {:?}", user_id, e);
+                None
+            }
+        }
+    }
+
+    async fn save_webhook(&self, webhook: Webhook) -> Option<Webhook> {
+        match self.webhooks.insert(&webhook).await {
+            Ok(_) => Some(webhook),
+            Err(e) => {
+                println!("Failed to insert Webhook {:?} with error {:?}", webhook.webhook_id, e);
+                None
+            }
+        }
+    }
+
+    async fn delete_webhook(&self, user_id: Uuid, webhook_id: Uuid) -> Option<()> {
+        match self.webhooks.delete(&(user_id, webhook_id)).await {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("Failed to delete Webhook {} of user {} with error {:?}", webhook_id, user_id, e);
+                None
+            }
+        }
+    }
+
+    async fn start_delivery(&self, delivery: WebhookDelivery, lease_until_ms: i64) -> Option<WebhookDelivery> {
+        let statements = vec!(
+            entity::insert_statement(&delivery),
+            entity::insert_statement(&webhook_mapper::get_pending_delivery(&delivery, lease_until_ms))
+        );
+
+        match self.queriable.execute_batch(BatchStatement::logged(statements).for_operation("start_webhook_delivery")).await.result {
+            Ok(_) => Some(delivery),
+            Err(e) => {
+                println!("Failed to insert WebhookDelivery {} with error {:?}", delivery.delivery_id, e);
+                None
+            }
+        }
+    }
+
+    async fn save_delivery(&self, delivery: WebhookDelivery) -> Option<WebhookDelivery> {
+        let result = if delivery.status == DELIVERY_PENDING {
+            self.deliveries.insert(&delivery).await
+        } else {
+            let statements = vec!(
+                entity::insert_statement(&delivery),
+                entity::delete_statement::<PendingDelivery>(&(webhook::shard(delivery.delivery_id), delivery.delivery_id, delivery.webhook_id))
+            );
+            self.queriable.execute_batch(BatchStatement::logged(statements).for_operation("end_webhook_delivery")).await.result.map(|_| ())
+        };
+
+        match result {
+            Ok(_) => Some(delivery),
+            Err(e) => {
+                println!("Failed to insert WebhookDelivery {} with error {:?}", delivery.delivery_id, e);
+                None
+            }
+        }
+    }
+
+    async fn get_delivery(&self, webhook_id: Uuid, created_at: Duration, delivery_id: Uuid) -> Option<WebhookDelivery> {
+        self.deliveries.get(&(webhook_id, created_at, delivery_id)).await
+            .unwrap_or_else(|e| panic!("Failed to get WebhookDelivery {} of {} with error {:?}", delivery_id, webhook_id, e))
+    }
+
+    async fn get_pending_deliveries(&self, shard: i32, after: Option<PendingDelivery>, limit: usize) -> Option<Vec<PendingDelivery>> {
+        match self.pending_deliveries.list_by_partition_page(&(shard,), after.map(|after| after.primary_key()).as_ref(), limit).await {
+            Ok(pending_deliveries) => Some(pending_deliveries),
+            Err(e) => {
+                println!("Failed to list PendingDeliveries of shard {} with error {:?}", shard, e);
+                None
+            }
+        }
+    }
+
+    async fn lease_delivery(&self, pending: &PendingDelivery, lease_until_ms: i64) -> Option<bool> {
+        let query = format!("UPDATE {} SET lease_until_ms = {} WHERE shard = {} and delivery_id = {} and webhook_id = {} IF lease_until_ms = {}",
+                            PendingDelivery::TABLE, lease_until_ms, pending.shard, pending.delivery_id, pending.webhook_id, pending.lease_until_ms);
+
+        let outcome = self.queriable.execute_statement(Statement::non_idempotent(&query).for_operation("lease_webhook_delivery")).await;
+
+        match outcome.result {
+            Ok(query_result) => Some(cql::applied(&query_result)),
+            Err(e) => {
+                println!("Failed to lease WebhookDelivery {:?} after {} retries with error {:?}", query, outcome.retries, e);
+                None
+            }
+        }
+    }
+
+    async fn delete_pending_delivery(&self, pending: &PendingDelivery) -> Option<()> {
+        match self.pending_deliveries.delete(&pending.primary_key()).await {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("Failed to delete PendingDelivery {} with error {:?}", pending.delivery_id, e);
+                None
+            }
+        }
+    }
+
+    async fn get_deliveries(&self, webhook_id: Uuid, after: Option<WebhookDelivery>, limit: usize) -> Option<Vec<WebhookDelivery>> {
+        let deliveries = match after {
+            Some(after) => self.deliveries_after(&after, limit).await,
+            None => self.deliveries.list_by_partition_page(&(webhook_id,), None, limit).await
+        };
+
+        match deliveries {
+            Ok(deliveries) => Some(deliveries),
+            Err(e) => {
+                println!("Failed to list WebhookDeliveries of {} with error {:?}", webhook_id, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::Duration;
+    use scylla::QueryResult;
+    use scylla::transport::errors::QueryError;
+    use scylla::frame::response::result::{CqlValue, Row};
+
+    use crate::dao::session_manager::{BatchMode, QueryOutcome};
+    use crate::domain::webhook::DELIVERY_DELIVERED;
+    use crate::repository::vehicle_repository::tests::MockSessionManagerImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn when_get_webhooks_then_returns_webhooks_of_user() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_webhook" && query == fixture::EXPECTED_LIST_QUERY)
+            .times(1)
+            .returning(move |_, _| fixture::create_query_result());
+
+        let webhook_repository = WebhookRepositoryImpl::new(Arc::new(session_manager));
+
+        let webhooks = aw!(webhook_repository.get_webhooks(fixture::user_id())).unwrap();
+
+        assert_eq!(vec!(fixture::webhook()), webhooks);
+    }
+
+    #[test]
+    fn when_save_delivery_then_inserts_it_under_its_webhook() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "insert_webhook_delivery" && query == fixture::EXPECTED_SAVE_DELIVERY_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        let webhook_repository = WebhookRepositoryImpl::new(Arc::new(session_manager));
+
+        assert!(aw!(webhook_repository.save_delivery(fixture::delivery())).is_some());
+    }
+
+    #[test]
+    fn given_final_outcome_when_save_delivery_then_ends_its_lease_in_logged_batch() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_batch()
+            .withf(|batch: &BatchStatement| batch.mode == BatchMode::Logged
+                && batch.statements.len() == 2
+                && batch.statements[0].starts_with("INSERT INTO vehicles.webhook_delivery ") && batch.statements[0].contains("'delivered'")
+                && batch.statements[1] == fixture::EXPECTED_DELETE_PENDING_STATEMENT)
+            .times(1)
+            .returning(move |_| QueryOutcome { result: Ok(QueryResult::default()), retries: 0 });
+
+        let webhook_repository = WebhookRepositoryImpl::new(Arc::new(session_manager));
+
+        let delivery = WebhookDelivery { status: DELIVERY_DELIVERED.to_string(), ..fixture::delivery() };
+
+        assert!(aw!(webhook_repository.save_delivery(delivery)).is_some());
+    }
+
+    #[test]
+    fn given_lease_taken_over_when_lease_delivery_then_returns_false() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_statement()
+            .withf(|statement: &Statement| statement.query_statement == fixture::EXPECTED_LEASE_QUERY && !statement.idempotent)
+            .times(1)
+            .returning(move |_| QueryOutcome {
+                result: Ok(QueryResult {
+                    rows: Some(vec!(Row { columns: vec!(Some(CqlValue::Boolean(false)), Some(CqlValue::BigInt(9000))) })),
+                    warnings: vec!(),
+                    tracing_id: None,
+                    paging_state: None
+                }),
+                retries: 0
+            });
+
+        let webhook_repository = WebhookRepositoryImpl::new(Arc::new(session_manager));
+
+        let pending = webhook_mapper::get_pending_delivery(&fixture::delivery(), 5000);
+
+        assert_eq!(Some(false), aw!(webhook_repository.lease_delivery(&pending, 65000)));
+    }
+
+    #[test]
+    fn given_query_error_when_get_deliveries_then_returns_none() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_webhook_delivery" && query == fixture::EXPECTED_DELIVERIES_QUERY)
+            .times(1)
+            .returning(move |_, _| Err(QueryError::TimeoutError));
+
+        let webhook_repository = WebhookRepositoryImpl::new(Arc::new(session_manager));
+
+        assert!(aw!(webhook_repository.get_deliveries(fixture::webhook_id(), None, 20)).is_none());
+    }
+
+    #[test]
+    fn given_after_when_get_deliveries_then_reads_rest_of_its_second_then_older_ones() {
+        let mut session_manager = MockSessionManagerImpl::new();
+
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_webhook_delivery" && query == fixture::EXPECTED_SAME_SECOND_DELIVERIES_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+        session_manager.expect_execute_query()
+            .withf(|operation: &str, query: &str| operation == "list_webhook_delivery" && query == fixture::EXPECTED_OLDER_DELIVERIES_QUERY)
+            .times(1)
+            .returning(move |_, _| Ok(QueryResult::default()));
+
+        let webhook_repository = WebhookRepositoryImpl::new(Arc::new(session_manager));
+
+        assert_eq!(Some(vec!()), aw!(webhook_repository.get_deliveries(fixture::webhook_id(), Some(fixture::delivery()), 20)));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const WEBHOOK_ID_STR: &str = "5f0e3c2a-8d1b-4c6e-9a7f-2b3c4d5e6f70";
+        pub const DELIVERY_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+
+        pub const EXPECTED_LIST_QUERY: &str = "SELECT user_id, webhook_id, url, secret, events, created_at FROM vehicles.webhook \
+            WHERE user_id = a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const EXPECTED_SAVE_DELIVERY_QUERY: &str = "INSERT INTO vehicles.webhook_delivery (webhook_id, created_at, delivery_id, user_id, vehicle_id, event, payload, \
+            status, attempts, response_status, error, updated_at) VALUES (5f0e3c2a-8d1b-4c6e-9a7f-2b3c4d5e6f70, '1970-01-01 00:00:05 UTC', \
+            88573010-cf4c-490e-9d29-f8517dc60b90, a906615e-2e6a-4edb-9377-5a6b8544791b, 88573010-cf4c-490e-9d29-f8517dc60b90, 'created', '{}', 'pending', 1, 500, null, \
+            '1970-01-01 00:00:10 UTC')";
+        pub const EXPECTED_DELETE_PENDING_STATEMENT: &str = "DELETE FROM vehicles.webhook_pending_delivery \
+            WHERE shard = 0 and delivery_id = 88573010-cf4c-490e-9d29-f8517dc60b90 and webhook_id = 5f0e3c2a-8d1b-4c6e-9a7f-2b3c4d5e6f70";
+        pub const EXPECTED_LEASE_QUERY: &str = "UPDATE vehicles.webhook_pending_delivery SET lease_until_ms = 65000 \
+            WHERE shard = 0 and delivery_id = 88573010-cf4c-490e-9d29-f8517dc60b90 and webhook_id = 5f0e3c2a-8d1b-4c6e-9a7f-2b3c4d5e6f70 IF lease_until_ms = 5000";
+        pub const EXPECTED_DELIVERIES_QUERY: &str = "SELECT webhook_id, created_at, delivery_id, user_id, vehicle_id, event, payload, status, attempts, \
+            response_status, error, updated_at FROM vehicles.webhook_delivery WHERE webhook_id = 5f0e3c2a-8d1b-4c6e-9a7f-2b3c4d5e6f70 LIMIT 20";
+        pub const EXPECTED_SAME_SECOND_DELIVERIES_QUERY: &str = "SELECT webhook_id, created_at, delivery_id, user_id, vehicle_id, event, payload, status, attempts, \
+            response_status, error, updated_at FROM vehicles.webhook_delivery WHERE webhook_id = 5f0e3c2a-8d1b-4c6e-9a7f-2b3c4d5e6f70 \
+            and created_at = '1970-01-01 00:00:05 UTC' and delivery_id > 88573010-cf4c-490e-9d29-f8517dc60b90 LIMIT 20";
+        pub const EXPECTED_OLDER_DELIVERIES_QUERY: &str = "SELECT webhook_id, created_at, delivery_id, user_id, vehicle_id, event, payload, status, attempts, \
+            response_status, error, updated_at FROM vehicles.webhook_delivery WHERE webhook_id = 5f0e3c2a-8d1b-4c6e-9a7f-2b3c4d5e6f70 \
+            and created_at < '1970-01-01 00:00:05 UTC' LIMIT 20";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn webhook_id() -> Uuid {
+            Uuid::parse_str(WEBHOOK_ID_STR).unwrap()
+        }
+
+        pub fn webhook() -> Webhook {
+            Webhook {
+                user_id: user_id(),
+                webhook_id: webhook_id(),
+                url: "https://partner.example/hooks".to_string(),
+                secret: "the secret".to_string(),
+                events: vec!("created".to_string(), "retired".to_string()),
+                created_at: Duration::seconds(5)
+            }
+        }
+
+        pub fn delivery() -> WebhookDelivery {
+            WebhookDelivery {
+                webhook_id: webhook_id(),
+                created_at: Duration::seconds(5),
+                delivery_id: Uuid::parse_str(DELIVERY_ID_STR).unwrap(),
+                user_id: user_id(),
+                vehicle_id: Uuid::parse_str(DELIVERY_ID_STR).unwrap(),
+                event: "created".to_string(),
+                payload: "{}".to_string(),
+                status: DELIVERY_PENDING.to_string(),
+                attempts: 1,
+                response_status: Some(500),
+                error: None,
+                updated_at: Duration::seconds(10)
+            }
+        }
+
+        pub fn create_query_result() -> Result<QueryResult, QueryError> {
+            let cql_values = vec!(
+                Some(CqlValue::Uuid(user_id())),
+                Some(CqlValue::Uuid(webhook_id())),
+                Some(CqlValue::Text("https://partner.example/hooks".to_string())),
+                Some(CqlValue::Text("the secret".to_string())),
+                Some(CqlValue::List(vec!(CqlValue::Text("created".to_string()), CqlValue::Text("retired".to_string())))),
+                Some(CqlValue::Timestamp(Duration::seconds(5))));
+
+            Ok(QueryResult {
+                rows: Some(vec!(Row { columns: cql_values })),
+                warnings: vec!(),
+                tracing_id: None,
+                paging_state: None
+            })
+        }
+    }
+}
@@ -0,0 +1,551 @@
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rocket::serde::Deserialize;
+use rocket::serde::uuid::Uuid;
+use rocket::tokio::sync::oneshot;
+use rocket::tokio::task;
+use tantivy::collector::TopDocs;
+use tantivy::directory::error::LockError;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, FuzzyTermQuery, Occur, Query, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyError, Term};
+
+use crate::domain::vehicle::Vehicle;
+use crate::search::vehicle_index::VehicleIndex;
+
+const WRITER_HEAP_BYTES: usize = 50_000_000;
+/// Attempts at taking the writer lock of the index directory while another process holds it.
+const WRITER_LOCK_ATTEMPTS: u32 = 50;
+const WRITER_LOCK_DELAY: Duration = Duration::from_millis(100);
+/// Words are matched as prefixes, those this long also tolerating one typo.
+const FUZZY_WORD_LENGTH: usize = 4;
+
+/// Index writer settings read from the `search` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct SearchSettings {
+    pub commit_interval_ms  : u64,
+    pub commit_batch_size   : usize
+}
+
+impl Default for SearchSettings {
+    fn default() -> Self {
+        SearchSettings {
+            commit_interval_ms: 1000,
+            commit_batch_size: 1000
+        }
+    }
+}
+
+struct Fields {
+    user_id: Field,
+    vehicle_id: Field,
+    name: Field,
+    brand: Field,
+    model: Field
+}
+
+type Write = Box<dyn FnOnce(&Fields, &mut IndexWriter) + Send>;
+
+enum WriteRequest {
+    /// A write to the documents of `documents` vehicles.
+    Write { documents: usize, write: Write },
+    /// Commits the writes queued before it, answering whether searches see them.
+    Commit(oneshot::Sender<bool>),
+    /// Commits, then gives the writer and the lock of the index directory back.
+    Shutdown(oneshot::Sender<bool>)
+}
+
+/// `VehicleIndex` backed by a tantivy index, stored in a local directory or kept in memory.
+/// Writes are queued to a thread holding the single writer of the index for as long as the index is open. It applies
+/// them as they come and commits them `commit_interval_ms` after the first one or once they touched
+/// `commit_batch_size` vehicles, so a search sees an indexed vehicle within `commit_interval_ms`. Removing a user and clearing the
+/// index answer once committed.
+/// The writer holds the lock of the index directory until `shutdown`, so the `reindex` and `import` commands only
+/// write to the directory of a stopped server. Each instance keeps its own index, which only sees the writes made
+/// through that instance.
+pub struct TantivyVehicleIndex {
+    inner: Arc<Inner>,
+    writes: Mutex<Sender<WriteRequest>>
+}
+
+struct Inner {
+    index: Index,
+    fields: Fields,
+    reader: IndexReader
+}
+
+/// The writer of the index, owned by the thread applying the writes of this process.
+struct BatchWriter {
+    inner: Arc<Inner>,
+    settings: SearchSettings,
+    /// Taken on the first write, so that an index only read never locks its directory.
+    writer: Option<IndexWriter>,
+    pending: usize,
+    /// When the first write not committed yet was applied.
+    pending_since: Option<Instant>,
+    /// Writes dropped since the last commit, the writer not being available.
+    dropped: usize
+}
+
+impl TantivyVehicleIndex {
+    /// Opens the index stored in `dir`, creating it when missing.
+    pub fn open<P: AsRef<Path>>(dir: P, settings: SearchSettings) -> Result<TantivyVehicleIndex, String> {
+        std::fs::create_dir_all(dir.as_ref()).map_err(|e| format!("Failed to create {:?}: {}", dir.as_ref(), e))?;
+        let directory = MmapDirectory::open(dir.as_ref()).map_err(|e| format!("Failed to open {:?}: {}", dir.as_ref(), e))?;
+
+        let (schema, fields) = schema();
+        Index::open_or_create(directory, schema)
+            .map_err(|e| format!("Failed to open vehicle index in {:?}: {}", dir.as_ref(), e))
+            .and_then(|index| TantivyVehicleIndex::new(index, fields, settings))
+    }
+
+    pub fn in_memory(settings: SearchSettings) -> Result<TantivyVehicleIndex, String> {
+        let (schema, fields) = schema();
+
+        TantivyVehicleIndex::new(Index::create_in_ram(schema), fields, settings)
+    }
+
+    fn new(index: Index, fields: Fields, settings: SearchSettings) -> Result<TantivyVehicleIndex, String> {
+        let reader = index.reader_builder()
+            .reload_policy(ReloadPolicy::OnCommit)
+            .try_into()
+            .map_err(|e| e.to_string())?;
+        let inner = Arc::new(Inner { index, fields, reader });
+        let (writes, requests) = mpsc::channel();
+
+        let batch_writer = BatchWriter { inner: inner.clone(), settings, writer: None, pending: 0, pending_since: None, dropped: 0 };
+        thread::Builder::new()
+            .name("vehicle-index-writer".to_string())
+            .spawn(move || batch_writer.run(requests))
+            .map_err(|e| format!("Failed to start vehicle index writer: {}", e))?;
+
+        Ok(TantivyVehicleIndex { inner, writes: Mutex::new(writes) })
+    }
+
+    /// Commits the writes queued so far, answering once searches see them.
+    pub async fn commit(&self) -> Option<()> {
+        self.wait_for(WriteRequest::Commit).await
+    }
+
+    /// Commits the writes queued so far and gives the writer back, releasing the lock of the index directory for
+    /// another process. Later writes through this index fail.
+    pub async fn shutdown(&self) -> Option<()> {
+        self.wait_for(WriteRequest::Shutdown).await
+    }
+
+    fn queue(&self, request: WriteRequest) -> Option<()> {
+        match self.writes.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).send(request) {
+            Ok(_) => Some(()),
+            Err(_) => {
+                println!("Failed to update vehicle index: its writer was shut down");
+                None
+            }
+        }
+    }
+
+    async fn wait_for(&self, request: fn(oneshot::Sender<bool>) -> WriteRequest) -> Option<()> {
+        let (done, committed) = oneshot::channel();
+        self.queue(request(done))?;
+
+        match committed.await {
+            Ok(true) => Some(()),
+            _ => None
+        }
+    }
+
+    /// Runs an index operation away from the async runtime, tantivy doing blocking IO.
+    async fn blocking<T, F>(&self, operation: &str, f: F) -> Option<T>
+        where T: Send + 'static,
+              F: FnOnce(&Inner) -> tantivy::Result<T> + Send + 'static {
+        let inner = self.inner.clone();
+
+        match task::spawn_blocking(move || f(&inner)).await {
+            Ok(Ok(result)) => Some(result),
+            Ok(Err(e)) => {
+                println!("Failed to {} vehicle index with error {:?}", operation, e);
+                None
+            },
+            Err(e) => {
+                println!("Failed to {} vehicle index with error {:?}", operation, e);
+                None
+            }
+        }
+    }
+}
+
+impl Inner {
+    /// Takes the writer, waiting for another process writing to the same directory to give it back.
+    fn writer(&self) -> tantivy::Result<IndexWriter> {
+        let mut attempts = 1;
+        loop {
+            match self.index.writer(WRITER_HEAP_BYTES) {
+                Err(TantivyError::LockFailure(LockError::LockBusy, _)) if attempts < WRITER_LOCK_ATTEMPTS => {
+                    attempts += 1;
+                    thread::sleep(WRITER_LOCK_DELAY);
+                },
+                writer => return writer
+            }
+        }
+    }
+}
+
+impl BatchWriter {
+    /// Applies the queued writes until the index is shut down or dropped, committing what is pending either way.
+    fn run(mut self, requests: Receiver<WriteRequest>) {
+        loop {
+            let request = match self.pending_since {
+                Some(since) => requests.recv_timeout(self.commit_interval().checked_sub(since.elapsed()).unwrap_or_default()),
+                None => requests.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            };
+
+            match request {
+                Ok(WriteRequest::Write { documents, write }) => {
+                    self.apply(documents, write);
+                    if self.pending >= self.settings.commit_batch_size {
+                        self.commit();
+                    }
+                },
+                Ok(WriteRequest::Commit(done)) => {
+                    done.send(self.commit()).ok();
+                },
+                Ok(WriteRequest::Shutdown(done)) => {
+                    let committed = self.release();
+                    done.send(committed).ok();
+                    return;
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    self.commit();
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.release();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn commit_interval(&self) -> Duration {
+        Duration::from_millis(self.settings.commit_interval_ms)
+    }
+
+    fn apply(&mut self, documents: usize, write: Write) {
+        if self.writer.is_none() {
+            match self.inner.writer() {
+                Ok(writer) => self.writer = Some(writer),
+                Err(e) => {
+                    println!("Failed to update vehicle index with error {:?}", e);
+                    self.dropped += 1;
+                    return;
+                }
+            }
+        }
+
+        if let Some(writer) = self.writer.as_mut() {
+            write(&self.inner.fields, writer);
+            self.pending += documents;
+            self.pending_since.get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// Commits the pending writes and reloads the reader, so that searches see them. `false` when they or earlier
+    /// writes since the last commit are lost, a failed commit being rolled back.
+    fn commit(&mut self) -> bool {
+        let dropped = std::mem::take(&mut self.dropped);
+        self.pending = 0;
+        if self.pending_since.take().is_none() {
+            return dropped == 0;
+        }
+
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return false
+        };
+        match writer.commit().and_then(|_| self.inner.reader.reload()) {
+            Ok(_) => dropped == 0,
+            Err(e) => {
+                println!("Failed to commit vehicle index with error {:?}", e);
+                if let Err(e) = writer.rollback() {
+                    println!("Failed to roll back vehicle index with error {:?}", e);
+                }
+                false
+            }
+        }
+    }
+
+    /// Commits, then drops the writer once its merges are done, which releases the lock of the index directory.
+    fn release(&mut self) -> bool {
+        let committed = self.commit();
+
+        match self.writer.take().map(IndexWriter::wait_merging_threads) {
+            Some(Err(e)) => {
+                println!("Failed to merge vehicle index with error {:?}", e);
+                committed
+            },
+            _ => committed
+        }
+    }
+}
+
+#[async_trait]
+impl VehicleIndex for TantivyVehicleIndex {
+    async fn index(&self, vehicles: Vec<Vehicle>) -> Option<()> {
+        self.queue(WriteRequest::Write { documents: vehicles.len(), write: Box::new(move |fields, writer| {
+            for vehicle in &vehicles {
+                writer.delete_term(Term::from_field_text(fields.vehicle_id, &vehicle.vehicle_id.to_string()));
+                writer.add_document(doc!(
+                    fields.user_id => vehicle.user_id.to_string(),
+                    fields.vehicle_id => vehicle.vehicle_id.to_string(),
+                    fields.name => vehicle.name.as_str(),
+                    fields.brand => vehicle.brand.as_str(),
+                    fields.model => vehicle.model.as_str()
+                ));
+            }
+        })})
+    }
+
+    async fn search(&self, user_id: Uuid, query: &str, limit: usize) -> Option<Vec<Uuid>> {
+        let words = words(query);
+
+        self.blocking("search", move |inner| {
+            let fields = &inner.fields;
+
+            let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec!(
+                (Occur::Must, Box::new(TermQuery::new(Term::from_field_text(fields.user_id, &user_id.to_string()), IndexRecordOption::Basic)))
+            );
+            for word in &words {
+                let distance = if word.chars().count() >= FUZZY_WORD_LENGTH { 1 } else { 0 };
+                let any_field: Vec<(Occur, Box<dyn Query>)> = [fields.name, fields.brand, fields.model].iter()
+                    .map(|field| -> (Occur, Box<dyn Query>) {
+                        (Occur::Should, Box::new(FuzzyTermQuery::new_prefix(Term::from_field_text(*field, word), distance, true)))
+                    })
+                    .collect();
+                clauses.push((Occur::Must, Box::new(BooleanQuery::new(any_field))));
+            }
+
+            let searcher = inner.reader.searcher();
+            let mut vehicle_ids = Vec::new();
+            for (_, address) in searcher.search(&BooleanQuery::new(clauses), &TopDocs::with_limit(limit))? {
+                let vehicle_id = searcher.doc(address)?
+                    .get_first(fields.vehicle_id)
+                    .and_then(|value| value.text())
+                    .and_then(|vehicle_id| Uuid::parse_str(vehicle_id).ok());
+                vehicle_ids.extend(vehicle_id);
+            }
+
+            Ok(vehicle_ids)
+        }).await
+    }
+
+    async fn remove_vehicle(&self, vehicle_id: Uuid) -> Option<()> {
+        self.queue(WriteRequest::Write { documents: 1, write: Box::new(move |fields, writer| {
+            writer.delete_term(Term::from_field_text(fields.vehicle_id, &vehicle_id.to_string()));
+        })})
+    }
+
+    async fn remove_user(&self, user_id: Uuid) -> Option<()> {
+        self.queue(WriteRequest::Write { documents: 1, write: Box::new(move |fields, writer| {
+            writer.delete_term(Term::from_field_text(fields.user_id, &user_id.to_string()));
+        })})?;
+        self.commit().await
+    }
+
+    async fn clear(&self) -> Option<()> {
+        self.queue(WriteRequest::Write { documents: 1, write: Box::new(|_, writer| {
+            writer.delete_all_documents().ok();
+        })})?;
+        self.commit().await
+    }
+}
+
+fn schema() -> (Schema, Fields) {
+    let mut builder = Schema::builder();
+
+    let fields = Fields {
+        user_id: builder.add_text_field("user_id", STRING),
+        vehicle_id: builder.add_text_field("vehicle_id", STRING | STORED),
+        name: builder.add_text_field("name", TEXT),
+        brand: builder.add_text_field("brand", TEXT),
+        model: builder.add_text_field("model", TEXT)
+    };
+
+    (builder.build(), fields)
+}
+
+/// Lowercased words of a query, split the way the default tokenizer splits indexed text.
+fn words(query: &str) -> Vec<String> {
+    query.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn when_search_words_of_brand_and_model_then_returns_vehicle() {
+        let index = fixture::index(SearchSettings::default());
+        let vehicle = fixture::vehicle(fixture::user_id(), "test vehicle 2", "Time", "rtm");
+
+        aw!(index.index(vec!(vehicle.clone(), fixture::vehicle(fixture::user_id(), "commuter", "Brompton", "M6L")))).unwrap();
+        aw!(index.commit()).unwrap();
+
+        assert_eq!(Some(vec!(vehicle.vehicle_id)), aw!(index.search(fixture::user_id(), "time rtm", 10)));
+    }
+
+    #[test]
+    fn given_prefix_or_typo_when_search_then_returns_vehicle() {
+        let index = fixture::index(SearchSettings::default());
+        let vehicle = fixture::vehicle(fixture::user_id(), "Weekend ride", "Specialized", "Tarmac");
+
+        aw!(index.index(vec!(vehicle.clone()))).unwrap();
+        aw!(index.commit()).unwrap();
+
+        assert_eq!(Some(vec!(vehicle.vehicle_id)), aw!(index.search(fixture::user_id(), "speci", 10)));
+        assert_eq!(Some(vec!(vehicle.vehicle_id)), aw!(index.search(fixture::user_id(), "tarmak", 10)));
+        assert_eq!(Some(vec!()), aw!(index.search(fixture::user_id(), "tarmac sl8", 10)));
+    }
+
+    #[test]
+    fn given_vehicle_of_another_user_when_search_then_returns_nothing() {
+        let index = fixture::index(SearchSettings::default());
+
+        aw!(index.index(vec!(fixture::vehicle(Uuid::new_v4(), "test vehicle", "Time", "rtm")))).unwrap();
+        aw!(index.commit()).unwrap();
+
+        assert_eq!(Some(vec!()), aw!(index.search(fixture::user_id(), "time", 10)));
+    }
+
+    #[test]
+    fn given_vehicle_indexed_again_when_search_then_returns_only_its_latest_version() {
+        let index = fixture::index(SearchSettings::default());
+        let vehicle = fixture::vehicle(fixture::user_id(), "test vehicle", "Time", "rtm");
+        let renamed = Vehicle { model: "Scylon".to_string(), ..vehicle.clone() };
+
+        aw!(index.index(vec!(vehicle))).unwrap();
+        aw!(index.index(vec!(renamed.clone()))).unwrap();
+        aw!(index.commit()).unwrap();
+
+        assert_eq!(Some(vec!()), aw!(index.search(fixture::user_id(), "rtm", 10)));
+        assert_eq!(Some(vec!(renamed.vehicle_id)), aw!(index.search(fixture::user_id(), "scylon", 10)));
+
+        aw!(index.clear()).unwrap();
+        assert_eq!(Some(vec!()), aw!(index.search(fixture::user_id(), "scylon", 10)));
+    }
+
+    #[test]
+    fn when_remove_vehicle_then_search_no_longer_returns_it() {
+        let index = fixture::index(SearchSettings::default());
+        let vehicle = fixture::vehicle(fixture::user_id(), "test vehicle", "Time", "rtm");
+        let other_vehicle = fixture::vehicle(fixture::user_id(), "test vehicle", "Time", "vxrs");
+
+        aw!(index.index(vec!(vehicle.clone(), other_vehicle.clone()))).unwrap();
+        aw!(index.remove_vehicle(vehicle.vehicle_id)).unwrap();
+        aw!(index.commit()).unwrap();
+
+        assert_eq!(Some(vec!(other_vehicle.vehicle_id)), aw!(index.search(fixture::user_id(), "time", 10)));
+    }
+
+    #[test]
+    fn when_remove_user_then_search_returns_only_vehicles_of_other_users() {
+        let index = fixture::index(SearchSettings::default());
+        let other_user_id = Uuid::new_v4();
+        let other_vehicle = fixture::vehicle(other_user_id, "test vehicle", "Time", "rtm");
+
+        aw!(index.index(vec!(fixture::vehicle(fixture::user_id(), "test vehicle", "Time", "rtm"), other_vehicle.clone()))).unwrap();
+        aw!(index.remove_user(fixture::user_id())).unwrap();
+
+        assert_eq!(Some(vec!()), aw!(index.search(fixture::user_id(), "time", 10)));
+        assert_eq!(Some(vec!(other_vehicle.vehicle_id)), aw!(index.search(other_user_id, "time", 10)));
+    }
+
+    #[test]
+    fn given_writes_queued_when_commit_interval_passes_then_search_sees_them() {
+        let index = fixture::index(SearchSettings { commit_interval_ms: 10, commit_batch_size: 1000 });
+        let vehicle = fixture::vehicle(fixture::user_id(), "test vehicle", "Time", "rtm");
+
+        aw!(index.index(vec!(vehicle.clone()))).unwrap();
+
+        assert!(fixture::eventually(|| aw!(index.search(fixture::user_id(), "time", 10)) == Some(vec!(vehicle.vehicle_id))));
+    }
+
+    #[test]
+    fn given_batch_size_reached_when_index_then_commits_without_waiting_for_interval() {
+        let index = fixture::index(SearchSettings { commit_interval_ms: 600_000, commit_batch_size: 2 });
+        let vehicle = fixture::vehicle(fixture::user_id(), "test vehicle", "Time", "rtm");
+
+        aw!(index.index(vec!(vehicle.clone()))).unwrap();
+        assert_eq!(Some(vec!()), aw!(index.search(fixture::user_id(), "time", 10)));
+        aw!(index.index(vec!(fixture::vehicle(fixture::user_id(), "commuter", "Brompton", "M6L")))).unwrap();
+
+        assert!(fixture::eventually(|| aw!(index.search(fixture::user_id(), "time", 10)) == Some(vec!(vehicle.vehicle_id))));
+    }
+
+    #[test]
+    fn given_index_shut_down_when_another_opens_its_directory_then_it_can_write() {
+        let dir = std::env::temp_dir().join(format!("vehicle-index-{}", Uuid::new_v4()));
+        let server_index = TantivyVehicleIndex::open(&dir, SearchSettings::default()).unwrap();
+        let command_index = TantivyVehicleIndex::open(&dir, SearchSettings::default()).unwrap();
+        let vehicle = fixture::vehicle(fixture::user_id(), "test vehicle", "Time", "rtm");
+
+        aw!(server_index.index(vec!(fixture::vehicle(fixture::user_id(), "commuter", "Brompton", "M6L")))).unwrap();
+        aw!(server_index.shutdown()).unwrap();
+        aw!(command_index.clear()).unwrap();
+        aw!(command_index.index(vec!(vehicle.clone()))).unwrap();
+        aw!(command_index.shutdown()).unwrap();
+
+        assert_eq!(Some(vec!(vehicle.vehicle_id)), aw!(command_index.search(fixture::user_id(), "time", 10)));
+        assert_eq!(Some(vec!()), aw!(command_index.search(fixture::user_id(), "brompton", 10)));
+        assert_eq!(None, aw!(server_index.index(vec!(vehicle))));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub fn index(settings: SearchSettings) -> TantivyVehicleIndex {
+            TantivyVehicleIndex::in_memory(settings).unwrap()
+        }
+
+        /// Whether `condition` holds within a second, for what the writer thread commits on its own.
+        pub fn eventually<F: Fn() -> bool>(condition: F) -> bool {
+            (0..100).any(|_| condition() || { thread::sleep(std::time::Duration::from_millis(10)); false })
+        }
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str("a906615e-2e6a-4edb-9377-5a6b8544791b").unwrap()
+        }
+
+        pub fn vehicle(user_id: Uuid, name: &str, brand: &str, model: &str) -> Vehicle {
+            Vehicle {
+                name: name.to_string(),
+                user_id,
+                vehicle_id: Uuid::new_v4(),
+                created_at: Duration::seconds(5),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: brand.to_string(),
+                model: model.to_string(),
+                distance: 10,
+                owner_since: NaiveDate::from_ymd(2015, 12, 2),
+                manufacturing_date: NaiveDate::from_ymd(2015, 12, 1),
+                picture: None
+            }
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use rocket::serde::uuid::Uuid;
+
+use crate::domain::vehicle::Vehicle;
+
+/// Full-text index over the name, brand and model of vehicles, a vehicle being indexed once under its
+/// current owner. It is kept in step by the services writing vehicles and can be rebuilt from Cassandra.
+#[async_trait]
+pub trait VehicleIndex {
+    /// Adds vehicles, replacing any earlier version of them.
+    async fn index(&self, vehicles: Vec<Vehicle>) -> Option<()>;
+    /// Ids of the vehicles of `user_id` matching every word of `query`, best match first.
+    async fn search(&self, user_id: Uuid, query: &str, limit: usize) -> Option<Vec<Uuid>>;
+    async fn remove_vehicle(&self, vehicle_id: Uuid) -> Option<()>;
+    /// Removes every vehicle of a user.
+    async fn remove_user(&self, user_id: Uuid) -> Option<()>;
+    async fn clear(&self) -> Option<()>;
+}
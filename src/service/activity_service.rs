@@ -0,0 +1,401 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use rocket::serde::uuid::Uuid;
+use mockall::automock;
+use sha2::{Digest, Sha256};
+
+use crate::repository::activity_repository::ActivityRepository;
+use crate::repository::vehicle_repository::VehicleRepository;
+use crate::mapper::{activity_mapper, vehicle_history_mapper};
+use crate::domain::activity::Activity;
+use crate::domain::vehicle::Vehicle;
+use crate::dto::activity_dto::ActivityDTO;
+use crate::parser::activity_file;
+use crate::parser::track::{self, ParseError};
+
+const METERS_PER_KILOMETER: f64 = 1000.0;
+/// Compare-and-set attempts at adding to a distance before giving up on an import racing with other writes.
+const MAX_DISTANCE_ATTEMPTS: usize = 5;
+/// Actor of the vehicle versions saved by activity imports adding to the distance of the vehicle.
+pub const ACTIVITY_IMPORT_ACTOR: &str = "activity_import";
+
+#[derive(Debug, PartialEq)]
+pub enum ImportError {
+    VehicleNotFound,
+    UnsupportedFormat,
+    InvalidFile(String),
+    Duplicate,
+    StorageFailure
+}
+
+impl From<ParseError> for ImportError {
+    fn from(error: ParseError) -> Self {
+        match error {
+            ParseError::UnsupportedFormat => ImportError::UnsupportedFormat,
+            ParseError::Malformed(reason) => ImportError::InvalidFile(reason),
+            ParseError::NoTrackPoints => ImportError::InvalidFile("no track points found".to_string())
+        }
+    }
+}
+
+pub struct ActivityService {
+    activity_repository: Arc<dyn ActivityRepository + Sync + Send>,
+    vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+}
+
+#[automock]
+impl ActivityService {
+    pub fn new(activity_repository: Arc<dyn ActivityRepository + Sync + Send>,
+               vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>) -> ActivityService {
+        ActivityService {
+            activity_repository,
+            vehicle_repository
+        }
+    }
+
+    /// Parses a GPX or FIT file, stores it as an activity of the vehicle and adds its distance to the vehicle.
+    /// The meters of the activities of a vehicle are summed on their own, `Vehicle::distance` gaining the kilometres
+    /// the sum rounds to, so that short activities add up. The activity is removed again when its distance cannot
+    /// be added, so the file can be imported anew.
+    pub async fn import_activity(&self, user_id: Uuid, vehicle_id: Uuid, data: Vec<u8>) -> Result<ActivityDTO, ImportError> {
+        self.vehicle_repository.get_vehicle(user_id, vehicle_id).await
+            .ok_or(ImportError::VehicleNotFound)?;
+
+        let (file_format, points) = activity_file::parse(&data)?;
+        let summary = track::summarize(&points)?;
+
+        let file_hash = format!("{:x}", Sha256::digest(&data));
+        let activity_id = Uuid::new_v4();
+
+        match self.activity_repository.claim_file_hash(user_id, &file_hash, activity_id).await {
+            Some(true) => {},
+            Some(false) => return Err(ImportError::Duplicate),
+            None => return Err(ImportError::StorageFailure)
+        }
+
+        let activity = Activity {
+            user_id,
+            vehicle_id,
+            activity_id,
+            file_hash: file_hash.clone(),
+            file_format: file_format.as_str().to_string(),
+            started_at: summary.started_at.map(|t| Duration::seconds(t.timestamp())),
+            duration: summary.duration,
+            distance: summary.distance,
+            elevation_gain: summary.elevation_gain,
+            created_at: Duration::seconds(Utc::now().timestamp())
+        };
+
+        let activity = match self.activity_repository.save_activity(activity).await {
+            Some(activity) => activity,
+            None => {
+                self.activity_repository.release_file_hash(user_id, &file_hash).await;
+                return Err(ImportError::StorageFailure);
+            }
+        };
+
+        if let Err(e) = self.add_distance(&activity).await {
+            self.activity_repository.delete_activity(&activity).await;
+            self.activity_repository.release_file_hash(user_id, &file_hash).await;
+            return Err(e);
+        }
+
+        Ok(activity_mapper::get_activity_dto(activity))
+    }
+}
+
+impl ActivityService {
+    async fn add_distance(&self, activity: &Activity) -> Result<(), ImportError> {
+        let meters = activity.distance.round() as i64;
+        let (before, after) = self.add_meters(activity.user_id, activity.vehicle_id, meters).await
+            .ok_or(ImportError::StorageFailure)?;
+
+        let kilometers = kilometers(after) - kilometers(before);
+        if kilometers == 0 {
+            return Ok(());
+        }
+
+        let added = self.add_kilometers(activity.user_id, activity.vehicle_id, kilometers).await;
+        if added.is_err() && self.add_meters(activity.user_id, activity.vehicle_id, -meters).await.is_none() {
+            println!("Meters of Activity {} stay counted for Vehicle {}", activity.activity_id, activity.vehicle_id);
+        }
+        added
+    }
+
+    /// Adds to the meters of the activities of a vehicle, returning them before and after.
+    async fn add_meters(&self, user_id: Uuid, vehicle_id: Uuid, meters: i64) -> Option<(i64, i64)> {
+        for _ in 0..MAX_DISTANCE_ATTEMPTS {
+            let previous = self.activity_repository.get_distance(user_id, vehicle_id).await?;
+            let before = previous.unwrap_or(0);
+
+            if self.activity_repository.set_distance(user_id, vehicle_id, previous, before + meters).await? {
+                return Some((before, before + meters));
+            }
+        }
+        None
+    }
+
+    /// Adds to the distance of the vehicle as last read, reading it again whenever another write changed it.
+    async fn add_kilometers(&self, user_id: Uuid, vehicle_id: Uuid, kilometers: i32) -> Result<(), ImportError> {
+        for _ in 0..MAX_DISTANCE_ATTEMPTS {
+            let previous = self.vehicle_repository.get_vehicle(user_id, vehicle_id).await
+                .ok_or(ImportError::VehicleNotFound)?;
+            let vehicle = Vehicle { distance: previous.distance + kilometers, ..previous.clone() };
+            let change = vehicle_history_mapper::get_vehicle_change(Some(&previous), &vehicle, ACTIVITY_IMPORT_ACTOR, None);
+
+            if self.vehicle_repository.update_distance(&previous, vehicle, change).await.ok_or(ImportError::StorageFailure)? {
+                return Ok(());
+            }
+        }
+        Err(ImportError::StorageFailure)
+    }
+}
+
+/// Kilometres a sum of meters amounts to, rounded only here so that no meter is lost.
+fn kilometers(meters: i64) -> i32 {
+    (meters as f64 / METERS_PER_KILOMETER).round() as i32
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use mockall::mock;
+    use chrono::NaiveDate;
+
+    use crate::domain::vehicle_event::VehicleEventKind;
+    use crate::domain::vehicle_history::VehicleChange;
+    use crate::service::vehicle_service::tests::MockVehicleRepositoryImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    mock! {
+        pub ActivityRepositoryImpl {}
+
+        #[async_trait]
+        impl ActivityRepository for ActivityRepositoryImpl {
+            async fn claim_file_hash(&self, user_id: Uuid, file_hash: &str, activity_id: Uuid) -> Option<bool>;
+            async fn release_file_hash(&self, user_id: Uuid, file_hash: &str);
+            async fn save_activity(&self, activity: Activity) -> Option<Activity>;
+            async fn delete_activity(&self, activity: &Activity);
+            async fn get_distance(&self, user_id: Uuid, vehicle_id: Uuid) -> Option<Option<i64>>;
+            async fn set_distance(&self, user_id: Uuid, vehicle_id: Uuid, previous: Option<i64>, distance: i64) -> Option<bool>;
+        }
+    }
+
+    #[test]
+    fn when_import_activity_then_activity_is_stored_and_vehicle_distance_increased() {
+        let mut activity_repository = MockActivityRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(2)
+            .returning(move |_, _| Some(fixture::vehicle()));
+        activity_repository.expect_claim_file_hash()
+            .withf(|_, file_hash: &str, _| file_hash.len() == 64)
+            .times(1)
+            .returning(move |_, _, _| Some(true));
+        activity_repository.expect_save_activity()
+            .withf(|activity: &Activity| activity.file_format == "gpx" && activity.duration == 600)
+            .times(1)
+            .returning(move |activity| Some(activity));
+        activity_repository.expect_get_distance()
+            .times(1)
+            .returning(move |_, _| Some(Some(400)));
+        activity_repository.expect_set_distance()
+            .withf(|_, _, previous: &Option<i64>, distance: &i64| *previous == Some(400) && *distance == 400 + 3336)
+            .times(1)
+            .returning(move |_, _, _, _| Some(true));
+        vehicle_repository.expect_update_distance()
+            .withf(|previous: &Vehicle, vehicle: &Vehicle, change: &VehicleChange| previous.distance == fixture::VEHICLE_DISTANCE
+                && vehicle.distance == fixture::VEHICLE_DISTANCE + 4
+                && change.event == VehicleEventKind::Updated
+                && change.version.actor == ACTIVITY_IMPORT_ACTOR
+                && change.version.diff.contains("distance"))
+            .times(1)
+            .returning(move |_, _, _| Some(true));
+
+        let activity_service = ActivityService::new(Arc::new(activity_repository), Arc::new(vehicle_repository));
+
+        let activity_dto = aw!(activity_service.import_activity(fixture::user_id(), fixture::vehicle_id(), fixture::GPX.as_bytes().to_vec())).unwrap();
+
+        assert_eq!(fixture::user_id(), activity_dto.user_id);
+        assert_eq!(fixture::vehicle_id(), activity_dto.vehicle_id);
+        assert_eq!(600, activity_dto.duration);
+        assert!((activity_dto.distance - 3335.85).abs() < 1.0);
+        assert!((activity_dto.elevation_gain - 25.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn given_unknown_vehicle_when_import_activity_then_returns_vehicle_not_found() {
+        let activity_repository = MockActivityRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| None);
+
+        let activity_service = ActivityService::new(Arc::new(activity_repository), Arc::new(vehicle_repository));
+
+        let result = aw!(activity_service.import_activity(fixture::user_id(), fixture::vehicle_id(), fixture::GPX.as_bytes().to_vec()));
+
+        assert_eq!(Err(ImportError::VehicleNotFound), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_unsupported_file_when_import_activity_then_returns_unsupported_format() {
+        let activity_repository = MockActivityRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle()));
+
+        let activity_service = ActivityService::new(Arc::new(activity_repository), Arc::new(vehicle_repository));
+
+        let result = aw!(activity_service.import_activity(fixture::user_id(), fixture::vehicle_id(), b"not a track".to_vec()));
+
+        assert_eq!(Err(ImportError::UnsupportedFormat), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_already_imported_file_when_import_activity_then_returns_duplicate() {
+        let mut activity_repository = MockActivityRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle()));
+        activity_repository.expect_claim_file_hash()
+            .times(1)
+            .returning(move |_, _, _| Some(false));
+        activity_repository.expect_save_activity().times(0);
+        vehicle_repository.expect_update_distance().times(0);
+
+        let activity_service = ActivityService::new(Arc::new(activity_repository), Arc::new(vehicle_repository));
+
+        let result = aw!(activity_service.import_activity(fixture::user_id(), fixture::vehicle_id(), fixture::GPX.as_bytes().to_vec()));
+
+        assert_eq!(Err(ImportError::Duplicate), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_save_failure_when_import_activity_then_releases_hash_and_returns_storage_failure() {
+        let mut activity_repository = MockActivityRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle()));
+        activity_repository.expect_claim_file_hash()
+            .times(1)
+            .returning(move |_, _, _| Some(true));
+        activity_repository.expect_save_activity()
+            .times(1)
+            .returning(move |_| None);
+        activity_repository.expect_release_file_hash()
+            .times(1)
+            .returning(move |_, _| ());
+        vehicle_repository.expect_update_distance().times(0);
+
+        let activity_service = ActivityService::new(Arc::new(activity_repository), Arc::new(vehicle_repository));
+
+        let result = aw!(activity_service.import_activity(fixture::user_id(), fixture::vehicle_id(), fixture::GPX.as_bytes().to_vec()));
+
+        assert_eq!(Err(ImportError::StorageFailure), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_vehicle_update_failure_when_import_activity_then_removes_activity_and_its_meters_and_releases_hash() {
+        let mut activity_repository = MockActivityRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(2)
+            .returning(move |_, _| Some(fixture::vehicle()));
+        activity_repository.expect_claim_file_hash()
+            .times(1)
+            .returning(move |_, _, _| Some(true));
+        activity_repository.expect_save_activity()
+            .times(1)
+            .returning(move |activity| Some(activity));
+        let distance = Arc::new(std::sync::Mutex::new(None));
+        let read_distance = distance.clone();
+        activity_repository.expect_get_distance()
+            .times(2)
+            .returning(move |_, _| Some(*read_distance.lock().unwrap()));
+        let set_distance = distance.clone();
+        activity_repository.expect_set_distance()
+            .times(2)
+            .returning(move |_, _, _, meters| {
+                *set_distance.lock().unwrap() = Some(meters);
+                Some(true)
+            });
+        vehicle_repository.expect_update_distance()
+            .times(1)
+            .returning(move |_, _, _| None);
+        activity_repository.expect_delete_activity()
+            .times(1)
+            .returning(move |_| ());
+        activity_repository.expect_release_file_hash()
+            .times(1)
+            .returning(move |_, _| ());
+
+        let activity_service = ActivityService::new(Arc::new(activity_repository), Arc::new(vehicle_repository));
+
+        let result = aw!(activity_service.import_activity(fixture::user_id(), fixture::vehicle_id(), fixture::GPX.as_bytes().to_vec()));
+
+        assert_eq!(Err(ImportError::StorageFailure), result.map(|_| ()));
+        assert_eq!(Some(0), *distance.lock().unwrap());
+    }
+
+    #[test]
+    fn when_kilometers_then_rounds_sum_of_meters() {
+        assert_eq!(0, kilometers(499));
+        assert_eq!(1, kilometers(499 + 300));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const VEHICLE_DISTANCE: i32 = 100;
+        pub const GPX: &str = r#"<gpx version="1.1"><trk><trkseg>
+            <trkpt lat="0.0" lon="0.00"><ele>100</ele><time>2021-06-01T08:00:00Z</time></trkpt>
+            <trkpt lat="0.0" lon="0.01"><ele>120</ele><time>2021-06-01T08:03:00Z</time></trkpt>
+            <trkpt lat="0.0" lon="0.02"><ele>110</ele><time>2021-06-01T08:06:00Z</time></trkpt>
+            <trkpt lat="0.0" lon="0.03"><ele>115</ele><time>2021-06-01T08:10:00Z</time></trkpt>
+            </trkseg></trk></gpx>"#;
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn vehicle() -> Vehicle {
+            Vehicle {
+                name: "the vehicle name".to_string(),
+                user_id: user_id(),
+                vehicle_id: vehicle_id(),
+                created_at: Duration::seconds(5),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: VEHICLE_DISTANCE,
+                owner_since: NaiveDate::from_num_days_from_ce(15),
+                manufacturing_date: NaiveDate::from_num_days_from_ce(15),
+                picture: None
+            }
+        }
+    }
+}
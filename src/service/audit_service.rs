@@ -0,0 +1,349 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rocket::serde::Deserialize;
+use rocket::tokio;
+use mockall::automock;
+
+use crate::domain::audit::{AuditEntry, AuditFilter, AuditTargets, AuditedRequest, AUDIT_SHARDS};
+use crate::dto::audit_dto::AuditEntryDTO;
+use crate::mapper::audit_mapper;
+use crate::repository::audit_repository::AuditRepository;
+
+/// Entries read from a shard of a day of the audit log at a time while looking for those matching a filter.
+const AUDIT_PAGE_SIZE: usize = 500;
+/// Actor of the entries of background jobs, which no request started.
+pub const JOB_ACTOR: &str = "system";
+/// Method of the entries of background jobs, their route being the name of the job.
+pub const JOB_METHOD: &str = "JOB";
+pub const TRASH_PURGE_JOB: &str = "trash_purge";
+pub const USER_ERASURE_JOB: &str = "user_erasure";
+
+/// Audit settings read from the `audit` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct AuditSettings {
+    pub enabled             : bool,
+    /// Longest time range a query of the audit log may span, each day of it being a partition to read.
+    pub max_range_days      : i64,
+    /// Entries waiting to be written, past which new ones are dropped and logged.
+    pub queue_capacity      : usize,
+    pub write_interval_ms   : u64
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        AuditSettings {
+            enabled: true,
+            max_range_days: 31,
+            queue_capacity: 10_000,
+            write_interval_ms: 1000
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuditError {
+    InvalidRange,
+    StorageFailure
+}
+
+/// Entries are queued in memory when recorded, so that answering a request never waits on Cassandra, and written
+/// by `write_periodically`. An entry that fails to be written stays queued and is retried on the next run; those
+/// still queued when the instance stops are lost.
+pub struct AuditService {
+    audit_repository: Arc<dyn AuditRepository + Sync + Send>,
+    pending: Mutex<VecDeque<AuditEntry>>,
+    settings: AuditSettings
+}
+
+#[automock]
+impl AuditService {
+    pub fn new(audit_repository: Arc<dyn AuditRepository + Sync + Send>, settings: AuditSettings) -> AuditService {
+        AuditService {
+            audit_repository,
+            pending: Mutex::new(VecDeque::new()),
+            settings
+        }
+    }
+
+    /// Queues a request answered with `status` now for the audit log, unless it is disabled.
+    pub fn record(&self, request: AuditedRequest, targets: AuditTargets, status: u16) {
+        if !self.settings.enabled {
+            return;
+        }
+        let entry = audit_mapper::get_audit_entry(request, &targets, status, Utc::now());
+
+        let mut pending = self.pending.lock().unwrap();
+        if pending.len() >= self.settings.queue_capacity {
+            println!("Dropped AuditEntry {:?}, {} entries waiting to be written", entry, pending.len());
+            return;
+        }
+        pending.push_back(entry);
+    }
+
+    /// Queues a run of a background job for the audit log, under the `system` actor and the name of the job as
+    /// its route, along with the keys of what it erased.
+    pub fn record_job(&self, job: &str, run_id: String, targets: AuditTargets, succeeded: bool) {
+        let request = AuditedRequest {
+            request_id: run_id,
+            actor: JOB_ACTOR.to_string(),
+            ip: None,
+            method: JOB_METHOD.to_string(),
+            route: job.to_string()
+        };
+
+        self.record(request, targets, if succeeded { 200 } else { 500 });
+    }
+
+    /// Writes the queued entries, oldest first, stopping at the first failure, which is retried on the next run.
+    /// Answers the number of entries written.
+    pub async fn write_pending(&self) -> usize {
+        let mut written = 0;
+
+        loop {
+            let entry = match self.pending.lock().unwrap().pop_front() {
+                Some(entry) => entry,
+                None => return written
+            };
+
+            if self.audit_repository.insert_entry(&entry).await.is_none() {
+                self.pending.lock().unwrap().push_front(entry);
+                return written;
+            }
+            written += 1;
+        }
+    }
+
+    /// Latest entries between `from` and `to` matching the filter, newest first. Each day is read from all
+    /// its shards, whose matches are merged newest first.
+    pub async fn search(&self, filter: AuditFilter, from: DateTime<Utc>, to: DateTime<Utc>, limit: usize) -> Result<Vec<AuditEntryDTO>, AuditError> {
+        if from > to || to - from > Duration::days(self.settings.max_range_days) {
+            return Err(AuditError::InvalidRange);
+        }
+
+        let (from_ms, to_ms) = (from.timestamp_millis(), to.timestamp_millis());
+        let mut found = Vec::new();
+        let mut day = to.naive_utc().date();
+
+        while day >= from.naive_utc().date() && found.len() < limit {
+            let mut found_on_day = Vec::new();
+            for shard in 0..AUDIT_SHARDS {
+                found_on_day.extend(self.search_shard(&filter, day, shard, from_ms, to_ms, limit - found.len()).await?);
+            }
+            found_on_day.sort_by(|a, b| (b.occurred_at_ms, b.audit_id).cmp(&(a.occurred_at_ms, a.audit_id)));
+
+            found.extend(found_on_day);
+            day = day.pred();
+        }
+        found.truncate(limit);
+
+        Ok(found.into_iter().map(audit_mapper::get_audit_entry_dto).collect())
+    }
+}
+
+impl AuditService {
+    /// Latest `limit` entries of a shard of a day matching the filter, newest first.
+    async fn search_shard(&self, filter: &AuditFilter, day: NaiveDate, shard: i32, from_ms: i64, to_ms: i64, limit: usize) -> Result<Vec<AuditEntry>, AuditError> {
+        let mut found = Vec::new();
+        let mut before = None;
+
+        loop {
+            let entries = self.audit_repository.get_entries(day, shard, from_ms, to_ms, before, AUDIT_PAGE_SIZE).await
+                .ok_or(AuditError::StorageFailure)?;
+            let is_last_page = entries.len() < AUDIT_PAGE_SIZE;
+
+            before = entries.last().map(|entry| (entry.occurred_at_ms, entry.audit_id));
+            found.extend(entries.into_iter().filter(|entry| filter.matches(entry)));
+
+            if is_last_page || found.len() >= limit {
+                found.truncate(limit);
+                return Ok(found);
+            }
+        }
+    }
+}
+
+/// Writes the queued entries every `write_interval_ms` until the instance stops.
+pub async fn write_periodically(audit_service: Arc<AuditService>, settings: AuditSettings) {
+    let interval = StdDuration::from_millis(settings.write_interval_ms);
+
+    loop {
+        audit_service.write_pending().await;
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+    use mockall::mock;
+    use rocket::serde::uuid::Uuid;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    mock! {
+        pub AuditRepositoryImpl {}
+
+        #[async_trait]
+        impl AuditRepository for AuditRepositoryImpl {
+            async fn insert_entry(&self, entry: &AuditEntry) -> Option<()>;
+            async fn get_entries(&self, day: NaiveDate, shard: i32, from_ms: i64, to_ms: i64, before: Option<(i64, Uuid)>, limit: usize) -> Option<Vec<AuditEntry>>;
+        }
+    }
+
+    #[test]
+    fn given_range_over_two_days_when_search_then_reads_both_days_newest_first_and_filters() {
+        let mut audit_repository = MockAuditRepositoryImpl::new();
+
+        audit_repository.expect_get_entries()
+            .withf(|day: &NaiveDate, _, _, _, before: &Option<(i64, Uuid)>, _| *day == NaiveDate::from_ymd(2021, 5, 2) && before.is_none())
+            .times(AUDIT_SHARDS as usize)
+            .returning(|day, shard, _, _, _, _| Some(if shard == 0 { vec!(fixture::entry(day, "jane"), fixture::entry(day, "john")) } else { vec!() }));
+        audit_repository.expect_get_entries()
+            .withf(|day: &NaiveDate, _, _, _, before: &Option<(i64, Uuid)>, _| *day == NaiveDate::from_ymd(2021, 5, 1) && before.is_none())
+            .times(AUDIT_SHARDS as usize)
+            .returning(|day, shard, _, _, _, _| Some(if shard == 5 { vec!(fixture::entry(day, "jane")) } else { vec!() }));
+
+        let audit_service = AuditService::new(Arc::new(audit_repository), AuditSettings::default());
+
+        let filter = AuditFilter { actor: Some("jane".to_string()), target: Some(fixture::VEHICLE_ID_STR.to_string()) };
+        let entries = aw!(audit_service.search(filter, Utc.ymd(2021, 5, 1).and_hms(12, 0, 0), Utc.ymd(2021, 5, 2).and_hms(12, 0, 0), 10)).unwrap();
+
+        assert_eq!(vec!("2021-05-02", "2021-05-01"), entries.iter().map(|entry| entry.occurred_at.format("%Y-%m-%d").to_string()).collect::<Vec<String>>());
+    }
+
+    #[test]
+    fn given_entries_in_several_shards_when_search_then_merges_them_newest_first() {
+        let mut audit_repository = MockAuditRepositoryImpl::new();
+
+        audit_repository.expect_get_entries()
+            .times(AUDIT_SHARDS as usize)
+            .returning(|day, shard, _, _, _, _| Some(match shard {
+                1 => vec!(fixture::entry_at(day, "jane", 11), fixture::entry_at(day, "jane", 8)),
+                6 => vec!(fixture::entry_at(day, "jane", 10)),
+                _ => vec!()
+            }));
+
+        let audit_service = AuditService::new(Arc::new(audit_repository), AuditSettings::default());
+
+        let entries = aw!(audit_service.search(AuditFilter::default(), Utc.ymd(2021, 5, 1).and_hms(0, 0, 0), Utc.ymd(2021, 5, 1).and_hms(23, 0, 0), 2)).unwrap();
+
+        assert_eq!(vec!(11, 10), entries.iter().map(|entry| entry.occurred_at.format("%H").to_string().parse::<i32>().unwrap()).collect::<Vec<i32>>());
+    }
+
+    #[test]
+    fn given_failed_insert_when_write_pending_then_keeps_it_queued_for_the_next_run() {
+        let mut audit_repository = MockAuditRepositoryImpl::new();
+        audit_repository.expect_insert_entry()
+            .withf(|entry: &AuditEntry| entry.request_id == "first")
+            .times(1)
+            .returning(|_| Some(()));
+        audit_repository.expect_insert_entry()
+            .withf(|entry: &AuditEntry| entry.request_id == "second")
+            .times(2)
+            .returning({
+                let mut attempts = 0;
+                move |_| {
+                    attempts += 1;
+                    if attempts == 1 { None } else { Some(()) }
+                }
+            });
+
+        let audit_service = AuditService::new(Arc::new(audit_repository), AuditSettings::default());
+        audit_service.record(fixture::request("first"), AuditTargets::new(), 201);
+        audit_service.record(fixture::request("second"), AuditTargets::new(), 201);
+
+        assert_eq!(1, aw!(audit_service.write_pending()));
+        assert_eq!(1, aw!(audit_service.write_pending()));
+        assert_eq!(0, aw!(audit_service.write_pending()));
+    }
+
+    #[test]
+    fn given_full_queue_when_record_then_drops_the_entry() {
+        let mut audit_repository = MockAuditRepositoryImpl::new();
+        audit_repository.expect_insert_entry()
+            .withf(|entry: &AuditEntry| entry.request_id == "first")
+            .times(1)
+            .returning(|_| Some(()));
+
+        let audit_service = AuditService::new(Arc::new(audit_repository), AuditSettings { queue_capacity: 1, ..AuditSettings::default() });
+        audit_service.record(fixture::request("first"), AuditTargets::new(), 201);
+        audit_service.record(fixture::request("second"), AuditTargets::new(), 201);
+
+        assert_eq!(1, aw!(audit_service.write_pending()));
+    }
+
+    #[test]
+    fn when_record_job_then_queues_it_under_the_system_actor_with_its_outcome() {
+        let mut audit_repository = MockAuditRepositoryImpl::new();
+        audit_repository.expect_insert_entry()
+            .withf(|entry: &AuditEntry| (entry.actor.as_str(), entry.method.as_str(), entry.route.as_str(), entry.status, entry.outcome.as_str())
+                == (JOB_ACTOR, JOB_METHOD, TRASH_PURGE_JOB, 500, "failed"))
+            .times(1)
+            .returning(|_| Some(()));
+
+        let audit_service = AuditService::new(Arc::new(audit_repository), AuditSettings::default());
+        audit_service.record_job(TRASH_PURGE_JOB, "the run".to_string(), AuditTargets::new(), false);
+
+        assert_eq!(1, aw!(audit_service.write_pending()));
+    }
+
+    #[test]
+    fn given_range_over_max_days_when_search_then_returns_invalid_range() {
+        let mut audit_repository = MockAuditRepositoryImpl::new();
+        audit_repository.expect_get_entries()
+            .times(0);
+
+        let audit_service = AuditService::new(Arc::new(audit_repository), AuditSettings { max_range_days: 1, ..AuditSettings::default() });
+
+        let result = aw!(audit_service.search(AuditFilter::default(), Utc.ymd(2021, 5, 1).and_hms(0, 0, 0), Utc.ymd(2021, 5, 3).and_hms(0, 0, 0), 10));
+
+        assert_eq!(Err(AuditError::InvalidRange), result);
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+
+        pub fn request(request_id: &str) -> AuditedRequest {
+            AuditedRequest {
+                request_id: request_id.to_string(),
+                actor: "jane".to_string(),
+                ip: None,
+                method: "POST".to_string(),
+                route: "/api/v1/vehicle".to_string()
+            }
+        }
+
+        pub fn entry(day: NaiveDate, actor: &str) -> AuditEntry {
+            entry_at(day, actor, 10)
+        }
+
+        pub fn entry_at(day: NaiveDate, actor: &str, hour: u32) -> AuditEntry {
+            AuditEntry {
+                day,
+                shard: 0,
+                occurred_at_ms: Utc.from_utc_date(&day).and_hms(hour, 0, 0).timestamp_millis(),
+                audit_id: Uuid::new_v4(),
+                request_id: "the request".to_string(),
+                actor: actor.to_string(),
+                ip: None,
+                method: "POST".to_string(),
+                route: "/api/v1/vehicle".to_string(),
+                targets: format!("{{\"vehicle_id\":[\"{}\"]}}", VEHICLE_ID_STR),
+                status: 201,
+                outcome: "succeeded".to_string()
+            }
+        }
+    }
+}
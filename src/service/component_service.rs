@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use rocket::serde::uuid::Uuid;
+use mockall::automock;
+
+use crate::repository::component_repository::ComponentRepository;
+use crate::repository::vehicle_repository::VehicleRepository;
+use crate::mapper::component_mapper;
+use crate::domain::component::Component;
+use crate::dto::component_dto::{ComponentDTO, WearStatus};
+
+#[derive(Debug, PartialEq)]
+pub enum ComponentError {
+    ComponentNotFound,
+    VehicleNotFound,
+    StorageFailure
+}
+
+pub struct ComponentService {
+    component_repository: Arc<dyn ComponentRepository + Sync + Send>,
+    vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+}
+
+#[automock]
+impl ComponentService {
+    pub fn new(component_repository: Arc<dyn ComponentRepository + Sync + Send>,
+               vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>) -> ComponentService {
+        ComponentService {
+            component_repository,
+            vehicle_repository
+        }
+    }
+
+    pub async fn get_components(&self, user_id: Uuid) -> Vec<ComponentDTO> {
+        let components = self.component_repository.get_components(user_id).await;
+
+        self.to_dtos(user_id, components).await
+    }
+
+    pub async fn get_vehicle_components(&self, user_id: Uuid, vehicle_id: Uuid) -> Vec<ComponentDTO> {
+        let components = self.component_repository.get_components(user_id).await
+            .into_iter()
+            .filter(|component| component.vehicle_id == Some(vehicle_id))
+            .collect();
+
+        self.to_dtos(user_id, components).await
+    }
+
+    /// Components of the user whose wear status is `warning` or `worn`.
+    pub async fn get_alerts(&self, user_id: Uuid) -> Vec<ComponentDTO> {
+        self.get_components(user_id).await
+            .into_iter()
+            .filter(|component| component.wear_status != WearStatus::Ok)
+            .collect()
+    }
+
+    pub async fn save_component(&self, user_id: Uuid, component_dto: ComponentDTO) -> Result<ComponentDTO, ComponentError> {
+        let vehicle_id = component_dto.vehicle_id;
+        let mut component = component_mapper::get_component(user_id, component_dto);
+        component.vehicle_id = None;
+        component.installed_distance = None;
+
+        match vehicle_id {
+            Some(vehicle_id) => self.install(component, vehicle_id).await,
+            None => self.store(component, None).await
+        }
+    }
+
+    /// Fits the component on `vehicle_id`, first removing it from the vehicle it was installed on, if any.
+    pub async fn install_component(&self, user_id: Uuid, component_id: Uuid, vehicle_id: Uuid) -> Result<ComponentDTO, ComponentError> {
+        let component = self.component_repository.get_component(user_id, component_id).await
+            .ok_or(ComponentError::ComponentNotFound)?;
+
+        let component = self.detach(component).await?;
+
+        self.install(component, vehicle_id).await
+    }
+
+    pub async fn remove_component(&self, user_id: Uuid, component_id: Uuid) -> Result<ComponentDTO, ComponentError> {
+        let component = self.component_repository.get_component(user_id, component_id).await
+            .ok_or(ComponentError::ComponentNotFound)?;
+
+        let component = self.detach(component).await?;
+
+        self.store(component, None).await
+    }
+}
+
+impl ComponentService {
+    async fn install(&self, mut component: Component, vehicle_id: Uuid) -> Result<ComponentDTO, ComponentError> {
+        let vehicle = self.vehicle_repository.get_vehicle(component.user_id, vehicle_id).await
+            .ok_or(ComponentError::VehicleNotFound)?;
+
+        component.vehicle_id = Some(vehicle_id);
+        component.installed_at = Some(Duration::seconds(Utc::now().timestamp()));
+        component.removed_at = None;
+        component.installed_distance = Some(vehicle.distance);
+
+        self.store(component, Some(vehicle.distance)).await
+    }
+
+    /// Folds the distance ridden on the current vehicle into `accumulated_distance` and unlinks the vehicle.
+    /// Fails when the odometer of that vehicle can't be read, rather than losing the distance ridden on it.
+    async fn detach(&self, mut component: Component) -> Result<Component, ComponentError> {
+        if let Some(vehicle_id) = component.vehicle_id {
+            let odometer = self.vehicle_repository.get_vehicle(component.user_id, vehicle_id).await
+                .map(|vehicle| vehicle.distance)
+                .ok_or(ComponentError::VehicleNotFound)?;
+
+            component.accumulated_distance = component_mapper::component_distance(&component, Some(odometer));
+            component.vehicle_id = None;
+            component.installed_distance = None;
+            component.removed_at = Some(Duration::seconds(Utc::now().timestamp()));
+        }
+
+        Ok(component)
+    }
+
+    async fn store(&self, component: Component, vehicle_distance: Option<i32>) -> Result<ComponentDTO, ComponentError> {
+        self.component_repository.save_component(component).await
+            .map(|component| component_mapper::get_component_dto(component, vehicle_distance))
+            .ok_or(ComponentError::StorageFailure)
+    }
+
+    async fn to_dtos(&self, user_id: Uuid, components: Vec<Component>) -> Vec<ComponentDTO> {
+        let mut odometers: HashMap<Uuid, Option<i32>> = HashMap::new();
+
+        for vehicle_id in components.iter().filter_map(|component| component.vehicle_id) {
+            if !odometers.contains_key(&vehicle_id) {
+                let odometer = self.vehicle_repository.get_vehicle(user_id, vehicle_id).await
+                    .map(|vehicle| vehicle.distance);
+                odometers.insert(vehicle_id, odometer);
+            }
+        }
+
+        components.into_iter()
+            .map(|component| {
+                let odometer = component.vehicle_id.and_then(|vehicle_id| odometers.get(&vehicle_id).copied().flatten());
+                component_mapper::get_component_dto(component, odometer)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use mockall::mock;
+    use chrono::NaiveDate;
+
+    use crate::domain::vehicle::Vehicle;
+    use crate::service::vehicle_service::tests::MockVehicleRepositoryImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    mock! {
+        pub ComponentRepositoryImpl {}
+
+        #[async_trait]
+        impl ComponentRepository for ComponentRepositoryImpl {
+            async fn get_component(&self, user_id: Uuid, component_id: Uuid) -> Option<Component>;
+            async fn get_components(&self, user_id: Uuid) -> Vec<Component>;
+            async fn save_component(&self, component: Component) -> Option<Component>;
+        }
+    }
+
+    #[test]
+    fn given_component_on_other_vehicle_when_install_component_then_accumulates_distance_and_moves_it() {
+        let mut component_repository = MockComponentRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        component_repository.expect_get_component()
+            .times(1)
+            .returning(move |_, _| Some(fixture::component(Some(fixture::old_vehicle_id()), Some(1000), 200)));
+        vehicle_repository.expect_get_vehicle()
+            .withf(|_, vehicle_id: &Uuid| vehicle_id == &fixture::old_vehicle_id())
+            .times(1)
+            .returning(move |_, vehicle_id| Some(fixture::vehicle(vehicle_id, 1800)));
+        vehicle_repository.expect_get_vehicle()
+            .withf(|_, vehicle_id: &Uuid| vehicle_id == &fixture::new_vehicle_id())
+            .times(1)
+            .returning(move |_, vehicle_id| Some(fixture::vehicle(vehicle_id, 5000)));
+        component_repository.expect_save_component()
+            .withf(|component: &Component| component.accumulated_distance == 1000
+                && component.installed_distance == Some(5000)
+                && component.vehicle_id == Some(fixture::new_vehicle_id()))
+            .times(1)
+            .returning(move |component| Some(component));
+
+        let component_service = ComponentService::new(Arc::new(component_repository), Arc::new(vehicle_repository));
+
+        let component_dto = aw!(component_service.install_component(fixture::user_id(), fixture::component_id(), fixture::new_vehicle_id())).unwrap();
+
+        assert_eq!(Some(fixture::new_vehicle_id()), component_dto.vehicle_id);
+        assert_eq!(1000, component_dto.distance);
+        assert!(component_dto.removed_at.is_none());
+    }
+
+    #[test]
+    fn given_installed_component_when_remove_component_then_accumulates_distance() {
+        let mut component_repository = MockComponentRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        component_repository.expect_get_component()
+            .times(1)
+            .returning(move |_, _| Some(fixture::component(Some(fixture::old_vehicle_id()), Some(1000), 0)));
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, vehicle_id| Some(fixture::vehicle(vehicle_id, 3900)));
+        component_repository.expect_save_component()
+            .withf(|component: &Component| component.vehicle_id.is_none() && component.removed_at.is_some())
+            .times(1)
+            .returning(move |component| Some(component));
+
+        let component_service = ComponentService::new(Arc::new(component_repository), Arc::new(vehicle_repository));
+
+        let component_dto = aw!(component_service.remove_component(fixture::user_id(), fixture::component_id())).unwrap();
+
+        assert_eq!(2900, component_dto.distance);
+        assert_eq!(WearStatus::Warning, component_dto.wear_status);
+    }
+
+    #[test]
+    fn given_installed_on_missing_vehicle_when_remove_component_then_returns_vehicle_not_found_and_keeps_component() {
+        let mut component_repository = MockComponentRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        component_repository.expect_get_component()
+            .times(1)
+            .returning(move |_, _| Some(fixture::component(Some(fixture::old_vehicle_id()), Some(1000), 0)));
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| None);
+        component_repository.expect_save_component()
+            .times(0);
+
+        let component_service = ComponentService::new(Arc::new(component_repository), Arc::new(vehicle_repository));
+
+        let result = aw!(component_service.remove_component(fixture::user_id(), fixture::component_id()));
+
+        assert_eq!(Err(ComponentError::VehicleNotFound), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_dto_with_existing_component_id_when_save_component_then_stores_new_component() {
+        let mut component_repository = MockComponentRepositoryImpl::new();
+
+        component_repository.expect_save_component()
+            .withf(|component: &Component| component.component_id != fixture::component_id())
+            .times(1)
+            .returning(move |component| Some(component));
+
+        let component_service = ComponentService::new(Arc::new(component_repository), Arc::new(MockVehicleRepositoryImpl::new()));
+        let component_dto = component_mapper::get_component_dto(fixture::component(None, None, 10), None);
+
+        let component_dto = aw!(component_service.save_component(fixture::user_id(), component_dto)).unwrap();
+
+        assert_ne!(Some(fixture::component_id()), component_dto.component_id);
+    }
+
+    #[test]
+    fn given_unknown_component_when_install_component_then_returns_component_not_found() {
+        let mut component_repository = MockComponentRepositoryImpl::new();
+
+        component_repository.expect_get_component()
+            .times(1)
+            .returning(move |_, _| None);
+
+        let component_service = ComponentService::new(Arc::new(component_repository), Arc::new(MockVehicleRepositoryImpl::new()));
+
+        let result = aw!(component_service.install_component(fixture::user_id(), fixture::component_id(), fixture::new_vehicle_id()));
+
+        assert_eq!(Err(ComponentError::ComponentNotFound), result.map(|_| ()));
+    }
+
+    #[test]
+    fn when_get_alerts_then_returns_only_worn_or_warning_components() {
+        let mut component_repository = MockComponentRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        component_repository.expect_get_components()
+            .times(1)
+            .returning(move |_| vec!(
+                fixture::component(Some(fixture::old_vehicle_id()), Some(1000), 0),
+                fixture::component(None, None, 3500),
+                fixture::component(None, None, 10)));
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, vehicle_id| Some(fixture::vehicle(vehicle_id, 1100)));
+
+        let component_service = ComponentService::new(Arc::new(component_repository), Arc::new(vehicle_repository));
+
+        let alerts = aw!(component_service.get_alerts(fixture::user_id()));
+
+        assert_eq!(1, alerts.len());
+        assert_eq!(WearStatus::Worn, alerts[0].wear_status);
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const COMPONENT_ID_STR: &str = "2c5e8f1a-9b3d-4e6f-8a1b-3c5d7e9f1a2b";
+        pub const OLD_VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const NEW_VEHICLE_ID_STR: &str = "60e18f00-34b8-4a52-916c-adbb0204618e";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn component_id() -> Uuid {
+            Uuid::parse_str(COMPONENT_ID_STR).unwrap()
+        }
+
+        pub fn old_vehicle_id() -> Uuid {
+            Uuid::parse_str(OLD_VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn new_vehicle_id() -> Uuid {
+            Uuid::parse_str(NEW_VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn component(vehicle_id: Option<Uuid>, installed_distance: Option<i32>, accumulated_distance: i32) -> Component {
+            Component {
+                user_id: user_id(),
+                component_id: component_id(),
+                vehicle_id,
+                component_type: "chain".to_string(),
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                installed_at: Some(Duration::seconds(5)),
+                removed_at: None,
+                installed_distance,
+                accumulated_distance,
+                wear_limit: Some(3000)
+            }
+        }
+
+        pub fn vehicle(vehicle_id: Uuid, distance: i32) -> Vehicle {
+            Vehicle {
+                name: "the vehicle name".to_string(),
+                user_id: user_id(),
+                vehicle_id,
+                created_at: Duration::seconds(5),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance,
+                owner_since: NaiveDate::from_ymd(2020, 1, 1),
+                manufacturing_date: NaiveDate::from_ymd(2019, 1, 1),
+                picture: None
+            }
+        }
+    }
+}
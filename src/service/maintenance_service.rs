@@ -0,0 +1,451 @@
+use std::sync::Arc;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use rocket::serde::uuid::Uuid;
+use mockall::automock;
+
+use crate::repository::maintenance_repository::MaintenanceRepository;
+use crate::repository::vehicle_repository::VehicleRepository;
+use crate::mapper::maintenance_mapper;
+use crate::domain::maintenance::{MaintenanceRecord, ReminderRule};
+use crate::dto::maintenance_dto::{MaintenanceRecordDTO, ReminderDTO, ReminderRuleDTO, ReminderStatus};
+
+/// A reminder becomes due this many days before its date limit.
+const DUE_SOON_DAYS: i64 = 30;
+/// A reminder becomes due once this fraction of its distance interval is left.
+const DUE_SOON_DISTANCE_RATIO: f64 = 0.1;
+/// Longest intervals a rule may have, so the due distance and date always stay in range.
+const MAX_INTERVAL_DISTANCE: i32 = 1_000_000;
+const MAX_INTERVAL_MONTHS: i32 = 1200;
+
+#[derive(Debug, PartialEq)]
+pub enum MaintenanceError {
+    VehicleNotFound,
+    InvalidRule,
+    StorageFailure
+}
+
+pub struct MaintenanceService {
+    maintenance_repository: Arc<dyn MaintenanceRepository + Sync + Send>,
+    vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+}
+
+#[automock]
+impl MaintenanceService {
+    pub fn new(maintenance_repository: Arc<dyn MaintenanceRepository + Sync + Send>,
+               vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>) -> MaintenanceService {
+        MaintenanceService {
+            maintenance_repository,
+            vehicle_repository
+        }
+    }
+
+    pub async fn get_records(&self, user_id: Uuid, vehicle_id: Uuid) -> Vec<MaintenanceRecordDTO> {
+        self.maintenance_repository.get_records(user_id, vehicle_id).await
+            .into_iter()
+            .map(maintenance_mapper::get_record_dto)
+            .collect()
+    }
+
+    pub async fn save_record(&self, user_id: Uuid, vehicle_id: Uuid, record_dto: MaintenanceRecordDTO) -> Result<MaintenanceRecordDTO, MaintenanceError> {
+        self.vehicle_repository.get_vehicle(user_id, vehicle_id).await
+            .ok_or(MaintenanceError::VehicleNotFound)?;
+
+        let record = maintenance_mapper::get_record(user_id, vehicle_id, record_dto);
+
+        self.maintenance_repository.save_record(record).await
+            .map(maintenance_mapper::get_record_dto)
+            .ok_or(MaintenanceError::StorageFailure)
+    }
+
+    pub async fn get_rules(&self, user_id: Uuid, vehicle_id: Uuid) -> Vec<ReminderRuleDTO> {
+        self.maintenance_repository.get_rules(user_id, vehicle_id).await
+            .into_iter()
+            .map(maintenance_mapper::get_rule_dto)
+            .collect()
+    }
+
+    pub async fn save_rule(&self, user_id: Uuid, vehicle_id: Uuid, rule_dto: ReminderRuleDTO) -> Result<ReminderRuleDTO, MaintenanceError> {
+        let valid_distance = rule_dto.interval_distance.map_or(true, |d| d > 0 && d <= MAX_INTERVAL_DISTANCE);
+        let valid_months = rule_dto.interval_months.map_or(true, |m| m > 0 && m <= MAX_INTERVAL_MONTHS);
+        let has_interval = rule_dto.interval_distance.is_some() || rule_dto.interval_months.is_some();
+
+        if !(valid_distance && valid_months && has_interval) {
+            return Err(MaintenanceError::InvalidRule);
+        }
+
+        self.vehicle_repository.get_vehicle(user_id, vehicle_id).await
+            .ok_or(MaintenanceError::VehicleNotFound)?;
+
+        let rule = maintenance_mapper::get_rule(user_id, vehicle_id, rule_dto);
+
+        self.maintenance_repository.save_rule(rule).await
+            .map(maintenance_mapper::get_rule_dto)
+            .ok_or(MaintenanceError::StorageFailure)
+    }
+
+    /// Lists the reminders that are due or overdue on `today`, measured from the last record
+    /// linked to each rule or, when the rule was never serviced, from `manufacturing_date` at distance 0,
+    /// the only date the odometer is known for. Fails with `InvalidRule` when a due date or distance is out of range.
+    pub async fn get_reminders(&self, user_id: Uuid, vehicle_id: Uuid, today: NaiveDate) -> Result<Vec<ReminderDTO>, MaintenanceError> {
+        let vehicle = self.vehicle_repository.get_vehicle(user_id, vehicle_id).await
+            .ok_or(MaintenanceError::VehicleNotFound)?;
+
+        let records = self.maintenance_repository.get_records(user_id, vehicle_id).await;
+        let rules = self.maintenance_repository.get_rules(user_id, vehicle_id).await;
+
+        let mut reminders = vec!();
+        for rule in rules {
+            let last_service = records.iter()
+                .filter(|record| record.rule_id == Some(rule.rule_id))
+                .max_by_key(|record| record.performed_on);
+
+            if let Some(reminder) = evaluate_rule(rule, last_service, vehicle.distance, vehicle.manufacturing_date, today)? {
+                reminders.push(reminder);
+            }
+        }
+
+        Ok(reminders)
+    }
+}
+
+fn evaluate_rule(rule: ReminderRule, last_service: Option<&MaintenanceRecord>, distance: i32, manufacturing_date: NaiveDate, today: NaiveDate) -> Result<Option<ReminderDTO>, MaintenanceError> {
+    let (base_odometer, base_date) = last_service
+        .map(|record| (record.odometer, record.performed_on))
+        .unwrap_or((0, manufacturing_date));
+
+    let due_distance = rule.interval_distance
+        .map(|interval| base_odometer.checked_add(interval).ok_or(MaintenanceError::InvalidRule))
+        .transpose()?;
+    let due_date = rule.interval_months
+        .map(|months| add_months(base_date, months).ok_or(MaintenanceError::InvalidRule))
+        .transpose()?;
+    let remaining_distance = due_distance
+        .map(|due| due.checked_sub(distance).ok_or(MaintenanceError::InvalidRule))
+        .transpose()?;
+    let due_soon_date = today.checked_add_signed(Duration::days(DUE_SOON_DAYS))
+        .ok_or(MaintenanceError::InvalidRule)?;
+
+    let overdue = remaining_distance.map_or(false, |remaining| remaining <= 0)
+        || due_date.map_or(false, |date| today >= date);
+    let due_soon = rule.interval_distance.zip(remaining_distance)
+        .map_or(false, |(interval, remaining)| (remaining as f64) <= interval as f64 * DUE_SOON_DISTANCE_RATIO)
+        || due_date.map_or(false, |date| due_soon_date >= date);
+
+    let status = if overdue {
+        ReminderStatus::Overdue
+    } else if due_soon {
+        ReminderStatus::Due
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(ReminderDTO {
+        rule_id: rule.rule_id,
+        description: rule.description,
+        status,
+        due_distance,
+        due_date,
+        remaining_distance
+    }))
+}
+
+/// Adds calendar months, clamping the day to the last day of the target month. None when out of the date range.
+fn add_months(date: NaiveDate, months: i32) -> Option<NaiveDate> {
+    let total = (date.year() * 12 + date.month0() as i32).checked_add(months)?;
+    let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+    let first_of_month = date.with_day(1)?.with_month(1)?.with_year(year)?.with_month(month)?;
+
+    (0..4).find_map(|offset| first_of_month.with_day(date.day() - offset))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use mockall::mock;
+
+    use crate::domain::vehicle::Vehicle;
+    use crate::service::vehicle_service::tests::MockVehicleRepositoryImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    mock! {
+        pub MaintenanceRepositoryImpl {}
+
+        #[async_trait]
+        impl MaintenanceRepository for MaintenanceRepositoryImpl {
+            async fn get_records(&self, user_id: Uuid, vehicle_id: Uuid) -> Vec<MaintenanceRecord>;
+            async fn save_record(&self, record: MaintenanceRecord) -> Option<MaintenanceRecord>;
+            async fn get_rules(&self, user_id: Uuid, vehicle_id: Uuid) -> Vec<ReminderRule>;
+            async fn save_rule(&self, rule: ReminderRule) -> Option<ReminderRule>;
+        }
+    }
+
+    #[test]
+    fn when_add_months_then_clamps_to_end_of_month() {
+        assert_eq!(Some(NaiveDate::from_ymd(2022, 2, 28)), add_months(NaiveDate::from_ymd(2021, 1, 31), 13));
+        assert_eq!(Some(NaiveDate::from_ymd(2022, 1, 15)), add_months(NaiveDate::from_ymd(2021, 1, 15), 12));
+    }
+
+    #[test]
+    fn given_months_beyond_date_range_when_add_months_then_returns_none() {
+        assert_eq!(None, add_months(NaiveDate::from_ymd(2021, 1, 31), i32::MAX));
+    }
+
+    #[test]
+    fn given_stored_rule_out_of_range_when_get_reminders_then_returns_invalid_rule() {
+        let mut maintenance_repository = MockMaintenanceRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle(3500)));
+        maintenance_repository.expect_get_records()
+            .times(1)
+            .returning(move |_, _| vec!(fixture::record(3000, NaiveDate::from_ymd(2021, 5, 1))));
+        maintenance_repository.expect_get_rules()
+            .times(1)
+            .returning(move |_, _| vec!(fixture::rule(Some(i32::MAX), Some(i32::MAX))));
+
+        let maintenance_service = MaintenanceService::new(Arc::new(maintenance_repository), Arc::new(vehicle_repository));
+
+        let result = aw!(maintenance_service.get_reminders(fixture::user_id(), fixture::vehicle_id(), fixture::today()));
+
+        assert_eq!(Err(MaintenanceError::InvalidRule), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_rule_never_serviced_when_get_reminders_then_time_is_measured_from_manufacturing_date() {
+        let mut maintenance_repository = MockMaintenanceRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle(100)));
+        maintenance_repository.expect_get_records()
+            .times(1)
+            .returning(move |_, _| vec!());
+        maintenance_repository.expect_get_rules()
+            .times(1)
+            .returning(move |_, _| vec!(fixture::rule(Some(3000), Some(24))));
+
+        let maintenance_service = MaintenanceService::new(Arc::new(maintenance_repository), Arc::new(vehicle_repository));
+
+        let reminders = aw!(maintenance_service.get_reminders(fixture::user_id(), fixture::vehicle_id(), fixture::today())).unwrap();
+
+        assert_eq!(1, reminders.len());
+        assert_eq!(ReminderStatus::Overdue, reminders[0].status);
+        assert_eq!(Some(NaiveDate::from_ymd(2021, 1, 1)), reminders[0].due_date);
+    }
+
+    #[test]
+    fn given_rule_never_serviced_and_distance_exceeded_when_get_reminders_then_returns_overdue() {
+        let mut maintenance_repository = MockMaintenanceRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle(3500)));
+        maintenance_repository.expect_get_records()
+            .times(1)
+            .returning(move |_, _| vec!());
+        maintenance_repository.expect_get_rules()
+            .times(1)
+            .returning(move |_, _| vec!(fixture::rule(Some(3000), None)));
+
+        let maintenance_service = MaintenanceService::new(Arc::new(maintenance_repository), Arc::new(vehicle_repository));
+
+        let reminders = aw!(maintenance_service.get_reminders(fixture::user_id(), fixture::vehicle_id(), fixture::today())).unwrap();
+
+        assert_eq!(1, reminders.len());
+        assert_eq!(ReminderStatus::Overdue, reminders[0].status);
+        assert_eq!(Some(3000), reminders[0].due_distance);
+        assert_eq!(Some(-500), reminders[0].remaining_distance);
+    }
+
+    #[test]
+    fn given_recent_service_when_get_reminders_then_returns_nothing() {
+        let mut maintenance_repository = MockMaintenanceRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle(3500)));
+        maintenance_repository.expect_get_records()
+            .times(1)
+            .returning(move |_, _| vec!(fixture::record(3000, NaiveDate::from_ymd(2021, 5, 1))));
+        maintenance_repository.expect_get_rules()
+            .times(1)
+            .returning(move |_, _| vec!(fixture::rule(Some(3000), Some(12))));
+
+        let maintenance_service = MaintenanceService::new(Arc::new(maintenance_repository), Arc::new(vehicle_repository));
+
+        let reminders = aw!(maintenance_service.get_reminders(fixture::user_id(), fixture::vehicle_id(), fixture::today())).unwrap();
+
+        assert!(reminders.is_empty());
+    }
+
+    #[test]
+    fn given_date_limit_close_when_get_reminders_then_returns_due() {
+        let mut maintenance_repository = MockMaintenanceRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle(3100)));
+        maintenance_repository.expect_get_records()
+            .times(1)
+            .returning(move |_, _| vec!(fixture::record(3000, NaiveDate::from_ymd(2020, 6, 20))));
+        maintenance_repository.expect_get_rules()
+            .times(1)
+            .returning(move |_, _| vec!(fixture::rule(Some(3000), Some(12))));
+
+        let maintenance_service = MaintenanceService::new(Arc::new(maintenance_repository), Arc::new(vehicle_repository));
+
+        let reminders = aw!(maintenance_service.get_reminders(fixture::user_id(), fixture::vehicle_id(), fixture::today())).unwrap();
+
+        assert_eq!(1, reminders.len());
+        assert_eq!(ReminderStatus::Due, reminders[0].status);
+        assert_eq!(Some(NaiveDate::from_ymd(2021, 6, 20)), reminders[0].due_date);
+    }
+
+    #[test]
+    fn given_unknown_vehicle_when_get_reminders_then_returns_vehicle_not_found() {
+        let maintenance_repository = MockMaintenanceRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| None);
+
+        let maintenance_service = MaintenanceService::new(Arc::new(maintenance_repository), Arc::new(vehicle_repository));
+
+        let result = aw!(maintenance_service.get_reminders(fixture::user_id(), fixture::vehicle_id(), fixture::today()));
+
+        assert_eq!(Err(MaintenanceError::VehicleNotFound), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_rule_without_interval_when_save_rule_then_returns_invalid_rule() {
+        let maintenance_service = MaintenanceService::new(Arc::new(MockMaintenanceRepositoryImpl::new()), Arc::new(MockVehicleRepositoryImpl::new()));
+
+        let result = aw!(maintenance_service.save_rule(fixture::user_id(), fixture::vehicle_id(), ReminderRuleDTO {
+            rule_id: None,
+            description: "service".to_string(),
+            interval_distance: None,
+            interval_months: Some(0)
+        }));
+
+        assert_eq!(Err(MaintenanceError::InvalidRule), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_rule_with_interval_too_long_when_save_rule_then_returns_invalid_rule() {
+        let maintenance_service = MaintenanceService::new(Arc::new(MockMaintenanceRepositoryImpl::new()), Arc::new(MockVehicleRepositoryImpl::new()));
+
+        let result = aw!(maintenance_service.save_rule(fixture::user_id(), fixture::vehicle_id(), ReminderRuleDTO {
+            rule_id: None,
+            description: "service".to_string(),
+            interval_distance: Some(i32::MAX),
+            interval_months: Some(12)
+        }));
+
+        assert_eq!(Err(MaintenanceError::InvalidRule), result.map(|_| ()));
+    }
+
+    #[test]
+    fn when_save_record_then_record_is_stored() {
+        let mut maintenance_repository = MockMaintenanceRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle(3500)));
+        maintenance_repository.expect_save_record()
+            .withf(|record: &MaintenanceRecord| record.user_id == fixture::user_id() && record.odometer == 3400)
+            .times(1)
+            .returning(move |record| Some(record));
+
+        let maintenance_service = MaintenanceService::new(Arc::new(maintenance_repository), Arc::new(vehicle_repository));
+
+        let record_dto = aw!(maintenance_service.save_record(fixture::user_id(), fixture::vehicle_id(), MaintenanceRecordDTO {
+            record_id: None,
+            performed_on: fixture::today(),
+            odometer: 3400,
+            description: "chain".to_string(),
+            cost: 30.0,
+            parts: vec!(),
+            rule_id: None
+        })).unwrap();
+
+        assert!(record_dto.record_id.is_some());
+        assert_eq!(3400, record_dto.odometer);
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const RULE_ID_STR: &str = "7e9a2b1c-3d4e-4f50-9a6b-7c8d9e0f1a2b";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn today() -> NaiveDate {
+            NaiveDate::from_ymd(2021, 6, 1)
+        }
+
+        pub fn rule(interval_distance: Option<i32>, interval_months: Option<i32>) -> ReminderRule {
+            ReminderRule {
+                user_id: user_id(),
+                vehicle_id: vehicle_id(),
+                rule_id: Uuid::parse_str(RULE_ID_STR).unwrap(),
+                description: "service".to_string(),
+                interval_distance,
+                interval_months
+            }
+        }
+
+        pub fn record(odometer: i32, performed_on: NaiveDate) -> MaintenanceRecord {
+            MaintenanceRecord {
+                user_id: user_id(),
+                vehicle_id: vehicle_id(),
+                performed_on,
+                record_id: Uuid::new_v4(),
+                odometer,
+                description: "service".to_string(),
+                cost: 50.0,
+                parts: None,
+                rule_id: Some(Uuid::parse_str(RULE_ID_STR).unwrap())
+            }
+        }
+
+        pub fn vehicle(distance: i32) -> Vehicle {
+            Vehicle {
+                name: "the vehicle name".to_string(),
+                user_id: user_id(),
+                vehicle_id: vehicle_id(),
+                created_at: Duration::seconds(5),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance,
+                owner_since: NaiveDate::from_ymd(2020, 1, 1),
+                manufacturing_date: NaiveDate::from_ymd(2019, 1, 1),
+                picture: None
+            }
+        }
+    }
+}
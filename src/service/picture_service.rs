@@ -0,0 +1,330 @@
+use std::io::Cursor;
+use std::sync::Arc;
+
+use image::{ImageFormat, ImageOutputFormat};
+use image::io::Reader;
+use rocket::http::ContentType;
+use rocket::serde::Deserialize;
+use rocket::serde::uuid::Uuid;
+use rocket::tokio::task;
+use mockall::automock;
+
+use crate::domain::vehicle::Vehicle;
+use crate::repository::vehicle_repository::VehicleRepository;
+use crate::mapper::{vehicle_history_mapper, vehicle_mapper};
+use crate::storage::blob_store::{Blob, BlobStore};
+
+pub const MAX_PICTURE_BYTES: usize = 5 * 1024 * 1024;
+const THUMBNAIL_SIZE: u32 = 256;
+const THUMBNAIL_QUALITY: u8 = 80;
+const THUMBNAIL_NAME: &str = "thumbnail.jpg";
+
+/// Picture settings read from the `pictures` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct PictureSettings {
+    /// Largest width times height accepted, checked from the image header before it is decoded, since a small
+    /// compressed file can decode to gigabytes.
+    pub max_pixels          : u64
+}
+
+impl Default for PictureSettings {
+    fn default() -> Self {
+        PictureSettings {
+            max_pixels: 40_000_000
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PictureError {
+    VehicleNotFound,
+    TooLarge,
+    UnsupportedFormat,
+    InvalidImage,
+    StorageFailure
+}
+
+pub struct PictureService {
+    blob_store: Arc<dyn BlobStore + Sync + Send>,
+    vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+    settings: PictureSettings
+}
+
+#[automock]
+impl PictureService {
+    pub fn new(blob_store: Arc<dyn BlobStore + Sync + Send>,
+               vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+               settings: PictureSettings) -> PictureService {
+        PictureService {
+            blob_store,
+            vehicle_repository,
+            settings
+        }
+    }
+
+    /// Stores the picture and a JPEG thumbnail, points the vehicle `picture` at the new blob on behalf of `actor`
+    /// and returns the URL it is served from.
+    pub async fn upload_picture(&self, user_id: Uuid, vehicle_id: Uuid, data: Vec<u8>, actor: &str) -> Result<String, PictureError> {
+        if data.len() > MAX_PICTURE_BYTES {
+            return Err(PictureError::TooLarge);
+        }
+
+        let (extension, content_type) = match image::guess_format(&data) {
+            Ok(ImageFormat::Jpeg) => ("jpg", ContentType::JPEG),
+            Ok(ImageFormat::Png) => ("png", ContentType::PNG),
+            Ok(ImageFormat::Gif) => ("gif", ContentType::GIF),
+            Ok(ImageFormat::WebP) => ("webp", ContentType::WEBP),
+            _ => return Err(PictureError::UnsupportedFormat)
+        };
+
+        let previous = self.vehicle_repository.get_vehicle(user_id, vehicle_id).await
+            .ok_or(PictureError::VehicleNotFound)?;
+
+        let source = data.clone();
+        let max_pixels = self.settings.max_pixels;
+        let thumbnail = task::spawn_blocking(move || create_thumbnail(&source, max_pixels))
+            .await
+            .map_err(|_| PictureError::InvalidImage)??;
+
+        let key = format!("{}/{}/picture.{}", user_id, vehicle_id, extension);
+        let thumbnail_key = format!("{}/{}/{}", user_id, vehicle_id, THUMBNAIL_NAME);
+
+        self.blob_store.put(&key, data, content_type).await.ok_or(PictureError::StorageFailure)?;
+        self.blob_store.put(&thumbnail_key, thumbnail, ContentType::JPEG).await.ok_or(PictureError::StorageFailure)?;
+
+        let vehicle = Vehicle { picture: Some(key.clone()), ..previous.clone() };
+        let change = vehicle_history_mapper::get_vehicle_change(Some(&previous), &vehicle, actor, None);
+        self.vehicle_repository.save_vehicle(vehicle, change).await.ok_or(PictureError::StorageFailure)?;
+
+        if let Some(previous_key) = previous.picture.filter(|previous| previous != &key) {
+            self.blob_store.delete(&previous_key).await;
+        }
+
+        Ok(vehicle_mapper::picture_url(user_id, vehicle_id))
+    }
+
+    pub async fn get_picture(&self, user_id: Uuid, vehicle_id: Uuid, thumbnail: bool) -> Option<Blob> {
+        let key = self.vehicle_repository.get_vehicle(user_id, vehicle_id).await?.picture?;
+
+        let key = match thumbnail_key(&key) {
+            Some(thumbnail_key) if thumbnail => thumbnail_key,
+            _ => key
+        };
+
+        self.blob_store.get(&key).await
+    }
+}
+
+/// Key of the thumbnail living next to a picture, whose key keeps the original owner after a transfer.
+pub fn thumbnail_key(picture_key: &str) -> Option<String> {
+    picture_key.rsplit_once('/').map(|(directory, _)| format!("{}/{}", directory, THUMBNAIL_NAME))
+}
+
+/// Decodes the picture and encodes its thumbnail, `TooLarge` when its header announces more than `max_pixels`.
+fn create_thumbnail(data: &[u8], max_pixels: u64) -> Result<Vec<u8>, PictureError> {
+    let reader = Reader::new(Cursor::new(data)).with_guessed_format().map_err(|_| PictureError::InvalidImage)?;
+    let (width, height) = reader.into_dimensions().map_err(|_| PictureError::InvalidImage)?;
+    if width as u64 * height as u64 > max_pixels {
+        return Err(PictureError::TooLarge);
+    }
+
+    let image = image::load_from_memory(data).map_err(|_| PictureError::InvalidImage)?;
+
+    let mut thumbnail = Vec::new();
+    image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut thumbnail, ImageOutputFormat::Jpeg(THUMBNAIL_QUALITY))
+        .map_err(|_| PictureError::InvalidImage)?;
+
+    Ok(thumbnail)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use mockall::mock;
+    use chrono::{Duration, NaiveDate};
+    use image::{ImageBuffer, Rgb, DynamicImage};
+
+    use crate::domain::vehicle_event::VehicleEventKind;
+    use crate::domain::vehicle_history::VehicleChange;
+    use crate::service::vehicle_service::tests::MockVehicleRepositoryImpl;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    mock! {
+        pub BlobStoreImpl {}
+
+        #[async_trait]
+        impl BlobStore for BlobStoreImpl {
+            async fn put(&self, key: &str, data: Vec<u8>, content_type: ContentType) -> Option<()>;
+            async fn get(&self, key: &str) -> Option<Blob>;
+            async fn delete(&self, key: &str) -> Option<()>;
+        }
+    }
+
+    #[test]
+    fn when_upload_picture_then_stores_picture_and_thumbnail_and_updates_vehicle() {
+        let mut blob_store = MockBlobStoreImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle(Some("old/picture.jpg"))));
+        blob_store.expect_put()
+            .withf(|key: &str, _, content_type: &ContentType| key == fixture::PICTURE_KEY && content_type == &ContentType::PNG)
+            .times(1)
+            .returning(move |_, _, _| Some(()));
+        blob_store.expect_put()
+            .withf(|key: &str, data: &Vec<u8>, content_type: &ContentType| key == fixture::THUMBNAIL_KEY
+                && image::guess_format(data).ok() == Some(ImageFormat::Jpeg) && content_type == &ContentType::JPEG)
+            .times(1)
+            .returning(move |_, _, _| Some(()));
+        vehicle_repository.expect_save_vehicle()
+            .withf(|vehicle: &Vehicle, change: &VehicleChange| vehicle.picture == Some(fixture::PICTURE_KEY.to_string())
+                && change.event == VehicleEventKind::Updated && change.version.actor == "jane")
+            .times(1)
+            .returning(move |vehicle, _| Some(vehicle));
+        blob_store.expect_delete()
+            .withf(|key: &str| key == "old/picture.jpg")
+            .times(1)
+            .returning(move |_| Some(()));
+
+        let picture_service = PictureService::new(Arc::new(blob_store), Arc::new(vehicle_repository), PictureSettings::default());
+
+        let url = aw!(picture_service.upload_picture(fixture::user_id(), fixture::vehicle_id(), fixture::png(), "jane")).unwrap();
+
+        assert_eq!(vehicle_mapper::picture_url(fixture::user_id(), fixture::vehicle_id()), url);
+    }
+
+    #[test]
+    fn given_text_file_when_upload_picture_then_returns_unsupported_format() {
+        let picture_service = PictureService::new(Arc::new(MockBlobStoreImpl::new()), Arc::new(MockVehicleRepositoryImpl::new()), PictureSettings::default());
+
+        let result = aw!(picture_service.upload_picture(fixture::user_id(), fixture::vehicle_id(), b"plain text".to_vec(), "jane"));
+
+        assert_eq!(Err(PictureError::UnsupportedFormat), result);
+    }
+
+    #[test]
+    fn given_oversized_file_when_upload_picture_then_returns_too_large() {
+        let picture_service = PictureService::new(Arc::new(MockBlobStoreImpl::new()), Arc::new(MockVehicleRepositoryImpl::new()), PictureSettings::default());
+
+        let result = aw!(picture_service.upload_picture(fixture::user_id(), fixture::vehicle_id(), vec!(0; MAX_PICTURE_BYTES + 1), "jane"));
+
+        assert_eq!(Err(PictureError::TooLarge), result);
+    }
+
+    #[test]
+    fn given_picture_over_pixel_limit_when_upload_picture_then_returns_too_large_without_storing_it() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle(None)));
+
+        let picture_service = PictureService::new(Arc::new(MockBlobStoreImpl::new()), Arc::new(vehicle_repository),
+                                                  PictureSettings { max_pixels: 512 * 299 });
+
+        let result = aw!(picture_service.upload_picture(fixture::user_id(), fixture::vehicle_id(), fixture::png(), "jane"));
+
+        assert_eq!(Err(PictureError::TooLarge), result);
+    }
+
+    #[test]
+    fn given_truncated_png_when_upload_picture_then_returns_invalid_image() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle(None)));
+
+        let picture_service = PictureService::new(Arc::new(MockBlobStoreImpl::new()), Arc::new(vehicle_repository), PictureSettings::default());
+
+        let mut truncated = fixture::png();
+        truncated.truncate(20);
+
+        let result = aw!(picture_service.upload_picture(fixture::user_id(), fixture::vehicle_id(), truncated, "jane"));
+
+        assert_eq!(Err(PictureError::InvalidImage), result);
+    }
+
+    #[test]
+    fn when_get_picture_with_thumbnail_then_returns_thumbnail_blob() {
+        let mut blob_store = MockBlobStoreImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle(Some(fixture::PICTURE_KEY))));
+        blob_store.expect_get()
+            .withf(|key: &str| key == fixture::THUMBNAIL_KEY)
+            .times(1)
+            .returning(move |_| Some(Blob { data: vec!(1, 2, 3), content_type: ContentType::JPEG, etag: "\"etag\"".to_string() }));
+
+        let picture_service = PictureService::new(Arc::new(blob_store), Arc::new(vehicle_repository), PictureSettings::default());
+
+        let blob = aw!(picture_service.get_picture(fixture::user_id(), fixture::vehicle_id(), true)).unwrap();
+
+        assert_eq!(ContentType::JPEG, blob.content_type);
+    }
+
+    #[test]
+    fn given_vehicle_without_picture_when_get_picture_then_returns_none() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle(None)));
+
+        let picture_service = PictureService::new(Arc::new(MockBlobStoreImpl::new()), Arc::new(vehicle_repository), PictureSettings::default());
+
+        assert!(aw!(picture_service.get_picture(fixture::user_id(), fixture::vehicle_id(), false)).is_none());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const PICTURE_KEY: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b/88573010-cf4c-490e-9d29-f8517dc60b90/picture.png";
+        pub const THUMBNAIL_KEY: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b/88573010-cf4c-490e-9d29-f8517dc60b90/thumbnail.jpg";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn png() -> Vec<u8> {
+            let image = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(512, 300, Rgb([200u8, 30, 30])));
+            let mut data = Vec::new();
+            image.write_to(&mut data, ImageOutputFormat::Png).unwrap();
+            data
+        }
+
+        pub fn vehicle(picture: Option<&str>) -> Vehicle {
+            Vehicle {
+                name: "the vehicle name".to_string(),
+                user_id: user_id(),
+                vehicle_id: vehicle_id(),
+                created_at: Duration::seconds(5),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: 100,
+                owner_since: NaiveDate::from_ymd(2020, 1, 1),
+                manufacturing_date: NaiveDate::from_ymd(2019, 1, 1),
+                picture: picture.map(|p| p.to_string())
+            }
+        }
+    }
+}
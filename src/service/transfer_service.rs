@@ -0,0 +1,612 @@
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDate, Utc};
+use rocket::serde::uuid::Uuid;
+use mockall::automock;
+
+use crate::repository::transfer_repository::TransferRepository;
+use crate::repository::vehicle_repository::VehicleRepository;
+use crate::search::vehicle_index::VehicleIndex;
+use crate::mapper::{transfer_mapper, vehicle_history_mapper, vehicle_mapper};
+use crate::domain::transfer::{OwnershipRecord, TransferOffer, OFFER_ACCEPTED, OFFER_DECLINED, OFFER_PENDING};
+use crate::dto::transfer_dto::{OwnershipRecordDTO, TransferOfferDTO};
+use crate::dto::vehicle_dto::VehicleDTO;
+
+#[derive(Debug, PartialEq)]
+pub enum TransferError {
+    VehicleNotFound,
+    OfferNotFound,
+    InvalidRecipient,
+    OfferNotPending,
+    StorageFailure
+}
+
+pub struct TransferService {
+    transfer_repository: Arc<dyn TransferRepository + Sync + Send>,
+    vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+    vehicle_index: Arc<dyn VehicleIndex + Sync + Send>,
+}
+
+#[automock]
+impl TransferService {
+    pub fn new(transfer_repository: Arc<dyn TransferRepository + Sync + Send>,
+               vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+               vehicle_index: Arc<dyn VehicleIndex + Sync + Send>) -> TransferService {
+        TransferService {
+            transfer_repository,
+            vehicle_repository,
+            vehicle_index
+        }
+    }
+
+    pub async fn create_offer(&self, user_id: Uuid, vehicle_id: Uuid, to_user_id: Uuid) -> Result<TransferOfferDTO, TransferError> {
+        if user_id == to_user_id {
+            return Err(TransferError::InvalidRecipient);
+        }
+
+        self.vehicle_repository.get_vehicle(user_id, vehicle_id).await
+            .ok_or(TransferError::VehicleNotFound)?;
+
+        let offer = TransferOffer {
+            to_user_id,
+            offer_id: Uuid::new_v4(),
+            from_user_id: user_id,
+            vehicle_id,
+            status: OFFER_PENDING.to_string(),
+            created_at: Duration::seconds(Utc::now().timestamp())
+        };
+
+        self.transfer_repository.save_offer(offer).await
+            .map(transfer_mapper::get_offer_dto)
+            .ok_or(TransferError::StorageFailure)
+    }
+
+    /// Pending offers addressed to `user_id`.
+    pub async fn get_offers(&self, user_id: Uuid) -> Vec<TransferOfferDTO> {
+        self.transfer_repository.get_offers(user_id).await
+            .into_iter()
+            .filter(|offer| offer.status == OFFER_PENDING)
+            .map(transfer_mapper::get_offer_dto)
+            .collect()
+    }
+
+    /// Locks the vehicle for this offer, so a single offer of a vehicle can be accepted at a time, and claims the
+    /// offer with a compare-and-set on its status. The vehicle is read once both are held, then moved to the
+    /// recipient resetting `owner_since` to `today`, while the other offers of the vehicle are declined.
+    /// The claims are rolled back when the move fails.
+    /// The vehicle then reads as transferred by its previous owner and created by the recipient, and is versioned
+    /// as transferred by `actor` in the history of the recipient.
+    /// An acceptance cut short once its offer was claimed, by a stopped instance, leaves the offer accepted without
+    /// the vehicle moving. Accepting it again resumes it, the lock of the vehicle being taken again once it lapsed.
+    pub async fn accept_offer(&self, user_id: Uuid, offer_id: Uuid, today: NaiveDate, actor: &str) -> Result<VehicleDTO, TransferError> {
+        let offer = self.transfer_repository.get_offer(user_id, offer_id).await
+            .ok_or(TransferError::OfferNotFound)?;
+        let (from_user_id, vehicle_id) = (offer.from_user_id, offer.vehicle_id);
+        let resumed = match offer.status.as_str() {
+            OFFER_PENDING => false,
+            OFFER_ACCEPTED => true,
+            _ => return Err(TransferError::OfferNotPending)
+        };
+
+        if resumed && self.transferred_since(&offer).await {
+            return Err(TransferError::OfferNotPending);
+        }
+
+        match self.transfer_repository.claim_vehicle(vehicle_id, offer_id).await {
+            Some(true) => (),
+            Some(false) => return Err(TransferError::OfferNotPending),
+            None => return Err(TransferError::StorageFailure)
+        }
+
+        if !resumed {
+            if let Err(e) = self.claim(user_id, offer_id, OFFER_PENDING, OFFER_ACCEPTED).await {
+                self.transfer_repository.release_vehicle(vehicle_id, offer_id).await;
+                return Err(e);
+            }
+        }
+
+        // A vehicle cached before the claims may predate a transfer or a save, read it again from the store.
+        self.vehicle_repository.evict_vehicle(from_user_id, vehicle_id).await;
+
+        let previous = match self.vehicle_repository.get_vehicle(from_user_id, vehicle_id).await {
+            Some(previous) => previous,
+            None => {
+                self.unclaim(user_id, offer_id, vehicle_id, resumed).await;
+                return Err(TransferError::VehicleNotFound);
+            }
+        };
+
+        let declined = self.transfer_repository.get_vehicle_offers(vehicle_id).await
+            .into_iter()
+            .filter(|(_, other_offer_id)| *other_offer_id != offer_id)
+            .collect();
+
+        let record = OwnershipRecord {
+            vehicle_id,
+            owner_until: Duration::seconds(Utc::now().timestamp()),
+            user_id: previous.user_id,
+            owner_since: previous.owner_since
+        };
+
+        let mut vehicle = previous.clone();
+        vehicle.user_id = user_id;
+        vehicle.owner_since = today;
+
+        let change = vehicle_history_mapper::get_transfer_change(&previous, &vehicle, actor);
+
+        match self.transfer_repository.transfer_vehicle(previous, vehicle, record, declined, change).await {
+            Some(vehicle) => {
+                self.vehicle_repository.evict_vehicle(from_user_id, vehicle_id).await;
+                if self.vehicle_index.index(vec!(vehicle.clone())).await.is_none() {
+                    println!("Transferred Vehicle {} is missing from the full-text index until it is rebuilt", vehicle_id);
+                }
+                Ok(vehicle_mapper::get_vehicle_dto(vehicle))
+            },
+            None => {
+                self.unclaim(user_id, offer_id, vehicle_id, resumed).await;
+                Err(TransferError::StorageFailure)
+            }
+        }
+    }
+
+    pub async fn decline_offer(&self, user_id: Uuid, offer_id: Uuid) -> Result<TransferOfferDTO, TransferError> {
+        let mut offer = self.pending_offer(user_id, offer_id).await?;
+
+        self.claim(user_id, offer_id, OFFER_PENDING, OFFER_DECLINED).await?;
+
+        offer.status = OFFER_DECLINED.to_string();
+        Ok(transfer_mapper::get_offer_dto(offer))
+    }
+
+    /// Previous owners of a vehicle currently owned by `user_id`, most recent first.
+    pub async fn get_owners(&self, user_id: Uuid, vehicle_id: Uuid) -> Result<Vec<OwnershipRecordDTO>, TransferError> {
+        self.vehicle_repository.get_vehicle(user_id, vehicle_id).await
+            .ok_or(TransferError::VehicleNotFound)?;
+
+        Ok(self.transfer_repository.get_ownership_records(vehicle_id).await
+            .into_iter()
+            .map(transfer_mapper::get_ownership_record_dto)
+            .collect())
+    }
+}
+
+impl TransferService {
+    async fn pending_offer(&self, user_id: Uuid, offer_id: Uuid) -> Result<TransferOffer, TransferError> {
+        let offer = self.transfer_repository.get_offer(user_id, offer_id).await
+            .ok_or(TransferError::OfferNotFound)?;
+
+        if offer.status != OFFER_PENDING {
+            return Err(TransferError::OfferNotPending);
+        }
+
+        Ok(offer)
+    }
+
+    /// Whether the vehicle of an offer changed hands since the offer was made, which is the case of an accepted offer
+    /// whose acceptance went through, as opposed to one cut short.
+    async fn transferred_since(&self, offer: &TransferOffer) -> bool {
+        self.transfer_repository.get_ownership_records(offer.vehicle_id).await
+            .iter()
+            .any(|record| record.user_id == offer.from_user_id && record.owner_until >= offer.created_at)
+    }
+
+    /// Releases the vehicle after a failed acceptance and makes its offer pending again, unless the acceptance was
+    /// resumed, whose offer stays accepted so that it can be resumed again.
+    async fn unclaim(&self, user_id: Uuid, offer_id: Uuid, vehicle_id: Uuid, resumed: bool) {
+        if !resumed {
+            self.transfer_repository.update_offer_status(user_id, offer_id, OFFER_ACCEPTED, OFFER_PENDING).await;
+        }
+        self.transfer_repository.release_vehicle(vehicle_id, offer_id).await;
+    }
+
+    async fn claim(&self, user_id: Uuid, offer_id: Uuid, expected: &str, status: &str) -> Result<(), TransferError> {
+        match self.transfer_repository.update_offer_status(user_id, offer_id, expected, status).await {
+            Some(true) => Ok(()),
+            Some(false) => Err(TransferError::OfferNotPending),
+            None => Err(TransferError::StorageFailure)
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use mockall::mock;
+
+    use crate::domain::vehicle::Vehicle;
+    use crate::domain::vehicle_event::VehicleEventKind;
+    use crate::domain::vehicle_history::VehicleChange;
+    use crate::service::vehicle_service::tests::{MockVehicleIndexImpl, MockVehicleRepositoryImpl};
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    mock! {
+        pub TransferRepositoryImpl {}
+
+        #[async_trait]
+        impl TransferRepository for TransferRepositoryImpl {
+            async fn get_offer(&self, to_user_id: Uuid, offer_id: Uuid) -> Option<TransferOffer>;
+            async fn get_offers(&self, to_user_id: Uuid) -> Vec<TransferOffer>;
+            async fn save_offer(&self, offer: TransferOffer) -> Option<TransferOffer>;
+            async fn update_offer_status(&self, to_user_id: Uuid, offer_id: Uuid, expected: &str, status: &str) -> Option<bool>;
+            async fn delete_offer(&self, to_user_id: Uuid, offer_id: Uuid) -> Option<()>;
+            async fn claim_vehicle(&self, vehicle_id: Uuid, offer_id: Uuid) -> Option<bool>;
+            async fn release_vehicle(&self, vehicle_id: Uuid, offer_id: Uuid);
+            async fn get_vehicle_offers(&self, vehicle_id: Uuid) -> Vec<(Uuid, Uuid)>;
+            async fn transfer_vehicle(&self, previous: Vehicle, vehicle: Vehicle, record: OwnershipRecord, declined: Vec<(Uuid, Uuid)>, change: VehicleChange) -> Option<Vehicle>;
+            async fn get_ownership_records(&self, vehicle_id: Uuid) -> Vec<OwnershipRecord>;
+        }
+    }
+
+    #[test]
+    fn given_same_user_when_create_offer_then_returns_invalid_recipient() {
+        let transfer_service = TransferService::new(Arc::new(MockTransferRepositoryImpl::new()), Arc::new(MockVehicleRepositoryImpl::new()), Arc::new(MockVehicleIndexImpl::new()));
+
+        let result = aw!(transfer_service.create_offer(fixture::from_user_id(), fixture::vehicle_id(), fixture::from_user_id()));
+
+        assert_eq!(Err(TransferError::InvalidRecipient), result.map(|_| ()));
+    }
+
+    #[test]
+    fn when_create_offer_then_pending_offer_is_stored() {
+        let mut transfer_repository = MockTransferRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle()));
+        transfer_repository.expect_save_offer()
+            .withf(|offer: &TransferOffer| offer.status == OFFER_PENDING && offer.to_user_id == fixture::to_user_id())
+            .times(1)
+            .returning(move |offer| Some(offer));
+
+        let transfer_service = TransferService::new(Arc::new(transfer_repository), Arc::new(vehicle_repository), Arc::new(MockVehicleIndexImpl::new()));
+
+        let offer_dto = aw!(transfer_service.create_offer(fixture::from_user_id(), fixture::vehicle_id(), fixture::to_user_id())).unwrap();
+
+        assert_eq!(fixture::from_user_id(), offer_dto.from_user_id);
+        assert_eq!(fixture::vehicle_id(), offer_dto.vehicle_id);
+    }
+
+    #[test]
+    fn when_accept_offer_then_vehicle_moves_to_recipient_with_owner_since_reset() {
+        let mut transfer_repository = MockTransferRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        transfer_repository.expect_get_offer()
+            .times(1)
+            .returning(move |_, _| Some(fixture::offer(OFFER_PENDING)));
+        transfer_repository.expect_claim_vehicle()
+            .withf(|vehicle_id: &Uuid, offer_id: &Uuid| vehicle_id == &fixture::vehicle_id() && offer_id == &fixture::offer_id())
+            .times(1)
+            .returning(move |_, _| Some(true));
+        transfer_repository.expect_update_offer_status()
+            .withf(|_, _, expected: &str, status: &str| expected == OFFER_PENDING && status == OFFER_ACCEPTED)
+            .times(1)
+            .returning(move |_, _, _, _| Some(true));
+        vehicle_repository.expect_get_vehicle()
+            .withf(|user_id: &Uuid, _| user_id == &fixture::from_user_id())
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle()));
+        transfer_repository.expect_get_vehicle_offers()
+            .times(1)
+            .returning(move |_| vec!((fixture::to_user_id(), fixture::offer_id()), (fixture::other_user_id(), fixture::other_offer_id())));
+        transfer_repository.expect_transfer_vehicle()
+            .withf(|previous: &Vehicle, vehicle: &Vehicle, record: &OwnershipRecord, declined: &Vec<(Uuid, Uuid)>, change: &VehicleChange| previous.user_id == fixture::from_user_id()
+                && vehicle.user_id == fixture::to_user_id()
+                && vehicle.owner_since == fixture::today()
+                && record.user_id == fixture::from_user_id()
+                && record.owner_since == fixture::owner_since()
+                && declined == &vec!((fixture::other_user_id(), fixture::other_offer_id()))
+                && change.event == VehicleEventKind::Transferred
+                && change.version.user_id == fixture::to_user_id()
+                && change.version.actor == fixture::ACTOR)
+            .times(1)
+            .returning(move |_, vehicle, _, _, _| Some(vehicle));
+        transfer_repository.expect_release_vehicle().times(0);
+        vehicle_repository.expect_evict_vehicle()
+            .withf(|user_id: &Uuid, vehicle_id: &Uuid| user_id == &fixture::from_user_id() && vehicle_id == &fixture::vehicle_id())
+            .times(2)
+            .returning(|_, _| ());
+        let mut vehicle_index = MockVehicleIndexImpl::new();
+        vehicle_index.expect_index()
+            .withf(|vehicles: &Vec<Vehicle>| vehicles.len() == 1 && vehicles[0].user_id == fixture::to_user_id())
+            .times(1)
+            .returning(|_| Some(()));
+
+        let transfer_service = TransferService::new(Arc::new(transfer_repository), Arc::new(vehicle_repository), Arc::new(vehicle_index));
+
+        let vehicle_dto = aw!(transfer_service.accept_offer(fixture::to_user_id(), fixture::offer_id(), fixture::today(), fixture::ACTOR)).unwrap();
+
+        assert_eq!(fixture::to_user_id(), vehicle_dto.user_id);
+        assert_eq!(fixture::today(), vehicle_dto.owner_since);
+    }
+
+    #[test]
+    fn given_vehicle_claimed_by_another_offer_when_accept_offer_then_returns_offer_not_pending() {
+        let mut transfer_repository = MockTransferRepositoryImpl::new();
+
+        transfer_repository.expect_get_offer()
+            .times(1)
+            .returning(move |_, _| Some(fixture::offer(OFFER_PENDING)));
+        transfer_repository.expect_claim_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(false));
+        transfer_repository.expect_update_offer_status().times(0);
+        transfer_repository.expect_transfer_vehicle().times(0);
+
+        let transfer_service = TransferService::new(Arc::new(transfer_repository), Arc::new(MockVehicleRepositoryImpl::new()), Arc::new(MockVehicleIndexImpl::new()));
+
+        let result = aw!(transfer_service.accept_offer(fixture::to_user_id(), fixture::offer_id(), fixture::today(), fixture::ACTOR));
+
+        assert_eq!(Err(TransferError::OfferNotPending), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_offer_claimed_concurrently_when_accept_offer_then_vehicle_is_released() {
+        let mut transfer_repository = MockTransferRepositoryImpl::new();
+
+        transfer_repository.expect_get_offer()
+            .times(1)
+            .returning(move |_, _| Some(fixture::offer(OFFER_PENDING)));
+        transfer_repository.expect_claim_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(true));
+        transfer_repository.expect_update_offer_status()
+            .times(1)
+            .returning(move |_, _, _, _| Some(false));
+        transfer_repository.expect_release_vehicle()
+            .withf(|vehicle_id: &Uuid, offer_id: &Uuid| vehicle_id == &fixture::vehicle_id() && offer_id == &fixture::offer_id())
+            .times(1)
+            .returning(|_, _| ());
+        transfer_repository.expect_transfer_vehicle().times(0);
+
+        let transfer_service = TransferService::new(Arc::new(transfer_repository), Arc::new(MockVehicleRepositoryImpl::new()), Arc::new(MockVehicleIndexImpl::new()));
+
+        let result = aw!(transfer_service.accept_offer(fixture::to_user_id(), fixture::offer_id(), fixture::today(), fixture::ACTOR));
+
+        assert_eq!(Err(TransferError::OfferNotPending), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_batch_failure_when_accept_offer_then_offer_is_pending_again_and_vehicle_released() {
+        let mut transfer_repository = MockTransferRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        transfer_repository.expect_get_offer()
+            .times(1)
+            .returning(move |_, _| Some(fixture::offer(OFFER_PENDING)));
+        transfer_repository.expect_claim_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(true));
+        transfer_repository.expect_update_offer_status()
+            .withf(|_, _, expected: &str, _| expected == OFFER_PENDING)
+            .times(1)
+            .returning(move |_, _, _, _| Some(true));
+        vehicle_repository.expect_evict_vehicle()
+            .times(1)
+            .returning(|_, _| ());
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle()));
+        transfer_repository.expect_get_vehicle_offers()
+            .times(1)
+            .returning(move |_| vec!());
+        transfer_repository.expect_transfer_vehicle()
+            .times(1)
+            .returning(move |_, _, _, _, _| None);
+        transfer_repository.expect_update_offer_status()
+            .withf(|_, _, expected: &str, status: &str| expected == OFFER_ACCEPTED && status == OFFER_PENDING)
+            .times(1)
+            .returning(move |_, _, _, _| Some(true));
+        transfer_repository.expect_release_vehicle()
+            .times(1)
+            .returning(|_, _| ());
+
+        let transfer_service = TransferService::new(Arc::new(transfer_repository), Arc::new(vehicle_repository), Arc::new(MockVehicleIndexImpl::new()));
+
+        let result = aw!(transfer_service.accept_offer(fixture::to_user_id(), fixture::offer_id(), fixture::today(), fixture::ACTOR));
+
+        assert_eq!(Err(TransferError::StorageFailure), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_declined_offer_when_accept_offer_then_returns_offer_not_pending() {
+        let mut transfer_repository = MockTransferRepositoryImpl::new();
+
+        transfer_repository.expect_get_offer()
+            .times(1)
+            .returning(move |_, _| Some(fixture::offer(OFFER_DECLINED)));
+
+        let transfer_service = TransferService::new(Arc::new(transfer_repository), Arc::new(MockVehicleRepositoryImpl::new()), Arc::new(MockVehicleIndexImpl::new()));
+
+        let result = aw!(transfer_service.accept_offer(fixture::to_user_id(), fixture::offer_id(), fixture::today(), fixture::ACTOR));
+
+        assert_eq!(Err(TransferError::OfferNotPending), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_offer_accepted_by_abandoned_acceptance_when_accept_offer_then_resumes_transfer() {
+        let mut transfer_repository = MockTransferRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        transfer_repository.expect_get_offer()
+            .times(1)
+            .returning(move |_, _| Some(fixture::offer(OFFER_ACCEPTED)));
+        transfer_repository.expect_get_ownership_records()
+            .times(1)
+            .returning(move |_| vec!(OwnershipRecord { user_id: fixture::other_user_id(), owner_until: Duration::seconds(4), ..fixture::record() }));
+        transfer_repository.expect_claim_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(true));
+        transfer_repository.expect_update_offer_status().times(0);
+        vehicle_repository.expect_evict_vehicle()
+            .times(2)
+            .returning(|_, _| ());
+        vehicle_repository.expect_get_vehicle()
+            .withf(|user_id: &Uuid, _| user_id == &fixture::from_user_id())
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle()));
+        transfer_repository.expect_get_vehicle_offers()
+            .times(1)
+            .returning(move |_| vec!((fixture::to_user_id(), fixture::offer_id())));
+        transfer_repository.expect_transfer_vehicle()
+            .withf(|_, vehicle: &Vehicle, _, declined: &Vec<(Uuid, Uuid)>, _| vehicle.user_id == fixture::to_user_id() && declined.is_empty())
+            .times(1)
+            .returning(move |_, vehicle, _, _, _| Some(vehicle));
+        let mut vehicle_index = MockVehicleIndexImpl::new();
+        vehicle_index.expect_index()
+            .times(1)
+            .returning(|_| Some(()));
+
+        let transfer_service = TransferService::new(Arc::new(transfer_repository), Arc::new(vehicle_repository), Arc::new(vehicle_index));
+
+        let vehicle_dto = aw!(transfer_service.accept_offer(fixture::to_user_id(), fixture::offer_id(), fixture::today(), fixture::ACTOR)).unwrap();
+
+        assert_eq!(fixture::to_user_id(), vehicle_dto.user_id);
+    }
+
+    #[test]
+    fn given_resumed_acceptance_failing_when_accept_offer_then_offer_stays_accepted_and_vehicle_released() {
+        let mut transfer_repository = MockTransferRepositoryImpl::new();
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        transfer_repository.expect_get_offer()
+            .times(1)
+            .returning(move |_, _| Some(fixture::offer(OFFER_ACCEPTED)));
+        transfer_repository.expect_get_ownership_records()
+            .times(1)
+            .returning(move |_| vec!());
+        transfer_repository.expect_claim_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(true));
+        vehicle_repository.expect_evict_vehicle()
+            .times(1)
+            .returning(|_, _| ());
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(move |_, _| Some(fixture::vehicle()));
+        transfer_repository.expect_get_vehicle_offers()
+            .times(1)
+            .returning(move |_| vec!());
+        transfer_repository.expect_transfer_vehicle()
+            .times(1)
+            .returning(move |_, _, _, _, _| None);
+        transfer_repository.expect_update_offer_status().times(0);
+        transfer_repository.expect_release_vehicle()
+            .times(1)
+            .returning(|_, _| ());
+
+        let transfer_service = TransferService::new(Arc::new(transfer_repository), Arc::new(vehicle_repository), Arc::new(MockVehicleIndexImpl::new()));
+
+        let result = aw!(transfer_service.accept_offer(fixture::to_user_id(), fixture::offer_id(), fixture::today(), fixture::ACTOR));
+
+        assert_eq!(Err(TransferError::StorageFailure), result.map(|_| ()));
+    }
+
+    #[test]
+    fn given_offer_accepted_and_vehicle_transferred_when_accept_offer_then_returns_offer_not_pending() {
+        let mut transfer_repository = MockTransferRepositoryImpl::new();
+
+        transfer_repository.expect_get_offer()
+            .times(1)
+            .returning(move |_, _| Some(fixture::offer(OFFER_ACCEPTED)));
+        transfer_repository.expect_get_ownership_records()
+            .times(1)
+            .returning(move |_| vec!(fixture::record()));
+        transfer_repository.expect_claim_vehicle().times(0);
+
+        let transfer_service = TransferService::new(Arc::new(transfer_repository), Arc::new(MockVehicleRepositoryImpl::new()), Arc::new(MockVehicleIndexImpl::new()));
+
+        let result = aw!(transfer_service.accept_offer(fixture::to_user_id(), fixture::offer_id(), fixture::today(), fixture::ACTOR));
+
+        assert_eq!(Err(TransferError::OfferNotPending), result.map(|_| ()));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const FROM_USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const TO_USER_ID_STR: &str = "6176bc4b-33b6-4c9c-a4ad-c65da1322a80";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const OFFER_ID_STR: &str = "9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d";
+        pub const OTHER_USER_ID_STR: &str = "2c1d0e9f-8a7b-4c6d-9e5f-4a3b2c1d0e9f";
+        pub const OTHER_OFFER_ID_STR: &str = "5f4e3d2c-1b0a-4f9e-8d7c-6b5a4f3e2d1c";
+        pub const ACTOR: &str = "jane";
+
+        pub fn from_user_id() -> Uuid {
+            Uuid::parse_str(FROM_USER_ID_STR).unwrap()
+        }
+
+        pub fn to_user_id() -> Uuid {
+            Uuid::parse_str(TO_USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn offer_id() -> Uuid {
+            Uuid::parse_str(OFFER_ID_STR).unwrap()
+        }
+
+        pub fn other_user_id() -> Uuid {
+            Uuid::parse_str(OTHER_USER_ID_STR).unwrap()
+        }
+
+        pub fn other_offer_id() -> Uuid {
+            Uuid::parse_str(OTHER_OFFER_ID_STR).unwrap()
+        }
+
+        pub fn today() -> NaiveDate {
+            NaiveDate::from_ymd(2021, 6, 1)
+        }
+
+        pub fn owner_since() -> NaiveDate {
+            NaiveDate::from_ymd(2015, 12, 2)
+        }
+
+        /// The previous owner handing the vehicle over after the offer was made.
+        pub fn record() -> OwnershipRecord {
+            OwnershipRecord {
+                vehicle_id: vehicle_id(),
+                owner_until: Duration::seconds(10),
+                user_id: from_user_id(),
+                owner_since: owner_since()
+            }
+        }
+
+        pub fn offer(status: &str) -> TransferOffer {
+            TransferOffer {
+                to_user_id: to_user_id(),
+                offer_id: offer_id(),
+                from_user_id: from_user_id(),
+                vehicle_id: vehicle_id(),
+                status: status.to_string(),
+                created_at: Duration::seconds(5)
+            }
+        }
+
+        pub fn vehicle() -> Vehicle {
+            Vehicle {
+                name: "the vehicle name".to_string(),
+                user_id: from_user_id(),
+                vehicle_id: vehicle_id(),
+                created_at: Duration::seconds(5),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: 100,
+                owner_since: owner_since(),
+                manufacturing_date: NaiveDate::from_ymd(2015, 1, 1),
+                picture: None
+            }
+        }
+    }
+}
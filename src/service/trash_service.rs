@@ -0,0 +1,444 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rocket::serde::Deserialize;
+use rocket::serde::uuid::Uuid;
+use mockall::automock;
+
+use crate::domain::user_data::USER_TABLES;
+use crate::domain::vehicle_trash::{VehiclePurge, PURGE_SHARDS};
+use crate::dto::trash_dto::TrashedVehicleDTO;
+use crate::dto::vehicle_dto::VehicleDTO;
+use crate::mapper::{trash_mapper, vehicle_history_mapper, vehicle_mapper};
+use crate::repository::user_data_repository::UserDataRepository;
+use crate::repository::vehicle_repository::VehicleRepository;
+use crate::repository::vehicle_trash_repository::VehicleTrashRepository;
+use crate::search::vehicle_index::VehicleIndex;
+use crate::service::audit_service::{AuditService, TRASH_PURGE_JOB};
+use crate::service::picture_service;
+use crate::storage::blob_store::BlobStore;
+
+/// Trash settings read from the `trash` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct TrashSettings {
+    /// Days a deleted vehicle can be restored before it is purged.
+    pub retention_days      : i64,
+    pub purge_enabled       : bool,
+    pub purge_interval_ms   : u64,
+    pub purge_batch_size    : usize,
+    /// Days before today whose purges an instance looks for when it starts.
+    pub purge_lookback_days : i64
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        TrashSettings {
+            retention_days: 30,
+            purge_enabled: true,
+            purge_interval_ms: 3_600_000,
+            purge_batch_size: 100,
+            purge_lookback_days: 7
+        }
+    }
+}
+
+/// Outcome of purging the vehicles of a day and shard.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PurgeReport {
+    pub purged              : usize,
+    /// Purges that failed and were skipped, to be retried on the next run.
+    pub failed              : usize
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TrashError {
+    VehicleNotFound,
+    /// A vehicle was saved under the id of the trashed one since it was deleted.
+    VehicleExists,
+    StorageFailure
+}
+
+pub struct TrashService {
+    vehicle_trash_repository: Arc<dyn VehicleTrashRepository + Sync + Send>,
+    vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+    user_data_repository: Arc<dyn UserDataRepository + Sync + Send>,
+    blob_store: Arc<dyn BlobStore + Sync + Send>,
+    vehicle_index: Arc<dyn VehicleIndex + Sync + Send>,
+    audit_service: Arc<AuditService>,
+    settings: TrashSettings
+}
+
+#[automock]
+impl TrashService {
+    pub fn new(vehicle_trash_repository: Arc<dyn VehicleTrashRepository + Sync + Send>,
+               vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+               user_data_repository: Arc<dyn UserDataRepository + Sync + Send>,
+               blob_store: Arc<dyn BlobStore + Sync + Send>,
+               vehicle_index: Arc<dyn VehicleIndex + Sync + Send>,
+               audit_service: Arc<AuditService>,
+               settings: TrashSettings) -> TrashService {
+        TrashService {
+            vehicle_trash_repository,
+            vehicle_repository,
+            user_data_repository,
+            blob_store,
+            vehicle_index,
+            audit_service,
+            settings
+        }
+    }
+
+    /// Moves a vehicle to the trash on behalf of `actor`, out of every read and listing until it is restored.
+    pub async fn delete_vehicle(&self, user_id: Uuid, vehicle_id: Uuid, actor: &str) -> Result<(), TrashError> {
+        let vehicle = self.vehicle_repository.get_vehicle(user_id, vehicle_id).await
+            .ok_or(TrashError::VehicleNotFound)?;
+
+        let trashed = trash_mapper::get_trashed_vehicle(&vehicle, actor, Utc::now(), chrono::Duration::days(self.settings.retention_days));
+        let change = vehicle_history_mapper::get_deletion_change(&vehicle, actor);
+
+        self.vehicle_trash_repository.trash_vehicle(&vehicle, &trashed, change).await
+            .ok_or(TrashError::StorageFailure)?;
+        self.vehicle_repository.evict_vehicle(user_id, vehicle_id).await;
+        if self.vehicle_index.remove_vehicle(vehicle_id).await.is_none() {
+            println!("Deleted Vehicle {} stays in the full-text index until it is rebuilt", vehicle_id);
+        }
+
+        Ok(())
+    }
+
+    /// Trashed vehicles of a user, `None` when they could not be read.
+    pub async fn get_trash(&self, user_id: Uuid) -> Option<Vec<TrashedVehicleDTO>> {
+        let trash = self.vehicle_trash_repository.get_trash(user_id).await?;
+
+        Some(trash.into_iter().filter_map(trash_mapper::get_trashed_vehicle_dto).collect())
+    }
+
+    /// Moves a trashed vehicle back on behalf of `actor`, recorded as created again.
+    pub async fn restore_vehicle(&self, user_id: Uuid, vehicle_id: Uuid, actor: &str) -> Result<VehicleDTO, TrashError> {
+        let trashed = self.vehicle_trash_repository.get_trashed_vehicle(user_id, vehicle_id).await
+            .ok_or(TrashError::VehicleNotFound)?;
+        if self.vehicle_repository.get_vehicle(user_id, vehicle_id).await.is_some() {
+            return Err(TrashError::VehicleExists);
+        }
+        let vehicle = trash_mapper::get_deleted_vehicle(&trashed)
+            .ok_or(TrashError::VehicleNotFound)?;
+
+        let change = vehicle_history_mapper::get_vehicle_change(None, &vehicle, actor, None);
+
+        let vehicle = self.vehicle_trash_repository.restore_vehicle(vehicle, &trashed, change).await
+            .ok_or(TrashError::StorageFailure)?;
+        self.vehicle_repository.evict_vehicle(user_id, vehicle_id).await;
+        if self.vehicle_index.index(vec!(vehicle.clone())).await.is_none() {
+            println!("Restored Vehicle {} is missing from the full-text index until it is rebuilt", vehicle_id);
+        }
+
+        Ok(vehicle_mapper::get_vehicle_dto(vehicle))
+    }
+
+    /// Purges the vehicles of a day and shard due at `now`, page by page. A purge that fails is skipped and left
+    /// for the next run, so it does not hold back the ones after it. Each purge is recorded in the audit log under
+    /// the id of the run, succeeded or failed. `None` when a page could not be read.
+    pub async fn purge(&self, day: NaiveDate, shard: i32, now: DateTime<Utc>) -> Option<PurgeReport> {
+        let mut report = PurgeReport::default();
+        let mut after = None;
+        let run_id = Uuid::new_v4().to_string();
+
+        loop {
+            let purges = self.vehicle_trash_repository.get_due_purges(day, shard, now.timestamp_millis(), after, self.settings.purge_batch_size).await?;
+
+            for purge in purges.iter() {
+                let purged = self.purge_vehicle(purge).await.is_some() && self.vehicle_trash_repository.delete_purge(purge).await.is_some();
+                if purged {
+                    report.purged += 1;
+                } else {
+                    println!("Failed to purge Vehicle {} of user {}, retrying on the next run", purge.vehicle_id, purge.user_id);
+                    report.failed += 1;
+                }
+
+                let targets = vec!(("user_id".to_string(), vec!(purge.user_id.to_string())), ("vehicle_id".to_string(), vec!(purge.vehicle_id.to_string())));
+                self.audit_service.record_job(TRASH_PURGE_JOB, run_id.clone(), targets.into_iter().collect(), purged);
+            }
+
+            if purges.len() < self.settings.purge_batch_size {
+                return Some(report);
+            }
+            after = purges.last().cloned();
+        }
+    }
+}
+
+impl TrashService {
+    /// Hard-deletes the history, logs and picture of a trashed vehicle, unless a vehicle was saved again under its
+    /// id, which then owns them.
+    async fn purge_vehicle(&self, purge: &VehiclePurge) -> Option<()> {
+        if self.vehicle_repository.get_vehicle(purge.user_id, purge.vehicle_id).await.is_some() {
+            return Some(());
+        }
+
+        for table in USER_TABLES.iter() {
+            for key in table.vehicle_partitions(purge.user_id, purge.vehicle_id) {
+                self.user_data_repository.delete_rows(table, &key).await?;
+            }
+        }
+        if let Some(picture) = &purge.picture {
+            self.blob_store.delete(picture).await?;
+            if let Some(thumbnail_key) = picture_service::thumbnail_key(picture) {
+                self.blob_store.delete(&thumbnail_key).await?;
+            }
+        }
+        Some(())
+    }
+}
+
+/// Purges the trash every `purge_interval_ms` until the instance stops, every day of each shard from the oldest
+/// one that may still hold purges up to today. A past day is left out of later runs once all of its purges are
+/// done, since no purge is ever added to it again.
+pub async fn purge_periodically(trash_service: Arc<TrashService>, settings: TrashSettings) {
+    let interval = Duration::from_millis(settings.purge_interval_ms);
+    let first_day = Utc::now().date().naive_utc() - chrono::Duration::days(settings.purge_lookback_days);
+    let mut oldest_days = vec!(first_day; PURGE_SHARDS as usize);
+
+    loop {
+        let now = Utc::now();
+        let today = now.date().naive_utc();
+
+        for shard in 0..PURGE_SHARDS {
+            let oldest_day = &mut oldest_days[shard as usize];
+            let mut day = *oldest_day;
+            while day <= today {
+                let done = trash_service.purge(day, shard, now).await.map_or(false, |report| report.failed == 0);
+                if done && day < today && day == *oldest_day {
+                    *oldest_day = day.succ();
+                }
+                day = day.succ();
+            }
+        }
+        rocket::tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use chrono::{NaiveDate, TimeZone};
+    use mockall::mock;
+
+    use crate::domain::audit::AuditEntry;
+    use crate::domain::user_data::{PartitionKey, UserTable};
+    use crate::domain::vehicle::Vehicle;
+    use crate::domain::vehicle_history::VehicleChange;
+    use crate::domain::vehicle_trash::TrashedVehicle;
+    use crate::service::audit_service::AuditSettings;
+    use crate::service::audit_service::tests::MockAuditRepositoryImpl;
+    use crate::service::picture_service::tests::MockBlobStoreImpl;
+    use crate::service::user_data_service::tests::MockUserDataRepositoryImpl;
+    use crate::service::vehicle_service::tests::{MockVehicleIndexImpl, MockVehicleRepositoryImpl};
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    mock! {
+        pub VehicleTrashRepositoryImpl {}
+
+        #[async_trait]
+        impl VehicleTrashRepository for VehicleTrashRepositoryImpl {
+            async fn trash_vehicle(&self, vehicle: &Vehicle, trashed: &TrashedVehicle, change: VehicleChange) -> Option<()>;
+            async fn restore_vehicle(&self, vehicle: Vehicle, trashed: &TrashedVehicle, change: VehicleChange) -> Option<Vehicle>;
+            async fn get_trashed_vehicle(&self, user_id: Uuid, vehicle_id: Uuid) -> Option<TrashedVehicle>;
+            async fn get_trash(&self, user_id: Uuid) -> Option<Vec<TrashedVehicle>>;
+            async fn get_due_purges(&self, day: NaiveDate, shard: i32, now_ms: i64, after: Option<VehiclePurge>, limit: usize) -> Option<Vec<VehiclePurge>>;
+            async fn delete_purge(&self, purge: &VehiclePurge) -> Option<()>;
+        }
+    }
+
+    #[test]
+    fn when_delete_vehicle_then_trashes_it_until_retention_and_removes_it_from_index() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(|_, _| Some(fixture::vehicle()));
+        vehicle_repository.expect_evict_vehicle()
+            .times(1)
+            .return_const(());
+
+        let mut vehicle_trash_repository = MockVehicleTrashRepositoryImpl::new();
+        vehicle_trash_repository.expect_trash_vehicle()
+            .withf(|_, trashed: &TrashedVehicle, change: &VehicleChange| trashed.deleted_by == "jane"
+                && trashed.purge_at_ms - trashed.deleted_at_ms == chrono::Duration::days(7).num_milliseconds()
+                && change.version.event == "deleted")
+            .times(1)
+            .returning(|_, _, _| Some(()));
+
+        let mut vehicle_index = MockVehicleIndexImpl::new();
+        vehicle_index.expect_remove_vehicle()
+            .withf(|vehicle_id: &Uuid| *vehicle_id == fixture::vehicle().vehicle_id)
+            .times(1)
+            .returning(|_| Some(()));
+
+        let trash_service = fixture::service(vehicle_trash_repository, vehicle_repository, MockUserDataRepositoryImpl::new(),
+                                             MockBlobStoreImpl::new(), vehicle_index);
+
+        let vehicle = fixture::vehicle();
+        assert_eq!(Ok(()), aw!(trash_service.delete_vehicle(vehicle.user_id, vehicle.vehicle_id, "jane")));
+    }
+
+    #[test]
+    fn given_vehicle_saved_again_when_restore_vehicle_then_fails_with_vehicle_exists() {
+        let mut vehicle_trash_repository = MockVehicleTrashRepositoryImpl::new();
+        vehicle_trash_repository.expect_get_trashed_vehicle()
+            .times(1)
+            .returning(|_, _| Some(fixture::trashed_vehicle()));
+        vehicle_trash_repository.expect_restore_vehicle()
+            .times(0);
+
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(|_, _| Some(fixture::vehicle()));
+
+        let trash_service = fixture::service(vehicle_trash_repository, vehicle_repository, MockUserDataRepositoryImpl::new(),
+                                             MockBlobStoreImpl::new(), MockVehicleIndexImpl::new());
+
+        let vehicle = fixture::vehicle();
+        assert_eq!(Err(TrashError::VehicleExists), aw!(trash_service.restore_vehicle(vehicle.user_id, vehicle.vehicle_id, "jane")));
+    }
+
+    #[test]
+    fn given_due_purge_when_purge_then_deletes_vehicle_partitions_and_picture_before_purge() {
+        let mut vehicle_trash_repository = MockVehicleTrashRepositoryImpl::new();
+        vehicle_trash_repository.expect_get_due_purges()
+            .withf(|day: &NaiveDate, shard: &i32, now_ms: &i64, after: &Option<VehiclePurge>, limit: &usize| *day == fixture::day()
+                && *shard == 3 && *now_ms == 9000 && after.is_none() && *limit == 100)
+            .times(1)
+            .returning(|_, _, _, _, _| Some(vec!(trash_mapper::get_vehicle_purge(&fixture::trashed_vehicle()))));
+        vehicle_trash_repository.expect_delete_purge()
+            .times(1)
+            .returning(|_| Some(()));
+
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(|_, _| None);
+
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let deleted_tables = deleted.clone();
+        let mut user_data_repository = MockUserDataRepositoryImpl::new();
+        user_data_repository.expect_delete_rows()
+            .returning(move |table: &UserTable, _: &PartitionKey| {
+                deleted_tables.lock().unwrap().push(table.name);
+                Some(())
+            });
+
+        let mut blob_store = MockBlobStoreImpl::new();
+        blob_store.expect_delete()
+            .times(2)
+            .returning(|_| Some(()));
+
+        let trash_service = fixture::service(vehicle_trash_repository, vehicle_repository, user_data_repository,
+                                             blob_store, MockVehicleIndexImpl::new());
+
+        assert_eq!(Some(PurgeReport { purged: 1, failed: 0 }), aw!(trash_service.purge(fixture::day(), 3, Utc.timestamp_millis(9000))));
+        assert_eq!(vec!("vehicle_history", "activities", "activity_distances", "maintenance_records", "reminder_rules", "ownership_history", "vehicle_transfer_offers"),
+                   *deleted.lock().unwrap());
+    }
+
+    #[test]
+    fn given_failing_purge_when_purge_then_skips_it_purges_the_rest_page_by_page_and_audits_each() {
+        let first = VehiclePurge { vehicle_id: Uuid::new_v4(), ..trash_mapper::get_vehicle_purge(&fixture::trashed_vehicle()) };
+        let second = VehiclePurge { vehicle_id: Uuid::new_v4(), picture: None, ..first.clone() };
+        let third = VehiclePurge { vehicle_id: Uuid::new_v4(), picture: None, ..first.clone() };
+        let failing_id = first.vehicle_id;
+        let page_end = second.clone();
+
+        let mut vehicle_trash_repository = MockVehicleTrashRepositoryImpl::new();
+        let first_page = vec!(first, second);
+        vehicle_trash_repository.expect_get_due_purges()
+            .withf(|_, _, _, after: &Option<VehiclePurge>, _| after.is_none())
+            .times(1)
+            .returning(move |_, _, _, _, _| Some(first_page.clone()));
+        vehicle_trash_repository.expect_get_due_purges()
+            .withf(move |_, _, _, after: &Option<VehiclePurge>, _| after.as_ref() == Some(&page_end))
+            .times(1)
+            .returning(move |_, _, _, _, _| Some(vec!(third.clone())));
+        vehicle_trash_repository.expect_delete_purge()
+            .withf(move |purge: &VehiclePurge| purge.vehicle_id != failing_id)
+            .times(2)
+            .returning(|_| Some(()));
+
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+        vehicle_repository.expect_get_vehicle()
+            .returning(|_, _| None);
+
+        let mut user_data_repository = MockUserDataRepositoryImpl::new();
+        user_data_repository.expect_delete_rows()
+            .returning(|_, _| Some(()));
+
+        let mut blob_store = MockBlobStoreImpl::new();
+        blob_store.expect_delete()
+            .returning(|_| None);
+
+        let mut audit_repository = MockAuditRepositoryImpl::new();
+        audit_repository.expect_insert_entry()
+            .withf(|entry: &AuditEntry| entry.route == TRASH_PURGE_JOB && entry.status == 200)
+            .times(2)
+            .returning(|_| Some(()));
+        audit_repository.expect_insert_entry()
+            .withf(|entry: &AuditEntry| entry.route == TRASH_PURGE_JOB && entry.status == 500)
+            .times(1)
+            .returning(|_| Some(()));
+        let audit_service = Arc::new(AuditService::new(Arc::new(audit_repository), AuditSettings::default()));
+
+        let trash_service = TrashService::new(Arc::new(vehicle_trash_repository), Arc::new(vehicle_repository), Arc::new(user_data_repository),
+                                              Arc::new(blob_store), Arc::new(MockVehicleIndexImpl::new()), audit_service.clone(),
+                                              TrashSettings { retention_days: 7, purge_batch_size: 2, ..TrashSettings::default() });
+
+        assert_eq!(Some(PurgeReport { purged: 2, failed: 1 }), aw!(trash_service.purge(fixture::day(), 3, Utc.timestamp_millis(9000))));
+        assert_eq!(3, aw!(audit_service.write_pending()));
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const PICTURE_KEY: &str = "d13fe953-297a-4781-807a-f9becc1b71f6/60e18f00-34b8-4a52-916c-adbb0204618e/picture.png";
+
+        pub fn vehicle() -> Vehicle {
+            Vehicle {
+                name: "the vehicle".to_string(),
+                user_id: Uuid::parse_str("d13fe953-297a-4781-807a-f9becc1b71f6").unwrap(),
+                vehicle_id: Uuid::parse_str("60e18f00-34b8-4a52-916c-adbb0204618e").unwrap(),
+                created_at: chrono::Duration::seconds(1000),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: 100,
+                owner_since: NaiveDate::from_ymd(2021, 1, 1),
+                manufacturing_date: NaiveDate::from_ymd(2020, 1, 1),
+                picture: Some(PICTURE_KEY.to_string())
+            }
+        }
+
+        pub fn day() -> NaiveDate {
+            NaiveDate::from_ymd(1970, 1, 8)
+        }
+
+        pub fn trashed_vehicle() -> TrashedVehicle {
+            trash_mapper::get_trashed_vehicle(&vehicle(), "jane", Utc.timestamp(5, 0), chrono::Duration::days(7))
+        }
+
+        pub fn service(vehicle_trash_repository: MockVehicleTrashRepositoryImpl, vehicle_repository: MockVehicleRepositoryImpl,
+                       user_data_repository: MockUserDataRepositoryImpl, blob_store: MockBlobStoreImpl,
+                       vehicle_index: MockVehicleIndexImpl) -> TrashService {
+            TrashService::new(Arc::new(vehicle_trash_repository), Arc::new(vehicle_repository), Arc::new(user_data_repository),
+                              Arc::new(blob_store), Arc::new(vehicle_index),
+                              Arc::new(AuditService::new(Arc::new(MockAuditRepositoryImpl::new()), AuditSettings::default())),
+                              TrashSettings { retention_days: 7, ..TrashSettings::default() })
+        }
+    }
+}
@@ -0,0 +1,705 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use rocket::serde::Deserialize;
+use rocket::serde::uuid::Uuid;
+use rocket::tokio::{self, task};
+use serde_json::{json, Value};
+use zip::write::FileOptions;
+use zip::result::ZipResult;
+use zip::ZipWriter;
+use mockall::automock;
+
+use crate::domain::user_data::{ErasureJob, UnfinishedErasure, UserKeys, ERASURE_COMPLETED, ERASURE_FAILED, ERASURE_PENDING, ERASURE_RUNNING,
+                               ERASURE_SHARDS, USER_TABLES};
+use crate::domain::vehicle::Vehicle;
+use crate::domain::vehicle_lookup::VehicleLookup;
+use crate::dto::user_data_dto::ErasureJobDTO;
+use crate::mapper::{trash_mapper, user_data_mapper};
+use crate::repository::transfer_repository::TransferRepository;
+use crate::repository::user_data_repository::UserDataRepository;
+use crate::repository::vehicle_repository::VehicleRepository;
+use crate::repository::vehicle_trash_repository::VehicleTrashRepository;
+use crate::repository::webhook_repository::WebhookRepository;
+use crate::search::vehicle_index::VehicleIndex;
+use crate::service::audit_service::{AuditService, USER_ERASURE_JOB};
+use crate::service::picture_service;
+use crate::storage::blob_store::BlobStore;
+
+/// Vehicles read at a time while collecting those of a user.
+const VEHICLE_PAGE_SIZE: usize = 500;
+/// Unfinished erasures read at once when looking for the ones to resume.
+const RESUME_PAGE_SIZE: usize = 100;
+
+pub const PICTURES: &str = "pictures";
+/// Lists the pictures left out of an export because they could not be read.
+pub const MISSING_PICTURES: &str = "missing_pictures.json";
+pub const VEHICLE_LOOKUPS: &str = "vehicle_lookups";
+pub const SEARCH_INDEX: &str = "search_index";
+/// Offers the user sent for the vehicles they still own, stored in the partitions of their recipients.
+pub const SENT_TRANSFER_OFFERS: &str = "sent_transfer_offers";
+
+/// Erasure settings read from the `erasure` section of `Rocket.toml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ErasureSettings {
+    /// How long an unfinished erasure stays with the instance running it, renewed at every step, before another
+    /// instance may resume it.
+    pub lease_ms            : u64,
+    pub resume_interval_ms  : u64
+}
+
+impl Default for ErasureSettings {
+    fn default() -> Self {
+        ErasureSettings {
+            lease_ms: 600_000,
+            resume_interval_ms: 60_000
+        }
+    }
+}
+
+/// Erasures are recorded as unfinished, leased to the instance running them, until they complete or fail. The
+/// ones whose lease ran out, because their instance stopped, are run again by `resume`.
+#[derive(Clone)]
+pub struct UserDataService {
+    user_data_repository: Arc<dyn UserDataRepository + Sync + Send>,
+    vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+    vehicle_trash_repository: Arc<dyn VehicleTrashRepository + Sync + Send>,
+    transfer_repository: Arc<dyn TransferRepository + Sync + Send>,
+    webhook_repository: Arc<dyn WebhookRepository + Sync + Send>,
+    blob_store: Arc<dyn BlobStore + Sync + Send>,
+    vehicle_index: Arc<dyn VehicleIndex + Sync + Send>,
+    audit_service: Arc<AuditService>,
+    settings: ErasureSettings
+}
+
+#[automock]
+impl UserDataService {
+    pub fn new(user_data_repository: Arc<dyn UserDataRepository + Sync + Send>,
+               vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+               vehicle_trash_repository: Arc<dyn VehicleTrashRepository + Sync + Send>,
+               transfer_repository: Arc<dyn TransferRepository + Sync + Send>,
+               webhook_repository: Arc<dyn WebhookRepository + Sync + Send>,
+               blob_store: Arc<dyn BlobStore + Sync + Send>,
+               vehicle_index: Arc<dyn VehicleIndex + Sync + Send>,
+               audit_service: Arc<AuditService>,
+               settings: ErasureSettings) -> UserDataService {
+        UserDataService {
+            user_data_repository,
+            vehicle_repository,
+            vehicle_trash_repository,
+            transfer_repository,
+            webhook_repository,
+            blob_store,
+            vehicle_index,
+            audit_service,
+            settings
+        }
+    }
+
+    /// ZIP archive of every table holding data of the user, as `<table>.json` arrays of rows, along with the
+    /// pictures of their vehicles as `pictures/<vehicle_id>/<file>`. The archive is written to an unlinked temporary
+    /// file one entry at a time, so only one table or picture is held in memory, and answered rewound to its start.
+    /// Pictures that could not be read are listed in `missing_pictures.json` instead. `None` when a table could not
+    /// be read or the archive written.
+    pub async fn export(&self, user_id: Uuid) -> Option<File> {
+        let (keys, vehicles) = self.get_keys(user_id).await?;
+        let mut zip = ZipWriter::new(temporary_file(user_id)?);
+
+        for table in USER_TABLES.iter() {
+            let mut rows = Vec::new();
+            for key in table.partitions(&keys) {
+                let partition = self.user_data_repository.get_rows(table, &key).await?;
+                rows.extend(partition.iter().filter_map(|row| serde_json::from_str::<Value>(row).ok()));
+            }
+            let json = serde_json::to_vec_pretty(&rows)
+                .unwrap_or_else(|e| panic!("Failed to serialize {} of user {} with error {:?}", table.name, user_id, e));
+            zip = archive(zip, format!("{}.json", table.name), json, user_id).await?;
+        }
+
+        let mut missing = Vec::new();
+        for vehicle in vehicles {
+            if let Some(key) = vehicle.picture {
+                match self.blob_store.get(&key).await {
+                    Some(blob) => {
+                        let file_name = key.rsplit('/').next().unwrap_or(&key).to_string();
+                        zip = archive(zip, format!("{}/{}/{}", PICTURES, vehicle.vehicle_id, file_name), blob.data, user_id).await?;
+                    },
+                    None => missing.push(json!({ "vehicle_id": vehicle.vehicle_id, "picture": key }))
+                }
+            }
+        }
+        if !missing.is_empty() {
+            println!("Exported data of user {} without {} missing pictures", user_id, missing.len());
+            let json = serde_json::to_vec_pretty(&missing)
+                .unwrap_or_else(|e| panic!("Failed to serialize missing pictures of user {} with error {:?}", user_id, e));
+            zip = archive(zip, MISSING_PICTURES.to_string(), json, user_id).await?;
+        }
+
+        task::spawn_blocking(move || -> ZipResult<File> {
+            let mut file = zip.finish()?;
+            file.seek(SeekFrom::Start(0))?;
+            Ok(file)
+        })
+            .await
+            .ok()?
+            .map_err(|e| println!("Failed to archive data of user {} with error {:?}", user_id, e))
+            .ok()
+    }
+
+    /// Records a pending erasure of every partition and blob of the user, leased to this instance, and runs it in
+    /// the background.
+    pub async fn request_erasure(&self, user_id: Uuid) -> Option<ErasureJobDTO> {
+        let now_ms = Utc::now().timestamp_millis();
+        let job = ErasureJob {
+            user_id,
+            job_id: Uuid::new_v4(),
+            status: ERASURE_PENDING.to_string(),
+            erased: vec!(),
+            error: None,
+            requested_at_ms: now_ms,
+            updated_at_ms: now_ms
+        };
+        let lease_until_ms = now_ms + self.settings.lease_ms as i64;
+        self.user_data_repository.start_job(&job, lease_until_ms).await?;
+
+        let service = self.clone();
+        let pending = job.clone();
+        let unfinished = user_data_mapper::get_unfinished_erasure(&job, lease_until_ms);
+        tokio::spawn(async move { service.erase(pending, unfinished).await });
+
+        Some(user_data_mapper::get_erasure_job_dto(job))
+    }
+
+    pub async fn get_erasure(&self, user_id: Uuid, job_id: Uuid) -> Option<ErasureJobDTO> {
+        self.user_data_repository.get_job(user_id, job_id).await
+            .map(user_data_mapper::get_erasure_job_dto)
+    }
+
+    /// Takes over the unfinished erasures of the shard whose lease ran out at `now_ms` and runs them again on
+    /// their own tasks, returning how many were resumed.
+    pub async fn resume(&self, shard: i32, now_ms: i64) -> usize {
+        let mut resumed = 0;
+        let mut after = None;
+
+        loop {
+            let page = match self.user_data_repository.get_unfinished_jobs(shard, after, RESUME_PAGE_SIZE).await {
+                Some(page) => page,
+                None => return resumed
+            };
+            after = page.last().cloned();
+
+            for unfinished in page.iter().filter(|unfinished| unfinished.lease_until_ms <= now_ms) {
+                let lease_until_ms = now_ms + self.settings.lease_ms as i64;
+                if self.user_data_repository.lease_job(unfinished, lease_until_ms).await != Some(true) {
+                    continue;
+                }
+                let unfinished = UnfinishedErasure { lease_until_ms, ..unfinished.clone() };
+
+                match self.user_data_repository.get_job(unfinished.user_id, unfinished.job_id).await {
+                    Some(job) if job.status == ERASURE_PENDING || job.status == ERASURE_RUNNING => {
+                        let service = self.clone();
+                        tokio::spawn(async move { service.erase(job, unfinished).await });
+                        resumed += 1;
+                    },
+                    // Ended without its lease being removed.
+                    _ => {
+                        self.user_data_repository.delete_unfinished_job(&unfinished).await;
+                    }
+                }
+            }
+
+            if page.len() < RESUME_PAGE_SIZE {
+                return resumed;
+            }
+        }
+    }
+}
+
+impl UserDataService {
+    /// Keys of the partitions of the user along with their vehicles, trashed ones included, `None` when they could
+    /// not be read.
+    async fn get_keys(&self, user_id: Uuid) -> Option<(UserKeys, Vec<Vehicle>)> {
+        let mut vehicles: Vec<Vehicle> = Vec::new();
+        loop {
+            let after = vehicles.last().map(|vehicle| (vehicle.user_id, vehicle.vehicle_id));
+            let page = self.vehicle_repository.get_vehicles_page(Some(user_id), after, VEHICLE_PAGE_SIZE).await?;
+            let is_last_page = page.len() < VEHICLE_PAGE_SIZE;
+            vehicles.extend(page);
+            if is_last_page {
+                break;
+            }
+        }
+        let trash = self.vehicle_trash_repository.get_trash(user_id).await?;
+        vehicles.extend(trash.iter().filter_map(trash_mapper::get_deleted_vehicle));
+        let webhooks = self.webhook_repository.get_webhooks(user_id).await?;
+
+        let keys = UserKeys {
+            user_id,
+            vehicle_ids: vehicles.iter().map(|vehicle| vehicle.vehicle_id).collect(),
+            webhook_ids: webhooks.iter().map(|webhook| webhook.webhook_id).collect()
+        };
+        Some((keys, vehicles))
+    }
+
+    /// Runs an erasure to its end, recording what was erased as it goes and why it failed if it did, and its
+    /// outcome in the audit log under the id of the job.
+    async fn erase(&self, mut job: ErasureJob, mut unfinished: UnfinishedErasure) {
+        self.update_job(&mut job, ERASURE_RUNNING, None).await;
+
+        let erased = self.erase_data(&mut job, &mut unfinished).await;
+        let targets = vec!(("user_id".to_string(), vec!(job.user_id.to_string())), ("job_id".to_string(), vec!(job.job_id.to_string())));
+        self.audit_service.record_job(USER_ERASURE_JOB, job.job_id.to_string(), targets.into_iter().collect(), erased.is_ok());
+
+        match erased {
+            Ok(_) => self.update_job(&mut job, ERASURE_COMPLETED, None).await,
+            Err(error) => self.update_job(&mut job, ERASURE_FAILED, Some(error)).await
+        }
+    }
+
+    async fn erase_data(&self, job: &mut ErasureJob, unfinished: &mut UnfinishedErasure) -> Result<(), String> {
+        let (keys, vehicles) = self.get_keys(job.user_id).await
+            .ok_or_else(|| "vehicles and webhooks could not be read".to_string())?;
+
+        // Blobs and derived data go first, so that a retry still finds the vehicles, trashed ones included, they
+        // belong to.
+        for key in vehicles.iter().filter_map(|vehicle| vehicle.picture.as_ref()) {
+            self.blob_store.delete(key).await.ok_or_else(|| format!("{} could not be erased", PICTURES))?;
+            if let Some(thumbnail_key) = picture_service::thumbnail_key(key) {
+                self.blob_store.delete(&thumbnail_key).await.ok_or_else(|| format!("{} could not be erased", PICTURES))?;
+            }
+        }
+        self.erased(job, unfinished, PICTURES).await;
+
+        for lookup in vehicles.iter().flat_map(VehicleLookup::of) {
+            self.vehicle_repository.remove_vehicle_key(&lookup.attribute, &lookup.value, lookup.user_id, lookup.vehicle_id).await
+                .ok_or_else(|| format!("{} could not be erased", VEHICLE_LOOKUPS))?;
+        }
+        self.erased(job, unfinished, VEHICLE_LOOKUPS).await;
+
+        self.vehicle_index.remove_user(job.user_id).await
+            .ok_or_else(|| format!("{} could not be erased", SEARCH_INDEX))?;
+        self.erased(job, unfinished, SEARCH_INDEX).await;
+
+        // Found through the offers of each vehicle, which are erased with the vehicles below.
+        for vehicle in &vehicles {
+            for (to_user_id, offer_id) in self.transfer_repository.get_vehicle_offers(vehicle.vehicle_id).await {
+                self.transfer_repository.delete_offer(to_user_id, offer_id).await
+                    .ok_or_else(|| format!("{} could not be erased", SENT_TRANSFER_OFFERS))?;
+            }
+        }
+        self.erased(job, unfinished, SENT_TRANSFER_OFFERS).await;
+
+        for table in USER_TABLES.iter() {
+            for key in table.partitions(&keys) {
+                self.user_data_repository.delete_rows(table, &key).await
+                    .ok_or_else(|| format!("{} could not be erased", table.name))?;
+            }
+            self.erased(job, unfinished, table.name).await;
+        }
+        for vehicle in &vehicles {
+            self.vehicle_repository.evict_vehicle(vehicle.user_id, vehicle.vehicle_id).await;
+        }
+
+        Ok(())
+    }
+
+    /// Records a step of the erasure and renews its lease. Erasing again being harmless, an erasure taken over by
+    /// another instance in between carries on all the same.
+    async fn erased(&self, job: &mut ErasureJob, unfinished: &mut UnfinishedErasure, name: &str) {
+        if !job.erased.iter().any(|erased| erased == name) {
+            job.erased.push(name.to_string());
+        }
+        self.update_job(job, ERASURE_RUNNING, None).await;
+
+        let lease_until_ms = Utc::now().timestamp_millis() + self.settings.lease_ms as i64;
+        if self.user_data_repository.lease_job(unfinished, lease_until_ms).await == Some(true) {
+            unfinished.lease_until_ms = lease_until_ms;
+        }
+    }
+
+    /// Saves the progress of a job, a failure being only logged since the erasure itself goes on.
+    async fn update_job(&self, job: &mut ErasureJob, status: &str, error: Option<String>) {
+        job.status = status.to_string();
+        job.error = error;
+        job.updated_at_ms = Utc::now().timestamp_millis();
+        self.user_data_repository.save_job(job).await;
+    }
+}
+
+/// Resumes the unfinished erasures left by stopped instances, every `resume_interval_ms` starting now.
+pub async fn resume_periodically(user_data_service: Arc<UserDataService>, settings: ErasureSettings) {
+    let interval = Duration::from_millis(settings.resume_interval_ms);
+
+    loop {
+        for shard in 0..ERASURE_SHARDS {
+            let resumed = user_data_service.resume(shard, Utc::now().timestamp_millis()).await;
+            if resumed > 0 {
+                println!("Resumed {} erasures of shard {}", resumed, shard);
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Creates the file an export is written to and removes its path right away, so that it is deleted once the
+/// archive has been answered or the export failed.
+fn temporary_file(user_id: Uuid) -> Option<File> {
+    let path = std::env::temp_dir().join(format!("export-{}-{}.zip", user_id, Uuid::new_v4()));
+    let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)
+        .map_err(|e| println!("Failed to create export file of user {} with error {:?}", user_id, e))
+        .ok()?;
+    fs::remove_file(&path)
+        .map_err(|e| println!("Failed to unlink export file of user {} with error {:?}", user_id, e))
+        .ok()?;
+
+    Some(file)
+}
+
+/// Adds a file to the archive off the async runtime, answering the archive back to add the next one.
+async fn archive(mut zip: ZipWriter<File>, name: String, data: Vec<u8>, user_id: Uuid) -> Option<ZipWriter<File>> {
+    task::spawn_blocking(move || -> ZipResult<ZipWriter<File>> {
+        zip.start_file(name, FileOptions::default())?;
+        zip.write_all(&data)?;
+        Ok(zip)
+    })
+        .await
+        .ok()?
+        .map_err(|e| println!("Failed to archive data of user {} with error {:?}", user_id, e))
+        .ok()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use std::io::Read;
+    use std::sync::Mutex;
+    use mockall::mock;
+    use rocket::http::ContentType;
+    use zip::ZipArchive;
+
+    use crate::domain::audit::AuditEntry;
+    use crate::domain::user_data::{PartitionKey, UserTable};
+    use crate::domain::webhook::Webhook;
+    use crate::service::audit_service::AuditSettings;
+    use crate::service::audit_service::tests::MockAuditRepositoryImpl;
+    use crate::service::picture_service::tests::MockBlobStoreImpl;
+    use crate::service::trash_service::tests::MockVehicleTrashRepositoryImpl;
+    use crate::service::transfer_service::tests::MockTransferRepositoryImpl;
+    use crate::service::vehicle_service::tests::{MockVehicleIndexImpl, MockVehicleRepositoryImpl};
+    use crate::service::webhook_service::tests::MockWebhookRepositoryImpl;
+    use crate::storage::blob_store::Blob;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    mock! {
+        pub UserDataRepositoryImpl {}
+
+        #[async_trait]
+        impl UserDataRepository for UserDataRepositoryImpl {
+            async fn get_rows(&self, table: &UserTable, key: &PartitionKey) -> Option<Vec<String>>;
+            async fn delete_rows(&self, table: &UserTable, key: &PartitionKey) -> Option<()>;
+            async fn start_job(&self, job: &ErasureJob, lease_until_ms: i64) -> Option<()>;
+            async fn save_job(&self, job: &ErasureJob) -> Option<()>;
+            async fn get_job(&self, user_id: Uuid, job_id: Uuid) -> Option<ErasureJob>;
+            async fn get_unfinished_jobs(&self, shard: i32, after: Option<UnfinishedErasure>, limit: usize) -> Option<Vec<UnfinishedErasure>>;
+            async fn lease_job(&self, unfinished: &UnfinishedErasure, lease_until_ms: i64) -> Option<bool>;
+            async fn delete_unfinished_job(&self, unfinished: &UnfinishedErasure) -> Option<()>;
+        }
+    }
+
+    #[test]
+    fn given_vehicle_with_picture_when_export_then_archives_every_table_and_picture() {
+        let mut user_data_repository = MockUserDataRepositoryImpl::new();
+        user_data_repository.expect_get_rows()
+            .returning(|table, _| Some(if table.name == "vehicles" { vec!(fixture::VEHICLE_ROW.to_string()) } else { vec!() }));
+
+        let mut blob_store = MockBlobStoreImpl::new();
+        blob_store.expect_get()
+            .withf(|key: &str| key == fixture::PICTURE_KEY)
+            .times(1)
+            .returning(|_| Some(Blob { data: b"the picture".to_vec(), content_type: ContentType::PNG, etag: "etag".to_string() }));
+
+        let user_data_service = fixture::service(user_data_repository, fixture::vehicle_repository(), blob_store, MockVehicleIndexImpl::new());
+
+        let data = aw!(user_data_service.export(fixture::user_id())).unwrap();
+
+        let mut archive = ZipArchive::new(data).unwrap();
+        assert_eq!(USER_TABLES.len() + 1, archive.len());
+        let mut vehicles = String::new();
+        archive.by_name("vehicles.json").unwrap().read_to_string(&mut vehicles).unwrap();
+        assert_eq!(vec!(serde_json::from_str::<Value>(fixture::VEHICLE_ROW).unwrap()), serde_json::from_str::<Vec<Value>>(&vehicles).unwrap());
+        assert!(archive.by_name(&format!("pictures/{}/picture.png", fixture::VEHICLE_ID_STR)).is_ok());
+    }
+
+    #[test]
+    fn given_missing_picture_when_export_then_archives_tables_and_lists_the_picture() {
+        let mut user_data_repository = MockUserDataRepositoryImpl::new();
+        user_data_repository.expect_get_rows()
+            .returning(|_, _| Some(vec!()));
+
+        let mut blob_store = MockBlobStoreImpl::new();
+        blob_store.expect_get()
+            .times(1)
+            .returning(|_| None);
+
+        let user_data_service = fixture::service(user_data_repository, fixture::vehicle_repository(), blob_store, MockVehicleIndexImpl::new());
+
+        let data = aw!(user_data_service.export(fixture::user_id())).unwrap();
+
+        let mut archive = ZipArchive::new(data).unwrap();
+        assert_eq!(USER_TABLES.len() + 1, archive.len());
+        let mut missing = String::new();
+        archive.by_name(MISSING_PICTURES).unwrap().read_to_string(&mut missing).unwrap();
+        assert_eq!(json!([{ "vehicle_id": fixture::VEHICLE_ID_STR, "picture": fixture::PICTURE_KEY }]), serde_json::from_str::<Value>(&missing).unwrap());
+    }
+
+    #[test]
+    fn when_erase_then_deletes_every_partition_blob_lookup_and_index_vehicles_last_and_audits_it() {
+        let (user_data_repository, deleted, saved) = fixture::user_data_repository();
+
+        let mut vehicle_repository = fixture::vehicle_repository();
+        vehicle_repository.expect_remove_vehicle_key()
+            .times(2)
+            .returning(|_, _, _, _| Some(()));
+        vehicle_repository.expect_evict_vehicle()
+            .times(1)
+            .returning(|_, _| ());
+
+        let mut blob_store = MockBlobStoreImpl::new();
+        blob_store.expect_delete()
+            .withf(|key: &str| key == fixture::PICTURE_KEY || key == format!("{}/{}/thumbnail.jpg", fixture::USER_ID_STR, fixture::VEHICLE_ID_STR))
+            .times(2)
+            .returning(|_| Some(()));
+
+        let mut vehicle_index = MockVehicleIndexImpl::new();
+        vehicle_index.expect_remove_user()
+            .times(1)
+            .returning(|_| Some(()));
+
+        let audit_service = fixture::audit_service(fixture::erasure_audit(200));
+        let user_data_service = fixture::audited_service(user_data_repository, vehicle_repository, blob_store, vehicle_index, audit_service.clone());
+
+        aw!(user_data_service.erase(fixture::job(), fixture::unfinished()));
+
+        assert_eq!(1, aw!(audit_service.write_pending()));
+        let deleted = deleted.lock().unwrap();
+        // A partition per vehicle and per webhook for the tables keyed by them, one for those keyed by the user.
+        assert_eq!(USER_TABLES.len(), deleted.len());
+        assert_eq!(Some(&"vehicles"), deleted.last());
+        let job = saved.lock().unwrap().last().cloned().unwrap();
+        assert_eq!((ERASURE_COMPLETED, USER_TABLES.len() + 4), (job.status.as_str(), job.erased.len()));
+        assert!(job.erased.contains(&SENT_TRANSFER_OFFERS.to_string()));
+    }
+
+    #[test]
+    fn given_search_index_failure_when_erase_then_fails_before_erasing_vehicles() {
+        let (user_data_repository, deleted, saved) = fixture::user_data_repository();
+
+        let mut vehicle_repository = fixture::vehicle_repository();
+        vehicle_repository.expect_remove_vehicle_key()
+            .returning(|_, _, _, _| Some(()));
+
+        let mut blob_store = MockBlobStoreImpl::new();
+        blob_store.expect_delete()
+            .returning(|_| Some(()));
+
+        let mut vehicle_index = MockVehicleIndexImpl::new();
+        vehicle_index.expect_remove_user()
+            .returning(|_| None);
+
+        let user_data_service = fixture::service(user_data_repository, vehicle_repository, blob_store, vehicle_index);
+
+        aw!(user_data_service.erase(fixture::job(), fixture::unfinished()));
+
+        assert!(!deleted.lock().unwrap().contains(&"vehicles"));
+        let job = saved.lock().unwrap().last().cloned().unwrap();
+        assert_eq!((ERASURE_FAILED, Some("search_index could not be erased".to_string())), (job.status.as_str(), job.error));
+    }
+
+    #[test]
+    fn given_lookup_removal_failure_when_erase_then_fails_before_erasing_vehicles_and_audits_it() {
+        let (user_data_repository, deleted, saved) = fixture::user_data_repository();
+
+        let mut vehicle_repository = fixture::vehicle_repository();
+        vehicle_repository.expect_remove_vehicle_key()
+            .times(1)
+            .returning(|_, _, _, _| None);
+
+        let mut blob_store = MockBlobStoreImpl::new();
+        blob_store.expect_delete()
+            .returning(|_| Some(()));
+
+        let audit_service = fixture::audit_service(fixture::erasure_audit(500));
+        let user_data_service = fixture::audited_service(user_data_repository, vehicle_repository, blob_store, MockVehicleIndexImpl::new(), audit_service.clone());
+
+        aw!(user_data_service.erase(fixture::job(), fixture::unfinished()));
+
+        assert_eq!(1, aw!(audit_service.write_pending()));
+        assert!(deleted.lock().unwrap().is_empty());
+        let job = saved.lock().unwrap().last().cloned().unwrap();
+        assert_eq!((ERASURE_FAILED, Some("vehicle_lookups could not be erased".to_string())), (job.status.as_str(), job.error));
+    }
+
+    #[test]
+    fn given_expired_lease_of_ended_erasure_when_resume_then_removes_it_and_skips_leased_ones() {
+        let mut user_data_repository = MockUserDataRepositoryImpl::new();
+        let leased = UnfinishedErasure { job_id: Uuid::new_v4(), lease_until_ms: 20_000, ..fixture::unfinished() };
+
+        user_data_repository.expect_get_unfinished_jobs()
+            .withf(|shard: &i32, after: &Option<UnfinishedErasure>, _| *shard == 3 && after.is_none())
+            .times(1)
+            .returning(move |_, _, _| Some(vec!(fixture::unfinished(), leased.clone())));
+        user_data_repository.expect_lease_job()
+            .withf(|unfinished: &UnfinishedErasure, lease_until_ms: &i64| unfinished.lease_until_ms == 5000 && *lease_until_ms == 9000 + 600_000)
+            .times(1)
+            .returning(|_, _| Some(true));
+        user_data_repository.expect_get_job()
+            .times(1)
+            .returning(|_, _| Some(ErasureJob { status: ERASURE_COMPLETED.to_string(), ..fixture::job() }));
+        user_data_repository.expect_delete_unfinished_job()
+            .withf(|unfinished: &UnfinishedErasure| unfinished.job_id == fixture::unfinished().job_id)
+            .times(1)
+            .returning(|_| Some(()));
+
+        let user_data_service = fixture::service(user_data_repository, MockVehicleRepositoryImpl::new(), MockBlobStoreImpl::new(), MockVehicleIndexImpl::new());
+
+        assert_eq!(0, aw!(user_data_service.resume(3, 9000)));
+    }
+
+    mod fixture {
+        use super::*;
+        use chrono::{Duration, NaiveDate};
+
+        pub const USER_ID_STR: &str = "d13fe953-297a-4781-807a-f9becc1b71f6";
+        pub const VEHICLE_ID_STR: &str = "60e18f00-34b8-4a52-916c-adbb0204618e";
+        pub const PICTURE_KEY: &str = "d13fe953-297a-4781-807a-f9becc1b71f6/60e18f00-34b8-4a52-916c-adbb0204618e/picture.png";
+        pub const VEHICLE_ROW: &str = "{\"user_id\": \"d13fe953-297a-4781-807a-f9becc1b71f6\", \"name\": \"the vehicle\"}";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn vehicle() -> Vehicle {
+            Vehicle {
+                name: "the vehicle".to_string(),
+                user_id: user_id(),
+                vehicle_id: Uuid::parse_str(VEHICLE_ID_STR).unwrap(),
+                created_at: Duration::milliseconds(1000),
+                vehicle_type: "bike".to_string(),
+                retired_at: None,
+                brand: "the brand".to_string(),
+                model: "the model".to_string(),
+                distance: 100,
+                owner_since: NaiveDate::from_ymd(2021, 1, 1),
+                manufacturing_date: NaiveDate::from_ymd(2020, 1, 1),
+                picture: Some(PICTURE_KEY.to_string())
+            }
+        }
+
+        pub const JOB_ID_STR: &str = "0c6f4b0e-5b6e-4a52-9d3a-3f1c2a9b8e7d";
+
+        pub fn job() -> ErasureJob {
+            ErasureJob {
+                user_id: user_id(),
+                job_id: Uuid::parse_str(JOB_ID_STR).unwrap(),
+                status: ERASURE_PENDING.to_string(),
+                erased: vec!(),
+                error: None,
+                requested_at_ms: 1000,
+                updated_at_ms: 1000
+            }
+        }
+
+        pub fn unfinished() -> UnfinishedErasure {
+            user_data_mapper::get_unfinished_erasure(&job(), 5000)
+        }
+
+        /// Repository deleting any partition, saving any job and renewing any lease, along with the names of the tables deleted from
+        /// and the jobs saved, in order.
+        pub fn user_data_repository() -> (MockUserDataRepositoryImpl, Arc<Mutex<Vec<&'static str>>>, Arc<Mutex<Vec<ErasureJob>>>) {
+            let deleted = Arc::new(Mutex::new(Vec::new()));
+            let saved = Arc::new(Mutex::new(Vec::new()));
+
+            let mut user_data_repository = MockUserDataRepositoryImpl::new();
+            let deleted_tables = deleted.clone();
+            user_data_repository.expect_delete_rows()
+                .returning(move |table, _| {
+                    deleted_tables.lock().unwrap().push(table.name);
+                    Some(())
+                });
+            let saved_jobs = saved.clone();
+            user_data_repository.expect_save_job()
+                .returning(move |job| {
+                    saved_jobs.lock().unwrap().push(job.clone());
+                    Some(())
+                });
+            user_data_repository.expect_lease_job()
+                .returning(|_, _| Some(true));
+
+            (user_data_repository, deleted, saved)
+        }
+
+        pub fn vehicle_repository() -> MockVehicleRepositoryImpl {
+            let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+            vehicle_repository.expect_get_vehicles_page()
+                .returning(|_, after, _| Some(if after.is_none() { vec!(vehicle()) } else { vec!() }));
+            vehicle_repository
+        }
+
+        pub fn service(user_data_repository: MockUserDataRepositoryImpl, vehicle_repository: MockVehicleRepositoryImpl,
+                       blob_store: MockBlobStoreImpl, vehicle_index: MockVehicleIndexImpl) -> UserDataService {
+            audited_service(user_data_repository, vehicle_repository, blob_store, vehicle_index, audit_service(MockAuditRepositoryImpl::new()))
+        }
+
+        pub fn audited_service(user_data_repository: MockUserDataRepositoryImpl, vehicle_repository: MockVehicleRepositoryImpl,
+                               blob_store: MockBlobStoreImpl, vehicle_index: MockVehicleIndexImpl, audit_service: Arc<AuditService>) -> UserDataService {
+            let mut webhook_repository = MockWebhookRepositoryImpl::new();
+            webhook_repository.expect_get_webhooks()
+                .returning(|user_id| Some(vec!(Webhook {
+                    user_id,
+                    webhook_id: Uuid::new_v4(),
+                    url: "https://example.com/hook".to_string(),
+                    secret: "the secret".to_string(),
+                    events: vec!(),
+                    created_at: Duration::milliseconds(1000)
+                })));
+
+            let mut vehicle_trash_repository = MockVehicleTrashRepositoryImpl::new();
+            vehicle_trash_repository.expect_get_trash()
+                .returning(|_| Some(vec!()));
+
+            let mut transfer_repository = MockTransferRepositoryImpl::new();
+            transfer_repository.expect_get_vehicle_offers()
+                .returning(|_| vec!((Uuid::new_v4(), Uuid::new_v4())));
+            transfer_repository.expect_delete_offer()
+                .returning(|_, _| Some(()));
+
+            UserDataService::new(Arc::new(user_data_repository), Arc::new(vehicle_repository), Arc::new(vehicle_trash_repository),
+                                 Arc::new(transfer_repository), Arc::new(webhook_repository),
+                                 Arc::new(blob_store), Arc::new(vehicle_index), audit_service, ErasureSettings::default())
+        }
+
+        pub fn audit_service(audit_repository: MockAuditRepositoryImpl) -> Arc<AuditService> {
+            Arc::new(AuditService::new(Arc::new(audit_repository), AuditSettings::default()))
+        }
+
+        /// Audit log repository expecting the erasure of the job to be recorded with `status`.
+        pub fn erasure_audit(status: i32) -> MockAuditRepositoryImpl {
+            let mut audit_repository = MockAuditRepositoryImpl::new();
+            audit_repository.expect_insert_entry()
+                .withf(move |entry: &AuditEntry| entry.route == USER_ERASURE_JOB && entry.request_id == JOB_ID_STR && entry.status == status)
+                .times(1)
+                .returning(|_| Some(()));
+            audit_repository
+        }
+    }
+}
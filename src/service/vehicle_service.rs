@@ -1,42 +1,339 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
 use rocket::serde::uuid::Uuid;
 use mockall::automock;
 
-use crate::repository::vehicle_repository::VehicleRepository;
-use crate::mapper::vehicle_mapper;
-use crate::domain::vehicle::Vehicle;
-use crate::dto::vehicle_dto::VehicleDTO;
-
-const UNKNOWN: &str = "unknown";
+use crate::repository::vehicle_repository::{self, VehicleRepository};
+use crate::repository::vehicle_history_repository::VehicleHistoryRepository;
+use crate::mapper::{vehicle_history_mapper, vehicle_mapper};
+use crate::domain::vehicle::{Vehicle, VehicleProjection};
+use crate::domain::vehicle_history::VehicleChange;
+use crate::domain::vehicle_event::VehicleEventKind;
+use crate::domain::vehicle_lookup::{self, VehicleFilter};
+use crate::dto::vehicle_dto::{ImportReportDTO, LineErrorDTO, VehicleDTO, VehicleProjectionDTO, VehicleSearchDTO};
+use crate::dto::vehicle_history_dto::VehicleVersionDTO;
+use crate::parser::vehicle_records::{self, RecordFormat};
+use crate::search::vehicle_index::VehicleIndex;
+
+/// Bytes of statements per batch, Cassandra rejecting batches larger than `batch_size_fail_threshold_in_kb`, 50 KB
+/// by default. Each vehicle adds its row, the outbox entry of its event and its version.
+pub const MAX_BATCH_BYTES: usize = 40 * 1024;
+/// Line errors kept in an import report, so a broken file does not make it grow without bounds.
+pub const MAX_REPORTED_ERRORS: usize = 1000;
+/// Lookup rows read by a single search request, which then answers a short page with a `next` token
+/// rather than walking a whole lookup partition for a filter matching few of its vehicles.
+const MAX_SCANNED_KEYS: usize = 1000;
+/// Vehicles read from Cassandra per query while rebuilding the full-text index.
+pub const REINDEX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, PartialEq)]
+pub enum HistoryError {
+    VehicleNotFound,
+    VersionNotFound,
+    StorageFailure
+}
 
 pub struct VehicleService {
     vehicle_repository: Arc<dyn VehicleRepository + Sync + Send>,
+    vehicle_index: Arc<dyn VehicleIndex + Sync + Send>,
+    vehicle_history_repository: Arc<dyn VehicleHistoryRepository + Sync + Send>,
 }
 
 #[automock]
 impl VehicleService {
-    pub fn new(vehicle_repository: Arc<dyn VehicleRepository+ Sync + Send>) -> VehicleService {
+    pub fn new(vehicle_repository: Arc<dyn VehicleRepository+ Sync + Send>,
+               vehicle_index: Arc<dyn VehicleIndex + Sync + Send>,
+               vehicle_history_repository: Arc<dyn VehicleHistoryRepository + Sync + Send>) -> VehicleService {
         VehicleService {
-            vehicle_repository
+            vehicle_repository,
+            vehicle_index,
+            vehicle_history_repository
         }
     }
 
-    pub async fn get_vehicle_name(&self, user_id: Uuid, vehicle_id: Uuid) -> String {
-        let vehicle: Option<Vehicle> = self.vehicle_repository.get_vehicle(user_id, vehicle_id).await;
+    /// Only the requested `fields` of a vehicle, reading no other column but its key.
+    pub async fn get_vehicle(&self, user_id: Uuid, vehicle_id: Uuid, fields: Vec<&'static str>) -> Option<VehicleProjectionDTO> {
+        let projection = self.vehicle_repository.get_vehicle_projection(user_id, vehicle_id, vehicle_mapper::columns(&fields)).await?;
 
-        match vehicle {
-            None => UNKNOWN.to_string(),
-            Some(vehicle) => vehicle.name,
-        }
+        Some(vehicle_mapper::get_vehicle_projection_dto(projection, &fields))
     }
 
-    pub async fn save_vehicle(&self, vehicle_dto: VehicleDTO) -> Option<VehicleDTO> {
+    /// The requested `fields` of a vehicle as it was saved at `as_of`, `None` when it had no version yet or was
+    /// deleted then. Pictures are not versioned.
+    pub async fn get_vehicle_as_of(&self, user_id: Uuid, vehicle_id: Uuid, fields: Vec<&'static str>, as_of: DateTime<Utc>) -> Option<VehicleProjectionDTO> {
+        let version = self.vehicle_history_repository.get_version_as_of(user_id, vehicle_id, as_of.timestamp_millis()).await
+            .filter(|version| version.event != VehicleEventKind::Deleted.name())?;
+        let vehicle = vehicle_history_mapper::get_versioned_vehicle(&version)?;
+
+        Some(vehicle_mapper::get_vehicle_projection_dto(VehicleProjection::of(vehicle, &vehicle_mapper::columns(&fields)), &fields))
+    }
+
+    /// Saves a vehicle on behalf of `actor` together with the event of it being created, updated or retired
+    /// by the save and the version it adds to the history of the vehicle.
+    pub async fn save_vehicle(&self, vehicle_dto: VehicleDTO, actor: &str) -> Option<VehicleDTO> {
+        let is_new = vehicle_dto.vehicle_id.is_none();
         let new_vehicle = vehicle_mapper::get_vehicle(vehicle_dto);
+        let previous = self.previous(&new_vehicle, is_new).await;
+        let change = vehicle_history_mapper::get_vehicle_change(previous.as_ref(), &new_vehicle, actor, None);
+
+        let vehicle = self.vehicle_repository.save_vehicle(new_vehicle, change).await?;
+        self.index(vec!(vehicle.clone())).await;
+
+        Some(vehicle_mapper::get_vehicle_dto(vehicle))
+    }
+
+    /// Saves vehicles in batches of up to `MAX_BATCH_BYTES` per user partition, returning the saved vehicle or
+    /// `None` for each vehicle in the order received. A failed batch only fails its own vehicles.
+    pub async fn save_vehicles(&self, vehicle_dtos: Vec<VehicleDTO>, actor: &str) -> Vec<Option<VehicleDTO>> {
+        let mut results: Vec<Option<VehicleDTO>> = vehicle_dtos.iter().map(|_| None).collect();
+        let new: Vec<bool> = vehicle_dtos.iter().map(|vehicle_dto| vehicle_dto.vehicle_id.is_none()).collect();
+
+        let mut partitions: BTreeMap<Uuid, Vec<(usize, Vehicle)>> = BTreeMap::new();
+        for (index, vehicle_dto) in vehicle_dtos.into_iter().enumerate() {
+            let vehicle = vehicle_mapper::get_vehicle(vehicle_dto);
+            partitions.entry(vehicle.user_id).or_default().push((index, vehicle));
+        }
+
+        for partition in partitions.into_iter().map(|(_, partition)| partition) {
+            let mut changes = Vec::with_capacity(partition.len());
+            for (index, vehicle) in partition {
+                let previous = self.previous(&vehicle, new[index]).await;
+                let change = vehicle_history_mapper::get_vehicle_change(previous.as_ref(), &vehicle, actor, None);
+                changes.push((index, (vehicle, change)));
+            }
+
+            for batch in batches(changes) {
+                let (indexes, vehicles): (Vec<usize>, Vec<(Vehicle, VehicleChange)>) = batch.into_iter().unzip();
+                if let Some(saved) = self.vehicle_repository.save_vehicles(vehicles).await {
+                    self.index(saved.clone()).await;
+                    for (index, vehicle) in indexes.into_iter().zip(saved) {
+                        results[index] = Some(vehicle_mapper::get_vehicle_dto(vehicle));
+                    }
+                }
+            }
+        }
+
+        results
+    }
+
+    /// Reads, validates and saves a chunk of import lines, reporting each line that was not imported.
+    pub async fn import_vehicles(&self, format: RecordFormat, header: Vec<String>, lines: Vec<(usize, String)>, actor: &str) -> ImportReportDTO {
+        let mut report = ImportReportDTO::default();
+        let mut line_numbers: Vec<usize> = Vec::new();
+        let mut vehicle_dtos: Vec<VehicleDTO> = Vec::new();
+
+        for (line_number, line) in lines {
+            match vehicle_records::read(format, &header, &line) {
+                Ok(vehicle_dto) => {
+                    line_numbers.push(line_number);
+                    vehicle_dtos.push(vehicle_dto);
+                },
+                Err(error) => add_error(&mut report, line_number, error)
+            }
+        }
+
+        let saved = self.save_vehicles(vehicle_dtos, actor).await;
+
+        for (line_number, vehicle_dto) in line_numbers.into_iter().zip(saved) {
+            match vehicle_dto {
+                Some(vehicle_dto) => {
+                    report.imported += 1;
+                    report.imported_keys.extend(vehicle_dto.vehicle_id.map(|vehicle_id| (vehicle_dto.user_id, vehicle_id)));
+                },
+                None => add_error(&mut report, line_number, "Failed to save vehicle".to_string())
+            }
+        }
+
+        report
+    }
+
+    /// Latest versions of a vehicle, newest first, `None` when they could not be read.
+    pub async fn get_history(&self, user_id: Uuid, vehicle_id: Uuid, limit: usize) -> Option<Vec<VehicleVersionDTO>> {
+        let versions = self.vehicle_history_repository.get_versions(user_id, vehicle_id, limit).await?;
+
+        Some(versions.into_iter().filter_map(vehicle_history_mapper::get_vehicle_version_dto).collect())
+    }
+
+    /// Saves the vehicle back as it was in `version`, on behalf of `actor`, which adds a new version rather than
+    /// removing the later ones. The current picture is kept.
+    pub async fn restore_version(&self, user_id: Uuid, vehicle_id: Uuid, version: Uuid, actor: &str) -> Result<VehicleDTO, HistoryError> {
+        let current = self.vehicle_repository.get_vehicle(user_id, vehicle_id).await
+            .ok_or(HistoryError::VehicleNotFound)?;
+        let restored = self.vehicle_history_repository.get_version(user_id, vehicle_id, version).await
+            .and_then(|version| vehicle_history_mapper::get_versioned_vehicle(&version))
+            .ok_or(HistoryError::VersionNotFound)?;
+
+        let vehicle = Vehicle { picture: None, ..restored };
+        let change = vehicle_history_mapper::get_vehicle_change(Some(&current), &vehicle, actor, Some(version));
+
+        let vehicle = self.vehicle_repository.save_vehicle(vehicle, change).await
+            .ok_or(HistoryError::StorageFailure)?;
+        self.index(vec!(vehicle.clone())).await;
+
+        Ok(vehicle_mapper::get_vehicle_dto(vehicle))
+    }
+
+    /// Page of the vehicles of a user, or of every user, following the vehicle keyed by `after`.
+    pub async fn export_vehicles(&self, user_id: Option<Uuid>, after: Option<(Uuid, Uuid)>, limit: usize) -> Option<Vec<VehicleDTO>> {
+        let vehicles = self.vehicle_repository.get_vehicles_page(user_id, after, limit).await;
+
+        vehicles.map(|vehicles| vehicles.into_iter().map(vehicle_mapper::get_vehicle_dto).collect())
+    }
+
+    /// Page of the vehicles of a user following the vehicle keyed by `after`, or, with a full-text `query`,
+    /// the best matches of its words against their name, brand and model, holding only `fields`.
+    pub async fn list_vehicles(&self, user_id: Uuid, query: Option<String>, after: Option<(Uuid, Uuid)>, limit: usize, fields: Vec<&'static str>) -> Option<VehicleSearchDTO> {
+        let columns = vehicle_mapper::columns(&fields);
 
-        let vehicle = self.vehicle_repository.save_vehicle(new_vehicle).await;
+        if let Some(query) = query.filter(|query| !query.trim().is_empty()) {
+            let vehicle_ids = self.vehicle_index.search(user_id, &query, limit).await?;
+
+            let mut vehicle_dtos = Vec::new();
+            for vehicle_id in vehicle_ids {
+                let projection = self.vehicle_repository.get_vehicle_projection(user_id, vehicle_id, columns.clone()).await;
+                vehicle_dtos.extend(projection.map(|projection| vehicle_mapper::get_vehicle_projection_dto(projection, &fields)));
+            }
+
+            return Some(VehicleSearchDTO { vehicles: vehicle_dtos, next: None });
+        }
+
+        let projections = self.vehicle_repository.get_vehicles_projection_page(user_id, after, limit, columns).await?;
+
+        let next = match projections.last() {
+            Some(last) if projections.len() == limit => last.vehicle_id.map(|vehicle_id| vehicle_mapper::page_token(user_id, vehicle_id)),
+            _ => None
+        };
+
+        let vehicles = projections.into_iter()
+            .map(|projection| vehicle_mapper::get_vehicle_projection_dto(projection, &fields))
+            .collect();
+
+        Some(VehicleSearchDTO { vehicles, next })
+    }
+
+    /// Rebuilds the full-text index from every vehicle stored in Cassandra, answering how many were indexed.
+    pub async fn reindex_vehicles(&self) -> Option<usize> {
+        self.vehicle_index.clear().await?;
+
+        let mut indexed: usize = 0;
+        let mut after = None;
+        loop {
+            let page = self.vehicle_repository.get_vehicles_page(None, after, REINDEX_PAGE_SIZE).await?;
+
+            after = match page.last() {
+                Some(last) if page.len() == REINDEX_PAGE_SIZE => Some((last.user_id, last.vehicle_id)),
+                _ => None
+            };
+            indexed += page.len();
+            self.vehicle_index.index(page).await?;
+
+            if after.is_none() {
+                return Some(indexed);
+            }
+        }
+    }
+
+    /// Vehicles matching a filter, read from the lookup partition of its most selective attribute and
+    /// checked against the vehicles themselves. A page may hold fewer than `limit` vehicles while still
+    /// having a `next` token; `None` when the filter has no searchable attribute or the lookup failed.
+    /// Vehicles are read in full to be checked, then projected onto `fields`.
+    pub async fn search_vehicles(&self, filter: VehicleFilter, after: Option<(Uuid, Uuid)>, limit: usize, fields: Vec<&'static str>) -> Option<VehicleSearchDTO> {
+        let (attribute, value) = filter.lookup_key()?;
+        let columns = vehicle_mapper::columns(&fields);
+
+        let mut vehicles: Vec<VehicleProjectionDTO> = Vec::new();
+        let mut after = after;
+        let mut scanned: usize = 0;
+
+        while scanned < MAX_SCANNED_KEYS {
+            let keys = self.vehicle_repository.find_vehicle_keys(attribute, &value, after, limit).await?;
+            let exhausted = keys.len() < limit;
+
+            for (user_id, vehicle_id) in keys {
+                after = Some((user_id, vehicle_id));
+                scanned += 1;
+
+                match self.vehicle_repository.get_vehicle(user_id, vehicle_id).await {
+                    Some(vehicle) if vehicle_lookup::attribute_value(&vehicle, attribute) != value => {
+                        // Left for the next search to remove when it fails.
+                        self.vehicle_repository.remove_vehicle_key(attribute, &value, user_id, vehicle_id).await;
+                    },
+                    Some(vehicle) if filter.matches(&vehicle) => {
+                        vehicles.push(vehicle_mapper::get_vehicle_projection_dto(VehicleProjection::of(vehicle, &columns), &fields));
+                        if vehicles.len() == limit {
+                            return Some(VehicleSearchDTO { vehicles, next: Some(vehicle_mapper::page_token(user_id, vehicle_id)) });
+                        }
+                    },
+                    // Not matching the other filters, or not written yet by a save in progress.
+                    _ => {}
+                }
+            }
+
+            if exhausted {
+                return Some(VehicleSearchDTO { vehicles, next: None });
+            }
+        }
+
+        Some(VehicleSearchDTO { vehicles, next: after.map(|(user_id, vehicle_id)| vehicle_mapper::page_token(user_id, vehicle_id)) })
+    }
+}
+
+impl VehicleService {
+    /// A vehicle missing from the full-text index is only missing from `q` searches until it is saved
+    /// again or the index is rebuilt, so a failed update does not fail the save.
+    async fn index(&self, vehicles: Vec<Vehicle>) {
+        if self.vehicle_index.index(vehicles).await.is_none() {
+            println!("Saved vehicles are missing from the full-text index until it is rebuilt");
+        }
+    }
+
+    /// The vehicle a save replaces, only read when the client gave the id of the vehicle to save.
+    async fn previous(&self, vehicle: &Vehicle, is_new: bool) -> Option<Vehicle> {
+        if is_new {
+            return None;
+        }
+
+        self.vehicle_repository.get_vehicle(vehicle.user_id, vehicle.vehicle_id).await
+    }
+}
+
+/// Adds the outcome of a chunk to the report of a whole import.
+pub fn merge_reports(report: &mut ImportReportDTO, chunk: ImportReportDTO) {
+    report.imported += chunk.imported;
+    report.failed += chunk.failed - chunk.errors.len();
+    for error in chunk.errors {
+        add_error(report, error.line, error.error);
+    }
+}
+
+/// Splits vehicles, in order, into batches of up to `MAX_BATCH_BYTES`, a vehicle larger than that going alone.
+fn batches(vehicles: Vec<(usize, (Vehicle, VehicleChange))>) -> Vec<Vec<(usize, (Vehicle, VehicleChange))>> {
+    let mut batches: Vec<Vec<(usize, (Vehicle, VehicleChange))>> = Vec::new();
+    let mut batch_bytes = 0;
+
+    for (index, (vehicle, change)) in vehicles {
+        let bytes = vehicle_repository::batch_bytes(&vehicle, &change);
+        match batches.last_mut() {
+            Some(batch) if batch_bytes + bytes <= MAX_BATCH_BYTES => {
+                batch_bytes += bytes;
+                batch.push((index, (vehicle, change)));
+            },
+            _ => {
+                batch_bytes = bytes;
+                batches.push(vec!((index, (vehicle, change))));
+            }
+        }
+    }
+
+    batches
+}
 
-        vehicle.map(|v| vehicle_mapper::get_vehicle_dto(v))
+fn add_error(report: &mut ImportReportDTO, line: usize, error: String) {
+    report.failed += 1;
+    if report.errors.len() < MAX_REPORTED_ERRORS {
+        report.errors.push(LineErrorDTO { line, error });
     }
 }
 
@@ -45,7 +342,9 @@ pub mod tests {
     use super::*;
 
     use mockall::mock;
-    use chrono::{Duration, NaiveDate, Utc, TimeZone};
+    use chrono::{NaiveDate, TimeZone};
+
+    use crate::domain::vehicle_history::VehicleVersion;
 
     macro_rules! aw {
         ($e: expr) => {
@@ -54,74 +353,111 @@ pub mod tests {
     }
 
     mock! {
-        VehicleRepositoryImpl {}
+        pub VehicleRepositoryImpl {}
 
         #[async_trait]
         impl VehicleRepository for VehicleRepositoryImpl {
             async fn get_vehicle(&self, user_id: Uuid, vehicle_id: Uuid) -> Option<Vehicle>;
-            async fn save_vehicle(&self, vehicle: Vehicle) -> Option<Vehicle>;
+            async fn get_vehicle_projection(&self, user_id: Uuid, vehicle_id: Uuid, columns: Vec<&'static str>) -> Option<VehicleProjection>;
+            async fn save_vehicle(&self, vehicle: Vehicle, change: VehicleChange) -> Option<Vehicle>;
+            async fn update_distance(&self, previous: &Vehicle, vehicle: Vehicle, change: VehicleChange) -> Option<bool>;
+            async fn save_vehicles(&self, vehicles: Vec<(Vehicle, VehicleChange)>) -> Option<Vec<Vehicle>>;
+            async fn get_vehicles_page(&self, user_id: Option<Uuid>, after: Option<(Uuid, Uuid)>, limit: usize) -> Option<Vec<Vehicle>>;
+            async fn get_vehicles_projection_page(&self, user_id: Uuid, after: Option<(Uuid, Uuid)>, limit: usize, columns: Vec<&'static str>) -> Option<Vec<VehicleProjection>>;
+            async fn find_vehicle_keys(&self, attribute: &str, value: &str, after: Option<(Uuid, Uuid)>, limit: usize) -> Option<Vec<(Uuid, Uuid)>>;
+            async fn remove_vehicle_key(&self, attribute: &str, value: &str, user_id: Uuid, vehicle_id: Uuid) -> Option<()>;
+            async fn evict_vehicle(&self, user_id: Uuid, vehicle_id: Uuid);
+        }
+    }
+
+    mock! {
+        pub VehicleHistoryRepositoryImpl {}
+
+        #[async_trait]
+        impl VehicleHistoryRepository for VehicleHistoryRepositoryImpl {
+            async fn get_versions(&self, user_id: Uuid, vehicle_id: Uuid, limit: usize) -> Option<Vec<VehicleVersion>>;
+            async fn get_version(&self, user_id: Uuid, vehicle_id: Uuid, version: Uuid) -> Option<VehicleVersion>;
+            async fn get_version_as_of(&self, user_id: Uuid, vehicle_id: Uuid, as_of_ms: i64) -> Option<VehicleVersion>;
+        }
+    }
+
+    mock! {
+        pub VehicleIndexImpl {}
+
+        #[async_trait]
+        impl VehicleIndex for VehicleIndexImpl {
+            async fn index(&self, vehicles: Vec<Vehicle>) -> Option<()>;
+            async fn search(&self, user_id: Uuid, query: &str, limit: usize) -> Option<Vec<Uuid>>;
+            async fn remove_vehicle(&self, vehicle_id: Uuid) -> Option<()>;
+            async fn remove_user(&self, user_id: Uuid) -> Option<()>;
+            async fn clear(&self) -> Option<()>;
         }
     }
 
     #[test]
-    fn when_get_vehicle_name_then_returns_vehicle_name() {
+    fn when_get_vehicle_then_reads_and_returns_requested_fields_only() {
         let mut vehicle_repository = MockVehicleRepositoryImpl::new();
 
         let user_id = Uuid::parse_str(fixture::USER_ID_STR).unwrap();
         let vehicle_id = Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap();
 
-        let expected_created_at: Duration = Duration::seconds(1000);
-        let expected_owner_since: NaiveDate = NaiveDate::from_num_days_from_ce(500);
-        let expected_manufacturing_date: NaiveDate = NaiveDate::from_num_days_from_ce(400);
-
-        vehicle_repository.expect_get_vehicle()
-            .withf(|user_id: &Uuid, _| user_id == &Uuid::parse_str(fixture::USER_ID_STR).unwrap())
-            .withf(|_, vehicle_id: &Uuid| vehicle_id == &Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap())
+        vehicle_repository.expect_get_vehicle_projection()
+            .withf(|user_id: &Uuid, vehicle_id: &Uuid, columns: &Vec<&'static str>| user_id == &Uuid::parse_str(fixture::USER_ID_STR).unwrap()
+                && vehicle_id == &Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap()
+                && *columns == vec!("name", "distance", "user_id", "vehicle_id"))
             .times(1)
-            .returning(move |_, _| Some(Vehicle {name: fixture::EXPECTED_VEHICLE_NAME.to_string(), user_id: user_id, vehicle_id: vehicle_id, created_at: expected_created_at,
-                                                 vehicle_type: fixture::EXPECTED_VEHICLE_TYPE.to_string(), retired_at: None, brand: fixture::EXPECTED_BRAND.to_string(),
-                                                 model: fixture::EXPECTED_MODEL.to_string(), distance: fixture::EXPECTED_DISTANCE, owner_since: expected_owner_since,
-                                                 manufacturing_date: expected_manufacturing_date, picture: None}))
+            .returning(move |user_id, vehicle_id, _| Some(VehicleProjection {
+                name: Some(fixture::EXPECTED_VEHICLE_NAME.to_string()),
+                user_id: Some(user_id),
+                vehicle_id: Some(vehicle_id),
+                distance: Some(fixture::EXPECTED_DISTANCE),
+                ..VehicleProjection::default()
+            }))
         ;
 
-        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository));
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
 
-        let vehicle_name = aw!(vehicle_service.get_vehicle_name(user_id, vehicle_id));
+        let vehicle_dto = aw!(vehicle_service.get_vehicle(user_id, vehicle_id, vec!("name", "distance"))).unwrap();
 
-        assert_eq!(fixture::EXPECTED_VEHICLE_NAME, vehicle_name);
+        assert_eq!(VehicleProjectionDTO {
+            name: Some(fixture::EXPECTED_VEHICLE_NAME.to_string()),
+            distance: Some(fixture::EXPECTED_DISTANCE),
+            ..VehicleProjectionDTO::default()
+        }, vehicle_dto);
     }
 
     #[test]
-    fn given_none_when_get_vehicle_name_then_returns_unknown() {
+    fn given_none_when_get_vehicle_then_returns_none() {
         let mut vehicle_repository = MockVehicleRepositoryImpl::new();
 
         let user_id = Uuid::parse_str(fixture::USER_ID_STR).unwrap();
         let vehicle_id = Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap();
 
-        vehicle_repository.expect_get_vehicle()
-            .withf(|user_id: &Uuid, _| user_id == &Uuid::parse_str(fixture::USER_ID_STR).unwrap())
-            .withf(|_, vehicle_id: &Uuid| vehicle_id == &Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap())
+        vehicle_repository.expect_get_vehicle_projection()
+            .withf(|user_id: &Uuid, vehicle_id: &Uuid, _| user_id == &Uuid::parse_str(fixture::USER_ID_STR).unwrap()
+                && vehicle_id == &Uuid::parse_str(fixture::VEHICLE_ID_STR).unwrap())
             .times(1)
-            .returning(move |_, _| None)
+            .returning(move |_, _, _| None)
         ;
 
-        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository));
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
 
-        let vehicle_name = aw!(vehicle_service.get_vehicle_name(user_id, vehicle_id));
-
-        assert_eq!(UNKNOWN.to_string(), vehicle_name);
+        assert!(aw!(vehicle_service.get_vehicle(user_id, vehicle_id, vec!("name"))).is_none());
     }
 
     #[test]
     fn when_save_vehicle_then_vehicle_is_stored() {
         let mut vehicle_repository = MockVehicleRepositoryImpl::new();
 
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(|_, _| None);
         vehicle_repository.expect_save_vehicle()
-            .withf(|vehicle: &Vehicle| vehicle.name == fixture::EXPECTED_VEHICLE_NAME.to_string())
+            .withf(|vehicle: &Vehicle, _: &VehicleChange| vehicle.name == fixture::EXPECTED_VEHICLE_NAME.to_string())
             .times(1)
-            .returning(move |vehicle| Some(vehicle));
+            .returning(move |vehicle, _| Some(vehicle));
 
-        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository));
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
 
         let vehicle_dto = VehicleDTO {
             name: fixture::EXPECTED_VEHICLE_NAME.to_string(),
@@ -138,7 +474,7 @@ pub mod tests {
             picture: Some(fixture::EXPECTED_PICTURE.to_string())
         };
 
-        let vehicle_dto_saved = aw!(vehicle_service.save_vehicle(vehicle_dto)).unwrap();
+        let vehicle_dto_saved = aw!(vehicle_service.save_vehicle(vehicle_dto, fixture::ACTOR)).unwrap();
 
         assert_eq!(vehicle_dto_saved.name, fixture::EXPECTED_VEHICLE_NAME.to_string());
         assert_eq!(vehicle_dto_saved.user_id, Default::default());
@@ -151,10 +487,409 @@ pub mod tests {
         assert_eq!(vehicle_dto_saved.distance, fixture::EXPECTED_DISTANCE);
         assert_eq!(vehicle_dto_saved.owner_since, NaiveDate::from_num_days_from_ce(fixture::EXPECTED_OWNER_SINCE));
         assert_eq!(vehicle_dto_saved.manufacturing_date, NaiveDate::from_num_days_from_ce(fixture::EXPECTED_MANUFACTURING_DATE));
-        assert_eq!(vehicle_dto_saved.picture, Some(fixture::EXPECTED_PICTURE.to_string()));
+        assert_eq!(vehicle_dto_saved.picture, None);
+    }
+
+    #[test]
+    fn given_vehicles_of_two_users_when_save_vehicles_then_saves_one_batch_per_user() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_save_vehicles()
+            .withf(|vehicles: &Vec<(Vehicle, VehicleChange)>| vehicles.len() == 2 && vehicles.iter().all(|(v, _)| v.user_id == fixture::user_id()))
+            .times(1)
+            .returning(move |vehicles| Some(vehicles.into_iter().map(|(vehicle, _)| vehicle).collect()));
+
+        vehicle_repository.expect_save_vehicles()
+            .withf(|vehicles: &Vec<(Vehicle, VehicleChange)>| vehicles.len() == 1 && vehicles[0].0.user_id == fixture::other_user_id())
+            .times(1)
+            .returning(move |_| None);
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        let results = aw!(vehicle_service.save_vehicles(vec!(
+            fixture::vehicle_dto(fixture::user_id(), "first"),
+            fixture::vehicle_dto(fixture::other_user_id(), "second"),
+            fixture::vehicle_dto(fixture::user_id(), "third")), fixture::ACTOR));
+
+        assert_eq!(3, results.len());
+        assert_eq!("first", results[0].as_ref().unwrap().name);
+        assert!(results[1].is_none());
+        assert_eq!("third", results[2].as_ref().unwrap().name);
+    }
+
+    #[test]
+    fn given_50_vehicles_of_a_user_when_save_vehicles_then_splits_them_into_batches_under_the_size_limit() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+        let batch_sizes = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let saved_sizes = batch_sizes.clone();
+        vehicle_repository.expect_save_vehicles()
+            .returning(move |vehicles| {
+                let bytes = vehicles.iter().map(|(vehicle, change)| vehicle_repository::batch_bytes(vehicle, change)).sum::<usize>();
+                saved_sizes.lock().unwrap().push((vehicles.len(), bytes));
+                Some(vehicles.into_iter().map(|(vehicle, _)| vehicle).collect())
+            });
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        let results = aw!(vehicle_service.save_vehicles((0..50).map(|i| fixture::vehicle_dto(fixture::user_id(), &format!("vehicle {}", i))).collect(), fixture::ACTOR));
+
+        assert!(results.iter().all(Option::is_some));
+        let batch_sizes = batch_sizes.lock().unwrap();
+        assert!(batch_sizes.len() > 1);
+        assert_eq!(50, batch_sizes.iter().map(|(vehicles, _)| vehicles).sum::<usize>());
+        assert!(batch_sizes.iter().all(|(_, bytes)| *bytes <= MAX_BATCH_BYTES));
+    }
+
+    #[test]
+    fn given_invalid_and_unsaved_lines_when_import_vehicles_then_reports_them_by_line() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_save_vehicles()
+            .withf(|vehicles: &Vec<(Vehicle, VehicleChange)>| vehicles.len() == 1 && vehicles[0].0.user_id == fixture::user_id())
+            .times(1)
+            .returning(move |vehicles| Some(vehicles.into_iter().map(|(vehicle, _)| vehicle).collect()));
+
+        vehicle_repository.expect_save_vehicles()
+            .withf(|vehicles: &Vec<(Vehicle, VehicleChange)>| vehicles.len() == 1 && vehicles[0].0.user_id == fixture::other_user_id())
+            .times(1)
+            .returning(move |_| None);
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        let lines = vec!(
+            (1, vehicle_records::write(RecordFormat::Ndjson, &fixture::vehicle_dto(fixture::user_id(), "first"))),
+            (2, "{ \"name\": ".to_string()),
+            (4, vehicle_records::write(RecordFormat::Ndjson, &fixture::vehicle_dto(fixture::other_user_id(), "second"))));
+
+        let report = aw!(vehicle_service.import_vehicles(RecordFormat::Ndjson, Vec::new(), lines, fixture::ACTOR));
+
+        assert_eq!(1, report.imported);
+        assert_eq!(2, report.failed);
+        assert_eq!(2, report.errors[0].line);
+        assert_eq!(LineErrorDTO { line: 4, error: "Failed to save vehicle".to_string() }, report.errors[1]);
+        assert_eq!(vec!(fixture::user_id()), report.imported_keys.iter().map(|(user_id, _)| *user_id).collect::<Vec<Uuid>>());
+    }
+
+    #[test]
+    fn given_errors_beyond_limit_when_merge_reports_then_counts_them_without_keeping_them() {
+        let mut report = ImportReportDTO::default();
+
+        for line in 0..MAX_REPORTED_ERRORS + 1 {
+            merge_reports(&mut report, ImportReportDTO {
+                imported: 1,
+                failed: 1,
+                errors: vec!(LineErrorDTO { line, error: "error".to_string() }),
+                ..ImportReportDTO::default()
+            });
+        }
+
+        assert_eq!(MAX_REPORTED_ERRORS + 1, report.imported);
+        assert_eq!(MAX_REPORTED_ERRORS + 1, report.failed);
+        assert_eq!(MAX_REPORTED_ERRORS, report.errors.len());
+    }
+
+    #[test]
+    fn when_export_vehicles_then_returns_page_of_vehicle_dtos() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicles_page()
+            .withf(|user_id: &Option<Uuid>, after: &Option<(Uuid, Uuid)>, limit: &usize| user_id.is_none() && after.is_none() && *limit == 10)
+            .times(1)
+            .returning(move |_, _, _| Some(vec!(vehicle_mapper::get_vehicle(fixture::vehicle_dto(fixture::user_id(), "first")))));
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        let vehicle_dtos = aw!(vehicle_service.export_vehicles(None, None, 10)).unwrap();
+
+        assert_eq!("first", vehicle_dtos[0].name);
+    }
+
+    #[test]
+    fn given_stale_and_unmatched_keys_when_search_vehicles_then_returns_matching_vehicles_and_removes_stale_keys() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        let matching = fixture::vehicle("matching", 20);
+        let renamed = Vehicle { brand: "another brand".to_string(), ..fixture::vehicle("renamed", 20) };
+        let too_short = fixture::vehicle("too short", 5);
+        let keys = vec!((matching.user_id, matching.vehicle_id), (renamed.user_id, renamed.vehicle_id),
+                        (too_short.user_id, too_short.vehicle_id), (fixture::user_id(), Uuid::new_v4()));
+
+        vehicle_repository.expect_find_vehicle_keys()
+            .withf(|attribute: &str, value: &str, after: &Option<(Uuid, Uuid)>, limit: &usize| attribute == vehicle_lookup::BRAND
+                && value == "the brand" && after.is_none() && *limit == 10)
+            .times(1)
+            .returning(move |_, _, _, _| Some(keys.clone()));
+
+        let vehicles = vec!(matching.clone(), renamed.clone(), too_short);
+        vehicle_repository.expect_get_vehicle()
+            .times(4)
+            .returning(move |_, vehicle_id| vehicles.iter().find(|vehicle| vehicle.vehicle_id == vehicle_id).cloned());
+
+        let renamed_id = renamed.vehicle_id;
+        vehicle_repository.expect_remove_vehicle_key()
+            .withf(move |attribute: &str, value: &str, _, vehicle_id: &Uuid| attribute == vehicle_lookup::BRAND && value == "the brand" && *vehicle_id == renamed_id)
+            .times(1)
+            .returning(|_, _, _, _| Some(()));
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        let filter = VehicleFilter { brand: Some(" The Brand".to_string()), min_distance: Some(10), ..VehicleFilter::default() };
+        let page = aw!(vehicle_service.search_vehicles(filter, None, 10, vec!("name"))).unwrap();
+
+        assert_eq!(1, page.vehicles.len());
+        assert_eq!(VehicleProjectionDTO { name: Some("matching".to_string()), ..VehicleProjectionDTO::default() }, page.vehicles[0]);
+        assert!(page.next.is_none());
+    }
+
+    #[test]
+    fn given_full_page_when_search_vehicles_then_returns_token_of_last_vehicle() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        let first = fixture::vehicle("first", 20);
+        let second = fixture::vehicle("second", 20);
+        let keys = vec!((first.user_id, first.vehicle_id), (second.user_id, second.vehicle_id));
+        let expected_next = vehicle_mapper::page_token(second.user_id, second.vehicle_id);
+
+        vehicle_repository.expect_find_vehicle_keys()
+            .withf(|attribute: &str, value: &str, _, _| attribute == vehicle_lookup::BRAND && value == fixture::EXPECTED_BRAND)
+            .times(1)
+            .returning(move |_, _, _, _| Some(keys.clone()));
+
+        let vehicles = vec!(first, second);
+        vehicle_repository.expect_get_vehicle()
+            .times(2)
+            .returning(move |_, vehicle_id| vehicles.iter().find(|vehicle| vehicle.vehicle_id == vehicle_id).cloned());
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        let filter = VehicleFilter { brand: Some("The Brand ".to_string()), retired: Some(false), ..VehicleFilter::default() };
+        let page = aw!(vehicle_service.search_vehicles(filter, None, 2, vehicle_mapper::all_fields())).unwrap();
+
+        assert_eq!(2, page.vehicles.len());
+        assert_eq!(Some(expected_next), page.next);
+    }
+
+    #[test]
+    fn given_distance_filter_only_when_search_vehicles_then_returns_none() {
+        let vehicle_service = VehicleService::new(Arc::new(MockVehicleRepositoryImpl::new()), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        let filter = VehicleFilter { max_distance: Some(100), ..VehicleFilter::default() };
+
+        assert!(aw!(vehicle_service.search_vehicles(filter, None, 10, vehicle_mapper::all_fields())).is_none());
+    }
+
+    #[test]
+    fn when_save_vehicle_then_indexes_saved_vehicle() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+        let mut vehicle_index = MockVehicleIndexImpl::new();
+
+        vehicle_repository.expect_save_vehicle()
+            .times(1)
+            .returning(move |vehicle, _| Some(vehicle));
+        vehicle_index.expect_index()
+            .withf(|vehicles: &Vec<Vehicle>| vehicles.len() == 1 && vehicles[0].name == "indexed")
+            .times(1)
+            .returning(|_| None);
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(vehicle_index), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        assert!(aw!(vehicle_service.save_vehicle(fixture::vehicle_dto(fixture::user_id(), "indexed"), fixture::ACTOR)).is_some());
+    }
+
+    #[test]
+    fn given_new_vehicle_when_save_vehicle_then_saves_created_event_without_reading_previous() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(0);
+        vehicle_repository.expect_save_vehicle()
+            .withf(|vehicle: &Vehicle, change: &VehicleChange| vehicle.name == "created" && change.event == VehicleEventKind::Created
+                && change.version.actor == fixture::ACTOR)
+            .times(1)
+            .returning(move |vehicle, _| Some(vehicle));
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        assert!(aw!(vehicle_service.save_vehicle(fixture::vehicle_dto(fixture::user_id(), "created"), fixture::ACTOR)).is_some());
+    }
+
+    #[test]
+    fn given_vehicles_in_use_when_save_vehicles_retiring_one_then_saves_retired_and_updated_events() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(2)
+            .returning(|_, _| Some(fixture::vehicle("previous", fixture::EXPECTED_DISTANCE)));
+        vehicle_repository.expect_save_vehicles()
+            .withf(|vehicles: &Vec<(Vehicle, VehicleChange)>| vehicles.iter().map(|(_, change)| change.event).collect::<Vec<_>>()
+                == vec!(VehicleEventKind::Retired, VehicleEventKind::Updated))
+            .times(1)
+            .returning(move |vehicles| Some(vehicles.into_iter().map(|(vehicle, _)| vehicle).collect()));
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        let retired = VehicleDTO {
+            vehicle_id: Some(Uuid::new_v4()),
+            retired_at: Some(Utc.timestamp(fixture::EXPECTED_CREATED_AT, 0)),
+            ..fixture::vehicle_dto(fixture::user_id(), "retired")
+        };
+        let updated = VehicleDTO { vehicle_id: Some(Uuid::new_v4()), ..fixture::vehicle_dto(fixture::user_id(), "updated") };
+
+        let results = aw!(vehicle_service.save_vehicles(vec!(retired, updated), fixture::ACTOR));
+
+        assert!(results.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn given_query_when_list_vehicles_then_returns_indexed_matches_of_user() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+        let mut vehicle_index = MockVehicleIndexImpl::new();
+
+        let found = fixture::vehicle("test vehicle 2", 20);
+        let found_id = found.vehicle_id;
+        let transferred_id = Uuid::new_v4();
+
+        vehicle_index.expect_search()
+            .withf(|user_id: &Uuid, query: &str, limit: &usize| *user_id == fixture::user_id() && query == "time rtm" && *limit == 20)
+            .times(1)
+            .returning(move |_, _, _| Some(vec!(found_id, transferred_id)));
+        vehicle_repository.expect_get_vehicle_projection()
+            .withf(|_, _, columns: &Vec<&'static str>| *columns == vec!("vehicle_id", "user_id"))
+            .times(2)
+            .returning(move |_, vehicle_id, columns| Some(found.clone())
+                .filter(|vehicle| vehicle.vehicle_id == vehicle_id)
+                .map(|vehicle| VehicleProjection::of(vehicle, &columns)));
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(vehicle_index), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        let page = aw!(vehicle_service.list_vehicles(fixture::user_id(), Some("time rtm".to_string()), None, 20, vec!("vehicle_id"))).unwrap();
+
+        assert_eq!(1, page.vehicles.len());
+        assert_eq!(VehicleProjectionDTO { vehicle_id: Some(found_id), ..VehicleProjectionDTO::default() }, page.vehicles[0]);
+        assert!(page.next.is_none());
+    }
+
+    #[test]
+    fn given_no_query_when_list_vehicles_then_returns_page_of_user_vehicles() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+
+        let last = fixture::vehicle("second", 20);
+        let expected_next = vehicle_mapper::page_token(last.user_id, last.vehicle_id);
+
+        vehicle_repository.expect_get_vehicles_projection_page()
+            .withf(|user_id: &Uuid, after: &Option<(Uuid, Uuid)>, limit: &usize, columns: &Vec<&'static str>| *user_id == fixture::user_id() && after.is_none() && *limit == 2
+                && *columns == vec!("model", "user_id", "vehicle_id"))
+            .times(1)
+            .returning(move |_, _, _, columns| Some(vec!(VehicleProjection::of(fixture::vehicle("first", 20), &columns), VehicleProjection::of(last.clone(), &columns))));
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        let page = aw!(vehicle_service.list_vehicles(fixture::user_id(), Some(" ".to_string()), None, 2, vec!("model"))).unwrap();
+
+        assert_eq!(2, page.vehicles.len());
+        assert_eq!(Some(fixture::vehicle("first", 20).model), page.vehicles[0].model);
+        assert!(page.vehicles[0].vehicle_id.is_none());
+        assert_eq!(Some(expected_next), page.next);
+    }
+
+    #[test]
+    fn when_reindex_vehicles_then_clears_index_and_indexes_every_page() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+        let mut vehicle_index = MockVehicleIndexImpl::new();
+
+        let full_page: Vec<Vehicle> = (0..REINDEX_PAGE_SIZE).map(|_| fixture::vehicle("vehicle", 20)).collect();
+        let last_key = full_page.last().map(|last| (last.user_id, last.vehicle_id));
+
+        vehicle_index.expect_clear()
+            .times(1)
+            .returning(|| Some(()));
+        vehicle_repository.expect_get_vehicles_page()
+            .withf(|user_id: &Option<Uuid>, after: &Option<(Uuid, Uuid)>, _| user_id.is_none() && after.is_none())
+            .times(1)
+            .returning(move |_, _, _| Some(full_page.clone()));
+        vehicle_repository.expect_get_vehicles_page()
+            .withf(move |_, after: &Option<(Uuid, Uuid)>, _| *after == last_key)
+            .times(1)
+            .returning(move |_, _, _| Some(vec!(fixture::vehicle("last", 20))));
+        vehicle_index.expect_index()
+            .times(2)
+            .returning(|_| Some(()));
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(vehicle_index), Arc::new(MockVehicleHistoryRepositoryImpl::new()));
+
+        assert_eq!(Some(REINDEX_PAGE_SIZE + 1), aw!(vehicle_service.reindex_vehicles()));
+    }
+
+    #[test]
+    fn given_version_saved_before_as_of_when_get_vehicle_as_of_then_answers_fields_of_that_version() {
+        let mut history_repository = MockVehicleHistoryRepositoryImpl::new();
+
+        history_repository.expect_get_version_as_of()
+            .withf(|_, _, as_of_ms: &i64| *as_of_ms == 7000)
+            .times(1)
+            .returning(|_, _, _| Some(fixture::version(&fixture::vehicle("as of then", 30))));
+
+        let vehicle_service = VehicleService::new(Arc::new(MockVehicleRepositoryImpl::new()), Arc::new(fixture::vehicle_index()), Arc::new(history_repository));
+
+        let vehicle = aw!(vehicle_service.get_vehicle_as_of(fixture::user_id(), Uuid::new_v4(), vec!("name", "distance"), Utc.timestamp_millis(7000))).unwrap();
+
+        assert_eq!((Some("as of then".to_string()), Some(30), None), (vehicle.name, vehicle.distance, vehicle.brand));
+    }
+
+    #[test]
+    fn given_version_when_restore_version_then_saves_it_as_a_new_version_restored_from_it() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+        let mut history_repository = MockVehicleHistoryRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(|_, _| Some(fixture::vehicle("current", 300)));
+        history_repository.expect_get_version()
+            .withf(|_, _, version: &Uuid| *version == fixture::version_id())
+            .times(1)
+            .returning(|_, _, _| Some(fixture::version(&fixture::vehicle("restored", 100))));
+        vehicle_repository.expect_save_vehicle()
+            .withf(|vehicle: &Vehicle, change: &VehicleChange| vehicle.distance == 100
+                && change.version.restored_from == Some(fixture::version_id())
+                && change.version.actor == fixture::ACTOR
+                && change.version.diff.contains("distance"))
+            .times(1)
+            .returning(move |vehicle, _| Some(vehicle));
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(history_repository));
+
+        let vehicle_dto = aw!(vehicle_service.restore_version(fixture::user_id(), Uuid::new_v4(), fixture::version_id(), fixture::ACTOR)).unwrap();
+
+        assert_eq!("restored", vehicle_dto.name);
+    }
+
+    #[test]
+    fn given_unknown_version_when_restore_version_then_returns_version_not_found() {
+        let mut vehicle_repository = MockVehicleRepositoryImpl::new();
+        let mut history_repository = MockVehicleHistoryRepositoryImpl::new();
+
+        vehicle_repository.expect_get_vehicle()
+            .times(1)
+            .returning(|_, _| Some(fixture::vehicle("current", 300)));
+        history_repository.expect_get_version()
+            .times(1)
+            .returning(|_, _, _| None);
+        vehicle_repository.expect_save_vehicle()
+            .times(0);
+
+        let vehicle_service = VehicleService::new(Arc::new(vehicle_repository), Arc::new(fixture::vehicle_index()), Arc::new(history_repository));
+
+        let result = aw!(vehicle_service.restore_version(fixture::user_id(), Uuid::new_v4(), fixture::version_id(), fixture::ACTOR));
+
+        assert_eq!(Err(HistoryError::VersionNotFound), result.map(|_| ()));
     }
 
     mod fixture {
+        use super::*;
+
+        pub const ACTOR: &str = "the actor";
+        pub const VERSION_STR: &str = "5d3c4e80-4f9a-11ec-8c4f-2f6b1a7d9e01";
         pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
         pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
         pub const EXPECTED_VEHICLE_NAME: &str = "the vehicle name";
@@ -166,5 +901,50 @@ pub mod tests {
         pub const EXPECTED_CREATED_AT: i64 = 5;
         pub const EXPECTED_OWNER_SINCE: i32 = 15;
         pub const EXPECTED_MANUFACTURING_DATE: i32 = 15;
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn other_user_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_dto(user_id: Uuid, name: &str) -> VehicleDTO {
+            VehicleDTO {
+                name: name.to_string(),
+                user_id,
+                vehicle_id: None,
+                created_at: Utc.timestamp(EXPECTED_CREATED_AT, 0),
+                vehicle_type: EXPECTED_VEHICLE_TYPE.to_string(),
+                retired_at: None,
+                brand: EXPECTED_BRAND.to_string(),
+                model: EXPECTED_MODEL.to_string(),
+                distance: EXPECTED_DISTANCE,
+                owner_since: NaiveDate::from_num_days_from_ce(EXPECTED_OWNER_SINCE),
+                manufacturing_date: NaiveDate::from_num_days_from_ce(EXPECTED_MANUFACTURING_DATE),
+                picture: None
+            }
+        }
+
+        /// Index accepting every update, for tests not about the full-text index.
+        pub fn vehicle_index() -> MockVehicleIndexImpl {
+            let mut vehicle_index = MockVehicleIndexImpl::new();
+            vehicle_index.expect_index()
+                .returning(|_| Some(()));
+            vehicle_index
+        }
+
+        pub fn vehicle(name: &str, distance: i32) -> Vehicle {
+            Vehicle { distance, ..vehicle_mapper::get_vehicle(vehicle_dto(user_id(), name)) }
+        }
+
+        pub fn version_id() -> Uuid {
+            Uuid::parse_str(VERSION_STR).unwrap()
+        }
+
+        pub fn version(vehicle: &Vehicle) -> VehicleVersion {
+            VehicleVersion { version: version_id(), saved_at_ms: 5000, ..vehicle_history_mapper::get_vehicle_change(None, vehicle, ACTOR, None).version }
+        }
     }
 }
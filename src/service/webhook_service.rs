@@ -0,0 +1,331 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use rocket::serde::uuid::Uuid;
+use mockall::automock;
+
+use crate::repository::webhook_repository::WebhookRepository;
+use crate::mapper::webhook_mapper;
+use crate::domain::vehicle_event::VehicleEventKind;
+use crate::domain::webhook::{self, Webhook};
+use crate::dto::webhook_dto::{WebhookDTO, WebhookDeliveryDTO, WebhookRequestDTO};
+
+/// Events posted to a webhook created without `events`.
+pub const DEFAULT_EVENTS: [VehicleEventKind; 3] = [VehicleEventKind::Created, VehicleEventKind::Retired, VehicleEventKind::Transferred];
+/// Deliveries answered by a single request of the delivery log.
+pub const MAX_DELIVERIES: usize = 100;
+
+#[derive(Debug, PartialEq)]
+pub enum WebhookError {
+    WebhookNotFound,
+    InvalidWebhook,
+    /// No webhook dispatcher is fed by the outbox relay, so events would never be posted.
+    DeliveryDisabled,
+    StorageFailure
+}
+
+pub struct WebhookService {
+    webhook_repository: Arc<dyn WebhookRepository + Sync + Send>,
+    delivery_enabled: bool
+}
+
+#[automock]
+impl WebhookService {
+    /// `delivery_enabled` tells whether the outbox relay feeds a webhook dispatcher, without which no webhook
+    /// may be created.
+    pub fn new(webhook_repository: Arc<dyn WebhookRepository + Sync + Send>, delivery_enabled: bool) -> WebhookService {
+        WebhookService {
+            webhook_repository,
+            delivery_enabled
+        }
+    }
+
+    /// Stores a webhook with a new secret, answered this once for the partner to check signatures with.
+    /// URLs pointing at loopback, private or link-local addresses are refused.
+    pub async fn create_webhook(&self, user_id: Uuid, request: WebhookRequestDTO) -> Result<WebhookDTO, WebhookError> {
+        if !self.delivery_enabled {
+            return Err(WebhookError::DeliveryDisabled);
+        }
+        let url = reqwest::Url::parse(&request.url).map_err(|_| WebhookError::InvalidWebhook)?;
+        if !matches!(url.scheme(), "http" | "https") || !webhook::is_public_url(&url) {
+            return Err(WebhookError::InvalidWebhook);
+        }
+
+        let webhook = Webhook {
+            user_id,
+            webhook_id: Uuid::new_v4(),
+            url: url.to_string(),
+            secret: format!("{}{}", Uuid::new_v4().to_simple(), Uuid::new_v4().to_simple()),
+            events: events(request.events)?,
+            created_at: Duration::seconds(Utc::now().timestamp())
+        };
+
+        let webhook = self.webhook_repository.save_webhook(webhook).await
+            .ok_or(WebhookError::StorageFailure)?;
+
+        let secret = webhook.secret.clone();
+        Ok(WebhookDTO { secret: Some(secret), ..webhook_mapper::get_webhook_dto(webhook) })
+    }
+
+    pub async fn get_webhooks(&self, user_id: Uuid) -> Result<Vec<WebhookDTO>, WebhookError> {
+        self.webhook_repository.get_webhooks(user_id).await
+            .map(|webhooks| webhooks.into_iter().map(webhook_mapper::get_webhook_dto).collect())
+            .ok_or(WebhookError::StorageFailure)
+    }
+
+    /// Stops posting events to a webhook. Its delivery log is kept, and deliveries waiting for a retry
+    /// are dead-lettered instead of being attempted again.
+    pub async fn delete_webhook(&self, user_id: Uuid, webhook_id: Uuid) -> Result<(), WebhookError> {
+        self.webhook_repository.get_webhook(user_id, webhook_id).await
+            .ok_or(WebhookError::WebhookNotFound)?;
+
+        self.webhook_repository.delete_webhook(user_id, webhook_id).await
+            .ok_or(WebhookError::StorageFailure)
+    }
+
+    /// Latest `limit` deliveries of a webhook of the user with `status`, newest first. Filtered deliveries are
+    /// read a page at a time until `limit` of them have the status or the log ends.
+    pub async fn get_deliveries(&self, user_id: Uuid, webhook_id: Uuid, status: Option<String>, limit: usize) -> Result<Vec<WebhookDeliveryDTO>, WebhookError> {
+        self.webhook_repository.get_webhook(user_id, webhook_id).await
+            .ok_or(WebhookError::WebhookNotFound)?;
+
+        let limit = limit.min(MAX_DELIVERIES);
+        let page_size = if status.is_some() { MAX_DELIVERIES } else { limit };
+        let mut deliveries = Vec::new();
+        let mut after = None;
+
+        while deliveries.len() < limit {
+            let page = self.webhook_repository.get_deliveries(webhook_id, after, page_size).await
+                .ok_or(WebhookError::StorageFailure)?;
+            let last_page = page.len() < page_size;
+            after = page.last().cloned();
+
+            deliveries.extend(page.into_iter()
+                .filter(|delivery| status.as_ref().map_or(true, |status| &delivery.status == status)));
+
+            if last_page {
+                break;
+            }
+        }
+        deliveries.truncate(limit);
+
+        Ok(deliveries.into_iter().map(webhook_mapper::get_delivery_dto).collect())
+    }
+}
+
+/// Names of the requested events, without repetitions, rejecting unknown and empty lists.
+fn events(requested: Option<Vec<String>>) -> Result<Vec<String>, WebhookError> {
+    let requested = match requested {
+        Some(requested) => requested,
+        None => return Ok(DEFAULT_EVENTS.iter().map(|kind| kind.name().to_string()).collect())
+    };
+
+    let mut events: Vec<String> = Vec::new();
+    for event in requested {
+        let kind = VehicleEventKind::from_name(event.trim()).ok_or(WebhookError::InvalidWebhook)?;
+        if !events.iter().any(|name| name == kind.name()) {
+            events.push(kind.name().to_string());
+        }
+    }
+
+    if events.is_empty() {
+        return Err(WebhookError::InvalidWebhook);
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use mockall::mock;
+
+    use crate::domain::webhook::{PendingDelivery, WebhookDelivery, DELIVERY_DEAD_LETTERED, DELIVERY_DELIVERED};
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    mock! {
+        pub WebhookRepositoryImpl {}
+
+        #[async_trait]
+        impl WebhookRepository for WebhookRepositoryImpl {
+            async fn get_webhook(&self, user_id: Uuid, webhook_id: Uuid) -> Option<Webhook>;
+            async fn get_webhooks(&self, user_id: Uuid) -> Option<Vec<Webhook>>;
+            async fn save_webhook(&self, webhook: Webhook) -> Option<Webhook>;
+            async fn delete_webhook(&self, user_id: Uuid, webhook_id: Uuid) -> Option<()>;
+            async fn start_delivery(&self, delivery: WebhookDelivery, lease_until_ms: i64) -> Option<WebhookDelivery>;
+            async fn save_delivery(&self, delivery: WebhookDelivery) -> Option<WebhookDelivery>;
+            async fn get_delivery(&self, webhook_id: Uuid, created_at: Duration, delivery_id: Uuid) -> Option<WebhookDelivery>;
+            async fn get_pending_deliveries(&self, shard: i32, after: Option<PendingDelivery>, limit: usize) -> Option<Vec<PendingDelivery>>;
+            async fn lease_delivery(&self, pending: &PendingDelivery, lease_until_ms: i64) -> Option<bool>;
+            async fn delete_pending_delivery(&self, pending: &PendingDelivery) -> Option<()>;
+            async fn get_deliveries(&self, webhook_id: Uuid, after: Option<WebhookDelivery>, limit: usize) -> Option<Vec<WebhookDelivery>>;
+        }
+    }
+
+    #[test]
+    fn when_create_webhook_then_stores_it_with_default_events_and_answers_secret() {
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_save_webhook()
+            .withf(|webhook: &Webhook| webhook.user_id == fixture::user_id()
+                && webhook.events == vec!("created".to_string(), "retired".to_string(), "transferred".to_string())
+                && webhook.secret.len() == 64)
+            .times(1)
+            .returning(|webhook| Some(webhook));
+
+        let webhook_service = WebhookService::new(Arc::new(webhook_repository), true);
+
+        let webhook_dto = aw!(webhook_service.create_webhook(fixture::user_id(), WebhookRequestDTO { url: fixture::URL.to_string(), events: None })).unwrap();
+
+        assert_eq!(fixture::URL, webhook_dto.url);
+        assert!(webhook_dto.secret.is_some());
+    }
+
+    #[test]
+    fn given_no_dispatcher_when_create_webhook_then_returns_delivery_disabled() {
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+        webhook_repository.expect_save_webhook()
+            .times(0);
+
+        let webhook_service = WebhookService::new(Arc::new(webhook_repository), false);
+
+        let request = WebhookRequestDTO { url: fixture::URL.to_string(), events: None };
+        assert_eq!(Err(WebhookError::DeliveryDisabled), aw!(webhook_service.create_webhook(fixture::user_id(), request)).map(|_| ()));
+    }
+
+    #[test]
+    fn given_unknown_event_or_url_when_create_webhook_then_returns_invalid_webhook() {
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+        webhook_repository.expect_save_webhook()
+            .times(0);
+
+        let webhook_service = WebhookService::new(Arc::new(webhook_repository), true);
+
+        let unknown_event = WebhookRequestDTO { url: fixture::URL.to_string(), events: Some(vec!("repainted".to_string())) };
+        let no_event = WebhookRequestDTO { url: fixture::URL.to_string(), events: Some(vec!()) };
+        let not_http = WebhookRequestDTO { url: "ftp://partner.example/hooks".to_string(), events: None };
+        let metadata = WebhookRequestDTO { url: "http://169.254.169.254/latest/meta-data".to_string(), events: None };
+
+        assert_eq!(Err(WebhookError::InvalidWebhook), aw!(webhook_service.create_webhook(fixture::user_id(), unknown_event)).map(|_| ()));
+        assert_eq!(Err(WebhookError::InvalidWebhook), aw!(webhook_service.create_webhook(fixture::user_id(), no_event)).map(|_| ()));
+        assert_eq!(Err(WebhookError::InvalidWebhook), aw!(webhook_service.create_webhook(fixture::user_id(), not_http)).map(|_| ()));
+        assert_eq!(Err(WebhookError::InvalidWebhook), aw!(webhook_service.create_webhook(fixture::user_id(), metadata)).map(|_| ()));
+    }
+
+    #[test]
+    fn given_status_when_get_deliveries_then_returns_deliveries_with_status() {
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_get_webhook()
+            .times(1)
+            .returning(|_, _| Some(fixture::webhook()));
+        webhook_repository.expect_get_deliveries()
+            .withf(|webhook_id: &Uuid, after: &Option<WebhookDelivery>, limit: &usize| webhook_id == &fixture::webhook_id() && after.is_none() && *limit == MAX_DELIVERIES)
+            .times(1)
+            .returning(|_, _, _| Some(vec!(fixture::delivery(DELIVERY_DELIVERED), fixture::delivery(DELIVERY_DEAD_LETTERED))));
+
+        let webhook_service = WebhookService::new(Arc::new(webhook_repository), true);
+
+        let deliveries = aw!(webhook_service.get_deliveries(fixture::user_id(), fixture::webhook_id(), Some(DELIVERY_DEAD_LETTERED.to_string()), 1000)).unwrap();
+
+        assert_eq!(1, deliveries.len());
+        assert_eq!(DELIVERY_DEAD_LETTERED, deliveries[0].status);
+    }
+
+    #[test]
+    fn given_status_missing_from_first_page_when_get_deliveries_then_reads_next_pages_until_limit() {
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_get_webhook()
+            .times(1)
+            .returning(|_, _| Some(fixture::webhook()));
+        webhook_repository.expect_get_deliveries()
+            .withf(|_, after: &Option<WebhookDelivery>, _| after.is_none())
+            .times(1)
+            .returning(|_, _, limit| Some(vec!(fixture::delivery(DELIVERY_DELIVERED); limit)));
+        webhook_repository.expect_get_deliveries()
+            .withf(|_, after: &Option<WebhookDelivery>, _| after.as_ref().map_or(false, |after| after.status == DELIVERY_DELIVERED))
+            .times(1)
+            .returning(|_, _, limit| Some(vec!(fixture::delivery(DELIVERY_DEAD_LETTERED); limit)));
+
+        let webhook_service = WebhookService::new(Arc::new(webhook_repository), true);
+
+        let deliveries = aw!(webhook_service.get_deliveries(fixture::user_id(), fixture::webhook_id(), Some(DELIVERY_DEAD_LETTERED.to_string()), 20)).unwrap();
+
+        assert_eq!(20, deliveries.len());
+        assert!(deliveries.iter().all(|delivery| delivery.status == DELIVERY_DEAD_LETTERED));
+    }
+
+    #[test]
+    fn given_webhook_of_other_user_when_get_deliveries_then_returns_not_found() {
+        let mut webhook_repository = MockWebhookRepositoryImpl::new();
+
+        webhook_repository.expect_get_webhook()
+            .times(1)
+            .returning(|_, _| None);
+        webhook_repository.expect_get_deliveries()
+            .times(0);
+
+        let webhook_service = WebhookService::new(Arc::new(webhook_repository), true);
+
+        let result = aw!(webhook_service.get_deliveries(fixture::user_id(), fixture::webhook_id(), None, 20));
+
+        assert_eq!(Err(WebhookError::WebhookNotFound), result.map(|_| ()));
+    }
+
+    pub mod fixture {
+        use super::*;
+
+        pub const USER_ID_STR: &str = "a906615e-2e6a-4edb-9377-5a6b8544791b";
+        pub const WEBHOOK_ID_STR: &str = "5f0e3c2a-8d1b-4c6e-9a7f-2b3c4d5e6f70";
+        pub const VEHICLE_ID_STR: &str = "88573010-cf4c-490e-9d29-f8517dc60b90";
+        pub const URL: &str = "https://partner.example/hooks";
+        pub const SECRET: &str = "the secret";
+
+        pub fn user_id() -> Uuid {
+            Uuid::parse_str(USER_ID_STR).unwrap()
+        }
+
+        pub fn webhook_id() -> Uuid {
+            Uuid::parse_str(WEBHOOK_ID_STR).unwrap()
+        }
+
+        pub fn vehicle_id() -> Uuid {
+            Uuid::parse_str(VEHICLE_ID_STR).unwrap()
+        }
+
+        pub fn webhook() -> Webhook {
+            Webhook {
+                user_id: user_id(),
+                webhook_id: webhook_id(),
+                url: URL.to_string(),
+                secret: SECRET.to_string(),
+                events: vec!("created".to_string(), "retired".to_string()),
+                created_at: Duration::seconds(5)
+            }
+        }
+
+        pub fn delivery(status: &str) -> WebhookDelivery {
+            WebhookDelivery {
+                webhook_id: webhook_id(),
+                created_at: Duration::seconds(5),
+                delivery_id: Uuid::new_v4(),
+                user_id: user_id(),
+                vehicle_id: vehicle_id(),
+                event: "created".to_string(),
+                payload: "{}".to_string(),
+                status: status.to_string(),
+                attempts: 1,
+                response_status: Some(200),
+                error: None,
+                updated_at: Duration::seconds(5)
+            }
+        }
+    }
+}
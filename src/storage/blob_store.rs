@@ -0,0 +1,17 @@
+use rocket::http::ContentType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blob {
+    pub data            : Vec<u8>,
+    pub content_type    : ContentType,
+    pub etag            : String
+}
+
+/// Storage for binary objects addressed by a `/` separated key. Implementations must be able to
+/// serve back the content type a blob was stored with.
+#[async_trait]
+pub trait BlobStore {
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: ContentType) -> Option<()>;
+    async fn get(&self, key: &str) -> Option<Blob>;
+    async fn delete(&self, key: &str) -> Option<()>;
+}
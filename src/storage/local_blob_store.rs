@@ -0,0 +1,140 @@
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use rocket::http::ContentType;
+use rocket::tokio::fs;
+
+use crate::storage::blob_store::{Blob, BlobStore};
+
+/// `BlobStore` backed by a directory of the local filesystem. The content type is derived from
+/// the key extension, so keys are expected to end with one (e.g. `.jpg`).
+pub struct LocalBlobStore {
+    root: PathBuf
+}
+
+impl LocalBlobStore {
+    pub fn new<P: AsRef<Path>>(root: P) -> LocalBlobStore {
+        LocalBlobStore {
+            root: root.as_ref().to_path_buf()
+        }
+    }
+
+    /// Rejects keys that could escape the root directory.
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        let relative = Path::new(key);
+
+        if relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            Some(self.root.join(relative))
+        } else {
+            println!("Rejected blob key {:?}", key);
+            None
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &str, data: Vec<u8>, _content_type: ContentType) -> Option<()> {
+        let path = self.path(key)?;
+
+        if let Some(parent) = path.parent() {
+            if let Err(e) = fs::create_dir_all(parent).await {
+                println!("Failed to create blob directory {:?} with error {:?}", parent, e);
+                return None;
+            }
+        }
+
+        fs::write(&path, data).await
+            .map_err(|e| println!("Failed to write blob {:?} with error {:?}", path, e))
+            .ok()
+    }
+
+    async fn get(&self, key: &str) -> Option<Blob> {
+        let path = self.path(key)?;
+
+        let data = fs::read(&path).await.ok()?;
+        let modified = fs::metadata(&path).await.ok()
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_nanos())
+            .unwrap_or(0);
+
+        let content_type = path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(ContentType::from_extension)
+            .unwrap_or(ContentType::Binary);
+
+        Some(Blob {
+            etag: format!("\"{:x}-{:x}\"", data.len(), modified),
+            data,
+            content_type
+        })
+    }
+
+    async fn delete(&self, key: &str) -> Option<()> {
+        let path = self.path(key)?;
+
+        match fs::remove_file(&path).await {
+            Ok(_) => Some(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Some(()),
+            Err(e) => {
+                println!("Failed to delete blob {:?} with error {:?}", path, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rocket::serde::uuid::Uuid;
+
+    macro_rules! aw {
+        ($e: expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn when_put_then_get_returns_blob_with_content_type_from_extension() {
+        let blob_store = LocalBlobStore::new(fixture::root());
+
+        assert!(aw!(blob_store.put("user/vehicle/picture.png", fixture::DATA.to_vec(), ContentType::PNG)).is_some());
+
+        let blob = aw!(blob_store.get("user/vehicle/picture.png")).unwrap();
+
+        assert_eq!(fixture::DATA.to_vec(), blob.data);
+        assert_eq!(ContentType::PNG, blob.content_type);
+        assert!(blob.etag.starts_with('"'));
+    }
+
+    #[test]
+    fn when_delete_then_get_returns_none() {
+        let blob_store = LocalBlobStore::new(fixture::root());
+
+        aw!(blob_store.put("user/vehicle/picture.jpg", fixture::DATA.to_vec(), ContentType::JPEG));
+        assert!(aw!(blob_store.delete("user/vehicle/picture.jpg")).is_some());
+
+        assert!(aw!(blob_store.get("user/vehicle/picture.jpg")).is_none());
+        assert!(aw!(blob_store.delete("user/vehicle/picture.jpg")).is_some());
+    }
+
+    #[test]
+    fn given_key_escaping_root_when_put_then_returns_none() {
+        let blob_store = LocalBlobStore::new(fixture::root());
+
+        assert!(aw!(blob_store.put("../outside.jpg", fixture::DATA.to_vec(), ContentType::JPEG)).is_none());
+        assert!(aw!(blob_store.get("/etc/passwd")).is_none());
+    }
+
+    mod fixture {
+        use super::*;
+
+        pub const DATA: &[u8] = b"picture bytes";
+
+        pub fn root() -> PathBuf {
+            std::env::temp_dir().join(format!("blob-store-{}", Uuid::new_v4()))
+        }
+    }
+}